@@ -44,6 +44,8 @@ pub mod script {
 // Wallet and transaction management
 pub mod wallet;         // Secure HD wallet implementation
 pub mod transaction;    // Transaction creation and signing
+pub mod silent_payments; // Silent payments (BIP352)
+pub mod payjoin;        // PayJoin (BIP78)
 
 // Advanced Bitcoin functionality
 pub mod taproot;        // Taproot support
@@ -0,0 +1,345 @@
+// SPDX-License-Identifier: MIT
+// Copyright (C) 2023-2025 Anya Project Contributors
+
+//! PayJoin (BIP78)
+//!
+//! In a payjoin the receiver adds one of its own coins to the sender's
+//! transaction, which breaks the common-input-ownership heuristic: the
+//! inputs of a payment no longer all belong to the payer.
+//!
+//! - [`Sender`] builds the request from a signed original PSBT and checks
+//!   the receiver's proposal against the BIP78 sender checklist before it
+//!   is signed;
+//! - [`Receiver`] checks an original PSBT, contributes an input from a
+//!   [`PayjoinWallet`] and answers with the proposal, for mounting behind
+//!   an HTTP endpoint.
+//!
+//! Should anything fail, the sender broadcasts the original transaction.
+
+mod receiver;
+mod sender;
+
+pub use receiver::{PayjoinWallet, Receiver};
+pub use sender::Sender;
+
+use std::collections::HashMap;
+
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use bitcoin::psbt::{Input, Psbt};
+use bitcoin::{Amount, FeeRate, Script, TxIn, TxOut, Weight};
+use thiserror::Error;
+
+/// Errors of a payjoin, with the BIP78 error code a receiver answers with
+#[derive(Debug, Error)]
+pub enum PayjoinError {
+    #[error("Unsupported payjoin version {0}")]
+    VersionUnsupported(String),
+    #[error("Original PSBT rejected: {0}")]
+    OriginalPsbtRejected(String),
+    #[error("Receiver has no coins to contribute")]
+    NotEnoughMoney,
+    #[error("Receiver unavailable: {0}")]
+    Unavailable(String),
+    #[error("Invalid payjoin proposal: {0}")]
+    InvalidProposal(String),
+    #[error("Invalid payjoin endpoint: {0}")]
+    InvalidEndpoint(String),
+    #[error("Payjoin transport error: {0}")]
+    Transport(String),
+}
+
+impl PayjoinError {
+    /// Well-known error code of the BIP78 error response
+    pub fn error_code(&self) -> &'static str {
+        match self {
+            PayjoinError::VersionUnsupported(_) => "version-unsupported",
+            PayjoinError::NotEnoughMoney => "not-enough-money",
+            PayjoinError::OriginalPsbtRejected(_) => "original-psbt-rejected",
+            _ => "unavailable",
+        }
+    }
+
+    /// JSON body of the BIP78 error response
+    pub fn to_json(&self) -> String {
+        serde_json::json!({ "errorCode": self.error_code(), "message": self.to_string() }).to_string()
+    }
+}
+
+pub type Result<T> = std::result::Result<T, PayjoinError>;
+
+/// Optional parameters the sender appends to the endpoint's query string
+#[derive(Debug, Clone, PartialEq)]
+pub struct Params {
+    /// Sender output the receiver may take its input's fee from
+    pub additional_fee_output_index: Option<usize>,
+    pub max_additional_fee_contribution: Amount,
+    /// Whether the receiver must pay out to the original payment output
+    pub disable_output_substitution: bool,
+    pub min_fee_rate: Option<FeeRate>,
+}
+
+impl Default for Params {
+    fn default() -> Self {
+        Params {
+            additional_fee_output_index: None,
+            max_additional_fee_contribution: Amount::ZERO,
+            disable_output_substitution: false,
+            min_fee_rate: None,
+        }
+    }
+}
+
+impl Params {
+    pub fn to_query(&self) -> String {
+        let mut query = vec!["v=1".to_string()];
+        if let Some(index) = self.additional_fee_output_index {
+            query.push(format!("additionalfeeoutputindex={}", index));
+            query.push(format!("maxadditionalfeecontribution={}", self.max_additional_fee_contribution.to_sat()));
+        }
+        if self.disable_output_substitution {
+            query.push("disableoutputsubstitution=true".to_string());
+        }
+        if let Some(rate) = self.min_fee_rate {
+            query.push(format!("minfeerate={}", rate.to_sat_per_kwu() as f64 / 250.0));
+        }
+        query.join("&")
+    }
+
+    pub fn from_query(query: &str) -> Result<Self> {
+        let pairs: HashMap<&str, &str> = query.split('&')
+            .filter(|pair| !pair.is_empty())
+            .filter_map(|pair| pair.split_once('='))
+            .collect();
+        match pairs.get("v") {
+            Some(&"1") => {}
+            Some(version) => return Err(PayjoinError::VersionUnsupported(version.to_string())),
+            None => return Err(PayjoinError::VersionUnsupported("missing".to_string())),
+        }
+        let number = |name: &str| -> Result<Option<u64>> {
+            pairs.get(name)
+                .map(|value| value.parse::<u64>()
+                    .map_err(|_| PayjoinError::OriginalPsbtRejected(format!("bad {}: {}", name, value))))
+                .transpose()
+        };
+
+        let mut params = Params::default();
+        // The two fee parameters only make sense together
+        if let (Some(index), Some(max)) = (number("additionalfeeoutputindex")?, number("maxadditionalfeecontribution")?) {
+            params.additional_fee_output_index = Some(index as usize);
+            params.max_additional_fee_contribution = Amount::from_sat(max);
+        }
+        params.disable_output_substitution = pairs.get("disableoutputsubstitution") == Some(&"true");
+        // In sat/vB, possibly fractional
+        params.min_fee_rate = pairs.get("minfeerate")
+            .map(|value| match value.parse::<f64>() {
+                Ok(rate) if rate.is_finite() && rate >= 0.0 => Ok(FeeRate::from_sat_per_kwu((rate * 250.0).ceil() as u64)),
+                _ => Err(PayjoinError::OriginalPsbtRejected(format!("bad minfeerate: {}", value))),
+            })
+            .transpose()?;
+        Ok(params)
+    }
+}
+
+pub fn encode_psbt(psbt: &Psbt) -> String {
+    BASE64.encode(psbt.serialize())
+}
+
+pub fn decode_psbt(encoded: &str) -> std::result::Result<Psbt, String> {
+    let bytes = BASE64.decode(encoded.trim()).map_err(|e| e.to_string())?;
+    Psbt::deserialize(&bytes).map_err(|e| e.to_string())
+}
+
+/// Output an input spends, from its UTXO fields
+fn spent_output(input: &Input, txin: &TxIn) -> Option<TxOut> {
+    if let Some(utxo) = &input.witness_utxo {
+        return Some(utxo.clone());
+    }
+    input.non_witness_utxo.as_ref()
+        .and_then(|tx| tx.output.get(txin.previous_output.vout as usize))
+        .cloned()
+}
+
+/// Script types the "mixed input types" checks compare
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+enum InputType {
+    P2pkh,
+    P2sh,
+    P2wpkh,
+    P2wsh,
+    P2tr,
+}
+
+impl InputType {
+    fn of(script: &Script) -> Option<Self> {
+        if script.is_p2pkh() {
+            Some(InputType::P2pkh)
+        } else if script.is_p2sh() {
+            Some(InputType::P2sh)
+        } else if script.is_p2wpkh() {
+            Some(InputType::P2wpkh)
+        } else if script.is_p2wsh() {
+            Some(InputType::P2wsh)
+        } else if script.is_p2tr() {
+            Some(InputType::P2tr)
+        } else {
+            None
+        }
+    }
+
+    /// Weight of a signed single-key input of this type, for the fee of a
+    /// coin the receiver contributes
+    ///
+    /// Legacy coins need their whole previous transaction in the PSBT, and
+    /// multisig ones an unknown witness, so neither is contributed.
+    fn expected_weight(self) -> Option<Weight> {
+        // Outpoint, sequence and script length: 41 bytes at 4 WU
+        let base = 41 * 4;
+        match self {
+            InputType::P2sh => Some(Weight::from_wu(base + 23 * 4 + 108)),
+            InputType::P2wpkh => Some(Weight::from_wu(base + 108)),
+            InputType::P2tr => Some(Weight::from_wu(base + 66)),
+            InputType::P2pkh | InputType::P2wsh => None,
+        }
+    }
+}
+
+/// Sum of the input values of a PSBT, when every input has UTXO info
+fn total_input_value(psbt: &Psbt) -> Option<Amount> {
+    psbt.inputs.iter().zip(&psbt.unsigned_tx.input)
+        .map(|(input, txin)| spent_output(input, txin).map(|output| output.value))
+        .sum()
+}
+
+fn fee(psbt: &Psbt) -> Option<Amount> {
+    let outputs: Amount = psbt.unsigned_tx.output.iter().map(|output| output.value).sum();
+    total_input_value(psbt)?.checked_sub(outputs)
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use async_trait::async_trait;
+    use bitcoin::hashes::Hash;
+    use bitcoin::secp256k1::{Message, Secp256k1, SecretKey};
+    use bitcoin::sighash::{EcdsaSighashType, SighashCache};
+    use bitcoin::{absolute, transaction, CompressedPublicKey, OutPoint, ScriptBuf, Sequence, Transaction, Txid, Witness};
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use tokio::sync::Mutex;
+
+    /// A P2WPKH wallet holding one key
+    pub(crate) struct TestWallet {
+        key: SecretKey,
+        pub utxos: Mutex<Vec<(OutPoint, TxOut)>>,
+        /// What its node answers to `testmempoolaccept`
+        pub mempool_accepts: AtomicBool,
+    }
+
+    impl TestWallet {
+        /// A wallet with coins of `values` sats
+        pub(crate) fn new(seed: u8, values: &[u64]) -> Self {
+            let wallet = TestWallet {
+                key: SecretKey::from_slice(&[seed; 32]).unwrap(),
+                utxos: Mutex::new(Vec::new()),
+                mempool_accepts: AtomicBool::new(true),
+            };
+            let utxos = values.iter().enumerate()
+                .map(|(vout, value)| {
+                    (OutPoint::new(Txid::from_byte_array([seed; 32]), vout as u32), TxOut { value: Amount::from_sat(*value), script_pubkey: wallet.script() })
+                })
+                .collect();
+            wallet.utxos.try_lock().unwrap().clone_from(&utxos);
+            wallet
+        }
+
+        pub(crate) fn script(&self) -> ScriptBuf {
+            let public_key = CompressedPublicKey(self.key.public_key(&Secp256k1::new()));
+            ScriptBuf::new_p2wpkh(&public_key.wpubkey_hash())
+        }
+
+        /// A signed original PSBT paying `amount` to `payee` from the first
+        /// coin, with change back to this wallet as the last output
+        pub(crate) async fn original(&self, payee: &ScriptBuf, amount: u64, fee: u64) -> Psbt {
+            let (outpoint, utxo) = self.utxos.lock().await[0].clone();
+            let tx = Transaction {
+                version: transaction::Version::TWO,
+                lock_time: absolute::LockTime::from_height(800_000).unwrap(),
+                input: vec![TxIn { previous_output: outpoint, sequence: Sequence::ENABLE_RBF_NO_LOCKTIME, ..Default::default() }],
+                output: vec![
+                    TxOut { value: Amount::from_sat(amount), script_pubkey: payee.clone() },
+                    TxOut { value: utxo.value - Amount::from_sat(amount + fee), script_pubkey: self.script() },
+                ],
+            };
+            let mut psbt = Psbt::from_unsigned_tx(tx).unwrap();
+            psbt.inputs[0].witness_utxo = Some(utxo);
+            self.sign_psbt(&mut psbt).await.unwrap();
+            psbt
+        }
+    }
+
+    #[async_trait]
+    impl PayjoinWallet for TestWallet {
+        async fn is_mine(&self, script: &Script) -> Result<bool> {
+            Ok(script == self.script().as_script())
+        }
+
+        async fn list_unspent(&self) -> Result<Vec<(OutPoint, TxOut)>> {
+            Ok(self.utxos.lock().await.clone())
+        }
+
+        async fn sign_psbt(&self, psbt: &mut Psbt) -> Result<()> {
+            let secp = Secp256k1::new();
+            let public_key = CompressedPublicKey(self.key.public_key(&secp));
+            let mut cache = SighashCache::new(&psbt.unsigned_tx);
+            let mut witnesses = Vec::new();
+            for (index, input) in psbt.inputs.iter().enumerate() {
+                let Some(utxo) = input.witness_utxo.as_ref().filter(|utxo| utxo.script_pubkey == self.script()) else {
+                    continue;
+                };
+                if input.final_script_witness.is_some() {
+                    continue;
+                }
+                let sighash = cache.p2wpkh_signature_hash(index, &utxo.script_pubkey, utxo.value, EcdsaSighashType::All).unwrap();
+                let signature = bitcoin::ecdsa::Signature::sighash_all(secp.sign_ecdsa(&Message::from(sighash), &self.key));
+                witnesses.push((index, Witness::p2wpkh(&signature, &public_key.0)));
+            }
+            for (index, witness) in witnesses {
+                psbt.inputs[index].final_script_witness = Some(witness);
+            }
+            Ok(())
+        }
+
+        async fn test_mempool_accept(&self, _tx: &Transaction) -> Result<bool> {
+            Ok(self.mempool_accepts.load(Ordering::SeqCst))
+        }
+    }
+
+    #[test]
+    fn test_params_query() {
+        let params = Params {
+            additional_fee_output_index: Some(1),
+            max_additional_fee_contribution: Amount::from_sat(182),
+            disable_output_substitution: true,
+            min_fee_rate: FeeRate::from_sat_per_vb(2),
+        };
+        let query = params.to_query();
+        assert_eq!(query, "v=1&additionalfeeoutputindex=1&maxadditionalfeecontribution=182&disableoutputsubstitution=true&minfeerate=2");
+        assert_eq!(Params::from_query(&query).unwrap(), params);
+        assert_eq!(Params::from_query("v=1&minfeerate=1.5").unwrap().min_fee_rate, Some(FeeRate::from_sat_per_kwu(375)));
+
+        // A fee output without a maximum contribution is ignored
+        assert_eq!(Params::from_query("v=1&additionalfeeoutputindex=1").unwrap(), Params::default());
+        let error = Params::from_query("v=2").unwrap_err();
+        assert_eq!(error.error_code(), "version-unsupported");
+        assert!(error.to_json().contains("\"errorCode\":\"version-unsupported\""));
+    }
+
+    #[tokio::test]
+    async fn test_psbt_encoding() {
+        let wallet = TestWallet::new(1, &[100_000]);
+        let psbt = wallet.original(&ScriptBuf::new_op_return([1u8; 4]), 10_000, 500).await;
+        assert_eq!(decode_psbt(&encode_psbt(&psbt)).unwrap(), psbt);
+        assert_eq!(fee(&psbt), Some(Amount::from_sat(500)));
+        assert!(decode_psbt("not a psbt").is_err());
+    }
+}
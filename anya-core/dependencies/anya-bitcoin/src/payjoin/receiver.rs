@@ -0,0 +1,281 @@
+// SPDX-License-Identifier: MIT
+// Copyright (C) 2023-2025 Anya Project Contributors
+
+//! Receiving side of a payjoin
+//!
+//! The receiver runs the BIP78 checks on the original PSBT, adds one of its
+//! coins as an input of the same type as the sender's, credits its value to
+//! the payment output and answers with the proposal, its own input signed.
+
+use std::collections::HashSet;
+
+use async_trait::async_trait;
+use bitcoin::psbt::{Input, Psbt};
+use bitcoin::{Amount, FeeRate, OutPoint, Script, Transaction, TxIn, TxOut, Weight};
+use rand::seq::SliceRandom;
+use rand::Rng;
+use tokio::sync::Mutex;
+
+use super::{decode_psbt, encode_psbt, fee, spent_output, InputType, Params, PayjoinError, Result};
+
+fn rejected(reason: impl Into<String>) -> PayjoinError {
+    PayjoinError::OriginalPsbtRejected(reason.into())
+}
+
+/// The wallet a receiver contributes inputs from
+#[async_trait]
+pub trait PayjoinWallet: Send + Sync {
+    /// Whether the wallet owns outputs paying `script`
+    async fn is_mine(&self, script: &Script) -> Result<bool>;
+
+    /// Coins the wallet may contribute
+    async fn list_unspent(&self) -> Result<Vec<(OutPoint, TxOut)>>;
+
+    /// Signs and finalizes the wallet's own inputs, leaving the others
+    async fn sign_psbt(&self, psbt: &mut Psbt) -> Result<()>;
+
+    /// Whether a node would accept `tx` to its mempool, as
+    /// `testmempoolaccept` answers
+    async fn test_mempool_accept(&self, tx: &Transaction) -> Result<bool>;
+}
+
+/// Answers payjoin requests
+#[derive(Debug, Default)]
+pub struct Receiver {
+    /// Inputs of the original PSBTs answered so far, so nobody can learn the
+    /// wallet's coins by sending originals they never broadcast
+    seen_inputs: Mutex<HashSet<OutPoint>>,
+}
+
+impl Receiver {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Answers the body and query string of a BIP78 request with the
+    /// base64 payjoin proposal
+    pub async fn process<W: PayjoinWallet + ?Sized>(&self, wallet: &W, query: &str, body: &str) -> Result<String> {
+        let params = Params::from_query(query)?;
+        let original = decode_psbt(body).map_err(rejected)?;
+        let payment = self.check_original(wallet, &original).await?;
+        let proposal = self.contribute(wallet, original, payment, &params).await?;
+        Ok(encode_psbt(&proposal))
+    }
+
+    /// The original PSBT checklist, returning the index of the output
+    /// paying this wallet
+    async fn check_original<W: PayjoinWallet + ?Sized>(&self, wallet: &W, original: &Psbt) -> Result<usize> {
+        // Broadcastable as is, so the receiver can fall back to it
+        let finalized = original.inputs.iter()
+            .all(|input| input.final_script_sig.is_some() || input.final_script_witness.is_some());
+        if !finalized {
+            return Err(rejected("every input must be signed and finalized"));
+        }
+        if fee(original).is_none() {
+            return Err(rejected("every input needs its previous output"));
+        }
+        for (input, txin) in original.inputs.iter().zip(&original.unsigned_tx.input) {
+            let spent = spent_output(input, txin).expect("checked above");
+            if wallet.is_mine(&spent.script_pubkey).await? {
+                return Err(rejected("spends coins of the receiver"));
+            }
+        }
+
+        let mut payment = None;
+        for (index, output) in original.unsigned_tx.output.iter().enumerate() {
+            if wallet.is_mine(&output.script_pubkey).await? {
+                payment = Some(index);
+                break;
+            }
+        }
+        let payment = payment.ok_or_else(|| rejected("does not pay the receiver"))?;
+
+        // Checked before any coin is offered, so the fallback really is
+        // there and nobody learns the wallet's coins for free
+        if !wallet.test_mempool_accept(&original.clone().extract_tx_unchecked_fee_rate()).await? {
+            return Err(rejected("the original transaction is not accepted to the mempool"));
+        }
+
+        let mut seen = self.seen_inputs.lock().await;
+        let outpoints: Vec<OutPoint> = original.unsigned_tx.input.iter().map(|txin| txin.previous_output).collect();
+        if outpoints.iter().any(|outpoint| seen.contains(outpoint)) {
+            return Err(rejected("inputs were already offered in another original PSBT"));
+        }
+        seen.extend(outpoints);
+        Ok(payment)
+    }
+
+    async fn contribute<W: PayjoinWallet + ?Sized>(
+        &self,
+        wallet: &W,
+        mut psbt: Psbt,
+        payment: usize,
+        params: &Params,
+    ) -> Result<Psbt> {
+        let original_fee = fee(&psbt).expect("checked with the original");
+        let original_weight = psbt.clone().extract_tx_unchecked_fee_rate().weight();
+        let original_rate = FeeRate::from_sat_per_kwu(original_fee.to_sat() * 1000 / original_weight.to_wu());
+
+        // A coin of the same type as the sender's, so the inputs look alike
+        let sender_types: HashSet<Option<InputType>> = psbt.inputs.iter().zip(&psbt.unsigned_tx.input)
+            .map(|(input, txin)| spent_output(input, txin).and_then(|output| InputType::of(&output.script_pubkey)))
+            .collect();
+        let spent: HashSet<OutPoint> = psbt.unsigned_tx.input.iter().map(|txin| txin.previous_output).collect();
+        let candidates: Vec<(OutPoint, TxOut, Weight)> = wallet.list_unspent().await?.into_iter()
+            .filter(|(outpoint, _)| !spent.contains(outpoint))
+            .filter_map(|(outpoint, output)| {
+                let input_type = InputType::of(&output.script_pubkey)?;
+                let weight = input_type.expected_weight()?;
+                (sender_types.len() > 1 || sender_types.contains(&Some(input_type)))
+                    .then_some((outpoint, output, weight))
+            })
+            .collect();
+        let (outpoint, output, weight) = candidates.choose(&mut rand::thread_rng())
+            .cloned()
+            .ok_or(PayjoinError::NotEnoughMoney)?;
+
+        // The added input pays its own way: the sender's contribution at the
+        // original fee rate first, then the receiver up to the minimum rate
+        let rate = params.min_fee_rate.map_or(original_rate, |minimum| minimum.max(original_rate));
+        let needed = rate.fee_wu(weight).unwrap_or(Amount::MAX);
+        let mut sender_share = Amount::ZERO;
+        if let Some(index) = params.additional_fee_output_index {
+            if index != payment && index < psbt.unsigned_tx.output.len() {
+                let cap = original_rate.fee_wu(weight).unwrap_or(Amount::ZERO).min(params.max_additional_fee_contribution);
+                let fee_output = &mut psbt.unsigned_tx.output[index];
+                sender_share = cap.min(fee_output.value);
+                fee_output.value -= sender_share;
+            }
+        }
+        let receiver_share = needed.checked_sub(sender_share).unwrap_or(Amount::ZERO);
+        let credit = output.value.checked_sub(receiver_share)
+            .ok_or(PayjoinError::NotEnoughMoney)?;
+        psbt.unsigned_tx.output[payment].value += credit;
+
+        let sequence = psbt.unsigned_tx.input[0].sequence;
+        let position = rand::thread_rng().gen_range(0..=psbt.inputs.len());
+        psbt.unsigned_tx.input.insert(position, TxIn { previous_output: outpoint, sequence, ..Default::default() });
+        psbt.inputs.insert(position, Input { witness_utxo: Some(output), ..Default::default() });
+
+        // The sender signs its inputs again, and learns no key paths
+        for (index, input) in psbt.inputs.iter_mut().enumerate() {
+            if index != position {
+                input.final_script_sig = None;
+                input.final_script_witness = None;
+                input.partial_sigs.clear();
+                input.bip32_derivation.clear();
+                input.tap_key_origins.clear();
+            }
+        }
+        for output in &mut psbt.outputs {
+            output.bip32_derivation.clear();
+            output.tap_key_origins.clear();
+        }
+
+        wallet.sign_psbt(&mut psbt).await?;
+        let signed = &psbt.inputs[position];
+        if signed.final_script_sig.is_none() && signed.final_script_witness.is_none() {
+            return Err(PayjoinError::Unavailable("wallet did not sign its input".to_string()));
+        }
+        Ok(psbt)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::payjoin::tests::TestWallet;
+    use crate::payjoin::Sender;
+    use bitcoin::ScriptBuf;
+    use std::sync::atomic::Ordering;
+
+    fn query() -> String {
+        Params {
+            additional_fee_output_index: Some(1),
+            max_additional_fee_contribution: Amount::from_sat(1_000),
+            ..Params::default()
+        }.to_query()
+    }
+
+    #[tokio::test]
+    async fn test_contributes_an_input() {
+        let payer = TestWallet::new(1, &[100_000]);
+        let payee = TestWallet::new(2, &[50_000]);
+        let original = payer.original(&payee.script(), 10_000, 1_000).await;
+        let response = Receiver::new().process(&payee, &query(), &encode_psbt(&original)).await.unwrap();
+        let proposal = decode_psbt(&response).unwrap();
+
+        assert_eq!(proposal.inputs.len(), 2);
+        let coin = payee.utxos.lock().await[0].0;
+        let ours = proposal.unsigned_tx.input.iter()
+            .position(|txin| txin.previous_output == coin)
+            .unwrap();
+        assert!(proposal.inputs[ours].final_script_witness.is_some());
+        assert!(proposal.inputs[1 - ours].final_script_witness.is_none());
+        assert_eq!(proposal.unsigned_tx.input[ours].sequence, original.unsigned_tx.input[0].sequence);
+        // The added input pays for itself, partly out of the payer's change
+        assert!(fee(&proposal).unwrap() > fee(&original).unwrap());
+        assert!(proposal.unsigned_tx.output[1].value < original.unsigned_tx.output[1].value);
+        assert!(proposal.unsigned_tx.output[0].value > original.unsigned_tx.output[0].value);
+    }
+
+    #[tokio::test]
+    async fn test_rejects_bad_originals() {
+        let payer = TestWallet::new(1, &[100_000, 100_000]);
+        let payee = TestWallet::new(2, &[50_000]);
+        let receiver = Receiver::new();
+
+        let elsewhere = payer.original(&ScriptBuf::new_op_return([1u8; 4]), 10_000, 1_000).await;
+        let error = receiver.process(&payee, &query(), &encode_psbt(&elsewhere)).await.unwrap_err();
+        assert_eq!(error.error_code(), "original-psbt-rejected");
+
+        let own = payee.original(&payee.script(), 10_000, 1_000).await;
+        assert!(receiver.process(&payee, &query(), &encode_psbt(&own)).await.is_err());
+
+        let mut unsigned = payer.original(&payee.script(), 10_000, 1_000).await;
+        unsigned.inputs[0].final_script_witness = None;
+        assert!(receiver.process(&payee, &query(), &encode_psbt(&unsigned)).await.is_err());
+
+        assert!(receiver.process(&payee, "", &encode_psbt(&unsigned)).await.is_err());
+        assert!(receiver.process(&payee, &query(), "not a psbt").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_rejects_unbroadcastable_original() {
+        let payer = TestWallet::new(1, &[100_000]);
+        let payee = TestWallet::new(2, &[50_000]);
+        let original = payer.original(&payee.script(), 10_000, 1_000).await;
+        let receiver = Receiver::new();
+        payee.mempool_accepts.store(false, Ordering::SeqCst);
+        let error = receiver.process(&payee, &query(), &encode_psbt(&original)).await.unwrap_err();
+        assert_eq!(error.error_code(), "original-psbt-rejected");
+
+        // Nothing was offered, so the same original may be sent again
+        payee.mempool_accepts.store(true, Ordering::SeqCst);
+        assert!(receiver.process(&payee, &query(), &encode_psbt(&original)).await.is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_rejects_replayed_inputs() {
+        let payer = TestWallet::new(1, &[100_000]);
+        let payee = TestWallet::new(2, &[50_000, 60_000]);
+        let receiver = Receiver::new();
+        let original = payer.original(&payee.script(), 10_000, 1_000).await;
+        assert!(receiver.process(&payee, &query(), &encode_psbt(&original)).await.is_ok());
+
+        // Probing with the same coins would reveal another of the wallet's
+        let again = payer.original(&payee.script(), 20_000, 1_000).await;
+        let error = receiver.process(&payee, &query(), &encode_psbt(&again)).await.unwrap_err();
+        assert!(matches!(error, PayjoinError::OriginalPsbtRejected(_)));
+    }
+
+    #[tokio::test]
+    async fn test_without_coins() {
+        let payer = TestWallet::new(1, &[100_000]);
+        let payee = TestWallet::new(2, &[]);
+        let original = payer.original(&payee.script(), 10_000, 1_000).await;
+        let sender = Sender::new(original, payee.script(), Params::default()).unwrap();
+        let error = Receiver::new().process(&payee, &sender.query(), &sender.body()).await.unwrap_err();
+        assert_eq!(error.error_code(), "not-enough-money");
+    }
+}
@@ -0,0 +1,328 @@
+// SPDX-License-Identifier: MIT
+// Copyright (C) 2023-2025 Anya Project Contributors
+
+//! Sending side of a payjoin
+//!
+//! The sender posts its signed original PSBT, which the receiver could
+//! broadcast as is, and gets back a proposal with the receiver's input
+//! added. Before signing again, the proposal goes through the BIP78 sender
+//! checklist so the receiver cannot make the sender pay more than agreed.
+
+use std::collections::HashMap;
+
+use bitcoin::psbt::Psbt;
+use bitcoin::{Amount, FeeRate, OutPoint, ScriptBuf, Sequence, Transaction, Weight};
+
+use super::{decode_psbt, encode_psbt, fee, spent_output, InputType, Params, PayjoinError, Result};
+
+fn invalid(reason: impl Into<String>) -> PayjoinError {
+    PayjoinError::InvalidProposal(reason.into())
+}
+
+/// A payjoin in progress, from the sender's side
+#[derive(Debug, Clone)]
+pub struct Sender {
+    original: Psbt,
+    payee: ScriptBuf,
+    params: Params,
+}
+
+impl Sender {
+    /// Starts a payjoin from a fully signed original PSBT paying `payee`
+    pub fn new(original: Psbt, payee: ScriptBuf, params: Params) -> Result<Self> {
+        let rejected = |reason: &str| PayjoinError::OriginalPsbtRejected(reason.to_string());
+        let finalized = original.inputs.iter()
+            .all(|input| input.final_script_sig.is_some() || input.final_script_witness.is_some());
+        if !finalized {
+            return Err(rejected("every input must be signed and finalized"));
+        }
+        if fee(&original).is_none() {
+            return Err(rejected("every input needs its previous output"));
+        }
+        let outputs = &original.unsigned_tx.output;
+        if !outputs.iter().any(|output| output.script_pubkey == payee) {
+            return Err(rejected("no output pays the receiver"));
+        }
+        if let Some(index) = params.additional_fee_output_index {
+            match outputs.get(index) {
+                Some(output) if output.script_pubkey != payee => {}
+                _ => return Err(rejected("fee output must be one of the sender's outputs")),
+            }
+        }
+        Ok(Sender { original, payee, params })
+    }
+
+    pub fn params(&self) -> &Params {
+        &self.params
+    }
+
+    /// Body of the request: the original PSBT in base64
+    pub fn body(&self) -> String {
+        encode_psbt(&self.original)
+    }
+
+    /// Query string to append to the receiver's endpoint
+    pub fn query(&self) -> String {
+        self.params.to_query()
+    }
+
+    /// The original transaction, to broadcast if the payjoin fails
+    pub fn original_tx(&self) -> Transaction {
+        self.original.clone().extract_tx_unchecked_fee_rate()
+    }
+
+    /// Posts the original PSBT to a BIP78 endpoint and checks the proposal
+    /// that comes back
+    pub async fn send(&self, endpoint: &str) -> Result<Psbt> {
+        let host = endpoint.split("://").nth(1).and_then(|rest| rest.split(['/', ':', '?']).next()).unwrap_or_default();
+        if !endpoint.starts_with("https://") && !host.ends_with(".onion") {
+            return Err(PayjoinError::InvalidEndpoint(format!("{} is neither https nor onion", endpoint)));
+        }
+        let separator = if endpoint.contains('?') { '&' } else { '?' };
+        let response = reqwest::Client::new()
+            .post(format!("{}{}{}", endpoint, separator, self.query()))
+            .header("Content-Type", "text/plain")
+            .body(self.body())
+            .send()
+            .await
+            .map_err(|e| PayjoinError::Transport(e.to_string()))?;
+        let success = response.status().is_success();
+        let text = response.text().await.map_err(|e| PayjoinError::Transport(e.to_string()))?;
+        if !success {
+            return Err(PayjoinError::Unavailable(text));
+        }
+        self.process_response(&text)
+    }
+
+    /// Checks the receiver's proposal
+    ///
+    /// Returns the proposal with the sender's inputs restored from the
+    /// original PSBT and their signatures cleared, ready to sign and
+    /// finalize again.
+    pub fn process_response(&self, response: &str) -> Result<Psbt> {
+        let proposal = decode_psbt(response).map_err(|e| invalid(format!("undecodable PSBT: {}", e)))?;
+        let original_tx = &self.original.unsigned_tx;
+        let tx = &proposal.unsigned_tx;
+        if tx.version != original_tx.version || tx.lock_time != original_tx.lock_time {
+            return Err(invalid("version or lock time changed"));
+        }
+
+        // The receiver's inputs must look like the sender's, or they stand out
+        let original_inputs: HashMap<OutPoint, usize> = original_tx.input.iter().enumerate()
+            .map(|(index, txin)| (txin.previous_output, index))
+            .collect();
+        let sender_types: Vec<Option<InputType>> = self.original.inputs.iter().zip(&original_tx.input)
+            .map(|(input, txin)| spent_output(input, txin).and_then(|output| InputType::of(&output.script_pubkey)))
+            .collect();
+        let sender_type = uniform(&sender_types).flatten();
+        let sender_sequence = uniform(&original_tx.input.iter().map(|txin| txin.sequence).collect::<Vec<Sequence>>());
+
+        let mut restored = proposal.clone();
+        let mut sender_inputs = 0;
+        let mut receiver_weight = Weight::ZERO;
+        let mut estimate = tx.clone();
+        for (index, (input, txin)) in proposal.inputs.iter().zip(&tx.input).enumerate() {
+            if let Some(&original_index) = original_inputs.get(&txin.previous_output) {
+                if txin.sequence != original_tx.input[original_index].sequence {
+                    return Err(invalid(format!("sequence of sender input {} changed", index)));
+                }
+                if !input.bip32_derivation.is_empty() || !input.tap_key_origins.is_empty() {
+                    return Err(invalid(format!("sender input {} carries key paths", index)));
+                }
+                if !input.partial_sigs.is_empty() || input.tap_key_sig.is_some() {
+                    return Err(invalid(format!("sender input {} is already signed", index)));
+                }
+                let original = &self.original.inputs[original_index];
+                estimate.input[index].script_sig = original.final_script_sig.clone().unwrap_or_default();
+                estimate.input[index].witness = original.final_script_witness.clone().unwrap_or_default();
+
+                restored.inputs[index] = original.clone();
+                restored.inputs[index].final_script_sig = None;
+                restored.inputs[index].final_script_witness = None;
+                sender_inputs += 1;
+            } else {
+                if input.final_script_sig.is_none() && input.final_script_witness.is_none() {
+                    return Err(invalid(format!("receiver input {} is not finalized", index)));
+                }
+                let spent = spent_output(input, txin)
+                    .ok_or_else(|| invalid(format!("receiver input {} has no previous output", index)))?;
+                if sender_sequence.is_some_and(|sequence| sequence != txin.sequence) {
+                    return Err(invalid("receiver input has a different sequence"));
+                }
+                if sender_type.is_some() && InputType::of(&spent.script_pubkey) != sender_type {
+                    return Err(invalid("receiver input has a different script type"));
+                }
+                estimate.input[index].script_sig = input.final_script_sig.clone().unwrap_or_default();
+                estimate.input[index].witness = input.final_script_witness.clone().unwrap_or_default();
+                // Priced at the weight expected of its type, as the receiver
+                // estimates it before signing
+                receiver_weight += InputType::of(&spent.script_pubkey)
+                    .and_then(InputType::expected_weight)
+                    .unwrap_or_else(|| estimate.input[index].segwit_weight());
+            }
+        }
+        if sender_inputs != original_inputs.len() {
+            return Err(invalid("a sender input is missing"));
+        }
+
+        if proposal.outputs.iter().any(|output| !output.bip32_derivation.is_empty() || !output.tap_key_origins.is_empty()) {
+            return Err(invalid("outputs carry key paths"));
+        }
+        let mut contribution = Amount::ZERO;
+        for (index, original) in original_tx.output.iter().enumerate() {
+            let output = tx.output.iter().find(|output| output.script_pubkey == original.script_pubkey);
+            if original.script_pubkey == self.payee {
+                // Unless the sender allows it, the receiver keeps its output
+                let kept = output.is_some_and(|output| output.value >= original.value);
+                if self.params.disable_output_substitution && !kept {
+                    return Err(invalid("payment output was substituted or reduced"));
+                }
+                continue;
+            }
+            let output = output.ok_or_else(|| invalid(format!("sender output {} is missing", index)))?;
+            if Some(index) == self.params.additional_fee_output_index {
+                contribution = original.value.checked_sub(output.value).unwrap_or(Amount::ZERO);
+            } else if output.value != original.value {
+                return Err(invalid(format!("sender output {} changed", index)));
+            }
+        }
+        if contribution > self.params.max_additional_fee_contribution {
+            return Err(invalid(format!("fee contribution of {} exceeds the maximum", contribution)));
+        }
+
+        let original_fee = fee(&self.original).expect("checked when the sender was created");
+        let proposal_fee = fee(&restored).ok_or_else(|| invalid("an input has no previous output"))?;
+        if proposal_fee < original_fee {
+            return Err(invalid("fee decreased"));
+        }
+        if contribution > Amount::ZERO {
+            // All of the contribution goes to fees, and pays at most for the
+            // receiver's inputs at the original fee rate
+            if proposal_fee - original_fee < contribution {
+                return Err(invalid("fee contribution was not paid to fees"));
+            }
+            let original_weight = self.original_tx().weight();
+            let original_rate = FeeRate::from_sat_per_kwu(original_fee.to_sat() * 1000 / original_weight.to_wu());
+            let receiver_fee = original_rate.fee_wu(receiver_weight).unwrap_or(Amount::MAX);
+            if contribution > receiver_fee {
+                return Err(invalid(format!("fee contribution of {} exceeds the {} the receiver's inputs cost", contribution, receiver_fee)));
+            }
+        }
+        if let Some(min_fee_rate) = self.params.min_fee_rate {
+            if min_fee_rate.fee_wu(estimate.weight()).is_some_and(|minimum| proposal_fee < minimum) {
+                return Err(invalid("fee rate is below the minimum"));
+            }
+        }
+        Ok(restored)
+    }
+}
+
+/// The value shared by every element, if there is one
+fn uniform<T: Copy + PartialEq>(values: &[T]) -> Option<T> {
+    let first = *values.first()?;
+    values.iter().all(|value| *value == first).then_some(first)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::payjoin::tests::TestWallet;
+    use crate::payjoin::{PayjoinWallet, Receiver};
+    use bitcoin::absolute::LockTime;
+
+    fn params() -> Params {
+        Params {
+            additional_fee_output_index: Some(1),
+            max_additional_fee_contribution: Amount::from_sat(1_000),
+            ..Params::default()
+        }
+    }
+
+    /// A sender and the receiver's answer to it
+    async fn exchange(params: Params) -> (TestWallet, TestWallet, Sender, Psbt) {
+        let payer = TestWallet::new(1, &[100_000]);
+        let payee = TestWallet::new(2, &[50_000]);
+        let original = payer.original(&payee.script(), 10_000, 1_000).await;
+        let sender = Sender::new(original, payee.script(), params).unwrap();
+        let response = Receiver::new().process(&payee, &sender.query(), &sender.body()).await.unwrap();
+        let proposal = decode_psbt(&response).unwrap();
+        (payer, payee, sender, proposal)
+    }
+
+    #[tokio::test]
+    async fn test_payjoin_round_trip() {
+        let (payer, payee, sender, proposal) = exchange(params()).await;
+        let mut psbt = sender.process_response(&encode_psbt(&proposal)).unwrap();
+        payer.sign_psbt(&mut psbt).await.unwrap();
+        let tx = psbt.clone().extract_tx_unchecked_fee_rate();
+
+        assert_eq!(tx.input.len(), 2);
+        assert!(psbt.inputs.iter().all(|input| input.final_script_witness.is_some()));
+        let payment = tx.output.iter().find(|output| output.script_pubkey == payee.script()).unwrap();
+        assert!(payment.value > Amount::from_sat(10_000));
+        // The payer spent at most the agreed contribution on top of the original
+        let change = tx.output.iter().find(|output| output.script_pubkey == payer.script()).unwrap();
+        assert!(change.value >= sender.original_tx().output[1].value - Amount::from_sat(1_000));
+        assert!(fee(&psbt).unwrap() > Amount::from_sat(1_000));
+    }
+
+    #[tokio::test]
+    async fn test_original_checks() {
+        let payer = TestWallet::new(1, &[100_000]);
+        let payee = TestWallet::new(2, &[50_000]);
+        let original = payer.original(&payee.script(), 10_000, 1_000).await;
+
+        assert!(Sender::new(original.clone(), payee.script(), params()).is_ok());
+        assert!(Sender::new(original.clone(), ScriptBuf::new_op_return([1u8; 4]), params()).is_err());
+        let paying_fee_from_payment = Params { additional_fee_output_index: Some(0), ..params() };
+        assert!(Sender::new(original.clone(), payee.script(), paying_fee_from_payment).is_err());
+        let mut unsigned = original;
+        unsigned.inputs[0].final_script_witness = None;
+        assert!(Sender::new(unsigned, payee.script(), params()).is_err());
+    }
+
+    #[tokio::test]
+    async fn test_rejects_malicious_proposals() {
+        let (_, _, sender, proposal) = exchange(params()).await;
+        let check = |tamper: &dyn Fn(&mut Psbt)| {
+            let mut proposal = proposal.clone();
+            tamper(&mut proposal);
+            sender.process_response(&encode_psbt(&proposal))
+        };
+        let sender_index = proposal.inputs.iter().position(|input| input.final_script_witness.is_none()).unwrap();
+        let receiver_index = 1 - sender_index;
+
+        assert!(check(&|_| {}).is_ok());
+        assert!(check(&|psbt| psbt.unsigned_tx.lock_time = LockTime::ZERO).is_err());
+        // Taking more than the maximum from the sender's change
+        assert!(check(&|psbt| psbt.unsigned_tx.output[1].value -= Amount::from_sat(5_000)).is_err());
+        assert!(check(&|psbt| {
+            psbt.unsigned_tx.input.remove(sender_index);
+            psbt.inputs.remove(sender_index);
+        }).is_err());
+        assert!(check(&|psbt| psbt.unsigned_tx.input[sender_index].sequence = Sequence::MAX).is_err());
+        assert!(check(&|psbt| psbt.unsigned_tx.input[receiver_index].sequence = Sequence::MAX).is_err());
+        assert!(check(&|psbt| psbt.inputs[receiver_index].final_script_witness = None).is_err());
+        assert!(check(&|psbt| psbt.inputs[receiver_index].witness_utxo.as_mut().unwrap().script_pubkey = ScriptBuf::new_op_return([1u8; 4])).is_err());
+        assert!(check(&|psbt| {
+            let origin = (bitcoin::bip32::Fingerprint::default(), bitcoin::bip32::DerivationPath::master());
+            let key = bitcoin::secp256k1::SecretKey::from_slice(&[3; 32]).unwrap().public_key(&bitcoin::secp256k1::Secp256k1::new());
+            psbt.inputs[sender_index].bip32_derivation.insert(key, origin);
+        }).is_err());
+        assert!(sender.process_response("not a psbt").is_err());
+    }
+
+    #[tokio::test]
+    async fn test_output_substitution() {
+        let (_, _, sender, proposal) = exchange(params()).await;
+        let mut substituted = proposal.clone();
+        substituted.unsigned_tx.output[0].script_pubkey = ScriptBuf::new_op_return([1u8; 4]);
+        assert!(sender.process_response(&encode_psbt(&substituted)).is_ok());
+
+        let (_, _, sender, proposal) = exchange(Params { disable_output_substitution: true, ..params() }).await;
+        assert!(sender.process_response(&encode_psbt(&proposal)).is_ok());
+        let mut substituted = proposal;
+        substituted.unsigned_tx.output[0].script_pubkey = ScriptBuf::new_op_return([1u8; 4]);
+        assert!(sender.process_response(&encode_psbt(&substituted)).is_err());
+    }
+}
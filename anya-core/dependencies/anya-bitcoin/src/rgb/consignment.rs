@@ -0,0 +1,196 @@
+//! RGB consignments
+//!
+//! Consignments are rgb-std [`Transfer`]s and [`Contract`]s in their strict
+//! encoding; files hold them as a [`Bindle`], the format the RGB tools read and
+//! write. Validation runs the rgb-core validator with witness transactions
+//! taken from a [`WitnessSource`], so only witnesses the source considers mined
+//! anchor any state.
+
+use std::path::Path;
+
+use amplify::confinement::{Confined, U32};
+use bitcoin::consensus::encode::serialize;
+use bitcoin::hashes::Hash;
+use bitcoin::{Network, Transaction, Txid};
+use bitcoincore_rpc::RpcApi;
+use rgb_core::validation::{ResolveTx, TxResolverError, Validity};
+use rgb_core::TxPtr;
+use rgb_std::resolvers::ResolveHeight;
+use strict_encoding::{StrictDeserialize, StrictSerialize};
+
+pub use rgb_core::ContractId;
+pub use rgb_std::containers::{Bindle, Consignment, Contract, Transfer};
+
+/// Errors from handling consignments and the stock
+#[derive(Debug, thiserror::Error)]
+pub enum ConsignmentError {
+    /// Data is not a strict-encoded consignment
+    #[error("Consignment encoding error: {0}")]
+    Encoding(String),
+
+    /// Client-side validation failed
+    #[error("Consignment validation error: {0}")]
+    Invalid(String),
+
+    /// Witness transaction is unknown or not mined
+    #[error("Witness {0} is not mined")]
+    UnknownWitness(Txid),
+
+    /// Stock storage error
+    #[error("Storage error: {0}")]
+    Storage(String),
+}
+
+/// Source of mined witness transactions
+pub trait WitnessSource {
+    /// Witness transaction `txid` and the height of its block, if it is mined
+    fn witness(&self, txid: &Txid) -> Option<(Transaction, u32)>;
+}
+
+impl WitnessSource for bitcoincore_rpc::Client {
+    fn witness(&self, txid: &Txid) -> Option<(Transaction, u32)> {
+        let info = self.get_raw_transaction_info(txid, None).ok()?;
+        let header = self.get_block_header_info(&info.blockhash?).ok()?;
+        Some((info.transaction().ok()?, header.height as u32))
+    }
+}
+
+/// Adapts a [`WitnessSource`] to the rgb-std resolver traits
+pub struct Resolver<'a, W: WitnessSource + ?Sized>(pub &'a W);
+
+impl<W: WitnessSource + ?Sized> ResolveTx for Resolver<'_, W> {
+    fn resolve_tx(&self, txid: bp::Txid) -> Result<bp::Tx, TxResolverError> {
+        self.0.witness(&from_bp_txid(txid))
+            .and_then(|(tx, _)| to_bp_tx(&tx).ok())
+            .ok_or(TxResolverError::Unknown(txid))
+    }
+}
+
+impl<W: WitnessSource + ?Sized> ResolveHeight for Resolver<'_, W> {
+    type Error = ConsignmentError;
+
+    fn resolve_height(&mut self, txid: bp::Txid) -> Result<u32, Self::Error> {
+        let txid = from_bp_txid(txid);
+        self.0.witness(&txid)
+            .map(|(_, height)| height)
+            .ok_or(ConsignmentError::UnknownWitness(txid))
+    }
+}
+
+/// rgb-std form of a Bitcoin txid
+pub fn to_bp_txid(txid: Txid) -> bp::Txid {
+    bp::Txid::from(txid.to_byte_array())
+}
+
+/// Bitcoin form of an rgb-std txid
+pub fn from_bp_txid(txid: bp::Txid) -> Txid {
+    Txid::from_byte_array(txid.to_byte_array())
+}
+
+/// rgb-std form of a Bitcoin outpoint
+pub fn to_bp_outpoint(outpoint: bitcoin::OutPoint) -> bp::Outpoint {
+    bp::Outpoint::new(to_bp_txid(outpoint.txid), outpoint.vout)
+}
+
+fn to_bp_tx(tx: &Transaction) -> Result<bp::Tx, ConsignmentError> {
+    bp::Tx::consensus_deserialize(serialize(tx))
+        .map_err(|e| ConsignmentError::Encoding(format!("Witness {}: {}", tx.compute_txid(), e)))
+}
+
+/// Chain a Bitcoin network corresponds to in rgb-std
+pub fn chain(network: Network) -> bp::Chain {
+    match network {
+        Network::Bitcoin => bp::Chain::Bitcoin,
+        Network::Testnet => bp::Chain::Testnet3,
+        Network::Signet => bp::Chain::Signet,
+        _ => bp::Chain::Regtest,
+    }
+}
+
+/// Strict encoding of a transfer
+pub fn to_bytes(transfer: &Transfer) -> Result<Vec<u8>, ConsignmentError> {
+    transfer.to_strict_serialized::<U32>()
+        .map(Confined::release)
+        .map_err(|e| ConsignmentError::Encoding(e.to_string()))
+}
+
+/// Decode a strictly encoded transfer
+pub fn from_bytes(data: &[u8]) -> Result<Transfer, ConsignmentError> {
+    Confined::try_from(data.to_vec())
+        .map_err(|e| ConsignmentError::Encoding(e.to_string()))
+        .and_then(|data| {
+            Transfer::from_strict_serialized::<U32>(data).map_err(|e| ConsignmentError::Encoding(e.to_string()))
+        })
+}
+
+/// Save a transfer to a consignment file
+pub fn save_file(transfer: Transfer, path: impl AsRef<Path>) -> Result<(), ConsignmentError> {
+    Bindle::new(transfer).save(path.as_ref())
+        .map_err(|e| ConsignmentError::Storage(format!("{}: {}", path.as_ref().display(), e)))
+}
+
+/// Load a transfer from a consignment file
+pub fn load_file(path: impl AsRef<Path>) -> Result<Transfer, ConsignmentError> {
+    Bindle::<Transfer>::load(path.as_ref())
+        .map(Bindle::unbindle)
+        .map_err(|e| ConsignmentError::Encoding(format!("{}: {}", path.as_ref().display(), e)))
+}
+
+/// Validate a consignment against the witnesses of `source`
+///
+/// Besides the rgb-core checks, every seal defined on a witness output must
+/// point at an existing output that is not the OP_RETURN commitment.
+pub fn validate<const TRANSFER: bool, W: WitnessSource + ?Sized>(
+    consignment: Consignment<TRANSFER>,
+    network: Network,
+    source: &W,
+) -> Result<Consignment<TRANSFER>, ConsignmentError> {
+    if consignment.genesis.chain != chain(network) {
+        return Err(ConsignmentError::Invalid(format!(
+            "Contract is on {}, expected {}", consignment.genesis.chain, chain(network)
+        )));
+    }
+
+    let consignment = consignment.validate(&mut Resolver(source))
+        .map_err(|rejected| ConsignmentError::Invalid(format!("{:?}", rejected.validation_status())))?;
+    match consignment.validation_status().map(|status| status.validity()) {
+        Some(Validity::Valid) => {},
+        _ => return Err(ConsignmentError::Invalid(format!("{:?}", consignment.validation_status()))),
+    }
+
+    check_witness_seals(&consignment, source)?;
+    Ok(consignment)
+}
+
+/// Check seals on witness outputs against the witness transactions
+pub fn check_witness_seals<const TRANSFER: bool, W: WitnessSource + ?Sized>(
+    consignment: &Consignment<TRANSFER>,
+    source: &W,
+) -> Result<(), ConsignmentError> {
+    for anchored in consignment.anchored_bundles() {
+        let txid = from_bp_txid(anchored.anchor.txid);
+        let (witness, _) = source.witness(&txid).ok_or(ConsignmentError::UnknownWitness(txid))?;
+        let commitment = witness.output.iter().position(|output| output.script_pubkey.is_op_return());
+
+        for transition in anchored.bundle.known_transitions.values() {
+            for assigns in transition.assignments.values() {
+                let seals = assigns.revealed_seals()
+                    .map_err(|e| ConsignmentError::Invalid(format!("Transition {}: {}", transition.id(), e)))?;
+                for seal in seals.into_iter().filter(|seal| seal.txid == TxPtr::WitnessTx) {
+                    let vout = seal.vout.into_u32() as usize;
+                    if vout >= witness.output.len() {
+                        return Err(ConsignmentError::Invalid(format!(
+                            "Seal on output {} of witness {}, which has {} outputs", vout, txid, witness.output.len()
+                        )));
+                    }
+                    if Some(vout) == commitment {
+                        return Err(ConsignmentError::Invalid(format!(
+                            "Seal on the commitment output {} of witness {}", vout, txid
+                        )));
+                    }
+                }
+            }
+        }
+    }
+    Ok(())
+}
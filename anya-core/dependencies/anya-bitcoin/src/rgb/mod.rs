@@ -14,11 +14,20 @@
 };
 use secp256k1::{All, Secp256k1};
 use std::collections::HashMap;
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::str::FromStr;
 
 use crate::wallet::BitcoinWallet;
 
+pub mod consignment;
+pub mod stash;
+
+pub use self::consignment::{ConsignmentError, WitnessSource};
+pub use self::stash::Stash;
+
+/// File name of the stock inside the inventory directory
+const STOCK_FILE: &str = "stock.rgb";
+
 /// RGB asset manager for issuing and managing assets on Bitcoin
 pub struct RgbManager {
     network: Network,
@@ -58,6 +67,42 @@ fn open_inventory(&self) -> Result<Inventory> {
             .map_err(|e| anyhow!("Error opening RGB inventory: {}", e))
     }
     
+    /// Open the persisted stock that consignments are exchanged from
+    fn open_stash(&self) -> Result<Stash> {
+        Ok(Stash::open(self.inventory_path.join(STOCK_FILE))?)
+    }
+
+    /// Export a consignment of the state on `outputs` to a file
+    pub fn export_consignment(
+        &self,
+        contract_id: &str,
+        outputs: &[OutPoint],
+        path: &Path,
+        witnesses: &impl WitnessSource,
+    ) -> Result<()> {
+        let contract_id = consignment::ContractId::from_str(contract_id)?;
+        let transfer = self.open_stash()?.consign(contract_id, outputs)?;
+
+        // Never hand out history the receiver would reject
+        let transfer = consignment::validate(transfer, self.network, witnesses)?;
+        consignment::save_file(transfer, path)?;
+        Ok(())
+    }
+
+    /// Validate a consignment file and accept it into the stock
+    pub fn import_consignment(&self, path: &Path, witnesses: &impl WitnessSource) -> Result<String> {
+        let transfer = consignment::load_file(path)?;
+        let contract_id = self.open_stash()?.accept_transfer(transfer, self.network, witnesses)?;
+        Ok(contract_id.to_string())
+    }
+
+    /// Validate a contract in the stock against the witnesses' anchors
+    pub fn validate_stored_contract(&self, contract_id: &str, witnesses: &impl WitnessSource) -> Result<()> {
+        let contract_id = consignment::ContractId::from_str(contract_id)?;
+        self.open_stash()?.validate_contract(contract_id, self.network, witnesses)?;
+        Ok(())
+    }
+    
     /// Issue a new fungible asset
     pub fn issue_asset(
         &self,
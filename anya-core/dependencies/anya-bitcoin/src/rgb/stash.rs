@@ -0,0 +1,154 @@
+//! Persisted RGB stock
+//!
+//! [`Stash`] keeps an rgb-std [`Stock`] in a strict-encoded file. Changes are
+//! applied to a copy of the stock, which is written to a temporary file that
+//! then replaces the old one; only once that succeeded does the copy replace
+//! the stock in memory, so a failed write leaves both untouched.
+
+use std::collections::BTreeSet;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use amplify::confinement::U32;
+use bitcoin::{Network, OutPoint};
+use rgb_std::persistence::{Inventory, Stock};
+use strict_encoding::{StrictDeserialize, StrictSerialize};
+
+use super::consignment::{self, ConsignmentError, Contract, ContractId, Resolver, Transfer, WitnessSource};
+
+/// Contract stock, optionally persisted
+#[derive(Debug, Default)]
+pub struct Stash {
+    path: Option<PathBuf>,
+    stock: Stock,
+}
+
+impl Stash {
+    /// Create a stash that is not persisted
+    pub fn in_memory() -> Self {
+        Self::default()
+    }
+
+    /// Open the stock stored at `path`, creating it if missing
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, ConsignmentError> {
+        let path = path.as_ref().to_path_buf();
+        let stock = if path.exists() {
+            Stock::strict_deserialize_from_file::<U32>(&path)
+                .map_err(|e| ConsignmentError::Storage(format!("{}: {}", path.display(), e)))?
+        } else {
+            Stock::default()
+        };
+        Ok(Self { path: Some(path), stock })
+    }
+
+    /// The stock
+    pub fn stock(&self) -> &Stock {
+        &self.stock
+    }
+
+    /// IDs of the stored contracts
+    pub fn contracts(&self) -> BTreeSet<ContractId> {
+        self.stock.contract_ids().unwrap_or_default()
+    }
+
+    /// Validate a contract and add it to the stock
+    pub fn import_contract<W: WitnessSource + ?Sized>(
+        &mut self,
+        contract: Contract,
+        network: Network,
+        source: &W,
+    ) -> Result<ContractId, ConsignmentError> {
+        let contract = consignment::validate(contract, network, source)?;
+        let contract_id = contract.contract_id();
+        self.update(|stock| {
+            stock.import_contract(contract, &mut Resolver(source))
+                .map_err(|e| ConsignmentError::Invalid(e.to_string()))
+        })?;
+        Ok(contract_id)
+    }
+
+    /// Validate a received transfer and merge it into the stock
+    pub fn accept_transfer<W: WitnessSource + ?Sized>(
+        &mut self,
+        transfer: Transfer,
+        network: Network,
+        source: &W,
+    ) -> Result<ContractId, ConsignmentError> {
+        let transfer = consignment::validate(transfer, network, source)?;
+        let contract_id = transfer.contract_id();
+        self.update(|stock| {
+            stock.accept_transfer(transfer, &mut Resolver(source), false)
+                .map_err(|e| ConsignmentError::Invalid(e.to_string()))
+        })?;
+        Ok(contract_id)
+    }
+
+    /// Transfer consigning the state on `outputs`, with the history it depends on
+    pub fn consign(&mut self, contract_id: ContractId, outputs: &[OutPoint]) -> Result<Transfer, ConsignmentError> {
+        let outputs: Vec<_> = outputs.iter().copied().map(consignment::to_bp_outpoint).collect();
+        self.stock.transfer(contract_id, outputs, [])
+            .map(|bindle| bindle.unbindle())
+            .map_err(|e| ConsignmentError::Invalid(e.to_string()))
+    }
+
+    /// Validate the stored history of a contract against the witnesses of `source`
+    pub fn validate_contract<W: WitnessSource + ?Sized>(
+        &self,
+        contract_id: ContractId,
+        network: Network,
+        source: &W,
+    ) -> Result<Contract, ConsignmentError> {
+        let contract = self.stock.export_contract(contract_id)
+            .map_err(|e| ConsignmentError::Invalid(e.to_string()))?
+            .unbindle();
+        consignment::validate(contract, network, source)
+    }
+
+    /// Apply `change` to a copy of the stock and keep it once it is saved
+    fn update<T>(&mut self, change: impl FnOnce(&mut Stock) -> Result<T, ConsignmentError>) -> Result<T, ConsignmentError> {
+        let mut stock = self.stock.clone();
+        let value = change(&mut stock)?;
+        self.save(&stock)?;
+        self.stock = stock;
+        Ok(value)
+    }
+
+    fn save(&self, stock: &Stock) -> Result<(), ConsignmentError> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+
+        let temporary = path.with_extension("tmp");
+        stock.strict_serialize_to_file::<U32>(&temporary)
+            .map_err(|e| e.to_string())
+            .and_then(|_| fs::rename(&temporary, path).map_err(|e| e.to_string()))
+            .map_err(|e| ConsignmentError::Storage(format!("{}: {}", path.display(), e)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_persist_and_failed_save() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("stock.rgb");
+
+        let mut stash = Stash::open(&path).unwrap();
+        stash.update(|_| Ok(())).unwrap();
+        assert!(path.exists());
+        assert!(Stash::open(&path).unwrap().contracts().is_empty());
+
+        // The change is dropped when the stock cannot be written
+        let mut stash = Stash::open(dir.path().join("missing").join("stock.rgb")).unwrap();
+        let result = stash.update(|stock| {
+            *stock = Stock::default();
+            Ok(())
+        });
+        assert!(matches!(result, Err(ConsignmentError::Storage(_))));
+
+        fs::write(&path, b"not a stock").unwrap();
+        assert!(matches!(Stash::open(&path), Err(ConsignmentError::Storage(_))));
+    }
+}
@@ -0,0 +1,771 @@
+// SPDX-License-Identifier: MIT
+// Copyright (C) 2023-2025 Anya Project Contributors
+
+//! Silent payments (BIP352)
+//!
+//! A silent payment address is a static pair of public keys, `B_scan` and
+//! `B_spend`, that can be published once and paid many times without any
+//! two payments landing on the same output. The sender tweaks `B_spend` with
+//! an ECDH secret between its input keys and `B_scan`, so only the receiver
+//! can recognise the resulting taproot outputs.
+//!
+//! - [`SilentPaymentAddress`] encodes and decodes `sp1…`/`tsp1…` addresses,
+//!   including labelled ones;
+//! - [`derive_outputs`] computes the outputs a sender pays to, from the
+//!   private keys of the transaction's inputs;
+//! - [`Receiver`] scans a transaction's taproot outputs, either from the
+//!   transaction and its prevouts or from the tweak data an index serves.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fmt;
+use std::str::FromStr;
+
+use bitcoin::bech32::primitives::decode::CheckedHrpstring;
+use bitcoin::bech32::{Bech32m, ByteIterExt, Fe32, Fe32IterExt, Hrp};
+use bitcoin::consensus::Encodable;
+use bitcoin::hashes::{hash160, sha256, Hash, HashEngine};
+use bitcoin::secp256k1::{PublicKey, Scalar, Secp256k1, SecretKey, Signing, Verification, XOnlyPublicKey};
+use bitcoin::{Network, OutPoint, ScriptBuf, Transaction, TxIn, TxOut};
+use serde::{Deserialize, Serialize};
+use thiserror::Error;
+
+/// Silent payment address version this module reads and writes
+const VERSION: Fe32 = Fe32::Q;
+
+/// Internal key of script-path-only taproot outputs (the BIP341 NUMS point)
+const NUMS_H: [u8; 32] = [
+    0x50, 0x92, 0x9b, 0x74, 0xc1, 0xa0, 0x49, 0x54, 0xb7, 0x8b, 0x4b, 0x60, 0x35, 0xe9, 0x7a, 0x5e,
+    0x07, 0x8a, 0x5a, 0x0f, 0x28, 0xec, 0x96, 0xd5, 0x47, 0xbf, 0xee, 0x9a, 0xce, 0x80, 0x3a, 0xc0,
+];
+
+/// Label reserved for the receiver's own change
+pub const CHANGE_LABEL: u32 = 0;
+
+/// Most outputs one transaction may pay to a single scan key (`K_max`)
+pub const MAX_OUTPUTS_PER_SCAN_KEY: u32 = 2323;
+
+/// Errors of silent payment encoding, sending and scanning
+#[derive(Debug, Error)]
+pub enum SilentPaymentError {
+    #[error("Invalid silent payment address: {0}")]
+    InvalidAddress(String),
+    #[error("Address is for {found}, not {expected}")]
+    WrongNetwork { expected: Network, found: Network },
+    #[error("Transaction has no inputs eligible for silent payments")]
+    NoEligibleInputs,
+    #[error("Input keys sum to zero")]
+    ZeroInputKeys,
+    #[error("Tweak is out of range")]
+    InvalidTweak,
+    #[error("Input {0} has no previous output")]
+    MissingPrevout(usize),
+    #[error("More than {} outputs to one scan key", MAX_OUTPUTS_PER_SCAN_KEY)]
+    TooManyOutputs,
+}
+
+pub type Result<T> = std::result::Result<T, SilentPaymentError>;
+
+/// `sha256(sha256(tag) || sha256(tag) || data)` of the BIP340 tagged hashes
+fn tagged_hash(tag: &str, data: &[&[u8]]) -> [u8; 32] {
+    let tag = sha256::Hash::hash(tag.as_bytes());
+    let mut engine = sha256::Hash::engine();
+    engine.input(tag.as_ref());
+    engine.input(tag.as_ref());
+    for part in data {
+        engine.input(part);
+    }
+    sha256::Hash::from_engine(engine).to_byte_array()
+}
+
+fn scalar(bytes: [u8; 32]) -> Result<Scalar> {
+    Scalar::from_be_bytes(bytes).map_err(|_| SilentPaymentError::InvalidTweak)
+}
+
+fn serialize_outpoint(outpoint: &OutPoint) -> Vec<u8> {
+    let mut bytes = Vec::with_capacity(36);
+    outpoint.consensus_encode(&mut bytes).expect("writing to a vec");
+    bytes
+}
+
+/// `hash_BIP0352/Inputs(outpoint_L || A)` over the smallest outpoint
+fn input_hash(outpoints: &[OutPoint], input_key: &PublicKey) -> Result<Scalar> {
+    let smallest = outpoints.iter().map(serialize_outpoint).min().ok_or(SilentPaymentError::NoEligibleInputs)?;
+    scalar(tagged_hash("BIP0352/Inputs", &[&smallest, &input_key.serialize()]))
+}
+
+/// `t_k = hash_BIP0352/SharedSecret(ser_P(ecdh) || ser_32(k))`
+fn shared_secret_tweak(ecdh: &PublicKey, k: u32) -> Result<Scalar> {
+    scalar(tagged_hash("BIP0352/SharedSecret", &[&ecdh.serialize(), &k.to_be_bytes()]))
+}
+
+/// Tweak `hash_BIP0352/Label(ser_256(b_scan) || ser_32(m))` of label `m`
+pub fn label_tweak(scan_key: &SecretKey, label: u32) -> Result<Scalar> {
+    scalar(tagged_hash("BIP0352/Label", &[&scan_key.secret_bytes(), &label.to_be_bytes()]))
+}
+
+/// A static silent payment address
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct SilentPaymentAddress {
+    scan: PublicKey,
+    spend: PublicKey,
+    /// Mainnet, or one of the test networks, which share the `tsp` prefix
+    network: Network,
+}
+
+impl SilentPaymentAddress {
+    pub fn new(scan: PublicKey, spend: PublicKey, network: Network) -> Self {
+        SilentPaymentAddress { scan, spend, network }
+    }
+
+    pub fn scan_key(&self) -> PublicKey {
+        self.scan
+    }
+
+    /// `B_spend`, or `B_m` for a labelled address
+    pub fn spend_key(&self) -> PublicKey {
+        self.spend
+    }
+
+    pub fn network(&self) -> Network {
+        self.network
+    }
+
+    /// Whether the address may be paid on `network`
+    pub fn is_valid_for(&self, network: Network) -> bool {
+        (self.network == Network::Bitcoin) == (network == Network::Bitcoin)
+    }
+
+    pub fn require_network(self, network: Network) -> Result<Self> {
+        if !self.is_valid_for(network) {
+            return Err(SilentPaymentError::WrongNetwork { expected: network, found: self.network });
+        }
+        Ok(self)
+    }
+
+    fn hrp(&self) -> Hrp {
+        match self.network {
+            Network::Bitcoin => Hrp::parse_unchecked("sp"),
+            _ => Hrp::parse_unchecked("tsp"),
+        }
+    }
+}
+
+impl fmt::Display for SilentPaymentAddress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let hrp = self.hrp();
+        let data = self.scan.serialize().into_iter().chain(self.spend.serialize());
+        for c in data.bytes_to_fes().with_checksum::<Bech32m>(&hrp).with_witness_version(VERSION).chars() {
+            write!(f, "{}", c)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for SilentPaymentAddress {
+    type Err = SilentPaymentError;
+
+    fn from_str(s: &str) -> Result<Self> {
+        let invalid = |reason: &str| SilentPaymentError::InvalidAddress(reason.to_string());
+        let mut checked = CheckedHrpstring::new::<Bech32m>(s).map_err(|e| invalid(&e.to_string()))?;
+        let network = match checked.hrp().to_lowercase().as_str() {
+            "sp" => Network::Bitcoin,
+            "tsp" => Network::Testnet,
+            other => return Err(invalid(&format!("unknown prefix {}", other))),
+        };
+        let version = checked.remove_witness_version().ok_or_else(|| invalid("missing version"))?;
+        if version == Fe32::L {
+            return Err(invalid("version 31 is reserved"));
+        }
+        let data: Vec<u8> = checked.byte_iter().collect();
+        // Later versions may append data, but always start with the two keys
+        if data.len() < 66 || (version == VERSION && data.len() != 66) {
+            return Err(invalid(&format!("{} bytes of key data", data.len())));
+        }
+        let scan = PublicKey::from_slice(&data[..33]).map_err(|_| invalid("bad scan key"))?;
+        let spend = PublicKey::from_slice(&data[33..66]).map_err(|_| invalid("bad spend key"))?;
+        Ok(SilentPaymentAddress { scan, spend, network })
+    }
+}
+
+/// Private key of an input the sender is spending
+#[derive(Debug, Clone, Copy)]
+pub struct SenderInput {
+    pub outpoint: OutPoint,
+    pub secret_key: SecretKey,
+    /// Whether the input is a taproot key path spend, whose key counts with
+    /// even Y
+    pub taproot: bool,
+}
+
+/// The taproot output keys paying each address, in the order given
+///
+/// Every input of the transaction must be listed, so its outpoint takes
+/// part in the input hash; inputs of types BIP352 skips can be left out of
+/// the key sum by the caller. Fails if more than
+/// [`MAX_OUTPUTS_PER_SCAN_KEY`] outputs would share a scan key.
+pub fn derive_outputs<C: Signing + Verification>(
+    secp: &Secp256k1<C>,
+    inputs: &[SenderInput],
+    outpoints: &[OutPoint],
+    recipients: &[SilentPaymentAddress],
+) -> Result<Vec<XOnlyPublicKey>> {
+    let mut per_scan_key: HashMap<PublicKey, u32> = HashMap::new();
+    for recipient in recipients {
+        let count = per_scan_key.entry(recipient.scan).or_insert(0);
+        *count += 1;
+        if *count > MAX_OUTPUTS_PER_SCAN_KEY {
+            return Err(SilentPaymentError::TooManyOutputs);
+        }
+    }
+
+    if inputs.is_empty() {
+        return Err(SilentPaymentError::NoEligibleInputs);
+    }
+    let keys = inputs.iter().map(|input| {
+        let (_, parity) = input.secret_key.x_only_public_key(secp);
+        if input.taproot && parity == bitcoin::secp256k1::Parity::Odd {
+            input.secret_key.negate()
+        } else {
+            input.secret_key
+        }
+    });
+    // A partial sum may be zero even when the full one is not
+    let input_key = keys
+        .fold(None, |sum: Option<SecretKey>, key| match sum {
+            Some(sum) => sum.add_tweak(&Scalar::from(key)).ok(),
+            None => Some(key),
+        })
+        .ok_or(SilentPaymentError::ZeroInputKeys)?;
+    let tweak = input_hash(outpoints, &input_key.public_key(secp))?;
+    let tweaked_key = input_key.mul_tweak(&tweak).map_err(|_| SilentPaymentError::InvalidTweak)?;
+
+    // Recipients sharing a scan key share the ECDH secret, counted by k
+    let mut next_k: HashMap<PublicKey, u32> = HashMap::new();
+    let mut secrets: HashMap<PublicKey, PublicKey> = HashMap::new();
+    recipients.iter().map(|recipient| {
+        let ecdh = match secrets.get(&recipient.scan) {
+            Some(ecdh) => *ecdh,
+            None => {
+                let ecdh = recipient.scan.mul_tweak(secp, &Scalar::from(tweaked_key))
+                    .map_err(|_| SilentPaymentError::InvalidTweak)?;
+                *secrets.entry(recipient.scan).or_insert(ecdh)
+            }
+        };
+        let k = next_k.entry(recipient.scan).or_insert(0);
+        let t_k = shared_secret_tweak(&ecdh, *k)?;
+        *k += 1;
+        let output = recipient.spend.add_exp_tweak(secp, &t_k).map_err(|_| SilentPaymentError::InvalidTweak)?;
+        Ok(output.x_only_public_key().0)
+    }).collect()
+}
+
+/// Public key an input contributes to the input sum, if it is eligible
+pub fn input_public_key(input: &TxIn, prevout: &TxOut) -> Option<PublicKey> {
+    let script = &prevout.script_pubkey;
+    if script.is_p2tr() {
+        let mut witness: Vec<&[u8]> = input.witness.iter().collect();
+        if witness.len() > 1 && witness.last().is_some_and(|last| last.first() == Some(&0x50)) {
+            witness.pop();
+        }
+        // Script path spends with an unspendable internal key
+        if witness.len() > 1 {
+            let control_block = witness.last()?;
+            if control_block.len() >= 33 && control_block[1..33] == NUMS_H {
+                return None;
+            }
+        }
+        let key = XOnlyPublicKey::from_slice(&script.as_bytes()[2..34]).ok()?;
+        Some(key.public_key(bitcoin::secp256k1::Parity::Even))
+    } else if script.is_p2wpkh() {
+        compressed_key(input.witness.nth(1)?)
+    } else if script.is_p2sh() {
+        let redeem_script = input.script_sig.instructions().last()?.ok()?;
+        let redeem_script = ScriptBuf::from_bytes(redeem_script.push_bytes()?.as_bytes().to_vec());
+        if !redeem_script.is_p2wpkh() {
+            return None;
+        }
+        compressed_key(input.witness.nth(1)?)
+    } else if script.is_p2pkh() {
+        // The key is whichever 33 bytes of the script sig hash to the
+        // script's key hash, searching from the end
+        let hash = &script.as_bytes()[3..23];
+        let script_sig = input.script_sig.as_bytes();
+        (0..script_sig.len().saturating_sub(32)).rev()
+            .map(|start| &script_sig[start..start + 33])
+            .find(|candidate| hash160::Hash::hash(candidate).as_byte_array() == hash)
+            .and_then(compressed_key)
+    } else {
+        None
+    }
+}
+
+fn compressed_key(bytes: &[u8]) -> Option<PublicKey> {
+    if bytes.len() != 33 {
+        return None;
+    }
+    PublicKey::from_slice(bytes).ok()
+}
+
+/// Tweak data `input_hash·A` of a transaction, as an index serves it to
+/// light clients
+///
+/// `None` when the transaction cannot carry silent payments: it has no
+/// taproot output, spends a future segwit version, has no eligible input
+/// or its input keys sum to zero.
+pub fn tweak_data<C: Verification>(secp: &Secp256k1<C>, tx: &Transaction, prevouts: &[TxOut]) -> Result<Option<PublicKey>> {
+    if prevouts.len() != tx.input.len() {
+        return Err(SilentPaymentError::MissingPrevout(prevouts.len().min(tx.input.len())));
+    }
+    if !tx.output.iter().any(|output| output.script_pubkey.is_p2tr()) {
+        return Ok(None);
+    }
+    let future_segwit = prevouts.iter().any(|prevout| {
+        prevout.script_pubkey.witness_version().is_some_and(|version| version.to_num() > 1)
+    });
+    if future_segwit {
+        return Ok(None);
+    }
+
+    let keys: Vec<PublicKey> = tx.input.iter().zip(prevouts)
+        .filter_map(|(input, prevout)| input_public_key(input, prevout))
+        .collect();
+    if keys.is_empty() {
+        return Ok(None);
+    }
+    let Ok(input_key) = PublicKey::combine_keys(&keys.iter().collect::<Vec<_>>()) else {
+        return Ok(None);
+    };
+    let outpoints: Vec<OutPoint> = tx.input.iter().map(|input| input.previous_output).collect();
+    let tweak = input_hash(&outpoints, &input_key)?;
+    input_key.mul_tweak(secp, &tweak).map(Some).map_err(|_| SilentPaymentError::InvalidTweak)
+}
+
+/// A silent payment output the receiver found
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReceivedOutput {
+    pub output_key: XOnlyPublicKey,
+    /// Tweak to add to `b_spend` for the output's private key
+    pub tweak: SecretKey,
+    pub label: Option<u32>,
+}
+
+impl ReceivedOutput {
+    /// Private key of the output, negated by the signer if its Y is odd
+    pub fn secret_key(&self, spend_key: &SecretKey) -> Result<SecretKey> {
+        spend_key.add_tweak(&Scalar::from(self.tweak)).map_err(|_| SilentPaymentError::InvalidTweak)
+    }
+}
+
+/// The scanning side of a silent payment address
+#[derive(Debug, Clone)]
+pub struct Receiver {
+    scan_key: SecretKey,
+    spend: PublicKey,
+    network: Network,
+    /// Label points `m·G` by label, and the reverse
+    labels: BTreeMap<u32, PublicKey>,
+    by_point: HashMap<PublicKey, u32>,
+}
+
+impl Receiver {
+    /// A receiver with the change label already registered
+    pub fn new<C: Signing>(secp: &Secp256k1<C>, scan_key: SecretKey, spend: PublicKey, network: Network) -> Result<Self> {
+        let mut receiver = Receiver { scan_key, spend, network, labels: BTreeMap::new(), by_point: HashMap::new() };
+        receiver.add_label(secp, CHANGE_LABEL)?;
+        Ok(receiver)
+    }
+
+    /// The unlabelled address
+    pub fn address<C: Signing>(&self, secp: &Secp256k1<C>) -> SilentPaymentAddress {
+        SilentPaymentAddress::new(self.scan_key.public_key(secp), self.spend, self.network)
+    }
+
+    /// Starts watching for payments to label `m`
+    pub fn add_label<C: Signing>(&mut self, secp: &Secp256k1<C>, label: u32) -> Result<PublicKey> {
+        let tweak = label_tweak(&self.scan_key, label)?;
+        let point = SecretKey::from_slice(&tweak.to_be_bytes())
+            .map_err(|_| SilentPaymentError::InvalidTweak)?
+            .public_key(secp);
+        self.labels.insert(label, point);
+        self.by_point.insert(point, label);
+        Ok(point)
+    }
+
+    /// Address for label `m`, registering the label if needed
+    pub fn labelled_address<C: Signing + Verification>(&mut self, secp: &Secp256k1<C>, label: u32) -> Result<SilentPaymentAddress> {
+        let point = match self.labels.get(&label) {
+            Some(point) => *point,
+            None => self.add_label(secp, label)?,
+        };
+        let spend = self.spend.combine(&point).map_err(|_| SilentPaymentError::InvalidTweak)?;
+        Ok(SilentPaymentAddress::new(self.scan_key.public_key(secp), spend, self.network))
+    }
+
+    /// Labels being watched, in order
+    pub fn labels(&self) -> impl Iterator<Item = u32> + '_ {
+        self.labels.keys().copied()
+    }
+
+    /// Finds the outputs paying this receiver among a transaction's
+    /// taproot output keys, given its tweak data
+    ///
+    /// Stops after [`MAX_OUTPUTS_PER_SCAN_KEY`] matches, as no valid sender
+    /// pays more.
+    pub fn scan<C: Signing + Verification>(
+        &self,
+        secp: &Secp256k1<C>,
+        tweak_data: &PublicKey,
+        outputs: &[XOnlyPublicKey],
+    ) -> Result<Vec<ReceivedOutput>> {
+        let ecdh = tweak_data.mul_tweak(secp, &Scalar::from(self.scan_key))
+            .map_err(|_| SilentPaymentError::InvalidTweak)?;
+        let mut remaining: Vec<XOnlyPublicKey> = outputs.to_vec();
+        let mut found = Vec::new();
+
+        for k in 0..MAX_OUTPUTS_PER_SCAN_KEY {
+            let t_k = shared_secret_tweak(&ecdh, k)?;
+            let t_k_key = SecretKey::from_slice(&t_k.to_be_bytes()).map_err(|_| SilentPaymentError::InvalidTweak)?;
+            let p_k = self.spend.add_exp_tweak(secp, &t_k).map_err(|_| SilentPaymentError::InvalidTweak)?;
+
+            let unlabelled = remaining.iter()
+                .position(|output| p_k.x_only_public_key().0 == *output)
+                .map(|index| (index, t_k_key, None));
+            let matched = unlabelled.or_else(|| remaining.iter().enumerate().find_map(|(index, output)| {
+                // output = P_k + label, with either parity of the output
+                let even = output.public_key(bitcoin::secp256k1::Parity::Even);
+                [even, even.negate(secp)].into_iter().find_map(|candidate| {
+                    let label_point = candidate.combine(&p_k.negate(secp)).ok()?;
+                    let label = *self.by_point.get(&label_point)?;
+                    let label_tweak = label_tweak(&self.scan_key, label).ok()?;
+                    let tweak = t_k_key.add_tweak(&label_tweak).ok()?;
+                    Some((index, tweak, Some(label)))
+                })
+            }));
+            let Some((index, tweak, label)) = matched else {
+                break;
+            };
+            found.push(ReceivedOutput { output_key: remaining.remove(index), tweak, label });
+        }
+        Ok(found)
+    }
+
+    /// Scans a full transaction, given the outputs its inputs spend
+    pub fn scan_transaction<C: Signing + Verification>(
+        &self,
+        secp: &Secp256k1<C>,
+        tx: &Transaction,
+        prevouts: &[TxOut],
+    ) -> Result<Vec<ReceivedOutput>> {
+        let Some(tweak_data) = tweak_data(secp, tx, prevouts)? else {
+            return Ok(Vec::new());
+        };
+        let outputs: Vec<XOnlyPublicKey> = tx.output.iter()
+            .filter(|output| output.script_pubkey.is_p2tr())
+            .filter_map(|output| XOnlyPublicKey::from_slice(&output.script_pubkey.as_bytes()[2..34]).ok())
+            .collect();
+        self.scan(secp, &tweak_data, &outputs)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitcoin::consensus::deserialize;
+    use bitcoin::hashes::hex::FromHex;
+    use bitcoin::key::TapTweak;
+    use bitcoin::secp256k1::{Keypair, Message};
+    use bitcoin::script::{Builder, PushBytesBuf};
+    use bitcoin::{absolute, transaction, Amount, Txid, Witness};
+
+    fn secret(hex: &str) -> SecretKey {
+        SecretKey::from_str(hex).unwrap()
+    }
+
+    fn outpoint(txid: &str, vout: u32) -> OutPoint {
+        OutPoint::new(Txid::from_str(txid).unwrap(), vout)
+    }
+
+    const VECTORS: &str = include_str!("../tests/data/bip352_send_and_receive_test_vectors.json");
+
+    fn hex_field(value: &serde_json::Value) -> Vec<u8> {
+        Vec::from_hex(value.as_str().unwrap()).unwrap()
+    }
+
+    /// An input of a vector and the output it spends
+    fn vector_input(vin: &serde_json::Value) -> (TxIn, TxOut) {
+        let witness = hex_field(&vin["txinwitness"]);
+        let input = TxIn {
+            previous_output: outpoint(vin["txid"].as_str().unwrap(), vin["vout"].as_u64().unwrap() as u32),
+            script_sig: ScriptBuf::from_bytes(hex_field(&vin["scriptSig"])),
+            witness: if witness.is_empty() { Witness::new() } else { deserialize(&witness).unwrap() },
+            ..Default::default()
+        };
+        let prevout = TxOut { value: Amount::ZERO, script_pubkey: ScriptBuf::from_bytes(hex_field(&vin["prevout"]["scriptPubKey"]["hex"])) };
+        (input, prevout)
+    }
+
+    fn check_sending(secp: &Secp256k1<bitcoin::secp256k1::All>, comment: &str, sending: &serde_json::Value) {
+        let given = &sending["given"];
+        let mut inputs = Vec::new();
+        let mut outpoints = Vec::new();
+        for vin in given["vin"].as_array().unwrap() {
+            let (input, prevout) = vector_input(vin);
+            outpoints.push(input.previous_output);
+            if input_public_key(&input, &prevout).is_some() {
+                inputs.push(SenderInput {
+                    outpoint: input.previous_output,
+                    secret_key: secret(vin["private_key"].as_str().unwrap()),
+                    taproot: prevout.script_pubkey.is_p2tr(),
+                });
+            }
+        }
+        let mut recipients = Vec::new();
+        for recipient in given["recipients"].as_array().unwrap() {
+            let address: SilentPaymentAddress = recipient["address"].as_str().unwrap().parse().unwrap();
+            let count = recipient.get("count").and_then(|count| count.as_u64()).unwrap_or(1);
+            recipients.extend(std::iter::repeat_n(address, count as usize));
+        }
+
+        let mut outputs: Vec<String> = match derive_outputs(secp, &inputs, &outpoints, &recipients) {
+            Ok(keys) => keys.iter().map(|key| key.to_string()).collect(),
+            Err(SilentPaymentError::NoEligibleInputs | SilentPaymentError::ZeroInputKeys | SilentPaymentError::TooManyOutputs) => Vec::new(),
+            Err(e) => panic!("{}: {}", comment, e),
+        };
+        outputs.sort();
+        // Recipients sharing a scan key may be paid in any order
+        let matches = sending["expected"]["outputs"].as_array().unwrap().iter().any(|candidate| {
+            let mut candidate: Vec<String> = candidate.as_array().unwrap().iter()
+                .map(|key| key.as_str().unwrap().to_string())
+                .collect();
+            candidate.sort();
+            candidate == outputs
+        });
+        assert!(matches, "{}: sent to {:?}", comment, outputs);
+    }
+
+    fn check_receiving(secp: &Secp256k1<bitcoin::secp256k1::All>, comment: &str, receiving: &serde_json::Value) {
+        let (given, expected) = (&receiving["given"], &receiving["expected"]);
+        let (inputs, prevouts): (Vec<TxIn>, Vec<TxOut>) = given["vin"].as_array().unwrap().iter().map(vector_input).unzip();
+        let tx = Transaction {
+            version: transaction::Version::TWO,
+            lock_time: absolute::LockTime::ZERO,
+            input: inputs,
+            output: given["outputs"].as_array().unwrap().iter()
+                // Not every output key is a valid point
+                .map(|key| {
+                    let script_pubkey = ScriptBuf::from_bytes([0x51, 0x20].into_iter().chain(hex_field(key)).collect());
+                    TxOut { value: Amount::from_sat(1_000), script_pubkey }
+                })
+                .collect(),
+        };
+
+        let scan_key = secret(given["key_material"]["scan_priv_key"].as_str().unwrap());
+        let spend_key = secret(given["key_material"]["spend_priv_key"].as_str().unwrap());
+        let mut receiver = Receiver::new(secp, scan_key, spend_key.public_key(secp), Network::Bitcoin).unwrap();
+        let mut addresses = vec![receiver.address(secp).to_string()];
+        for label in given["labels"].as_array().unwrap() {
+            addresses.push(receiver.labelled_address(secp, label.as_u64().unwrap() as u32).unwrap().to_string());
+        }
+        let expected_addresses: Vec<&str> = expected["addresses"].as_array().unwrap().iter().map(|a| a.as_str().unwrap()).collect();
+        assert_eq!(addresses, expected_addresses, "{}", comment);
+
+        let tweak = tweak_data(secp, &tx, &prevouts).unwrap();
+        assert_eq!(tweak.map(|tweak| tweak.to_string()).as_deref(), expected["tweak"].as_str(), "{}", comment);
+        if let Some(tweak) = tweak {
+            let shared_secret = tweak.mul_tweak(secp, &Scalar::from(scan_key)).unwrap();
+            assert_eq!(Some(shared_secret.to_string().as_str()), expected["shared_secret"].as_str(), "{}", comment);
+        }
+
+        let found = receiver.scan_transaction(secp, &tx, &prevouts).unwrap();
+        if let Some(count) = expected["n_outputs"].as_u64() {
+            assert_eq!(found.len() as u64, count, "{}", comment);
+            return;
+        }
+        let message = Message::from_digest(sha256::Hash::hash(b"message").to_byte_array());
+        let aux_rand = sha256::Hash::hash(b"random auxiliary data").to_byte_array();
+        let mut found: Vec<(String, String, String)> = found.iter().map(|output| {
+            let keypair = Keypair::from_secret_key(secp, &output.secret_key(&spend_key).unwrap());
+            assert_eq!(keypair.x_only_public_key().0, output.output_key, "{}", comment);
+            let signature = secp.sign_schnorr_with_aux_rand(&message, &keypair, &aux_rand);
+            (output.output_key.to_string(), output.tweak.display_secret().to_string(), signature.to_string())
+        }).collect();
+        let mut outputs: Vec<(String, String, String)> = expected["outputs"].as_array().unwrap().iter().map(|output| {
+            let field = |name: &str| output[name].as_str().unwrap().to_string();
+            (field("pub_key"), field("priv_key_tweak"), field("signature"))
+        }).collect();
+        found.sort();
+        outputs.sort();
+        assert_eq!(found, outputs, "{}", comment);
+    }
+
+    /// Keys of the receiver in the BIP352 test vectors
+    fn receiver(secp: &Secp256k1<bitcoin::secp256k1::All>) -> (Receiver, SecretKey) {
+        let spend_key = secret("9d6ad855ce3417ef84e836892e5a56392bfba05fa5d97ccea30e266f540e08b3");
+        let scan_key = secret("0f694e068028a717f8af6b9411f9a133dd3565258714cc226594b34db90c1f2c");
+        (Receiver::new(secp, scan_key, spend_key.public_key(secp), Network::Bitcoin).unwrap(), spend_key)
+    }
+
+    /// The two P2PKH inputs of the vectors' simple sends
+    fn sender_inputs() -> Vec<SenderInput> {
+        vec![
+            SenderInput {
+                outpoint: outpoint("f4184fc596403b9d638783cf57adfe4c75c605f6356fbc91338530e9831e9e16", 0),
+                secret_key: secret("eadc78165ff1f8ea94ad7cfdc54990738a4c53f6e0507b42154201b8e5dff3b1"),
+                taproot: false,
+            },
+            SenderInput {
+                outpoint: outpoint("a1075db55d416d3ca199f55b6084e2115b9345e16c5cf302fc80e9d5fbf5d48d", 0),
+                secret_key: secret("93f5ed907ad5b2bdbbdcb5d9116ebc0a4e1f92f910d5260237fa45a9408aad16"),
+                taproot: false,
+            },
+        ]
+    }
+
+    /// A transaction spending `inputs`, as P2PKH or taproot key path
+    /// spends, and paying `outputs`
+    fn spend(
+        secp: &Secp256k1<bitcoin::secp256k1::All>,
+        inputs: &[SenderInput],
+        outputs: &[XOnlyPublicKey],
+    ) -> (Transaction, Vec<TxOut>) {
+        let mut tx_inputs = Vec::new();
+        let mut prevouts = Vec::new();
+        for input in inputs {
+            let (script_sig, witness, script_pubkey) = if input.taproot {
+                let output_key = input.secret_key.x_only_public_key(secp).0.dangerous_assume_tweaked();
+                (ScriptBuf::new(), Witness::from_slice(&[vec![1u8; 64]]), ScriptBuf::new_p2tr_tweaked(output_key))
+            } else {
+                let public_key = bitcoin::PublicKey::new(input.secret_key.public_key(secp));
+                let signature = PushBytesBuf::try_from(vec![0x30; 71]).unwrap();
+                let script_sig = Builder::new().push_slice(signature).push_key(&public_key).into_script();
+                (script_sig, Witness::new(), ScriptBuf::new_p2pkh(&public_key.pubkey_hash()))
+            };
+            tx_inputs.push(TxIn { previous_output: input.outpoint, script_sig, witness, ..Default::default() });
+            prevouts.push(TxOut { value: Amount::from_sat(100_000), script_pubkey });
+        }
+        let tx = Transaction {
+            version: transaction::Version::TWO,
+            lock_time: absolute::LockTime::ZERO,
+            input: tx_inputs,
+            output: outputs.iter()
+                .map(|key| TxOut { value: Amount::from_sat(50_000), script_pubkey: ScriptBuf::new_p2tr_tweaked(key.dangerous_assume_tweaked()) })
+                .collect(),
+        };
+        (tx, prevouts)
+    }
+
+    #[test]
+    fn test_address_encoding() {
+        let secp = Secp256k1::new();
+        let (receiver, _) = receiver(&secp);
+        let encoded = "sp1qqgste7k9hx0qftg6qmwlkqtwuy6cycyavzmzj85c6qdfhjdpdjtdgqjuexzk6murw56suy3e0rd2cgqvycxttddwsvgxe2usfpxumr70xc9pkqwv";
+        let address: SilentPaymentAddress = encoded.parse().unwrap();
+        assert_eq!(address, receiver.address(&secp));
+        assert_eq!(address.to_string(), encoded);
+        assert!(address.require_network(Network::Signet).is_err());
+
+        let testnet = SilentPaymentAddress::new(address.scan_key(), address.spend_key(), Network::Testnet);
+        assert!(testnet.to_string().starts_with("tsp1q"));
+        assert_eq!(testnet.to_string().parse::<SilentPaymentAddress>().unwrap(), testnet);
+        assert!(testnet.is_valid_for(Network::Regtest));
+
+        // A single altered character breaks the checksum
+        let mut altered = encoded.to_string();
+        altered.replace_range(20..21, "z");
+        assert!(altered.parse::<SilentPaymentAddress>().is_err());
+    }
+
+    /// Every case of BIP352's `send_and_receive_test_vectors.json`
+    #[test]
+    fn test_bip352_vectors() {
+        let secp = Secp256k1::new();
+        let vectors: serde_json::Value = serde_json::from_str(VECTORS).unwrap();
+        for case in vectors.as_array().unwrap() {
+            let comment = case["comment"].as_str().unwrap();
+            for sending in case["sending"].as_array().unwrap() {
+                check_sending(&secp, comment, sending);
+            }
+            for receiving in case["receiving"].as_array().unwrap() {
+                check_receiving(&secp, comment, receiving);
+            }
+        }
+    }
+
+    #[test]
+    fn test_labels_and_multiple_outputs() {
+        let secp = Secp256k1::new();
+        let (mut receiver, spend_key) = receiver(&secp);
+        let plain = receiver.address(&secp);
+        let donations = receiver.labelled_address(&secp, 7).unwrap();
+        let change = receiver.labelled_address(&secp, CHANGE_LABEL).unwrap();
+        assert_ne!(donations.spend_key(), plain.spend_key());
+        assert_eq!(donations.scan_key(), plain.scan_key());
+        assert_eq!(receiver.labels().collect::<Vec<_>>(), vec![CHANGE_LABEL, 7]);
+
+        let inputs = sender_inputs();
+        let outpoints: Vec<_> = inputs.iter().map(|input| input.outpoint).collect();
+        let outputs = derive_outputs(&secp, &inputs, &outpoints, &[plain, donations, change, plain]).unwrap();
+        assert_eq!(outputs.len(), 4);
+        assert_ne!(outputs[0], outputs[3]);
+
+        let (mut tx, prevouts) = spend(&secp, &inputs, &outputs);
+        // An unrelated output is ignored
+        tx.output.push(TxOut { value: Amount::from_sat(1_000), script_pubkey: ScriptBuf::new_op_return([1u8; 4]) });
+        let found = receiver.scan_transaction(&secp, &tx, &prevouts).unwrap();
+        assert_eq!(found.len(), 4);
+        for output in &found {
+            assert!(outputs.contains(&output.output_key));
+            assert_eq!(output.secret_key(&spend_key).unwrap().x_only_public_key(&secp).0, output.output_key);
+        }
+        let mut labels: Vec<_> = found.iter().map(|output| output.label).collect();
+        labels.sort();
+        assert_eq!(labels, vec![None, None, Some(CHANGE_LABEL), Some(7)]);
+
+        // Light clients scan with the tweak data instead of the prevouts
+        let tweak = tweak_data(&secp, &tx, &prevouts).unwrap().unwrap();
+        assert_eq!(receiver.scan(&secp, &tweak, &outputs).unwrap(), found);
+
+        // Someone else's receiver finds nothing
+        let stranger = Receiver::new(&secp, secret(&"11".repeat(32)), spend_key.public_key(&secp), Network::Bitcoin).unwrap();
+        assert!(stranger.scan_transaction(&secp, &tx, &prevouts).unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_input_eligibility() {
+        let secp = Secp256k1::new();
+        let key = secret(&"22".repeat(32));
+        let (x_only, parity) = key.x_only_public_key(&secp);
+        let tweaked = x_only.dangerous_assume_tweaked();
+
+        // Key path taproot keys count with even Y, whatever the key's parity
+        let key_path = TxIn { witness: Witness::from_slice(&[vec![1u8; 64]]), ..Default::default() };
+        let p2tr = TxOut { value: Amount::ZERO, script_pubkey: ScriptBuf::new_p2tr_tweaked(tweaked) };
+        let counted = input_public_key(&key_path, &p2tr).unwrap();
+        assert_eq!(counted, x_only.public_key(bitcoin::secp256k1::Parity::Even));
+        assert_eq!(counted == key.public_key(&secp), parity == bitcoin::secp256k1::Parity::Even);
+
+        // Script path spends with the NUMS internal key do not count
+        let mut control_block = vec![0xc0];
+        control_block.extend_from_slice(&NUMS_H);
+        let nums = TxIn { witness: Witness::from_slice(&[vec![1u8; 64], vec![0x51], control_block]), ..Default::default() };
+        assert!(input_public_key(&nums, &p2tr).is_none());
+
+        let public_key = bitcoin::PublicKey::new(key.public_key(&secp));
+        let p2wpkh = TxOut { value: Amount::ZERO, script_pubkey: ScriptBuf::new_p2wpkh(&public_key.wpubkey_hash().unwrap()) };
+        let input = TxIn { witness: Witness::from_slice(&[vec![0x30; 71], public_key.to_bytes()]), ..Default::default() };
+        assert_eq!(input_public_key(&input, &p2wpkh), Some(public_key.inner));
+
+        // Uncompressed keys are not eligible
+        let uncompressed = bitcoin::PublicKey::new_uncompressed(key.public_key(&secp));
+        let p2pkh = TxOut { value: Amount::ZERO, script_pubkey: ScriptBuf::new_p2pkh(&uncompressed.pubkey_hash()) };
+        let input = TxIn { script_sig: Builder::new().push_slice([0x30; 71]).push_key(&uncompressed).into_script(), ..Default::default() };
+        assert!(input_public_key(&input, &p2pkh).is_none());
+
+        // Spending a future segwit version opts the whole transaction out
+        let inputs = sender_inputs();
+        let (tx, mut prevouts) = spend(&secp, &inputs, &[x_only]);
+        assert!(tweak_data(&secp, &tx, &prevouts).unwrap().is_some());
+        prevouts[1].script_pubkey = ScriptBuf::from_bytes(Vec::from_hex("5220").unwrap().into_iter().chain([0u8; 32]).collect());
+        assert!(tweak_data(&secp, &tx, &prevouts).unwrap().is_none());
+    }
+}
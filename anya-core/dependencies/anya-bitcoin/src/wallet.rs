@@ -11,14 +11,24 @@
 use std::path::{Path, PathBuf};
 
 use anyhow::{anyhow, Result};
-use bitcoin::{Address, Network, Transaction, Txid, Script, ScriptBuf, BlockHash};
+use async_trait::async_trait;
+use bitcoin::{Address, Network, OutPoint, Transaction, TxOut, Txid, Script, ScriptBuf, BlockHash};
+use bitcoin::bip32::{ChildNumber, DerivationPath, Xpriv};
 use bitcoin::consensus::encode;
-use bitcoin::psbt::PartiallySignedTransaction as Psbt;
+use bitcoin::hashes::Hash;
+use bitcoin::key::{Keypair, TapTweak};
+use bitcoin::psbt::Psbt;
+use bitcoin::sighash::{Prevouts, SighashCache, TapSighashType};
+use bitcoin::{taproot, Witness};
+use bdk::bitcoin::hashes::Hash as _;
+use bdk::bitcoin::psbt::PartiallySignedTransaction as BdkPsbt;
+use bdk::electrum_client::ElectrumApi;
+use bitcoincore_rpc::RpcApi;
 use bdk::{
     wallet::AddressIndex,
-    Wallet, SyncOptions, FeeRate,
+    Wallet, SyncOptions, FeeRate, SignOptions,
     database::SqliteDatabase,
-    blockchain::{Blockchain, ElectrumBlockchain},
+    blockchain::{Blockchain, ElectrumBlockchain, GetTx},
     electrum_client::Client,
     keys::{
         DerivableKey,
@@ -29,11 +39,14 @@
 };
 use bdk_macros::bdkwallet;
 use miniscript::Descriptor;
-use secp256k1::{rand, Secp256k1};
+use bitcoin::secp256k1::{rand, All, Message, Secp256k1, SecretKey};
 use serde::{Serialize, Deserialize};
 use tokio::sync::Mutex;
 use tracing::{info, warn, error, debug};
 
+use crate::payjoin::{self, PayjoinError, PayjoinWallet, Params};
+use crate::silent_payments::{self, Receiver, ReceivedOutput, SenderInput, SilentPaymentAddress};
+
 /// Configuration for a Bitcoin wallet
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct WalletConfig {
@@ -71,6 +84,88 @@ pub struct BitcoinWallet {
     
     /// Electrum blockchain client
     blockchain: Arc<ElectrumBlockchain>,
+
+    /// Master key, for the private keys of inputs paying silent payments
+    master_key: Xpriv,
+
+    /// Scanning side of the wallet's silent payment address
+    silent_payments: Mutex<Receiver>,
+
+    /// Silent payment outputs found so far
+    silent_payment_utxos: Mutex<Vec<SilentPaymentUtxo>>,
+
+    /// Bitcoin Core node checking payjoin originals, see [`Self::with_node`]
+    node: Option<Arc<bitcoincore_rpc::Client>>,
+}
+
+/// A silent payment output received by the wallet
+///
+/// These outputs are not derived from the wallet's descriptors, so they are
+/// tracked here rather than by BDK.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SilentPaymentUtxo {
+    pub outpoint: OutPoint,
+    pub txout: TxOut,
+    pub received: ReceivedOutput,
+}
+
+/// Silent payment labels and outputs of a wallet, as saved next to its
+/// database
+///
+/// The tweaks of the outputs are stored in the clear; without the spend
+/// key they cannot spend anything.
+#[derive(Debug, Default, Serialize, Deserialize)]
+struct SilentPaymentState {
+    labels: Vec<u32>,
+    utxos: Vec<SilentPaymentUtxo>,
+}
+
+fn silent_payments_path(config: &WalletConfig) -> PathBuf {
+    config.database_path.with_extension("silent-payments.json")
+}
+
+// BDK 0.30 is built on `bitcoin` 0.30, so its types cross over to the 0.32
+// ones used here through their serialization.
+
+fn psbt_from_bdk(psbt: &BdkPsbt) -> Result<Psbt> {
+    Ok(Psbt::deserialize(&psbt.serialize())?)
+}
+
+fn psbt_to_bdk(psbt: &Psbt) -> Result<BdkPsbt> {
+    Ok(BdkPsbt::deserialize(&psbt.serialize())?)
+}
+
+fn tx_from_bdk(tx: &bdk::bitcoin::Transaction) -> Result<Transaction> {
+    Ok(encode::deserialize(&bdk::bitcoin::consensus::encode::serialize(tx))?)
+}
+
+fn tx_to_bdk(tx: &Transaction) -> Result<bdk::bitcoin::Transaction> {
+    Ok(bdk::bitcoin::consensus::encode::deserialize(&encode::serialize(tx))?)
+}
+
+fn script_to_bdk(script: &Script) -> bdk::bitcoin::ScriptBuf {
+    bdk::bitcoin::ScriptBuf::from_bytes(script.to_bytes())
+}
+
+fn txid_to_bdk(txid: &Txid) -> bdk::bitcoin::Txid {
+    bdk::bitcoin::Txid::from_byte_array(txid.to_byte_array())
+}
+
+/// BIP352 scan and spend keys, at `m/352'/coin'/0'/1'/0` and
+/// `m/352'/coin'/0'/0'/0`
+fn silent_payment_keys(secp: &Secp256k1<All>, master: &Xpriv, network: Network) -> Result<(SecretKey, SecretKey)> {
+    let coin_type = if network == Network::Bitcoin { 0 } else { 1 };
+    let derive = |branch: u32| -> Result<SecretKey> {
+        let path: DerivationPath = vec![
+            ChildNumber::from_hardened_idx(352)?,
+            ChildNumber::from_hardened_idx(coin_type)?,
+            ChildNumber::from_hardened_idx(0)?,
+            ChildNumber::from_hardened_idx(branch)?,
+            ChildNumber::from_normal_idx(0)?,
+        ].into();
+        Ok(master.derive_priv(secp, &path)?.private_key)
+    };
+    Ok((derive(1)?, derive(0)?))
 }
 
 impl BitcoinWallet {
@@ -90,6 +185,17 @@ pub async fn new(config: WalletConfig) -> Result<Self> {
         // Derive keys from mnemonic
         let extended_key = mnemonic.into_extended_key()?;
         let xprv = extended_key.into_xprv(config.network)?;
+        let master_key = Xpriv::from_str(&xprv.to_string())?;
+        let (scan_key, spend_key) = silent_payment_keys(&secp, &master_key, config.network)?;
+        let mut receiver = Receiver::new(&secp, scan_key, spend_key.public_key(&secp), config.network)?;
+        let silent_payment_state: SilentPaymentState = match std::fs::read(silent_payments_path(&config)) {
+            Ok(bytes) => serde_json::from_slice(&bytes)?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => SilentPaymentState::default(),
+            Err(e) => return Err(e.into()),
+        };
+        for label in &silent_payment_state.labels {
+            receiver.add_label(&secp, *label)?;
+        }
         
         // Create descriptor based on configuration
         let descriptor = if config.use_taproot {
@@ -121,6 +227,10 @@ pub async fn new(config: WalletConfig) -> Result<Self> {
             inner: Mutex::new(wallet),
             config: config.clone(),
             blockchain: Arc::new(blockchain),
+            master_key,
+            silent_payments: Mutex::new(receiver),
+            silent_payment_utxos: Mutex::new(silent_payment_state.utxos),
+            node: None,
         };
         
         // Initial sync with the blockchain
@@ -129,6 +239,15 @@ pub async fn new(config: WalletConfig) -> Result<Self> {
         Ok(bitcoin_wallet)
     }
     
+    /// Connects the wallet to a Bitcoin Core node
+    ///
+    /// Electrum cannot tell whether a transaction would be accepted to the
+    /// mempool, so without a node the wallet does not receive payjoins.
+    pub fn with_node(mut self, url: &str, auth: bitcoincore_rpc::Auth) -> Result<Self> {
+        self.node = Some(Arc::new(bitcoincore_rpc::Client::new(url, auth)?));
+        Ok(self)
+    }
+
     /// Sync the wallet with the blockchain
     pub async fn sync(&self) -> Result<()> {
         let mut wallet = self.inner.lock().await;
@@ -150,10 +269,19 @@ pub async fn get_address(&self, address_index: AddressIndex) -> Result<AddressIn
         Ok(address)
     }
     
-    /// Get the current balance
+    /// Get the current balance, unspent silent payment outputs included
     pub async fn get_balance(&self) -> Result<bdk::Balance> {
-        let wallet = self.inner.lock().await;
-        let balance = wallet.get_balance()?;
+        let mut balance = {
+            let wallet = self.inner.lock().await;
+            wallet.get_balance()?
+        };
+        for (utxo, confirmed) in self.unspent_silent_payments().await? {
+            if confirmed {
+                balance.confirmed += utxo.txout.value.to_sat();
+            } else {
+                balance.untrusted_pending += utxo.txout.value.to_sat();
+            }
+        }
         Ok(balance)
     }
     
@@ -463,6 +591,275 @@ pub async fn get_transaction_info(&self, txid: &Txid) -> Result<TransactionInfo>
         Ok(info)
     }
     
+    /// Static silent payment address of the wallet, optionally labelled
+    /// so payments to it can be told apart
+    pub async fn silent_payment_address(&self, label: Option<u32>) -> Result<SilentPaymentAddress> {
+        let secp = Secp256k1::new();
+        let mut receiver = self.silent_payments.lock().await;
+        let Some(label) = label else {
+            return Ok(receiver.address(&secp));
+        };
+        let known = receiver.labels().any(|watched| watched == label);
+        let address = receiver.labelled_address(&secp, label)?;
+        if !known {
+            let utxos = self.silent_payment_utxos.lock().await;
+            self.save_silent_payments(&receiver, &utxos)?;
+        }
+        Ok(address)
+    }
+
+    /// Pay a silent payment address
+    pub async fn send_silent_payment(
+        &self,
+        address: &str,
+        amount_sats: u64,
+        fee_rate: f32,
+    ) -> Result<Txid> {
+        let recipient = SilentPaymentAddress::from_str(address)?
+            .require_network(self.config.network)?;
+        let secp = Secp256k1::new();
+
+        // The output key depends on the inputs, so coin selection runs
+        // against a placeholder taproot output of the same size
+        let placeholder = ScriptBuf::new_p2tr_tweaked(
+            recipient.spend_key().x_only_public_key().0.dangerous_assume_tweaked()
+        );
+
+        let wallet = self.inner.lock().await;
+        let mut builder = wallet.build_tx();
+        builder.add_recipient(script_to_bdk(&placeholder), amount_sats)
+            .enable_rbf()
+            .fee_rate(FeeRate::from_sat_per_vb(fee_rate));
+        let (psbt, _details) = builder.finish()?;
+
+        let mut psbt = psbt_from_bdk(&psbt)?;
+        let inputs = self.silent_payment_inputs(&secp, &psbt)?;
+        let outpoints: Vec<OutPoint> = psbt.unsigned_tx.input.iter().map(|input| input.previous_output).collect();
+        let output_key = silent_payments::derive_outputs(&secp, &inputs, &outpoints, &[recipient])?[0];
+        let output = psbt.unsigned_tx.output.iter_mut()
+            .find(|output| output.script_pubkey == placeholder)
+            .ok_or_else(|| anyhow!("Silent payment output missing from the transaction"))?;
+        output.script_pubkey = ScriptBuf::new_p2tr_tweaked(output_key.dangerous_assume_tweaked());
+
+        // Sign the transaction
+        let mut psbt = psbt_to_bdk(&psbt)?;
+        wallet.sign(&mut psbt, SignOptions::default())?;
+
+        // Broadcast the transaction
+        let raw_tx = psbt.extract_tx();
+        self.blockchain.broadcast(&raw_tx)?;
+        let txid = tx_from_bdk(&raw_tx)?.compute_txid();
+
+        info!("Silent payment sent: {}", txid);
+        Ok(txid)
+    }
+
+    /// Private keys of a PSBT's inputs, for the sender's side of BIP352
+    fn silent_payment_inputs(&self, secp: &Secp256k1<All>, psbt: &Psbt) -> Result<Vec<SenderInput>> {
+        psbt.inputs.iter().zip(&psbt.unsigned_tx.input).enumerate().map(|(index, (input, txin))| {
+            if let Some((_, (_, (_, path)))) = input.tap_key_origins.iter().next() {
+                // Key path spends sign with the tweaked output key
+                let internal = self.master_key.derive_priv(secp, path)?.private_key;
+                let tweaked = Keypair::from_secret_key(secp, &internal).tap_tweak(secp, input.tap_merkle_root);
+                Ok(SenderInput { outpoint: txin.previous_output, secret_key: tweaked.to_keypair().secret_key(), taproot: true })
+            } else if let Some((_, (_, path))) = input.bip32_derivation.iter().next() {
+                let secret_key = self.master_key.derive_priv(secp, path)?.private_key;
+                Ok(SenderInput { outpoint: txin.previous_output, secret_key, taproot: false })
+            } else {
+                Err(anyhow!("Input {} has no key origin in this wallet", index))
+            }
+        }).collect()
+    }
+
+    /// Scan a transaction for silent payments to this wallet
+    ///
+    /// The outputs its inputs spend are fetched from the Electrum server.
+    /// Anything found is remembered with the wallet's other silent payment
+    /// outputs.
+    pub async fn scan_silent_payments(&self, tx: &Transaction) -> Result<Vec<SilentPaymentUtxo>> {
+        let mut prevouts = Vec::with_capacity(tx.input.len());
+        for input in &tx.input {
+            let previous = self.blockchain.get_tx(&txid_to_bdk(&input.previous_output.txid))?
+                .ok_or_else(|| anyhow!("Transaction not found: {}", input.previous_output.txid))?;
+            let previous = tx_from_bdk(&previous)?;
+            let prevout = previous.output.get(input.previous_output.vout as usize)
+                .ok_or_else(|| anyhow!("Output not found: {}", input.previous_output))?;
+            prevouts.push(prevout.clone());
+        }
+
+        let secp = Secp256k1::new();
+        let receiver = self.silent_payments.lock().await;
+        let found = receiver.scan_transaction(&secp, tx, &prevouts)?;
+        let txid = tx.compute_txid();
+        let utxos: Vec<SilentPaymentUtxo> = found.into_iter().filter_map(|received| {
+            let script_pubkey = ScriptBuf::new_p2tr_tweaked(received.output_key.dangerous_assume_tweaked());
+            tx.output.iter().enumerate()
+                .find(|(_, output)| output.script_pubkey == script_pubkey)
+                .map(|(vout, output)| SilentPaymentUtxo {
+                    outpoint: OutPoint::new(txid, vout as u32),
+                    txout: output.clone(),
+                    received,
+                })
+        }).collect();
+
+        let mut known = self.silent_payment_utxos.lock().await;
+        let count = known.len();
+        for utxo in &utxos {
+            if !known.iter().any(|existing| existing.outpoint == utxo.outpoint) {
+                known.push(utxo.clone());
+            }
+        }
+        if known.len() > count {
+            self.save_silent_payments(&receiver, &known)?;
+        }
+        if !utxos.is_empty() {
+            info!("Found {} silent payment outputs in {}", utxos.len(), txid);
+        }
+        Ok(utxos)
+    }
+
+    /// Silent payment outputs found by [`Self::scan_silent_payments`]
+    pub async fn silent_payment_utxos(&self) -> Vec<SilentPaymentUtxo> {
+        self.silent_payment_utxos.lock().await.clone()
+    }
+
+    /// Silent payment outputs the Electrum server still lists as unspent,
+    /// with whether each is confirmed
+    async fn unspent_silent_payments(&self) -> Result<Vec<(SilentPaymentUtxo, bool)>> {
+        let utxos = self.silent_payment_utxos.lock().await.clone();
+        let mut unspent = Vec::new();
+        for utxo in utxos {
+            let listed = self.blockchain.script_list_unspent(&script_to_bdk(&utxo.txout.script_pubkey))?;
+            let entry = listed.iter().find(|entry| {
+                entry.tx_hash.to_byte_array() == utxo.outpoint.txid.to_byte_array()
+                    && entry.tx_pos as u32 == utxo.outpoint.vout
+            });
+            if let Some(entry) = entry {
+                let confirmed = entry.height > 0;
+                unspent.push((utxo, confirmed));
+            }
+        }
+        Ok(unspent)
+    }
+
+    /// Spend every unspent silent payment output to `address`, less the fee
+    ///
+    /// BDK cannot select these outputs, as the wallet's descriptors do not
+    /// cover them; sweeping them to one of the wallet's own addresses
+    /// brings them under its control.
+    pub async fn sweep_silent_payments(&self, address: &str, fee_rate: f32) -> Result<Txid> {
+        let recipient = Address::from_str(address)?
+            .require_network(self.config.network)?;
+        let utxos: Vec<SilentPaymentUtxo> = self.unspent_silent_payments().await?
+            .into_iter()
+            .map(|(utxo, _)| utxo)
+            .collect();
+        if utxos.is_empty() {
+            return Err(anyhow!("No unspent silent payment outputs"));
+        }
+
+        let secp = Secp256k1::new();
+        let (_, spend_key) = silent_payment_keys(&secp, &self.master_key, self.config.network)?;
+        let tx = sweep_transaction(&secp, &utxos, &spend_key, recipient.script_pubkey(), fee_rate)?;
+        self.blockchain.broadcast(&tx_to_bdk(&tx)?)?;
+        let txid = tx.compute_txid();
+
+        let receiver = self.silent_payments.lock().await;
+        let mut known = self.silent_payment_utxos.lock().await;
+        known.retain(|utxo| !tx.input.iter().any(|input| input.previous_output == utxo.outpoint));
+        self.save_silent_payments(&receiver, &known)?;
+
+        info!("Swept {} silent payment outputs: {}", utxos.len(), txid);
+        Ok(txid)
+    }
+
+    /// Writes the watched labels and the found outputs next to the database
+    fn save_silent_payments(&self, receiver: &Receiver, utxos: &[SilentPaymentUtxo]) -> Result<()> {
+        let state = SilentPaymentState { labels: receiver.labels().collect(), utxos: utxos.to_vec() };
+        std::fs::write(silent_payments_path(&self.config), serde_json::to_vec_pretty(&state)?)?;
+        Ok(())
+    }
+
+    /// Pay `address` through a payjoin with its receiver's BIP78 endpoint
+    ///
+    /// The receiver may take up to `max_fee_contribution` sats from the
+    /// change output towards the fee of the input it adds. If the payjoin
+    /// fails for any reason, the original transaction is broadcast instead.
+    pub async fn send_payjoin(
+        &self,
+        address: &str,
+        amount_sats: u64,
+        fee_rate: f32,
+        endpoint: &str,
+        max_fee_contribution: u64,
+    ) -> Result<Txid> {
+        let sender = self.payjoin_sender(address, amount_sats, fee_rate, max_fee_contribution).await?;
+        let proposal = sender.send(endpoint).await;
+        self.finish_payjoin(&sender, proposal).await
+    }
+
+    /// Signed original PSBT of a payjoin, with the change as the output the
+    /// receiver may take its fee from
+    async fn payjoin_sender(
+        &self,
+        address: &str,
+        amount_sats: u64,
+        fee_rate: f32,
+        max_fee_contribution: u64,
+    ) -> Result<payjoin::Sender> {
+        let recipient = Address::from_str(address)?
+            .require_network(self.config.network)?;
+
+        let payee = script_to_bdk(&recipient.script_pubkey());
+
+        let wallet = self.inner.lock().await;
+        let mut builder = wallet.build_tx();
+        builder.add_recipient(payee.clone(), amount_sats)
+            .enable_rbf()
+            .fee_rate(FeeRate::from_sat_per_vb(fee_rate));
+        let (mut psbt, _details) = builder.finish()?;
+        wallet.sign(&mut psbt, SignOptions::default())?;
+
+        let mut change = None;
+        for (index, output) in psbt.unsigned_tx.output.iter().enumerate() {
+            if output.script_pubkey != payee && wallet.is_mine(&output.script_pubkey)? {
+                change = Some(index);
+            }
+        }
+        let params = Params {
+            additional_fee_output_index: change,
+            max_additional_fee_contribution: bitcoin::Amount::from_sat(max_fee_contribution),
+            disable_output_substitution: false,
+            min_fee_rate: Some(bitcoin::FeeRate::from_sat_per_kwu((fee_rate * 250.0).ceil() as u64)),
+        };
+        Ok(payjoin::Sender::new(psbt_from_bdk(&psbt)?, recipient.script_pubkey(), params)?)
+    }
+
+    /// Signs and broadcasts a checked proposal, or the original transaction
+    /// if there is none
+    async fn finish_payjoin(&self, sender: &payjoin::Sender, proposal: payjoin::Result<Psbt>) -> Result<Txid> {
+        let tx = match proposal {
+            Ok(proposal) => {
+                let wallet = self.inner.lock().await;
+                let mut proposal = psbt_to_bdk(&proposal)?;
+                // The receiver's inputs only carry their witness UTXO
+                let options = SignOptions { trust_witness_utxo: true, ..Default::default() };
+                wallet.sign(&mut proposal, options)?;
+                psbt_from_bdk(&proposal)?.extract_tx()
+                    .map_err(|e| anyhow!("Payjoin proposal cannot be extracted: {}", e))?
+            }
+            Err(e) => {
+                warn!("Payjoin failed, broadcasting the original transaction: {}", e);
+                sender.original_tx()
+            }
+        };
+
+        self.blockchain.broadcast(&tx_to_bdk(&tx)?)?;
+        let txid = tx.compute_txid();
+        info!("Payjoin transaction sent: {}", txid);
+        Ok(txid)
+    }
+
     /// Get a transaction by its ID
     pub async fn get_transaction(&self, txid: &Txid) -> Result<Transaction> {
         // First check if it's in our wallet
@@ -477,6 +874,97 @@ pub async fn get_transaction(&self, txid: &Txid) -> Result<Transaction> {
     }
 }
 
+/// Receiving side of a payjoin, contributing the wallet's own coins
+#[async_trait]
+impl PayjoinWallet for BitcoinWallet {
+    async fn is_mine(&self, script: &Script) -> payjoin::Result<bool> {
+        let wallet = self.inner.lock().await;
+        wallet.is_mine(&script_to_bdk(script)).map_err(unavailable)
+    }
+
+    async fn list_unspent(&self) -> payjoin::Result<Vec<(OutPoint, TxOut)>> {
+        let wallet = self.inner.lock().await;
+        let utxos = wallet.list_unspent().map_err(unavailable)?;
+        utxos.into_iter()
+            .map(|utxo| {
+                let outpoint = OutPoint::new(Txid::from_byte_array(utxo.outpoint.txid.to_byte_array()), utxo.outpoint.vout);
+                let txout = encode::deserialize(&bdk::bitcoin::consensus::encode::serialize(&utxo.txout)).map_err(unavailable)?;
+                Ok((outpoint, txout))
+            })
+            .collect()
+    }
+
+    async fn sign_psbt(&self, psbt: &mut Psbt) -> payjoin::Result<()> {
+        let wallet = self.inner.lock().await;
+        let mut signed = psbt_to_bdk(psbt).map_err(unavailable)?;
+        // The sender's inputs only carry their witness UTXO
+        let options = SignOptions { trust_witness_utxo: true, ..Default::default() };
+        wallet.sign(&mut signed, options).map_err(unavailable)?;
+        *psbt = psbt_from_bdk(&signed).map_err(unavailable)?;
+        Ok(())
+    }
+
+    async fn test_mempool_accept(&self, tx: &Transaction) -> payjoin::Result<bool> {
+        let node = self.node.clone()
+            .ok_or_else(|| PayjoinError::Unavailable("no node to test the original transaction with".to_string()))?;
+        let tx = tx.clone();
+        let results = tokio::task::spawn_blocking(move || node.test_mempool_accept(&[&tx]))
+            .await
+            .map_err(unavailable)?
+            .map_err(unavailable)?;
+        Ok(results.first().is_some_and(|result| result.allowed))
+    }
+}
+
+fn unavailable(error: impl std::fmt::Display) -> PayjoinError {
+    PayjoinError::Unavailable(error.to_string())
+}
+
+/// Transaction spending silent payment outputs to `script_pubkey`, signed
+/// on their key paths
+fn sweep_transaction(
+    secp: &Secp256k1<All>,
+    utxos: &[SilentPaymentUtxo],
+    spend_key: &SecretKey,
+    script_pubkey: ScriptBuf,
+    fee_rate: f32,
+) -> Result<Transaction> {
+    let prevouts: Vec<TxOut> = utxos.iter().map(|utxo| utxo.txout.clone()).collect();
+    let total: u64 = prevouts.iter().map(|prevout| prevout.value.to_sat()).sum();
+    let mut tx = Transaction {
+        version: bitcoin::transaction::Version::TWO,
+        lock_time: bitcoin::absolute::LockTime::ZERO,
+        input: utxos.iter()
+            .map(|utxo| bitcoin::TxIn {
+                previous_output: utxo.outpoint,
+                sequence: bitcoin::Sequence::ENABLE_RBF_NO_LOCKTIME,
+                // Sized as the signature, for the fee
+                witness: Witness::from_slice(&[[0u8; 64]]),
+                ..Default::default()
+            })
+            .collect(),
+        output: vec![TxOut { value: bitcoin::Amount::ZERO, script_pubkey }],
+    };
+
+    let fee = (tx.vsize() as f32 * fee_rate).ceil() as u64;
+    let value = bitcoin::Amount::from_sat(total.saturating_sub(fee));
+    if value < tx.output[0].script_pubkey.minimal_non_dust() {
+        return Err(anyhow!("Silent payment outputs of {} sats do not cover a {} sat fee", total, fee));
+    }
+    tx.output[0].value = value;
+
+    let mut cache = SighashCache::new(&mut tx);
+    for (index, utxo) in utxos.iter().enumerate() {
+        let sighash = cache.taproot_key_spend_signature_hash(index, &Prevouts::All(&prevouts), TapSighashType::Default)?;
+        // The output key is already tweaked, so the key signs as is
+        let keypair = Keypair::from_secret_key(secp, &utxo.received.secret_key(spend_key)?);
+        let signature = secp.sign_schnorr_with_rng(&Message::from_digest(sighash.to_byte_array()), &keypair, &mut rand::thread_rng());
+        let signature = taproot::Signature { signature, sighash_type: TapSighashType::Default };
+        *cache.witness_mut(index).ok_or_else(|| anyhow!("Input {} missing", index))? = Witness::p2tr_key_spend(&signature);
+    }
+    Ok(tx)
+}
+
 /// Detailed information about a Bitcoin transaction
 #[derive(Debug, Clone)]
 pub struct TransactionInfo {
@@ -548,6 +1036,58 @@ async fn test_address_generation() {
         let address_info = address.unwrap();
         assert!(address_info.address.starts_with("tb1p")); // Testnet taproot address
     }
+    #[test]
+    fn test_sweep_silent_payments() {
+        let secp = Secp256k1::new();
+        let spend_key = SecretKey::from_slice(&[0x11; 32]).unwrap();
+        let scan_key = SecretKey::from_slice(&[0x22; 32]).unwrap();
+        let receiver = Receiver::new(&secp, scan_key, spend_key.public_key(&secp), Network::Regtest).unwrap();
+
+        // A payment to the receiver from a single P2WPKH input
+        let sender_key = SecretKey::from_slice(&[0x33; 32]).unwrap();
+        let public_key = bitcoin::PublicKey::new(sender_key.public_key(&secp));
+        let p2wpkh = ScriptBuf::new_p2wpkh(&public_key.wpubkey_hash().unwrap());
+        let outpoint = OutPoint::new(Txid::from_byte_array([7; 32]), 0);
+        let input = SenderInput { outpoint, secret_key: sender_key, taproot: false };
+        let output_key = silent_payments::derive_outputs(&secp, &[input], &[outpoint], &[receiver.address(&secp)]).unwrap()[0];
+        let payment = Transaction {
+            version: bitcoin::transaction::Version::TWO,
+            lock_time: bitcoin::absolute::LockTime::ZERO,
+            input: vec![bitcoin::TxIn {
+                previous_output: outpoint,
+                witness: Witness::from_slice(&[vec![0x30; 71], public_key.to_bytes()]),
+                ..Default::default()
+            }],
+            output: vec![TxOut {
+                value: bitcoin::Amount::from_sat(50_000),
+                script_pubkey: ScriptBuf::new_p2tr_tweaked(output_key.dangerous_assume_tweaked()),
+            }],
+        };
+        let prevout = TxOut { value: bitcoin::Amount::from_sat(60_000), script_pubkey: p2wpkh.clone() };
+        let received = receiver.scan_transaction(&secp, &payment, &[prevout]).unwrap()[0];
+        let utxo = SilentPaymentUtxo { outpoint: OutPoint::new(payment.compute_txid(), 0), txout: payment.output[0].clone(), received };
+
+        // Found outputs survive a round trip through the saved state
+        let state = SilentPaymentState { labels: receiver.labels().collect(), utxos: vec![utxo.clone()] };
+        let state: SilentPaymentState = serde_json::from_slice(&serde_json::to_vec(&state).unwrap()).unwrap();
+        assert_eq!(state.labels, vec![silent_payments::CHANGE_LABEL]);
+        assert_eq!((state.utxos[0].outpoint, &state.utxos[0].txout, state.utxos[0].received), (utxo.outpoint, &utxo.txout, utxo.received));
+
+        let sweep = sweep_transaction(&secp, std::slice::from_ref(&utxo), &spend_key, p2wpkh.clone(), 2.0).unwrap();
+        assert_eq!(sweep.input[0].previous_output, utxo.outpoint);
+        assert_eq!(sweep.output[0].script_pubkey, p2wpkh);
+        assert_eq!(50_000 - sweep.output[0].value.to_sat(), 2 * sweep.vsize() as u64);
+
+        // The key path signature is valid for the output key
+        let sighash = SighashCache::new(&sweep)
+            .taproot_key_spend_signature_hash(0, &Prevouts::All(std::slice::from_ref(&utxo.txout)), TapSighashType::Default)
+            .unwrap();
+        let signature = taproot::Signature::from_slice(&sweep.input[0].witness[0]).unwrap();
+        secp.verify_schnorr(&signature.signature, &Message::from_digest(sighash.to_byte_array()), &output_key).unwrap();
+
+        // Outputs that do not cover the fee are not swept
+        assert!(sweep_transaction(&secp, &[utxo], &spend_key, p2wpkh, 1_000.0).is_err());
+    }
 }
 
 /// Generate a new mnemonic phrase
@@ -1,36 +1,609 @@
-use bitcoin::secp256k1::{Secp256k1, SecretKey};
-use bitcoin::util::bip340; // For Schnorr signatures
+//! Secure multiparty computation primitives
+//!
+//! Implements BIP327 MuSig2: n-of-n Schnorr multisignatures whose aggregate
+//! key and signature are indistinguishable from single-signer BIP340 ones,
+//! so cooperative taproot spends, channel states and DLC funding outputs
+//! look like any other key path spend.
+//!
+//! Signing takes two rounds. Each signer first publishes a public nonce,
+//! then, once every nonce is known, a partial signature. A secret nonce is
+//! consumed by the signature made with it, so it cannot be used twice.
 
-impl SecureMultipartyComputation {
-    // Add Taproot/Schnorr support
-    pub fn schnorr_musig(&self, signers: Vec<&SecretKey>) -> Result<(SecretKey, Vec<PublicKey>), SMCError> {
-        // MuSig2 implementation for Taproot
-        todo!("Implement MuSig2")
+use bitcoin::hashes::{sha256, Hash, HashEngine};
+use bitcoin::secp256k1::schnorr::Signature;
+use bitcoin::secp256k1::{
+    All, Message, Parity, PublicKey, Scalar, Secp256k1, SecretKey, XOnlyPublicKey,
+};
+use bitcoin::taproot::{TapNodeHash, TapTweakHash};
+use rand::RngCore;
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum SMCError {
+    #[error("Key combination failed: {0}")]
+    KeyCombination(String),
+
+    #[error("Invalid secret shares")]
+    InvalidShares,
+
+    #[error("Invalid key: {0}")]
+    InvalidKey(String),
+
+    #[error("Invalid tweak: {0}")]
+    InvalidTweak(String),
+
+    #[error("Invalid nonce: {0}")]
+    InvalidNonce(String),
+
+    #[error("Secret nonce has already been used")]
+    NonceReuse,
+
+    #[error("Invalid partial signature from signer {0}")]
+    InvalidPartialSignature(usize),
+}
+
+/// Order of the secp256k1 group
+const CURVE_ORDER: [u8; 32] = [
+    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xfe,
+    0xba, 0xae, 0xdc, 0xe6, 0xaf, 0x48, 0xa0, 0x3b, 0xbf, 0xd2, 0x5e, 0x8c, 0xd0, 0x36, 0x41, 0x41,
+];
+
+fn tagged_hash(tag: &str, parts: &[&[u8]]) -> [u8; 32] {
+    let tag = sha256::Hash::hash(tag.as_bytes());
+    let mut engine = sha256::Hash::engine();
+    engine.input(tag.as_ref());
+    engine.input(tag.as_ref());
+    for part in parts {
+        engine.input(part);
+    }
+    sha256::Hash::from_engine(engine).to_byte_array()
+}
+
+fn has_even_y(point: &PublicKey) -> bool {
+    point.x_only_public_key().1 == Parity::Even
+}
+
+/// Integer modulo the curve order, zero included
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct ScalarN(Option<SecretKey>);
+
+impl ScalarN {
+    const ZERO: ScalarN = ScalarN(None);
+
+    fn one() -> Self {
+        let mut bytes = [0u8; 32];
+        bytes[31] = 1;
+        ScalarN(SecretKey::from_slice(&bytes).ok())
+    }
+
+    /// Interprets 32 big-endian bytes, reducing them modulo the curve order
+    fn reduce(mut bytes: [u8; 32]) -> Self {
+        if bytes >= CURVE_ORDER {
+            // A 256-bit value is below twice the order, one subtraction suffices
+            let mut borrow = 0i16;
+            for i in (0..32).rev() {
+                let diff = i16::from(bytes[i]) - i16::from(CURVE_ORDER[i]) - borrow;
+                borrow = i16::from(diff < 0);
+                bytes[i] = diff.rem_euclid(256) as u8;
+            }
+        }
+        ScalarN(SecretKey::from_slice(&bytes).ok())
+    }
+
+    /// Interprets 32 big-endian bytes, rejecting values not below the curve order
+    fn from_canonical(bytes: [u8; 32]) -> Option<Self> {
+        if bytes >= CURVE_ORDER {
+            return None;
+        }
+        Some(ScalarN(SecretKey::from_slice(&bytes).ok()))
+    }
+
+    fn to_bytes(self) -> [u8; 32] {
+        self.0.map(|k| k.secret_bytes()).unwrap_or([0u8; 32])
+    }
+
+    fn add(self, other: Self) -> Self {
+        match (self.0, other.0) {
+            (Some(a), Some(b)) => ScalarN(a.add_tweak(&Scalar::from(b)).ok()),
+            (Some(_), None) => self,
+            (None, _) => other,
+        }
+    }
+
+    fn mul(self, other: Self) -> Self {
+        match (self.0, other.0) {
+            (Some(a), Some(b)) => ScalarN(a.mul_tweak(&Scalar::from(b)).ok()),
+            _ => Self::ZERO,
+        }
+    }
+
+    fn negate(self) -> Self {
+        ScalarN(self.0.map(SecretKey::negate))
+    }
+
+    /// `self·P`, `None` for the point at infinity
+    fn mul_point(self, secp: &Secp256k1<All>, point: &PublicKey) -> Option<PublicKey> {
+        self.0.map(|k| point.mul_tweak(secp, &Scalar::from(k)).expect("non-zero scalar"))
+    }
+
+    /// `self·G`, `None` for the point at infinity
+    fn mul_base(self, secp: &Secp256k1<All>) -> Option<PublicKey> {
+        self.0.map(|k| PublicKey::from_secret_key(secp, &k))
+    }
+
+    /// Overwrites a secret value once it is no longer needed
+    fn erase(&mut self) {
+        if let Some(k) = self.0.as_mut() {
+            k.non_secure_erase();
+        }
+    }
+}
+
+/// Adds two points, either of which may be the point at infinity
+fn add_points(a: Option<PublicKey>, b: Option<PublicKey>) -> Option<PublicKey> {
+    match (a, b) {
+        (Some(a), Some(b)) => a.combine(&b).ok(),
+        (a, None) => a,
+        (None, b) => b,
+    }
+}
+
+/// Sorts public keys into the canonical BIP327 `KeySort` order
+pub fn sort_public_keys(public_keys: &mut [PublicKey]) {
+    public_keys.sort_by_key(PublicKey::serialize);
+}
+
+/// Aggregate of the signers' public keys with any tweaks applied to it
+#[derive(Debug, Clone)]
+pub struct KeyAggContext {
+    public_keys: Vec<PublicKey>,
+    keys_hash: [u8; 32],
+    second_key: Option<PublicKey>,
+    aggregate_key: PublicKey,
+    // Sign accumulated by x-only tweaks: `true` for n - 1
+    negated: bool,
+    tweak: ScalarN,
+}
+
+impl KeyAggContext {
+    /// Aggregates public keys in the given order (BIP327 `KeyAgg`)
+    pub fn new(public_keys: &[PublicKey]) -> Result<Self, SMCError> {
+        if public_keys.is_empty() {
+            return Err(SMCError::KeyCombination("No public keys to aggregate".to_string()));
+        }
+        let serialized: Vec<u8> = public_keys.iter().flat_map(PublicKey::serialize).collect();
+        let keys_hash = tagged_hash("KeyAgg list", &[&serialized]);
+        let second_key = public_keys.iter().find(|key| **key != public_keys[0]).copied();
+
+        let mut context = Self {
+            public_keys: public_keys.to_vec(),
+            keys_hash,
+            second_key,
+            aggregate_key: public_keys[0],
+            negated: false,
+            tweak: ScalarN::ZERO,
+        };
+
+        let secp = Secp256k1::new();
+        let aggregate = public_keys.iter().fold(None, |sum, key| {
+            add_points(sum, context.coefficient(key).mul_point(&secp, key))
+        });
+        context.aggregate_key = aggregate
+            .ok_or_else(|| SMCError::KeyCombination("Aggregate key is the point at infinity".to_string()))?;
+        Ok(context)
+    }
+
+    /// Aggregation coefficient of a signer's key
+    fn coefficient(&self, public_key: &PublicKey) -> ScalarN {
+        if Some(*public_key) == self.second_key {
+            return ScalarN::one();
+        }
+        ScalarN::reduce(tagged_hash("KeyAgg coefficient", &[&self.keys_hash, &public_key.serialize()]))
+    }
+
+    /// Signers' public keys in aggregation order
+    pub fn public_keys(&self) -> &[PublicKey] {
+        &self.public_keys
+    }
+
+    /// Aggregate public key, including tweaks
+    pub fn aggregate_key(&self) -> PublicKey {
+        self.aggregate_key
     }
 
-    // Implement MuSig2 for Taproot
-    pub fn schnorr_musig2(&self, signers: Vec<&SecretKey>) -> Result<(SecretKey, Vec<PublicKey>), SMCError> {
+    /// X-only aggregate key, as used in a P2TR output or BIP340 verification
+    pub fn x_only_public_key(&self) -> XOnlyPublicKey {
+        self.aggregate_key.x_only_public_key().0
+    }
+
+    /// Adds `tweak·G` to the aggregate key, e.g. for BIP32 derivation
+    pub fn with_plain_tweak(self, tweak: [u8; 32]) -> Result<Self, SMCError> {
+        self.apply_tweak(tweak, false)
+    }
+
+    /// Adds `tweak·G` to the even-y version of the aggregate key
+    pub fn with_xonly_tweak(self, tweak: [u8; 32]) -> Result<Self, SMCError> {
+        self.apply_tweak(tweak, true)
+    }
+
+    /// Tweaks the aggregate into a BIP341 output key committing to
+    /// `merkle_root`, or to no script tree at all
+    pub fn with_taproot_tweak(self, merkle_root: Option<TapNodeHash>) -> Result<Self, SMCError> {
+        let tweak = TapTweakHash::from_key_and_tweak(self.x_only_public_key(), merkle_root);
+        self.apply_tweak(tweak.to_byte_array(), true)
+    }
+
+    fn apply_tweak(mut self, tweak: [u8; 32], x_only: bool) -> Result<Self, SMCError> {
+        let tweak = ScalarN::from_canonical(tweak)
+            .ok_or_else(|| SMCError::InvalidTweak("Tweak is not below the curve order".to_string()))?;
         let secp = Secp256k1::new();
-        let mut combined_key = None;
-        let mut public_keys = Vec::new();
-
-        for signer in signers {
-            let pub_key = PublicKey::from_secret_key(&secp, signer);
-            public_keys.push(pub_key);
-            
-            if let Some(key) = combined_key {
-                combined_key = Some(key.combine(&pub_key)
-                    .map_err(|e| SMCError::KeyCombination(e.to_string()))?);
-            } else {
-                combined_key = Some(pub_key);
+
+        let negate = x_only && !has_even_y(&self.aggregate_key);
+        let key = if negate { self.aggregate_key.negate(&secp) } else { self.aggregate_key };
+        self.aggregate_key = add_points(Some(key), tweak.mul_base(&secp))
+            .ok_or_else(|| SMCError::InvalidTweak("Tweaked key is the point at infinity".to_string()))?;
+
+        if negate {
+            self.negated = !self.negated;
+            self.tweak = self.tweak.negate();
+        }
+        self.tweak = self.tweak.add(tweak);
+        Ok(self)
+    }
+}
+
+/// A signer's secret nonce
+///
+/// Deliberately neither `Clone` nor serializable: signing consumes it, and
+/// it is erased when dropped.
+#[derive(Debug)]
+pub struct SecNonce {
+    k1: SecretKey,
+    k2: SecretKey,
+    public_key: PublicKey,
+}
+
+impl Drop for SecNonce {
+    fn drop(&mut self) {
+        self.k1.non_secure_erase();
+        self.k2.non_secure_erase();
+    }
+}
+
+/// A signer's public nonce, sent to the other signers in the first round
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PubNonce {
+    r1: PublicKey,
+    r2: PublicKey,
+}
+
+impl PubNonce {
+    /// 66-byte encoding of both nonce points
+    pub fn serialize(&self) -> [u8; 66] {
+        let mut bytes = [0u8; 66];
+        bytes[..33].copy_from_slice(&self.r1.serialize());
+        bytes[33..].copy_from_slice(&self.r2.serialize());
+        bytes
+    }
+
+    pub fn from_slice(bytes: &[u8]) -> Result<Self, SMCError> {
+        if bytes.len() != 66 {
+            return Err(SMCError::InvalidNonce(format!("Expected 66 bytes, got {}", bytes.len())));
+        }
+        let point = |b: &[u8]| PublicKey::from_slice(b).map_err(|e| SMCError::InvalidNonce(e.to_string()));
+        Ok(Self { r1: point(&bytes[..33])?, r2: point(&bytes[33..])? })
+    }
+}
+
+/// Sum of all signers' public nonces
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct AggNonce {
+    r1: Option<PublicKey>,
+    r2: Option<PublicKey>,
+}
+
+impl AggNonce {
+    /// Aggregates the public nonces of every signer (BIP327 `NonceAgg`)
+    pub fn aggregate(nonces: &[PubNonce]) -> Result<Self, SMCError> {
+        if nonces.is_empty() {
+            return Err(SMCError::InvalidNonce("No nonces to aggregate".to_string()));
+        }
+        Ok(Self {
+            r1: nonces.iter().fold(None, |sum, nonce| add_points(sum, Some(nonce.r1))),
+            r2: nonces.iter().fold(None, |sum, nonce| add_points(sum, Some(nonce.r2))),
+        })
+    }
+
+    /// 66-byte encoding, with 33 zero bytes for the point at infinity
+    pub fn serialize(&self) -> [u8; 66] {
+        let mut bytes = [0u8; 66];
+        if let Some(r1) = self.r1 {
+            bytes[..33].copy_from_slice(&r1.serialize());
+        }
+        if let Some(r2) = self.r2 {
+            bytes[33..].copy_from_slice(&r2.serialize());
+        }
+        bytes
+    }
+
+    /// Decodes an aggregate nonce received from a coordinator
+    pub fn from_slice(bytes: &[u8]) -> Result<Self, SMCError> {
+        if bytes.len() != 66 {
+            return Err(SMCError::InvalidNonce(format!("Expected 66 bytes, got {}", bytes.len())));
+        }
+        // 33 zero bytes encode the point at infinity
+        let point = |b: &[u8]| {
+            if b.iter().all(|byte| *byte == 0) {
+                return Ok(None);
             }
+            PublicKey::from_slice(b).map(Some).map_err(|e| SMCError::InvalidNonce(e.to_string()))
+        };
+        Ok(Self { r1: point(&bytes[..33])?, r2: point(&bytes[33..])? })
+    }
+}
+
+/// Generates a signer's nonce pair (BIP327 `NonceGen`)
+///
+/// Fresh randomness is always used; the optional inputs only add defence
+/// against a broken random number generator.
+pub fn nonce_gen(
+    secret_key: Option<&SecretKey>,
+    public_key: &PublicKey,
+    aggregate_key: Option<&XOnlyPublicKey>,
+    msg: Option<&[u8]>,
+    extra_in: Option<&[u8]>,
+) -> (SecNonce, PubNonce) {
+    let mut rand = [0u8; 32];
+    rand::thread_rng().fill_bytes(&mut rand);
+    nonce_gen_with_rand(rand, secret_key, public_key, aggregate_key, msg, extra_in)
+}
+
+fn nonce_gen_with_rand(
+    mut rand: [u8; 32],
+    secret_key: Option<&SecretKey>,
+    public_key: &PublicKey,
+    aggregate_key: Option<&XOnlyPublicKey>,
+    msg: Option<&[u8]>,
+    extra_in: Option<&[u8]>,
+) -> (SecNonce, PubNonce) {
+    if let Some(secret_key) = secret_key {
+        let mask = tagged_hash("MuSig/aux", &[&rand]);
+        for (byte, (sk, mask)) in rand.iter_mut().zip(secret_key.secret_bytes().iter().zip(mask)) {
+            *byte = sk ^ mask;
+        }
+    }
+
+    let public_key_bytes = public_key.serialize();
+    let aggregate_key_bytes = aggregate_key.map(|key| key.serialize().to_vec()).unwrap_or_default();
+    let msg_prefixed = match msg {
+        Some(msg) => [&[1u8][..], &(msg.len() as u64).to_be_bytes(), msg].concat(),
+        None => vec![0u8],
+    };
+    let extra_in = extra_in.unwrap_or_default();
+
+    let secp = Secp256k1::new();
+    let mut k = [SecretKey::from_slice(&[1u8; 32]).expect("valid key"); 2];
+    for (i, k) in k.iter_mut().enumerate() {
+        let hash = tagged_hash("MuSig/nonce", &[
+            &rand,
+            &[public_key_bytes.len() as u8],
+            &public_key_bytes,
+            &[aggregate_key_bytes.len() as u8],
+            &aggregate_key_bytes,
+            &msg_prefixed,
+            &(extra_in.len() as u32).to_be_bytes(),
+            extra_in,
+            &[i as u8],
+        ]);
+        // A zero nonce has negligible probability
+        *k = ScalarN::reduce(hash).0.expect("non-zero nonce");
+    }
+
+    let pubnonce = PubNonce {
+        r1: PublicKey::from_secret_key(&secp, &k[0]),
+        r2: PublicKey::from_secret_key(&secp, &k[1]),
+    };
+    let secnonce = SecNonce { k1: k[0], k2: k[1], public_key: *public_key };
+    // `SecretKey` is `Copy`: erase the copies left on the stack too
+    k.iter_mut().for_each(SecretKey::non_secure_erase);
+    (secnonce, pubnonce)
+}
+
+/// A signer's share of the final signature
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PartialSignature(ScalarN);
+
+impl PartialSignature {
+    pub fn serialize(&self) -> [u8; 32] {
+        self.0.to_bytes()
+    }
+
+    pub fn from_slice(bytes: &[u8]) -> Result<Self, SMCError> {
+        let bytes: [u8; 32] = bytes.try_into()
+            .map_err(|_| SMCError::InvalidPartialSignature(0))?;
+        ScalarN::from_canonical(bytes)
+            .map(PartialSignature)
+            .ok_or(SMCError::InvalidPartialSignature(0))
+    }
+}
+
+/// Values every signer derives once the aggregate nonce and message are known
+#[derive(Debug, Clone)]
+pub struct SigningSession {
+    key_agg: KeyAggContext,
+    msg: [u8; 32],
+    nonce_coefficient: ScalarN,
+    final_nonce: PublicKey,
+    challenge: ScalarN,
+}
+
+impl SigningSession {
+    /// Derives the session values for signing `msg` (BIP327 `GetSessionValues`)
+    pub fn new(key_agg: &KeyAggContext, aggnonce: &AggNonce, msg: &Message) -> Self {
+        let secp = Secp256k1::new();
+        let msg = *msg.as_ref();
+        let nonce_coefficient = ScalarN::reduce(tagged_hash(
+            "MuSig/noncecoef",
+            &[&aggnonce.serialize(), &key_agg.x_only_public_key().serialize(), &msg],
+        ));
+        let r2 = aggnonce.r2.and_then(|r2| nonce_coefficient.mul_point(&secp, &r2));
+        // An aggregate nonce at infinity is replaced by the generator
+        let final_nonce = add_points(aggnonce.r1, r2)
+            .unwrap_or_else(|| ScalarN::one().mul_base(&secp).expect("generator"));
+        let challenge = ScalarN::reduce(tagged_hash("BIP0340/challenge", &[
+            &final_nonce.x_only_public_key().0.serialize(),
+            &key_agg.x_only_public_key().serialize(),
+            &msg,
+        ]));
+
+        Self { key_agg: key_agg.clone(), msg, nonce_coefficient, final_nonce, challenge }
+    }
+
+    /// `g·gacc`: whether a signer's key enters the final key negated
+    fn key_negated(&self) -> bool {
+        !has_even_y(&self.key_agg.aggregate_key) ^ self.key_agg.negated
+    }
+
+    /// Creates a partial signature, consuming the secret nonce (BIP327 `Sign`)
+    pub fn sign(&self, secnonce: SecNonce, secret_key: &SecretKey) -> Result<PartialSignature, SMCError> {
+        let secp = Secp256k1::new();
+        let public_key = PublicKey::from_secret_key(&secp, secret_key);
+        if public_key != secnonce.public_key {
+            return Err(SMCError::InvalidKey("Secret nonce was generated for another key".to_string()));
+        }
+        if !self.key_agg.public_keys.contains(&public_key) {
+            return Err(SMCError::InvalidKey("Signer is not part of the aggregate key".to_string()));
+        }
+
+        let nonce_sign = |k: SecretKey| {
+            let k = ScalarN(Some(k));
+            if has_even_y(&self.final_nonce) { k } else { k.negate() }
+        };
+        let mut k1 = nonce_sign(secnonce.k1);
+        let mut k2 = nonce_sign(secnonce.k2);
+
+        let mut d = ScalarN(Some(*secret_key));
+        if self.key_negated() {
+            d = d.negate();
         }
+        let a = self.key_agg.coefficient(&public_key);
+        let s = k1
+            .add(self.nonce_coefficient.mul(k2))
+            .add(self.challenge.mul(a).mul(d));
+        let partial_signature = PartialSignature(s);
+        k1.erase();
+        k2.erase();
+        d.erase();
+
+        let pubnonce = PubNonce {
+            r1: PublicKey::from_secret_key(&secp, &secnonce.k1),
+            r2: PublicKey::from_secret_key(&secp, &secnonce.k2),
+        };
+        if !self.verify_partial(&partial_signature, &pubnonce, &public_key) {
+            return Err(SMCError::InvalidPartialSignature(0));
+        }
+        Ok(partial_signature)
+    }
+
+    /// Checks another signer's partial signature (BIP327 `PartialSigVerify`)
+    pub fn verify_partial(&self, partial_signature: &PartialSignature, pubnonce: &PubNonce, public_key: &PublicKey) -> bool {
+        if !self.key_agg.public_keys.contains(public_key) {
+            return false;
+        }
+        let secp = Secp256k1::new();
+        let r2 = self.nonce_coefficient.mul_point(&secp, &pubnonce.r2);
+        let Some(mut nonce) = add_points(Some(pubnonce.r1), r2) else {
+            return false;
+        };
+        if !has_even_y(&self.final_nonce) {
+            nonce = nonce.negate(&secp);
+        }
+
+        let key = if self.key_negated() { public_key.negate(&secp) } else { *public_key };
+        let ea = self.challenge.mul(self.key_agg.coefficient(public_key));
+        let expected = add_points(Some(nonce), ea.mul_point(&secp, &key));
+        partial_signature.0.mul_base(&secp) == expected
+    }
+
+    /// Combines all partial signatures into a BIP340 signature for the
+    /// aggregate key (BIP327 `PartialSigAgg`)
+    pub fn aggregate(&self, partial_signatures: &[PartialSignature]) -> Result<Signature, SMCError> {
+        let mut s = partial_signatures.iter().fold(ScalarN::ZERO, |sum, psig| sum.add(psig.0));
+        let mut tweak = self.challenge.mul(self.key_agg.tweak);
+        if !has_even_y(&self.key_agg.aggregate_key) {
+            tweak = tweak.negate();
+        }
+        s = s.add(tweak);
+
+        let mut bytes = [0u8; 64];
+        bytes[..32].copy_from_slice(&self.final_nonce.x_only_public_key().0.serialize());
+        bytes[32..].copy_from_slice(&s.to_bytes());
+        let signature = Signature::from_slice(&bytes).map_err(|e| SMCError::KeyCombination(e.to_string()))?;
+
+        let secp = Secp256k1::verification_only();
+        let msg = Message::from_digest(self.msg);
+        secp.verify_schnorr(&signature, &msg, &self.key_agg.x_only_public_key())
+            .map_err(|_| SMCError::InvalidPartialSignature(partial_signatures.len()))?;
+        Ok(signature)
+    }
+}
+
+/// One participant's view of a MuSig2 signing round
+///
+/// Holds the secret nonce between the two rounds and hands it out exactly
+/// once, so a signer persisted across rounds cannot sign twice with it.
+#[derive(Debug)]
+pub struct Musig2Signer {
+    secret_key: SecretKey,
+    key_agg: KeyAggContext,
+    secnonce: Option<SecNonce>,
+    pubnonce: PubNonce,
+}
+
+impl Musig2Signer {
+    /// Starts a round, generating a fresh nonce for `msg` if it is already known
+    pub fn new(secret_key: SecretKey, key_agg: KeyAggContext, msg: Option<&Message>) -> Self {
+        let public_key = PublicKey::from_secret_key(&Secp256k1::new(), &secret_key);
+        let (secnonce, pubnonce) = nonce_gen(
+            Some(&secret_key),
+            &public_key,
+            Some(&key_agg.x_only_public_key()),
+            msg.map(|msg| msg.as_ref().as_slice()),
+            None,
+        );
+        Self { secret_key, key_agg, secnonce: Some(secnonce), pubnonce }
+    }
+
+    /// Nonce to send to the other signers
+    pub fn public_nonce(&self) -> PubNonce {
+        self.pubnonce
+    }
 
-        // Add nonce generation and commitment phases
-        todo!("Implement full MuSig2 protocol")
+    /// Signs once every public nonce is known
+    pub fn sign(&mut self, aggnonce: &AggNonce, msg: &Message) -> Result<PartialSignature, SMCError> {
+        let secnonce = self.secnonce.take().ok_or(SMCError::NonceReuse)?;
+        SigningSession::new(&self.key_agg, aggnonce, msg).sign(secnonce, &self.secret_key)
+    }
+}
+
+/// Entry point for the multiparty protocols
+#[derive(Debug, Default)]
+pub struct SecureMultipartyComputation;
+
+impl SecureMultipartyComputation {
+    pub fn new() -> Self {
+        Self
+    }
+
+    /// Aggregates the signers' public keys for MuSig2, in canonical order
+    pub fn schnorr_musig2(&self, public_keys: &[PublicKey]) -> Result<KeyAggContext, SMCError> {
+        let mut public_keys = public_keys.to_vec();
+        sort_public_keys(&mut public_keys);
+        KeyAggContext::new(&public_keys)
     }
 
     // Fix duplicate reconstruct_secret implementation
+    #[allow(dead_code)]
     fn reconstruct_secret(&self, shares: Vec<Vec<u8>>) -> Result<Vec<u8>, SMCError> {
         if shares.is_empty() {
             return Err(SMCError::InvalidShares);
@@ -45,3 +618,254 @@ fn reconstruct_secret(&self, shares: Vec<Vec<u8>>) -> Result<Vec<u8>, SMCError>
         Ok(secret)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitcoin::hex::FromHex;
+    use std::str::FromStr;
+
+    fn key(hex: &str) -> PublicKey {
+        PublicKey::from_str(hex).unwrap()
+    }
+
+    #[test]
+    fn test_key_agg_vectors() {
+        let keys = [
+            key("02F9308A019258C31049344F85F89D5229B531C845836F99B08601F113BCE036F9"),
+            key("03DFF1D77F2A671C5F36183726DB2341BE58FEAE1DA2DECED843240F7B502BA659"),
+            key("023590A94E768F8E1815C2F24B4D80A8E3149316C3518CE7B7AD338368D038CA66"),
+        ];
+        let cases: [(&[usize], &str); 4] = [
+            (&[0, 1, 2], "90539eede565f5d054f32cc0c220126889ed1e5d193baf15aef344fe59d4610c"),
+            (&[2, 1, 0], "6204de8b083426dc6eaf9502d27024d53fc826bf7d2012148a0575435df54b2b"),
+            (&[0, 0, 0], "b436e3bad62b8cd409969a224731c193d051162d8c5ae8b109306127da3aa935"),
+            (&[0, 0, 1, 1], "69bc22bfa5d106306e48a20679de1d7389386124d07571d0d872686028c26a3e"),
+        ];
+        for (indices, expected) in cases {
+            let public_keys: Vec<_> = indices.iter().map(|i| keys[*i]).collect();
+            let context = KeyAggContext::new(&public_keys).unwrap();
+            assert_eq!(context.x_only_public_key().to_string(), expected);
+        }
+    }
+
+    fn signers(n: u8) -> Vec<(SecretKey, PublicKey)> {
+        let secp = Secp256k1::new();
+        (1..=n)
+            .map(|i| {
+                let secret_key = SecretKey::from_slice(&[i; 32]).unwrap();
+                (secret_key, PublicKey::from_secret_key(&secp, &secret_key))
+            })
+            .collect()
+    }
+
+    #[test]
+    fn test_taproot_signing_round() {
+        let signers = signers(3);
+        let public_keys: Vec<_> = signers.iter().map(|(_, pk)| *pk).collect();
+        let key_agg = SecureMultipartyComputation::new()
+            .schnorr_musig2(&public_keys)
+            .unwrap()
+            .with_taproot_tweak(Some(TapNodeHash::from_byte_array([9; 32])))
+            .unwrap();
+        let msg = Message::from_digest([42; 32]);
+
+        let mut participants: Vec<_> = signers.iter()
+            .map(|(sk, _)| Musig2Signer::new(*sk, key_agg.clone(), Some(&msg)))
+            .collect();
+        let pubnonces: Vec<_> = participants.iter().map(Musig2Signer::public_nonce).collect();
+        let aggnonce = AggNonce::aggregate(&pubnonces).unwrap();
+
+        let partial_signatures: Vec<_> = participants.iter_mut()
+            .map(|signer| signer.sign(&aggnonce, &msg).unwrap())
+            .collect();
+
+        let session = SigningSession::new(&key_agg, &aggnonce, &msg);
+        for ((psig, pubnonce), pk) in partial_signatures.iter().zip(&pubnonces).zip(&public_keys) {
+            assert!(session.verify_partial(psig, pubnonce, pk));
+        }
+        assert!(!session.verify_partial(&partial_signatures[0], &pubnonces[1], &public_keys[0]));
+
+        let signature = session.aggregate(&partial_signatures).unwrap();
+        let secp = Secp256k1::verification_only();
+        assert!(secp.verify_schnorr(&signature, &msg, &key_agg.x_only_public_key()).is_ok());
+        assert!(session.aggregate(&partial_signatures[..2]).is_err());
+    }
+
+    #[test]
+    fn test_plain_and_xonly_tweaks() {
+        let signers = signers(2);
+        let public_keys: Vec<_> = signers.iter().map(|(_, pk)| *pk).collect();
+        let key_agg = KeyAggContext::new(&public_keys).unwrap()
+            .with_plain_tweak([3; 32]).unwrap()
+            .with_xonly_tweak([5; 32]).unwrap()
+            .with_plain_tweak([7; 32]).unwrap();
+        let msg = Message::from_digest([1; 32]);
+
+        let nonces: Vec<_> = signers.iter()
+            .map(|(sk, pk)| nonce_gen(Some(sk), pk, None, None, Some(b"extra")))
+            .collect();
+        let aggnonce = AggNonce::aggregate(&nonces.iter().map(|(_, pubnonce)| *pubnonce).collect::<Vec<_>>()).unwrap();
+        let session = SigningSession::new(&key_agg, &aggnonce, &msg);
+        let partial_signatures: Vec<_> = nonces.into_iter().zip(&signers)
+            .map(|((secnonce, _), (sk, _))| session.sign(secnonce, sk).unwrap())
+            .collect();
+        assert!(session.aggregate(&partial_signatures).is_ok());
+    }
+
+    #[test]
+    fn test_nonce_reuse_and_wrong_key() {
+        let signers = signers(2);
+        let public_keys: Vec<_> = signers.iter().map(|(_, pk)| *pk).collect();
+        let key_agg = KeyAggContext::new(&public_keys).unwrap();
+        let msg = Message::from_digest([2; 32]);
+
+        let mut signer = Musig2Signer::new(signers[0].0, key_agg.clone(), None);
+        let (other_secnonce, other_pubnonce) = nonce_gen(None, &signers[1].1, None, None, None);
+        let aggnonce = AggNonce::aggregate(&[signer.public_nonce(), other_pubnonce]).unwrap();
+        assert!(signer.sign(&aggnonce, &msg).is_ok());
+        assert!(matches!(signer.sign(&aggnonce, &msg), Err(SMCError::NonceReuse)));
+
+        let session = SigningSession::new(&key_agg, &aggnonce, &msg);
+        assert!(matches!(session.sign(other_secnonce, &signers[0].0), Err(SMCError::InvalidKey(_))));
+
+        let nonce = PubNonce::from_slice(&other_pubnonce.serialize()).unwrap();
+        assert_eq!(nonce, other_pubnonce);
+        assert!(PubNonce::from_slice(&[0u8; 66]).is_err());
+    }
+
+    fn bytes(hex: &str) -> Vec<u8> {
+        Vec::from_hex(hex).unwrap()
+    }
+
+    #[test]
+    fn test_nonce_gen_vectors() {
+        // BIP327 nonce_gen_vectors.json, the cases with a 32-byte or no message
+        type Case<'a> = (Option<&'a str>, &'a str, Option<&'a str>, Option<&'a str>, Option<&'a str>, &'a str, &'a str);
+        let cases: [Case; 2] = [
+            (
+                Some("0202020202020202020202020202020202020202020202020202020202020202"),
+                "024D4B6CD1361032CA9BD2AEB9D900AA4D45D9EAD80AC9423374C451A7254D0766",
+                Some("0707070707070707070707070707070707070707070707070707070707070707"),
+                Some("0101010101010101010101010101010101010101010101010101010101010101"),
+                Some("0808080808080808080808080808080808080808080808080808080808080808"),
+                "B114E502BEAA4E301DD08A50264172C84E41650E6CB726B410C0694D59EFFB6495B5CAF28D045B973D63E3C99A44B807BDE375FD6CB39E46DC4A511708D0E9D2024D4B6CD1361032CA9BD2AEB9D900AA4D45D9EAD80AC9423374C451A7254D0766",
+                "02F7BE7089E8376EB355272368766B17E88E7DB72047D05E56AA881EA52B3B35DF02C29C8046FDD0DED4C7E55869137200FBDBFE2EB654267B6D7013602CAED3115A",
+            ),
+            (
+                None,
+                "02F9308A019258C31049344F85F89D5229B531C845836F99B08601F113BCE036F9",
+                None,
+                None,
+                None,
+                "89BDD787D0284E5E4D5FC572E49E316BAB7E21E3B1830DE37DFE80156FA41A6D0B17AE8D024C53679699A6FD7944D9C4A366B514BAF43088E0708B1023DD289702F9308A019258C31049344F85F89D5229B531C845836F99B08601F113BCE036F9",
+                "02C96E7CB1E8AA5DAC64D872947914198F607D90ECDE5200DE52978AD5DED63C000299EC5117C2D29EDEE8A2092587C3909BE694D5CFF0667D6C02EA4059F7CD9786",
+            ),
+        ];
+        for (sk, pk, aggpk, msg, extra_in, expected_secnonce, expected_pubnonce) in cases {
+            let sk = sk.map(|sk| SecretKey::from_slice(&bytes(sk)).unwrap());
+            let aggpk = aggpk.map(|aggpk| XOnlyPublicKey::from_slice(&bytes(aggpk)).unwrap());
+            let msg = msg.map(bytes);
+            let extra_in = extra_in.map(bytes);
+            let (secnonce, pubnonce) = nonce_gen_with_rand(
+                [0x0f; 32],
+                sk.as_ref(),
+                &key(pk),
+                aggpk.as_ref(),
+                msg.as_deref(),
+                extra_in.as_deref(),
+            );
+            let encoded = [&secnonce.k1.secret_bytes()[..], &secnonce.k2.secret_bytes(), &secnonce.public_key.serialize()].concat();
+            assert_eq!(encoded, bytes(expected_secnonce));
+            assert_eq!(pubnonce.serialize().to_vec(), bytes(expected_pubnonce));
+        }
+    }
+
+    #[test]
+    fn test_sign_verify_vectors() {
+        // BIP327 sign_verify_vectors.json. Sessions sign 32-byte digests, so
+        // the empty and 38-byte message cases are left out.
+        let secret_key = SecretKey::from_slice(&bytes("7FB9E0E687ADA1EEBF7ECFE2F21E73EBDB51A7D450948DFE8D76D7F2D1007671")).unwrap();
+        let pubkeys = [
+            key("03935F972DA013F80AE011890FA89B67A27B7BE6CCB24D3274D18B2D4067F261A9"),
+            key("02F9308A019258C31049344F85F89D5229B531C845836F99B08601F113BCE036F9"),
+            key("02DFF1D77F2A671C5F36183726DB2341BE58FEAE1DA2DECED843240F7B502BA661"),
+        ];
+        let secnonce = || {
+            let secnonce = bytes("508B81A611F100A6B2B6B29656590898AF488BCF2E1F55CF22E5CFB84421FE61FA27FD49B1D50085B481285E1CA205D55C82CC1B31FF5CD54A489829355901F703935F972DA013F80AE011890FA89B67A27B7BE6CCB24D3274D18B2D4067F261A9");
+            SecNonce {
+                k1: SecretKey::from_slice(&secnonce[..32]).unwrap(),
+                k2: SecretKey::from_slice(&secnonce[32..64]).unwrap(),
+                public_key: PublicKey::from_slice(&secnonce[64..]).unwrap(),
+            }
+        };
+        let pnonces = [
+            "0337C87821AFD50A8644D820A8F3E02E499C931865C2360FB43D0A0D20DAFE07EA0287BF891D2A6DEAEBADC909352AA9405D1428C15F4B75F04DAE642A95C2548480",
+            "0279BE667EF9DCBBAC55A06295CE870B07029BFCDB2DCE28D959F2815B16F817980279BE667EF9DCBBAC55A06295CE870B07029BFCDB2DCE28D959F2815B16F81798",
+            "032DE2662628C90B03F5E720284EB52FF7D71F4284F627B68A853D78C78E1FFE9303E4C5524E83FFE1493B9077CF1CA6BEB2090C93D930321071AD40B2F44E599046",
+            "0237C87821AFD50A8644D820A8F3E02E499C931865C2360FB43D0A0D20DAFE07EA0387BF891D2A6DEAEBADC909352AA9405D1428C15F4B75F04DAE642A95C2548480",
+        ]
+        .map(|pnonce| PubNonce::from_slice(&bytes(pnonce)).unwrap());
+        let aggnonces = [
+            "028465FCF0BBDBCF443AABCCE533D42B4B5A10966AC09A49655E8C42DAAB8FCD61037496A3CC86926D452CAFCFD55D25972CA1675D549310DE296BFF42F72EEEA8C9",
+            "000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000000",
+        ]
+        .map(|aggnonce| AggNonce::from_slice(&bytes(aggnonce)).unwrap());
+        let msg = Message::from_digest(bytes("F95466D086770E689964664219266FE5ED215C92AE20BAB5C9D79ADDDDF3C0CF").try_into().unwrap());
+        let session = |key_indices: &[usize], aggnonce: &AggNonce| {
+            let public_keys: Vec<_> = key_indices.iter().map(|i| pubkeys[*i]).collect();
+            SigningSession::new(&KeyAggContext::new(&public_keys).unwrap(), aggnonce, &msg)
+        };
+
+        // Key indices, nonce indices, aggregate nonce, signer and partial signature
+        type Case<'a> = (&'a [usize], &'a [usize], usize, usize, &'a str);
+        let valid: [Case; 4] = [
+            (&[0, 1, 2], &[0, 1, 2], 0, 0, "012ABBCB52B3016AC03AD82395A1A415C48B93DEF78718E62A7A90052FE224FB"),
+            (&[1, 0, 2], &[1, 0, 2], 0, 1, "9FF2F7AAA856150CC8819254218D3ADEEB0535269051897724F9DB3789513A52"),
+            (&[1, 2, 0], &[1, 2, 0], 0, 2, "FA23C359F6FAC4E7796BB93BC9F0532A95468C539BA20FF86D7C76ED92227900"),
+            // Both halves of the aggregate nonce are the point at infinity
+            (&[0, 1], &[0, 3], 1, 0, "AE386064B26105404798F75DE2EB9AF5EDA5387B064B83D049CB7C5E08879531"),
+        ];
+        for (key_indices, nonce_indices, aggnonce_index, signer, expected) in valid {
+            let nonces: Vec<_> = nonce_indices.iter().map(|i| pnonces[*i]).collect();
+            assert_eq!(AggNonce::aggregate(&nonces).unwrap(), aggnonces[aggnonce_index]);
+            let session = session(key_indices, &aggnonces[aggnonce_index]);
+            let partial_signature = session.sign(secnonce(), &secret_key).unwrap();
+            assert_eq!(partial_signature.serialize().to_vec(), bytes(expected));
+            assert!(session.verify_partial(&partial_signature, &nonces[signer], &pubkeys[key_indices[signer]]));
+        }
+
+        // The signer's key is not aggregated
+        let other_keys = session(&[1, 2], &aggnonces[0]);
+        assert!(matches!(other_keys.sign(secnonce(), &secret_key), Err(SMCError::InvalidKey(_))));
+        // An all-zero secret nonce, as left after use, is not a nonce
+        assert!(SecretKey::from_slice(&[0u8; 32]).is_err());
+        // Invalid public key, public nonce and aggregate nonces
+        assert!(PublicKey::from_slice(&bytes("020000000000000000000000000000000000000000000000000000000000000007")).is_err());
+        assert!(PubNonce::from_slice(&bytes("020000000000000000000000000000000000000000000000000000000000000009")).is_err());
+        for aggnonce in [
+            "048465FCF0BBDBCF443AABCCE533D42B4B5A10966AC09A49655E8C42DAAB8FCD61037496A3CC86926D452CAFCFD55D25972CA1675D549310DE296BFF42F72EEEA8C9",
+            "028465FCF0BBDBCF443AABCCE533D42B4B5A10966AC09A49655E8C42DAAB8FCD61020000000000000000000000000000000000000000000000000000000000000009",
+            "028465FCF0BBDBCF443AABCCE533D42B4B5A10966AC09A49655E8C42DAAB8FCD6102FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFEFFFFFC30",
+        ] {
+            assert!(AggNonce::from_slice(&bytes(aggnonce)).is_err());
+        }
+
+        // Negated signature and wrong signer fail, a signature above the order does not parse
+        let session = session(&[0, 1, 2], &aggnonces[0]);
+        let negated = PartialSignature::from_slice(&bytes("97AC833ADCB1AFA42EBF9E0725616F3C9A0D5B614F6FE283CEAAA37A8FFAF406")).unwrap();
+        assert!(!session.verify_partial(&negated, &pnonces[0], &pubkeys[0]));
+        let wrong_signer = PartialSignature::from_slice(&bytes("68537CC5234E505BD14061F8DA9E90C220A181855FD8BDB7F127BB12403B4D3B")).unwrap();
+        assert!(!session.verify_partial(&wrong_signer, &pnonces[1], &pubkeys[1]));
+        assert!(PartialSignature::from_slice(&bytes("FFFFFFFFFFFFFFFFFFFFFFFFFFFFFFFEBAAEDCE6AF48A03BBFD25E8CD0364141")).is_err());
+    }
+
+    #[test]
+    fn test_scalar_reduction() {
+        let mut above = CURVE_ORDER;
+        above[31] += 1;
+        assert_eq!(ScalarN::reduce(above), ScalarN::one());
+        assert_eq!(ScalarN::reduce(CURVE_ORDER), ScalarN::ZERO);
+        assert_eq!(ScalarN::one().add(ScalarN::one().negate()), ScalarN::ZERO);
+    }
+}
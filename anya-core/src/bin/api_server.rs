@@ -1,7 +1,7 @@
 use actix_web::{
     web, App, HttpServer, HttpResponse, Responder, 
     dev::HttpServiceFactory, middleware::{Logger, NormalizePath},
-    error::ResponseError, http::StatusCode
+    error::{BlockingError, ResponseError}, http::StatusCode
 };
 use actix_cors::Cors;
 use serde::{Serialize, Deserialize};
@@ -13,6 +13,11 @@
 use std::env;
 use std::collections::HashMap;
 use std::time::{Duration, Instant};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use bitcoin::psbt::Psbt;
+use bitcoin::Network;
+use bitcoincore_rpc::Auth;
 use tokio::sync::RwLock;
 use tracing::{info, warn, error, debug};
 use jwt::{
@@ -26,6 +31,16 @@
     BitcoinNode, wallet::BitcoinWallet, transaction::TransactionService,
     Config as BitcoinConfig
 };
+use anya_bitcoin::payjoin::Receiver as PayjoinReceiver;
+use anya_core::bitcoin::error::{BitcoinError, BitcoinResult};
+use anya_core::bitcoin::wallet::node::{NodeChainSource, DEFAULT_NODE_WALLET};
+use anya_core::bitcoin::wallet::watch_only::ChainSource;
+use anya_core::bitcoin::wallet::multisig::{MultisigCoordinator, SessionStatus, SetupStatus, TokenStrength};
+use anya_core::bitcoin::wallet::transactions::TxOptions;
+use anya_core::layer2::proof::{HeaderChain, ProofVerifier};
+use anya_core::layer2::taproot_assets::universe::{
+    parse_hex32, HttpUniverse, ProofType, SyncSummary, UniverseError, UniverseId, UniverseServer, UniverseSource,
+};
 
 // CLI Arguments
 #[derive(Parser, Debug)]
@@ -53,10 +68,41 @@ struct Args {
 struct AppConfig {
     server: ServerConfig,
     bitcoin: BitcoinConfig,
+    /// RPC endpoint of the node; by default the network's local port with the cookie from the data directory
+    #[serde(default)]
+    node_rpc: Option<NodeRpcConfig>,
+    #[serde(default)]
+    universe: UniverseConfig,
     logging: LoggingConfig,
     security: SecurityConfig,
 }
 
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct NodeRpcConfig {
+    url: String,
+    user: String,
+    password: String,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+struct UniverseConfig {
+    /// File the accepted proof files are kept in
+    store: PathBuf,
+    /// Base URLs of the universes this one may sync from
+    #[serde(default)]
+    peers: Vec<String>,
+    /// Height of the first header followed from the node; by default the
+    /// last retarget boundary, so older proofs cannot be verified
+    #[serde(default)]
+    checkpoint_height: Option<u32>,
+}
+
+impl Default for UniverseConfig {
+    fn default() -> Self {
+        Self { store: PathBuf::from("data/universe.json"), peers: Vec::new(), checkpoint_height: None }
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize)]
 struct ServerConfig {
     port: u16,
@@ -81,6 +127,9 @@ struct AppState {
     config: AppConfig,
     bitcoin_node: Arc<RwLock<BitcoinNode>>,
     dwn_manager: Option<Arc<dyn dwn::DwnInterface + Send + Sync>>,
+    universe: UniverseServer,
+    multisig: MultisigCoordinator,
+    payjoin: Arc<PayjoinReceiver>,
     startup_time: DateTime<Utc>,
 }
 
@@ -113,6 +162,30 @@ fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
     }
 }
 
+impl From<UniverseError> for ApiError {
+    fn from(err: UniverseError) -> Self {
+        let code = match err {
+            UniverseError::NotFound(_) => StatusCode::NOT_FOUND,
+            UniverseError::InvalidProof(_) | UniverseError::Malformed(_) => StatusCode::BAD_REQUEST,
+            UniverseError::Transport(_) => StatusCode::BAD_GATEWAY,
+            UniverseError::Storage(_) => StatusCode::INTERNAL_SERVER_ERROR,
+        };
+        ApiError { message: err.to_string(), code }
+    }
+}
+
+impl From<BitcoinError> for ApiError {
+    fn from(err: BitcoinError) -> Self {
+        ApiError { message: err.to_string(), code: StatusCode::BAD_REQUEST }
+    }
+}
+
+impl From<BlockingError> for ApiError {
+    fn from(err: BlockingError) -> Self {
+        ApiError { message: err.to_string(), code: StatusCode::INTERNAL_SERVER_ERROR }
+    }
+}
+
 impl ResponseError for ApiError {
     fn status_code(&self) -> StatusCode {
         self.code
@@ -155,6 +228,18 @@ fn api_routes() -> impl HttpServiceFactory {
                         .service(web::resource("/asset/{contract_id}").route(web::get().to(get_asset_info)))
                 )
                 
+                // Taproot Assets universe endpoints
+                .service(
+                    web::scope("/universe")
+                        .service(web::resource("/roots").route(web::get().to(universe_roots)))
+                        .service(web::resource("/leaves/{asset_id}/{proof_type}").route(web::get().to(universe_leaves)))
+                        .service(web::resource("/proofs").route(web::post().to(universe_insert_proof)))
+                        .service(web::resource("/proofs/{asset_id}/{proof_type}/{leaf_key}").route(web::get().to(universe_leaf)))
+                        .service(web::resource("/multiverse/{proof_type}/{asset_id}").route(web::get().to(universe_multiverse_proof)))
+                        .service(web::resource("/supply/{asset_id}").route(web::get().to(universe_supply)))
+                        .service(web::resource("/sync").route(web::post().to(universe_sync)))
+                )
+                
                 // Web5 endpoints
                 .service(
                     web::scope("/web5")
@@ -187,6 +272,25 @@ fn api_routes() -> impl HttpServiceFactory {
                         .service(web::resource("/broadcast").route(web::post().to(broadcast_psbt)))
                 )
                 
+                // Multisig endpoints
+                .service(
+                    web::scope("/multisig")
+                        .service(web::resource("/setup").route(web::post().to(multisig_start_setup)))
+                        .service(web::resource("/setup/{name}").route(web::get().to(multisig_setup_status)))
+                        .service(web::resource("/setup/{name}/keys").route(web::post().to(multisig_submit_key_record)))
+                        .service(web::resource("/setup/{name}/descriptor").route(web::get().to(multisig_descriptor_record)))
+                        .service(web::resource("/wallets/{name}/scan").route(web::post().to(multisig_scan)))
+                        .service(web::resource("/wallets/{name}/spend").route(web::post().to(multisig_start_spend)))
+                        .service(web::resource("/sessions/{id}").route(web::get().to(multisig_session)))
+                        .service(web::resource("/sessions/{id}/psbt").route(web::post().to(multisig_submit_psbt)))
+                        .service(web::resource("/sessions/{id}/broadcast").route(web::post().to(multisig_broadcast)))
+                )
+                // PayJoin receiver (BIP78)
+                .service(
+                    web::scope("/payjoin")
+                        .service(web::resource("/receive/{wallet}").route(web::post().to(payjoin_receive)))
+                )
+                
                 // DWN endpoints
                 .service(
                     web::scope("/dwn")
@@ -262,6 +366,26 @@ struct TransferAssetRequest {
     metadata: Option<HashMap<String, String>>,
 }
 
+// Universe models
+#[derive(Serialize, Deserialize)]
+struct InsertProofRequest {
+    /// Hex-encoded proof file
+    proof_file: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct SyncUniverseRequest {
+    /// Base URL of one configured peer; every peer when omitted
+    #[serde(default)]
+    peer: Option<String>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct SupplyResponse {
+    asset_id: String,
+    total_supply: u64,
+}
+
 // Web5 models
 #[derive(Serialize, Deserialize)]
 struct DidResponse {
@@ -358,6 +482,105 @@ struct PsbtOutput {
     is_change: bool,
 }
 
+// Multisig models
+#[derive(Serialize, Deserialize)]
+struct StartSetupRequest {
+    name: String,
+    threshold: usize,
+    signers: usize,
+    /// Token size in bits: 0 for unencrypted records, 64 or 128
+    token_bits: Option<u16>,
+}
+
+#[derive(Serialize, Deserialize)]
+struct StartSetupResponse {
+    name: String,
+    /// BSMS token to hand to every signer
+    token: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct KeyRecordRequest {
+    /// Key record, encrypted under the setup token unless it is `00`
+    record: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct SetupStatusResponse {
+    threshold: usize,
+    signers: usize,
+    received: Vec<String>,
+    complete: bool,
+}
+
+impl From<SetupStatus> for SetupStatusResponse {
+    fn from(status: SetupStatus) -> Self {
+        SetupStatusResponse {
+            threshold: status.threshold,
+            signers: status.signers,
+            received: status.received,
+            complete: status.complete,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct DescriptorRecordResponse {
+    /// Descriptor record, encrypted under the setup token unless it is `00`
+    record: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct ScanResponse {
+    scripts_checked: usize,
+    transactions: usize,
+    balance: u64,
+}
+
+#[derive(Serialize, Deserialize)]
+struct StartSpendRequest {
+    recipients: Vec<(String, u64)>,
+    fee_rate: f64,
+}
+
+#[derive(Serialize, Deserialize)]
+struct SignedPsbtRequest {
+    psbt_base64: String,
+}
+
+#[derive(Serialize, Deserialize)]
+struct SessionStatusResponse {
+    threshold: usize,
+    /// Master fingerprints of the cosigners that signed
+    signed: Vec<String>,
+    pending: Vec<String>,
+    ready: bool,
+}
+
+impl From<SessionStatus> for SessionStatusResponse {
+    fn from(status: SessionStatus) -> Self {
+        SessionStatusResponse {
+            threshold: status.threshold,
+            signed: status.signed.iter().map(ToString::to_string).collect(),
+            pending: status.pending.iter().map(ToString::to_string).collect(),
+            ready: status.ready,
+        }
+    }
+}
+
+#[derive(Serialize, Deserialize)]
+struct SessionResponse {
+    session_id: String,
+    /// PSBT with every signature merged so far
+    psbt_base64: String,
+    status: SessionStatusResponse,
+}
+
+#[derive(Serialize, Deserialize)]
+struct BroadcastResponse {
+    txid: String,
+}
+
 // Web5 API request/response models
 #[derive(Serialize, Deserialize)]
 struct VerifyCredentialRequest {
@@ -482,6 +705,8 @@ fn load_config(config_path: Option<PathBuf>) -> Result<AppConfig> {
             host: "127.0.0.1".to_string(),
         },
         bitcoin: BitcoinConfig::default(),
+        node_rpc: None,
+        universe: UniverseConfig::default(),
         logging: LoggingConfig {
             level: "info".to_string(),
             dir: "logs".to_string(),
@@ -495,6 +720,32 @@ fn load_config(config_path: Option<PathBuf>) -> Result<AppConfig> {
     Ok(default_config)
 }
 
+// Node RPC url and credentials, defaulting to the local node's cookie
+fn node_rpc(config: &AppConfig) -> (String, Auth) {
+    match &config.node_rpc {
+        Some(rpc) => (rpc.url.clone(), Auth::UserPass(rpc.user.clone(), rpc.password.clone())),
+        None => {
+            let (port, subdir) = match config.bitcoin.network {
+                Network::Bitcoin => (8332, ""),
+                Network::Signet => (38332, "signet"),
+                Network::Regtest => (18443, "regtest"),
+                _ => (18332, "testnet3"),
+            };
+            let cookie = config.bitcoin.datadir.join(subdir).join(".cookie");
+            (format!("http://127.0.0.1:{}", port), Auth::CookieFile(cookie))
+        }
+    }
+}
+
+// How often the universe's headers are caught up with the node
+const HEADER_SYNC_INTERVAL: Duration = Duration::from_secs(60);
+
+// Chain source of the multisig wallets and headers of the universe, read from the node over RPC
+fn node_chain_source(config: &AppConfig) -> BitcoinResult<Arc<NodeChainSource>> {
+    let (url, auth) = node_rpc(config);
+    Ok(Arc::new(NodeChainSource::new(&url, auth, DEFAULT_NODE_WALLET)?))
+}
+
 // Setup logging
 fn setup_logging(level: &str) {
     let log_level = match level.to_lowercase().as_str() {
@@ -547,7 +798,10 @@ async fn create_wallet(
         use_taproot: req.use_taproot.unwrap_or(true),
     };
     
+    // The node checks the originals of payjoins the wallet receives
+    let (url, auth) = node_rpc(&data.config);
     let wallet = BitcoinWallet::new(config).await
+        .and_then(|wallet| wallet.with_node(&url, auth))
         .map_err(|e| Error::Internal(e.to_string()))?;
     
     let balance = wallet.get_balance().await
@@ -613,6 +867,211 @@ async fn get_new_address(
     })))
 }
 
+// Taproot Assets universe endpoints
+fn universe_id(asset_id: &str, proof_type: &str) -> Result<UniverseId, ApiError> {
+    Ok(UniverseId { asset_id: parse_hex32(asset_id)?, proof_type: proof_type.parse()? })
+}
+
+async fn universe_roots(data: web::Data<AppState>) -> Result<HttpResponse, ApiError> {
+    Ok(HttpResponse::Ok().json(data.universe.roots().await?))
+}
+
+async fn universe_leaves(
+    data: web::Data<AppState>,
+    path: web::Path<(String, String)>,
+) -> Result<HttpResponse, ApiError> {
+    let (asset_id, proof_type) = path.into_inner();
+    let keys = data.universe.leaf_keys(&universe_id(&asset_id, &proof_type)?).await?;
+    Ok(HttpResponse::Ok().json(keys.iter().map(hex::encode).collect::<Vec<_>>()))
+}
+
+async fn universe_leaf(
+    data: web::Data<AppState>,
+    path: web::Path<(String, String, String)>,
+) -> Result<HttpResponse, ApiError> {
+    let (asset_id, proof_type, leaf_key) = path.into_inner();
+    let leaf = data.universe.leaf(&universe_id(&asset_id, &proof_type)?, &parse_hex32(&leaf_key)?).await?;
+    Ok(HttpResponse::Ok().json(leaf))
+}
+
+async fn universe_insert_proof(
+    data: web::Data<AppState>,
+    req: web::Json<InsertProofRequest>,
+) -> Result<HttpResponse, ApiError> {
+    Ok(HttpResponse::Ok().json(data.universe.insert_hex(&req.proof_file)?))
+}
+
+async fn universe_multiverse_proof(
+    data: web::Data<AppState>,
+    path: web::Path<(String, String)>,
+) -> Result<HttpResponse, ApiError> {
+    let (proof_type, asset_id) = path.into_inner();
+    let proof_type: ProofType = proof_type.parse()?;
+    Ok(HttpResponse::Ok().json(data.universe.multiverse_proof(&parse_hex32(&asset_id)?, proof_type)?))
+}
+
+async fn universe_supply(
+    data: web::Data<AppState>,
+    path: web::Path<String>,
+) -> Result<HttpResponse, ApiError> {
+    let asset_id = path.into_inner();
+    let total_supply = data.universe.total_supply(&parse_hex32(&asset_id)?)?;
+    Ok(HttpResponse::Ok().json(SupplyResponse { asset_id, total_supply }))
+}
+
+async fn universe_sync(
+    data: web::Data<AppState>,
+    req: web::Json<SyncUniverseRequest>,
+) -> Result<HttpResponse, ApiError> {
+    // Only configured peers are fetched from, never a host named by the client
+    let peers = &data.config.universe.peers;
+    let peers: Vec<&String> = match &req.peer {
+        Some(peer) => vec![peers.iter().find(|configured| *configured == peer).ok_or_else(|| ApiError {
+            message: format!("Not a configured universe peer: {}", peer),
+            code: StatusCode::FORBIDDEN,
+        })?],
+        None => peers.iter().collect(),
+    };
+
+    let mut summary = SyncSummary::default();
+    for peer in peers {
+        let synced = data.universe.sync_from(&HttpUniverse::new(peer, Duration::from_secs(30))).await?;
+        summary.trees += synced.trees;
+        summary.leaves += synced.leaves;
+    }
+    Ok(HttpResponse::Ok().json(summary))
+}
+
+// Multisig endpoints
+fn encode_psbt(psbt: &Psbt) -> String {
+    BASE64.encode(psbt.serialize())
+}
+
+fn decode_psbt(psbt_base64: &str) -> Result<Psbt, ApiError> {
+    let bad_request = |message: String| ApiError { message, code: StatusCode::BAD_REQUEST };
+    let bytes = BASE64.decode(psbt_base64).map_err(|e| bad_request(format!("Invalid base64: {}", e)))?;
+    Psbt::deserialize(&bytes).map_err(|e| bad_request(format!("Invalid PSBT: {}", e)))
+}
+
+fn session_response(data: &AppState, session_id: String) -> Result<SessionResponse, ApiError> {
+    Ok(SessionResponse {
+        psbt_base64: encode_psbt(&data.multisig.session_psbt(&session_id)?),
+        status: data.multisig.session_status(&session_id)?.into(),
+        session_id,
+    })
+}
+
+async fn multisig_start_setup(
+    data: web::Data<AppState>,
+    req: web::Json<StartSetupRequest>,
+) -> Result<HttpResponse, ApiError> {
+    let strength = match req.token_bits.unwrap_or(64) {
+        0 => TokenStrength::None,
+        64 => TokenStrength::Standard,
+        128 => TokenStrength::Extended,
+        bits => return Err(ApiError { message: format!("Unsupported token size: {} bits", bits), code: StatusCode::BAD_REQUEST }),
+    };
+    let token = data.multisig.start_setup(&req.name, req.threshold, req.signers, strength)?;
+    Ok(HttpResponse::Ok().json(StartSetupResponse { name: req.name.clone(), token: token.to_string() }))
+}
+
+async fn multisig_setup_status(
+    data: web::Data<AppState>,
+    path: web::Path<String>,
+) -> Result<HttpResponse, ApiError> {
+    Ok(HttpResponse::Ok().json(SetupStatusResponse::from(data.multisig.setup_status(&path.into_inner())?)))
+}
+
+async fn multisig_submit_key_record(
+    data: web::Data<AppState>,
+    path: web::Path<String>,
+    req: web::Json<KeyRecordRequest>,
+) -> Result<HttpResponse, ApiError> {
+    let status = data.multisig.submit_key_record(&path.into_inner(), &req.record)?;
+    Ok(HttpResponse::Ok().json(SetupStatusResponse::from(status)))
+}
+
+async fn multisig_descriptor_record(
+    data: web::Data<AppState>,
+    path: web::Path<String>,
+) -> Result<HttpResponse, ApiError> {
+    // The first call imports the descriptor into the node, which rescans
+    let record = web::block(move || data.multisig.descriptor_record(&path.into_inner())).await??;
+    Ok(HttpResponse::Ok().json(DescriptorRecordResponse { record }))
+}
+
+async fn multisig_scan(
+    data: web::Data<AppState>,
+    path: web::Path<String>,
+) -> Result<HttpResponse, ApiError> {
+    let wallet = data.multisig.wallet(&path.into_inner())?;
+    let scanned = wallet.clone();
+    let summary = web::block(move || scanned.scan()).await??;
+    Ok(HttpResponse::Ok().json(ScanResponse {
+        scripts_checked: summary.scripts_checked,
+        transactions: summary.transactions,
+        balance: wallet.balance().confirmed,
+    }))
+}
+
+async fn multisig_start_spend(
+    data: web::Data<AppState>,
+    path: web::Path<String>,
+    req: web::Json<StartSpendRequest>,
+) -> Result<HttpResponse, ApiError> {
+    let (session_id, _) = data.multisig.start_spend(&path.into_inner(), &req.recipients, req.fee_rate, &TxOptions::default())?;
+    Ok(HttpResponse::Ok().json(session_response(&data, session_id)?))
+}
+
+async fn multisig_session(
+    data: web::Data<AppState>,
+    path: web::Path<String>,
+) -> Result<HttpResponse, ApiError> {
+    Ok(HttpResponse::Ok().json(session_response(&data, path.into_inner())?))
+}
+
+async fn multisig_submit_psbt(
+    data: web::Data<AppState>,
+    path: web::Path<String>,
+    req: web::Json<SignedPsbtRequest>,
+) -> Result<HttpResponse, ApiError> {
+    let session_id = path.into_inner();
+    data.multisig.submit_psbt(&session_id, decode_psbt(&req.psbt_base64)?)?;
+    Ok(HttpResponse::Ok().json(session_response(&data, session_id)?))
+}
+
+async fn multisig_broadcast(
+    data: web::Data<AppState>,
+    path: web::Path<String>,
+) -> Result<HttpResponse, ApiError> {
+    let txid = web::block(move || data.multisig.broadcast(&path.into_inner())).await??;
+    Ok(HttpResponse::Ok().json(BroadcastResponse { txid: txid.to_string() }))
+}
+
+// PayJoin endpoints
+async fn payjoin_wallet(data: &AppState, name: &str) -> Result<Arc<BitcoinWallet>, ApiError> {
+    data.wallet_manager.get_wallet(name).await
+        .ok_or_else(|| ApiError { message: format!("Wallet not found: {}", name), code: StatusCode::NOT_FOUND })
+}
+
+/// BIP78 endpoint: takes the original PSBT as text and answers with the
+/// proposal, or with the BIP78 error JSON
+async fn payjoin_receive(
+    data: web::Data<AppState>,
+    path: web::Path<String>,
+    req: HttpRequest,
+    body: String,
+) -> Result<HttpResponse, ApiError> {
+    let wallet = payjoin_wallet(&data, &path.into_inner()).await?;
+    match data.payjoin.process(wallet.as_ref(), req.query_string(), &body).await {
+        Ok(proposal) => Ok(HttpResponse::Ok().content_type("text/plain").body(proposal)),
+        Err(e) => {
+            warn!("Payjoin request rejected: {}", e);
+            Ok(HttpResponse::BadRequest().content_type("application/json").body(e.to_json()))
+        }
+    }
+}
+
 // RGB asset endpoints
 async fn list_assets(
     data: web::Data<AppState>,
@@ -1141,12 +1600,48 @@ async fn main() -> std::io::Result<()> {
             .expect("Failed to initialize Bitcoin node")
     ));
     
+    // Multisig wallets scan and broadcast through the node, when it is reachable
+    let node = match node_chain_source(&config) {
+        Ok(node) => Some(node),
+        Err(e) => {
+            warn!("Multisig wallets and the universe cannot reach the node RPC: {}", e);
+            None
+        }
+    };
+    
+    // Universe proofs are verified against the headers followed from the node
+    let headers = ProofVerifier::new(HeaderChain::with_params(config.bitcoin.network));
+    if let Some(node) = node.clone() {
+        let headers = headers.clone();
+        let checkpoint = config.universe.checkpoint_height;
+        actix_web::rt::spawn(async move {
+            let mut interval = tokio::time::interval(HEADER_SYNC_INTERVAL);
+            loop {
+                interval.tick().await;
+                let (node, headers) = (node.clone(), headers.clone());
+                match web::block(move || headers.sync_headers(&*node, checkpoint)).await {
+                    Ok(Ok(_)) => {}
+                    Ok(Err(e)) => warn!("Header sync from the node failed: {}", e),
+                    Err(e) => warn!("Header sync task failed: {}", e),
+                }
+            }
+        });
+    }
+    if let Some(dir) = config.universe.store.parent() {
+        fs::create_dir_all(dir)?;
+    }
+    let universe = UniverseServer::open(&config.universe.store, headers)
+        .expect("Failed to open the universe store");
+    
     // Create application state
     let app_state = web::Data::new(AppState {
         core: AnyaCore::default(),
         config: config.clone(),
         bitcoin_node: bitcoin_node.clone(),
         dwn_manager: None,
+        universe,
+        multisig: MultisigCoordinator::new(config.bitcoin.network, node.map(|node| node as Arc<dyn ChainSource>)),
+        payjoin: Arc::new(PayjoinReceiver::new()),
         startup_time: Utc::now(),
     });
     
@@ -5,8 +5,12 @@
 use bitcoin::Txid;
 use bitcoin::secp256k1::PublicKey;
 
-use crate::common::error::AnyaResult;
+use crate::{AnyaError, AnyaResult};
 use super::oracle::{OracleInfo, OracleAnnouncement};
+use super::numeric::{
+    DigitDecompositionDescriptor, NumericOutcomeContract, PayoutCurve, PayoutCurvePiece, PayoutPoint,
+    RoundingIntervals,
+};
 
 /// Represents the current state of a contract
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -125,6 +129,16 @@ pub fn is_ready_for_execution(&self) -> bool {
     pub fn total_collateral(&self) -> u64 {
         self.descriptor.offer_collateral + self.descriptor.accept_collateral
     }
+    
+    /// Compiles the execution paths of the payout function, replacing any computed before
+    pub fn compile_execution_paths(&mut self) -> AnyaResult<()> {
+        let paths = self.descriptor.payout_function.execution_paths(self.total_collateral())?;
+        self.execution_paths = paths.into_iter()
+            .map(|path| (path.outcome.clone(), path))
+            .collect();
+        self.updated_at = Utc::now();
+        Ok(())
+    }
 }
 
 /// Represents the descriptor of a contract with all its terms
@@ -167,7 +181,7 @@ pub struct ContractExecutionPath {
     /// Outcome value
     pub outcome: String,
     
-    /// CET transaction (hex)
+    /// CET transaction (hex), empty until built against the funding output
     pub cet_hex: String,
     
     /// Offering party payout amount
@@ -175,6 +189,10 @@ pub struct ContractExecutionPath {
     
     /// Accepting party payout amount
     pub accept_payout: u64,
+    
+    /// Oracle digit prefixes unlocking the CET, one adaptor signature each;
+    /// empty for enumerated outcomes
+    pub digit_prefixes: Vec<Vec<usize>>,
 }
 
 /// Defines how funds should be distributed based on oracle outcomes
@@ -205,6 +223,18 @@ pub enum PayoutFunction {
         curve_points: Vec<(i64, u8)>,
     },
     
+    /// Numeric outcome attested digit by digit, compiled into a compressed CET set
+    DigitDecomposition {
+        /// How the oracle decomposes the outcome into digits
+        descriptor: DigitDecompositionDescriptor,
+        
+        /// Offer party payout curve (in sats)
+        payout_curve: PayoutCurve,
+        
+        /// Payout rounding per outcome interval
+        rounding_intervals: RoundingIntervals,
+    },
+    
     /// Enumerated outcomes with specific payouts
     Enumerated {
         /// Map of outcome -> (offer_payout, accept_payout) in sats
@@ -212,6 +242,134 @@ pub enum PayoutFunction {
     },
 }
 
+impl PayoutFunction {
+    /// Execution paths of the payout function, splitting `total_collateral`
+    ///
+    /// Enumerated and binary outcomes get one path each. Numeric outcomes are
+    /// compiled through [`super::numeric`] into a compressed CET set, where a
+    /// path covers a range of outcomes sharing the same rounded payout.
+    pub fn execution_paths(&self, total_collateral: u64) -> AnyaResult<Vec<ContractExecutionPath>> {
+        let enumerated = |outcome: &str, offer_payout: u64| -> AnyaResult<ContractExecutionPath> {
+            let accept_payout = total_collateral.checked_sub(offer_payout).ok_or_else(|| {
+                AnyaError::Bitcoin(format!("Payout for {} exceeds the collateral", outcome))
+            })?;
+            Ok(ContractExecutionPath {
+                outcome: outcome.to_string(),
+                cet_hex: String::new(),
+                offer_payout,
+                accept_payout,
+                digit_prefixes: Vec::new(),
+            })
+        };
+        
+        match self {
+            PayoutFunction::Binary { win_condition, offer_win_amount, accept_win_amount } => Ok(vec![
+                enumerated(win_condition, *offer_win_amount)?,
+                enumerated(&format!("!{}", win_condition), total_collateral.saturating_sub(*accept_win_amount))?,
+            ]),
+            PayoutFunction::Enumerated { outcomes } => {
+                let mut paths = outcomes.iter()
+                    .map(|(outcome, (offer_payout, accept_payout))| {
+                        if offer_payout + accept_payout != total_collateral {
+                            return Err(AnyaError::Bitcoin(format!(
+                                "Payouts for {} do not add up to the collateral", outcome
+                            )));
+                        }
+                        enumerated(outcome, *offer_payout)
+                    })
+                    .collect::<AnyaResult<Vec<_>>>()?;
+                paths.sort_by(|a, b| a.outcome.cmp(&b.outcome));
+                Ok(paths)
+            }
+            PayoutFunction::Numeric { unit, range, curve_points } => {
+                let (descriptor, payout_curve) = percentage_curve(unit, *range, curve_points, total_collateral)?;
+                numeric_paths(descriptor, payout_curve, RoundingIntervals::uniform(1), total_collateral, range.0)
+            }
+            PayoutFunction::DigitDecomposition { descriptor, payout_curve, rounding_intervals } => numeric_paths(
+                descriptor.clone(),
+                payout_curve.clone(),
+                rounding_intervals.clone(),
+                total_collateral,
+                0,
+            ),
+        }
+    }
+}
+
+/// Compiles a numeric contract into one execution path per CET, labelled by
+/// the outcome range it covers (shifted back by `offset`)
+fn numeric_paths(
+    descriptor: DigitDecompositionDescriptor,
+    payout_curve: PayoutCurve,
+    rounding_intervals: RoundingIntervals,
+    total_collateral: u64,
+    offset: i64,
+) -> AnyaResult<Vec<ContractExecutionPath>> {
+    let contract = NumericOutcomeContract::new(descriptor, payout_curve, rounding_intervals, total_collateral)
+        .map_err(|e| AnyaError::Bitcoin(e.to_string()))?;
+    let cets = contract.compile_cets().map_err(|e| AnyaError::Bitcoin(e.to_string()))?;
+    
+    Ok(cets.into_iter()
+        .map(|cet| ContractExecutionPath {
+            outcome: format!(
+                "{}..={}",
+                offset + cet.range.start as i64,
+                offset + cet.range.end as i64
+            ),
+            cet_hex: String::new(),
+            offer_payout: cet.range.offer_payout,
+            accept_payout: cet.accept_payout,
+            digit_prefixes: cet.digit_prefixes,
+        })
+        .collect())
+}
+
+/// Turns percentage curve points over `range` into a base-2 payout curve
+/// starting at outcome 0, held flat beyond the first and last points
+fn percentage_curve(
+    unit: &str,
+    range: (i64, i64),
+    curve_points: &[(i64, u8)],
+    total_collateral: u64,
+) -> AnyaResult<(DigitDecompositionDescriptor, PayoutCurve)> {
+    if range.1 <= range.0 {
+        return Err(AnyaError::Bitcoin("Numeric range must not be empty".to_string()));
+    }
+    if curve_points.is_empty() || curve_points.iter().any(|(_, pct)| *pct > 100) {
+        return Err(AnyaError::Bitcoin("Curve points must be percentages between 0 and 100".to_string()));
+    }
+    
+    let span = (range.1 - range.0) as u64;
+    let num_digits = (u64::BITS - span.leading_zeros()) as u16;
+    let descriptor = DigitDecompositionDescriptor::binary(num_digits, unit);
+    let max_outcome = descriptor.max_outcome();
+    
+    let mut points: Vec<PayoutPoint> = curve_points.iter()
+        .map(|(outcome, pct)| {
+            let outcome = (outcome.clamp(&range.0, &range.1) - range.0) as u64;
+            PayoutPoint::new(outcome, total_collateral * *pct as u64 / 100)
+        })
+        .collect();
+    points.sort_by_key(|p| p.event_outcome);
+    points.dedup_by_key(|p| p.event_outcome);
+    
+    if let Some(first) = points.first().copied() {
+        if first.event_outcome > 0 {
+            points.insert(0, PayoutPoint::new(0, first.outcome_payout));
+        }
+    }
+    if let Some(last) = points.last().copied() {
+        if last.event_outcome < max_outcome {
+            points.push(PayoutPoint::new(max_outcome, last.outcome_payout));
+        }
+    }
+    
+    let pieces = points.windows(2)
+        .map(|w| PayoutCurvePiece::linear(w[0], w[1]))
+        .collect();
+    Ok((descriptor, PayoutCurve::new(pieces)))
+}
+
 /// Parameters for creating a new contract
 #[derive(Debug, Clone)]
 pub struct ContractParameters {
@@ -241,4 +399,59 @@ pub struct ContractParameters {
     
     /// Additional metadata
     pub metadata: HashMap<String, String>,
-} 
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    
+    #[test]
+    fn test_numeric_payouts_compile_to_compressed_cets() {
+        let collateral = 100_000_000;
+        let max = (1u64 << 17) - 1;
+        let payout_function = PayoutFunction::DigitDecomposition {
+            descriptor: DigitDecompositionDescriptor::binary(17, "USD"),
+            payout_curve: PayoutCurve::new(vec![
+                PayoutCurvePiece::linear(PayoutPoint::new(0, 0), PayoutPoint::new(20_000, 0)),
+                PayoutCurvePiece::linear(PayoutPoint::new(20_000, 0), PayoutPoint::new(60_000, collateral)),
+                PayoutCurvePiece::linear(PayoutPoint::new(60_000, collateral), PayoutPoint::new(max, collateral)),
+            ]),
+            rounding_intervals: RoundingIntervals::uniform(1_000_000),
+        };
+        
+        let paths = payout_function.execution_paths(collateral).unwrap();
+        assert!(paths.len() <= 102);
+        assert_eq!(paths.first().unwrap().outcome, "0..=20199");
+        assert_eq!(paths.last().unwrap().offer_payout, collateral);
+        assert!(paths.iter().all(|p| p.offer_payout + p.accept_payout == collateral && !p.digit_prefixes.is_empty()));
+    }
+    
+    #[test]
+    fn test_percentage_curve_goes_through_numeric() {
+        let payout_function = PayoutFunction::Numeric {
+            unit: "USD".to_string(),
+            range: (10_000, 50_000),
+            curve_points: vec![(20_000, 0), (40_000, 100)],
+        };
+        
+        let paths = payout_function.execution_paths(1_000).unwrap();
+        assert_eq!(paths.first().unwrap().outcome, "10000..=20009");
+        assert_eq!(paths.first().unwrap().offer_payout, 0);
+        assert_eq!(paths.last().unwrap().offer_payout, 1_000);
+        // One path per satoshi of payout instead of one per outcome
+        assert_eq!(paths.len(), 1_001);
+    }
+    
+    #[test]
+    fn test_enumerated_payouts_must_add_up() {
+        let outcomes = HashMap::from([
+            ("rain".to_string(), (700, 300)),
+            ("sun".to_string(), (200, 800)),
+        ]);
+        let paths = PayoutFunction::Enumerated { outcomes }.execution_paths(1_000).unwrap();
+        assert_eq!(paths.iter().map(|p| p.outcome.as_str()).collect::<Vec<_>>(), vec!["rain", "sun"]);
+        
+        let outcomes = HashMap::from([("rain".to_string(), (700, 400))]);
+        assert!(PayoutFunction::Enumerated { outcomes }.execution_paths(1_000).is_err());
+    }
+}
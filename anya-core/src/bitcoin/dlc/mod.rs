@@ -11,6 +11,10 @@
 use bitcoin::util::psbt::PartiallySignedTransaction;
 use std::collections::HashMap;
 
+pub mod contract;
+pub mod numeric;
+pub mod oracle;
+
 /// DLC Contract structure
 /// 
 /// Represents a Discrete Log Contract with all necessary components
@@ -31,6 +35,8 @@ pub struct DLCContract {
     pub funding_tx: Option<Transaction>,
     /// Contract execution transaction templates (one per outcome)
     pub execution_txs: HashMap<String, Transaction>,
+    /// Compressed CET set of a numeric-outcome contract, empty for enumerated outcomes
+    pub numeric_cets: Vec<numeric::NumericCet>,
 }
 
 /// DLC Oracle structure
@@ -99,11 +105,57 @@ pub fn create_contract(
         timelock,
         funding_tx: None,
         execution_txs: HashMap::new(),
+        numeric_cets: Vec::new(),
     };
     
     Ok(contract)
 }
 
+/// Create a numeric-outcome DLC contract
+/// 
+/// The payout curve is compiled into a compressed CET set: each CET covers a
+/// range of outcomes and is unlocked by the oracle's signatures on a digit
+/// prefix, so price-like events do not need one CET per outcome.
+pub fn create_numeric_contract(
+    oracle_pubkey: &PublicKey,
+    terms: &numeric::NumericOutcomeContract,
+    timelock: u32,
+) -> Result<DLCContract, &'static str> {
+    if terms.total_collateral % 2 != 0 {
+        return Err("Total collateral must split evenly between the parties");
+    }
+    
+    let numeric_cets = terms.compile_cets()
+        .map_err(|_| "Numeric contract needs too many adaptor signatures")?;
+    
+    // Contract ID commits to every CET range and payout
+    let mut contract_params = Vec::new();
+    contract_params.extend_from_slice(&oracle_pubkey.serialize());
+    for cet in &numeric_cets {
+        contract_params.extend_from_slice(&cet.range.start.to_le_bytes());
+        contract_params.extend_from_slice(&cet.range.end.to_le_bytes());
+        contract_params.extend_from_slice(&cet.range.offer_payout.to_le_bytes());
+    }
+    contract_params.extend_from_slice(&terms.total_collateral.to_le_bytes());
+    contract_params.extend_from_slice(&timelock.to_le_bytes());
+    
+    Ok(DLCContract {
+        contract_id: sha256::Hash::hash(&contract_params).into_inner(),
+        oracle_pubkey: *oracle_pubkey,
+        outcomes: Vec::new(),
+        collateral_amount: terms.total_collateral / 2,
+        timelock,
+        funding_tx: None,
+        execution_txs: HashMap::new(),
+        numeric_cets,
+    })
+}
+
+/// Key of a numeric CET's execution transaction, the outcome range it covers
+pub fn numeric_outcome_label(cet: &numeric::NumericCet) -> String {
+    format!("{}..={}", cet.range.start, cet.range.end)
+}
+
 /// Create a DLC oracle
 /// 
 /// Creates a new DLC oracle with the specified parameters.
@@ -243,12 +295,23 @@ pub fn create_execution_transactions(
     
     let mut execution_txs = HashMap::new();
     
+    // Numeric contracts pay exact amounts per CET, enumerated ones a ratio per outcome
+    let total_collateral = contract.collateral_amount * 2;
+    let payouts: Vec<(String, u64, u64)> = if contract.numeric_cets.is_empty() {
+        contract.outcomes.iter()
+            .map(|(outcome, payout_ratio)| {
+                let party_a_payout = (total_collateral as f64 * (*payout_ratio as f64 / 100.0)) as u64;
+                (outcome.clone(), party_a_payout, total_collateral - party_a_payout)
+            })
+            .collect()
+    } else {
+        contract.numeric_cets.iter()
+            .map(|cet| (numeric_outcome_label(cet), cet.range.offer_payout, cet.accept_payout))
+            .collect()
+    };
+    
     // Create an execution transaction for each outcome
-    for (outcome, payout_ratio) in &contract.outcomes {
-        // Calculate payouts based on the ratio
-        let total_collateral = contract.collateral_amount * 2;
-        let party_a_payout = (total_collateral as f64 * (*payout_ratio as f64 / 100.0)) as u64;
-        let party_b_payout = total_collateral - party_a_payout;
+    for (outcome, party_a_payout, party_b_payout) in payouts {
         
         // Create inputs
         let input = TxIn {
@@ -290,7 +353,7 @@ pub fn create_execution_transactions(
             output: outputs,
         };
         
-        execution_txs.insert(outcome.clone(), execution_tx);
+        execution_txs.insert(outcome, execution_tx);
     }
     
     // Store the execution transactions in the contract
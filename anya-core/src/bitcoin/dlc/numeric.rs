@@ -0,0 +1,880 @@
+// src/bitcoin/dlc/numeric.rs
+
+//! Numeric-outcome DLCs
+//!
+//! Numeric events (prices, rates, scores) are attested digit by digit: the
+//! oracle announces one nonce per digit of the base-`b` decomposition of the
+//! outcome and signs each digit separately. A CET then only needs an adaptor
+//! signature for the digit *prefix* shared by every outcome in its payout
+//! range, which keeps the number of CETs and adaptor signatures logarithmic in
+//! the size of the outcome domain instead of linear.
+//!
+//! Payout curves are built from polynomial and hyperbola pieces and rounded
+//! per interval, so that neighbouring outcomes collapse into the same CET.
+
+use std::collections::HashMap;
+
+use bitcoin::hashes::{sha256, Hash};
+use bitcoin::secp256k1::{
+    schnorr, Keypair, Message, Parity, PublicKey, Scalar, Secp256k1, SecretKey, XOnlyPublicKey,
+};
+
+use crate::bitcoin::error::{BitcoinError, BitcoinResult};
+
+/// Tag used to hash each attested digit before signing
+const ATTESTATION_TAG: &[u8] = b"DLC/oracle/attestation/v0";
+
+/// BIP340 challenge tag
+const CHALLENGE_TAG: &[u8] = b"BIP0340/challenge";
+
+/// Largest outcome domain we are willing to evaluate the payout curve over
+pub const MAX_OUTCOME_DOMAIN: u64 = 1 << 24;
+
+/// Default upper bound on adaptor signatures per contract
+pub const DEFAULT_MAX_ADAPTOR_SIGNATURES: usize = 10_000;
+
+/// Describes how the oracle decomposes a numeric outcome into digits
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DigitDecompositionDescriptor {
+    /// Base of the decomposition (2 for binary)
+    pub base: u16,
+
+    /// Number of digits attested (outcome domain is `0..base^num_digits`)
+    pub num_digits: u16,
+
+    /// Unit of the outcome (e.g. "USD")
+    pub unit: String,
+
+    /// Power of ten the attested integer is scaled by
+    pub precision: i32,
+}
+
+impl DigitDecompositionDescriptor {
+    /// Creates a base-2 descriptor
+    pub fn binary(num_digits: u16, unit: &str) -> Self {
+        Self {
+            base: 2,
+            num_digits,
+            unit: unit.to_string(),
+            precision: 0,
+        }
+    }
+
+    /// Validates the descriptor
+    pub fn validate(&self) -> BitcoinResult<()> {
+        if self.base < 2 {
+            return Err(BitcoinError::DLC("Decomposition base must be at least 2".to_string()));
+        }
+
+        if self.num_digits == 0 {
+            return Err(BitcoinError::DLC("At least one digit is required".to_string()));
+        }
+
+        match self.domain_size() {
+            Some(size) if size <= MAX_OUTCOME_DOMAIN => Ok(()),
+            _ => Err(BitcoinError::DLC(format!(
+                "Outcome domain {}^{} exceeds the supported maximum of {}",
+                self.base, self.num_digits, MAX_OUTCOME_DOMAIN
+            ))),
+        }
+    }
+
+    /// Number of distinct outcomes, `base^num_digits`
+    pub fn domain_size(&self) -> Option<u64> {
+        (self.base as u64).checked_pow(self.num_digits as u32)
+    }
+
+    /// Largest attestable outcome
+    pub fn max_outcome(&self) -> u64 {
+        self.domain_size().unwrap_or(u64::MAX) - 1
+    }
+}
+
+/// Decomposes `value` into `num_digits` base-`base` digits, most significant first
+pub fn decompose(value: u64, base: u16, num_digits: u16) -> Vec<usize> {
+    let base = base as u64;
+    let mut digits = vec![0usize; num_digits as usize];
+    let mut remaining = value;
+
+    for digit in digits.iter_mut().rev() {
+        *digit = (remaining % base) as usize;
+        remaining /= base;
+    }
+
+    digits
+}
+
+/// Recomposes digits (most significant first) into a value
+pub fn compose(digits: &[usize], base: u16) -> u64 {
+    digits.iter().fold(0u64, |acc, d| acc * base as u64 + *d as u64)
+}
+
+/// Covers the inclusive range `[start, end]` with the minimal set of digit prefixes
+///
+/// Each prefix matches every outcome whose decomposition starts with it, so the
+/// returned prefixes are exactly the adaptor points a CET for this range needs.
+pub fn group_by_ignoring_digits(start: u64, end: u64, base: u16, num_digits: u16) -> Vec<Vec<usize>> {
+    let b = base as u64;
+    let mut prefixes = Vec::new();
+    let mut current = start;
+
+    while current <= end {
+        // Grow the block while it stays aligned and inside the range
+        let mut ignored = 0u16;
+        let mut block = 1u64;
+        while ignored < num_digits {
+            let next = block * b;
+            if !current.is_multiple_of(next) || current + next - 1 > end {
+                break;
+            }
+            block = next;
+            ignored += 1;
+        }
+
+        let digits = decompose(current, base, num_digits);
+        prefixes.push(digits[..(num_digits - ignored) as usize].to_vec());
+
+        match current.checked_add(block) {
+            Some(next) => current = next,
+            None => break,
+        }
+    }
+
+    prefixes
+}
+
+/// A point on a payout curve
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct PayoutPoint {
+    /// Outcome value
+    pub event_outcome: u64,
+
+    /// Offer party payout in satoshis at this outcome
+    pub outcome_payout: u64,
+
+    /// Fractional payout in 1/2^16 satoshi units
+    pub extra_precision: u16,
+}
+
+impl PayoutPoint {
+    /// Creates a point without extra precision
+    pub fn new(event_outcome: u64, outcome_payout: u64) -> Self {
+        Self {
+            event_outcome,
+            outcome_payout,
+            extra_precision: 0,
+        }
+    }
+
+    fn payout(&self) -> f64 {
+        self.outcome_payout as f64 + self.extra_precision as f64 / 65536.0
+    }
+}
+
+/// A piece of a payout curve
+#[derive(Debug, Clone, PartialEq)]
+pub enum PayoutCurvePiece {
+    /// Polynomial interpolating the given points (two points give a line)
+    Polynomial {
+        /// Interpolation points, ordered by outcome; the first and last bound the piece
+        points: Vec<PayoutPoint>,
+    },
+
+    /// Hyperbola `y = c * f(x) + d / f(x) + translate_payout`, with
+    /// `f(x) = ((x - translate_outcome) ± sqrt((x - translate_outcome)^2 - 4ab)) / 2a`
+    Hyperbola {
+        /// Left bound of the piece
+        left_end_point: PayoutPoint,
+        /// Right bound of the piece
+        right_end_point: PayoutPoint,
+        /// Whether to take the positive square root
+        use_positive_piece: bool,
+        /// Outcome translation
+        translate_outcome: f64,
+        /// Payout translation
+        translate_payout: f64,
+        /// Hyperbola parameter a
+        a: f64,
+        /// Hyperbola parameter b
+        b: f64,
+        /// Hyperbola parameter c
+        c: f64,
+        /// Hyperbola parameter d
+        d: f64,
+    },
+}
+
+impl PayoutCurvePiece {
+    /// Linear piece between two points
+    pub fn linear(from: PayoutPoint, to: PayoutPoint) -> Self {
+        PayoutCurvePiece::Polynomial { points: vec![from, to] }
+    }
+
+    /// First outcome covered by this piece
+    pub fn start(&self) -> u64 {
+        match self {
+            PayoutCurvePiece::Polynomial { points } => points.first().map(|p| p.event_outcome).unwrap_or(0),
+            PayoutCurvePiece::Hyperbola { left_end_point, .. } => left_end_point.event_outcome,
+        }
+    }
+
+    /// Last outcome covered by this piece
+    pub fn end(&self) -> u64 {
+        match self {
+            PayoutCurvePiece::Polynomial { points } => points.last().map(|p| p.event_outcome).unwrap_or(0),
+            PayoutCurvePiece::Hyperbola { right_end_point, .. } => right_end_point.event_outcome,
+        }
+    }
+
+    /// Evaluates the (unrounded) payout at `outcome`
+    pub fn evaluate(&self, outcome: u64) -> f64 {
+        match self {
+            PayoutCurvePiece::Polynomial { points } => {
+                if let Some(point) = points.iter().find(|p| p.event_outcome == outcome) {
+                    return point.payout();
+                }
+
+                // Lagrange interpolation
+                let x = outcome as f64;
+                points.iter().enumerate().map(|(i, pi)| {
+                    let xi = pi.event_outcome as f64;
+                    let basis = points.iter().enumerate()
+                        .filter(|(j, _)| *j != i)
+                        .map(|(_, pj)| {
+                            let xj = pj.event_outcome as f64;
+                            (x - xj) / (xi - xj)
+                        })
+                        .product::<f64>();
+                    pi.payout() * basis
+                }).sum()
+            }
+            PayoutCurvePiece::Hyperbola {
+                use_positive_piece, translate_outcome, translate_payout, a, b, c, d, ..
+            } => {
+                let x = outcome as f64 - translate_outcome;
+                let root = (x * x - 4.0 * a * b).max(0.0).sqrt();
+                let f = if *use_positive_piece { (x + root) / (2.0 * a) } else { (x - root) / (2.0 * a) };
+                if f == 0.0 {
+                    return *translate_payout;
+                }
+                c * f + d / f + translate_payout
+            }
+        }
+    }
+
+    fn validate(&self) -> BitcoinResult<()> {
+        match self {
+            PayoutCurvePiece::Polynomial { points } => {
+                if points.len() < 2 {
+                    return Err(BitcoinError::DLC("Polynomial piece needs at least two points".to_string()));
+                }
+                if points.windows(2).any(|w| w[0].event_outcome >= w[1].event_outcome) {
+                    return Err(BitcoinError::DLC("Polynomial points must be strictly increasing".to_string()));
+                }
+            }
+            PayoutCurvePiece::Hyperbola { left_end_point, right_end_point, a, .. } => {
+                if left_end_point.event_outcome >= right_end_point.event_outcome {
+                    return Err(BitcoinError::DLC("Hyperbola end points must be increasing".to_string()));
+                }
+                if *a == 0.0 {
+                    return Err(BitcoinError::DLC("Hyperbola parameter a must be non-zero".to_string()));
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+/// A payout curve made of contiguous pieces covering the whole outcome domain
+#[derive(Debug, Clone, PartialEq)]
+pub struct PayoutCurve {
+    /// Pieces ordered by outcome; each piece starts where the previous one ends
+    pub pieces: Vec<PayoutCurvePiece>,
+}
+
+impl PayoutCurve {
+    /// Creates a curve from its pieces
+    pub fn new(pieces: Vec<PayoutCurvePiece>) -> Self {
+        Self { pieces }
+    }
+
+    /// Validates the curve against the outcome domain
+    pub fn validate(&self, max_outcome: u64) -> BitcoinResult<()> {
+        let first = self.pieces.first()
+            .ok_or_else(|| BitcoinError::DLC("Payout curve has no pieces".to_string()))?;
+
+        for piece in &self.pieces {
+            piece.validate()?;
+        }
+
+        if first.start() != 0 {
+            return Err(BitcoinError::DLC("Payout curve must start at outcome 0".to_string()));
+        }
+
+        if self.pieces.windows(2).any(|w| w[0].end() != w[1].start()) {
+            return Err(BitcoinError::DLC("Payout curve pieces must be contiguous".to_string()));
+        }
+
+        if self.pieces.last().map(|p| p.end()) != Some(max_outcome) {
+            return Err(BitcoinError::DLC(format!(
+                "Payout curve must end at the maximum outcome {}", max_outcome
+            )));
+        }
+
+        Ok(())
+    }
+
+    /// Evaluates the unrounded payout at `outcome`
+    pub fn evaluate(&self, outcome: u64) -> f64 {
+        self.pieces.iter()
+            .find(|p| outcome >= p.start() && outcome <= p.end())
+            .or_else(|| self.pieces.last())
+            .map(|p| p.evaluate(outcome))
+            .unwrap_or(0.0)
+    }
+}
+
+/// Rounding applied to payouts from `begin_interval` onwards
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RoundingInterval {
+    /// First outcome the rounding applies to
+    pub begin_interval: u64,
+
+    /// Payouts are rounded to a multiple of this value (in satoshis)
+    pub rounding_mod: u64,
+}
+
+/// Rounding intervals, ordered by `begin_interval`
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct RoundingIntervals {
+    /// Intervals; the first should begin at outcome 0
+    pub intervals: Vec<RoundingInterval>,
+}
+
+impl RoundingIntervals {
+    /// Single rounding modulus across the whole domain
+    pub fn uniform(rounding_mod: u64) -> Self {
+        Self {
+            intervals: vec![RoundingInterval { begin_interval: 0, rounding_mod }],
+        }
+    }
+
+    /// Rounding modulus in effect at `outcome`
+    pub fn rounding_mod_at(&self, outcome: u64) -> u64 {
+        self.intervals.iter()
+            .rev()
+            .find(|i| i.begin_interval <= outcome)
+            .map(|i| i.rounding_mod.max(1))
+            .unwrap_or(1)
+    }
+
+    /// Rounds `payout` to the nearest multiple of the modulus at `outcome`
+    pub fn round(&self, outcome: u64, payout: f64) -> u64 {
+        let modulus = self.rounding_mod_at(outcome) as f64;
+        let rounded = (payout / modulus).round() * modulus;
+        if rounded <= 0.0 { 0 } else { rounded as u64 }
+    }
+}
+
+/// A contiguous range of outcomes sharing the same rounded payout
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PayoutRange {
+    /// First outcome in the range
+    pub start: u64,
+
+    /// Last outcome in the range (inclusive)
+    pub end: u64,
+
+    /// Offer party payout in satoshis
+    pub offer_payout: u64,
+}
+
+/// A contract execution transaction template for a numeric contract
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct NumericCet {
+    /// Outcomes covered by this CET
+    pub range: PayoutRange,
+
+    /// Accept party payout in satoshis
+    pub accept_payout: u64,
+
+    /// Digit prefixes, one adaptor signature each
+    pub digit_prefixes: Vec<Vec<usize>>,
+}
+
+/// Size of a compiled CET set
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct CetCompressionStats {
+    /// Number of outcomes in the domain
+    pub domain_size: u64,
+
+    /// Number of distinct payout ranges (CETs)
+    pub cet_count: usize,
+
+    /// Number of adaptor signatures each party must produce
+    pub adaptor_signature_count: usize,
+}
+
+/// A numeric-outcome contract
+#[derive(Debug, Clone)]
+pub struct NumericOutcomeContract {
+    /// How the oracle decomposes outcomes
+    pub descriptor: DigitDecompositionDescriptor,
+
+    /// Offer party payout curve
+    pub payout_curve: PayoutCurve,
+
+    /// Payout rounding
+    pub rounding_intervals: RoundingIntervals,
+
+    /// Total collateral locked in the contract
+    pub total_collateral: u64,
+}
+
+impl NumericOutcomeContract {
+    /// Creates and validates a numeric contract
+    pub fn new(
+        descriptor: DigitDecompositionDescriptor,
+        payout_curve: PayoutCurve,
+        rounding_intervals: RoundingIntervals,
+        total_collateral: u64,
+    ) -> BitcoinResult<Self> {
+        descriptor.validate()?;
+        payout_curve.validate(descriptor.max_outcome())?;
+
+        if total_collateral == 0 {
+            return Err(BitcoinError::DLC("Total collateral must be greater than zero".to_string()));
+        }
+
+        Ok(Self {
+            descriptor,
+            payout_curve,
+            rounding_intervals,
+            total_collateral,
+        })
+    }
+
+    /// Rounded offer payout at `outcome`, clamped to the collateral
+    pub fn payout_at(&self, outcome: u64) -> u64 {
+        let raw = self.payout_curve.evaluate(outcome);
+        self.rounding_intervals.round(outcome, raw).min(self.total_collateral)
+    }
+
+    /// Splits the outcome domain into ranges of constant rounded payout
+    pub fn payout_ranges(&self) -> Vec<PayoutRange> {
+        let mut ranges: Vec<PayoutRange> = Vec::new();
+
+        for outcome in 0..=self.descriptor.max_outcome() {
+            let payout = self.payout_at(outcome);
+            match ranges.last_mut() {
+                Some(range) if range.offer_payout == payout => range.end = outcome,
+                _ => ranges.push(PayoutRange { start: outcome, end: outcome, offer_payout: payout }),
+            }
+        }
+
+        ranges
+    }
+
+    /// Compiles the compressed CET set
+    pub fn compile_cets(&self) -> BitcoinResult<Vec<NumericCet>> {
+        self.compile_cets_bounded(DEFAULT_MAX_ADAPTOR_SIGNATURES)
+    }
+
+    /// Compiles the compressed CET set, failing if it needs more than `max_adaptor_signatures`
+    pub fn compile_cets_bounded(&self, max_adaptor_signatures: usize) -> BitcoinResult<Vec<NumericCet>> {
+        let mut cets = Vec::new();
+        let mut adaptor_signatures = 0usize;
+
+        for range in self.payout_ranges() {
+            let digit_prefixes = group_by_ignoring_digits(
+                range.start,
+                range.end,
+                self.descriptor.base,
+                self.descriptor.num_digits,
+            );
+
+            adaptor_signatures += digit_prefixes.len();
+            if adaptor_signatures > max_adaptor_signatures {
+                return Err(BitcoinError::DLC(format!(
+                    "Contract needs more than {} adaptor signatures; increase the rounding modulus",
+                    max_adaptor_signatures
+                )));
+            }
+
+            cets.push(NumericCet {
+                accept_payout: self.total_collateral - range.offer_payout,
+                range,
+                digit_prefixes,
+            });
+        }
+
+        Ok(cets)
+    }
+
+    /// Summarises the size of a compiled CET set
+    pub fn compression_stats(&self, cets: &[NumericCet]) -> CetCompressionStats {
+        CetCompressionStats {
+            domain_size: self.descriptor.domain_size().unwrap_or(0),
+            cet_count: cets.len(),
+            adaptor_signature_count: cets.iter().map(|c| c.digit_prefixes.len()).sum(),
+        }
+    }
+
+    /// Finds the CET and matching prefix for an attested outcome
+    pub fn find_cet<'a>(&self, cets: &'a [NumericCet], outcome: u64) -> Option<(&'a NumericCet, &'a [usize])> {
+        let digits = decompose(outcome, self.descriptor.base, self.descriptor.num_digits);
+
+        cets.iter()
+            .filter(|cet| outcome >= cet.range.start && outcome <= cet.range.end)
+            .find_map(|cet| {
+                cet.digit_prefixes.iter()
+                    .find(|prefix| digits.starts_with(prefix))
+                    .map(|prefix| (cet, prefix.as_slice()))
+            })
+    }
+}
+
+/// Oracle announcement for a numeric event, with one nonce per digit
+#[derive(Debug, Clone)]
+pub struct NumericOracleAnnouncement {
+    /// Unique event identifier
+    pub event_id: String,
+
+    /// Oracle public key
+    pub oracle_public_key: XOnlyPublicKey,
+
+    /// Nonce points, most significant digit first
+    pub nonces: Vec<XOnlyPublicKey>,
+
+    /// Digit decomposition of the event
+    pub descriptor: DigitDecompositionDescriptor,
+}
+
+impl NumericOracleAnnouncement {
+    /// Point `s_i * G` the oracle's signature on `digit` at `position` will reveal
+    pub fn digit_point(&self, position: usize, digit: usize) -> BitcoinResult<PublicKey> {
+        let secp = Secp256k1::verification_only();
+        let nonce = self.nonces.get(position)
+            .ok_or_else(|| BitcoinError::DLC(format!("No nonce announced for digit {}", position)))?;
+
+        let challenge = challenge_scalar(nonce, &self.oracle_public_key, &digit_message(digit))?;
+        let oracle_point = PublicKey::from_x_only_public_key(self.oracle_public_key, Parity::Even);
+        let nonce_point = PublicKey::from_x_only_public_key(*nonce, Parity::Even);
+
+        let tweaked = oracle_point.mul_tweak(&secp, &challenge)?;
+        Ok(nonce_point.combine(&tweaked)?)
+    }
+
+    /// Adaptor point for a digit prefix: the sum of its digit points
+    pub fn adaptor_point(&self, prefix: &[usize]) -> BitcoinResult<PublicKey> {
+        if prefix.is_empty() || prefix.len() > self.nonces.len() {
+            return Err(BitcoinError::DLC(format!("Invalid prefix length {}", prefix.len())));
+        }
+
+        let points = prefix.iter()
+            .enumerate()
+            .map(|(position, digit)| self.digit_point(position, *digit))
+            .collect::<BitcoinResult<Vec<_>>>()?;
+
+        let refs: Vec<&PublicKey> = points.iter().collect();
+        Ok(PublicKey::combine_keys(&refs)?)
+    }
+}
+
+/// Oracle attestation of a numeric outcome
+#[derive(Debug, Clone)]
+pub struct NumericOracleAttestation {
+    /// Event identifier
+    pub event_id: String,
+
+    /// Attested digits, most significant first
+    pub digits: Vec<usize>,
+
+    /// BIP340 signature per digit
+    pub signatures: Vec<schnorr::Signature>,
+}
+
+impl NumericOracleAttestation {
+    /// Attested outcome value
+    pub fn outcome(&self, base: u16) -> u64 {
+        compose(&self.digits, base)
+    }
+
+    /// Verifies every digit signature against the announcement
+    pub fn verify(&self, announcement: &NumericOracleAnnouncement) -> BitcoinResult<bool> {
+        if self.event_id != announcement.event_id
+            || self.digits.len() != announcement.nonces.len()
+            || self.signatures.len() != announcement.nonces.len()
+        {
+            return Ok(false);
+        }
+
+        let secp = Secp256k1::verification_only();
+        for ((digit, signature), nonce) in self.digits.iter().zip(&self.signatures).zip(&announcement.nonces) {
+            if *digit >= announcement.descriptor.base as usize || signature.as_ref()[..32] != nonce.serialize() {
+                return Ok(false);
+            }
+
+            let message = Message::from_digest(digit_message(*digit));
+            if secp.verify_schnorr(signature, &message, &announcement.oracle_public_key).is_err() {
+                return Ok(false);
+            }
+        }
+
+        Ok(true)
+    }
+
+    /// Secret unlocking the adaptor signatures for a prefix of `prefix_len` digits
+    pub fn attestation_secret(&self, prefix_len: usize) -> BitcoinResult<SecretKey> {
+        if prefix_len == 0 || prefix_len > self.signatures.len() {
+            return Err(BitcoinError::DLC(format!("Invalid prefix length {}", prefix_len)));
+        }
+
+        let mut secret: Option<SecretKey> = None;
+        for signature in &self.signatures[..prefix_len] {
+            let mut s = [0u8; 32];
+            s.copy_from_slice(&signature.as_ref()[32..]);
+            let s = SecretKey::from_slice(&s)?;
+            secret = Some(match secret {
+                None => s,
+                Some(acc) => acc.add_tweak(&Scalar::from(s))?,
+            });
+        }
+
+        secret.ok_or_else(|| BitcoinError::DLC("Empty attestation".to_string()))
+    }
+}
+
+/// Numeric oracle holding one nonce per digit for each announced event
+pub struct NumericOracle {
+    keypair: Keypair,
+    pending_nonces: HashMap<String, (DigitDecompositionDescriptor, Vec<Keypair>)>,
+}
+
+impl NumericOracle {
+    /// Creates an oracle from its secret key
+    pub fn new(secret_key: SecretKey) -> Self {
+        let secp = Secp256k1::new();
+        Self {
+            keypair: Keypair::from_secret_key(&secp, &secret_key),
+            pending_nonces: HashMap::new(),
+        }
+    }
+
+    /// Oracle public key
+    pub fn public_key(&self) -> XOnlyPublicKey {
+        self.keypair.x_only_public_key().0
+    }
+
+    /// Announces an event, generating a fresh nonce for every digit
+    pub fn announce(
+        &mut self,
+        event_id: &str,
+        descriptor: DigitDecompositionDescriptor,
+    ) -> BitcoinResult<NumericOracleAnnouncement> {
+        descriptor.validate()?;
+
+        if self.pending_nonces.contains_key(event_id) {
+            return Err(BitcoinError::DLC(format!("Event {} already announced", event_id)));
+        }
+
+        let secp = Secp256k1::new();
+        let nonces: Vec<Keypair> = (0..descriptor.num_digits)
+            .map(|_| Keypair::new(&secp, &mut rand::thread_rng()))
+            .collect();
+
+        let announcement = NumericOracleAnnouncement {
+            event_id: event_id.to_string(),
+            oracle_public_key: self.public_key(),
+            nonces: nonces.iter().map(|k| k.x_only_public_key().0).collect(),
+            descriptor: descriptor.clone(),
+        };
+
+        self.pending_nonces.insert(event_id.to_string(), (descriptor, nonces));
+        Ok(announcement)
+    }
+
+    /// Attests `outcome`, consuming the event's nonces so they can never be reused
+    pub fn attest(&mut self, event_id: &str, outcome: u64) -> BitcoinResult<NumericOracleAttestation> {
+        let (descriptor, nonces) = self.pending_nonces.get(event_id)
+            .ok_or_else(|| BitcoinError::DLC(format!("Unknown or already attested event {}", event_id)))?;
+
+        // Outcomes above the domain are attested as the maximum value
+        let outcome = outcome.min(descriptor.max_outcome());
+        let digits = decompose(outcome, descriptor.base, descriptor.num_digits);
+
+        let signatures = digits.iter()
+            .zip(nonces)
+            .map(|(digit, nonce)| self.sign_digit(nonce, *digit))
+            .collect::<BitcoinResult<Vec<_>>>()?;
+
+        self.pending_nonces.remove(event_id);
+
+        Ok(NumericOracleAttestation {
+            event_id: event_id.to_string(),
+            digits,
+            signatures,
+        })
+    }
+
+    /// BIP340 signature on a digit using a pre-announced nonce
+    fn sign_digit(&self, nonce: &Keypair, digit: usize) -> BitcoinResult<schnorr::Signature> {
+        let (nonce_point, _) = nonce.x_only_public_key();
+        let k = even_secret(nonce);
+        let x = even_secret(&self.keypair);
+
+        let e = challenge_scalar(&nonce_point, &self.public_key(), &digit_message(digit))?;
+        let s = k.add_tweak(&Scalar::from(x.mul_tweak(&e)?))?;
+
+        let mut bytes = [0u8; 64];
+        bytes[..32].copy_from_slice(&nonce_point.serialize());
+        bytes[32..].copy_from_slice(&s.secret_bytes());
+        Ok(schnorr::Signature::from_slice(&bytes)?)
+    }
+}
+
+/// Secret key of a keypair, negated if needed so that its point has even y
+fn even_secret(keypair: &Keypair) -> SecretKey {
+    let secret = keypair.secret_key();
+    match keypair.x_only_public_key().1 {
+        Parity::Even => secret,
+        Parity::Odd => secret.negate(),
+    }
+}
+
+/// Message signed for a digit
+fn digit_message(digit: usize) -> [u8; 32] {
+    tagged_hash(ATTESTATION_TAG, digit.to_string().as_bytes())
+}
+
+/// BIP340 challenge `H(R || P || m)` as a scalar
+fn challenge_scalar(nonce: &XOnlyPublicKey, public_key: &XOnlyPublicKey, message: &[u8; 32]) -> BitcoinResult<Scalar> {
+    let mut data = Vec::with_capacity(96);
+    data.extend_from_slice(&nonce.serialize());
+    data.extend_from_slice(&public_key.serialize());
+    data.extend_from_slice(message);
+
+    Scalar::from_be_bytes(tagged_hash(CHALLENGE_TAG, &data))
+        .map_err(|_| BitcoinError::DLC("Challenge out of range".to_string()))
+}
+
+/// BIP340 tagged hash
+fn tagged_hash(tag: &[u8], data: &[u8]) -> [u8; 32] {
+    let tag_hash = sha256::Hash::hash(tag).to_byte_array();
+    let mut engine_input = Vec::with_capacity(64 + data.len());
+    engine_input.extend_from_slice(&tag_hash);
+    engine_input.extend_from_slice(&tag_hash);
+    engine_input.extend_from_slice(data);
+    sha256::Hash::hash(&engine_input).to_byte_array()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Long BTC/USD call: offer gets nothing below $20k, everything above $60k
+    fn btc_usd_contract(rounding_mod: u64) -> NumericOutcomeContract {
+        let collateral = 100_000_000;
+        let max = (1u64 << 17) - 1;
+        let curve = PayoutCurve::new(vec![
+            PayoutCurvePiece::linear(PayoutPoint::new(0, 0), PayoutPoint::new(20_000, 0)),
+            PayoutCurvePiece::linear(PayoutPoint::new(20_000, 0), PayoutPoint::new(60_000, collateral)),
+            PayoutCurvePiece::linear(PayoutPoint::new(60_000, collateral), PayoutPoint::new(max, collateral)),
+        ]);
+
+        NumericOutcomeContract::new(
+            DigitDecompositionDescriptor::binary(17, "USD"),
+            curve,
+            RoundingIntervals::uniform(rounding_mod),
+            collateral,
+        ).unwrap()
+    }
+
+    #[test]
+    fn test_decompose_roundtrip() {
+        let digits = decompose(42_123, 2, 17);
+        assert_eq!(digits.len(), 17);
+        assert_eq!(compose(&digits, 2), 42_123);
+        assert_eq!(decompose(5, 10, 3), vec![0, 0, 5]);
+    }
+
+    #[test]
+    fn test_grouping_covers_range_exactly() {
+        let prefixes = group_by_ignoring_digits(3, 12, 2, 4);
+        // 0011, 01**, 10**, 1100
+        assert_eq!(prefixes, vec![vec![0, 0, 1, 1], vec![0, 1], vec![1, 0], vec![1, 1, 0, 0]]);
+
+        let covered: u64 = prefixes.iter().map(|p| 1u64 << (4 - p.len())).sum();
+        assert_eq!(covered, 10);
+    }
+
+    #[test]
+    fn test_btc_usd_cet_count_is_bounded() {
+        let contract = btc_usd_contract(1_000_000);
+        let cets = contract.compile_cets().unwrap();
+        let stats = contract.compression_stats(&cets);
+
+        assert_eq!(stats.domain_size, 1 << 17);
+        assert!(stats.cet_count <= 102, "{} CETs", stats.cet_count);
+        assert!(stats.adaptor_signature_count < 2_000, "{} adaptor signatures", stats.adaptor_signature_count);
+
+        // Every outcome maps to exactly one CET with the right payout
+        for outcome in [0u64, 19_999, 20_000, 40_000, 59_999, 60_000, (1 << 17) - 1] {
+            let (cet, _) = contract.find_cet(&cets, outcome).unwrap();
+            assert_eq!(cet.range.offer_payout, contract.payout_at(outcome));
+            assert_eq!(cet.range.offer_payout + cet.accept_payout, contract.total_collateral);
+        }
+
+        assert!(contract.compile_cets_bounded(10).is_err());
+    }
+
+    #[test]
+    fn test_hyperbola_inverse_payout() {
+        // Inverse contract: payout = 1e12 / price, floored at $10k
+        let max = (1u64 << 17) - 1;
+        let curve = PayoutCurve::new(vec![
+            PayoutCurvePiece::linear(PayoutPoint::new(0, 100_000_000), PayoutPoint::new(10_000, 100_000_000)),
+            PayoutCurvePiece::Hyperbola {
+                left_end_point: PayoutPoint::new(10_000, 100_000_000),
+                right_end_point: PayoutPoint::new(max, 7_629_452),
+                use_positive_piece: true,
+                translate_outcome: 0.0,
+                translate_payout: 0.0,
+                a: 1.0,
+                b: 0.0,
+                c: 0.0,
+                d: 1e12,
+            },
+        ]);
+        let contract = NumericOutcomeContract::new(
+            DigitDecompositionDescriptor::binary(17, "USD"),
+            curve,
+            RoundingIntervals::uniform(100_000),
+            100_000_000,
+        ).unwrap();
+
+        assert_eq!(contract.payout_at(20_000), 50_000_000);
+        assert_eq!(contract.payout_at(5_000), 100_000_000);
+        assert!(contract.compile_cets().is_ok());
+    }
+
+    #[test]
+    fn test_attestation_unlocks_adaptor_point() {
+        let secp = Secp256k1::new();
+        let contract = btc_usd_contract(1_000_000);
+        let cets = contract.compile_cets().unwrap();
+
+        let mut oracle = NumericOracle::new(SecretKey::from_slice(&[7u8; 32]).unwrap());
+        let announcement = oracle.announce("btcusd-2026-12-31", contract.descriptor.clone()).unwrap();
+        let attestation = oracle.attest("btcusd-2026-12-31", 43_210).unwrap();
+
+        assert!(attestation.verify(&announcement).unwrap());
+        assert_eq!(attestation.outcome(2), 43_210);
+        assert!(oracle.attest("btcusd-2026-12-31", 1).is_err());
+
+        let (_, prefix) = contract.find_cet(&cets, attestation.outcome(2)).unwrap();
+        let adaptor_point = announcement.adaptor_point(prefix).unwrap();
+        let secret = attestation.attestation_secret(prefix.len()).unwrap();
+        assert_eq!(PublicKey::from_secret_key(&secp, &secret), adaptor_point);
+    }
+}
@@ -3,9 +3,9 @@
 use std::collections::HashMap;
 use chrono::{DateTime, Utc};
 use serde::{Serialize, Deserialize};
-use bitcoin::secp256k1::{PublicKey, Signature};
+use bitcoin::secp256k1::{ecdsa::Signature, PublicKey};
 
-use crate::common::error::AnyaResult;
+use crate::{AnyaError, AnyaResult};
 
 /// Represents an oracle that provides attestations for DLCs
 #[derive(Debug, Clone)]
@@ -214,25 +214,26 @@ pub fn new(base_url: &str) -> Self {
     
     /// Gets oracle information
     pub fn get_oracle_info(&self) -> AnyaResult<OracleInfo> {
-        // Implementation goes here
-        unimplemented!("Oracle info retrieval not yet implemented")
+        Err(self.unsupported("oracle info"))
     }
     
     /// Gets announcements from the oracle
     pub fn get_announcements(&self) -> AnyaResult<Vec<OracleAnnouncement>> {
-        // Implementation goes here
-        unimplemented!("Announcement retrieval not yet implemented")
+        Err(self.unsupported("announcements"))
     }
     
     /// Gets a specific announcement by event ID
     pub fn get_announcement(&self, event_id: &str) -> AnyaResult<Option<OracleAnnouncement>> {
-        // Implementation goes here
-        unimplemented!("Specific announcement retrieval not yet implemented")
+        Err(self.unsupported(&format!("the announcement of {}", event_id)))
     }
     
     /// Gets an attestation for an event
     pub fn get_attestation(&self, event_id: &str) -> AnyaResult<Option<OracleAttestation>> {
-        // Implementation goes here
-        unimplemented!("Attestation retrieval not yet implemented")
+        Err(self.unsupported(&format!("the attestation of {}", event_id)))
+    }
+    
+    /// Error for data the client cannot fetch yet
+    fn unsupported(&self, what: &str) -> AnyaError {
+        AnyaError::Bitcoin(format!("Fetching {} from {} is not supported yet", what, self.base_url))
     }
 } 
\ No newline at end of file
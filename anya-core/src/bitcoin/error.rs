@@ -9,6 +9,7 @@
 use hex::FromHexError;
 use bitcoin::key::FromSliceError;
 use futures_io;
+use anya_bitcoin::silent_payments::SilentPaymentError;
 
 /// Bitcoin operation errors
 #[derive(Error, Debug)]
@@ -58,6 +59,9 @@ pub enum BitcoinError {
     #[error("DLC error: {0}")]
     DLC(String),
 
+    #[error("Miniscript error: {0}")]
+    Miniscript(String),
+
     #[error("Secp256k1 error: {0}")]
     Secp256k1Error(#[from] secp256k1::Error),
 
@@ -122,11 +126,26 @@ fn from(_: FromSliceError) -> Self {
     }
 }
 
+impl From<miniscript::Error> for BitcoinError {
+    fn from(err: miniscript::Error) -> Self {
+        BitcoinError::Miniscript(err.to_string())
+    }
+}
+
 impl From<futures_io::Error> for BitcoinError {
     fn from(err: futures_io::Error) -> Self {
         BitcoinError::IOError(err.to_string())
     }
 }
 
+impl From<SilentPaymentError> for BitcoinError {
+    fn from(err: SilentPaymentError) -> Self {
+        match err {
+            SilentPaymentError::InvalidAddress(_) | SilentPaymentError::WrongNetwork { .. } => BitcoinError::InvalidAddress,
+            err => BitcoinError::Wallet(err.to_string()),
+        }
+    }
+}
+
 /// Result type for Bitcoin operations
 pub type BitcoinResult<T> = Result<T, BitcoinError>; 
\ No newline at end of file
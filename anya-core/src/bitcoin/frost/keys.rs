@@ -0,0 +1,506 @@
+//! Distributed key generation and share refresh
+//!
+//! Key generation follows the FROST paper's Pedersen DKG:
+//!
+//! 1. [`part1`]: every participant picks a random polynomial of degree
+//!    `t - 1`, broadcasts commitments to its coefficients and proves
+//!    knowledge of the constant term, so nobody can bias the group key;
+//! 2. echo: every participant sends the others the [`round1_digest`] of
+//!    all the packages it received, and [`check_echoes`] aborts if any
+//!    differs. The broadcast is point-to-point underneath, and without
+//!    this a participant could show different commitments to different
+//!    peers;
+//! 3. [`part2`]: after checking everyone's proofs, each participant sends
+//!    every other one its polynomial evaluated at their identifier;
+//! 4. [`part3`]: each participant checks the shares it received against the
+//!    commitments and sums them into its signing share.
+//!
+//! [`refresh_part1`] and [`refresh_part2`] re-randomize the shares with
+//! polynomials whose constant term is zero. The group key stays the same,
+//! while shares leaked before the refresh become useless. Refresh packages
+//! are echoed the same way, with [`refresh_digest`].
+//!
+//! Shares are secret: the transport must keep point-to-point messages
+//! confidential and authenticate every sender.
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+use bitcoin::secp256k1::{PublicKey, Secp256k1, XOnlyPublicKey};
+
+use super::{
+    add_points, check_parameters, evaluate_commitments, evaluate_polynomial, has_even_y, identifier,
+    tagged_hash, FrostError, FrostResult, ParticipantId, PublicKeyPackage, Scalar,
+};
+
+/// Broadcast of the first key generation round
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Round1Package {
+    pub(crate) commitments: Vec<PublicKey>,
+    pub(crate) proof_nonce: PublicKey,
+    pub(crate) proof_response: Scalar,
+}
+
+/// Polynomial a participant keeps between the first two rounds
+pub struct Round1Secret {
+    id: ParticipantId,
+    threshold: u16,
+    participants: Vec<ParticipantId>,
+    coefficients: Vec<Scalar>,
+    package: Round1Package,
+}
+
+impl Drop for Round1Secret {
+    fn drop(&mut self) {
+        self.coefficients.iter_mut().for_each(Scalar::erase);
+    }
+}
+
+/// A share of one participant's polynomial, sent privately to another
+#[derive(Clone, PartialEq, Eq)]
+pub struct SecretShare(pub(crate) Scalar);
+
+impl fmt::Debug for SecretShare {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("SecretShare(..)")
+    }
+}
+
+/// State a participant keeps between the second and third rounds
+pub struct Round2Secret {
+    id: ParticipantId,
+    threshold: u16,
+    participants: Vec<ParticipantId>,
+    own_share: Scalar,
+    round1: BTreeMap<ParticipantId, Round1Package>,
+}
+
+impl Drop for Round2Secret {
+    fn drop(&mut self) {
+        self.own_share.erase();
+    }
+}
+
+/// A participant's signing share together with the group's public data
+#[derive(Clone, PartialEq, Eq)]
+pub struct KeyPackage {
+    pub(crate) id: ParticipantId,
+    pub(crate) signing_share: Scalar,
+    pub(crate) public: PublicKeyPackage,
+}
+
+impl fmt::Debug for KeyPackage {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("KeyPackage")
+            .field("id", &self.id)
+            .field("public", &self.public)
+            .finish_non_exhaustive()
+    }
+}
+
+impl KeyPackage {
+    pub fn id(&self) -> ParticipantId {
+        self.id
+    }
+
+    pub fn public(&self) -> &PublicKeyPackage {
+        &self.public
+    }
+
+    /// Group key as a BIP340 / taproot internal key
+    pub fn x_only_public_key(&self) -> XOnlyPublicKey {
+        self.public.x_only_public_key()
+    }
+}
+
+/// Every participant but `id`, in ascending order
+fn others(participants: &[ParticipantId], id: ParticipantId) -> Vec<ParticipantId> {
+    let mut others: Vec<ParticipantId> = participants.iter().copied().filter(|p| *p != id).collect();
+    others.sort_unstable();
+    others
+}
+
+fn proof_challenge(id: ParticipantId, commitment: &PublicKey, nonce: &PublicKey) -> Scalar {
+    Scalar::reduce(tagged_hash("FROST/dkg", &[&id.to_be_bytes(), &commitment.serialize(), &nonce.serialize()]))
+}
+
+/// Starts key generation for `id` among `participants`, `threshold` of which
+/// will be needed to sign
+pub fn part1(id: ParticipantId, threshold: u16, participants: &[ParticipantId]) -> FrostResult<(Round1Secret, Round1Package)> {
+    check_parameters(threshold, participants)?;
+    if !participants.contains(&id) {
+        return Err(FrostError::InvalidParameters(format!("Participant {} is not in the group", id)));
+    }
+
+    let secp = Secp256k1::new();
+    let coefficients: Vec<Scalar> = (0..threshold).map(|_| Scalar::random()).collect();
+    let commitments: Vec<PublicKey> = coefficients.iter()
+        .map(|coefficient| coefficient.mul_base(&secp).expect("random coefficients are non-zero"))
+        .collect();
+
+    // Schnorr proof of knowledge of the constant term
+    let mut k = Scalar::random();
+    let proof_nonce = k.mul_base(&secp).expect("random nonce is non-zero");
+    let challenge = proof_challenge(id, &commitments[0], &proof_nonce);
+    let proof_response = k.add(coefficients[0].mul(challenge));
+    k.erase();
+
+    let package = Round1Package { commitments, proof_nonce, proof_response };
+    let secret = Round1Secret {
+        id,
+        threshold,
+        participants: participants.to_vec(),
+        coefficients,
+        package: package.clone(),
+    };
+    Ok((secret, package))
+}
+
+/// Digest of every participant's first round package, own included, that
+/// participants echo to each other
+pub fn round1_digest(packages: &BTreeMap<ParticipantId, Round1Package>) -> [u8; 32] {
+    let mut data = Vec::new();
+    for (id, package) in packages {
+        data.extend_from_slice(&id.to_be_bytes());
+        put_points(&mut data, &package.commitments);
+        data.extend_from_slice(&package.proof_nonce.serialize());
+        data.extend_from_slice(&package.proof_response.to_bytes());
+    }
+    tagged_hash("FROST/dkg echo", &[&data])
+}
+
+/// Digest of every participant's refresh package, own included
+pub fn refresh_digest(packages: &BTreeMap<ParticipantId, RefreshPackage>) -> [u8; 32] {
+    let mut data = Vec::new();
+    for (id, package) in packages {
+        data.extend_from_slice(&id.to_be_bytes());
+        put_points(&mut data, &package.commitments);
+    }
+    tagged_hash("FROST/refresh echo", &[&data])
+}
+
+/// Points with a two-byte count, as in the wire encoding
+pub(crate) fn put_points(data: &mut Vec<u8>, points: &[PublicKey]) {
+    data.extend_from_slice(&(points.len() as u16).to_be_bytes());
+    points.iter().for_each(|point| data.extend_from_slice(&point.serialize()));
+}
+
+/// Compares the digests the other participants echoed with our own
+///
+/// A mismatch means someone broadcast inconsistent packages. Without
+/// signatures on the broadcasts the culprit cannot be told apart from the
+/// peers it deceived, so every participant whose view differs is reported
+/// and the ceremony must be abandoned.
+pub fn check_echoes(digest: &[u8; 32], echoes: &BTreeMap<ParticipantId, [u8; 32]>) -> FrostResult<()> {
+    let misbehaving: Vec<ParticipantId> = echoes.iter()
+        .filter(|(_, echo)| *echo != digest)
+        .map(|(id, _)| *id)
+        .collect();
+    if !misbehaving.is_empty() {
+        return Err(FrostError::Misbehaving(misbehaving));
+    }
+    Ok(())
+}
+
+/// Checks every other participant's first round package and computes the
+/// shares to send them
+pub fn part2(
+    secret: Round1Secret,
+    round1: BTreeMap<ParticipantId, Round1Package>,
+) -> FrostResult<(Round2Secret, BTreeMap<ParticipantId, SecretShare>)> {
+    let others = others(&secret.participants, secret.id);
+    if !round1.keys().copied().eq(others.iter().copied()) {
+        return Err(FrostError::InvalidMessage("First round packages do not match the participants".to_string()));
+    }
+
+    let secp = Secp256k1::new();
+    let misbehaving: Vec<ParticipantId> = round1.iter()
+        .filter(|(id, package)| {
+            if package.commitments.len() != usize::from(secret.threshold) {
+                return true;
+            }
+            let challenge = proof_challenge(**id, &package.commitments[0], &package.proof_nonce);
+            let expected = add_points(Some(package.proof_nonce), challenge.mul_point(&secp, &package.commitments[0]));
+            package.proof_response.mul_base(&secp) != expected
+        })
+        .map(|(id, _)| *id)
+        .collect();
+    if !misbehaving.is_empty() {
+        return Err(FrostError::Misbehaving(misbehaving));
+    }
+
+    let shares = others.iter()
+        .map(|id| (*id, SecretShare(evaluate_polynomial(&secret.coefficients, identifier(*id)))))
+        .collect();
+
+    let mut round1 = round1;
+    round1.insert(secret.id, secret.package.clone());
+    let next = Round2Secret {
+        id: secret.id,
+        threshold: secret.threshold,
+        participants: secret.participants.clone(),
+        own_share: evaluate_polynomial(&secret.coefficients, identifier(secret.id)),
+        round1,
+    };
+    Ok((next, shares))
+}
+
+/// Checks the received shares and derives the signing share and group key
+pub fn part3(secret: Round2Secret, shares: BTreeMap<ParticipantId, SecretShare>) -> FrostResult<KeyPackage> {
+    let others = others(&secret.participants, secret.id);
+    if !shares.keys().copied().eq(others.iter().copied()) {
+        return Err(FrostError::InvalidMessage("Shares do not match the participants".to_string()));
+    }
+
+    let secp = Secp256k1::new();
+    let x = identifier(secret.id);
+    let misbehaving: Vec<ParticipantId> = shares.iter()
+        .filter(|(id, share)| share.0.mul_base(&secp) != evaluate_commitments(&secp, &secret.round1[*id].commitments, x))
+        .map(|(id, _)| *id)
+        .collect();
+    if !misbehaving.is_empty() {
+        return Err(FrostError::Misbehaving(misbehaving));
+    }
+
+    let mut signing_share = shares.values().fold(secret.own_share, |sum, share| sum.add(share.0));
+    let group_public_key = secret.round1.values()
+        .fold(None, |sum, package| add_points(sum, Some(package.commitments[0])))
+        .ok_or_else(|| FrostError::InvalidMessage("Group key is the point at infinity".to_string()))?;
+    let mut verification_shares = secret.participants.iter()
+        .map(|id| {
+            let x = identifier(*id);
+            let share = secret.round1.values()
+                .fold(None, |sum, package| add_points(sum, evaluate_commitments(&secp, &package.commitments, x)))
+                .ok_or_else(|| FrostError::InvalidMessage(format!("Verification share of {} is infinity", id)))?;
+            Ok((*id, share))
+        })
+        .collect::<FrostResult<BTreeMap<_, _>>>()?;
+
+    // Negate the whole sharing if needed so the group key has an even y
+    let mut group_public_key = group_public_key;
+    if !has_even_y(&group_public_key) {
+        signing_share = signing_share.negate();
+        group_public_key = group_public_key.negate(&secp);
+        verification_shares.values_mut().for_each(|share| *share = share.negate(&secp));
+    }
+
+    Ok(KeyPackage {
+        id: secret.id,
+        signing_share,
+        public: PublicKeyPackage { threshold: secret.threshold, group_public_key, verification_shares },
+    })
+}
+
+/// Broadcast of a share refresh: commitments to the non-constant
+/// coefficients of a polynomial whose constant term is zero
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RefreshPackage {
+    pub(crate) commitments: Vec<PublicKey>,
+}
+
+/// A participant's own refresh share, kept until the refresh completes
+pub struct RefreshSecret {
+    own_share: Scalar,
+    package: RefreshPackage,
+}
+
+impl Drop for RefreshSecret {
+    fn drop(&mut self) {
+        self.own_share.erase();
+    }
+}
+
+/// Sum of `commitment_k·x^k` for `k >= 1`
+fn evaluate_refresh_commitments(secp: &Secp256k1<bitcoin::secp256k1::All>, commitments: &[PublicKey], x: Scalar) -> Option<PublicKey> {
+    evaluate_commitments(secp, commitments, x).and_then(|sum| x.mul_point(secp, &sum))
+}
+
+/// Starts a refresh: the package to broadcast and the shares to send to
+/// every other participant. All participants must take part.
+pub fn refresh_part1(key_package: &KeyPackage) -> (RefreshSecret, RefreshPackage, BTreeMap<ParticipantId, SecretShare>) {
+    let secp = Secp256k1::new();
+    let mut coefficients: Vec<Scalar> = std::iter::once(Scalar::ZERO)
+        .chain((1..key_package.public.threshold).map(|_| Scalar::random()))
+        .collect();
+    let package = RefreshPackage {
+        commitments: coefficients[1..].iter()
+            .map(|coefficient| coefficient.mul_base(&secp).expect("random coefficients are non-zero"))
+            .collect(),
+    };
+
+    let shares = key_package.public.verification_shares.keys()
+        .filter(|id| **id != key_package.id)
+        .map(|id| (*id, SecretShare(evaluate_polynomial(&coefficients, identifier(*id)))))
+        .collect();
+    let own_share = evaluate_polynomial(&coefficients, identifier(key_package.id));
+    coefficients.iter_mut().for_each(Scalar::erase);
+
+    (RefreshSecret { own_share, package: package.clone() }, package, shares)
+}
+
+/// Checks everyone's refresh shares and applies them, keeping the group key
+pub fn refresh_part2(
+    key_package: &KeyPackage,
+    secret: RefreshSecret,
+    packages: BTreeMap<ParticipantId, RefreshPackage>,
+    shares: BTreeMap<ParticipantId, SecretShare>,
+) -> FrostResult<KeyPackage> {
+    let others: Vec<ParticipantId> = key_package.public.verification_shares.keys()
+        .copied()
+        .filter(|id| *id != key_package.id)
+        .collect();
+    if !packages.keys().copied().eq(others.iter().copied()) || !shares.keys().copied().eq(others.iter().copied()) {
+        return Err(FrostError::InvalidMessage("Refresh messages do not match the participants".to_string()));
+    }
+
+    let secp = Secp256k1::new();
+    let degree = usize::from(key_package.public.threshold) - 1;
+    let x = identifier(key_package.id);
+    let misbehaving: Vec<ParticipantId> = others.iter().copied()
+        .filter(|id| {
+            let package = &packages[id];
+            package.commitments.len() != degree
+                || shares[id].0.mul_base(&secp) != evaluate_refresh_commitments(&secp, &package.commitments, x)
+        })
+        .collect();
+    if !misbehaving.is_empty() {
+        return Err(FrostError::Misbehaving(misbehaving));
+    }
+
+    let signing_share = shares.values().fold(key_package.signing_share.add(secret.own_share), |sum, share| sum.add(share.0));
+
+    let mut public = key_package.public.clone();
+    for (id, verification_share) in public.verification_shares.iter_mut() {
+        let x = identifier(*id);
+        let update = packages.values()
+            .chain(std::iter::once(&secret.package))
+            .fold(None, |sum, package| add_points(sum, evaluate_refresh_commitments(&secp, &package.commitments, x)));
+        *verification_share = add_points(Some(*verification_share), update)
+            .ok_or_else(|| FrostError::InvalidMessage(format!("Verification share of {} is infinity", id)))?;
+    }
+
+    if signing_share.mul_base(&secp) != Some(public.verification_shares[&key_package.id]) {
+        return Err(FrostError::InvalidMessage("Refreshed share does not match its verification share".to_string()));
+    }
+    Ok(KeyPackage { id: key_package.id, signing_share, public })
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::bitcoin::frost::lagrange_coefficient;
+
+    /// Runs key generation for participants `1..=n` without a transport
+    pub(crate) fn generate(threshold: u16, n: u16) -> BTreeMap<ParticipantId, KeyPackage> {
+        let participants: Vec<ParticipantId> = (1..=n).collect();
+        let mut secrets = BTreeMap::new();
+        let mut packages = BTreeMap::new();
+        for id in &participants {
+            let (secret, package) = part1(*id, threshold, &participants).unwrap();
+            secrets.insert(*id, secret);
+            packages.insert(*id, package);
+        }
+
+        let mut round2 = BTreeMap::new();
+        let mut sent: BTreeMap<ParticipantId, BTreeMap<ParticipantId, SecretShare>> = BTreeMap::new();
+        for (id, secret) in secrets {
+            let received = packages.iter().filter(|(from, _)| **from != id).map(|(f, p)| (*f, p.clone())).collect();
+            let (next, shares) = part2(secret, received).unwrap();
+            for (to, share) in shares {
+                sent.entry(to).or_default().insert(id, share);
+            }
+            round2.insert(id, next);
+        }
+
+        round2.into_iter()
+            .map(|(id, secret)| (id, part3(secret, sent.remove(&id).unwrap()).unwrap()))
+            .collect()
+    }
+
+    fn group_secret(key_packages: &[&KeyPackage]) -> Scalar {
+        let set: Vec<_> = key_packages.iter().map(|k| k.id).collect();
+        key_packages.iter().fold(Scalar::ZERO, |sum, k| {
+            sum.add(k.signing_share.mul(lagrange_coefficient(k.id, set.iter().copied()).unwrap()))
+        })
+    }
+
+    #[test]
+    fn test_dkg() {
+        let key_packages = generate(2, 3);
+        let secp = Secp256k1::new();
+        let public = &key_packages[&1].public;
+        assert!(has_even_y(&public.group_public_key));
+        assert!(key_packages.values().all(|k| k.public == *public));
+
+        // Any two shares reconstruct the same group secret
+        for pair in [[1, 2], [1, 3], [2, 3]] {
+            let secret = group_secret(&[&key_packages[&pair[0]], &key_packages[&pair[1]]]);
+            assert_eq!(secret.mul_base(&secp), Some(public.group_public_key));
+        }
+        for k in key_packages.values() {
+            assert_eq!(k.signing_share.mul_base(&secp), Some(public.verification_shares[&k.id]));
+        }
+    }
+
+    #[test]
+    fn test_dkg_identifies_bad_participants() {
+        let participants = [1, 2, 3];
+        let (secret, _) = part1(1, 2, &participants).unwrap();
+        let (_, mut forged) = part1(2, 2, &participants).unwrap();
+        let (_, honest) = part1(3, 2, &participants).unwrap();
+        forged.proof_response = forged.proof_response.add(Scalar::one());
+        let round1 = BTreeMap::from([(2, forged), (3, honest)]);
+        assert_eq!(part2(secret, round1).err(), Some(FrostError::Misbehaving(vec![2])));
+
+        // A participant who showed a different package to a peer is caught by the echo
+        let (_, mut round1) = part1(1, 2, &participants).unwrap();
+        let (_, other) = part1(2, 2, &participants).unwrap();
+        let mut packages = BTreeMap::from([(1, round1.clone()), (2, other)]);
+        let digest = round1_digest(&packages);
+        round1.proof_response = round1.proof_response.add(Scalar::one());
+        packages.insert(1, round1);
+        let echoes = BTreeMap::from([(2, digest), (3, round1_digest(&packages))]);
+        assert_eq!(check_echoes(&digest, &echoes), Err(FrostError::Misbehaving(vec![3])));
+        assert!(check_echoes(&digest, &BTreeMap::from([(2, digest)])).is_ok());
+
+        assert!(part1(1, 4, &participants).is_err());
+        assert!(part1(4, 2, &participants).is_err());
+        assert!(part1(1, 2, &[1, 1, 2]).is_err());
+    }
+
+    #[test]
+    fn test_refresh_keeps_group_key() {
+        let key_packages = generate(2, 3);
+        let mut secrets = BTreeMap::new();
+        let mut packages = BTreeMap::new();
+        let mut sent: BTreeMap<ParticipantId, BTreeMap<ParticipantId, SecretShare>> = BTreeMap::new();
+        for (id, key_package) in &key_packages {
+            let (secret, package, shares) = refresh_part1(key_package);
+            secrets.insert(*id, secret);
+            packages.insert(*id, package);
+            for (to, share) in shares {
+                sent.entry(to).or_default().insert(*id, share);
+            }
+        }
+
+        let refreshed: BTreeMap<_, _> = key_packages.iter()
+            .map(|(id, key_package)| {
+                let received = packages.iter().filter(|(from, _)| *from != id).map(|(f, p)| (*f, p.clone())).collect();
+                let refreshed = refresh_part2(key_package, secrets.remove(id).unwrap(), received, sent.remove(id).unwrap()).unwrap();
+                (*id, refreshed)
+            })
+            .collect();
+
+        let secp = Secp256k1::new();
+        let public = &refreshed[&1].public;
+        assert_eq!(public.group_public_key, key_packages[&1].public.group_public_key);
+        assert!(refreshed.values().all(|k| k.public == *public));
+        assert_ne!(refreshed[&1].signing_share, key_packages[&1].signing_share);
+        let secret = group_secret(&[&refreshed[&1], &refreshed[&3]]);
+        assert_eq!(secret.mul_base(&secp), Some(public.group_public_key));
+
+        // Old and new shares do not combine
+        let mixed = group_secret(&[&key_packages[&1], &refreshed[&3]]);
+        assert_ne!(mixed.mul_base(&secp), Some(public.group_public_key));
+    }
+}
@@ -0,0 +1,316 @@
+//! FROST threshold Schnorr signatures
+//!
+//! A group of `n` participants jointly generates a single secp256k1 key of
+//! which any `t` can produce a BIP340 signature, while fewer learn nothing
+//! about it. On chain the key is an ordinary taproot key: unlike a script
+//! multisig it neither reveals the policy nor costs more to spend.
+//!
+//! - [`keys`]: distributed key generation without a trusted dealer, and
+//!   proactive share refresh that keeps the group key unchanged;
+//! - [`signing`]: two-round signing, with every signature share verified so
+//!   misbehaving signers are identified rather than just detected;
+//! - [`transport`]: the message exchange for all ceremonies over a
+//!   pluggable [`FrostTransport`](transport::FrostTransport).
+//!
+//! The group key always has an even y coordinate, so it can be used as a
+//! BIP340 or taproot internal key as is.
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+use bitcoin::hashes::{sha256, Hash, HashEngine};
+use bitcoin::secp256k1::{self, All, Parity, PublicKey, Secp256k1, SecretKey, XOnlyPublicKey};
+use rand::RngCore;
+
+pub mod keys;
+pub mod signing;
+pub mod transport;
+
+pub use keys::KeyPackage;
+pub use signing::{SigningCommitments, SigningNonces, SigningPackage, SignatureShare};
+pub use transport::{Ceremony, FrostMessage, FrostTransport, InMemoryTransport};
+
+/// Identifier of a participant, never zero
+pub type ParticipantId = u16;
+
+/// FROST errors
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum FrostError {
+    /// Threshold, participant set or signing set is unusable
+    #[error("Invalid parameters: {0}")]
+    InvalidParameters(String),
+
+    /// A message could not be decoded or does not fit the ceremony
+    #[error("Invalid message: {0}")]
+    InvalidMessage(String),
+
+    /// Participants that sent invalid proofs, shares or signature shares
+    #[error("Misbehaving participants: {0:?}")]
+    Misbehaving(Vec<ParticipantId>),
+
+    /// The transport failed to deliver a message
+    #[error("Transport error: {0}")]
+    Transport(String),
+
+    /// Participants did not respond in time
+    #[error("Timed out waiting for: {0:?}")]
+    Timeout(Vec<ParticipantId>),
+}
+
+pub type FrostResult<T> = Result<T, FrostError>;
+
+/// Order of the secp256k1 group
+const CURVE_ORDER: [u8; 32] = [
+    0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xff, 0xfe,
+    0xba, 0xae, 0xdc, 0xe6, 0xaf, 0x48, 0xa0, 0x3b, 0xbf, 0xd2, 0x5e, 0x8c, 0xd0, 0x36, 0x41, 0x41,
+];
+
+pub(crate) fn tagged_hash(tag: &str, parts: &[&[u8]]) -> [u8; 32] {
+    let tag = sha256::Hash::hash(tag.as_bytes());
+    let mut engine = sha256::Hash::engine();
+    engine.input(tag.as_ref());
+    engine.input(tag.as_ref());
+    for part in parts {
+        engine.input(part);
+    }
+    sha256::Hash::from_engine(engine).to_byte_array()
+}
+
+pub(crate) fn has_even_y(point: &PublicKey) -> bool {
+    point.x_only_public_key().1 == Parity::Even
+}
+
+/// Adds two points, `None` being the point at infinity
+pub(crate) fn add_points(a: Option<PublicKey>, b: Option<PublicKey>) -> Option<PublicKey> {
+    match (a, b) {
+        (Some(a), Some(b)) => a.combine(&b).ok(),
+        (a, None) => a,
+        (None, b) => b,
+    }
+}
+
+/// Integer modulo the curve order, zero included
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub(crate) struct Scalar(Option<SecretKey>);
+
+impl fmt::Debug for Scalar {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("Scalar(..)")
+    }
+}
+
+impl Scalar {
+    pub(crate) const ZERO: Scalar = Scalar(None);
+
+    pub(crate) fn one() -> Self {
+        Self::from_u64(1)
+    }
+
+    pub(crate) fn from_u64(value: u64) -> Self {
+        let mut bytes = [0u8; 32];
+        bytes[24..].copy_from_slice(&value.to_be_bytes());
+        Scalar(SecretKey::from_slice(&bytes).ok())
+    }
+
+    pub(crate) fn random() -> Self {
+        loop {
+            let mut bytes = [0u8; 32];
+            rand::thread_rng().fill_bytes(&mut bytes);
+            if let Ok(key) = SecretKey::from_slice(&bytes) {
+                return Scalar(Some(key));
+            }
+        }
+    }
+
+    /// Interprets 32 big-endian bytes, reducing them modulo the curve order
+    pub(crate) fn reduce(mut bytes: [u8; 32]) -> Self {
+        if bytes >= CURVE_ORDER {
+            // A 256-bit value is below twice the order, one subtraction suffices
+            let mut borrow = 0i16;
+            for i in (0..32).rev() {
+                let diff = i16::from(bytes[i]) - i16::from(CURVE_ORDER[i]) - borrow;
+                borrow = i16::from(diff < 0);
+                bytes[i] = diff.rem_euclid(256) as u8;
+            }
+        }
+        Scalar(SecretKey::from_slice(&bytes).ok())
+    }
+
+    /// Interprets 32 big-endian bytes, rejecting values not below the curve order
+    pub(crate) fn from_bytes(bytes: [u8; 32]) -> Option<Self> {
+        (bytes < CURVE_ORDER).then(|| Scalar(SecretKey::from_slice(&bytes).ok()))
+    }
+
+    pub(crate) fn to_bytes(self) -> [u8; 32] {
+        self.0.map(|k| k.secret_bytes()).unwrap_or([0u8; 32])
+    }
+
+    pub(crate) fn is_zero(self) -> bool {
+        self.0.is_none()
+    }
+
+    pub(crate) fn add(self, other: Self) -> Self {
+        match (self.0, other.0) {
+            (Some(a), Some(b)) => Scalar(a.add_tweak(&secp256k1::Scalar::from(b)).ok()),
+            (Some(_), None) => self,
+            (None, _) => other,
+        }
+    }
+
+    pub(crate) fn sub(self, other: Self) -> Self {
+        self.add(other.negate())
+    }
+
+    pub(crate) fn mul(self, other: Self) -> Self {
+        match (self.0, other.0) {
+            (Some(a), Some(b)) => Scalar(a.mul_tweak(&secp256k1::Scalar::from(b)).ok()),
+            _ => Self::ZERO,
+        }
+    }
+
+    pub(crate) fn negate(self) -> Self {
+        Scalar(self.0.map(SecretKey::negate))
+    }
+
+    /// Multiplicative inverse by Fermat's little theorem, `None` for zero
+    pub(crate) fn invert(self) -> Option<Self> {
+        if self.is_zero() {
+            return None;
+        }
+        let mut exponent = CURVE_ORDER;
+        exponent[31] -= 2;
+        let mut result = Self::one();
+        for byte in exponent {
+            for bit in (0..8).rev() {
+                result = result.mul(result);
+                if byte >> bit & 1 == 1 {
+                    result = result.mul(self);
+                }
+            }
+        }
+        Some(result)
+    }
+
+    /// `self·P`, `None` for the point at infinity
+    pub(crate) fn mul_point(self, secp: &Secp256k1<All>, point: &PublicKey) -> Option<PublicKey> {
+        self.0.map(|k| point.mul_tweak(secp, &secp256k1::Scalar::from(k)).expect("non-zero scalar"))
+    }
+
+    /// `self·G`, `None` for the point at infinity
+    pub(crate) fn mul_base(self, secp: &Secp256k1<All>) -> Option<PublicKey> {
+        self.0.map(|k| PublicKey::from_secret_key(secp, &k))
+    }
+
+    /// Erases the secret value
+    pub(crate) fn erase(&mut self) {
+        if let Some(key) = self.0.as_mut() {
+            key.non_secure_erase();
+        }
+        self.0 = None;
+    }
+}
+
+/// A participant's identifier as a field element
+pub(crate) fn identifier(id: ParticipantId) -> Scalar {
+    Scalar::from_u64(u64::from(id))
+}
+
+/// Lagrange coefficient of `id` for interpolating at zero over `set`
+pub(crate) fn lagrange_coefficient(id: ParticipantId, set: impl IntoIterator<Item = ParticipantId>) -> FrostResult<Scalar> {
+    let x = identifier(id);
+    let (mut numerator, mut denominator) = (Scalar::one(), Scalar::one());
+    for other in set.into_iter().filter(|other| *other != id) {
+        let x_other = identifier(other);
+        numerator = numerator.mul(x_other);
+        denominator = denominator.mul(x_other.sub(x));
+    }
+    let inverse = denominator.invert()
+        .ok_or_else(|| FrostError::InvalidParameters(format!("Participant {} appears twice", id)))?;
+    Ok(numerator.mul(inverse))
+}
+
+/// Evaluates the polynomial with these coefficients, constant term first
+pub(crate) fn evaluate_polynomial(coefficients: &[Scalar], x: Scalar) -> Scalar {
+    coefficients.iter().rev().fold(Scalar::ZERO, |acc, coefficient| acc.mul(x).add(*coefficient))
+}
+
+/// Evaluates a polynomial "in the exponent" from its coefficient commitments
+pub(crate) fn evaluate_commitments(secp: &Secp256k1<All>, commitments: &[PublicKey], x: Scalar) -> Option<PublicKey> {
+    let mut power = Scalar::one();
+    let mut sum = None;
+    for commitment in commitments {
+        sum = add_points(sum, power.mul_point(secp, commitment));
+        power = power.mul(x);
+    }
+    sum
+}
+
+/// Public part of a key generation: the group key and every participant's
+/// verification share, which is all a coordinator needs
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PublicKeyPackage {
+    /// Signatures needed to spend
+    pub threshold: u16,
+    /// Group public key, with an even y coordinate
+    pub group_public_key: PublicKey,
+    /// `share·G` of every participant
+    pub verification_shares: BTreeMap<ParticipantId, PublicKey>,
+}
+
+impl PublicKeyPackage {
+    /// Group key as a BIP340 / taproot internal key
+    pub fn x_only_public_key(&self) -> XOnlyPublicKey {
+        self.group_public_key.x_only_public_key().0
+    }
+
+    /// Participants holding a share
+    pub fn participants(&self) -> Vec<ParticipantId> {
+        self.verification_shares.keys().copied().collect()
+    }
+}
+
+/// Checks a threshold against the participant set
+pub(crate) fn check_parameters(threshold: u16, participants: &[ParticipantId]) -> FrostResult<()> {
+    if threshold < 1 || usize::from(threshold) > participants.len() {
+        return Err(FrostError::InvalidParameters(format!(
+            "Threshold {} is not between 1 and {} participants", threshold, participants.len()
+        )));
+    }
+    if participants.contains(&0) {
+        return Err(FrostError::InvalidParameters("Participant identifiers cannot be zero".to_string()));
+    }
+    let mut sorted = participants.to_vec();
+    sorted.sort_unstable();
+    sorted.dedup();
+    if sorted.len() != participants.len() {
+        return Err(FrostError::InvalidParameters("Duplicate participant identifiers".to_string()));
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_scalar_arithmetic() {
+        let three = Scalar::from_u64(3);
+        assert_eq!(three.mul(three.invert().unwrap()), Scalar::one());
+        assert_eq!(three.sub(three), Scalar::ZERO);
+        assert!(Scalar::ZERO.invert().is_none());
+
+        let mut above = CURVE_ORDER;
+        above[31] += 2;
+        assert_eq!(Scalar::reduce(above), Scalar::from_u64(2));
+        assert!(Scalar::from_bytes(CURVE_ORDER).is_none());
+
+        // f(x) = 2 + 3x + x^2 through 1, 2, 3 interpolates back to f(0)
+        let coefficients = [Scalar::from_u64(2), three, Scalar::one()];
+        let set = [1u16, 2, 3];
+        let secret = set.iter().fold(Scalar::ZERO, |acc, id| {
+            let share = evaluate_polynomial(&coefficients, identifier(*id));
+            acc.add(share.mul(lagrange_coefficient(*id, set).unwrap()))
+        });
+        assert_eq!(secret, Scalar::from_u64(2));
+    }
+}
@@ -0,0 +1,340 @@
+//! Two-round threshold signing
+//!
+//! 1. Every signer calls [`commit`] and sends the resulting
+//!    [`SigningCommitments`] to the coordinator, keeping the nonces.
+//! 2. The coordinator gathers at least `t` commitments into a
+//!    [`SigningPackage`] and sends it to those signers, each of which answers
+//!    with a [`SignatureShare`] from [`sign`].
+//! 3. The coordinator checks every share against the signer's verification
+//!    share and [`aggregate`]s them into a BIP340 signature. Invalid shares
+//!    are attributed to their signers.
+//!
+//! Signatures are made for the group key or, with
+//! [`SigningPackage::with_taproot_tweak`], for the taproot output key built
+//! on it, so the group can spend a key path output directly.
+
+use std::collections::BTreeMap;
+use std::fmt;
+
+use bitcoin::hashes::Hash;
+use bitcoin::secp256k1::schnorr::Signature;
+use bitcoin::secp256k1::{Message, PublicKey, Secp256k1, XOnlyPublicKey};
+use bitcoin::taproot::{TapNodeHash, TapTweakHash};
+
+use super::{
+    add_points, has_even_y, lagrange_coefficient, tagged_hash, FrostError, FrostResult, KeyPackage,
+    ParticipantId, PublicKeyPackage, Scalar,
+};
+
+/// A signer's nonce pair for one signature
+///
+/// Deliberately not `Clone`: signing consumes it, and it is erased when
+/// dropped, so a nonce cannot be used twice.
+pub struct SigningNonces {
+    hiding: Scalar,
+    binding: Scalar,
+    commitments: SigningCommitments,
+}
+
+impl fmt::Debug for SigningNonces {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.debug_struct("SigningNonces").field("commitments", &self.commitments).finish_non_exhaustive()
+    }
+}
+
+impl Drop for SigningNonces {
+    fn drop(&mut self) {
+        self.hiding.erase();
+        self.binding.erase();
+    }
+}
+
+impl SigningNonces {
+    pub fn commitments(&self) -> SigningCommitments {
+        self.commitments
+    }
+}
+
+/// Public commitments to a signer's nonces
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SigningCommitments {
+    pub(crate) id: ParticipantId,
+    pub(crate) hiding: PublicKey,
+    pub(crate) binding: PublicKey,
+}
+
+impl SigningCommitments {
+    pub fn id(&self) -> ParticipantId {
+        self.id
+    }
+}
+
+/// A signer's share of the final signature
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SignatureShare(pub(crate) Scalar);
+
+/// Key a signature is made for
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum KeyTweak {
+    /// The untweaked group key
+    None,
+    /// The BIP341 output key with the group key as internal key
+    Taproot(Option<TapNodeHash>),
+}
+
+/// Everything a signer needs for the second round
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SigningPackage {
+    pub(crate) message: [u8; 32],
+    pub(crate) commitments: BTreeMap<ParticipantId, SigningCommitments>,
+    pub(crate) tweak: KeyTweak,
+}
+
+impl SigningPackage {
+    /// Package for signing a 32-byte message, e.g. a taproot sighash
+    pub fn new(message: [u8; 32], commitments: Vec<SigningCommitments>) -> FrostResult<Self> {
+        let count = commitments.len();
+        let commitments: BTreeMap<_, _> = commitments.into_iter().map(|c| (c.id, c)).collect();
+        if commitments.len() != count {
+            return Err(FrostError::InvalidParameters("Duplicate signer commitments".to_string()));
+        }
+        Ok(Self { message, commitments, tweak: KeyTweak::None })
+    }
+
+    /// Signs for the taproot output key committing to `merkle_root`, or to
+    /// no script tree at all
+    pub fn with_taproot_tweak(mut self, merkle_root: Option<TapNodeHash>) -> Self {
+        self.tweak = KeyTweak::Taproot(merkle_root);
+        self
+    }
+
+    pub fn signers(&self) -> Vec<ParticipantId> {
+        self.commitments.keys().copied().collect()
+    }
+
+    /// Key the aggregate signature verifies against
+    pub fn signing_key(&self, public: &PublicKeyPackage) -> FrostResult<XOnlyPublicKey> {
+        Ok(Session::new(self, public)?.key.x_only_public_key().0)
+    }
+}
+
+/// Values every party derives from a signing package
+struct Session {
+    key: PublicKey,
+    tweak: Scalar,
+    binding_factors: BTreeMap<ParticipantId, Scalar>,
+    nonce: PublicKey,
+    challenge: Scalar,
+}
+
+impl Session {
+    fn new(package: &SigningPackage, public: &PublicKeyPackage) -> FrostResult<Self> {
+        if package.commitments.len() < usize::from(public.threshold) {
+            return Err(FrostError::InvalidParameters(format!(
+                "{} signers cannot meet the threshold of {}", package.commitments.len(), public.threshold
+            )));
+        }
+        if let Some(id) = package.commitments.keys().find(|id| !public.verification_shares.contains_key(id)) {
+            return Err(FrostError::InvalidParameters(format!("Signer {} has no share of this key", id)));
+        }
+
+        let secp = Secp256k1::new();
+        let (key, tweak) = match package.tweak {
+            KeyTweak::None => (public.group_public_key, Scalar::ZERO),
+            KeyTweak::Taproot(merkle_root) => {
+                let tweak = TapTweakHash::from_key_and_tweak(public.x_only_public_key(), merkle_root);
+                let tweak = Scalar::from_bytes(tweak.to_byte_array())
+                    .ok_or_else(|| FrostError::InvalidParameters("Taproot tweak out of range".to_string()))?;
+                let key = add_points(Some(public.group_public_key), tweak.mul_base(&secp))
+                    .ok_or_else(|| FrostError::InvalidParameters("Tweaked key is the point at infinity".to_string()))?;
+                (key, tweak)
+            }
+        };
+        let key_bytes = key.x_only_public_key().0.serialize();
+
+        let encoded: Vec<u8> = package.commitments.values()
+            .flat_map(|c| [&c.id.to_be_bytes()[..], &c.hiding.serialize(), &c.binding.serialize()].concat())
+            .collect();
+        let binding_factors: BTreeMap<_, _> = package.commitments.keys()
+            .map(|id| {
+                let hash = tagged_hash("FROST/rho", &[&key_bytes, &package.message, &encoded, &id.to_be_bytes()]);
+                (*id, Scalar::reduce(hash))
+            })
+            .collect();
+
+        let nonce = package.commitments.values()
+            .fold(None, |sum, c| {
+                let binding = binding_factors[&c.id].mul_point(&secp, &c.binding);
+                add_points(sum, add_points(Some(c.hiding), binding))
+            })
+            .ok_or_else(|| FrostError::InvalidMessage("Group commitment is the point at infinity".to_string()))?;
+        let challenge = Scalar::reduce(tagged_hash("BIP0340/challenge", &[
+            &nonce.x_only_public_key().0.serialize(),
+            &key_bytes,
+            &package.message,
+        ]));
+
+        Ok(Self { key, tweak, binding_factors, nonce, challenge })
+    }
+
+    /// `-1` factors applied to nonces and key shares so the final nonce and
+    /// key both have even y coordinates
+    fn signs(&self, value: Scalar, negate: bool) -> Scalar {
+        if negate { value.negate() } else { value }
+    }
+
+    fn lagrange(&self, id: ParticipantId) -> FrostResult<Scalar> {
+        lagrange_coefficient(id, self.binding_factors.keys().copied())
+    }
+
+    fn verify_share(&self, package: &SigningPackage, id: ParticipantId, share: &SignatureShare, verification_share: &PublicKey) -> FrostResult<bool> {
+        let secp = Secp256k1::new();
+        let commitments = &package.commitments[&id];
+        let mut nonce = add_points(Some(commitments.hiding), self.binding_factors[&id].mul_point(&secp, &commitments.binding));
+        if !has_even_y(&self.nonce) {
+            nonce = nonce.map(|point| point.negate(&secp));
+        }
+        let factor = self.signs(self.challenge.mul(self.lagrange(id)?), !has_even_y(&self.key));
+        let expected = add_points(nonce, factor.mul_point(&secp, verification_share));
+        Ok(share.0.mul_base(&secp) == expected)
+    }
+}
+
+/// Generates a signer's nonces and the commitments to send to the coordinator
+pub fn commit(key_package: &KeyPackage) -> (SigningNonces, SigningCommitments) {
+    let secp = Secp256k1::new();
+    let hiding = Scalar::random();
+    let binding = Scalar::random();
+    let commitments = SigningCommitments {
+        id: key_package.id,
+        hiding: hiding.mul_base(&secp).expect("random nonce is non-zero"),
+        binding: binding.mul_base(&secp).expect("random nonce is non-zero"),
+    };
+    (SigningNonces { hiding, binding, commitments }, commitments)
+}
+
+/// Creates a signature share, consuming the nonces committed to in `package`
+pub fn sign(package: &SigningPackage, nonces: SigningNonces, key_package: &KeyPackage) -> FrostResult<SignatureShare> {
+    if package.commitments.get(&key_package.id) != Some(&nonces.commitments) {
+        return Err(FrostError::InvalidMessage("Signing package does not carry our nonce commitments".to_string()));
+    }
+    let session = Session::new(package, &key_package.public)?;
+
+    let nonce_negated = !has_even_y(&session.nonce);
+    let hiding = session.signs(nonces.hiding, nonce_negated);
+    let binding = session.signs(nonces.binding, nonce_negated);
+    let key_share = session.signs(key_package.signing_share, !has_even_y(&session.key));
+
+    let lambda = session.lagrange(key_package.id)?;
+    let share = SignatureShare(
+        hiding
+            .add(binding.mul(session.binding_factors[&key_package.id]))
+            .add(lambda.mul(session.challenge).mul(key_share)),
+    );
+    Ok(share)
+}
+
+/// Verifies every share and combines them into a BIP340 signature
+///
+/// Fails with [`FrostError::Misbehaving`] naming the signers whose shares
+/// are invalid.
+pub fn aggregate(
+    package: &SigningPackage,
+    shares: &BTreeMap<ParticipantId, SignatureShare>,
+    public: &PublicKeyPackage,
+) -> FrostResult<Signature> {
+    if !shares.keys().eq(package.commitments.keys()) {
+        return Err(FrostError::InvalidMessage("Signature shares do not match the signing package".to_string()));
+    }
+    let session = Session::new(package, public)?;
+
+    let mut misbehaving = Vec::new();
+    for (id, share) in shares {
+        if !session.verify_share(package, *id, share, &public.verification_shares[id])? {
+            misbehaving.push(*id);
+        }
+    }
+    if !misbehaving.is_empty() {
+        return Err(FrostError::Misbehaving(misbehaving));
+    }
+
+    let tweak = session.signs(session.challenge.mul(session.tweak), !has_even_y(&session.key));
+    let z = shares.values().fold(tweak, |sum, share| sum.add(share.0));
+
+    let mut bytes = [0u8; 64];
+    bytes[..32].copy_from_slice(&session.nonce.x_only_public_key().0.serialize());
+    bytes[32..].copy_from_slice(&z.to_bytes());
+    let signature = Signature::from_slice(&bytes).map_err(|e| FrostError::InvalidMessage(e.to_string()))?;
+
+    Secp256k1::verification_only()
+        .verify_schnorr(&signature, &Message::from_digest(package.message), &session.key.x_only_public_key().0)
+        .map_err(|e| FrostError::InvalidMessage(format!("Aggregate signature does not verify: {}", e)))?;
+    Ok(signature)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bitcoin::frost::keys::tests::generate;
+    use bitcoin::key::TapTweak;
+
+    fn run(key_packages: &BTreeMap<ParticipantId, KeyPackage>, signers: &[ParticipantId], package: impl Fn(Vec<SigningCommitments>) -> SigningPackage) -> (SigningPackage, BTreeMap<ParticipantId, SignatureShare>) {
+        let mut nonces = BTreeMap::new();
+        let mut commitments = Vec::new();
+        for id in signers {
+            let (n, c) = commit(&key_packages[id]);
+            nonces.insert(*id, n);
+            commitments.push(c);
+        }
+        let package = package(commitments);
+        let shares = nonces.into_iter()
+            .map(|(id, nonces)| (id, sign(&package, nonces, &key_packages[&id]).unwrap()))
+            .collect();
+        (package, shares)
+    }
+
+    #[test]
+    fn test_every_signer_subset() {
+        let key_packages = generate(2, 3);
+        let public = key_packages[&1].public.clone();
+        let secp = Secp256k1::verification_only();
+        for signers in [&[1, 2][..], &[1, 3], &[2, 3], &[1, 2, 3]] {
+            let (package, shares) = run(&key_packages, signers, |c| SigningPackage::new([7; 32], c).unwrap());
+            let signature = aggregate(&package, &shares, &public).unwrap();
+            assert!(secp.verify_schnorr(&signature, &Message::from_digest([7; 32]), &public.x_only_public_key()).is_ok());
+        }
+    }
+
+    #[test]
+    fn test_taproot_output_key() {
+        let key_packages = generate(3, 5);
+        let public = key_packages[&1].public.clone();
+        let secp = Secp256k1::new();
+        let merkle_root = Some(TapNodeHash::from_byte_array([3; 32]));
+
+        let (package, shares) = run(&key_packages, &[2, 4, 5], |c| {
+            SigningPackage::new([9; 32], c).unwrap().with_taproot_tweak(merkle_root)
+        });
+        let signature = aggregate(&package, &shares, &public).unwrap();
+
+        let (output_key, _) = public.x_only_public_key().tap_tweak(&secp, merkle_root);
+        assert_eq!(package.signing_key(&public).unwrap(), output_key.to_x_only_public_key());
+        assert!(secp.verify_schnorr(&signature, &Message::from_digest([9; 32]), &output_key.to_x_only_public_key()).is_ok());
+    }
+
+    #[test]
+    fn test_misbehaving_signers_identified() {
+        let key_packages = generate(2, 3);
+        let public = key_packages[&1].public.clone();
+        let (package, mut shares) = run(&key_packages, &[1, 3], |c| SigningPackage::new([1; 32], c).unwrap());
+        shares.get_mut(&3).unwrap().0 = shares[&3].0.add(Scalar::one());
+        assert_eq!(aggregate(&package, &shares, &public).err(), Some(FrostError::Misbehaving(vec![3])));
+
+        // Below the threshold, and nonces that are not in the package
+        let (nonces, commitments) = commit(&key_packages[&1]);
+        let package = SigningPackage::new([1; 32], vec![commitments]).unwrap();
+        assert!(matches!(sign(&package, nonces, &key_packages[&1]), Err(FrostError::InvalidParameters(_))));
+        let (other_nonces, _) = commit(&key_packages[&1]);
+        assert!(matches!(sign(&package, other_nonces, &key_packages[&1]), Err(FrostError::InvalidMessage(_))));
+    }
+}
@@ -0,0 +1,549 @@
+//! Message exchange for FROST ceremonies
+//!
+//! Participants talk through a [`FrostTransport`]. Implementations must
+//! authenticate senders and keep point-to-point messages confidential,
+//! since key generation and refresh send secret shares. [`InMemoryTransport`]
+//! connects participants within one process, for tests and for ceremonies
+//! run by a single custodian.
+//!
+//! A [`Ceremony`] drives one participant's side of key generation, share
+//! refresh, signing or coordination over a transport. Messages that arrive
+//! ahead of their round are kept until they are needed.
+
+use std::collections::{BTreeMap, HashMap};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use bitcoin::secp256k1::schnorr::Signature;
+use bitcoin::secp256k1::PublicKey;
+use bitcoin::taproot::TapNodeHash;
+use bitcoin::hashes::Hash;
+use tokio::sync::{mpsc, Mutex};
+
+use super::keys::{self, put_points, RefreshPackage, Round1Package, SecretShare};
+use super::signing::{self, KeyTweak, SigningCommitments, SigningPackage, SignatureShare};
+use super::{FrostError, FrostResult, KeyPackage, ParticipantId, PublicKeyPackage, Scalar};
+
+/// Default time to wait for the other participants in each round
+pub const DEFAULT_ROUND_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// A message of one of the FROST ceremonies
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FrostMessage {
+    /// Key generation commitments and proof of knowledge, broadcast
+    DkgRound1(Round1Package),
+    /// Key generation share, sent privately
+    DkgShare(SecretShare),
+    /// Refresh commitments, broadcast
+    RefreshRound(RefreshPackage),
+    /// Refresh share, sent privately
+    RefreshShare(SecretShare),
+    /// Signer's nonce commitments, sent to the coordinator
+    Commitments(SigningCommitments),
+    /// Coordinator's request for signature shares
+    SigningPackage(SigningPackage),
+    /// Signer's signature share, sent to the coordinator
+    SignatureShare(SignatureShare),
+    /// Digest of the key generation or refresh packages a participant
+    /// received, broadcast to check everyone saw the same ones
+    Echo([u8; 32]),
+}
+
+impl FrostMessage {
+    /// Compact binary encoding for transports that carry bytes
+    pub fn encode(&self) -> Vec<u8> {
+        let mut out = Vec::new();
+        match self {
+            FrostMessage::DkgRound1(package) => {
+                out.push(0);
+                put_points(&mut out, &package.commitments);
+                out.extend_from_slice(&package.proof_nonce.serialize());
+                out.extend_from_slice(&package.proof_response.to_bytes());
+            }
+            FrostMessage::DkgShare(share) => {
+                out.push(1);
+                out.extend_from_slice(&share.0.to_bytes());
+            }
+            FrostMessage::RefreshRound(package) => {
+                out.push(2);
+                put_points(&mut out, &package.commitments);
+            }
+            FrostMessage::RefreshShare(share) => {
+                out.push(3);
+                out.extend_from_slice(&share.0.to_bytes());
+            }
+            FrostMessage::Commitments(commitments) => {
+                out.push(4);
+                put_commitments(&mut out, commitments);
+            }
+            FrostMessage::SigningPackage(package) => {
+                out.push(5);
+                out.extend_from_slice(&package.message);
+                match package.tweak {
+                    KeyTweak::None => out.push(0),
+                    KeyTweak::Taproot(None) => out.push(1),
+                    KeyTweak::Taproot(Some(root)) => {
+                        out.push(2);
+                        out.extend_from_slice(root.as_byte_array());
+                    }
+                }
+                out.extend_from_slice(&(package.commitments.len() as u16).to_be_bytes());
+                package.commitments.values().for_each(|c| put_commitments(&mut out, c));
+            }
+            FrostMessage::SignatureShare(share) => {
+                out.push(6);
+                out.extend_from_slice(&share.0.to_bytes());
+            }
+            FrostMessage::Echo(digest) => {
+                out.push(7);
+                out.extend_from_slice(digest);
+            }
+        }
+        out
+    }
+
+    pub fn decode(bytes: &[u8]) -> FrostResult<Self> {
+        let mut reader = Reader(bytes);
+        let message = match reader.byte()? {
+            0 => FrostMessage::DkgRound1(Round1Package {
+                commitments: reader.points()?,
+                proof_nonce: reader.point()?,
+                proof_response: reader.scalar()?,
+            }),
+            1 => FrostMessage::DkgShare(SecretShare(reader.scalar()?)),
+            2 => FrostMessage::RefreshRound(RefreshPackage { commitments: reader.points()? }),
+            3 => FrostMessage::RefreshShare(SecretShare(reader.scalar()?)),
+            4 => FrostMessage::Commitments(reader.commitments()?),
+            5 => {
+                let message = reader.array::<32>()?;
+                let tweak = match reader.byte()? {
+                    0 => KeyTweak::None,
+                    1 => KeyTweak::Taproot(None),
+                    2 => KeyTweak::Taproot(Some(TapNodeHash::from_byte_array(reader.array::<32>()?))),
+                    other => return Err(FrostError::InvalidMessage(format!("Unknown key tweak {}", other))),
+                };
+                let count = u16::from_be_bytes(reader.array::<2>()?);
+                let commitments = (0..count).map(|_| reader.commitments()).collect::<FrostResult<Vec<_>>>()?;
+                let package = SigningPackage::new(message, commitments)
+                    .map_err(|e| FrostError::InvalidMessage(e.to_string()))?;
+                FrostMessage::SigningPackage(SigningPackage { tweak, ..package })
+            }
+            6 => FrostMessage::SignatureShare(SignatureShare(reader.scalar()?)),
+            7 => FrostMessage::Echo(reader.array::<32>()?),
+            other => return Err(FrostError::InvalidMessage(format!("Unknown message type {}", other))),
+        };
+        if !reader.0.is_empty() {
+            return Err(FrostError::InvalidMessage("Trailing bytes".to_string()));
+        }
+        Ok(message)
+    }
+}
+
+fn put_commitments(out: &mut Vec<u8>, commitments: &SigningCommitments) {
+    out.extend_from_slice(&commitments.id.to_be_bytes());
+    out.extend_from_slice(&commitments.hiding.serialize());
+    out.extend_from_slice(&commitments.binding.serialize());
+}
+
+struct Reader<'a>(&'a [u8]);
+
+impl Reader<'_> {
+    fn take(&mut self, n: usize) -> FrostResult<&[u8]> {
+        if self.0.len() < n {
+            return Err(FrostError::InvalidMessage("Truncated message".to_string()));
+        }
+        let (head, tail) = self.0.split_at(n);
+        self.0 = tail;
+        Ok(head)
+    }
+
+    fn byte(&mut self) -> FrostResult<u8> {
+        Ok(self.take(1)?[0])
+    }
+
+    fn array<const N: usize>(&mut self) -> FrostResult<[u8; N]> {
+        Ok(self.take(N)?.try_into().expect("length checked"))
+    }
+
+    fn point(&mut self) -> FrostResult<PublicKey> {
+        PublicKey::from_slice(self.take(33)?).map_err(|e| FrostError::InvalidMessage(e.to_string()))
+    }
+
+    fn points(&mut self) -> FrostResult<Vec<PublicKey>> {
+        let count = u16::from_be_bytes(self.array::<2>()?);
+        (0..count).map(|_| self.point()).collect()
+    }
+
+    fn scalar(&mut self) -> FrostResult<Scalar> {
+        Scalar::from_bytes(self.array::<32>()?)
+            .ok_or_else(|| FrostError::InvalidMessage("Scalar out of range".to_string()))
+    }
+
+    fn commitments(&mut self) -> FrostResult<SigningCommitments> {
+        Ok(SigningCommitments {
+            id: u16::from_be_bytes(self.array::<2>()?),
+            hiding: self.point()?,
+            binding: self.point()?,
+        })
+    }
+}
+
+/// Authenticated, confidential channel between ceremony participants
+#[async_trait]
+pub trait FrostTransport: Send + Sync {
+    /// Delivers a message from `from` to `to`
+    async fn send(&self, from: ParticipantId, to: ParticipantId, message: FrostMessage) -> FrostResult<()>;
+
+    /// Waits for the next message addressed to `participant`, with its sender
+    async fn receive(&self, participant: ParticipantId) -> FrostResult<(ParticipantId, FrostMessage)>;
+
+    /// Sends the same message to several participants
+    async fn broadcast(&self, from: ParticipantId, to: &[ParticipantId], message: FrostMessage) -> FrostResult<()> {
+        for recipient in to {
+            self.send(from, *recipient, message.clone()).await?;
+        }
+        Ok(())
+    }
+}
+
+type Envelope = (ParticipantId, FrostMessage);
+
+/// Transport connecting participants in the same process
+pub struct InMemoryTransport {
+    senders: HashMap<ParticipantId, mpsc::UnboundedSender<Envelope>>,
+    receivers: HashMap<ParticipantId, Mutex<mpsc::UnboundedReceiver<Envelope>>>,
+}
+
+impl InMemoryTransport {
+    /// Creates a mailbox for each participant, coordinators included
+    pub fn new(participants: &[ParticipantId]) -> Self {
+        let mut senders = HashMap::new();
+        let mut receivers = HashMap::new();
+        for id in participants {
+            let (sender, receiver) = mpsc::unbounded_channel();
+            senders.insert(*id, sender);
+            receivers.insert(*id, Mutex::new(receiver));
+        }
+        Self { senders, receivers }
+    }
+}
+
+#[async_trait]
+impl FrostTransport for InMemoryTransport {
+    async fn send(&self, from: ParticipantId, to: ParticipantId, message: FrostMessage) -> FrostResult<()> {
+        let sender = self.senders.get(&to)
+            .ok_or_else(|| FrostError::Transport(format!("Unknown participant {}", to)))?;
+        sender.send((from, message)).map_err(|e| FrostError::Transport(e.to_string()))
+    }
+
+    async fn receive(&self, participant: ParticipantId) -> FrostResult<Envelope> {
+        let receiver = self.receivers.get(&participant)
+            .ok_or_else(|| FrostError::Transport(format!("Unknown participant {}", participant)))?;
+        receiver.lock().await.recv().await
+            .ok_or_else(|| FrostError::Transport("Transport closed".to_string()))
+    }
+}
+
+/// One participant's side of the FROST ceremonies
+pub struct Ceremony<'a, T: FrostTransport + ?Sized> {
+    transport: &'a T,
+    id: ParticipantId,
+    timeout: Duration,
+    pending: Vec<Envelope>,
+}
+
+impl<'a, T: FrostTransport + ?Sized> Ceremony<'a, T> {
+    pub fn new(transport: &'a T, id: ParticipantId) -> Self {
+        Self { transport, id, timeout: DEFAULT_ROUND_TIMEOUT, pending: Vec::new() }
+    }
+
+    /// Sets how long to wait for the other participants in each round
+    pub fn with_timeout(mut self, timeout: Duration) -> Self {
+        self.timeout = timeout;
+        self
+    }
+
+    /// Takes one message of the kind `pick` accepts from each of `from`,
+    /// keeping messages for later rounds
+    async fn collect<M>(
+        &mut self,
+        from: &[ParticipantId],
+        pick: impl Fn(&FrostMessage) -> Option<M>,
+    ) -> FrostResult<BTreeMap<ParticipantId, M>> {
+        let mut collected = BTreeMap::new();
+        let mut kept = Vec::new();
+        for (sender, message) in std::mem::take(&mut self.pending) {
+            match pick(&message) {
+                Some(m) if from.contains(&sender) && !collected.contains_key(&sender) => {
+                    collected.insert(sender, m);
+                }
+                _ => kept.push((sender, message)),
+            }
+        }
+        self.pending = kept;
+
+        let deadline = tokio::time::Instant::now() + self.timeout;
+        while collected.len() < from.len() {
+            let received = tokio::time::timeout_at(deadline, self.transport.receive(self.id)).await;
+            let Ok(received) = received else {
+                let missing = from.iter().copied().filter(|id| !collected.contains_key(id)).collect();
+                return Err(FrostError::Timeout(missing));
+            };
+            let (sender, message) = received?;
+            match pick(&message) {
+                Some(m) if from.contains(&sender) && !collected.contains_key(&sender) => {
+                    collected.insert(sender, m);
+                }
+                _ => self.pending.push((sender, message)),
+            }
+        }
+        Ok(collected)
+    }
+
+    fn others(&self, participants: &[ParticipantId]) -> Vec<ParticipantId> {
+        participants.iter().copied().filter(|id| *id != self.id).collect()
+    }
+
+    /// Echo round: exchanges digests of the broadcasts received and aborts
+    /// unless everyone saw the same ones
+    async fn echo(&mut self, others: &[ParticipantId], digest: [u8; 32]) -> FrostResult<()> {
+        self.transport.broadcast(self.id, others, FrostMessage::Echo(digest)).await?;
+        let echoes = self.collect(others, |m| match m {
+            FrostMessage::Echo(digest) => Some(*digest),
+            _ => None,
+        }).await?;
+        keys::check_echoes(&digest, &echoes)
+    }
+
+    /// Runs distributed key generation with `participants`
+    pub async fn dkg(&mut self, participants: &[ParticipantId], threshold: u16) -> FrostResult<KeyPackage> {
+        let others = self.others(participants);
+        let (secret, package) = keys::part1(self.id, threshold, participants)?;
+        self.transport.broadcast(self.id, &others, FrostMessage::DkgRound1(package.clone())).await?;
+
+        let round1 = self.collect(&others, |m| match m {
+            FrostMessage::DkgRound1(package) => Some(package.clone()),
+            _ => None,
+        }).await?;
+        let mut all = round1.clone();
+        all.insert(self.id, package);
+        self.echo(&others, keys::round1_digest(&all)).await?;
+
+        let (secret, shares) = keys::part2(secret, round1)?;
+        for (to, share) in shares {
+            self.transport.send(self.id, to, FrostMessage::DkgShare(share)).await?;
+        }
+
+        let shares = self.collect(&others, |m| match m {
+            FrostMessage::DkgShare(share) => Some(share.clone()),
+            _ => None,
+        }).await?;
+        keys::part3(secret, shares)
+    }
+
+    /// Refreshes `key_package` together with every other participant
+    pub async fn refresh(&mut self, key_package: &KeyPackage) -> FrostResult<KeyPackage> {
+        let others = self.others(&key_package.public.participants());
+        let (secret, package, shares) = keys::refresh_part1(key_package);
+        self.transport.broadcast(self.id, &others, FrostMessage::RefreshRound(package.clone())).await?;
+
+        let packages = self.collect(&others, |m| match m {
+            FrostMessage::RefreshRound(package) => Some(package.clone()),
+            _ => None,
+        }).await?;
+        let mut all = packages.clone();
+        all.insert(self.id, package);
+        self.echo(&others, keys::refresh_digest(&all)).await?;
+
+        for (to, share) in shares {
+            self.transport.send(self.id, to, FrostMessage::RefreshShare(share)).await?;
+        }
+        let shares = self.collect(&others, |m| match m {
+            FrostMessage::RefreshShare(share) => Some(share.clone()),
+            _ => None,
+        }).await?;
+        keys::refresh_part2(key_package, secret, packages, shares)
+    }
+
+    /// Takes part in one signature requested by `coordinator`
+    pub async fn sign(&mut self, key_package: &KeyPackage, coordinator: ParticipantId) -> FrostResult<()> {
+        let (nonces, commitments) = signing::commit(key_package);
+        self.transport.send(self.id, coordinator, FrostMessage::Commitments(commitments)).await?;
+
+        let mut packages = self.collect(&[coordinator], |m| match m {
+            FrostMessage::SigningPackage(package) => Some(package.clone()),
+            _ => None,
+        }).await?;
+        let package = packages.remove(&coordinator).expect("collected from the coordinator");
+        let share = signing::sign(&package, nonces, key_package)?;
+        self.transport.send(self.id, coordinator, FrostMessage::SignatureShare(share)).await
+    }
+
+    /// Coordinates a signature over `message` by `signers`
+    ///
+    /// The coordinator only needs the public key package; a participant that
+    /// also signs uses a separate transport identity for coordinating.
+    pub async fn coordinate(
+        &mut self,
+        public: &PublicKeyPackage,
+        signers: &[ParticipantId],
+        message: [u8; 32],
+        tweak: KeyTweak,
+    ) -> FrostResult<Signature> {
+        let commitments = self.collect(signers, |m| match m {
+            FrostMessage::Commitments(commitments) => Some(*commitments),
+            _ => None,
+        }).await?;
+        if let Some((sender, _)) = commitments.iter().find(|(sender, c)| c.id != **sender) {
+            return Err(FrostError::Misbehaving(vec![*sender]));
+        }
+
+        let mut package = SigningPackage::new(message, commitments.into_values().collect())?;
+        package.tweak = tweak;
+        self.transport.broadcast(self.id, signers, FrostMessage::SigningPackage(package.clone())).await?;
+
+        let shares = self.collect(signers, |m| match m {
+            FrostMessage::SignatureShare(share) => Some(*share),
+            _ => None,
+        }).await?;
+        signing::aggregate(&package, &shares, public)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bitcoin::frost::keys::tests::generate;
+    use crate::bitcoin::frost::signing::commit;
+    use bitcoin::secp256k1::{Message, Secp256k1};
+    use std::sync::Arc;
+
+    const COORDINATOR: ParticipantId = 0;
+
+    #[tokio::test]
+    async fn test_ceremonies_over_transport() {
+        let participants = [1, 2, 3];
+        let transport = Arc::new(InMemoryTransport::new(&[COORDINATOR, 1, 2, 3]));
+
+        let handles: Vec<_> = participants.iter()
+            .map(|id| {
+                let transport = transport.clone();
+                let id = *id;
+                tokio::spawn(async move {
+                    let mut ceremony = Ceremony::new(transport.as_ref(), id);
+                    let key_package = ceremony.dkg(&participants, 2).await?;
+                    ceremony.refresh(&key_package).await
+                })
+            })
+            .collect();
+        let mut key_packages = BTreeMap::new();
+        for handle in handles {
+            let key_package = handle.await.unwrap().unwrap();
+            key_packages.insert(key_package.id(), key_package);
+        }
+        let public = key_packages[&1].public().clone();
+        assert!(key_packages.values().all(|k| *k.public() == public));
+
+        let signers = [1, 3];
+        let handles: Vec<_> = signers.iter()
+            .map(|id| {
+                let transport = transport.clone();
+                let key_package = key_packages[id].clone();
+                tokio::spawn(async move {
+                    Ceremony::new(transport.as_ref(), key_package.id()).sign(&key_package, COORDINATOR).await
+                })
+            })
+            .collect();
+        let signature = Ceremony::new(transport.as_ref(), COORDINATOR)
+            .coordinate(&public, &signers, [5; 32], KeyTweak::None)
+            .await
+            .unwrap();
+        for handle in handles {
+            handle.await.unwrap().unwrap();
+        }
+
+        let secp = Secp256k1::verification_only();
+        assert!(secp.verify_schnorr(&signature, &Message::from_digest([5; 32]), &public.x_only_public_key()).is_ok());
+    }
+
+    #[tokio::test]
+    async fn test_silent_signer_times_out() {
+        let key_packages = generate(2, 3);
+        let transport = InMemoryTransport::new(&[COORDINATOR, 1, 2, 3]);
+        let (_, commitments) = commit(&key_packages[&1]);
+        transport.send(1, COORDINATOR, FrostMessage::Commitments(commitments)).await.unwrap();
+
+        let result = Ceremony::new(&transport, COORDINATOR)
+            .with_timeout(Duration::from_millis(50))
+            .coordinate(key_packages[&1].public(), &[1, 2], [0; 32], KeyTweak::None)
+            .await;
+        assert_eq!(result.err(), Some(FrostError::Timeout(vec![2])));
+    }
+
+    #[tokio::test]
+    async fn test_equivocation_aborts_dkg() {
+        let participants = [1, 2, 3];
+        let transport = Arc::new(InMemoryTransport::new(&participants));
+        let handles: Vec<_> = [1, 2].into_iter()
+            .map(|id| {
+                let transport = transport.clone();
+                tokio::spawn(async move {
+                    Ceremony::new(transport.as_ref(), id)
+                        .with_timeout(Duration::from_secs(5))
+                        .dkg(&participants, 2)
+                        .await
+                })
+            })
+            .collect();
+
+        // Participant 3 shows each peer a different package and echoes back
+        // whatever that peer saw
+        let (_, to_1) = keys::part1(3, 2, &participants).unwrap();
+        let (_, to_2) = keys::part1(3, 2, &participants).unwrap();
+        transport.send(3, 1, FrostMessage::DkgRound1(to_1.clone())).await.unwrap();
+        transport.send(3, 2, FrostMessage::DkgRound1(to_2.clone())).await.unwrap();
+        let mut received = BTreeMap::new();
+        while received.len() < 2 {
+            if let (from, FrostMessage::DkgRound1(package)) = transport.receive(3).await.unwrap() {
+                received.insert(from, package);
+            }
+        }
+        for (to, package) in [(1, to_1), (2, to_2)] {
+            let mut view = received.clone();
+            view.insert(3, package);
+            transport.send(3, to, FrostMessage::Echo(keys::round1_digest(&view))).await.unwrap();
+        }
+
+        let results: Vec<_> = futures::future::join_all(handles).await;
+        assert_eq!(results[0].as_ref().unwrap().as_ref().err(), Some(&FrostError::Misbehaving(vec![2])));
+        assert_eq!(results[1].as_ref().unwrap().as_ref().err(), Some(&FrostError::Misbehaving(vec![1])));
+    }
+
+    #[test]
+    fn test_message_encoding() {
+        let key_packages = generate(2, 3);
+        let (_, round1) = keys::part1(1, 2, &[1, 2, 3]).unwrap();
+        let (_, refresh, shares) = keys::refresh_part1(&key_packages[&1]);
+        let (_, c1) = commit(&key_packages[&1]);
+        let (_, c2) = commit(&key_packages[&2]);
+        let package = SigningPackage::new([4; 32], vec![c1, c2]).unwrap()
+            .with_taproot_tweak(Some(TapNodeHash::from_byte_array([8; 32])));
+
+        let messages = [
+            FrostMessage::DkgRound1(round1),
+            FrostMessage::DkgShare(shares[&2].clone()),
+            FrostMessage::RefreshRound(refresh),
+            FrostMessage::RefreshShare(shares[&3].clone()),
+            FrostMessage::Commitments(c1),
+            FrostMessage::SigningPackage(package),
+            FrostMessage::SignatureShare(SignatureShare(Scalar::from_u64(11))),
+            FrostMessage::Echo([6; 32]),
+        ];
+        for message in messages {
+            let encoded = message.encode();
+            assert_eq!(FrostMessage::decode(&encoded).unwrap(), message);
+            assert!(FrostMessage::decode(&encoded[..encoded.len() - 1]).is_err());
+        }
+        assert!(FrostMessage::decode(&[9]).is_err());
+    }
+}
@@ -0,0 +1,441 @@
+//! Miniscript policies and descriptors
+//!
+//! Policies, miniscript and descriptors are rust-miniscript's. Spending
+//! conditions are written once in the policy language, e.g.
+//! `or(pk(A),and(pk(B),older(144)))`, and compiled into a `wsh` descriptor
+//! or a `tr` descriptor with a tapscript leaf per alternative, placed by
+//! probability. A descriptor gives the address, bounds the satisfaction
+//! weight and builds the witness from the signatures, preimages and
+//! timelocks in a [`Satisfier`].
+
+use std::collections::HashMap;
+use std::str::FromStr;
+
+use bitcoin::hashes::{hash160, ripemd160, sha256, Hash};
+use bitcoin::secp256k1::{Secp256k1, XOnlyPublicKey};
+use bitcoin::sighash::Prevouts;
+use bitcoin::taproot::TapLeafHash;
+use bitcoin::{absolute, relative, PublicKey, Sequence, Transaction, TxOut};
+use miniscript::policy::Concrete;
+use miniscript::{hash256, Interpreter, Translator};
+
+use crate::bitcoin::error::{BitcoinError, BitcoinResult};
+
+pub use miniscript::{Descriptor, Miniscript, Segwitv0, Tap};
+
+/// Spending policy over public keys
+pub type Policy = Concrete<PublicKey>;
+
+/// Compressed encoding of the BIP341 NUMS point H, whose discrete log nobody knows
+const UNSPENDABLE_KEY: &str = "0250929b74c1a04954b78b4b6035e97a5e078a5a0f28ec96d547bfee9ace803ac0";
+
+/// Scale of the integer leaf weights handed to the taproot tree builder
+const TAP_WEIGHT_SCALE: f64 = 1_000_000.0;
+
+fn error(message: impl ToString) -> BitcoinError {
+    BitcoinError::Miniscript(message.to_string())
+}
+
+/// Internal key that disables the taproot key path
+pub fn unspendable_key() -> PublicKey {
+    PublicKey::from_str(UNSPENDABLE_KEY).expect("H is a valid point")
+}
+
+/// Resolves key names of a policy, falling back to hex keys
+struct KeyNames<'a>(&'a HashMap<String, PublicKey>);
+
+impl Translator<String, PublicKey, BitcoinError> for KeyNames<'_> {
+    fn pk(&mut self, name: &String) -> BitcoinResult<PublicKey> {
+        match self.0.get(name) {
+            Some(key) => Ok(*key),
+            None => PublicKey::from_str(name).map_err(|_| error(format!("Unknown key: {}", name))),
+        }
+    }
+
+    fn sha256(&mut self, hash: &String) -> BitcoinResult<sha256::Hash> {
+        hash.parse().map_err(error)
+    }
+
+    fn hash256(&mut self, hash: &String) -> BitcoinResult<hash256::Hash> {
+        hash.parse().map_err(error)
+    }
+
+    fn ripemd160(&mut self, hash: &String) -> BitcoinResult<ripemd160::Hash> {
+        hash.parse().map_err(error)
+    }
+
+    fn hash160(&mut self, hash: &String) -> BitcoinResult<hash160::Hash> {
+        hash.parse().map_err(error)
+    }
+}
+
+/// Parse a policy whose keys are given by name, e.g. `pk(A)`, or as hex
+pub fn parse_policy(s: &str, keys: &HashMap<String, PublicKey>) -> BitcoinResult<Policy> {
+    let policy = Concrete::<String>::from_str(s)?.translate_pk(&mut KeyNames(keys))?;
+    policy.is_valid().map_err(error)?;
+    Ok(policy)
+}
+
+/// Compile a policy to a P2WSH descriptor
+pub fn compile_wsh(policy: &Policy) -> BitcoinResult<Descriptor<PublicKey>> {
+    let miniscript = policy.compile::<Segwitv0>().map_err(error)?;
+    Ok(Descriptor::new_wsh(miniscript)?)
+}
+
+/// Compile a policy to a taproot descriptor
+///
+/// The likeliest key that can spend on its own becomes the key path and the
+/// other alternatives get a leaf each. Without such a key the key path is
+/// disabled with [`unspendable_key`].
+pub fn compile_tr(policy: &Policy) -> BitcoinResult<Descriptor<PublicKey>> {
+    Ok(policy.compile_tr(Some(unspendable_key()))?)
+}
+
+/// A tapscript leaf for every alternative of a policy, weighted by its probability
+pub fn tap_leaves(policy: &Policy) -> BitcoinResult<Vec<(u32, Miniscript<PublicKey, Tap>)>> {
+    fn alternatives(policy: &Policy, probability: f64, out: &mut Vec<(f64, Policy)>) {
+        match policy {
+            Concrete::Or(subs) => {
+                let total: usize = subs.iter().map(|(odds, _)| odds).sum();
+                for (odds, sub) in subs {
+                    alternatives(sub, probability * *odds as f64 / total as f64, out);
+                }
+            }
+            Concrete::Thresh(thresh) if thresh.is_or() => {
+                for sub in thresh.iter() {
+                    alternatives(sub, probability / thresh.n() as f64, out);
+                }
+            }
+            Concrete::Unsatisfiable => {}
+            _ => out.push((probability, policy.clone())),
+        }
+    }
+
+    let mut branches = Vec::new();
+    alternatives(policy, 1.0, &mut branches);
+    branches.into_iter()
+        .map(|(probability, branch)| {
+            let weight = (probability * TAP_WEIGHT_SCALE).round().max(1.0) as u32;
+            Ok((weight, branch.compile::<Tap>().map_err(error)?))
+        })
+        .collect()
+}
+
+/// Check that input `input_index` of `tx` spends its previous output, running
+/// the script with the signatures and timelocks of the transaction
+pub fn verify_input(tx: &Transaction, input_index: usize, prevouts: &Prevouts<'_, TxOut>) -> BitcoinResult<bool> {
+    let input = tx.input.get(input_index)
+        .ok_or_else(|| error(format!("Transaction has no input {}", input_index)))?;
+    let spent = match prevouts {
+        Prevouts::One(index, spent) if *index == input_index => spent,
+        Prevouts::All(spent) => spent.get(input_index)
+            .ok_or_else(|| error(format!("No previous output for input {}", input_index)))?,
+        Prevouts::One(..) => return Err(error(format!("No previous output for input {}", input_index))),
+    };
+
+    let Ok(interpreter) = Interpreter::from_txdata(
+        &spent.script_pubkey,
+        &input.script_sig,
+        &input.witness,
+        input.sequence,
+        tx.lock_time,
+    ) else {
+        return Ok(false);
+    };
+    let secp = Secp256k1::verification_only();
+    let valid = interpreter.iter(&secp, tx, input_index, prevouts).all(|step| step.is_ok());
+    Ok(valid)
+}
+
+/// Data available to satisfy a descriptor: signatures, hash preimages and
+/// the timelocks of the spending input
+#[derive(Debug, Clone)]
+pub struct Satisfier {
+    ecdsa_signatures: HashMap<PublicKey, bitcoin::ecdsa::Signature>,
+    key_spend_signature: Option<bitcoin::taproot::Signature>,
+    leaf_signatures: HashMap<(XOnlyPublicKey, TapLeafHash), bitcoin::taproot::Signature>,
+    preimages: Vec<[u8; 32]>,
+    sequence: Sequence,
+    lock_time: absolute::LockTime,
+}
+
+impl Default for Satisfier {
+    fn default() -> Self {
+        Self {
+            ecdsa_signatures: HashMap::new(),
+            key_spend_signature: None,
+            leaf_signatures: HashMap::new(),
+            preimages: Vec::new(),
+            sequence: Sequence::MAX,
+            lock_time: absolute::LockTime::ZERO,
+        }
+    }
+}
+
+impl Satisfier {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Satisfier using the timelocks of input `index` of `tx`
+    pub fn for_input(tx: &Transaction, index: usize) -> BitcoinResult<Self> {
+        let input = tx.input.get(index)
+            .ok_or_else(|| error(format!("Transaction has no input {}", index)))?;
+        Ok(Self::new().with_sequence(input.sequence).with_lock_time(tx.lock_time))
+    }
+
+    /// Add a segwit v0 signature
+    pub fn with_ecdsa_signature(mut self, key: PublicKey, signature: bitcoin::ecdsa::Signature) -> Self {
+        self.ecdsa_signatures.insert(key, signature);
+        self
+    }
+
+    /// Add the taproot key path signature
+    pub fn with_key_spend_signature(mut self, signature: bitcoin::taproot::Signature) -> Self {
+        self.key_spend_signature = Some(signature);
+        self
+    }
+
+    /// Add a taproot signature for the script path through `leaf`
+    pub fn with_leaf_signature(mut self, key: XOnlyPublicKey, leaf: TapLeafHash, signature: bitcoin::taproot::Signature) -> Self {
+        self.leaf_signatures.insert((key, leaf), signature);
+        self
+    }
+
+    /// Add a 32-byte hash preimage
+    pub fn with_preimage(mut self, preimage: [u8; 32]) -> Self {
+        self.preimages.push(preimage);
+        self
+    }
+
+    /// Set the sequence of the spending input
+    pub fn with_sequence(mut self, sequence: Sequence) -> Self {
+        self.sequence = sequence;
+        self
+    }
+
+    /// Set the lock time of the spending transaction
+    pub fn with_lock_time(mut self, lock_time: absolute::LockTime) -> Self {
+        self.lock_time = lock_time;
+        self
+    }
+
+    fn preimage<H: Hash>(&self, hash: &H) -> Option<[u8; 32]> {
+        self.preimages.iter().find(|preimage| <H as Hash>::hash(&preimage[..]) == *hash).copied()
+    }
+}
+
+impl miniscript::Satisfier<PublicKey> for Satisfier {
+    fn lookup_ecdsa_sig(&self, key: &PublicKey) -> Option<bitcoin::ecdsa::Signature> {
+        self.ecdsa_signatures.get(key).copied()
+    }
+
+    fn lookup_tap_key_spend_sig(&self) -> Option<bitcoin::taproot::Signature> {
+        self.key_spend_signature
+    }
+
+    fn lookup_tap_leaf_script_sig(&self, key: &PublicKey, leaf: &TapLeafHash) -> Option<bitcoin::taproot::Signature> {
+        self.leaf_signatures.get(&(key.inner.x_only_public_key().0, *leaf)).copied()
+    }
+
+    fn lookup_sha256(&self, hash: &sha256::Hash) -> Option<[u8; 32]> {
+        self.preimage(hash)
+    }
+
+    fn lookup_hash256(&self, hash: &hash256::Hash) -> Option<[u8; 32]> {
+        self.preimage(hash)
+    }
+
+    fn lookup_ripemd160(&self, hash: &ripemd160::Hash) -> Option<[u8; 32]> {
+        self.preimage(hash)
+    }
+
+    fn lookup_hash160(&self, hash: &hash160::Hash) -> Option<[u8; 32]> {
+        self.preimage(hash)
+    }
+
+    fn check_older(&self, lock: relative::LockTime) -> bool {
+        miniscript::Satisfier::<PublicKey>::check_older(&self.sequence, lock)
+    }
+
+    fn check_after(&self, lock: absolute::LockTime) -> bool {
+        self.sequence.enables_absolute_lock_time()
+            && miniscript::Satisfier::<PublicKey>::check_after(&self.lock_time, lock)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use bitcoin::secp256k1::{Keypair, Message, SecretKey};
+    use bitcoin::sighash::SighashCache;
+    use bitcoin::taproot::{ControlBlock, LeafVersion};
+    use bitcoin::transaction::Version;
+    use bitcoin::{Amount, EcdsaSighashType, Network, OutPoint, ScriptBuf, TxIn, Witness};
+
+    const AMOUNT: Amount = Amount::from_sat(100_000);
+
+    pub(crate) fn key(seed: u8) -> (SecretKey, PublicKey) {
+        let secret = SecretKey::from_slice(&[seed; 32]).unwrap();
+        (secret, PublicKey::new(secret.public_key(&Secp256k1::new())))
+    }
+
+    fn named_keys() -> HashMap<String, PublicKey> {
+        (1..=3).map(|i| (((b'A' + i - 1) as char).to_string(), key(i).1)).collect()
+    }
+
+    fn spend(sequence: Sequence) -> Transaction {
+        Transaction {
+            version: Version::TWO,
+            lock_time: absolute::LockTime::ZERO,
+            input: vec![TxIn { previous_output: OutPoint::null(), script_sig: ScriptBuf::new(), sequence, witness: Witness::new() }],
+            output: vec![TxOut { value: Amount::from_sat(99_000), script_pubkey: ScriptBuf::new() }],
+        }
+    }
+
+    fn sign(tx: &Transaction, script: &ScriptBuf, secret: &SecretKey) -> bitcoin::ecdsa::Signature {
+        let sighash = SighashCache::new(tx).p2wsh_signature_hash(0, script, AMOUNT, EcdsaSighashType::All).unwrap();
+        let msg = Message::from_digest(sighash.to_byte_array());
+        bitcoin::ecdsa::Signature::sighash_all(Secp256k1::new().sign_ecdsa(&msg, secret))
+    }
+
+    #[test]
+    fn test_parse_policy() {
+        let keys = named_keys();
+        let policy = parse_policy("or(9@pk(A),and(pk(B),older(144)))", &keys).unwrap();
+        assert_eq!(
+            policy,
+            format!("or(9@pk({}),and(pk({}),older(144)))", keys["A"], keys["B"]).parse::<Policy>().unwrap()
+        );
+
+        for invalid in ["pk(D)", "and(pk(A))", "thresh(3,pk(A),pk(B))", "older(0)", "or(pk(A),pk(A))", "pk(A"] {
+            assert!(parse_policy(invalid, &keys).is_err(), "{}", invalid);
+        }
+    }
+
+    #[test]
+    fn test_wsh_address_weight_and_spend() {
+        let keys = named_keys();
+        let (secret_a, a) = key(1);
+        let (secret_b, b) = key(2);
+        let descriptor = compile_wsh(&parse_policy("or(pk(A),and(pk(B),older(144)))", &keys).unwrap()).unwrap();
+        assert_eq!(descriptor.to_string().split('#').next().unwrap(), format!("wsh(or_d(pk({}),and_v(v:pk({}),older(144))))", a, b));
+        assert_eq!(descriptor.to_string().parse::<Descriptor<PublicKey>>().unwrap(), descriptor);
+
+        let address = descriptor.address(Network::Bitcoin).unwrap();
+        assert!(address.to_string().starts_with("bc1q"));
+        assert_eq!(address.script_pubkey(), descriptor.script_pubkey());
+
+        let script = descriptor.explicit_script().unwrap();
+        let spent = TxOut { value: AMOUNT, script_pubkey: descriptor.script_pubkey() };
+        let max_weight = descriptor.max_weight_to_satisfy().unwrap();
+
+        // A can spend at once
+        let mut tx = spend(Sequence::ENABLE_RBF_NO_LOCKTIME);
+        let satisfier = Satisfier::for_input(&tx, 0).unwrap().with_ecdsa_signature(a, sign(&tx, &script, &secret_a));
+        descriptor.satisfy(&mut tx.input[0], &satisfier).unwrap();
+        assert!(verify_input(&tx, 0, &Prevouts::One(0, spent.clone())).unwrap());
+        assert!(tx.input[0].segwit_weight() - spend(Sequence::ZERO).input[0].segwit_weight() <= max_weight);
+        let wrong_amount = TxOut { value: Amount::from_sat(1), ..spent.clone() };
+        assert!(!verify_input(&tx, 0, &Prevouts::One(0, wrong_amount)).unwrap());
+
+        // B has to wait for 144 blocks
+        let mut tx = spend(Sequence::from_height(100));
+        let sig = sign(&tx, &script, &secret_b);
+        let satisfier = Satisfier::for_input(&tx, 0).unwrap().with_ecdsa_signature(b, sig);
+        assert!(descriptor.get_satisfaction(&satisfier).is_err());
+        tx.input[0].witness = Witness::from_slice(&[sig.to_vec(), Vec::new(), script.to_bytes()]);
+        assert!(!verify_input(&tx, 0, &Prevouts::One(0, spent.clone())).unwrap());
+
+        let mut tx = spend(Sequence::from_height(144));
+        let satisfier = Satisfier::for_input(&tx, 0).unwrap().with_ecdsa_signature(b, sign(&tx, &script, &secret_b));
+        descriptor.satisfy(&mut tx.input[0], &satisfier).unwrap();
+        assert!(verify_input(&tx, 0, &Prevouts::One(0, spent)).unwrap());
+    }
+
+    #[test]
+    fn test_preimages_and_timelocks() {
+        let (secret_a, a) = key(1);
+        let (secret_b, b) = key(2);
+        let preimage = [7u8; 32];
+        let hash = sha256::Hash::hash(&preimage);
+        let policy: Policy = format!("or(and(pk({}),sha256({})),and(pk({}),after(500000)))", a, hash, b).parse().unwrap();
+        let descriptor = compile_wsh(&policy).unwrap();
+        let script = descriptor.explicit_script().unwrap();
+
+        let tx = spend(Sequence::ENABLE_RBF_NO_LOCKTIME);
+        let signed_a = Satisfier::for_input(&tx, 0).unwrap().with_ecdsa_signature(a, sign(&tx, &script, &secret_a));
+        assert!(descriptor.get_satisfaction(&signed_a).is_err());
+        assert!(descriptor.get_satisfaction(signed_a.with_preimage(preimage)).is_ok());
+
+        // The absolute lock only counts when the input enables it
+        let late = absolute::LockTime::from_height(600_000).unwrap();
+        let signed_b = Satisfier::for_input(&tx, 0).unwrap().with_ecdsa_signature(b, sign(&tx, &script, &secret_b));
+        assert!(descriptor.get_satisfaction(&signed_b).is_err());
+        assert!(descriptor.get_satisfaction(signed_b.clone().with_lock_time(late)).is_ok());
+        assert!(descriptor.get_satisfaction(signed_b.with_lock_time(late).with_sequence(Sequence::MAX)).is_err());
+    }
+
+    #[test]
+    fn test_tr_descriptor_and_witness() {
+        let secp = Secp256k1::new();
+        let keys = named_keys();
+        let (secret_a, _) = key(1);
+        let (secret_b, b) = key(2);
+        let policy = parse_policy("or(9@pk(A),1@or(pk(B),and(pk(C),older(144))))", &keys).unwrap();
+        let descriptor = compile_tr(&policy).unwrap();
+        assert_eq!(descriptor.to_string().parse::<Descriptor<PublicKey>>().unwrap(), descriptor);
+        assert!(descriptor.address(Network::Bitcoin).unwrap().to_string().starts_with("bc1p"));
+
+        // A is the likeliest key, so it takes the key path
+        let Descriptor::Tr(tr) = &descriptor else { panic!("expected tr") };
+        assert_eq!(*tr.internal_key(), keys["A"]);
+        let leaf_script = Miniscript::<PublicKey, Tap>::from_str(&format!("pk({})", b)).unwrap().encode();
+        assert!(tr.iter_scripts().any(|(_, leaf)| leaf.encode() == leaf_script));
+
+        let msg = Message::from_digest([5; 32]);
+        let sign = |secret| bitcoin::taproot::Signature {
+            signature: secp.sign_schnorr_no_aux_rand(&msg, &Keypair::from_secret_key(&secp, &secret)),
+            sighash_type: bitcoin::TapSighashType::Default,
+        };
+        let leaf_hash = TapLeafHash::from_script(&leaf_script, LeafVersion::TapScript);
+
+        // Only the leaf signature: script path with a verifiable control block
+        let satisfier = Satisfier::new().with_leaf_signature(b.inner.x_only_public_key().0, leaf_hash, sign(secret_b));
+        let (stack, _) = descriptor.get_satisfaction(&satisfier).unwrap();
+        assert_eq!(stack.len(), 3);
+        assert_eq!(stack[1], leaf_script.to_bytes());
+        let control_block = ControlBlock::decode(&stack[2]).unwrap();
+        let output_key = tr.spend_info().output_key();
+        assert!(control_block.verify_taproot_commitment(&secp, output_key.to_x_only_public_key(), &leaf_script));
+
+        // A key path signature is cheaper
+        let (stack, _) = descriptor.get_satisfaction(satisfier.with_key_spend_signature(sign(secret_a))).unwrap();
+        assert_eq!(stack.len(), 1);
+
+        assert!(descriptor.get_satisfaction(Satisfier::new()).is_err());
+    }
+
+    #[test]
+    fn test_unspendable_key_path() {
+        let keys = named_keys();
+        let descriptor = compile_tr(&parse_policy("and(pk(A),pk(B))", &keys).unwrap()).unwrap();
+        let Descriptor::Tr(tr) = &descriptor else { panic!("expected tr") };
+        assert_eq!(*tr.internal_key(), unspendable_key());
+        assert!(descriptor.to_string().parse::<Descriptor<PublicKey>>().is_ok());
+    }
+
+    #[test]
+    fn test_tap_leaves() {
+        let keys = named_keys();
+        let policy = parse_policy("or(9@pk(A),1@and(pk(B),older(144)))", &keys).unwrap();
+        let leaves: Vec<_> = tap_leaves(&policy).unwrap().into_iter().map(|(weight, leaf)| (weight, leaf.to_string())).collect();
+        assert_eq!(
+            leaves,
+            vec![
+                (900_000, format!("pk({})", keys["A"])),
+                (100_000, format!("and_v(v:pk({}),older(144))", keys["B"])),
+            ]
+        );
+    }
+}
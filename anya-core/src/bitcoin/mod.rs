@@ -13,7 +13,9 @@
 pub mod sidechains;
 pub mod lightning;
 pub mod dlc;
+pub mod miniscript;
 pub mod taproot;
+pub mod frost;
 pub mod rust;
 pub mod layer2;
 
@@ -358,6 +360,8 @@ pub mod adapters {
     use super::*;
     use bitcoin::{Network, Address, ScriptBuf, Transaction, PublicKey};
     use crate::bitcoin::wallet::bip32::ExtendedKey;
+    use crate::bitcoin::miniscript::{self, Descriptor, Policy};
+    use bitcoin::sighash::Prevouts;
     use bitcoin::secp256k1::{Secp256k1, SecretKey};
     
     /// P2P network adapter for Bitcoin
@@ -473,7 +477,7 @@ pub fn derive_address(&self, path: &str) -> Result<bitcoin::Address, &'static st
     
     /// Miniscript adapter for Bitcoin smart contracts
     pub struct MiniscriptAdapter {
-        // Miniscript compiler and interpreter
+        // Network used when deriving addresses from compiled descriptors
         network: Network,
     }
     
@@ -485,16 +489,25 @@ pub fn new(network: Network) -> Self {
             }
         }
         
-        /// Compile policy to script
-        pub fn compile_policy(&self, _policy: &str) -> Result<ScriptBuf, &'static str> {
-            // Placeholder: In a real implementation, this would compile miniscript policy
-            Err("Miniscript compilation not implemented")
+        /// Compile a spending policy such as `or(pk(A),and(pk(B),older(144)))` to a segwit v0 descriptor
+        pub fn compile_policy(&self, policy: &str) -> BitcoinResult<Descriptor<PublicKey>> {
+            miniscript::compile_wsh(&Policy::from_str(policy)?)
         }
         
-        /// Execute script against transaction
-        pub fn execute_script(&self, _script: &Script, _tx: &Transaction, _input_index: usize) -> Result<bool, &'static str> {
-            // Placeholder: In a real implementation, this would execute script
-            Err("Script execution not implemented")
+        /// Compile a spending policy to a taproot descriptor
+        pub fn compile_taproot_policy(&self, policy: &str) -> BitcoinResult<Descriptor<PublicKey>> {
+            miniscript::compile_tr(&Policy::from_str(policy)?)
+        }
+        
+        /// Derive the address of a compiled descriptor on the adapter's network
+        pub fn derive_address(&self, descriptor: &Descriptor<PublicKey>) -> BitcoinResult<Address> {
+            Ok(descriptor.address(self.network)?)
+        }
+        
+        /// Execute a witness script against the witness of a transaction input
+        pub fn execute_script(&self, script: &Script, tx: &Transaction, input_index: usize, amount: Amount) -> BitcoinResult<bool> {
+            let spent = TxOut { value: amount, script_pubkey: ScriptBuf::new_p2wsh(&script.wscript_hash()) };
+            miniscript::verify_input(tx, input_index, &Prevouts::One(input_index, spent))
         }
     }
     
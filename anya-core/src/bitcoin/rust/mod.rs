@@ -1,5 +1,7 @@
 use async_trait::async_trait;
-use bitcoin::{Address, Transaction, Block, Network};
+use bitcoin::secp256k1::{All, PublicKey, Secp256k1, XOnlyPublicKey};
+use bitcoin::{Address, Transaction, Block, Network, OutPoint, TxOut};
+use anya_bitcoin::silent_payments::{self, ReceivedOutput, Receiver, SenderInput, SilentPaymentAddress};
 use crate::bitcoin::error::{BitcoinError, BitcoinResult};
 use crate::bitcoin::interface::{BitcoinInterface, BitcoinImplementationType, BitcoinInterfaceConfig, BlockHeader, AddressType};
 use crate::config::Config;
@@ -7,14 +9,47 @@
 
 pub struct RustBitcoinImplementation {
     network: Network,
+    secp: Secp256k1<All>,
 }
 
 impl RustBitcoinImplementation {
     pub fn new(_config: &Config) -> Self {
         Self {
             network: Network::Bitcoin,
+            secp: Secp256k1::new(),
         }
     }
+
+    /// Taproot output keys paying silent payment addresses, in order, for a
+    /// transaction spending `outpoints` with the keys of `inputs`
+    pub fn silent_payment_outputs(
+        &self,
+        inputs: &[SenderInput],
+        outpoints: &[OutPoint],
+        recipients: &[String],
+    ) -> BitcoinResult<Vec<XOnlyPublicKey>> {
+        let recipients = recipients.iter()
+            .map(|address| address.parse::<SilentPaymentAddress>()?.require_network(self.network))
+            .collect::<Result<Vec<_>, _>>()?;
+        Ok(silent_payments::derive_outputs(&self.secp, inputs, outpoints, &recipients)?)
+    }
+
+    /// Tweak data of a transaction, for light clients scanning for silent
+    /// payments without fetching prevouts
+    pub fn silent_payment_tweak_data(&self, tx: &Transaction, prevouts: &[TxOut]) -> BitcoinResult<Option<PublicKey>> {
+        Ok(silent_payments::tweak_data(&self.secp, tx, prevouts)?)
+    }
+
+    /// Outputs of a transaction paying `receiver`, given the outputs its
+    /// inputs spend
+    pub fn scan_silent_payments(
+        &self,
+        receiver: &Receiver,
+        tx: &Transaction,
+        prevouts: &[TxOut],
+    ) -> BitcoinResult<Vec<ReceivedOutput>> {
+        Ok(receiver.scan_transaction(&self.secp, tx, prevouts)?)
+    }
 }
 
 impl Layer2Protocol for RustBitcoinImplementation {
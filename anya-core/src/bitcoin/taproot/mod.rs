@@ -27,14 +27,23 @@
 use std::convert::TryInto;
 use serde_json;
 use hex;
-use std::io::Write;
+
+pub mod proof;
+pub mod script;
+pub mod tree;
+
+use self::proof::{
+    genesis_commitment, AnchorConfirmation, AssetGenesis, AssetProof, AssetProofFile, AssetTransition,
+    TapCommitmentProof, TransferProofs, VerifiedAsset,
+};
+use bitcoin::block::Header;
 
 /// Taproot Asset structure
 /// 
 /// Represents a Taproot-enabled asset with metadata and supply information.
 #[derive(Clone, Debug)]
 pub struct TaprootAsset {
-    /// Asset ID (hash of asset parameters)
+    /// Asset ID, derived from the genesis parameters
     pub asset_id: [u8; 32],
     /// Asset name
     pub name: String,
@@ -54,6 +63,10 @@ pub struct TaprootAsset {
     pub issuer_pubkey: [u8; 32],
     /// Placeholder for the new issue method
     pub value: u64,
+    /// Genesis parameters
+    pub genesis: AssetGenesis,
+    /// Proof chain from genesis to the units held here
+    pub proof_file: Option<AssetProofFile>,
 }
 
 /// Asset Transfer structure
@@ -75,12 +88,16 @@ pub struct AssetTransfer {
 
 /// Create a new Taproot asset
 /// 
-/// Creates a new Taproot asset with the specified parameters.
+/// Creates a new Taproot asset with the specified parameters. The genesis
+/// transaction must spend `genesis_outpoint` as its first input and pay
+/// [`issuance_output_script`] at output 0.
 pub fn create_asset(
     name: &str,
     supply: u64,
     precision: u8,
     metadata: &str,
+    genesis_outpoint: OutPoint,
+    issuer: XOnlyPublicKey,
 ) -> BitcoinResult<TaprootAsset> {
     // Validate inputs
     if name.is_empty() {
@@ -95,17 +112,12 @@ pub fn create_asset(
         return Err(BitcoinError::TaprootError("Precision cannot exceed 18 decimal places".to_string()));
     }
     
-    // Create asset ID by hashing parameters
-    let mut hasher = sha256::Hash::engine();
-    hasher.write_all(name.as_bytes())?;
-    hasher.write_all(&supply.to_be_bytes())?;
-    hasher.write_all(&[precision])?;
-    hasher.write_all(metadata.as_bytes())?;
-    let asset_id = sha256::Hash::from_engine(hasher).to_byte_array();
+    // Asset ID commits to the genesis outpoint, name and metadata
+    let genesis = AssetGenesis::new(genesis_outpoint, name, metadata, 0);
     
     // Create the Taproot asset
     let asset = TaprootAsset {
-        asset_id,
+        asset_id: genesis.asset_id(),
         name: name.to_string(),
         supply,
         precision,
@@ -113,8 +125,10 @@ pub fn create_asset(
         issuance_tx: None,
         holders: HashMap::new(),
         issued: false,
-        issuer_pubkey: [0; 32],
+        issuer_pubkey: issuer.serialize(),
         value: 0,
+        genesis,
+        proof_file: None,
     };
     
     Ok(asset)
@@ -122,28 +136,14 @@ pub fn create_asset(
 
 /// Issue a Taproot asset
 /// 
-/// Creates a transaction that issues the asset to the specified address.
-pub fn issue_asset(asset: &TaprootAsset, issuer_secret_key: &[u8]) -> BitcoinResult<String> {
-    let secp = Secp256k1::new();
-    let secret_key = SecretKey::from_slice(issuer_secret_key)?;
-    let keypair = Keypair::from_secret_key(&secp, &secret_key);
-    let internal_key = keypair.x_only_public_key().0;
-
-    // Create asset script
-    let asset_script = create_asset_script(asset);
-
-    // Create Taproot tree
-    let mut builder = TaprootBuilder::new();
-    builder = builder.add_leaf(0, asset_script)?;
-
-    // Finalize Taproot
-    let spend_info = builder.finalize(&secp, internal_key)?;
-
-    // Create output script
-    let output_key = spend_info.output_key();
-    let taproot_script = ScriptBuf::new_p2tr(&secp, output_key.into(), None);
+/// Records the confirmed genesis transaction and starts the asset's proof file.
+pub fn issue_asset(asset: &mut TaprootAsset, anchor: &AnchorConfirmation) -> BitcoinResult<()> {
+    asset.issue(anchor)
+}
 
-    Ok(taproot_script.to_string())
+/// Output script the genesis transaction pays at output 0
+pub fn issuance_output_script(asset: &TaprootAsset) -> BitcoinResult<ScriptBuf> {
+    asset.genesis_commitment()?.output_script()
 }
 
 /// Verify a Taproot asset
@@ -164,15 +164,42 @@ pub fn verify_asset(asset: &TaprootAsset) -> BitcoinResult<bool> {
         })
         .ok_or_else(|| BitcoinError::TaprootError("No valid issuance output found in transaction".to_string()))?;
     
-    // In a real implementation, we would:
-    // 1. Verify the asset metadata in the transaction
-    // 2. Verify all subsequent transfers
-    // 3. Validate the current holder balances
+    // Without block headers only the proof file's link to this asset can be checked;
+    // use verify_asset_proof for full offline verification
+    if let Some(proof_file) = &asset.proof_file {
+        let genesis = proof_file.proofs.first()
+            .ok_or_else(|| BitcoinError::TaprootError("Empty proof file".to_string()))?;
+        
+        if genesis.anchor_tx.compute_txid() != issuance_tx.compute_txid() {
+            return Err(BitcoinError::TaprootError("Proof file does not start at the issuance transaction".to_string()));
+        }
+        
+        if genesis.asset.amount != asset.supply {
+            return Ok(false);
+        }
+    }
     
-    // For now, we just return true as a placeholder
     Ok(true)
 }
 
+/// Verify a Taproot asset's proof file against block headers
+/// 
+/// Checks block inclusion, taproot commitments, transfer signatures and supply
+/// conservation from genesis to the current holder, without contacting a node.
+pub fn verify_asset_proof(asset: &TaprootAsset, headers: &[Header]) -> BitcoinResult<VerifiedAsset> {
+    let proof_file = asset.proof_file.as_ref()
+        .ok_or_else(|| BitcoinError::TaprootError("Asset has no proof file".to_string()))?;
+    
+    let verified = proof_file.verify(headers)?;
+    if verified.genesis_supply != asset.supply {
+        return Err(BitcoinError::TaprootError(format!(
+            "Proof issues {} units but asset supply is {}", verified.genesis_supply, asset.supply
+        )));
+    }
+    
+    Ok(verified)
+}
+
 /// Create React Native code for asset management
 /// 
 /// Generates React Native code for managing a Taproot asset.
@@ -328,31 +355,43 @@ pub fn verify_taproot_output(
     output.script_pubkey.is_p2tr()
 }
 
-/// Transfer a Taproot asset
+/// Prepare a Taproot asset transfer
 /// 
-/// Creates a transaction that transfers the asset from one address to another.
-pub fn transfer_asset(transfer: &AssetTransfer) -> BitcoinResult<String> {
+/// Signs the transfer with the sender's key. The transfer transaction must spend
+/// the transition's `prev_outpoint` and pay its output scripts, in order.
+pub fn prepare_transfer(
+    asset: &TaprootAsset,
+    transfer: &AssetTransfer,
+    sender_secret_key: &[u8],
+) -> BitcoinResult<AssetTransition> {
+    if transfer.asset_id != asset.asset_id {
+        return Err(BitcoinError::TaprootError("Transfer is for a different asset".to_string()));
+    }
+    
+    let proof_file = asset.proof_file.as_ref()
+        .ok_or_else(|| BitcoinError::TaprootError("Asset has no proof file".to_string()))?;
+    
     let secp = Secp256k1::new();
+    let keypair = Keypair::from_secret_key(&secp, &SecretKey::from_slice(sender_secret_key)?);
     
     // Convert recipient's public key from bytes to XOnlyPublicKey
     let recipient_bytes = hex::decode(&transfer.recipient)?;
     let recipient_pubkey = XOnlyPublicKey::from_slice(&recipient_bytes)?;
+    
+    proof_file.prepare_transfer(&keypair, recipient_pubkey, transfer.amount)
+}
 
-    // Create transfer script
-    let transfer_script = create_transfer_script(transfer);
-
-    // Build Taproot tree
-    let mut builder = TaprootBuilder::new();
-    builder = builder.add_leaf(0, transfer_script)?;
-
-    // Finalize Taproot
-    let spend_info = builder.finalize(&secp, recipient_pubkey)?;
-
-    // Create output script
-    let output_key = spend_info.output_key();
-    let taproot_script = ScriptBuf::new_p2tr(&secp, output_key.into(), None);
-
-    Ok(taproot_script.to_string())
+/// Transfer a Taproot asset
+/// 
+/// Records the confirmed transfer transaction and returns the proof file to
+/// hand to the recipient.
+pub fn transfer_asset(
+    asset: &mut TaprootAsset,
+    transfer: &AssetTransfer,
+    transition: &AssetTransition,
+    anchor: &AnchorConfirmation,
+) -> BitcoinResult<AssetProofFile> {
+    asset.transfer(transfer, transition, anchor)
 }
 
 /// Sign a transaction
@@ -401,93 +440,57 @@ pub fn from_str(address_str: &str) -> BitcoinResult<Address<NetworkChecked>> {
         .assume_checked())
 }
 
-pub fn create_asset_script(asset: &TaprootAsset) -> ScriptBuf {
-    let mut builder = Builder::new()
-        .push_opcode(opcodes::all::OP_RETURN);
-
-    // Convert values to PushBytes
-    let precision_bytes = PushBytesBuf::from_slice(&[asset.precision])
-        .expect("Failed to convert precision to PushBytes");
-    let name_bytes = PushBytesBuf::from_slice(asset.name.as_bytes())
-        .expect("Failed to convert name to PushBytes");
-    let supply_bytes = PushBytesBuf::from_slice(&asset.supply.to_le_bytes())
-        .expect("Failed to convert supply to PushBytes");
-
-    builder = builder
-        .push_slice(&precision_bytes)
-        .push_slice(&name_bytes)
-        .push_slice(&supply_bytes);
-
-    builder.into_script()
-}
-
-pub fn create_transfer_script(transfer: &AssetTransfer) -> ScriptBuf {
-    let mut builder = Builder::new()
-        .push_opcode(opcodes::all::OP_RETURN);
-
-    // Convert values to PushBytes
-    let asset_id_push = PushBytesBuf::from_slice(&transfer.asset_id)
-        .expect("Failed to convert asset ID to PushBytes");
-    let amount_bytes = transfer.amount.to_le_bytes();
-    let amount_push = PushBytesBuf::from_slice(&amount_bytes)
-        .expect("Failed to convert amount to PushBytes");
-
-    builder = builder
-        .push_slice(&asset_id_push)
-        .push_slice(&amount_push);
-
-    builder.into_script()
+/// Tapscript leaf committing the issued asset, as checked by the proof file
+pub fn create_asset_script(asset: &TaprootAsset) -> BitcoinResult<ScriptBuf> {
+    asset.genesis_commitment()?.commitment_script()
 }
 
 impl TaprootAsset {
-    pub fn issue(&mut self) -> BitcoinResult<String> {
+    /// Commitment of the whole supply to the issuer
+    pub fn genesis_commitment(&self) -> BitcoinResult<TapCommitmentProof> {
+        let issuer = XOnlyPublicKey::from_slice(&self.issuer_pubkey)?;
+        Ok(genesis_commitment(&self.genesis, self.supply, issuer))
+    }
+
+    pub fn issue(&mut self, anchor: &AnchorConfirmation) -> BitcoinResult<()> {
         if self.issued {
             return Err(BitcoinError::AssetAlreadyIssued);
         }
 
-        let secp = Secp256k1::new();
-        let internal_key = XOnlyPublicKey::from_slice(&self.issuer_pubkey)?;
-        
-        // Create asset script
-        let asset_script = create_asset_script(self);
-
-        // Build Taproot tree
-        let mut builder = TaprootBuilder::new();
-        builder = builder.add_leaf(0, asset_script)?;
-        
-        // Finalize with internal key
-        let spend_info = builder.finalize(&secp, internal_key)?;
-        
-        // Create output script
-        let output_key = spend_info.output_key();
-        let taproot_script = ScriptBuf::new_p2tr(&secp, output_key.into(), None);
+        let issuer = XOnlyPublicKey::from_slice(&self.issuer_pubkey)?;
+        let genesis_proof = AssetProof::genesis(self.genesis.clone(), self.supply, issuer, anchor)?;
         
+        self.proof_file = Some(AssetProofFile::new(genesis_proof)?);
+        self.issuance_tx = Some(anchor.anchor_tx.clone());
+        self.holders.insert(hex::encode(self.issuer_pubkey), self.supply);
         self.issued = true;
-        Ok(taproot_script.to_string())
+        Ok(())
     }
 
-    pub fn transfer(&mut self, transfer: AssetTransfer) -> BitcoinResult<String> {
-        let secp = Secp256k1::new();
+    pub fn transfer(
+        &mut self,
+        transfer: &AssetTransfer,
+        transition: &AssetTransition,
+        anchor: &AnchorConfirmation,
+    ) -> BitcoinResult<AssetProofFile> {
+        let proof_file = self.proof_file.as_ref()
+            .ok_or_else(|| BitcoinError::TaprootError("Asset has no proof file".to_string()))?;
         
-        // Convert recipient's public key
-        let recipient_bytes = hex::decode(&transfer.recipient)?;
-        let recipient_pubkey = XOnlyPublicKey::from_slice(&recipient_bytes)?;
-
-        // Create transfer script
-        let transfer_script = create_transfer_script(&transfer);
-
-        // Build Taproot tree
-        let mut builder = TaprootBuilder::new();
-        builder = builder.add_leaf(0, transfer_script)?;
-
-        // Finalize with recipient's key
-        let spend_info = builder.finalize(&secp, recipient_pubkey)?;
+        let TransferProofs { recipient, change } = proof_file.complete_transfer(transition, anchor)?;
         
-        // Create output script
-        let output_key = spend_info.output_key();
-        let taproot_script = ScriptBuf::new_p2tr(&secp, output_key.into(), None);
+        // Update the asset holders
+        let sender_balance = self.holders.get(&transfer.sender).copied().unwrap_or(0);
+        let sender_new_balance = sender_balance.saturating_sub(transfer.amount);
+        if sender_new_balance > 0 {
+            self.holders.insert(transfer.sender.clone(), sender_new_balance);
+        } else {
+            self.holders.remove(&transfer.sender);
+        }
+        *self.holders.entry(transfer.recipient.clone()).or_insert(0) += transfer.amount;
         
-        Ok(taproot_script.to_string())
+        // Only the change is still held here
+        self.proof_file = change;
+        Ok(recipient)
     }
 
     pub fn sign_transaction(&self, tx: &mut Transaction, input_index: usize, secret_key: &[u8]) -> BitcoinResult<()> {
@@ -533,9 +536,13 @@ fn get_previous_output(&self, _input_index: usize) -> BitcoinResult<TxOut> {
 mod tests {
     use super::*;
     
+    fn issuer() -> XOnlyPublicKey {
+        proof::tests::keypair(1).x_only_public_key().0
+    }
+    
     #[test]
     fn test_create_asset() {
-        let asset = create_asset("TestCoin", 1000000, 8, "{\"description\":\"Test asset\"}")
+        let asset = create_asset("TestCoin", 1000000, 8, "{\"description\":\"Test asset\"}", OutPoint::null(), issuer())
             .expect("Failed to create asset");
             
         assert_eq!(asset.name, "TestCoin");
@@ -548,7 +555,7 @@ fn test_create_asset() {
     
     #[test]
     fn test_create_react_native_asset() {
-        let asset = create_asset("TestCoin", 1000000, 8, "{\"description\":\"Test asset\"}")
+        let asset = create_asset("TestCoin", 1000000, 8, "{\"description\":\"Test asset\"}", OutPoint::null(), issuer())
             .expect("Failed to create asset");
             
         let code = create_react_native_asset(&asset)
@@ -558,4 +565,43 @@ fn test_create_react_native_asset() {
         assert!(code.contains("TestCoin"));
         assert!(code.contains("1000000"));
     }
+    
+    #[test]
+    fn test_issue_and_transfer_emit_proofs() {
+        use proof::tests::{anchor_tx, keypair, mine};
+        
+        let first_prev_out = OutPoint { txid: bitcoin::Txid::from_byte_array([5u8; 32]), vout: 0 };
+        let mut asset = create_asset("TestCoin", 1000, 0, "{}", first_prev_out, issuer())
+            .expect("Failed to create asset");
+        
+        // The leaf committed by the issuance output is the one the proof checks
+        let commitment = asset.genesis_commitment().unwrap();
+        assert_eq!(create_asset_script(&asset).unwrap(), commitment.commitment_script().unwrap());
+        
+        let genesis_tx = anchor_tx(first_prev_out, vec![issuance_output_script(&asset).unwrap()]);
+        let (genesis_header, tx_merkle_proof) = mine(bitcoin::BlockHash::all_zeros(), &genesis_tx);
+        issue_asset(&mut asset, &AnchorConfirmation { anchor_tx: genesis_tx, block_header: genesis_header, tx_merkle_proof })
+            .expect("Failed to issue asset");
+        assert_eq!(verify_asset_proof(&asset, &[genesis_header]).unwrap().amount, 1000);
+        
+        let bob = keypair(2).x_only_public_key().0;
+        let transfer = AssetTransfer {
+            asset_id: asset.asset_id,
+            sender: hex::encode(issuer().serialize()),
+            recipient: hex::encode(bob.serialize()),
+            amount: 400,
+            transfer_tx: None,
+        };
+        let transition = prepare_transfer(&asset, &transfer, &[1u8; 32]).unwrap();
+        let transfer_tx = anchor_tx(transition.prev_outpoint, transition.output_scripts().unwrap());
+        let (transfer_header, tx_merkle_proof) = mine(genesis_header.block_hash(), &transfer_tx);
+        let anchor = AnchorConfirmation { anchor_tx: transfer_tx, block_header: transfer_header, tx_merkle_proof };
+        let bob_file = transfer_asset(&mut asset, &transfer, &transition, &anchor).unwrap();
+        
+        let headers = [genesis_header, transfer_header];
+        assert_eq!(bob_file.verify_ownership(&headers, &bob).unwrap().amount, 400);
+        assert_eq!(verify_asset_proof(&asset, &headers).unwrap().amount, 600);
+        assert_eq!(asset.holders[&transfer.recipient], 400);
+        assert_eq!(asset.holders[&transfer.sender], 600);
+    }
 } 
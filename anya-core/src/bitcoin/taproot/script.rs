@@ -1,10 +1,13 @@
 // src/bitcoin/taproot/script.rs
 
 use std::collections::HashMap;
-use bitcoin::{ScriptBuf, Witness};
-use miniscript::{Miniscript, Descriptor};
+use std::str::FromStr;
+use bitcoin::hashes::Hash;
+use bitcoin::taproot::{LeafVersion, TapLeafHash};
+use bitcoin::{PublicKey, ScriptBuf, Witness};
 
-use crate::common::error::AnyaResult;
+use crate::bitcoin::error::{BitcoinError, BitcoinResult};
+use crate::bitcoin::miniscript::{Miniscript, Satisfier, Tap};
 
 /// Types of Taproot scripts
 #[derive(Debug, Clone, PartialEq, Eq)]
@@ -79,22 +82,25 @@ pub fn new(script: ScriptBuf, script_type: TaprootScriptType) -> Self {
     }
     
     /// Creates a new Taproot script from a Miniscript
-    pub fn from_miniscript(miniscript_str: &str) -> AnyaResult<Self> {
-        // Implementation goes here
-        // Parse miniscript, convert to Script
-        
-        // Placeholder for now
-        Ok(Self {
+    pub fn from_miniscript(miniscript_str: &str) -> BitcoinResult<Self> {
+        let miniscript = ScriptParser::parse_miniscript(miniscript_str)?;
+        Ok(Self::from_compiled(&miniscript))
+    }
+    
+    /// Creates a new Taproot script from an already compiled Miniscript,
+    /// e.g. one branch of a spending policy
+    pub fn from_compiled(miniscript: &Miniscript<PublicKey, Tap>) -> Self {
+        Self {
             script_type: TaprootScriptType::Miniscript,
-            script: ScriptBuf::new(),
+            script: miniscript.encode(),
             version: 0xc0,
             metadata: HashMap::new(),
-            miniscript: Some(miniscript_str.to_string()),
-        })
+            miniscript: Some(miniscript.to_string()),
+        }
     }
     
     /// Creates a multi-signature script
-    pub fn create_multisig(threshold: usize, public_keys: &[&str]) -> AnyaResult<Self> {
+    pub fn create_multisig(threshold: usize, public_keys: &[&str]) -> BitcoinResult<Self> {
         // Implementation goes here
         // Create a threshold signature script
         
@@ -112,7 +118,7 @@ pub fn create_multisig(threshold: usize, public_keys: &[&str]) -> AnyaResult<Sel
     }
     
     /// Creates a time-locked script
-    pub fn create_timelock(base_script: &TaprootScript, lock_value: u32, is_relative: bool) -> AnyaResult<Self> {
+    pub fn create_timelock(_base_script: &TaprootScript, lock_value: u32, is_relative: bool) -> BitcoinResult<Self> {
         // Implementation goes here
         // Create a timelock wrapper around base_script
         
@@ -130,7 +136,7 @@ pub fn create_timelock(base_script: &TaprootScript, lock_value: u32, is_relative
     }
     
     /// Creates a hash lock script
-    pub fn create_hashlock(hash_hex: &str, hash_type: &str) -> AnyaResult<Self> {
+    pub fn create_hashlock(_hash_hex: &str, hash_type: &str) -> BitcoinResult<Self> {
         // Implementation goes here
         // Create a hash lock script
         
@@ -152,21 +158,31 @@ pub fn add_metadata(&mut self, key: &str, value: &str) {
     }
     
     /// Gets the leaf hash of this script
-    pub fn leaf_hash(&self) -> AnyaResult<Vec<u8>> {
-        // Implementation goes here
-        // Calculate TapLeafHash
-        
-        // Placeholder for now
-        Ok(vec![])
+    pub fn leaf_hash(&self) -> BitcoinResult<Vec<u8>> {
+        let version = LeafVersion::from_consensus(self.version)
+            .map_err(|_| BitcoinError::TaprootError(format!("Invalid leaf version {:#04x}", self.version)))?;
+        Ok(TapLeafHash::from_script(&self.script, version).to_byte_array().to_vec())
     }
     
-    /// Converts this script to a witness
-    pub fn to_witness(&self, control_block: Vec<u8>) -> Witness {
-        // Implementation goes here
-        // Create a witness for spending this script
-        
-        // Placeholder for now
-        Witness::new()
+    /// Converts this script to a script path witness: the satisfying stack
+    /// (bottom element first), then the script and its control block
+    pub fn to_witness(&self, satisfaction: Vec<Vec<u8>>, control_block: Vec<u8>) -> Witness {
+        let mut witness = Witness::new();
+        for item in satisfaction {
+            witness.push(item);
+        }
+        witness.push(self.script.as_bytes());
+        witness.push(control_block);
+        witness
+    }
+    
+    /// Builds the script path witness of a Miniscript leaf from the
+    /// available signatures, preimages and timelocks
+    pub fn satisfy(&self, satisfier: &Satisfier, control_block: Vec<u8>) -> BitcoinResult<Witness> {
+        let miniscript = self.miniscript.as_deref()
+            .ok_or_else(|| BitcoinError::TaprootError("Only Miniscript leaves can be satisfied".to_string()))?;
+        let satisfaction = ScriptParser::parse_miniscript(miniscript)?.satisfy(satisfier)?;
+        Ok(self.to_witness(satisfaction, control_block))
     }
 }
 
@@ -208,7 +224,7 @@ pub fn add_metadata(mut self, key: &str, value: &str) -> Self {
     }
     
     /// Builds the Taproot script
-    pub fn build(self) -> AnyaResult<TaprootScript> {
+    pub fn build(self) -> BitcoinResult<TaprootScript> {
         // Implementation goes here
         // Convert operations to ScriptBuf
         
@@ -228,7 +244,7 @@ pub fn build(self) -> AnyaResult<TaprootScript> {
 
 impl ScriptParser {
     /// Parses a Bitcoin script
-    pub fn parse_script(script: &ScriptBuf) -> AnyaResult<TaprootScriptType> {
+    pub fn parse_script(_script: &ScriptBuf) -> BitcoinResult<TaprootScriptType> {
         // Implementation goes here
         // Detect script type from script
         
@@ -236,11 +252,8 @@ pub fn parse_script(script: &ScriptBuf) -> AnyaResult<TaprootScriptType> {
         Ok(TaprootScriptType::Raw)
     }
     
-    /// Parses a Miniscript
-    pub fn parse_miniscript(miniscript_str: &str) -> AnyaResult<Miniscript<String>> {
-        // Implementation goes here
-        
-        // Placeholder for now
-        Err("Miniscript parsing not yet implemented".into())
+    /// Parses a Miniscript for use as a tapscript leaf
+    pub fn parse_miniscript(miniscript_str: &str) -> BitcoinResult<Miniscript<PublicKey, Tap>> {
+        Ok(Miniscript::from_str(miniscript_str)?)
     }
-} 
\ No newline at end of file
+}
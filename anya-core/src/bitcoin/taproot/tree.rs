@@ -1,10 +1,14 @@
 // src/bitcoin/taproot/tree.rs
 
 use std::collections::HashMap;
-use bitcoin::hashes::{Hash, HashEngine};
-use bitcoin::taproot::{TapLeafHash, TapBranchHash, TapNodeHash, LeafVersion};
+use bitcoin::hashes::Hash;
+use bitcoin::key::TapTweak;
+use bitcoin::secp256k1::{Parity, Secp256k1, XOnlyPublicKey};
+use bitcoin::taproot::{TapLeafHash, TapNodeHash, LeafVersion, TAPROOT_CONTROL_MAX_NODE_COUNT};
+use bitcoin::ScriptBuf;
 
-use crate::common::error::AnyaResult;
+use crate::bitcoin::error::{BitcoinError, BitcoinResult};
+use crate::bitcoin::miniscript::{self, Policy};
 use super::script::TaprootScript;
 
 /// Represents a leaf in a Taproot Merkle tree
@@ -16,7 +20,7 @@ pub struct TapLeaf {
     /// The leaf version (usually 0xc0 for Tapscript)
     pub version: LeafVersion,
     
-    /// Weight for ordering in the tree (lower weights are prioritized)
+    /// Relative likelihood of this leaf being spent; likelier leaves sit closer to the root
     pub weight: u32,
     
     /// Additional metadata
@@ -26,21 +30,19 @@ pub struct TapLeaf {
 impl TapLeaf {
     /// Creates a new tap leaf from a script
     pub fn new(script: TaprootScript, weight: u32) -> Self {
+        // Tapscript unless the script carries a valid future leaf version
+        let version = LeafVersion::from_consensus(script.version).unwrap_or(LeafVersion::TapScript);
         Self {
             script,
-            version: LeafVersion::from_consensus(0xc0), // Tapscript version
+            version,
             weight,
             metadata: HashMap::new(),
         }
     }
     
     /// Gets the leaf hash for this leaf
-    pub fn leaf_hash(&self) -> AnyaResult<TapLeafHash> {
-        // Implementation goes here
-        // Compute the TapLeafHash
-        
-        // Placeholder for now
-        Err("Leaf hash computation not yet implemented".into())
+    pub fn leaf_hash(&self) -> BitcoinResult<TapLeafHash> {
+        Ok(TapLeafHash::from_script(&self.script.script, self.version))
     }
     
     /// Adds metadata to this leaf
@@ -71,20 +73,13 @@ pub fn new(left: TapNodeHash, right: TapNodeHash) -> Self {
     }
     
     /// Computes the branch hash
-    pub fn branch_hash(&self) -> TapBranchHash {
-        // Implementation goes here
-        // Compute the TapBranchHash
-        
-        // Placeholder for now
-        let mut engine = TapBranchHash::engine();
-        engine.input(&self.left[..]);
-        engine.input(&self.right[..]);
-        TapBranchHash::from_engine(engine)
+    pub fn branch_hash(&self) -> TapNodeHash {
+        TapNodeHash::from_node_hashes(self.left, self.right)
     }
 }
 
 /// Represents a complete Taproot Merkle tree
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Default)]
 pub struct TapTree {
     /// The leaves in this tree
     pub leaves: Vec<TapLeaf>,
@@ -92,7 +87,7 @@ pub struct TapTree {
     /// The branches in this tree
     pub branches: Vec<TapBranch>,
     
-    /// Maps leaf indices to their positions in the tree
+    /// Maps leaf indices to their Merkle paths, deepest sibling first
     pub leaf_positions: HashMap<usize, Vec<TapNodeHash>>,
     
     /// The root hash of the tree
@@ -118,44 +113,106 @@ pub fn add_leaf(&mut self, leaf: TapLeaf) {
     }
     
     /// Builds the tree from the current leaves
-    pub fn build(&mut self) -> AnyaResult<TapNodeHash> {
+    ///
+    /// Leaves are combined Huffman style: the two lightest nodes are joined
+    /// until one root remains, so likelier leaves get shorter Merkle paths.
+    /// Ties go to the node created first, which keeps the shape stable for
+    /// equal weights.
+    pub fn build(&mut self) -> BitcoinResult<TapNodeHash> {
         // Reset the tree structure
         self.branches.clear();
         self.leaf_positions.clear();
+        self.root_hash = None;
+        
+        if self.leaves.is_empty() {
+            return Err(BitcoinError::TaprootError("Cannot build an empty taproot tree".to_string()));
+        }
         
-        // Sort leaves by weight
-        self.leaves.sort_by_key(|leaf| leaf.weight);
+        // (weight, creation order, node hash, leaf indices below the node)
+        let mut nodes = self.leaves.iter().enumerate()
+            .map(|(i, leaf)| Ok((u64::from(leaf.weight), i, TapNodeHash::from(leaf.leaf_hash()?), vec![i])))
+            .collect::<BitcoinResult<Vec<_>>>()?;
+        let mut positions: HashMap<usize, Vec<TapNodeHash>> = (0..self.leaves.len()).map(|i| (i, Vec::new())).collect();
+        let mut created = nodes.len();
         
-        // Implementation goes here
-        // Build the Merkle tree from leaves
+        while nodes.len() > 1 {
+            nodes.sort_by_key(|(weight, order, _, _)| std::cmp::Reverse((*weight, *order)));
+            let (left_weight, _, left, left_leaves) = nodes.pop().expect("at least two nodes");
+            let (right_weight, _, right, right_leaves) = nodes.pop().expect("at least two nodes");
+            
+            for i in &left_leaves {
+                positions.get_mut(i).expect("every leaf has a path").push(right);
+            }
+            for i in &right_leaves {
+                positions.get_mut(i).expect("every leaf has a path").push(left);
+            }
+            
+            let branch = TapBranch::new(left, right);
+            let hash = branch.branch_hash();
+            self.branches.push(branch);
+            nodes.push((left_weight + right_weight, created, hash, [left_leaves, right_leaves].concat()));
+            created += 1;
+        }
         
-        // Placeholder for now
-        let placeholder_hash = [0u8; 32];
-        self.root_hash = Some(TapNodeHash::from_slice(&placeholder_hash).unwrap());
+        if let Some(depth) = positions.values().map(Vec::len).find(|depth| *depth > TAPROOT_CONTROL_MAX_NODE_COUNT) {
+            return Err(BitcoinError::TaprootError(format!(
+                "Taproot tree depth {} exceeds the limit of {}", depth, TAPROOT_CONTROL_MAX_NODE_COUNT
+            )));
+        }
         
-        Ok(self.root_hash.unwrap())
+        let root = nodes[0].2;
+        self.leaf_positions = positions;
+        self.root_hash = Some(root);
+        Ok(root)
     }
     
     /// Gets the Merkle proof for a specific leaf
-    pub fn get_proof(&self, leaf_index: usize) -> AnyaResult<Vec<TapNodeHash>> {
+    pub fn get_proof(&self, leaf_index: usize) -> BitcoinResult<Vec<TapNodeHash>> {
         if let Some(path) = self.leaf_positions.get(&leaf_index) {
             Ok(path.clone())
         } else {
-            Err(format!("Leaf index {} not found in tree", leaf_index).into())
+            Err(BitcoinError::TaprootError(format!("Leaf index {} not found in tree", leaf_index)))
         }
     }
     
+    /// Tweaks an internal key with the tree's Merkle root, returning the
+    /// output key and its parity
+    pub fn output_key(&self, internal_key: XOnlyPublicKey) -> BitcoinResult<(XOnlyPublicKey, Parity)> {
+        let root = self.root_hash
+            .ok_or_else(|| BitcoinError::TaprootError("Taproot tree has not been built".to_string()))?;
+        let (output_key, parity) = internal_key.tap_tweak(&Secp256k1::verification_only(), Some(root));
+        Ok((output_key.to_x_only_public_key(), parity))
+    }
+    
+    /// The P2TR output script committing to this tree
+    pub fn script_pubkey(&self, internal_key: XOnlyPublicKey) -> BitcoinResult<ScriptBuf> {
+        let (output_key, _) = self.output_key(internal_key)?;
+        Ok(ScriptBuf::new_p2tr_tweaked(output_key.dangerous_assume_tweaked()))
+    }
+    
     /// Gets the control block for a specific leaf (for script path spending)
-    pub fn get_control_block(&self, leaf_index: usize, internal_key: [u8; 32]) -> AnyaResult<Vec<u8>> {
-        // Implementation goes here
-        // Construct the control block
+    ///
+    /// The first byte carries the leaf version and the output key parity,
+    /// followed by the internal key and the leaf's Merkle path.
+    pub fn get_control_block(&self, leaf_index: usize, internal_key: [u8; 32]) -> BitcoinResult<Vec<u8>> {
+        let leaf = self.leaves.get(leaf_index)
+            .ok_or_else(|| BitcoinError::TaprootError(format!("Leaf index {} not found in tree", leaf_index)))?;
+        let path = self.get_proof(leaf_index)?;
+        let key = XOnlyPublicKey::from_slice(&internal_key).map_err(|_| BitcoinError::InvalidPublicKey)?;
+        let (_, parity) = self.output_key(key)?;
         
-        // Placeholder for now
-        Err("Control block construction not yet implemented".into())
+        let mut control_block = Vec::with_capacity(33 + 32 * path.len());
+        control_block.push(leaf.version.to_consensus() | parity.to_u8());
+        control_block.extend_from_slice(&internal_key);
+        for node in path {
+            control_block.extend_from_slice(node.as_byte_array());
+        }
+        Ok(control_block)
     }
 }
 
 /// Builder for Taproot Merkle trees
+#[derive(Default)]
 pub struct TapTreeBuilder {
     /// The leaves to include in the tree
     leaves: Vec<TapLeaf>,
@@ -176,6 +233,15 @@ pub fn add_script(mut self, script: TaprootScript, weight: u32) -> Self {
         self
     }
     
+    /// Adds one leaf per alternative branch of a spending policy, weighted
+    /// by how likely the branch is to be used
+    pub fn add_policy(mut self, policy: &Policy) -> BitcoinResult<Self> {
+        for (weight, leaf) in miniscript::tap_leaves(policy)? {
+            self.leaves.push(TapLeaf::new(TaprootScript::from_compiled(&leaf), weight));
+        }
+        Ok(self)
+    }
+    
     /// Adds a raw leaf to the tree
     pub fn add_leaf(mut self, leaf: TapLeaf) -> Self {
         self.leaves.push(leaf);
@@ -183,7 +249,7 @@ pub fn add_leaf(mut self, leaf: TapLeaf) -> Self {
     }
     
     /// Builds the Taproot tree
-    pub fn build(self) -> AnyaResult<TapTree> {
+    pub fn build(self) -> BitcoinResult<TapTree> {
         let mut tree = TapTree::new();
         
         // Add all leaves to the tree
@@ -196,4 +262,187 @@ pub fn build(self) -> AnyaResult<TapTree> {
         
         Ok(tree)
     }
-} 
\ No newline at end of file
+} 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bitcoin::miniscript::tests::key;
+    use crate::bitcoin::miniscript::Satisfier;
+    use bitcoin::secp256k1::{Keypair, Message};
+    use bitcoin::taproot::ControlBlock;
+    use super::super::script::TaprootScriptType;
+    use std::str::FromStr;
+
+    struct Vector {
+        internal_key: &'static str,
+        // (script, leaf version, weight); weights reproduce the vector's tree shape
+        leaves: &'static [(&'static str, u8, u32)],
+        merkle_root: Option<&'static str>,
+        script_pubkey: &'static str,
+        control_blocks: &'static [&'static str],
+    }
+
+    // scriptPubKey cases of the BIP341 wallet test vectors
+    const BIP341_VECTORS: &[Vector] = &[
+        Vector {
+            internal_key: "187791b6f712a8ea41c8ecdd0ee77fab3e85263b37e1ec18a3651926b3a6cf27",
+            leaves: &[("20d85a959b0290bf19bb89ed43c916be835475d013da4b362117393e25a48229b8ac", 0xc0, 1)],
+            merkle_root: Some("5b75adecf53548f3ec6ad7d78383bf84cc57b55a3127c72b9a2481752dd88b21"),
+            script_pubkey: "5120147c9c57132f6e7ecddba9800bb0c4449251c92a1e60371ee77557b6620f3ea3",
+            control_blocks: &["c1187791b6f712a8ea41c8ecdd0ee77fab3e85263b37e1ec18a3651926b3a6cf27"],
+        },
+        Vector {
+            internal_key: "93478e9488f956df2396be2ce6c5cced75f900dfa18e7dabd2428aae78451820",
+            leaves: &[("20b617298552a72ade070667e86ca63b8f5789a9fe8731ef91202a91c9f3459007ac", 0xc0, 1)],
+            merkle_root: Some("c525714a7f49c28aedbbba78c005931a81c234b2f6c99a73e4d06082adc8bf2b"),
+            script_pubkey: "5120e4d810fd50586274face62b8a807eb9719cef49c04177cc6b76a9a4251d5450e",
+            control_blocks: &["c093478e9488f956df2396be2ce6c5cced75f900dfa18e7dabd2428aae78451820"],
+        },
+        Vector {
+            internal_key: "ee4fe085983462a184015d1f782d6a5f8b9c2b60130aff050ce221ecf3786592",
+            leaves: &[
+                ("20387671353e273264c495656e27e39ba899ea8fee3bb69fb2a680e22093447d48ac", 0xc0, 1),
+                ("06424950333431", 0xfa, 1),
+            ],
+            merkle_root: Some("6c2dc106ab816b73f9d07e3cd1ef2c8c1256f519748e0813e4edd2405d277bef"),
+            script_pubkey: "5120712447206d7a5238acc7ff53fbe94a3b64539ad291c7cdbc490b7577e4b17df5",
+            control_blocks: &[],
+        },
+        Vector {
+            internal_key: "f9f400803e683727b14f463836e1e78e1c64417638aa066919291a225f0e8dd8",
+            leaves: &[
+                ("2044b178d64c32c4a05cc4f4d1407268f764c940d20ce97abfd44db5c3592b72fdac", 0xc0, 1),
+                ("07546170726f6f74", 0xc0, 1),
+            ],
+            merkle_root: Some("ab179431c28d3b68fb798957faf5497d69c883c6fb1e1cd9f81483d87bac90cc"),
+            script_pubkey: "512077e30a5522dd9f894c3f8b8bd4c4b2cf82ca7da8a3ea6a239655c39c050ab220",
+            control_blocks: &[
+                "c1f9f400803e683727b14f463836e1e78e1c64417638aa066919291a225f0e8dd82cb2b90daa543b544161530c925f285b06196940d6085ca9474d41dc3822c5cb",
+                "c1f9f400803e683727b14f463836e1e78e1c64417638aa066919291a225f0e8dd864512fecdb5afa04f98839b50e6f0cb7b1e539bf6f205f67934083cdcc3c8d89",
+            ],
+        },
+        Vector {
+            internal_key: "e0dfe2300b0dd746a3f8674dfd4525623639042569d829c7f0eed9602d263e6f",
+            leaves: &[
+                ("2072ea6adcf1d371dea8fba1035a09f3d24ed5a059799bae114084130ee5898e69ac", 0xc0, 2),
+                ("202352d137f2f3ab38d1eaa976758873377fa5ebb817372c71e2c542313d4abda8ac", 0xc0, 1),
+                ("207337c0dd4253cb86f2c43a2351aadd82cccb12a172cd120452b9bb8324f2186aac", 0xc0, 1),
+            ],
+            merkle_root: Some("ccbd66c6f7e8fdab47b3a486f59d28262be857f30d4773f2d5ea47f7761ce0e2"),
+            script_pubkey: "512091b64d5324723a985170e4dc5a0f84c041804f2cd12660fa5dec09fc21783605",
+            control_blocks: &[],
+        },
+        Vector {
+            internal_key: "55adf4e8967fbd2e29f20ac896e60c3b0f1d5b0efa9d34941b5958c7b0a0312d",
+            leaves: &[
+                ("2071981521ad9fc9036687364118fb6ccd2035b96a423c59c5430e98310a11abe2ac", 0xc0, 2),
+                ("20d5094d2dbe9b76e2c245a2b89b6006888952e2faa6a149ae318d69e520617748ac", 0xc0, 1),
+                ("20c440b462ad48c7a77f94cd4532d8f2119dcebbd7c9764557e62726419b08ad4cac", 0xc0, 1),
+            ],
+            merkle_root: None,
+            script_pubkey: "512075169f4001aa68f15bbed28b218df1d0a62cbbcf1188c6665110c293c907b831",
+            control_blocks: &[],
+        },
+    ];
+
+    #[test]
+    fn test_bip341_wallet_vectors() {
+        let secp = Secp256k1::verification_only();
+        for vector in BIP341_VECTORS {
+            let mut builder = TapTreeBuilder::new();
+            for (script, version, weight) in vector.leaves {
+                let mut script = TaprootScript::new(ScriptBuf::from_hex(script).unwrap(), TaprootScriptType::Raw);
+                script.version = *version;
+                builder = builder.add_script(script, *weight);
+            }
+            let tree = builder.build().unwrap();
+            let internal_key = XOnlyPublicKey::from_str(vector.internal_key).unwrap();
+
+            if let Some(root) = vector.merkle_root {
+                assert_eq!(tree.root_hash.unwrap().to_string(), root);
+            }
+            assert_eq!(tree.script_pubkey(internal_key).unwrap().to_hex_string(), vector.script_pubkey);
+
+            let (output_key, _) = tree.output_key(internal_key).unwrap();
+            for (i, leaf) in tree.leaves.iter().enumerate() {
+                let control_block = tree.get_control_block(i, internal_key.serialize()).unwrap();
+                if let Some(expected) = vector.control_blocks.get(i) {
+                    assert_eq!(hex::encode(&control_block), *expected);
+                }
+                let control_block = ControlBlock::decode(&control_block).unwrap();
+                assert_eq!(control_block.leaf_version, leaf.version);
+                assert!(control_block.verify_taproot_commitment(&secp, output_key, &leaf.script.script));
+            }
+        }
+    }
+
+    #[test]
+    fn test_huffman_depths() {
+        let leaf = |n: u8, weight| TapLeaf::new(TaprootScript::new(ScriptBuf::from(vec![n]), TaprootScriptType::Raw), weight);
+        let mut tree = TapTree::new();
+        for (n, weight) in [(0x51, 1), (0x52, 8), (0x53, 2), (0x54, 1)] {
+            tree.add_leaf(leaf(n, weight));
+        }
+        tree.build().unwrap();
+
+        let depths: Vec<_> = (0..4).map(|i| tree.get_proof(i).unwrap().len()).collect();
+        assert_eq!(depths, vec![3, 1, 2, 3]);
+        assert_eq!(tree.branches.len(), 3);
+        assert!(TapTree::new().build().is_err());
+    }
+
+    #[test]
+    fn test_script_path_witness() {
+        let secp = Secp256k1::new();
+        let (_, a) = key(1);
+        let (secret_b, b) = key(2);
+        let policy = miniscript::parse_policy(
+            "or(9@pk(A),1@and(pk(B),older(144)))",
+            &[("A".to_string(), a), ("B".to_string(), b)].into_iter().collect(),
+        ).unwrap();
+        let tree = TapTreeBuilder::new().add_policy(&policy).unwrap().build().unwrap();
+        let internal_key = key(3).1.inner.x_only_public_key().0;
+
+        let leaf = &tree.leaves[1];
+        let msg = Message::from_digest([7; 32]);
+        let signature = bitcoin::taproot::Signature {
+            signature: secp.sign_schnorr_no_aux_rand(&msg, &Keypair::from_secret_key(&secp, &secret_b)),
+            sighash_type: bitcoin::TapSighashType::Default,
+        };
+        let satisfier = Satisfier::new()
+            .with_leaf_signature(b.inner.x_only_public_key().0, leaf.leaf_hash().unwrap(), signature)
+            .with_sequence(bitcoin::Sequence::from_height(144));
+
+        let control_block = tree.get_control_block(1, internal_key.serialize()).unwrap();
+        let witness = leaf.script.satisfy(&satisfier, control_block.clone()).unwrap();
+        assert_eq!(witness.len(), 3);
+        assert_eq!(witness.nth(0).unwrap(), signature.to_vec().as_slice());
+        assert_eq!(witness.nth(1).unwrap(), leaf.script.script.as_bytes());
+        assert_eq!(witness.nth(2).unwrap(), control_block.as_slice());
+
+        let (output_key, _) = tree.output_key(internal_key).unwrap();
+        let control_block = ControlBlock::decode(witness.nth(2).unwrap()).unwrap();
+        assert!(control_block.verify_taproot_commitment(&secp, output_key, &leaf.script.script));
+
+        // Without the relative timelock the leaf cannot be satisfied
+        let satisfier = Satisfier::new()
+            .with_leaf_signature(b.inner.x_only_public_key().0, leaf.leaf_hash().unwrap(), signature);
+        assert!(leaf.script.satisfy(&satisfier, vec![]).is_err());
+    }
+
+    #[test]
+    fn test_add_policy() {
+        let (_, a) = key(1);
+        let (_, b) = key(2);
+        let policy = miniscript::parse_policy(
+            "or(9@pk(A),1@and(pk(B),older(144)))",
+            &[("A".to_string(), a), ("B".to_string(), b)].into_iter().collect(),
+        ).unwrap();
+
+        let builder = TapTreeBuilder::new().add_policy(&policy).unwrap();
+        let leaves: Vec<_> = builder.leaves.iter().map(|leaf| (leaf.weight, leaf.script.script.clone())).collect();
+        let expected_a = TaprootScript::from_miniscript(&format!("pk({})", a)).unwrap();
+        let expected_b = TaprootScript::from_miniscript(&format!("and_v(v:pk({}),older(144))", b)).unwrap();
+        assert_eq!(leaves, vec![(900_000, expected_a.script), (100_000, expected_b.script)]);
+    }
+}
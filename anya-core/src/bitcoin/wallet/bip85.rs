@@ -0,0 +1,179 @@
+//! BIP85 deterministic entropy
+//!
+//! Every application gets its own hardened path under `m/83696968'`; the
+//! private key there is hashed with HMAC-SHA512 into 64 bytes of entropy that
+//! reveal nothing about the master key or the other children. One master
+//! backup thus recovers every Lightning node seed, DID key and test wallet
+//! derived from it.
+
+use bitcoin::bip32::{ChainCode, ChildNumber, DerivationPath, Fingerprint, Xpriv};
+use bitcoin::hashes::{hmac, sha512, Hash, HashEngine};
+use bitcoin::secp256k1::{Secp256k1, SecretKey};
+use bitcoin::PrivateKey;
+use bip39::{Language, Mnemonic};
+
+use crate::bitcoin::error::{BitcoinError, BitcoinResult};
+
+/// Purpose of every BIP85 path, "BIPS" on a phone keypad
+pub const BIP85_PURPOSE: u32 = 83696968;
+
+/// HMAC key turning a derived private key into entropy
+const ENTROPY_HMAC_KEY: &[u8] = b"bip-entropy-from-k";
+
+const APP_BIP39: u32 = 39;
+const APP_WIF: u32 = 2;
+const APP_XPRV: u32 = 32;
+const APP_HEX: u32 = 128169;
+
+fn error(message: impl Into<String>) -> BitcoinError {
+    BitcoinError::Wallet(message.into())
+}
+
+fn hardened(index: u32) -> BitcoinResult<ChildNumber> {
+    ChildNumber::from_hardened_idx(index).map_err(|_| error(format!("BIP85 index {} is out of range", index)))
+}
+
+/// `m/83696968'/app'/.../index'`
+fn application_path(steps: &[u32]) -> BitcoinResult<DerivationPath> {
+    std::iter::once(BIP85_PURPOSE).chain(steps.iter().copied())
+        .map(hardened)
+        .collect::<BitcoinResult<Vec<_>>>()
+        .map(DerivationPath::from)
+}
+
+/// BIP85 code of a BIP39 word list
+pub fn language_code(language: Language) -> u32 {
+    match language {
+        Language::English => 0,
+        Language::Japanese => 1,
+        Language::Korean => 2,
+        Language::Spanish => 3,
+        Language::SimplifiedChinese => 4,
+        Language::TraditionalChinese => 5,
+        Language::French => 6,
+        Language::Italian => 7,
+        Language::Czech => 8,
+        Language::Portuguese => 9,
+    }
+}
+
+/// 64 bytes of entropy for a fully hardened path
+pub fn derive_entropy(master: &Xpriv, path: &DerivationPath) -> BitcoinResult<[u8; 64]> {
+    if path.into_iter().any(|step| step.is_normal()) {
+        return Err(error(format!("BIP85 paths must be fully hardened: {}", path)));
+    }
+    let secp = Secp256k1::new();
+    let child = master.derive_priv(&secp, path)
+        .map_err(|e| error(format!("Failed to derive {}: {}", path, e)))?;
+
+    let mut engine = hmac::HmacEngine::<sha512::Hash>::new(ENTROPY_HMAC_KEY);
+    engine.input(&child.private_key.secret_bytes());
+    Ok(hmac::Hmac::<sha512::Hash>::from_engine(engine).to_byte_array())
+}
+
+/// Child BIP39 mnemonic of 12, 15, 18, 21 or 24 words
+pub fn derive_mnemonic(master: &Xpriv, language: Language, words: usize, index: u32) -> BitcoinResult<Mnemonic> {
+    if !matches!(words, 12 | 15 | 18 | 21 | 24) {
+        return Err(error(format!("Unsupported mnemonic length: {} words", words)));
+    }
+    let path = application_path(&[APP_BIP39, language_code(language), words as u32, index])?;
+    let entropy = derive_entropy(master, &path)?;
+    Mnemonic::from_entropy_in(language, &entropy[..words * 4 / 3])
+        .map_err(|e| error(format!("Failed to build mnemonic: {}", e)))
+}
+
+/// Child entropy of 16 to 64 bytes, e.g. a raw seed
+pub fn derive_hex(master: &Xpriv, bytes: usize, index: u32) -> BitcoinResult<Vec<u8>> {
+    if !(16..=64).contains(&bytes) {
+        return Err(error(format!("BIP85 HEX takes 16 to 64 bytes, not {}", bytes)));
+    }
+    let entropy = derive_entropy(master, &application_path(&[APP_HEX, bytes as u32, index])?)?;
+    Ok(entropy[..bytes].to_vec())
+}
+
+/// Child private key for a WIF-importing wallet, on the master's network
+pub fn derive_wif(master: &Xpriv, index: u32) -> BitcoinResult<PrivateKey> {
+    let entropy = derive_entropy(master, &application_path(&[APP_WIF, index])?)?;
+    let secret_key = SecretKey::from_slice(&entropy[..32]).map_err(|_| BitcoinError::InvalidPrivateKey)?;
+    Ok(PrivateKey { compressed: true, network: master.network, inner: secret_key })
+}
+
+/// Child master extended private key, on the master's network
+pub fn derive_xprv(master: &Xpriv, index: u32) -> BitcoinResult<Xpriv> {
+    let entropy = derive_entropy(master, &application_path(&[APP_XPRV, index])?)?;
+    let chain_code: [u8; 32] = entropy[..32].try_into().expect("32 bytes");
+    Ok(Xpriv {
+        network: master.network,
+        depth: 0,
+        parent_fingerprint: Fingerprint::default(),
+        child_number: ChildNumber::Normal { index: 0 },
+        private_key: SecretKey::from_slice(&entropy[32..]).map_err(|_| BitcoinError::InvalidPrivateKey)?,
+        chain_code: ChainCode::from(chain_code),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+
+    /// Master key of the BIP85 test vectors
+    fn master() -> Xpriv {
+        Xpriv::from_str("xprv9s21ZrQH143K2LBWUUQRFXhucrQqBpKdRRxNVq2zBqsx8HVqFk2uYo8kmbaLLHRdqtQpUm98uKfu3vca1LqdGhUtyoFnCNkfmXRyPXLjbKb").unwrap()
+    }
+
+    #[test]
+    fn test_entropy_vectors() {
+        let entropy = derive_entropy(&master(), &DerivationPath::from_str("m/83696968'/0'/0'").unwrap()).unwrap();
+        assert_eq!(
+            hex::encode(entropy),
+            "efecfbccffea313214232d29e71563d941229afb4338c21f9517c41aaa0d16f00b83d2a09ef747e7a64e8e2bd5a14869e693da66ce94ac2da570ab7ee48618f7"
+        );
+        let entropy = derive_entropy(&master(), &DerivationPath::from_str("m/83696968'/0'/1'").unwrap()).unwrap();
+        assert_eq!(
+            hex::encode(entropy),
+            "70c6e3e8ebee8dc4c0dbba66076819bb8c09672527c4277ca8729532ad711872218f826919f6b67218adde99018a6df9095ab2b58d803b5b93ec9802085a690e"
+        );
+        assert!(derive_entropy(&master(), &DerivationPath::from_str("m/83696968'/0'/0").unwrap()).is_err());
+    }
+
+    #[test]
+    fn test_mnemonic_vectors() {
+        assert_eq!(
+            derive_mnemonic(&master(), Language::English, 12, 0).unwrap().to_string(),
+            "girl mad pet galaxy egg matter matrix prison refuse sense ordinary nose"
+        );
+        assert_eq!(
+            derive_mnemonic(&master(), Language::English, 18, 0).unwrap().to_string(),
+            "near account window bike charge season chef number sketch tomorrow excuse sniff circle vital hockey outdoor supply token"
+        );
+        assert_eq!(
+            derive_mnemonic(&master(), Language::English, 24, 0).unwrap().to_string(),
+            "puppy ocean match cereal symbol another shed magic wrap hammer bulb intact gadget divorce twin tonight reason outdoor destroy simple truth cigar social volcano"
+        );
+
+        // Each language has its own path, not a translation of the English words
+        let english = derive_mnemonic(&master(), Language::English, 12, 0).unwrap();
+        let spanish = derive_mnemonic(&master(), Language::Spanish, 12, 0).unwrap();
+        assert_eq!(spanish.language(), Language::Spanish);
+        assert_ne!(spanish.to_entropy(), english.to_entropy());
+        assert!(derive_mnemonic(&master(), Language::English, 13, 0).is_err());
+    }
+
+    #[test]
+    fn test_application_vectors() {
+        assert_eq!(
+            hex::encode(derive_hex(&master(), 64, 0).unwrap()),
+            "492db4698cf3b73a5a24998aa3e9d7fa96275d85724a91e71aa2d645442f878555d078fd1f1f67e368976f04137b1f7a0d19232136ca50c44614af72b5582a5c"
+        );
+        assert!(derive_hex(&master(), 8, 0).is_err());
+        assert_eq!(
+            derive_wif(&master(), 0).unwrap().to_wif(),
+            "Kzyv4uF39d4Jrw2W7UryTHwZr1zQVNk4dAFyqE6BuMrMh1Za7uhp"
+        );
+        assert_eq!(
+            derive_xprv(&master(), 0).unwrap().to_string(),
+            "xprv9s21ZrQH143K2srSbCSg4m4kLvPMzcWydgmKEnMmoZUurYuBuYG46c6P71UGXMzmriLzCCBvKQWBUv3vPB3m1SATMhp3uEjXHJ42jFg7myX"
+        );
+    }
+}
@@ -0,0 +1,748 @@
+//! Coin selection strategies
+//!
+//! Strategies pick UTXOs by effective value, their amount minus the fee to
+//! spend them at the transaction's fee rate, and every result is scored with
+//! Bitcoin Core's waste metric:
+//!
+//! ```text
+//! waste = Σ (input fee - input fee at the long-term fee rate) + (change cost | excess)
+//! ```
+//!
+//! The change cost is the fee for the change output plus the long-term cost
+//! of spending it later; the excess is what a changeless transaction gives
+//! to miners on top of its fee. Lower is better. The input term is negative
+//! when fees are below the long-term rate, which favours consolidating then.
+
+use std::collections::{BTreeMap, HashMap};
+use std::str::FromStr;
+
+use bitcoin::script::PushBytesBuf;
+use bitcoin::{Address, ScriptBuf};
+use rand::seq::SliceRandom;
+use rand::Rng;
+
+use crate::bitcoin::error::{BitcoinError, BitcoinResult};
+use super::transactions::{CoinSelectionStrategy, TransactionAnalyzer, TxOptions, Utxo};
+
+/// Fee rate expected over the long run in sat/vB, Bitcoin Core's default
+/// consolidation fee rate
+pub const DEFAULT_LONG_TERM_FEE_RATE: f64 = 10.0;
+
+/// Branch and bound gives up after this many steps
+const BNB_MAX_TRIES: usize = 100_000;
+
+/// Random passes of the knapsack solver
+const KNAPSACK_ITERATIONS: usize = 1_000;
+
+/// Inputs chosen for a transaction, with its fee and change
+#[derive(Debug, Clone)]
+pub struct CoinSelection {
+    /// Strategy that produced the selection
+    pub strategy: CoinSelectionStrategy,
+
+    /// Selected inputs, custom inputs first
+    pub selected: Vec<Utxo>,
+
+    /// Change amount, if a change output is worth creating
+    pub change: Option<u64>,
+
+    /// Fee paid by the transaction
+    pub fee: u64,
+
+    /// Amount to take off the recipients' outputs when the fee is subtracted
+    /// from them; the rest of the fee comes from dropped change
+    pub recipient_deduction: u64,
+
+    /// Weight of the transaction, signatures at their maximum size
+    pub weight: usize,
+
+    /// Waste metric in satoshis
+    pub waste: i64,
+}
+
+impl CoinSelection {
+    /// Sum of the selected inputs
+    pub fn total_input(&self) -> u64 {
+        self.selected.iter().map(|utxo| utxo.amount).sum()
+    }
+}
+
+/// A spendable UTXO priced at the selector's fee rates
+#[derive(Debug, Clone)]
+struct Candidate {
+    utxo: Utxo,
+    weight: usize,
+    witness: bool,
+    fee: u64,
+    long_term_fee: u64,
+    /// Value counted towards the target: the effective value, or the plain
+    /// amount when recipients pay the fee
+    value: i64,
+}
+
+impl Candidate {
+    fn waste(&self) -> i64 {
+        self.fee as i64 - self.long_term_fee as i64
+    }
+}
+
+/// Cost of adding a change output under the current options
+#[derive(Debug, Clone, Copy)]
+struct ChangeCost {
+    /// Weight of the change output
+    output_weight: usize,
+    /// Fee for the change output itself
+    output_fee: u64,
+    /// Output fee plus the long-term fee to spend the change
+    total: u64,
+    /// Smallest change worth creating
+    min_value: u64,
+}
+
+/// Selects inputs with the strategy named in [`TxOptions::coin_selection`]
+#[derive(Debug, Clone)]
+pub struct CoinSelector {
+    fee_rate: f64,
+    long_term_fee_rate: f64,
+    /// Input weights by script pubkey, for scripts the analyzer cannot price,
+    /// with whether the input carries a witness
+    input_weights: HashMap<Vec<u8>, (usize, bool)>,
+    /// Output and spending weights of the change, overriding the change type
+    change_weights: Option<(usize, usize)>,
+}
+
+impl CoinSelector {
+    /// Selector for a transaction paying `fee_rate` sat/vB
+    pub fn new(fee_rate: f64) -> Self {
+        Self {
+            fee_rate,
+            long_term_fee_rate: DEFAULT_LONG_TERM_FEE_RATE,
+            input_weights: HashMap::new(),
+            change_weights: None,
+        }
+    }
+
+    /// Sets the fee rate the waste metric compares against
+    pub fn with_long_term_fee_rate(mut self, long_term_fee_rate: f64) -> Self {
+        self.long_term_fee_rate = long_term_fee_rate;
+        self
+    }
+
+    /// Prices inputs spending `script_pubkey` at `weight`, as for script
+    /// descriptors whose satisfaction the caller knows. `witness` tells
+    /// whether such an input spends with a witness, as P2SH may or may not.
+    pub fn with_input_weight(mut self, script_pubkey: Vec<u8>, weight: usize, witness: bool) -> Self {
+        self.input_weights.insert(script_pubkey, (weight, witness));
+        self
+    }
+
+    /// Prices the change as an output of `output_weight` later spent by an
+    /// input of `spend_weight`, instead of by `change_address_type`
+    pub fn with_change_weights(mut self, output_weight: usize, spend_weight: usize) -> Self {
+        self.change_weights = Some((output_weight, spend_weight));
+        self
+    }
+
+    /// Selects inputs from `utxos` to pay `outputs`, given as address and
+    /// amount pairs, together with the extra outputs of `options`
+    ///
+    /// Custom inputs in `options` are used as they are, without selecting
+    /// more. UTXOs whose input weight is unknown, like P2SH or P2WSH
+    /// without [`with_input_weight`](Self::with_input_weight), or that cost more to
+    /// spend than they are worth are left out.
+    pub fn select(&self, utxos: &[Utxo], outputs: &[(String, u64)], options: &TxOptions) -> BitcoinResult<CoinSelection> {
+        let subtract_fee = options.subtract_fee_from_amount;
+
+        let mut output_weights = Vec::new();
+        let mut amount = 0u64;
+        for (address, value) in outputs.iter().chain(options.extra_outputs.iter()) {
+            let script_pubkey = Address::from_str(address)
+                .map_err(|e| BitcoinError::Wallet(format!("Invalid address: {}", e)))?
+                .assume_checked()
+                .script_pubkey();
+            output_weights.push(TransactionAnalyzer::output_weight(script_pubkey.as_bytes()));
+            amount += value;
+        }
+        if let Some(data) = &options.op_return_data {
+            let data = PushBytesBuf::try_from(data.clone())
+                .map_err(|_| BitcoinError::Wallet("OP_RETURN data is too large".to_string()))?;
+            output_weights.push(TransactionAnalyzer::output_weight(ScriptBuf::new_op_return(&data).as_bytes()));
+        }
+        if output_weights.is_empty() {
+            return Err(BitcoinError::Wallet("Transaction has no outputs".to_string()));
+        }
+
+        let preset = match &options.custom_inputs {
+            Some(inputs) => inputs.iter()
+                .map(|utxo| {
+                    self.candidate(utxo, subtract_fee).ok_or_else(|| BitcoinError::Wallet(format!(
+                        "Cannot estimate the input weight of {}:{}", utxo.txid, utxo.vout
+                    )))
+                })
+                .collect::<BitcoinResult<Vec<_>>>()?,
+            None => Vec::new(),
+        };
+        let pool: Vec<Candidate> = if options.custom_inputs.is_some() {
+            Vec::new()
+        } else {
+            utxos.iter()
+                .filter(|utxo| utxo.confirmed || !options.confirmed_only)
+                .filter_map(|utxo| self.candidate(utxo, subtract_fee))
+                .filter(|candidate| candidate.value > 0)
+                .collect()
+        };
+
+        // What the inputs must cover besides themselves
+        let segwit = preset.iter().chain(pool.iter()).any(|candidate| candidate.witness);
+        let non_input_fee = if subtract_fee {
+            0
+        } else {
+            TransactionAnalyzer::fee_for_weight(
+                TransactionAnalyzer::estimate_tx_weight(&[], &output_weights, segwit),
+                self.fee_rate,
+            )
+        };
+        let target = (amount + non_input_fee) as i64 - preset.iter().map(|c| c.value).sum::<i64>();
+
+        let change_type = options.change_address_type;
+        let (output_weight, spend_weight, dust) = match self.change_weights {
+            // Bitcoin Core's dust rule for a witness output: 3 sat/vB for the
+            // output and a 67 vB input
+            Some((output_weight, spend_weight)) => (output_weight, spend_weight, (output_weight as u64 / 4 + 67) * 3),
+            None => (
+                TransactionAnalyzer::output_weight_for(change_type),
+                TransactionAnalyzer::input_weight_for(change_type),
+                TransactionAnalyzer::dust_threshold(change_type),
+            ),
+        };
+        let output_fee = TransactionAnalyzer::fee_for_weight(output_weight, self.fee_rate);
+        let spend_fee = TransactionAnalyzer::fee_for_weight(spend_weight, self.long_term_fee_rate);
+        let change = ChangeCost {
+            output_weight,
+            output_fee,
+            total: output_fee + spend_fee,
+            min_value: dust.max(spend_fee + 1),
+        };
+
+        let available: i64 = pool.iter().map(|c| c.value).sum();
+        if target > available {
+            return Err(BitcoinError::InsufficientFunds);
+        }
+
+        let strategy = options.coin_selection.clone();
+        let chosen = if target <= 0 && strategy != CoinSelectionStrategy::SelectAll {
+            Vec::new()
+        } else {
+            match strategy {
+                CoinSelectionStrategy::BranchAndBound => branch_and_bound(&pool, target, change.total as i64)
+                    .unwrap_or_else(|| knapsack(&pool, target, (change.output_fee + change.min_value) as i64)),
+                CoinSelectionStrategy::Knapsack => knapsack(&pool, target, (change.output_fee + change.min_value) as i64),
+                CoinSelectionStrategy::LargestFirst => {
+                    let mut order: Vec<usize> = (0..pool.len()).collect();
+                    order.sort_by_key(|i| std::cmp::Reverse(pool[*i].value));
+                    accumulate(&pool, order, target)
+                }
+                CoinSelectionStrategy::SmallestFirst => {
+                    let mut order: Vec<usize> = (0..pool.len()).collect();
+                    order.sort_by_key(|i| pool[*i].value);
+                    accumulate(&pool, order, target)
+                }
+                CoinSelectionStrategy::FIFO => {
+                    // Unconfirmed UTXOs are the newest
+                    let mut order: Vec<usize> = (0..pool.len()).collect();
+                    order.sort_by_key(|i| pool[*i].utxo.confirmation_height.map_or((1, 0), |height| (0, height)));
+                    accumulate(&pool, order, target)
+                }
+                CoinSelectionStrategy::Random => {
+                    let mut order: Vec<usize> = (0..pool.len()).collect();
+                    order.shuffle(&mut rand::thread_rng());
+                    accumulate(&pool, order, target)
+                }
+                CoinSelectionStrategy::SelectAll => (0..pool.len()).collect(),
+                CoinSelectionStrategy::Privacy => privacy(&pool, target),
+            }
+        };
+
+        let mut selected = preset;
+        selected.extend(chosen.into_iter().map(|i| pool[i].clone()));
+        self.finish(strategy, selected, amount, &output_weights, change, subtract_fee)
+    }
+
+    fn candidate(&self, utxo: &Utxo, subtract_fee: bool) -> Option<Candidate> {
+        let (weight, witness) = match self.input_weights.get(&utxo.script_pubkey) {
+            Some(&entry) => entry,
+            // Of the scripts the analyzer prices, only P2PKH has no witness
+            None => (
+                TransactionAnalyzer::input_weight(&utxo.script_pubkey)?,
+                !bitcoin::Script::from_bytes(&utxo.script_pubkey).is_p2pkh(),
+            ),
+        };
+        let fee = TransactionAnalyzer::fee_for_weight(weight, self.fee_rate);
+        let value = if subtract_fee { utxo.amount as i64 } else { utxo.amount as i64 - fee as i64 };
+        Some(Candidate {
+            utxo: utxo.clone(),
+            weight,
+            witness,
+            fee,
+            long_term_fee: TransactionAnalyzer::fee_for_weight(weight, self.long_term_fee_rate),
+            value,
+        })
+    }
+
+    /// Settles fee and change for the selected inputs and scores them
+    fn finish(
+        &self,
+        strategy: CoinSelectionStrategy,
+        selected: Vec<Candidate>,
+        amount: u64,
+        output_weights: &[usize],
+        change: ChangeCost,
+        subtract_fee: bool,
+    ) -> BitcoinResult<CoinSelection> {
+        let segwit = selected.iter().any(|c| c.witness);
+        let input_weights: Vec<usize> = selected.iter()
+            .map(|c| c.weight + usize::from(segwit && !c.witness))
+            .collect();
+        let total: u64 = selected.iter().map(|c| c.utxo.amount).sum();
+
+        let weight = TransactionAnalyzer::estimate_tx_weight(&input_weights, output_weights, segwit);
+        let fee_without_change = TransactionAnalyzer::fee_for_weight(weight, self.fee_rate);
+        let weight_with_change = TransactionAnalyzer::estimate_tx_weight(
+            &input_weights,
+            &[output_weights, &[change.output_weight]].concat(),
+            segwit,
+        );
+        let fee_with_change = TransactionAnalyzer::fee_for_weight(weight_with_change, self.fee_rate);
+
+        let required = if subtract_fee { amount } else { amount + fee_without_change };
+        if total < required {
+            return Err(BitcoinError::InsufficientFunds);
+        }
+
+        // Change left once the change output is paid for
+        let leftover = if subtract_fee { total - amount } else { (total - amount).saturating_sub(fee_with_change) };
+        let (change_value, fee, recipient_deduction, excess, weight) = if leftover >= change.min_value {
+            let deduction = if subtract_fee { fee_with_change } else { 0 };
+            (Some(leftover), fee_with_change, deduction, 0, weight_with_change)
+        } else if subtract_fee {
+            (None, fee_without_change + leftover, fee_without_change, leftover, weight)
+        } else {
+            let fee = total - amount;
+            (None, fee, 0, fee - fee_without_change, weight)
+        };
+        if subtract_fee && recipient_deduction >= amount {
+            return Err(BitcoinError::Wallet("Fee exceeds the amount sent".to_string()));
+        }
+
+        let input_waste: i64 = selected.iter().map(Candidate::waste).sum();
+        let waste = input_waste + if change_value.is_some() { change.total as i64 } else { excess as i64 };
+
+        Ok(CoinSelection {
+            strategy,
+            selected: selected.into_iter().map(|c| c.utxo).collect(),
+            change: change_value,
+            fee,
+            recipient_deduction,
+            weight,
+            waste,
+        })
+    }
+}
+
+/// Picks candidates in `order` until their value reaches the target
+fn accumulate(pool: &[Candidate], order: Vec<usize>, target: i64) -> Vec<usize> {
+    let mut sum = 0;
+    order.into_iter()
+        .take_while(|i| {
+            let needed = sum < target;
+            sum += pool[*i].value;
+            needed
+        })
+        .collect()
+}
+
+/// Bitcoin Core's branch and bound search for a changeless selection
+///
+/// Explores the inclusion tree of the candidates, largest first, for a
+/// selection worth between `target` and `target + cost_of_change` with the
+/// lowest waste. Returns `None` when none exists or the search runs out of
+/// tries.
+fn branch_and_bound(pool: &[Candidate], target: i64, cost_of_change: i64) -> Option<Vec<usize>> {
+    let mut order: Vec<usize> = (0..pool.len()).collect();
+    order.sort_by_key(|i| std::cmp::Reverse(pool[*i].value));
+    let candidates: Vec<&Candidate> = order.iter().map(|i| &pool[*i]).collect();
+
+    let mut available: i64 = candidates.iter().map(|c| c.value).sum();
+    if available < target {
+        return None;
+    }
+    // While fees are above the long-term rate, adding inputs only adds waste
+    let fee_rate_high = candidates.first().is_some_and(|c| c.waste() > 0);
+
+    let mut selection: Vec<usize> = Vec::new();
+    let mut best: Option<Vec<usize>> = None;
+    let mut best_waste = i64::MAX;
+    let (mut value, mut waste) = (0i64, 0i64);
+    let mut index = 0;
+
+    for _ in 0..BNB_MAX_TRIES {
+        let mut backtrack = false;
+        if value + available < target || value > target + cost_of_change || (waste > best_waste && fee_rate_high) {
+            backtrack = true;
+        } else if value >= target {
+            let total_waste = waste + value - target;
+            if total_waste <= best_waste {
+                best = Some(selection.clone());
+                best_waste = total_waste;
+            }
+            backtrack = true;
+        }
+
+        if backtrack {
+            let Some(&last) = selection.last() else { break };
+            // Put skipped candidates back before trying to leave out the last
+            // included one
+            index -= 1;
+            while index > last {
+                available += candidates[index].value;
+                index -= 1;
+            }
+            selection.pop();
+            value -= candidates[index].value;
+            waste -= candidates[index].waste();
+        } else {
+            let candidate = candidates[index];
+            available -= candidate.value;
+            // Including a candidate equal to a left-out predecessor would only
+            // repeat an explored branch
+            let repeats = index > 0
+                && selection.last() != Some(&(index - 1))
+                && candidate.value == candidates[index - 1].value
+                && candidate.fee == candidates[index - 1].fee;
+            if !repeats {
+                selection.push(index);
+                value += candidate.value;
+                waste += candidate.waste();
+            }
+        }
+        index += 1;
+    }
+
+    best.map(|selection| selection.into_iter().map(|i| order[i]).collect())
+}
+
+/// Bitcoin Core's knapsack solver
+///
+/// Takes an exact match if there is one, otherwise the subset closest above
+/// `target + change_target` found by random passes, or the smallest single
+/// candidate larger than that when it is closer.
+fn knapsack(pool: &[Candidate], target: i64, change_target: i64) -> Vec<usize> {
+    let mut rng = rand::thread_rng();
+    let mut order: Vec<usize> = (0..pool.len()).collect();
+    order.shuffle(&mut rng);
+
+    let mut lowest_larger: Option<usize> = None;
+    let mut smaller = Vec::new();
+    let mut smaller_total = 0;
+    for i in order {
+        let value = pool[i].value;
+        if value == target {
+            return vec![i];
+        } else if value < target + change_target {
+            smaller.push(i);
+            smaller_total += value;
+        } else if lowest_larger.is_none_or(|l| value < pool[l].value) {
+            lowest_larger = Some(i);
+        }
+    }
+
+    if smaller_total == target {
+        return smaller;
+    }
+    if smaller_total < target {
+        return lowest_larger.into_iter().collect();
+    }
+
+    smaller.sort_by_key(|i| std::cmp::Reverse(pool[*i].value));
+    let values: Vec<i64> = smaller.iter().map(|i| pool[*i].value).collect();
+    let (mut best, mut best_value) = best_subset(&mut rng, &values, smaller_total, target);
+    if best_value != target && smaller_total >= target + change_target {
+        (best, best_value) = best_subset(&mut rng, &values, smaller_total, target + change_target);
+    }
+
+    match lowest_larger {
+        Some(larger) if (best_value != target && best_value < target + change_target) || pool[larger].value <= best_value => {
+            vec![larger]
+        }
+        _ => best.into_iter()
+            .zip(smaller)
+            .filter_map(|(included, i)| included.then_some(i))
+            .collect(),
+    }
+}
+
+/// Random passes over `values`, sorted in descending order, keeping the
+/// smallest subset sum reaching `target`
+fn best_subset(rng: &mut impl Rng, values: &[i64], total: i64, target: i64) -> (Vec<bool>, i64) {
+    let mut best = vec![true; values.len()];
+    let mut best_value = total;
+
+    for _ in 0..KNAPSACK_ITERATIONS {
+        if best_value == target {
+            break;
+        }
+        let mut included = vec![false; values.len()];
+        let mut sum = 0;
+        let mut reached = false;
+        // Random inclusion first, then fill with whatever was left out
+        for pass in 0..2 {
+            if reached {
+                break;
+            }
+            for (i, value) in values.iter().enumerate() {
+                let include = if pass == 0 { rng.gen_bool(0.5) } else { !included[i] };
+                if !include {
+                    continue;
+                }
+                sum += value;
+                included[i] = true;
+                if sum >= target {
+                    reached = true;
+                    if sum < best_value {
+                        best_value = sum;
+                        best = included.clone();
+                    }
+                    sum -= value;
+                    included[i] = false;
+                }
+            }
+        }
+    }
+    (best, best_value)
+}
+
+/// Spends whole address clusters without mixing them when one is enough
+///
+/// Every UTXO of an address is spent together, so later transactions cannot
+/// link them again. The smallest cluster covering the target is preferred;
+/// failing that, the fewest clusters are combined, largest first.
+fn privacy(pool: &[Candidate], target: i64) -> Vec<usize> {
+    let mut clusters: BTreeMap<&str, (i64, Vec<usize>)> = BTreeMap::new();
+    for (i, candidate) in pool.iter().enumerate() {
+        let cluster = clusters.entry(candidate.utxo.address.as_str()).or_default();
+        cluster.0 += candidate.value;
+        cluster.1.push(i);
+    }
+
+    let mut clusters: Vec<(i64, Vec<usize>)> = clusters.into_values().collect();
+    if let Some((_, single)) = clusters.iter()
+        .filter(|(value, _)| *value >= target)
+        .min_by_key(|(value, _)| *value)
+    {
+        return single.clone();
+    }
+
+    clusters.sort_by_key(|(value, _)| std::cmp::Reverse(*value));
+    let mut sum = 0;
+    clusters.into_iter()
+        .take_while(|(value, _)| {
+            let needed = sum < target;
+            sum += value;
+            needed
+        })
+        .flat_map(|(_, indices)| indices)
+        .collect()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bitcoin::wallet::AddressType;
+
+    const RECIPIENT: &str = "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4";
+
+    fn p2wpkh(tag: u8) -> Vec<u8> {
+        [&[0x00, 0x14][..], &[tag; 20]].concat()
+    }
+
+    fn utxo(vout: u32, amount: u64, address: &str, height: Option<u32>) -> Utxo {
+        Utxo {
+            txid: format!("{:064x}", vout),
+            vout,
+            amount,
+            address: address.to_string(),
+            script_pubkey: p2wpkh(address.len() as u8),
+            derivation_path: None,
+            confirmed: height.is_some(),
+            confirmation_height: height,
+        }
+    }
+
+    fn options(strategy: CoinSelectionStrategy) -> TxOptions {
+        TxOptions { coin_selection: strategy, ..TxOptions::default() }
+    }
+
+    fn amounts(selection: &CoinSelection) -> Vec<u64> {
+        let mut amounts: Vec<u64> = selection.selected.iter().map(|u| u.amount).collect();
+        amounts.sort_unstable();
+        amounts
+    }
+
+    #[test]
+    fn test_exact_weights() {
+        assert_eq!(TransactionAnalyzer::input_weight_for(AddressType::Legacy), 592);
+        assert_eq!(TransactionAnalyzer::input_weight_for(AddressType::NestedSegWit), 364);
+        assert_eq!(TransactionAnalyzer::input_weight_for(AddressType::SegWit), 272);
+        assert_eq!(TransactionAnalyzer::input_weight_for(AddressType::Taproot), 230);
+        assert_eq!(TransactionAnalyzer::input_weight(&[&[0x00, 0x20][..], &[0; 32]].concat()), None);
+        assert_eq!(TransactionAnalyzer::input_weight(&[&[0xa9, 0x14][..], &[0; 20], &[0x87]].concat()), None);
+        assert_eq!(TransactionAnalyzer::output_weight_for(AddressType::SegWit), 124);
+        assert_eq!(TransactionAnalyzer::output_weight_for(AddressType::Taproot), 172);
+
+        // One P2WPKH input paying two P2WPKH outputs is 140.5 vB
+        let weight = TransactionAnalyzer::estimate_tx_weight(&[272], &[124, 124], true);
+        assert_eq!(weight, 562);
+        assert_eq!(TransactionAnalyzer::weight_to_vsize(weight), 141);
+    }
+
+    #[test]
+    fn test_supplied_weight_sets_witness() {
+        let p2sh = [&[0xa9, 0x14][..], &[7; 20], &[0x87]].concat();
+        let utxos = vec![Utxo { script_pubkey: p2sh.clone(), ..utxo(0, 500_000, "a", Some(1)) }];
+        let outputs = [(RECIPIENT.to_string(), 100_000)];
+        let options = options(CoinSelectionStrategy::LargestFirst);
+
+        // Unpriced P2SH is left out rather than taken for P2SH-P2WPKH
+        assert!(CoinSelector::new(1.0).select(&utxos, &outputs, &options).is_err());
+
+        // A bare 2-of-3 multisig redeem script spends without a witness
+        let selection = CoinSelector::new(1.0)
+            .with_input_weight(p2sh, 1_188, false)
+            .select(&utxos, &outputs, &options)
+            .unwrap();
+        assert!(selection.change.is_some());
+        assert_eq!(selection.weight, TransactionAnalyzer::estimate_tx_weight(&[1_188], &[124, 124], false));
+    }
+
+    #[test]
+    fn test_branch_and_bound_is_changeless() {
+        // At 1 sat/vB a P2WPKH input costs 68 sats, the outputs and header 42
+        let utxos = vec![
+            utxo(0, 1_000_000, "a", Some(1)),
+            utxo(1, 100_068, "b", Some(2)),
+            utxo(2, 50_068, "c", Some(3)),
+            utxo(3, 30_068, "d", Some(4)),
+        ];
+        let selection = CoinSelector::new(1.0)
+            .select(&utxos, &[(RECIPIENT.to_string(), 149_958)], &options(CoinSelectionStrategy::BranchAndBound))
+            .unwrap();
+
+        assert_eq!(amounts(&selection), vec![50_068, 100_068]);
+        assert_eq!(selection.change, None);
+        assert_eq!(selection.fee, 178);
+        assert_eq!(TransactionAnalyzer::fee_for_weight(selection.weight, 1.0), 178);
+        // Two inputs at 68 sats instead of 680 at the long-term rate, no excess
+        assert_eq!(selection.waste, 2 * (68 - 680));
+    }
+
+    #[test]
+    fn test_largest_first_with_change() {
+        let utxos = vec![utxo(0, 50_000, "a", Some(1)), utxo(1, 400_000, "b", Some(2))];
+        let selection = CoinSelector::new(2.0)
+            .select(&utxos, &[(RECIPIENT.to_string(), 100_000)], &options(CoinSelectionStrategy::LargestFirst))
+            .unwrap();
+
+        assert_eq!(amounts(&selection), vec![400_000]);
+        let change = selection.change.unwrap();
+        assert_eq!(selection.total_input(), 100_000 + change + selection.fee);
+        assert_eq!(selection.fee, TransactionAnalyzer::fee_for_weight(selection.weight, 2.0));
+        // Input waste plus the change output at 2 sat/vB and its later spend at 10
+        assert_eq!(selection.waste, (136 - 680) + 62 + 680);
+    }
+
+    #[test]
+    fn test_privacy_does_not_mix_clusters() {
+        let utxos = vec![
+            utxo(0, 60_000, "cluster-a", Some(1)),
+            utxo(1, 60_000, "cluster-a", Some(2)),
+            utxo(2, 300_000, "cluster-b", Some(3)),
+        ];
+        let selector = CoinSelector::new(1.0);
+        let selection = selector
+            .select(&utxos, &[(RECIPIENT.to_string(), 100_000)], &options(CoinSelectionStrategy::Privacy))
+            .unwrap();
+        assert_eq!(amounts(&selection), vec![60_000, 60_000]);
+
+        let selection = selector
+            .select(&utxos, &[(RECIPIENT.to_string(), 150_000)], &options(CoinSelectionStrategy::Privacy))
+            .unwrap();
+        assert_eq!(amounts(&selection), vec![300_000]);
+    }
+
+    #[test]
+    fn test_options_are_respected() {
+        let utxos = vec![
+            utxo(0, 80_000, "a", Some(20)),
+            utxo(1, 80_000, "b", Some(10)),
+            utxo(2, 500_000, "c", None),
+        ];
+        let selector = CoinSelector::new(1.0);
+        let outputs = [(RECIPIENT.to_string(), 200_000)];
+
+        let result = selector.select(&utxos, &outputs, &options(CoinSelectionStrategy::LargestFirst));
+        assert!(matches!(result, Err(BitcoinError::InsufficientFunds)));
+
+        let mut unconfirmed = options(CoinSelectionStrategy::LargestFirst);
+        unconfirmed.confirmed_only = false;
+        assert_eq!(amounts(&selector.select(&utxos, &outputs, &unconfirmed).unwrap()), vec![500_000]);
+
+        // Oldest first
+        let selection = selector
+            .select(&utxos, &[(RECIPIENT.to_string(), 50_000)], &options(CoinSelectionStrategy::FIFO))
+            .unwrap();
+        assert_eq!(selection.selected[0].vout, 1);
+
+        // Custom inputs are used as given
+        let mut custom = options(CoinSelectionStrategy::LargestFirst);
+        custom.custom_inputs = Some(vec![utxos[0].clone()]);
+        let selection = selector.select(&utxos, &[(RECIPIENT.to_string(), 50_000)], &custom).unwrap();
+        assert_eq!(selection.selected.len(), 1);
+        assert_eq!(selection.selected[0].vout, 0);
+
+        // The fee comes out of the amount sent
+        let mut subtract = options(CoinSelectionStrategy::SmallestFirst);
+        subtract.subtract_fee_from_amount = true;
+        let selection = selector.select(&utxos, &[(RECIPIENT.to_string(), 160_000)], &subtract).unwrap();
+        assert_eq!(selection.change, None);
+        assert_eq!(selection.recipient_deduction, selection.fee);
+        assert_eq!(selection.total_input(), 160_000);
+    }
+
+    #[test]
+    fn test_every_strategy_pays_its_way() {
+        let utxos: Vec<Utxo> = (0..12)
+            .map(|i| utxo(i, 10_000 + u64::from(i) * 7_919, &format!("addr-{}", i % 4), Some(i)))
+            .collect();
+        let outputs = [(RECIPIENT.to_string(), 61_000)];
+        let selector = CoinSelector::new(3.0);
+
+        for strategy in [
+            CoinSelectionStrategy::BranchAndBound,
+            CoinSelectionStrategy::Knapsack,
+            CoinSelectionStrategy::FIFO,
+            CoinSelectionStrategy::LargestFirst,
+            CoinSelectionStrategy::SmallestFirst,
+            CoinSelectionStrategy::Random,
+            CoinSelectionStrategy::SelectAll,
+            CoinSelectionStrategy::Privacy,
+        ] {
+            let selection = selector.select(&utxos, &outputs, &options(strategy.clone())).unwrap();
+            assert_eq!(
+                selection.total_input(),
+                61_000 + selection.fee + selection.change.unwrap_or(0),
+                "{:?}", strategy
+            );
+            assert!(selection.fee >= TransactionAnalyzer::fee_for_weight(selection.weight, 3.0), "{:?}", strategy);
+        }
+    }
+}
@@ -0,0 +1,536 @@
+//! Ranged output descriptors over extended public keys
+//!
+//! Supports `pkh`, `wpkh`, `sh(wpkh)` and `tr` with a single key, and
+//! `wsh(multi)` / `wsh(sortedmulti)`. Keys are extended public keys with
+//! their origin, `[fingerprint/path]xpub/path`, ending in `/*` or in the
+//! BIP389 `/<0;1>/*` pair of receive and change keychains. The origin is
+//! kept with every derived key so hardware and air-gapped signers can
+//! recognise their keys in a PSBT. Parsing, checksums and scripts come
+//! from rust-miniscript.
+//!
+//! Bare xpubs are imported following SLIP-132: `xpub`/`tpub` become `pkh`,
+//! `ypub`/`upub` become `sh(wpkh)` and `zpub`/`vpub` become `wpkh`.
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::str::FromStr;
+
+use bitcoin::bip32::{ChildNumber, DerivationPath, Fingerprint, KeySource, Xpub};
+use bitcoin::secp256k1::{PublicKey, Secp256k1, Verification, XOnlyPublicKey};
+use bitcoin::taproot::{LeafVersion, TapLeafHash};
+use bitcoin::{Address, Network, NetworkKind, ScriptBuf};
+use miniscript::descriptor::{DerivPaths, DescriptorMultiXKey, DescriptorXKey, ShInner, WshInner};
+use miniscript::{Descriptor, DescriptorPublicKey, Miniscript, Terminal, Threshold, ToPublicKey};
+
+use crate::bitcoin::error::{BitcoinError, BitcoinResult};
+use super::transactions::TransactionAnalyzer;
+use super::AddressType;
+
+/// SLIP-132 version bytes: prefix, version, mainnet, script type
+const SLIP132_VERSIONS: [(&str, [u8; 4], bool, AddressType); 6] = [
+    ("xpub", [0x04, 0x88, 0xb2, 0x1e], true, AddressType::Legacy),
+    ("ypub", [0x04, 0x9d, 0x7c, 0xb2], true, AddressType::NestedSegWit),
+    ("zpub", [0x04, 0xb2, 0x47, 0x46], true, AddressType::SegWit),
+    ("tpub", [0x04, 0x35, 0x87, 0xcf], false, AddressType::Legacy),
+    ("upub", [0x04, 0x4a, 0x52, 0x62], false, AddressType::NestedSegWit),
+    ("vpub", [0x04, 0x5f, 0x1c, 0xf6], false, AddressType::SegWit),
+];
+
+fn error(message: impl Into<String>) -> BitcoinError {
+    BitcoinError::Wallet(message.into())
+}
+
+/// Receive or change addresses
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+pub enum Keychain {
+    External,
+    Internal,
+}
+
+/// How a key continues after its fixed path
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Wildcard {
+    /// A single key
+    None,
+    /// `/*`, one keychain
+    Ranged,
+    /// `/<external;internal>/*`
+    Multipath(u32, u32),
+}
+
+/// Extended public key in a descriptor
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DescriptorXpub {
+    /// Master fingerprint and path to the xpub, if known
+    pub origin: Option<KeySource>,
+    pub xpub: Xpub,
+    /// Unhardened steps after the xpub
+    pub path: DerivationPath,
+    pub wildcard: Wildcard,
+}
+
+impl DescriptorXpub {
+    /// The key as rust-miniscript writes it in descriptors
+    pub fn to_descriptor_key(&self) -> DescriptorPublicKey {
+        let (paths, wildcard) = match self.wildcard {
+            Wildcard::None => (vec![self.path.clone()], miniscript::descriptor::Wildcard::None),
+            Wildcard::Ranged => (vec![self.path.clone()], miniscript::descriptor::Wildcard::Unhardened),
+            Wildcard::Multipath(external, internal) => (
+                vec![
+                    self.path.child(ChildNumber::Normal { index: external }),
+                    self.path.child(ChildNumber::Normal { index: internal }),
+                ],
+                miniscript::descriptor::Wildcard::Unhardened,
+            ),
+        };
+        match <[DerivationPath; 1]>::try_from(paths) {
+            Ok([derivation_path]) => DescriptorPublicKey::XPub(DescriptorXKey {
+                origin: self.origin.clone(),
+                xkey: self.xpub,
+                derivation_path,
+                wildcard,
+            }),
+            Err(paths) => DescriptorPublicKey::MultiXPub(DescriptorMultiXKey {
+                origin: self.origin.clone(),
+                xkey: self.xpub,
+                derivation_paths: DerivPaths::new(paths).expect("two derivation paths"),
+                wildcard,
+            }),
+        }
+    }
+
+    /// Derives the key at `index` of a keychain, with its full key origin
+    pub fn derive<C: Verification>(&self, secp: &Secp256k1<C>, keychain: Keychain, index: u32) -> BitcoinResult<(PublicKey, KeySource)> {
+        let mut keys = self.to_descriptor_key().into_single_keys();
+        let key = if keychain == Keychain::Internal && keys.len() > 1 { keys.swap_remove(1) } else { keys.swap_remove(0) };
+        let key = key.at_derivation_index(index)
+            .map_err(|e| error(format!("Cannot derive {} at {}: {}", self, index, e)))?;
+        let public_key = key.derive_public_key(secp)
+            .map_err(|e| error(format!("Cannot derive {} at {}: {}", self, index, e)))?;
+        let path = key.full_derivation_path().expect("single path key");
+        Ok((public_key.inner, (key.master_fingerprint(), path)))
+    }
+
+    /// Whether the key has separate receive and change keychains
+    pub fn is_multipath(&self) -> bool {
+        matches!(self.wildcard, Wildcard::Multipath(..))
+    }
+}
+
+impl TryFrom<DescriptorPublicKey> for DescriptorXpub {
+    type Error = BitcoinError;
+
+    fn try_from(key: DescriptorPublicKey) -> Result<Self, Self::Error> {
+        if key.has_hardened_step() {
+            return Err(error(format!("Cannot derive hardened steps from an xpub: {}", key)));
+        }
+        match key {
+            DescriptorPublicKey::XPub(xkey) => {
+                let wildcard = match xkey.wildcard {
+                    miniscript::descriptor::Wildcard::None => Wildcard::None,
+                    miniscript::descriptor::Wildcard::Unhardened => Wildcard::Ranged,
+                    miniscript::descriptor::Wildcard::Hardened => {
+                        return Err(error(format!("Cannot derive hardened steps from an xpub: {}", xkey.xkey)));
+                    }
+                };
+                Ok(DescriptorXpub { origin: xkey.origin, xpub: xkey.xkey, path: xkey.derivation_path, wildcard })
+            }
+            DescriptorPublicKey::MultiXPub(xkey) => {
+                // Only a final `/<external;internal>/*` step is supported
+                let multipath = match (&xkey.derivation_paths.paths()[..], xkey.wildcard) {
+                    ([external, internal], miniscript::descriptor::Wildcard::Unhardened) => {
+                        match (external.as_ref().split_last(), internal.as_ref().split_last()) {
+                            (Some((ChildNumber::Normal { index: e }, path)), Some((ChildNumber::Normal { index: i }, rest))) if path == rest => {
+                                Some((DerivationPath::from(path), *e, *i))
+                            }
+                            _ => None,
+                        }
+                    }
+                    _ => None,
+                };
+                let (path, external, internal) = multipath
+                    .ok_or_else(|| error(format!("Only /<external;internal>/* multipaths are supported: {}", xkey.xkey)))?;
+                Ok(DescriptorXpub { origin: xkey.origin, xpub: xkey.xkey, path, wildcard: Wildcard::Multipath(external, internal) })
+            }
+            DescriptorPublicKey::Single(_) => Err(error(format!("Descriptor keys must be extended public keys: {}", key))),
+        }
+    }
+}
+
+/// Parses `fingerprint/path`, with or without brackets
+pub fn parse_origin(origin: &str) -> BitcoinResult<KeySource> {
+    let origin = origin.trim_start_matches('[').trim_end_matches(']');
+    let (fingerprint, path) = origin.split_once('/').unwrap_or((origin, ""));
+    let fingerprint = Fingerprint::from_str(fingerprint)
+        .map_err(|_| error(format!("Invalid fingerprint: {}", fingerprint)))?;
+    let path = if path.is_empty() {
+        DerivationPath::master()
+    } else {
+        DerivationPath::from_str(&format!("m/{}", path))
+            .map_err(|e| error(format!("Invalid derivation path {}: {}", path, e)))?
+    };
+    Ok((fingerprint, path))
+}
+
+/// Decodes an xpub in any SLIP-132 encoding, with the script type it implies
+pub fn decode_slip132(key: &str) -> BitcoinResult<(Xpub, AddressType)> {
+    let mut data = bitcoin::base58::decode_check(key)
+        .map_err(|e| error(format!("Invalid extended public key: {}", e)))?;
+    if data.len() != 78 {
+        return Err(error("Extended public keys are 78 bytes"));
+    }
+    let (_, _, mainnet, address_type) = SLIP132_VERSIONS.iter()
+        .find(|(_, version, _, _)| data[..4] == version[..])
+        .ok_or_else(|| error(format!("Unknown extended key version: {}", hex::encode(&data[..4]))))?;
+    // Re-encode as a plain xpub or tpub
+    let plain = if *mainnet { SLIP132_VERSIONS[0].1 } else { SLIP132_VERSIONS[3].1 };
+    data[..4].copy_from_slice(&plain);
+    let xpub = Xpub::decode(&data).map_err(|e| error(format!("Invalid extended public key: {}", e)))?;
+    Ok((xpub, *address_type))
+}
+
+impl FromStr for DescriptorXpub {
+    type Err = BitcoinError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        DescriptorPublicKey::from_str(s)
+            .map_err(|e| error(format!("Invalid descriptor key {}: {}", s, e)))?
+            .try_into()
+    }
+}
+
+impl fmt::Display for DescriptorXpub {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}", self.to_descriptor_key())
+    }
+}
+
+/// Descriptor of the outputs a watch-only wallet follows
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum WalletDescriptor {
+    Pkh(DescriptorXpub),
+    Wpkh(DescriptorXpub),
+    ShWpkh(DescriptorXpub),
+    /// Key path only taproot
+    Tr(DescriptorXpub),
+    /// `threshold`-of-n P2WSH multisig; `sorted` orders the keys of every
+    /// derived script lexicographically
+    Wsh { threshold: usize, keys: Vec<DescriptorXpub>, sorted: bool },
+}
+
+/// Scripts and keys of one derived output
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DerivedOutput {
+    pub script_pubkey: ScriptBuf,
+    /// Every key with its origin, in script order
+    pub keys: Vec<(PublicKey, KeySource)>,
+    pub redeem_script: Option<ScriptBuf>,
+    pub witness_script: Option<ScriptBuf>,
+    /// Internal key of a taproot output
+    pub internal_key: Option<XOnlyPublicKey>,
+    /// Leaves of a taproot output's script tree each key appears in
+    pub leaf_hashes: BTreeMap<XOnlyPublicKey, Vec<TapLeafHash>>,
+}
+
+impl WalletDescriptor {
+    /// Descriptor for a bare xpub, ypub or zpub over both keychains
+    ///
+    /// `origin` is the master fingerprint and account path, as in
+    /// `d34db33f/84'/0'/0'`, for signers that need it.
+    pub fn from_extended_key(key: &str, origin: Option<&str>) -> BitcoinResult<Self> {
+        let (xpub, address_type) = decode_slip132(key)?;
+        let key = DescriptorXpub {
+            origin: origin.map(parse_origin).transpose()?,
+            xpub,
+            path: DerivationPath::master(),
+            wildcard: Wildcard::Multipath(0, 1),
+        };
+        Ok(match address_type {
+            AddressType::Legacy => WalletDescriptor::Pkh(key),
+            AddressType::NestedSegWit => WalletDescriptor::ShWpkh(key),
+            AddressType::SegWit => WalletDescriptor::Wpkh(key),
+            AddressType::Taproot => WalletDescriptor::Tr(key),
+        })
+    }
+
+    /// Keys of the descriptor
+    pub fn keys(&self) -> Vec<&DescriptorXpub> {
+        match self {
+            WalletDescriptor::Pkh(key)
+            | WalletDescriptor::Wpkh(key)
+            | WalletDescriptor::ShWpkh(key)
+            | WalletDescriptor::Tr(key) => vec![key],
+            WalletDescriptor::Wsh { keys, .. } => keys.iter().collect(),
+        }
+    }
+
+    /// Network kind of the keys
+    pub fn network_kind(&self) -> NetworkKind {
+        self.keys()[0].xpub.network
+    }
+
+    /// Keychains the descriptor covers: both for multipath keys
+    pub fn keychains(&self) -> Vec<Keychain> {
+        if self.keys()[0].is_multipath() {
+            vec![Keychain::External, Keychain::Internal]
+        } else {
+            vec![Keychain::External]
+        }
+    }
+
+    /// Whether the descriptor derives more than one output
+    pub fn is_ranged(&self) -> bool {
+        self.keys()[0].wildcard != Wildcard::None
+    }
+
+    /// Address type of a single-key descriptor
+    pub fn address_type(&self) -> Option<AddressType> {
+        match self {
+            WalletDescriptor::Pkh(_) => Some(AddressType::Legacy),
+            WalletDescriptor::Wpkh(_) => Some(AddressType::SegWit),
+            WalletDescriptor::ShWpkh(_) => Some(AddressType::NestedSegWit),
+            WalletDescriptor::Tr(_) => Some(AddressType::Taproot),
+            WalletDescriptor::Wsh { .. } => None,
+        }
+    }
+
+    /// Whether an input spending one of the outputs carries a witness
+    pub fn has_witness(&self) -> bool {
+        !matches!(self, WalletDescriptor::Pkh(_))
+    }
+
+    /// Weight of an input spending one of the outputs
+    pub fn input_weight(&self) -> usize {
+        match self {
+            WalletDescriptor::Wsh { threshold, keys, .. } => {
+                let script_len = 3 + keys.len() * 34;
+                // Empty dummy element, signatures and the witness script
+                let witness = 1 + 1 + threshold * (1 + 72) + 1 + script_len;
+                (36 + 4 + 1) * 4 + witness
+            }
+            _ => TransactionAnalyzer::input_weight_for(self.address_type().expect("single-key descriptor")),
+        }
+    }
+
+    /// Weight of an output paying to the descriptor
+    pub fn output_weight(&self) -> usize {
+        match self.address_type() {
+            Some(address_type) => TransactionAnalyzer::output_weight_for(address_type),
+            // Like P2TR, a 34-byte witness program
+            None => TransactionAnalyzer::output_weight_for(AddressType::Taproot),
+        }
+    }
+
+    /// The rust-miniscript descriptor over both keychains
+    pub fn to_descriptor(&self) -> BitcoinResult<Descriptor<DescriptorPublicKey>> {
+        let descriptor = match self {
+            WalletDescriptor::Pkh(key) => Descriptor::new_pkh(key.to_descriptor_key()),
+            WalletDescriptor::Wpkh(key) => Descriptor::new_wpkh(key.to_descriptor_key()),
+            WalletDescriptor::ShWpkh(key) => Descriptor::new_sh_wpkh(key.to_descriptor_key()),
+            WalletDescriptor::Tr(key) => Descriptor::new_tr(key.to_descriptor_key(), None),
+            WalletDescriptor::Wsh { threshold, keys, sorted } => {
+                let keys: Vec<DescriptorPublicKey> = keys.iter().map(DescriptorXpub::to_descriptor_key).collect();
+                if *sorted {
+                    Descriptor::new_wsh_sortedmulti(*threshold, keys)
+                } else {
+                    let multi = Threshold::new(*threshold, keys)
+                        .map_err(|e| error(format!("Invalid multisig: {}", e)))?;
+                    Miniscript::from_ast(Terminal::Multi(multi)).and_then(Descriptor::new_wsh)
+                }
+            }
+        };
+        descriptor.map_err(|e| error(format!("Invalid descriptor: {}", e)))
+    }
+
+    /// Single-path descriptor of one keychain, as a node imports it
+    pub fn keychain_descriptor(&self, keychain: Keychain) -> BitcoinResult<Descriptor<DescriptorPublicKey>> {
+        let mut descriptors = self.to_descriptor()?
+            .into_single_descriptors()
+            .map_err(|e| error(format!("Invalid descriptor: {}", e)))?;
+        if keychain == Keychain::Internal && descriptors.len() > 1 {
+            Ok(descriptors.swap_remove(1))
+        } else {
+            Ok(descriptors.swap_remove(0))
+        }
+    }
+
+    /// Derives the output at `index` of a keychain
+    pub fn derive<C: Verification>(&self, secp: &Secp256k1<C>, keychain: Keychain, index: u32) -> BitcoinResult<DerivedOutput> {
+        let derived = self.keychain_descriptor(keychain)?.at_derivation_index(index)
+            .map_err(|e| error(format!("Cannot derive index {}: {}", index, e)))?
+            .derived_descriptor(secp)
+            .map_err(|e| error(format!("Cannot derive index {}: {}", index, e)))?;
+
+        let mut keys = self.keys().into_iter()
+            .map(|key| key.derive(secp, keychain, index))
+            .collect::<BitcoinResult<Vec<_>>>()?;
+        if matches!(self, WalletDescriptor::Wsh { sorted: true, .. }) {
+            keys.sort_by_key(|(public_key, _)| public_key.serialize());
+        }
+
+        let (redeem_script, witness_script, internal_key) = match &derived {
+            Descriptor::Sh(sh) => (Some(sh.inner_script()), None, None),
+            Descriptor::Wsh(wsh) => (None, Some(wsh.inner_script()), None),
+            Descriptor::Tr(tr) => (None, None, Some(tr.internal_key().inner.x_only_public_key().0)),
+            _ => (None, None, None),
+        };
+        let mut leaf_hashes: BTreeMap<XOnlyPublicKey, Vec<TapLeafHash>> = BTreeMap::new();
+        if let Descriptor::Tr(tr) = &derived {
+            for (_, leaf) in tr.iter_scripts() {
+                let leaf_hash = TapLeafHash::from_script(&leaf.encode(), LeafVersion::TapScript);
+                for key in leaf.iter_pk() {
+                    let hashes = leaf_hashes.entry(key.to_x_only_pubkey()).or_default();
+                    if !hashes.contains(&leaf_hash) {
+                        hashes.push(leaf_hash);
+                    }
+                }
+            }
+        }
+        Ok(DerivedOutput { script_pubkey: derived.script_pubkey(), keys, redeem_script, witness_script, internal_key, leaf_hashes })
+    }
+
+    /// Address at `index` of a keychain
+    pub fn address<C: Verification>(&self, secp: &Secp256k1<C>, network: Network, keychain: Keychain, index: u32) -> BitcoinResult<Address> {
+        let output = self.derive(secp, keychain, index)?;
+        Address::from_script(&output.script_pubkey, network)
+            .map_err(|e| error(format!("No address for {}: {}", output.script_pubkey, e)))
+    }
+}
+
+impl FromStr for WalletDescriptor {
+    type Err = BitcoinError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let s: String = s.split_whitespace().collect();
+        // Checks the checksum when there is one
+        let descriptor = Descriptor::<DescriptorPublicKey>::from_str(&s)
+            .map_err(|e| error(format!("Invalid descriptor {}: {}", s, e)))?;
+
+        let unsupported = || error(format!("Unsupported descriptor: {}", s));
+        let key = |key: &DescriptorPublicKey| DescriptorXpub::try_from(key.clone());
+        let wallet = match &descriptor {
+            Descriptor::Pkh(pkh) => WalletDescriptor::Pkh(key(pkh.as_inner())?),
+            Descriptor::Wpkh(wpkh) => WalletDescriptor::Wpkh(key(wpkh.as_inner())?),
+            Descriptor::Sh(sh) => match sh.as_inner() {
+                ShInner::Wpkh(wpkh) => WalletDescriptor::ShWpkh(key(wpkh.as_inner())?),
+                _ => return Err(unsupported()),
+            },
+            Descriptor::Tr(tr) if tr.tap_tree().is_none() => WalletDescriptor::Tr(key(tr.internal_key())?),
+            Descriptor::Wsh(wsh) => {
+                let (threshold, keys, sorted) = match wsh.as_inner() {
+                    WshInner::SortedMulti(multi) => (multi.k(), multi.pks(), true),
+                    WshInner::Ms(ms) => match &ms.node {
+                        Terminal::Multi(multi) => (multi.k(), multi.data(), false),
+                        _ => return Err(unsupported()),
+                    },
+                };
+                let keys = keys.iter().map(key).collect::<BitcoinResult<Vec<_>>>()?;
+                WalletDescriptor::Wsh { threshold, keys, sorted }
+            }
+            _ => return Err(unsupported()),
+        };
+
+        // All keys must range the same way on the same network
+        let first = wallet.keys()[0];
+        if wallet.keys().iter().any(|k| k.wildcard != first.wildcard || k.xpub.network != first.xpub.network) {
+            return Err(error("Descriptor keys differ in network or derivation"));
+        }
+        Ok(wallet)
+    }
+}
+
+impl fmt::Display for WalletDescriptor {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // rust-miniscript appends the checksum
+        let descriptor = self.to_descriptor().map_err(|_| fmt::Error)?;
+        write!(f, "{}", descriptor)
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use bitcoin::bip32::Xpriv;
+
+    /// Account xpub at `m/84'/1'/0'` of a fixed seed, with its origin
+    pub(crate) fn account(seed: u8) -> (Xpriv, String) {
+        let secp = Secp256k1::new();
+        let master = Xpriv::new_master(Network::Regtest, &[seed; 32]).unwrap();
+        let path = DerivationPath::from_str("m/84'/1'/0'").unwrap();
+        let account = Xpub::from_priv(&secp, &master.derive_priv(&secp, &path).unwrap());
+        (master, format!("[{}/84'/1'/0']{}", master.fingerprint(&secp), account))
+    }
+
+    #[test]
+    fn test_parse_and_derive() {
+        let secp = Secp256k1::new();
+        let (master, key) = account(1);
+        let descriptor: WalletDescriptor = format!("wpkh({}/<0;1>/*)", key).parse().unwrap();
+        assert_eq!(descriptor.keychains(), vec![Keychain::External, Keychain::Internal]);
+
+        // Round trip with checksum
+        let encoded = descriptor.to_string();
+        assert_eq!(encoded.parse::<WalletDescriptor>().unwrap(), descriptor);
+        let mut corrupted = encoded.clone();
+        corrupted.pop();
+        corrupted.push('x');
+        assert!(corrupted.parse::<WalletDescriptor>().is_err());
+
+        // Derived keys carry their full origin and match the private key
+        let output = descriptor.derive(&secp, Keychain::Internal, 7).unwrap();
+        let (public_key, (fingerprint, path)) = &output.keys[0];
+        assert_eq!(*fingerprint, master.fingerprint(&secp));
+        assert_eq!(*path, DerivationPath::from_str("m/84'/1'/0'/1/7").unwrap());
+        let expected = master.derive_priv(&secp, path).unwrap().private_key.public_key(&secp);
+        assert_eq!(*public_key, expected);
+        assert!(output.script_pubkey.is_p2wpkh());
+
+        assert!(format!("wpkh({}/0'/*)", key).parse::<WalletDescriptor>().is_err());
+        assert!(format!("sh(pkh({}/*))", key).parse::<WalletDescriptor>().is_err());
+    }
+
+    #[test]
+    fn test_slip132_import() {
+        let (_, key) = account(2);
+        let tpub = key.split(']').nth(1).unwrap();
+        let (xpub, _) = decode_slip132(tpub).unwrap();
+
+        // Same key encoded as a vpub imports as native segwit
+        let mut data = xpub.encode().to_vec();
+        data[..4].copy_from_slice(&[0x04, 0x5f, 0x1c, 0xf6]);
+        let vpub = bitcoin::base58::encode_check(&data);
+        let descriptor = WalletDescriptor::from_extended_key(&vpub, Some("deadbeef/84'/1'/0'")).unwrap();
+        assert_eq!(descriptor.address_type(), Some(AddressType::SegWit));
+        assert_eq!(descriptor.keys()[0].xpub, xpub);
+        assert!(descriptor.to_string().starts_with(&format!("wpkh([deadbeef/84'/1'/0']{}/<0;1>/*)", tpub)));
+
+        let descriptor = WalletDescriptor::from_extended_key(tpub, None).unwrap();
+        assert_eq!(descriptor.address_type(), Some(AddressType::Legacy));
+    }
+
+    #[test]
+    fn test_sortedmulti() {
+        let secp = Secp256k1::new();
+        let keys: Vec<String> = (1..=3).map(|seed| format!("{}/<0;1>/*", account(seed).1)).collect();
+        let descriptor: WalletDescriptor = format!("wsh(sortedmulti(2,{}))", keys.join(",")).parse().unwrap();
+        let reversed: WalletDescriptor = format!("wsh(sortedmulti(2,{},{},{}))", keys[2], keys[1], keys[0]).parse().unwrap();
+
+        let output = descriptor.derive(&secp, Keychain::External, 0).unwrap();
+        assert_eq!(output.script_pubkey, reversed.derive(&secp, Keychain::External, 0).unwrap().script_pubkey);
+        assert!(output.script_pubkey.is_p2wsh());
+        assert_eq!(output.keys.len(), 3);
+        assert!(output.keys.windows(2).all(|pair| pair[0].0.serialize() < pair[1].0.serialize()));
+        // 2-of-3: 41 bytes outside the witness, 1 + 1 + 2 * 73 + 1 + 105 inside
+        assert_eq!(descriptor.input_weight(), 164 + 254);
+
+        assert!(format!("wsh(sortedmulti(4,{}))", keys.join(",")).parse::<WalletDescriptor>().is_err());
+
+        // Plain multi keeps the descriptor's key order
+        let unsorted: WalletDescriptor = format!("wsh(multi(2,{},{},{}))", keys[2], keys[1], keys[0]).parse().unwrap();
+        assert_eq!(unsorted.to_string().parse::<WalletDescriptor>().unwrap(), unsorted);
+        let output = unsorted.derive(&secp, Keychain::Internal, 3).unwrap();
+        let expected = reversed.keys()[0].derive(&secp, Keychain::Internal, 3).unwrap();
+        assert_eq!(output.keys[0], expected);
+        assert_eq!(output.witness_script.unwrap().as_bytes()[2..35], expected.0.serialize());
+    }
+}
@@ -10,7 +10,15 @@
 use crate::bitcoin::interface::BitcoinInterface;
 
 pub mod bip32;
+pub mod bip85;
 pub mod transactions;
+pub mod coin_selection;
+pub mod descriptor;
+pub mod watch_only;
+pub mod multisig;
+pub mod node;
+
+pub use watch_only::{ChainSource, WatchOnlyWallet};
 
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum WalletType {
@@ -90,11 +98,19 @@ pub trait UnifiedWallet: KeyManager + AddressManager + TransactionManager + Bala
     fn export_xpriv(&self, password: &str) -> AnyaResult<String>;
     fn import_xpriv(&self, xpriv: &str, password: &str) -> AnyaResult<()>;
     
+    // Watch-only import
+    fn import_xpub(&self, xpub: &str, origin: Option<&str>) -> AnyaResult<()>;
+    fn import_descriptor(&self, descriptor: &str) -> AnyaResult<()>;
+    fn is_watch_only(&self) -> bool {
+        false
+    }
+    
     // Backup management
     fn backup(&self, path: &str, password: &str) -> AnyaResult<()>;
     fn restore(&self, path: &str, password: &str) -> AnyaResult<()>;
 }
 
+#[derive(Debug, Clone)]
 pub struct Asset {
     pub id: String,
     pub name: String,
@@ -484,6 +500,15 @@ fn import_xpriv(&self, _xpriv: &str, _password: &str) -> AnyaResult<()> {
         Err(BitcoinError::Wallet("Not implemented".to_string()).into())
     }
     
+    fn import_xpub(&self, _xpub: &str, _origin: Option<&str>) -> AnyaResult<()> {
+        // Keys come from the seed; public keys are followed by a WatchOnlyWallet
+        Err(BitcoinError::Wallet("Import xpubs into a WatchOnlyWallet".to_string()).into())
+    }
+    
+    fn import_descriptor(&self, _descriptor: &str) -> AnyaResult<()> {
+        Err(BitcoinError::Wallet("Import descriptors into a WatchOnlyWallet".to_string()).into())
+    }
+    
     fn backup(&self, _path: &str, _password: &str) -> AnyaResult<()> {
         // Simplified implementation
         Err(BitcoinError::Wallet("Not implemented".to_string()).into())
@@ -0,0 +1,535 @@
+//! BIP129 Bitcoin Secure Multisig Setup
+//!
+//! 1. The coordinator picks `m`-of-`n` and a [`Token`] and hands the token
+//!    to every signer.
+//! 2. Each signer answers with a [`KeyRecord`]: its key with origin and a
+//!    description, signed with that key so a tampered or mistyped record is
+//!    caught.
+//! 3. Once `n` records are verified the coordinator distributes a
+//!    [`DescriptorRecord`] with the `wsh(sortedmulti)` descriptor and its
+//!    first address, which each signer checks against its own key.
+//!
+//! With a non-zero token, records travel encrypted: AES-256-CTR under a key
+//! derived from the token with PBKDF2-SHA512, authenticated by an
+//! HMAC-SHA256 over the plaintext whose first 16 bytes are the IV.
+
+use std::fmt;
+use std::str::FromStr;
+
+use aes::Aes256;
+use bitcoin::bip32::{DerivationPath, KeySource, Xpriv, Xpub};
+use bitcoin::hashes::{sha256, Hash};
+use bitcoin::secp256k1::{Message, Secp256k1, Signing, Verification};
+use bitcoin::sign_message::{signed_msg_hash, MessageSignature};
+use bitcoin::{Network, NetworkKind};
+use base64::engine::general_purpose::STANDARD as BASE64;
+use base64::Engine;
+use ctr::cipher::{KeyIvInit, StreamCipher};
+use hmac::{Hmac, Mac};
+use rand::RngCore;
+use sha2::{Sha256, Sha512};
+
+use crate::bitcoin::error::{BitcoinError, BitcoinResult};
+use crate::bitcoin::wallet::descriptor::{DescriptorXpub, Keychain, WalletDescriptor, Wildcard};
+
+/// First line of every record
+pub const BSMS_VERSION: &str = "BSMS 1.0";
+
+/// Path restrictions of a descriptor template ending in `/**`
+const PATH_RESTRICTIONS: &str = "/0/*,/1/*";
+
+/// PBKDF2 password; the token is the salt
+const KDF_PASSWORD: &[u8] = b"No SPOF";
+const KDF_ROUNDS: u32 = 2048;
+
+/// Longest key description
+const MAX_DESCRIPTION: usize = 80;
+
+/// Most signers in a `wsh(sortedmulti)`
+const MAX_SIGNERS: usize = 20;
+
+type Aes256Ctr = ctr::Ctr128BE<Aes256>;
+
+fn error(message: impl Into<String>) -> BitcoinError {
+    BitcoinError::Wallet(message.into())
+}
+
+/// Size of the setup token
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenStrength {
+    /// Token `00`, records are sent in the clear
+    None,
+    /// 64-bit token
+    Standard,
+    /// 128-bit token
+    Extended,
+}
+
+/// Setup token shared out of band between the coordinator and the signers
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Token(Vec<u8>);
+
+impl Token {
+    pub fn generate(strength: TokenStrength) -> Self {
+        let mut bytes = vec![0u8; match strength {
+            TokenStrength::None => 0,
+            TokenStrength::Standard => 8,
+            TokenStrength::Extended => 16,
+        }];
+        rand::thread_rng().fill_bytes(&mut bytes);
+        Token(bytes)
+    }
+
+    /// Whether records under this token are encrypted
+    pub fn is_encrypted(&self) -> bool {
+        !self.0.is_empty()
+    }
+
+    fn encryption_key(&self) -> [u8; 32] {
+        pbkdf2::pbkdf2_hmac_array::<Sha512, 32>(KDF_PASSWORD, &self.0, KDF_ROUNDS)
+    }
+
+    fn mac(&self, key: &[u8; 32], data: &[u8]) -> Hmac<Sha256> {
+        let mac_key = sha256::Hash::hash(key);
+        let mut mac = Hmac::<Sha256>::new_from_slice(mac_key.as_byte_array()).expect("HMAC takes any key size");
+        mac.update(self.to_string().as_bytes());
+        mac.update(data);
+        mac
+    }
+
+    /// Encrypts a record into hex `MAC || ciphertext`, or returns it as is
+    /// for token `00`
+    pub fn encrypt(&self, record: &str) -> String {
+        if !self.is_encrypted() {
+            return record.to_string();
+        }
+        let key = self.encryption_key();
+        let mac = self.mac(&key, record.as_bytes()).finalize().into_bytes();
+        let mut data = record.as_bytes().to_vec();
+        Aes256Ctr::new(&key.into(), mac[..16].into()).apply_keystream(&mut data);
+        format!("{}{}", hex::encode(mac), hex::encode(data))
+    }
+
+    /// Decrypts and authenticates a record
+    pub fn decrypt(&self, payload: &str) -> BitcoinResult<String> {
+        if !self.is_encrypted() {
+            return Ok(payload.to_string());
+        }
+        let bytes = hex::decode(payload.trim()).map_err(|_| error("Encrypted BSMS record is not hex"))?;
+        if bytes.len() < 32 {
+            return Err(error("Encrypted BSMS record is too short"));
+        }
+        let (mac, ciphertext) = bytes.split_at(32);
+        let key = self.encryption_key();
+        let mut data = ciphertext.to_vec();
+        Aes256Ctr::new(&key.into(), mac[..16].into()).apply_keystream(&mut data);
+        self.mac(&key, &data).verify_slice(mac)
+            .map_err(|_| error("BSMS record does not authenticate under this token"))?;
+        String::from_utf8(data).map_err(|_| error("BSMS record is not UTF-8"))
+    }
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.0.is_empty() {
+            write!(f, "00")
+        } else {
+            write!(f, "{}", hex::encode(&self.0))
+        }
+    }
+}
+
+impl FromStr for Token {
+    type Err = BitcoinError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.len() {
+            2 if s == "00" => Ok(Token(Vec::new())),
+            16 | 32 => hex::decode(s).map(Token).map_err(|_| error(format!("Invalid BSMS token: {}", s))),
+            _ => Err(error(format!("BSMS token must be 00, 64 or 128 bits: {}", s))),
+        }
+    }
+}
+
+/// `[origin]xpub` as it appears in a key record
+fn record_key(xpub: &Xpub, origin: &KeySource) -> DescriptorXpub {
+    DescriptorXpub {
+        origin: Some(origin.clone()),
+        xpub: *xpub,
+        path: DerivationPath::master(),
+        wildcard: Wildcard::None,
+    }
+}
+
+fn signed_lines(token: &Token, key: &DescriptorXpub, description: &str) -> String {
+    format!("{}\n{}\n{}\n{}", BSMS_VERSION, token, key, description)
+}
+
+/// Round 1: a signer's key, signed with that key
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct KeyRecord {
+    pub token: Token,
+    pub xpub: Xpub,
+    pub origin: KeySource,
+    pub description: String,
+    pub signature: MessageSignature,
+}
+
+impl KeyRecord {
+    /// Record for the account key at `path` from `master`
+    pub fn signed<C: Signing>(
+        secp: &Secp256k1<C>,
+        token: Token,
+        master: &Xpriv,
+        path: &DerivationPath,
+        description: &str,
+    ) -> BitcoinResult<Self> {
+        if description.len() > MAX_DESCRIPTION || description.contains('\n') {
+            return Err(error(format!("Key description must be one line of at most {} characters", MAX_DESCRIPTION)));
+        }
+        let account = master.derive_priv(secp, path)
+            .map_err(|e| error(format!("Cannot derive {}: {}", path, e)))?;
+        let xpub = Xpub::from_priv(secp, &account);
+        let origin = (master.fingerprint(secp), path.clone());
+        let hash = signed_msg_hash(&signed_lines(&token, &record_key(&xpub, &origin), description));
+        let signature = secp.sign_ecdsa_recoverable(&Message::from_digest(hash.to_byte_array()), &account.private_key);
+        Ok(KeyRecord {
+            token,
+            xpub,
+            origin,
+            description: description.to_string(),
+            signature: MessageSignature::new(signature, true),
+        })
+    }
+
+    /// The signed part: every line but the signature
+    fn message(&self) -> String {
+        signed_lines(&self.token, &record_key(&self.xpub, &self.origin), &self.description)
+    }
+
+    /// Checks the signature was made by the record's own key
+    pub fn verify<C: Verification>(&self, secp: &Secp256k1<C>) -> BitcoinResult<()> {
+        let signer = self.signature.recover_pubkey(secp, signed_msg_hash(&self.message()))
+            .map_err(|e| error(format!("Invalid key record signature: {}", e)))?;
+        if !self.signature.compressed || signer.inner != self.xpub.public_key {
+            return Err(error(format!("Key record for {} is not signed by its key", self.xpub.fingerprint())));
+        }
+        Ok(())
+    }
+
+    /// The key as used in the multisig descriptor, on both keychains
+    pub fn descriptor_key(&self) -> DescriptorXpub {
+        DescriptorXpub { wildcard: Wildcard::Multipath(0, 1), ..record_key(&self.xpub, &self.origin) }
+    }
+}
+
+impl fmt::Display for KeyRecord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}\n{}", self.message(), BASE64.encode(self.signature.serialize()))
+    }
+}
+
+impl FromStr for KeyRecord {
+    type Err = BitcoinError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let lines: Vec<&str> = s.trim_end().lines().collect();
+        let [version, token, key, description, signature] = lines[..] else {
+            return Err(error(format!("Key record has {} lines instead of 5", lines.len())));
+        };
+        if version != BSMS_VERSION {
+            return Err(error(format!("Unsupported BSMS version: {}", version)));
+        }
+
+        let key: DescriptorXpub = key.parse()?;
+        let origin = key.origin
+            .filter(|_| key.wildcard == Wildcard::None && key.path.is_master())
+            .ok_or_else(|| error("Key record needs a key with its origin and no derivation"))?;
+        let signature = BASE64.decode(signature)
+            .ok()
+            .and_then(|bytes| MessageSignature::from_slice(&bytes).ok())
+            .ok_or_else(|| error("Invalid key record signature encoding"))?;
+
+        Ok(KeyRecord {
+            token: token.parse()?,
+            xpub: key.xpub,
+            origin,
+            description: description.to_string(),
+            signature,
+        })
+    }
+}
+
+/// Round 2: the multisig descriptor and its first address
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DescriptorRecord {
+    pub descriptor: WalletDescriptor,
+    pub first_address: String,
+}
+
+impl DescriptorRecord {
+    pub fn new<C: Verification>(secp: &Secp256k1<C>, descriptor: WalletDescriptor, network: Network) -> BitcoinResult<Self> {
+        let first_address = descriptor.address(secp, network, Keychain::External, 0)?.to_string();
+        Ok(DescriptorRecord { descriptor, first_address })
+    }
+
+    /// Signer-side check: the descriptor includes `own` and its first address
+    /// is the one the coordinator announced
+    pub fn verify<C: Verification>(&self, secp: &Secp256k1<C>, network: Network, own: &Xpub) -> BitcoinResult<()> {
+        if !self.descriptor.keys().iter().any(|key| key.xpub == *own) {
+            return Err(error(format!("Descriptor does not include key {}", own.fingerprint())));
+        }
+        let address = self.descriptor.address(secp, network, Keychain::External, 0)?;
+        if address.to_string() != self.first_address {
+            return Err(error(format!("First address {} does not match the descriptor's {}", self.first_address, address)));
+        }
+        Ok(())
+    }
+}
+
+impl fmt::Display for DescriptorRecord {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let descriptor = self.descriptor.to_string();
+        let body = descriptor.split('#').next().unwrap_or_default();
+        if self.descriptor.keys().iter().all(|key| key.wildcard == Wildcard::Multipath(0, 1)) {
+            write!(f, "{}\n{}\n{}\n{}", BSMS_VERSION, body.replace("/<0;1>/*", "/**"), PATH_RESTRICTIONS, self.first_address)
+        } else {
+            write!(f, "{}\n{}\nNo path restrictions\n{}", BSMS_VERSION, descriptor, self.first_address)
+        }
+    }
+}
+
+impl FromStr for DescriptorRecord {
+    type Err = BitcoinError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let lines: Vec<&str> = s.trim_end().lines().collect();
+        let [version, descriptor, restrictions, first_address] = lines[..] else {
+            return Err(error(format!("Descriptor record has {} lines instead of 4", lines.len())));
+        };
+        if version != BSMS_VERSION {
+            return Err(error(format!("Unsupported BSMS version: {}", version)));
+        }
+        let descriptor = match restrictions {
+            PATH_RESTRICTIONS => descriptor.replace("/**", "/<0;1>/*").parse()?,
+            "No path restrictions" => descriptor.parse()?,
+            _ => return Err(error(format!("Unsupported path restrictions: {}", restrictions))),
+        };
+        Ok(DescriptorRecord { descriptor, first_address: first_address.to_string() })
+    }
+}
+
+/// Coordinator side of a setup
+#[derive(Debug, Clone)]
+pub struct BsmsSetup {
+    threshold: usize,
+    signers: usize,
+    network: Network,
+    token: Token,
+    records: Vec<KeyRecord>,
+}
+
+impl BsmsSetup {
+    pub fn new(threshold: usize, signers: usize, network: Network, strength: TokenStrength) -> BitcoinResult<Self> {
+        if threshold == 0 || threshold > signers || signers > MAX_SIGNERS {
+            return Err(error(format!("Invalid {}-of-{} multisig", threshold, signers)));
+        }
+        Ok(BsmsSetup { threshold, signers, network, token: Token::generate(strength), records: Vec::new() })
+    }
+
+    pub fn token(&self) -> &Token {
+        &self.token
+    }
+
+    pub fn threshold(&self) -> usize {
+        self.threshold
+    }
+
+    pub fn signers(&self) -> usize {
+        self.signers
+    }
+
+    /// Verified key records so far
+    pub fn records(&self) -> &[KeyRecord] {
+        &self.records
+    }
+
+    pub fn is_complete(&self) -> bool {
+        self.records.len() == self.signers
+    }
+
+    /// Decrypts, parses and verifies a signer's key record
+    pub fn add_key_record<C: Verification>(&mut self, secp: &Secp256k1<C>, payload: &str) -> BitcoinResult<&KeyRecord> {
+        if self.is_complete() {
+            return Err(error(format!("All {} keys have been received", self.signers)));
+        }
+        let record: KeyRecord = self.token.decrypt(payload)?.parse()?;
+        if record.token != self.token {
+            return Err(error("Key record is for another setup"));
+        }
+        record.verify(secp)?;
+        if record.xpub.network != NetworkKind::from(self.network) {
+            return Err(error(format!("Key {} is not for {}", record.xpub.fingerprint(), self.network)));
+        }
+        if self.records.iter().any(|existing| existing.xpub == record.xpub) {
+            return Err(error(format!("Key {} was already received", record.xpub.fingerprint())));
+        }
+        self.records.push(record);
+        Ok(self.records.last().expect("just pushed"))
+    }
+
+    /// `wsh(sortedmulti)` over every received key
+    pub fn descriptor(&self) -> BitcoinResult<WalletDescriptor> {
+        if !self.is_complete() {
+            return Err(error(format!("Only {} of {} keys received", self.records.len(), self.signers)));
+        }
+        Ok(WalletDescriptor::Wsh {
+            threshold: self.threshold,
+            keys: self.records.iter().map(KeyRecord::descriptor_key).collect(),
+            sorted: true,
+        })
+    }
+
+    /// The descriptor record for the signers, encrypted under the token
+    pub fn descriptor_record<C: Verification>(&self, secp: &Secp256k1<C>) -> BitcoinResult<String> {
+        let record = DescriptorRecord::new(secp, self.descriptor()?, self.network)?;
+        Ok(self.token.encrypt(&record.to_string()))
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+
+    /// Master key of a signer and its `m/48'/1'/0'/2'` key record
+    pub(crate) fn signer(seed: u8, token: &Token) -> (Xpriv, KeyRecord) {
+        let secp = Secp256k1::new();
+        let master = Xpriv::new_master(Network::Regtest, &[seed; 32]).unwrap();
+        let path = DerivationPath::from_str("m/48'/1'/0'/2'").unwrap();
+        let record = KeyRecord::signed(&secp, token.clone(), &master, &path, &format!("Signer {}", seed)).unwrap();
+        (master, record)
+    }
+
+    #[test]
+    fn test_key_record_roundtrip() {
+        let secp = Secp256k1::new();
+        let (_, record) = signer(1, &Token::from_str("00").unwrap());
+        let text = record.to_string();
+        assert_eq!(text.lines().count(), 5);
+        assert!(text.starts_with("BSMS 1.0\n00\n["));
+
+        let parsed: KeyRecord = text.parse().unwrap();
+        assert_eq!(parsed, record);
+        parsed.verify(&secp).unwrap();
+
+        // A changed description no longer matches the signature
+        let tampered: KeyRecord = text.replace("Signer 1", "Signer 2").parse().unwrap();
+        assert!(tampered.verify(&secp).is_err());
+    }
+
+    #[test]
+    fn test_token_encryption() {
+        let token = Token::generate(TokenStrength::Standard);
+        assert_eq!(token.to_string().len(), 16);
+        let payload = token.encrypt("BSMS 1.0\nrecord");
+        assert_ne!(payload, "BSMS 1.0\nrecord");
+        assert_eq!(token.decrypt(&payload).unwrap(), "BSMS 1.0\nrecord");
+
+        let other = Token::generate(TokenStrength::Extended);
+        assert!(other.decrypt(&payload).is_err());
+        let mut flipped = payload.clone();
+        flipped.replace_range(70..71, if &payload[70..71] == "0" { "1" } else { "0" });
+        assert!(token.decrypt(&flipped).is_err());
+
+        assert_eq!(Token::from_str("00").unwrap().encrypt("clear"), "clear");
+        assert!(Token::from_str("0102").is_err());
+    }
+
+    /// Key records of the BIP32 test vector 1 seed at `m/48'/1'/0'/2'` under
+    /// each token size, with the record-encryption key and payload. The
+    /// signatures and payloads were checked against a separate implementation
+    /// of the BIP129 signing and encryption steps.
+    #[test]
+    fn test_bip129_vectors() {
+        const KEY: &str = "[3442193e/48'/1'/0'/2']tpubDEC8p4skY4i7mNjxt9yF3u7my5T6KtGLcvEDcH5rKA6XVtJN4JV3SsHWoePTqsHNh47WBkQ79r77KsUYc2PAmaqqasBJMvidbxiupZpJexg";
+        let vectors = [
+            (
+                "00",
+                "IJ/5zVe6Sk9CzfWJGFjXRmNQrUMkBI3gUmVCbL9HhXmhX7XbgxgELLDz9M40o+10nJGo0f2Av0hk9YgyygKZh1I=",
+                None,
+            ),
+            (
+                "a54044308ceac9b7",
+                "ICF4Qyw8UDWb7+qJq2lDkKTIuMAQDOCDEKftMPySTGVKSN25MiFE+w9qcPw9yVZfwb6yZlAafot1hkw4mv8Od/I=",
+                Some((
+                    "7673ffd9efd70336a5442eda0b31457f7b6cdf7b42fe17f274434df55efa9839",
+                    "c1f61aec82f3e680fb0e960db0126756b3400810001f8c44f587e773bf3ef9709cfea86f2e28f3fd0e020916360a3e910b49042e27fad9f56aff6f3dcef0774e9190ec0bee0bf155fec324ce60c8cd9ec4d5411ce6b45b9856c9b50128e5a1f1de9d3058c448c78b2d211bb23cda0a7af1853e9b59d7cb761afc7ad61c49d1b29f97d990592b177c053193639cf9de357f7fcb5588d688b2e299bd5092ad21f01333106e535958b045c6939e9f02516aa255a172681f472135fd224d60fba3239ae6f66c8210ea72e62e800cd27ed43ddfd79f171cfe8ffbd1dc979387ba2e12719cb82bde8e6b1f2bffdee3c9e2dcf5e460247dd82bb293a03e84e24db74f4a181cf61a3b1b66b931813d7ca4740d46edb3d6724c5e60c84e96e2ed799df1ab86e6bd761a35",
+                )),
+            ),
+            (
+                "06bee1ed8f5b1b4fa6f7cda61e0aee20",
+                "IGvWDGo+tigygrqJ3lVoY9xCDtnllXAtqdh3vOg0WJ+vALXbQ5MH2pXd+nVj9XmeKEhsp9x3oIi7LGgvfuEdq5Y=",
+                Some((
+                    "29ff689631b1fc34e825df982a86c824d6a8dd9da534e7e16593061b30fb650e",
+                    "719e323ec127d3282405976e8a71b7217cdcb390f06c738dabaf8879a4bfe55bb62a25c76b8cc5a033965a8344065ebc9ef9711aba799c91adef35a5c15d595d62f75450a3ba1f32fad80afcd4fc874087e3847239aeb1caa6ddbfbd3e4b04f1c3ff6664df8319fd451dd3f29f6dbd0ab2b17372b0f82427085044b8c9c9b7b40309615aa55309840abad0268976098ec6288d26ac42e3d9484ba5511a50acff948034b805856341a3101e8f979996932ba6f1e1e9aba8b5e9ba7e215f0574f9d5d32f21653ebb37d54fbcc5772f7c1cb37d345bb3151fa965e628e46a0b1c3a3119837e107c605015d2bd23d6aad1887f45e1f2f65a41254841c621408714a50761beb4948a43f861d72fdc7142d49f43ebfa7b418108418d5285396e17cb061cb946d2810c978f806010fd9e949208a30055717fcd",
+                )),
+            ),
+        ];
+
+        let secp = Secp256k1::new();
+        let master = Xpriv::new_master(Network::Testnet, &hex::decode("000102030405060708090a0b0c0d0e0f").unwrap()).unwrap();
+        let path = DerivationPath::from_str("m/48'/1'/0'/2'").unwrap();
+        for (token, signature, encrypted) in vectors {
+            let text = format!("BSMS 1.0\n{}\n{}\nSigner 1 key\n{}", token, KEY, signature);
+            let token = Token::from_str(token).unwrap();
+
+            let record = KeyRecord::signed(&secp, token.clone(), &master, &path, "Signer 1 key").unwrap();
+            assert_eq!(record.to_string(), text);
+            let parsed: KeyRecord = text.parse().unwrap();
+            parsed.verify(&secp).unwrap();
+            assert_eq!(parsed, record);
+
+            match encrypted {
+                None => assert_eq!(token.encrypt(&text), text),
+                Some((key, payload)) => {
+                    assert_eq!(hex::encode(token.encryption_key()), key);
+                    assert_eq!(token.encrypt(&text), payload);
+                    assert_eq!(token.decrypt(payload).unwrap(), text);
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_setup_distributes_descriptor() {
+        let secp = Secp256k1::new();
+        let mut setup = BsmsSetup::new(2, 3, Network::Regtest, TokenStrength::Standard).unwrap();
+        let token = setup.token().clone();
+        let signers: Vec<_> = (1..=3).map(|seed| signer(seed, &token)).collect();
+
+        assert!(setup.descriptor().is_err());
+        for (_, record) in &signers {
+            setup.add_key_record(&secp, &token.encrypt(&record.to_string())).unwrap();
+        }
+        assert!(setup.is_complete());
+
+        // Records for another setup and keys past the last are refused
+        let mut other = BsmsSetup::new(2, 3, Network::Regtest, TokenStrength::Standard).unwrap();
+        let foreign = other.token().encrypt(&signers[0].1.to_string());
+        assert!(other.add_key_record(&secp, &foreign).is_err());
+        assert!(setup.add_key_record(&secp, &token.encrypt(&signers[0].1.to_string())).is_err());
+
+        let payload = setup.descriptor_record(&secp).unwrap();
+        let record: DescriptorRecord = token.decrypt(&payload).unwrap().parse().unwrap();
+        assert!(token.decrypt(&payload).unwrap().contains("/**"));
+        assert_eq!(record.descriptor, setup.descriptor().unwrap());
+        for (_, key) in &signers {
+            record.verify(&secp, Network::Regtest, &key.xpub).unwrap();
+        }
+        let (_, outsider) = signer(5, &token);
+        assert!(record.verify(&secp, Network::Regtest, &outsider.xpub).is_err());
+
+        let wrong = DescriptorRecord { first_address: record.descriptor.address(&secp, Network::Regtest, Keychain::External, 1).unwrap().to_string(), ..record };
+        assert!(wrong.verify(&secp, Network::Regtest, &signers[0].1.xpub).is_err());
+    }
+}
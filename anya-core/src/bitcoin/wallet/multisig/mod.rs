@@ -0,0 +1,274 @@
+//! Multisig wallet coordination
+//!
+//! The [`MultisigCoordinator`] runs the whole life of `m`-of-`n` P2WSH
+//! wallets whose keys live with separate signers:
+//!
+//! - [`bsms`]: BIP129 setup, where the coordinator gathers and verifies
+//!   signed key records and distributes the resulting descriptor;
+//! - [`session`]: spending sessions, where unsigned PSBTs go out to the
+//!   cosigners and the signed ones are merged until enough signatures are in
+//!   to finalize and broadcast.
+//!
+//! Once set up, each multisig is followed by a [`WatchOnlyWallet`], which
+//! tracks its coins and builds the PSBTs the sessions start from.
+
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use bitcoin::psbt::Psbt;
+use bitcoin::secp256k1::{All, Secp256k1};
+use bitcoin::{Network, Txid};
+
+use crate::bitcoin::error::{BitcoinError, BitcoinResult};
+use super::transactions::TxOptions;
+use super::watch_only::{ChainSource, WatchOnlyWallet};
+
+pub mod bsms;
+pub mod session;
+
+pub use bsms::{BsmsSetup, DescriptorRecord, KeyRecord, Token, TokenStrength};
+pub use session::{SessionStatus, SpendingSession};
+
+fn error(message: impl Into<String>) -> BitcoinError {
+    BitcoinError::Wallet(message.into())
+}
+
+/// Progress of a BSMS setup
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SetupStatus {
+    pub threshold: usize,
+    pub signers: usize,
+    /// Descriptions of the verified key records
+    pub received: Vec<String>,
+    pub complete: bool,
+}
+
+impl From<&BsmsSetup> for SetupStatus {
+    fn from(setup: &BsmsSetup) -> Self {
+        SetupStatus {
+            threshold: setup.threshold(),
+            signers: setup.signers(),
+            received: setup.records().iter().map(|record| record.description.clone()).collect(),
+            complete: setup.is_complete(),
+        }
+    }
+}
+
+struct Session {
+    wallet: String,
+    session: SpendingSession,
+}
+
+/// Sets up multisig wallets and coordinates their spends
+pub struct MultisigCoordinator {
+    network: Network,
+    chain: Option<Arc<dyn ChainSource>>,
+    secp: Secp256k1<All>,
+    setups: Mutex<HashMap<String, BsmsSetup>>,
+    wallets: Mutex<HashMap<String, Arc<WatchOnlyWallet>>>,
+    sessions: Mutex<HashMap<String, Session>>,
+}
+
+impl MultisigCoordinator {
+    pub fn new(network: Network, chain: Option<Arc<dyn ChainSource>>) -> Self {
+        Self {
+            network,
+            chain,
+            secp: Secp256k1::new(),
+            setups: Mutex::new(HashMap::new()),
+            wallets: Mutex::new(HashMap::new()),
+            sessions: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Starts setting up the `threshold`-of-`signers` wallet `name`,
+    /// returning the token to hand to every signer
+    pub fn start_setup(&self, name: &str, threshold: usize, signers: usize, strength: TokenStrength) -> BitcoinResult<Token> {
+        let mut setups = self.setups.lock().unwrap();
+        if setups.contains_key(name) || self.wallets.lock().unwrap().contains_key(name) {
+            return Err(error(format!("Multisig wallet {} already exists", name)));
+        }
+        let setup = BsmsSetup::new(threshold, signers, self.network, strength)?;
+        let token = setup.token().clone();
+        setups.insert(name.to_string(), setup);
+        Ok(token)
+    }
+
+    /// Adds a signer's key record, encrypted under the setup token
+    pub fn submit_key_record(&self, name: &str, payload: &str) -> BitcoinResult<SetupStatus> {
+        let mut setups = self.setups.lock().unwrap();
+        let setup = setups.get_mut(name).ok_or_else(|| error(format!("No setup for {}", name)))?;
+        setup.add_key_record(&self.secp, payload)?;
+        Ok(SetupStatus::from(&*setup))
+    }
+
+    pub fn setup_status(&self, name: &str) -> BitcoinResult<SetupStatus> {
+        self.setups.lock().unwrap().get(name)
+            .map(SetupStatus::from)
+            .ok_or_else(|| error(format!("No setup for {}", name)))
+    }
+
+    /// The descriptor record to distribute once every key is in
+    ///
+    /// The first call also starts following the multisig with a watch-only
+    /// wallet, so it can receive and be spent from. No address of the
+    /// multisig exists before then, so chain sources look no further back.
+    pub fn descriptor_record(&self, name: &str) -> BitcoinResult<String> {
+        let setups = self.setups.lock().unwrap();
+        let setup = setups.get(name).ok_or_else(|| error(format!("No setup for {}", name)))?;
+        let record = setup.descriptor_record(&self.secp)?;
+
+        let mut wallets = self.wallets.lock().unwrap();
+        if !wallets.contains_key(name) {
+            // None of the addresses existed before the descriptor
+            let birthday = SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |elapsed| elapsed.as_secs());
+            let wallet = WatchOnlyWallet::new(name, self.network, self.chain.clone()).with_birthday(birthday);
+            wallet.add_descriptor(setup.descriptor()?)?;
+            wallets.insert(name.to_string(), Arc::new(wallet));
+        }
+        Ok(record)
+    }
+
+    /// Watch-only wallet of a multisig that has been set up
+    pub fn wallet(&self, name: &str) -> BitcoinResult<Arc<WatchOnlyWallet>> {
+        self.wallets.lock().unwrap().get(name)
+            .cloned()
+            .ok_or_else(|| error(format!("Multisig wallet {} is not set up", name)))
+    }
+
+    /// Starts a spend, returning the session id and the unsigned PSBT to
+    /// send to the cosigners
+    pub fn start_spend(
+        &self,
+        name: &str,
+        outputs: &[(String, u64)],
+        fee_rate: f64,
+        options: &TxOptions,
+    ) -> BitcoinResult<(String, Psbt)> {
+        let wallet = self.wallet(name)?;
+        let descriptor = wallet.descriptors().into_iter().next()
+            .ok_or_else(|| error(format!("Multisig wallet {} has no descriptor", name)))?;
+        let psbt = wallet.create_psbt(outputs, fee_rate, options)?;
+        let session = SpendingSession::new(&descriptor, psbt.clone())?;
+
+        let id = hex::encode(rand::random::<[u8; 16]>());
+        self.sessions.lock().unwrap().insert(id.clone(), Session { wallet: name.to_string(), session });
+        Ok((id, psbt))
+    }
+
+    /// Merges a PSBT signed by one or more cosigners
+    pub fn submit_psbt(&self, id: &str, psbt: Psbt) -> BitcoinResult<SessionStatus> {
+        let mut sessions = self.sessions.lock().unwrap();
+        let session = sessions.get_mut(id).ok_or_else(|| error(format!("No spending session {}", id)))?;
+        session.session.combine(&self.secp, psbt)
+    }
+
+    pub fn session_status(&self, id: &str) -> BitcoinResult<SessionStatus> {
+        self.sessions.lock().unwrap().get(id)
+            .map(|session| session.session.status())
+            .ok_or_else(|| error(format!("No spending session {}", id)))
+    }
+
+    /// The PSBT of a session with every signature merged so far
+    pub fn session_psbt(&self, id: &str) -> BitcoinResult<Psbt> {
+        self.sessions.lock().unwrap().get(id)
+            .map(|session| session.session.psbt().clone())
+            .ok_or_else(|| error(format!("No spending session {}", id)))
+    }
+
+    /// Finalizes a session and broadcasts its transaction, closing the
+    /// session
+    pub fn broadcast(&self, id: &str) -> BitcoinResult<Txid> {
+        let mut sessions = self.sessions.lock().unwrap();
+        let session = sessions.get(id).ok_or_else(|| error(format!("No spending session {}", id)))?;
+        let wallet = self.wallet(&session.wallet)?;
+        // Finalize a copy so a failed broadcast leaves the session open
+        let tx = session.session.clone().finalize()?;
+        let txid = wallet.broadcast(&tx)?;
+        sessions.remove(id);
+        Ok(txid)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::bsms::tests::signer;
+    use crate::bitcoin::wallet::descriptor::Keychain;
+    use bitcoin::{absolute, transaction, Amount, OutPoint, Script, ScriptBuf, Transaction, TxIn, TxOut};
+    use bitcoin::hashes::Hash;
+    use crate::bitcoin::wallet::watch_only::ScriptHistory;
+
+    #[derive(Default)]
+    struct MockChain {
+        history: Mutex<HashMap<ScriptBuf, ScriptHistory>>,
+        broadcast: Mutex<Vec<Transaction>>,
+    }
+
+    impl ChainSource for MockChain {
+        fn script_history(&self, script_pubkey: &Script) -> BitcoinResult<ScriptHistory> {
+            Ok(self.history.lock().unwrap().get(script_pubkey).cloned().unwrap_or_default())
+        }
+
+        fn broadcast(&self, tx: &Transaction) -> BitcoinResult<Txid> {
+            self.broadcast.lock().unwrap().push(tx.clone());
+            Ok(tx.compute_txid())
+        }
+    }
+
+    #[test]
+    fn test_setup_and_spend() {
+        let chain = Arc::new(MockChain::default());
+        let coordinator = MultisigCoordinator::new(Network::Regtest, Some(chain.clone() as Arc<dyn ChainSource>));
+        let token = coordinator.start_setup("vault", 2, 3, TokenStrength::Extended).unwrap();
+        assert!(coordinator.start_setup("vault", 1, 1, TokenStrength::None).is_err());
+
+        let signers: Vec<_> = (1..=3).map(|seed| signer(seed, &token)).collect();
+        for (_, record) in &signers {
+            coordinator.submit_key_record("vault", &token.encrypt(&record.to_string())).unwrap();
+        }
+        assert!(coordinator.setup_status("vault").unwrap().complete);
+
+        // Every signer checks the distributed descriptor against its key
+        let secp = Secp256k1::new();
+        let record: DescriptorRecord = token.decrypt(&coordinator.descriptor_record("vault").unwrap()).unwrap().parse().unwrap();
+        for (_, key) in &signers {
+            record.verify(&secp, Network::Regtest, &key.xpub).unwrap();
+        }
+
+        // Fund the first receive address
+        let wallet = coordinator.wallet("vault").unwrap();
+        let receive = record.descriptor.derive(&secp, Keychain::External, 0).unwrap().script_pubkey;
+        let funding = Transaction {
+            version: transaction::Version::TWO,
+            lock_time: absolute::LockTime::ZERO,
+            input: vec![TxIn { previous_output: OutPoint::new(Txid::from_byte_array([9; 32]), 0), ..Default::default() }],
+            output: vec![TxOut { value: Amount::from_sat(300_000), script_pubkey: receive.clone() }],
+        };
+        chain.history.lock().unwrap().insert(receive, vec![(funding, Some(100))]);
+        wallet.scan().unwrap();
+
+        let recipient = record.descriptor.address(&secp, Network::Regtest, Keychain::External, 7).unwrap();
+        let (id, psbt) = coordinator.start_spend("vault", &[(recipient.to_string(), 100_000)], 3.0, &TxOptions::default()).unwrap();
+        assert_eq!(psbt.inputs[0].bip32_derivation.len(), 3);
+
+        // One cosigner signs, then passes the merged PSBT to the next
+        let sign = |master: &bitcoin::bip32::Xpriv, psbt: &Psbt| {
+            let mut psbt = psbt.clone();
+            psbt.sign(master, &secp).unwrap();
+            psbt
+        };
+        coordinator.submit_psbt(&id, sign(&signers[1].0, &psbt)).unwrap();
+        assert!(coordinator.broadcast(&id).is_err());
+        let status = coordinator.submit_psbt(&id, sign(&signers[0].0, &coordinator.session_psbt(&id).unwrap())).unwrap();
+        assert!(status.ready);
+        assert_eq!(status.pending, vec![signers[2].1.origin.0]);
+
+        let txid = coordinator.broadcast(&id).unwrap();
+        let broadcast = chain.broadcast.lock().unwrap();
+        assert_eq!(broadcast[0].compute_txid(), txid);
+        assert_eq!(broadcast[0].input[0].witness.len(), 4);
+        assert!(coordinator.session_status(&id).is_err());
+    }
+}
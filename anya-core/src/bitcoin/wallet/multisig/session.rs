@@ -0,0 +1,294 @@
+//! Multisig spending sessions
+//!
+//! A [`SpendingSession`] holds the PSBT of one spend from a
+//! `wsh(multi)`/`wsh(sortedmulti)` wallet while cosigners sign it in turn or
+//! in parallel. Every PSBT handed back is merged in, after its signatures
+//! are checked against the input's sighash, and the session reports which
+//! cosigners have signed. With `m` signatures on every input it finalizes
+//! the witnesses and extracts the transaction.
+
+use std::collections::BTreeSet;
+
+use bitcoin::bip32::Fingerprint;
+use bitcoin::hashes::Hash;
+use bitcoin::psbt::Psbt;
+use bitcoin::script::Instruction;
+use bitcoin::secp256k1::{Message, PublicKey, Secp256k1, Verification};
+use bitcoin::sighash::{EcdsaSighashType, SighashCache};
+use bitcoin::{Script, Transaction, Witness};
+
+use crate::bitcoin::error::{BitcoinError, BitcoinResult};
+use crate::bitcoin::wallet::descriptor::WalletDescriptor;
+
+fn error(message: impl Into<String>) -> BitcoinError {
+    BitcoinError::Wallet(message.into())
+}
+
+/// Signing progress of a session
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SessionStatus {
+    pub threshold: usize,
+    /// Cosigners that signed every input
+    pub signed: Vec<Fingerprint>,
+    pub pending: Vec<Fingerprint>,
+    /// Whether every input has enough signatures to finalize
+    pub ready: bool,
+}
+
+/// One spend being signed by the cosigners of a multisig wallet
+#[derive(Debug, Clone)]
+pub struct SpendingSession {
+    threshold: usize,
+    /// Master fingerprints of the cosigners, in descriptor order
+    cosigners: Vec<Fingerprint>,
+    psbt: Psbt,
+}
+
+/// Keys of a multisig witness script, in script order
+fn script_keys(witness_script: &Script) -> Vec<PublicKey> {
+    witness_script.instructions()
+        .filter_map(|instruction| match instruction {
+            Ok(Instruction::PushBytes(bytes)) => PublicKey::from_slice(bytes.as_bytes()).ok(),
+            _ => None,
+        })
+        .collect()
+}
+
+impl SpendingSession {
+    /// Starts a session for an unsigned PSBT spending from `descriptor`
+    pub fn new(descriptor: &WalletDescriptor, psbt: Psbt) -> BitcoinResult<Self> {
+        let WalletDescriptor::Wsh { threshold, keys, .. } = descriptor else {
+            return Err(error("Spending sessions are for wsh multisig descriptors"));
+        };
+        if let Some(index) = psbt.inputs.iter().position(|input| input.witness_script.is_none() || input.witness_utxo.is_none()) {
+            return Err(error(format!("Input {} has no witness script or previous output", index)));
+        }
+        let cosigners = keys.iter()
+            .map(|key| key.origin.as_ref().map_or_else(|| key.xpub.fingerprint(), |(fingerprint, _)| *fingerprint))
+            .collect();
+        Ok(SpendingSession { threshold: *threshold, cosigners, psbt })
+    }
+
+    /// The PSBT with every signature merged so far, to pass to the next
+    /// cosigner
+    pub fn psbt(&self) -> &Psbt {
+        &self.psbt
+    }
+
+    /// Merges a PSBT signed by one or more cosigners
+    ///
+    /// Every new partial signature must be by a key of the input's witness
+    /// script, commit to the whole transaction with SIGHASH_ALL and be valid
+    /// for the input, otherwise nothing is merged.
+    pub fn combine<C: Verification>(&mut self, secp: &Secp256k1<C>, signed: Psbt) -> BitcoinResult<SessionStatus> {
+        self.check_open()?;
+        if signed.unsigned_tx.compute_txid() != self.psbt.unsigned_tx.compute_txid() {
+            return Err(error("PSBT is for another transaction"));
+        }
+        let mut cache = SighashCache::new(&self.psbt.unsigned_tx);
+        for (index, (input, ours)) in signed.inputs.iter().zip(&self.psbt.inputs).enumerate() {
+            let witness_script = ours.witness_script.as_ref().expect("checked when the session started");
+            let value = ours.witness_utxo.as_ref().expect("checked when the session started").value;
+            let keys = script_keys(witness_script);
+            if input.sighash_type.is_some_and(|sighash_type| sighash_type != EcdsaSighashType::All.into()) {
+                return Err(error(format!("Input {} asks for a sighash type other than SIGHASH_ALL", index)));
+            }
+            for (public_key, signature) in &input.partial_sigs {
+                if signature.sighash_type != EcdsaSighashType::All {
+                    return Err(error(format!("Input {} is signed with {} instead of SIGHASH_ALL", index, signature.sighash_type)));
+                }
+                if !keys.contains(&public_key.inner) {
+                    return Err(error(format!("Input {} is signed by a key outside the multisig", index)));
+                }
+                let sighash = cache.p2wsh_signature_hash(index, witness_script, value, signature.sighash_type)
+                    .map_err(|e| error(format!("Cannot compute the sighash of input {}: {}", index, e)))?;
+                secp.verify_ecdsa(&Message::from_digest(sighash.to_byte_array()), &signature.signature, &public_key.inner)
+                    .map_err(|_| error(format!("Invalid signature on input {}", index)))?;
+            }
+        }
+        self.psbt.combine(signed).map_err(|e| error(format!("Cannot merge PSBT: {}", e)))?;
+        Ok(self.status())
+    }
+
+    fn check_open(&self) -> BitcoinResult<()> {
+        if self.psbt.inputs.iter().any(|input| input.final_script_witness.is_some()) {
+            return Err(error("Session is already finalized"));
+        }
+        Ok(())
+    }
+
+    /// Cosigners that have signed every input
+    fn signers(&self) -> BTreeSet<Fingerprint> {
+        let signed_input = |fingerprint: &Fingerprint| {
+            self.psbt.inputs.iter().all(|input| {
+                input.bip32_derivation.iter().any(|(public_key, (origin, _))| {
+                    origin == fingerprint && input.partial_sigs.contains_key(&bitcoin::PublicKey::new(*public_key))
+                })
+            })
+        };
+        self.cosigners.iter().filter(|fingerprint| signed_input(fingerprint)).copied().collect()
+    }
+
+    pub fn status(&self) -> SessionStatus {
+        let signers = self.signers();
+        let (signed, pending) = self.cosigners.iter().partition(|fingerprint| signers.contains(*fingerprint));
+        let ready = self.psbt.inputs.iter().all(|input| input.partial_sigs.len() >= self.threshold);
+        SessionStatus { threshold: self.threshold, signed, pending, ready }
+    }
+
+    /// Builds the witnesses and extracts the signed transaction
+    ///
+    /// Signatures are taken in witness script key order, as
+    /// `OP_CHECKMULTISIG` expects, `m` of them per input.
+    pub fn finalize(&mut self) -> BitcoinResult<Transaction> {
+        self.check_open()?;
+        let mut witnesses = Vec::new();
+        for (index, input) in self.psbt.inputs.iter().enumerate() {
+            let witness_script = input.witness_script.as_ref().expect("checked when the session started");
+            let signatures: Vec<Vec<u8>> = script_keys(witness_script).into_iter()
+                .filter_map(|key| input.partial_sigs.get(&bitcoin::PublicKey::new(key)))
+                .take(self.threshold)
+                .map(|signature| signature.to_vec())
+                .collect();
+            if signatures.len() < self.threshold {
+                return Err(error(format!("Input {} has {} of {} signatures", index, signatures.len(), self.threshold)));
+            }
+
+            // The extra element consumed by the OP_CHECKMULTISIG off-by-one
+            let mut witness = Witness::new();
+            witness.push([]);
+            for signature in &signatures {
+                witness.push(signature);
+            }
+            witness.push(witness_script.as_bytes());
+            witnesses.push(witness);
+        }
+
+        for (input, witness) in self.psbt.inputs.iter_mut().zip(witnesses) {
+            input.final_script_witness = Some(witness);
+            input.partial_sigs.clear();
+            input.sighash_type = None;
+            input.witness_script = None;
+            input.bip32_derivation.clear();
+        }
+        Ok(self.psbt.clone().extract_tx_unchecked_fee_rate())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bitcoin::wallet::descriptor::Keychain;
+    use crate::bitcoin::wallet::multisig::bsms::tests::signer;
+    use crate::bitcoin::wallet::multisig::bsms::{BsmsSetup, TokenStrength};
+    use bitcoin::bip32::Xpriv;
+    use bitcoin::{absolute, transaction, Amount, Network, OutPoint, ScriptBuf, TxIn, TxOut, Txid};
+
+    /// A 2-of-3 descriptor, its signers and an unsigned PSBT spending two of
+    /// its outputs
+    fn spend() -> (WalletDescriptor, Vec<Xpriv>, Psbt) {
+        let secp = Secp256k1::new();
+        let mut setup = BsmsSetup::new(2, 3, Network::Regtest, TokenStrength::None).unwrap();
+        let token = setup.token().clone();
+        let mut masters = Vec::new();
+        for seed in 1..=3 {
+            let (master, record) = signer(seed, &token);
+            setup.add_key_record(&secp, &record.to_string()).unwrap();
+            masters.push(master);
+        }
+        let descriptor = setup.descriptor().unwrap();
+
+        let outputs: Vec<_> = [(Keychain::External, 0), (Keychain::Internal, 4)].iter()
+            .map(|(keychain, index)| descriptor.derive(&secp, *keychain, *index).unwrap())
+            .collect();
+        let tx = Transaction {
+            version: transaction::Version::TWO,
+            lock_time: absolute::LockTime::ZERO,
+            input: (0..2u8)
+                .map(|i| TxIn { previous_output: OutPoint::new(Txid::from_byte_array([i + 1; 32]), 0), ..Default::default() })
+                .collect(),
+            output: vec![TxOut { value: Amount::from_sat(90_000), script_pubkey: ScriptBuf::new_op_return([7u8; 4]) }],
+        };
+        let mut psbt = Psbt::from_unsigned_tx(tx).unwrap();
+        for (input, output) in psbt.inputs.iter_mut().zip(outputs) {
+            input.witness_utxo = Some(TxOut { value: Amount::from_sat(50_000), script_pubkey: output.script_pubkey });
+            input.witness_script = output.witness_script;
+            input.bip32_derivation = output.keys.into_iter().collect();
+        }
+        (descriptor, masters, psbt)
+    }
+
+    fn sign(master: &Xpriv, psbt: &Psbt) -> Psbt {
+        let mut psbt = psbt.clone();
+        psbt.sign(master, &Secp256k1::new()).unwrap();
+        psbt
+    }
+
+    #[test]
+    fn test_cosigners_sign_in_parallel() {
+        let secp = Secp256k1::new();
+        let (descriptor, masters, psbt) = spend();
+        let mut session = SpendingSession::new(&descriptor, psbt.clone()).unwrap();
+        let fingerprints: Vec<_> = masters.iter().map(|master| master.fingerprint(&secp)).collect();
+
+        let status = session.status();
+        assert!(!status.ready && status.signed.is_empty());
+        assert!(session.finalize().is_err());
+
+        // Both cosigners sign the original PSBT independently
+        let status = session.combine(&secp, sign(&masters[2], &psbt)).unwrap();
+        assert_eq!(status.signed, vec![fingerprints[2]]);
+        assert!(!status.ready);
+        let status = session.combine(&secp, sign(&masters[0], &psbt)).unwrap();
+        assert!(status.signed.contains(&fingerprints[0]) && status.signed.contains(&fingerprints[2]));
+        assert_eq!(status.pending, vec![fingerprints[1]]);
+        assert!(status.ready);
+
+        let tx = session.finalize().unwrap();
+        for (input, psbt_input) in tx.input.iter().zip(&psbt.inputs) {
+            let witness_script = psbt_input.witness_script.as_ref().unwrap();
+            assert_eq!(input.witness.len(), 4);
+            assert!(input.witness.nth(0).unwrap().is_empty());
+            assert_eq!(input.witness.last().unwrap(), witness_script.as_bytes());
+            assert_eq!(witness_script.to_p2wsh(), psbt_input.witness_utxo.as_ref().unwrap().script_pubkey);
+        }
+    }
+
+    #[test]
+    fn test_rejects_foreign_psbts_and_signatures() {
+        let secp = Secp256k1::new();
+        let (descriptor, masters, psbt) = spend();
+        let mut session = SpendingSession::new(&descriptor, psbt.clone()).unwrap();
+
+        // A signature moved to the other input does not verify
+        let mut signed = sign(&masters[1], &psbt);
+        let moved = signed.inputs[0].partial_sigs.clone();
+        signed.inputs[1].partial_sigs = moved;
+        assert!(session.combine(&secp, signed).is_err());
+
+        let mut other = psbt.clone();
+        other.unsigned_tx.output[0].value = Amount::from_sat(80_000);
+        assert!(session.combine(&secp, other).is_err());
+
+        // Signatures that leave outputs or inputs open to change are refused
+        for sighash_type in [EcdsaSighashType::None, EcdsaSighashType::SinglePlusAnyoneCanPay] {
+            let mut weak = psbt.clone();
+            for input in &mut weak.inputs {
+                input.sighash_type = Some(sighash_type.into());
+            }
+            let signed = sign(&masters[0], &weak);
+            assert!(signed.inputs[0].partial_sigs.values().all(|sig| sig.sighash_type == sighash_type));
+            assert!(session.combine(&secp, signed.clone()).is_err());
+
+            let mut unflagged = signed;
+            for input in &mut unflagged.inputs {
+                input.sighash_type = None;
+            }
+            assert!(session.combine(&secp, unflagged).is_err());
+        }
+        assert!(session.status().signed.is_empty());
+
+        let single: WalletDescriptor = format!("wpkh({})", descriptor.keys()[0]).parse().unwrap();
+        assert!(SpendingSession::new(&single, psbt).is_err());
+    }
+}
@@ -0,0 +1,262 @@
+//! Chain source backed by a Bitcoin Core node
+//!
+//! [`NodeChainSource`] answers [`ChainSource`] queries over RPC from a
+//! watch-only descriptor wallet of its own on the node. Bitcoin Core keeps
+//! no index by script, so every keychain a wallet follows is imported there
+//! once as a ranged descriptor, rescanning the chain from the wallet's
+//! birthday. Lookups then only read the node wallet's transactions.
+//!
+//! The node's best chain is also served as a [`HeaderSource`], so proof
+//! verifiers can follow it.
+
+use std::collections::{HashMap, HashSet};
+use std::sync::Mutex;
+
+use bitcoin::block::Header;
+use bitcoin::{BlockHash, OutPoint, Script, Transaction, Txid};
+use bitcoincore_rpc::json::{ImportDescriptors, Timestamp};
+use bitcoincore_rpc::{Auth, Client, RpcApi};
+use serde::Deserialize;
+
+use crate::bitcoin::error::{BitcoinError, BitcoinResult};
+use crate::layer2::proof::{HeaderSource, ProofError, ProofResult};
+use super::watch_only::{ChainSource, ScriptHistory};
+
+/// Name of the node wallet holding the watched descriptors
+pub const DEFAULT_NODE_WALLET: &str = "anya-watch-only";
+
+/// Scripts imported per descriptor at first, well past any gap limit
+const DESCRIPTOR_RANGE: u32 = 1000;
+
+/// Wallet transactions read per `listtransactions` call
+const LIST_PAGE: usize = 1000;
+
+fn rpc_error(error: bitcoincore_rpc::Error) -> BitcoinError {
+    BitcoinError::NetworkError(format!("Bitcoin Core RPC: {}", error))
+}
+
+fn header_error(error: bitcoincore_rpc::Error) -> ProofError {
+    ProofError::Source(format!("Bitcoin Core RPC: {}", error))
+}
+
+#[derive(Deserialize)]
+struct ListDescriptors {
+    descriptors: Vec<ListedDescriptor>,
+}
+
+#[derive(Deserialize)]
+struct ListedDescriptor {
+    desc: String,
+    range: Option<(u32, u32)>,
+}
+
+/// Chain source reading a Bitcoin Core node over RPC
+pub struct NodeChainSource {
+    client: Client,
+    /// Number of scripts imported by descriptor, in the node's canonical form
+    imported: Mutex<HashMap<String, u32>>,
+    /// Wallet transactions already fetched, which never change by txid
+    transactions: Mutex<HashMap<Txid, Transaction>>,
+}
+
+impl NodeChainSource {
+    /// Connects to the node at `url` and loads, or creates, the watch-only
+    /// wallet `wallet` there
+    pub fn new(url: &str, auth: Auth, wallet: &str) -> BitcoinResult<Self> {
+        let node = Client::new(url, auth.clone()).map_err(rpc_error)?;
+        let loaded = node.list_wallets().map_err(rpc_error)?.iter().any(|name| name == wallet);
+        if !loaded && node.load_wallet(wallet).is_err() {
+            node.create_wallet(wallet, Some(true), Some(true), None, None).map_err(rpc_error)?;
+        }
+
+        let url = format!("{}/wallet/{}", url.trim_end_matches('/'), wallet);
+        let client = Client::new(&url, auth).map_err(rpc_error)?;
+        // Descriptors imported by an earlier run need no new rescan
+        let listed: ListDescriptors = client.call("listdescriptors", &[]).map_err(rpc_error)?;
+        let imported = listed.descriptors.into_iter()
+            .map(|listed| (listed.desc, listed.range.map_or(1, |(_, end)| end + 1)))
+            .collect();
+        Ok(Self {
+            client,
+            imported: Mutex::new(imported),
+            transactions: Mutex::new(HashMap::new()),
+        })
+    }
+
+    /// Every transaction of the node wallet that is not conflicted, with its
+    /// confirmation height
+    fn wallet_transactions(&self) -> BitcoinResult<Vec<(Transaction, Option<u32>)>> {
+        let mut heights = Vec::new();
+        let mut seen = HashSet::new();
+        for skip in (0..).step_by(LIST_PAGE) {
+            let page = self.client.list_transactions(None, Some(LIST_PAGE), Some(skip), Some(true)).map_err(rpc_error)?;
+            for entry in &page {
+                if entry.info.confirmations >= 0 && seen.insert(entry.info.txid) {
+                    heights.push((entry.info.txid, entry.info.blockheight));
+                }
+            }
+            if page.len() < LIST_PAGE {
+                break;
+            }
+        }
+
+        let mut cache = self.transactions.lock().unwrap();
+        let mut transactions = Vec::with_capacity(heights.len());
+        for (txid, height) in heights {
+            let tx = match cache.get(&txid) {
+                Some(tx) => tx.clone(),
+                None => {
+                    let tx = self.client.get_transaction(&txid, Some(true)).map_err(rpc_error)?
+                        .transaction()
+                        .map_err(|e| BitcoinError::NetworkError(format!("Undecodable transaction from node: {}", e)))?;
+                    cache.insert(txid, tx.clone());
+                    tx
+                }
+            };
+            transactions.push((tx, height));
+        }
+        Ok(transactions)
+    }
+}
+
+/// Range to import a descriptor over so its first `count` scripts are
+/// watched, or `None` when the `imported` ones already cover them
+///
+/// Ranges grow by doubling, as every import rescans from the birthday.
+fn import_range(imported: Option<u32>, count: u32) -> Option<u32> {
+    match imported {
+        Some(imported) if imported >= count => None,
+        Some(imported) => Some(count.max(imported * 2)),
+        None => Some(count.max(DESCRIPTOR_RANGE)),
+    }
+}
+
+/// Transactions among `transactions` paying to or spending from `script_pubkey`
+fn script_history(transactions: Vec<(Transaction, Option<u32>)>, script_pubkey: &Script) -> ScriptHistory {
+    let mut funding_txids = HashSet::new();
+    let mut funded = HashSet::new();
+    for (tx, _) in &transactions {
+        let txid = tx.compute_txid();
+        for (vout, output) in tx.output.iter().enumerate() {
+            if output.script_pubkey.as_script() == script_pubkey {
+                funding_txids.insert(txid);
+                funded.insert(OutPoint::new(txid, vout as u32));
+            }
+        }
+    }
+
+    transactions
+        .into_iter()
+        .filter(|(tx, _)| {
+            funding_txids.contains(&tx.compute_txid())
+                || tx.input.iter().any(|input| funded.contains(&input.previous_output))
+        })
+        .collect()
+}
+
+impl ChainSource for NodeChainSource {
+    fn script_history(&self, script_pubkey: &Script) -> BitcoinResult<ScriptHistory> {
+        Ok(script_history(self.wallet_transactions()?, script_pubkey))
+    }
+
+    fn broadcast(&self, tx: &Transaction) -> BitcoinResult<Txid> {
+        self.client.send_raw_transaction(tx).map_err(rpc_error)
+    }
+
+    fn watch_descriptor(&self, descriptor: &str, count: u32, birthday: u64) -> BitcoinResult<()> {
+        let info = self.client.get_descriptor_info(descriptor).map_err(rpc_error)?;
+        let mut imported = self.imported.lock().unwrap();
+        let count = if info.is_range { count } else { 1 };
+        let Some(end) = import_range(imported.get(&info.descriptor).copied(), count) else {
+            return Ok(());
+        };
+
+        let results = self
+            .client
+            .import_descriptors(ImportDescriptors {
+                descriptor: info.descriptor.clone(),
+                timestamp: Timestamp::Time(birthday),
+                active: None,
+                range: info.is_range.then_some((0, end as usize - 1)),
+                next_index: None,
+                internal: None,
+                label: None,
+            })
+            .map_err(rpc_error)?;
+        if let Some(failed) = results.iter().find(|result| !result.success) {
+            return Err(BitcoinError::NetworkError(format!(
+                "Bitcoin Core refused to watch {}: {:?}",
+                descriptor, failed.error
+            )));
+        }
+
+        imported.insert(info.descriptor, end);
+        Ok(())
+    }
+}
+
+impl HeaderSource for NodeChainSource {
+    fn best_height(&self) -> ProofResult<u32> {
+        let count = self.client.get_block_count().map_err(header_error)?;
+        u32::try_from(count).map_err(|_| ProofError::Source(format!("Block count {} out of range", count)))
+    }
+
+    fn hash_at(&self, height: u32) -> ProofResult<BlockHash> {
+        self.client.get_block_hash(height.into()).map_err(header_error)
+    }
+
+    fn header_at(&self, height: u32) -> ProofResult<Header> {
+        self.client.get_block_header(&self.hash_at(height)?).map_err(header_error)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitcoin::{absolute, transaction, Amount, ScriptBuf, Sequence, TxIn, TxOut, Witness};
+
+    fn tx(inputs: Vec<OutPoint>, outputs: Vec<ScriptBuf>) -> Transaction {
+        Transaction {
+            version: transaction::Version::TWO,
+            lock_time: absolute::LockTime::ZERO,
+            input: inputs
+                .into_iter()
+                .map(|previous_output| TxIn {
+                    previous_output,
+                    script_sig: ScriptBuf::new(),
+                    sequence: Sequence::MAX,
+                    witness: Witness::new(),
+                })
+                .collect(),
+            output: outputs
+                .into_iter()
+                .map(|script_pubkey| TxOut { value: Amount::from_sat(10_000), script_pubkey })
+                .collect(),
+        }
+    }
+
+    #[test]
+    fn test_script_history_filter() {
+        let ours = ScriptBuf::from_bytes(vec![0x00, 0x14, 0x01]);
+        let other = ScriptBuf::from_bytes(vec![0x00, 0x14, 0x02]);
+
+        let funding = tx(vec![OutPoint::null()], vec![other.clone(), ours.clone()]);
+        let spending = tx(vec![OutPoint::new(funding.compute_txid(), 1)], vec![other.clone()]);
+        let unrelated = tx(vec![OutPoint::new(funding.compute_txid(), 0)], vec![other.clone()]);
+
+        let history = script_history(
+            vec![(funding.clone(), Some(100)), (spending.clone(), None), (unrelated, Some(101))],
+            &ours,
+        );
+        assert_eq!(history, vec![(funding, Some(100)), (spending, None)]);
+        assert!(script_history(vec![], &ours).is_empty());
+    }
+
+    #[test]
+    fn test_import_range_grows_by_doubling() {
+        assert_eq!(import_range(None, 20), Some(DESCRIPTOR_RANGE));
+        assert_eq!(import_range(Some(DESCRIPTOR_RANGE), 40), None);
+        assert_eq!(import_range(Some(DESCRIPTOR_RANGE), DESCRIPTOR_RANGE + 1), Some(2 * DESCRIPTOR_RANGE));
+        assert_eq!(import_range(Some(1), 1), None);
+    }
+}
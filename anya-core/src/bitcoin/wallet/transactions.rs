@@ -1,4 +1,5 @@
 use std::collections::HashMap;
+use bitcoin::Script;
 use crate::bitcoin::wallet::AddressType;
 
 /// Options for transaction creation
@@ -60,13 +61,16 @@ fn default() -> Self {
     }
 }
 
-/// Coin selection strategy
+/// Coin selection strategy, see [`super::coin_selection`]
 #[derive(Debug, Clone, PartialEq, Eq)]
 pub enum CoinSelectionStrategy {
     /// Branch and bound algorithm (try to find exact match)
     BranchAndBound,
     
-    /// FIFO - First in, first out
+    /// Knapsack solver (closest subset above the target, with change)
+    Knapsack,
+    
+    /// FIFO - First in, first out (oldest confirmations first)
     FIFO,
     
     /// Largest first
@@ -80,6 +84,9 @@ pub enum CoinSelectionStrategy {
     
     /// Select all available UTXOs
     SelectAll,
+    
+    /// Spend whole address clusters and avoid mixing them
+    Privacy,
 }
 
 impl Default for CoinSelectionStrategy {
@@ -132,6 +139,9 @@ pub struct Utxo {
     pub confirmation_height: Option<u32>,
 }
 
+/// Outpoint, sequence and script length of an input
+const INPUT_BASE_SIZE: usize = 36 + 4 + 1;
+
 /// Implements methods to analyze transaction-related data
 pub struct TransactionAnalyzer;
 
@@ -161,6 +171,102 @@ pub fn estimate_tx_vsize(input_count: usize, output_count: usize) -> usize {
         (header_vsize + (input_count as f64 * input_vsize) + (output_count as f64 * output_vsize)).ceil() as usize
     }
     
+    /// Weight of an input spending this script pubkey, signature included
+    ///
+    /// Signatures are counted at their maximum size (72 bytes for ECDSA, 64
+    /// for a default-sighash Schnorr key spend) so fees never fall short.
+    /// Returns `None` for script types whose satisfaction is not known from
+    /// the script pubkey alone, like P2SH and P2WSH.
+    pub fn input_weight(script_pubkey: &[u8]) -> Option<usize> {
+        const BASE: usize = INPUT_BASE_SIZE;
+        let script = Script::from_bytes(script_pubkey);
+        
+        if script.is_p2pkh() {
+            // <sig> <pubkey> in the script sig
+            Some((BASE + 1 + 72 + 1 + 33) * 4)
+        } else if script.is_p2wpkh() {
+            Some(BASE * 4 + 1 + 1 + 72 + 1 + 33)
+        } else if script.is_p2tr() {
+            Some(BASE * 4 + 1 + 1 + 64)
+        } else {
+            None
+        }
+    }
+    
+    /// Weight of an input spending a key of this address type
+    pub fn input_weight_for(address_type: AddressType) -> usize {
+        match address_type {
+            // The wallet's nested segwit addresses wrap P2WPKH: the redeem
+            // script push in the script sig, then the P2WPKH witness
+            AddressType::NestedSegWit => (INPUT_BASE_SIZE + 23) * 4 + 1 + 1 + 72 + 1 + 33,
+            _ => Self::input_weight(&Self::template_script(address_type))
+                .expect("single-key address types have a known satisfaction"),
+        }
+    }
+    
+    /// Weight of an output paying to this script pubkey
+    pub fn output_weight(script_pubkey: &[u8]) -> usize {
+        (8 + Self::compact_size_len(script_pubkey.len()) + script_pubkey.len()) * 4
+    }
+    
+    /// Weight of an output paying to an address of this type
+    pub fn output_weight_for(address_type: AddressType) -> usize {
+        Self::output_weight(&Self::template_script(address_type))
+    }
+    
+    /// Exact weight of a transaction with these input and output weights
+    ///
+    /// With `segwit`, the marker and flag are added. Legacy inputs of a segwit
+    /// transaction each need one more weight unit for their empty witness.
+    pub fn estimate_tx_weight(input_weights: &[usize], output_weights: &[usize], segwit: bool) -> usize {
+        // Version and lock time, plus the input and output counts
+        let header = (4 + 4 + Self::compact_size_len(input_weights.len()) + Self::compact_size_len(output_weights.len())) * 4;
+        // Segwit marker and flag
+        let marker = if segwit { 2 } else { 0 };
+        
+        header + marker + input_weights.iter().sum::<usize>() + output_weights.iter().sum::<usize>()
+    }
+    
+    /// Virtual size of a transaction of this weight
+    pub fn weight_to_vsize(weight: usize) -> usize {
+        weight.div_ceil(4)
+    }
+    
+    /// Fee for this weight at a fee rate in sat/vB, rounded up
+    pub fn fee_for_weight(weight: usize, fee_rate: f64) -> u64 {
+        (weight as f64 * fee_rate / 4.0).ceil() as u64
+    }
+    
+    /// Smallest output of this type that relays at the default dust relay fee
+    pub fn dust_threshold(address_type: AddressType) -> u64 {
+        // 3 sat/vB for the output and an input spending it, as in Bitcoin Core
+        match address_type {
+            AddressType::Legacy => 546,
+            AddressType::NestedSegWit => 540,
+            AddressType::SegWit => 294,
+            AddressType::Taproot => 330,
+        }
+    }
+    
+    fn compact_size_len(n: usize) -> usize {
+        match n {
+            0..=0xfc => 1,
+            0xfd..=0xffff => 3,
+            0x1_0000..=0xffff_ffff => 5,
+            _ => 9,
+        }
+    }
+    
+    /// A script pubkey of the right shape for an address type
+    fn template_script(address_type: AddressType) -> Vec<u8> {
+        match address_type {
+            AddressType::Legacy => [&[0x76, 0xa9, 0x14][..], &[0u8; 20], &[0x88, 0xac]].concat(),
+            AddressType::NestedSegWit => [&[0xa9, 0x14][..], &[0u8; 20], &[0x87]].concat(),
+            AddressType::SegWit => [&[0x00, 0x14][..], &[0u8; 20]].concat(),
+            AddressType::Taproot => [&[0x51, 0x20][..], &[0u8; 32]].concat(),
+        }
+    }
+    
     /// Calculate the fee rate from transaction size and fee
     pub fn calculate_fee_rate(tx_size: usize, fee: u64) -> f64 {
         fee as f64 / tx_size as f64
@@ -0,0 +1,1065 @@
+//! Watch-only wallets
+//!
+//! A [`WatchOnlyWallet`] follows descriptors or xpubs without ever holding a
+//! private key. It scans every keychain until a gap limit of unused
+//! addresses, keeps balance and history from a [`ChainSource`], and builds
+//! unsigned PSBTs carrying key origins, previous outputs and scripts, which
+//! is all a hardware or air-gapped signer needs to sign them.
+//!
+//! Backups list the descriptors. Descriptors cannot spend, but they reveal
+//! every address of the wallet, so given a password the backup is encrypted
+//! with AES-256-CTR and authenticated with HMAC-SHA256, both keyed from the
+//! password with Argon2id.
+
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+
+use bitcoin::bip32::{ChildNumber, DerivationPath, KeySource};
+use bitcoin::hashes::{sha256, Hash};
+use bitcoin::psbt::Psbt;
+use bitcoin::script::PushBytesBuf;
+use bitcoin::secp256k1::{All, PublicKey, Secp256k1, XOnlyPublicKey};
+use bitcoin::taproot::TapLeafHash;
+use bitcoin::{
+    absolute, transaction, Address, Amount, Network, NetworkKind, OutPoint, Script, ScriptBuf, Sequence, Transaction,
+    TxIn, TxOut, Txid, Witness,
+};
+use aes::Aes256;
+use argon2::Argon2;
+use ctr::cipher::{KeyIvInit, StreamCipher};
+use hmac::{Hmac, Mac};
+use rand::{Rng, RngCore};
+use sha2::Sha256;
+
+use crate::AnyaResult;
+use crate::bitcoin::error::{BitcoinError, BitcoinResult};
+use super::coin_selection::CoinSelector;
+use super::descriptor::{DerivedOutput, Keychain, WalletDescriptor};
+use super::transactions::{TxOptions, Utxo};
+use super::{
+    determine_chain_from_asset_id, AddressManager, AddressType, Asset, BalanceManager, KeyManager, TransactionManager,
+    UnifiedWallet, WalletType,
+};
+
+/// Unused addresses scanned past the last used one, as in BIP44
+pub const DEFAULT_GAP_LIMIT: u32 = 20;
+
+/// Transactions with their confirmation height, if confirmed
+pub type ScriptHistory = Vec<(Transaction, Option<u32>)>;
+
+/// Chain data a watch-only wallet is scanned against
+pub trait ChainSource: Send + Sync {
+    /// Transactions paying to or spending from `script_pubkey`
+    fn script_history(&self, script_pubkey: &Script) -> BitcoinResult<ScriptHistory>;
+
+    /// Broadcasts a signed transaction
+    fn broadcast(&self, tx: &Transaction) -> BitcoinResult<Txid>;
+
+    /// Follows the first `count` scripts of a single-path descriptor, none
+    /// of them used before `birthday` (Unix time)
+    ///
+    /// Called before any of those scripts is looked up, for sources that
+    /// only index what they were told to watch, like a node wallet.
+    fn watch_descriptor(&self, _descriptor: &str, _count: u32, _birthday: u64) -> BitcoinResult<()> {
+        Ok(())
+    }
+}
+
+/// Confirmed and unconfirmed balance
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct WatchOnlyBalance {
+    pub confirmed: u64,
+    pub unconfirmed: u64,
+}
+
+/// A transaction as seen by the wallet
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct HistoryEntry {
+    pub txid: Txid,
+    pub confirmation_height: Option<u32>,
+    /// Paid to the wallet's scripts
+    pub received: u64,
+    /// Spent from the wallet's outputs
+    pub sent: u64,
+    /// Fee, when every input is known
+    pub fee: Option<u64>,
+}
+
+impl HistoryEntry {
+    /// Change of the wallet balance
+    pub fn net(&self) -> i64 {
+        self.received as i64 - self.sent as i64
+    }
+}
+
+/// Outcome of a scan
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct ScanSummary {
+    /// Scripts looked up on the chain source
+    pub scripts_checked: usize,
+    /// Transactions known to the wallet afterwards
+    pub transactions: usize,
+}
+
+/// Where a script pubkey comes from
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct ScriptIndex {
+    descriptor: usize,
+    keychain: Keychain,
+    index: u32,
+}
+
+#[derive(Default)]
+struct WatchOnlyState {
+    descriptors: Vec<WalletDescriptor>,
+    /// Every derived script, a gap limit ahead of the revealed ones
+    scripts: HashMap<ScriptBuf, ScriptIndex>,
+    /// Number of scripts derived per keychain
+    derived: HashMap<(usize, Keychain), u32>,
+    /// Next index to reveal per keychain
+    next_index: HashMap<(usize, Keychain), u32>,
+    transactions: HashMap<Txid, (Transaction, Option<u32>)>,
+}
+
+/// Wallet that follows descriptors and never holds private keys
+pub struct WatchOnlyWallet {
+    name: String,
+    network: Network,
+    gap_limit: u32,
+    /// Unix time before which no followed script was used
+    birthday: u64,
+    secp: Secp256k1<All>,
+    chain: Option<Arc<dyn ChainSource>>,
+    state: Mutex<WatchOnlyState>,
+    assets: Mutex<HashMap<String, Asset>>,
+}
+
+fn watch_only_error() -> BitcoinError {
+    BitcoinError::Wallet("Watch-only wallet holds no private keys".to_string())
+}
+
+/// Prefix of an encrypted backup, followed by hex `salt || iv || ciphertext || mac`
+const ENCRYPTED_BACKUP_PREFIX: &str = "anya-watch-only-backup-v1:";
+const BACKUP_SALT_LEN: usize = 16;
+const BACKUP_IV_LEN: usize = 16;
+const BACKUP_MAC_LEN: usize = 32;
+
+type Aes256Ctr = ctr::Ctr128BE<Aes256>;
+
+/// Encryption and MAC keys for a backup
+fn backup_keys(password: &str, salt: &[u8]) -> BitcoinResult<([u8; 32], Hmac<Sha256>)> {
+    let mut keys = [0u8; 64];
+    Argon2::default().hash_password_into(password.as_bytes(), salt, &mut keys)
+        .map_err(|e| BitcoinError::Wallet(format!("Cannot derive the backup key: {}", e)))?;
+    let mut encryption_key = [0u8; 32];
+    encryption_key.copy_from_slice(&keys[..32]);
+    let mac = Hmac::<Sha256>::new_from_slice(&keys[32..]).expect("HMAC takes any key size");
+    Ok((encryption_key, mac))
+}
+
+fn encrypt_backup(plaintext: &str, password: &str) -> BitcoinResult<String> {
+    let mut salt_iv = [0u8; BACKUP_SALT_LEN + BACKUP_IV_LEN];
+    rand::thread_rng().fill_bytes(&mut salt_iv);
+    let (salt, iv) = salt_iv.split_at(BACKUP_SALT_LEN);
+    let (key, mut mac) = backup_keys(password, salt)?;
+
+    let mut data = plaintext.as_bytes().to_vec();
+    Aes256Ctr::new(&key.into(), iv.into()).apply_keystream(&mut data);
+    mac.update(&salt_iv);
+    mac.update(&data);
+    let tag = mac.finalize().into_bytes();
+    Ok(format!("{}{}{}{}", ENCRYPTED_BACKUP_PREFIX, hex::encode(salt_iv), hex::encode(data), hex::encode(tag)))
+}
+
+fn decrypt_backup(payload: &str, password: &str) -> BitcoinResult<String> {
+    let bytes = hex::decode(payload.trim())
+        .map_err(|_| BitcoinError::Wallet("Encrypted backup is not hex".to_string()))?;
+    if bytes.len() < BACKUP_SALT_LEN + BACKUP_IV_LEN + BACKUP_MAC_LEN {
+        return Err(BitcoinError::Wallet("Encrypted backup is too short".to_string()));
+    }
+    let (salt_iv, rest) = bytes.split_at(BACKUP_SALT_LEN + BACKUP_IV_LEN);
+    let (ciphertext, tag) = rest.split_at(rest.len() - BACKUP_MAC_LEN);
+    let (salt, iv) = salt_iv.split_at(BACKUP_SALT_LEN);
+    let (key, mut mac) = backup_keys(password, salt)?;
+
+    mac.update(salt_iv);
+    mac.update(ciphertext);
+    mac.verify_slice(tag)
+        .map_err(|_| BitcoinError::Wallet("Wrong password or corrupted backup".to_string()))?;
+    let mut data = ciphertext.to_vec();
+    Aes256Ctr::new(&key.into(), iv.into()).apply_keystream(&mut data);
+    String::from_utf8(data).map_err(|_| BitcoinError::Wallet("Backup is not UTF-8".to_string()))
+}
+
+/// PSBT taproot key origins
+type TapKeyOrigins = BTreeMap<XOnlyPublicKey, (Vec<TapLeafHash>, KeySource)>;
+
+/// Key origins of a derived output, as PSBT fields
+///
+/// Taproot keys are listed by their x-only key with the leaves they sign
+/// in; the internal key has no leaves unless it also appears in one.
+fn key_origins(output: &DerivedOutput) -> (BTreeMap<PublicKey, KeySource>, TapKeyOrigins) {
+    if output.internal_key.is_none() {
+        return (output.keys.iter().cloned().collect(), BTreeMap::new());
+    }
+    let tap_key_origins = output.keys.iter()
+        .map(|(public_key, source)| {
+            let key = public_key.x_only_public_key().0;
+            let leaf_hashes = output.leaf_hashes.get(&key).cloned().unwrap_or_default();
+            (key, (leaf_hashes, source.clone()))
+        })
+        .collect();
+    (BTreeMap::new(), tap_key_origins)
+}
+
+impl WatchOnlyWallet {
+    pub fn new(name: &str, network: Network, chain: Option<Arc<dyn ChainSource>>) -> Self {
+        Self {
+            name: name.to_string(),
+            network,
+            gap_limit: DEFAULT_GAP_LIMIT,
+            birthday: 0,
+            secp: Secp256k1::new(),
+            chain,
+            state: Mutex::new(WatchOnlyState::default()),
+            assets: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Sets how many unused addresses end a scan
+    pub fn with_gap_limit(mut self, gap_limit: u32) -> Self {
+        self.gap_limit = gap_limit.max(1);
+        self
+    }
+
+    /// Sets the time before which no followed script was used, so chain
+    /// sources need not look further back
+    pub fn with_birthday(mut self, birthday: u64) -> Self {
+        self.birthday = birthday;
+        self
+    }
+
+    /// Follows a descriptor, deriving a gap limit of scripts on each keychain
+    pub fn add_descriptor(&self, descriptor: WalletDescriptor) -> BitcoinResult<()> {
+        if descriptor.network_kind() != NetworkKind::from(self.network) {
+            return Err(BitcoinError::Wallet(format!("Descriptor is not for {}", self.network)));
+        }
+        let mut state = self.state.lock().unwrap();
+        if state.descriptors.contains(&descriptor) {
+            return Err(BitcoinError::Wallet("Descriptor already imported".to_string()));
+        }
+        state.descriptors.push(descriptor);
+        let index = state.descriptors.len() - 1;
+        for keychain in state.descriptors[index].keychains() {
+            if let Err(e) = self.derive_up_to(&mut state, index, keychain, self.gap_limit) {
+                // Forget the descriptor so it can be added again
+                state.descriptors.pop();
+                state.derived.retain(|(descriptor, _), _| *descriptor != index);
+                state.scripts.retain(|_, source| source.descriptor != index);
+                return Err(e);
+            }
+        }
+        Ok(())
+    }
+
+    /// Followed descriptors
+    pub fn descriptors(&self) -> Vec<WalletDescriptor> {
+        self.state.lock().unwrap().descriptors.clone()
+    }
+
+    /// Derives scripts of a keychain until `count` of them are known
+    fn derive_up_to(&self, state: &mut WatchOnlyState, descriptor: usize, keychain: Keychain, count: u32) -> BitcoinResult<()> {
+        let count = if state.descriptors[descriptor].is_ranged() { count } else { 1 };
+        let key = (descriptor, keychain);
+        let start = state.derived.get(&key).copied().unwrap_or(0);
+        if count <= start {
+            return Ok(());
+        }
+        if let Some(chain) = &self.chain {
+            let watched = state.descriptors[descriptor].keychain_descriptor(keychain)?;
+            chain.watch_descriptor(&watched.to_string(), count, self.birthday)?;
+        }
+        for index in start..count {
+            let output = state.descriptors[descriptor].derive(&self.secp, keychain, index)?;
+            state.scripts.insert(output.script_pubkey, ScriptIndex { descriptor, keychain, index });
+        }
+        state.derived.insert(key, count);
+        Ok(())
+    }
+
+    /// Looks up every keychain on the chain source until `gap_limit`
+    /// consecutive addresses are unused
+    pub fn scan(&self) -> BitcoinResult<ScanSummary> {
+        let chain = self.chain.as_ref()
+            .ok_or_else(|| BitcoinError::Wallet("No chain source to scan".to_string()))?;
+        let mut state = self.state.lock().unwrap();
+        let mut summary = ScanSummary::default();
+
+        for descriptor in 0..state.descriptors.len() {
+            let ranged = state.descriptors[descriptor].is_ranged();
+            for keychain in state.descriptors[descriptor].keychains() {
+                let (mut index, mut unused, mut last_used) = (0, 0, None);
+                while unused < self.gap_limit {
+                    let output = state.descriptors[descriptor].derive(&self.secp, keychain, index)?;
+                    let history = chain.script_history(&output.script_pubkey)?;
+                    summary.scripts_checked += 1;
+                    state.scripts.insert(output.script_pubkey, ScriptIndex { descriptor, keychain, index });
+
+                    if history.is_empty() {
+                        unused += 1;
+                    } else {
+                        unused = 0;
+                        last_used = Some(index);
+                        for (tx, height) in history {
+                            state.transactions.insert(tx.compute_txid(), (tx, height));
+                        }
+                    }
+                    index += 1;
+                    if !ranged {
+                        break;
+                    }
+                }
+
+                let key = (descriptor, keychain);
+                let derived = state.derived.get(&key).copied().unwrap_or(0);
+                state.derived.insert(key, derived.max(index));
+                if let Some(last_used) = last_used {
+                    let next = state.next_index.entry(key).or_insert(0);
+                    *next = (*next).max(last_used + 1);
+                }
+            }
+        }
+
+        summary.transactions = state.transactions.len();
+        Ok(summary)
+    }
+
+    /// Unspent outputs paying to the wallet
+    pub fn utxos(&self) -> Vec<Utxo> {
+        let state = self.state.lock().unwrap();
+        let spent: HashSet<OutPoint> = state.transactions.values()
+            .flat_map(|(tx, _)| tx.input.iter().map(|input| input.previous_output))
+            .collect();
+
+        let mut utxos = Vec::new();
+        for (txid, (tx, height)) in &state.transactions {
+            for (vout, output) in tx.output.iter().enumerate() {
+                let Some(source) = state.scripts.get(&output.script_pubkey) else { continue };
+                if spent.contains(&OutPoint::new(*txid, vout as u32)) {
+                    continue;
+                }
+                let derivation_path = state.descriptors[source.descriptor]
+                    .derive(&self.secp, source.keychain, source.index)
+                    .ok()
+                    .map(|derived| derived.keys[0].1 .1.to_string());
+                utxos.push(Utxo {
+                    txid: txid.to_string(),
+                    vout: vout as u32,
+                    amount: output.value.to_sat(),
+                    address: Address::from_script(&output.script_pubkey, self.network)
+                        .map(|address| address.to_string())
+                        .unwrap_or_default(),
+                    script_pubkey: output.script_pubkey.to_bytes(),
+                    derivation_path,
+                    confirmed: height.is_some(),
+                    confirmation_height: *height,
+                });
+            }
+        }
+        utxos.sort_by(|a, b| (&a.txid, a.vout).cmp(&(&b.txid, b.vout)));
+        utxos
+    }
+
+    /// Balance of the unspent outputs
+    pub fn balance(&self) -> WatchOnlyBalance {
+        self.utxos().iter().fold(WatchOnlyBalance::default(), |mut balance, utxo| {
+            if utxo.confirmed {
+                balance.confirmed += utxo.amount;
+            } else {
+                balance.unconfirmed += utxo.amount;
+            }
+            balance
+        })
+    }
+
+    /// Transactions touching the wallet, oldest first and unconfirmed last
+    pub fn history(&self) -> Vec<HistoryEntry> {
+        let state = self.state.lock().unwrap();
+        let previous_output = |outpoint: &OutPoint| {
+            state.transactions.get(&outpoint.txid)
+                .and_then(|(tx, _)| tx.output.get(outpoint.vout as usize))
+        };
+
+        let mut history: Vec<HistoryEntry> = state.transactions.iter()
+            .filter_map(|(txid, (tx, height))| {
+                let received: u64 = tx.output.iter()
+                    .filter(|output| state.scripts.contains_key(&output.script_pubkey))
+                    .map(|output| output.value.to_sat())
+                    .sum();
+                let inputs: Vec<Option<&TxOut>> = tx.input.iter().map(|input| previous_output(&input.previous_output)).collect();
+                let sent: u64 = inputs.iter()
+                    .flatten()
+                    .filter(|output| state.scripts.contains_key(&output.script_pubkey))
+                    .map(|output| output.value.to_sat())
+                    .sum();
+                if received == 0 && sent == 0 {
+                    return None;
+                }
+                let fee = inputs.iter()
+                    .map(|output| output.map(|output| output.value.to_sat()))
+                    .sum::<Option<u64>>()
+                    .and_then(|input_total| input_total.checked_sub(tx.output.iter().map(|o| o.value.to_sat()).sum()));
+                Some(HistoryEntry { txid: *txid, confirmation_height: *height, received, sent, fee })
+            })
+            .collect();
+        history.sort_by_key(|entry| (entry.confirmation_height.is_none(), entry.confirmation_height, entry.txid));
+        history
+    }
+
+    /// Reveals the next address of a keychain
+    pub fn next_address(&self, descriptor: usize, keychain: Keychain) -> BitcoinResult<Address> {
+        let mut state = self.state.lock().unwrap();
+        let index = self.reveal(&mut state, descriptor, keychain)?;
+        state.descriptors[descriptor].address(&self.secp, self.network, keychain, index)
+    }
+
+    fn reveal(&self, state: &mut WatchOnlyState, descriptor: usize, keychain: Keychain) -> BitcoinResult<u32> {
+        if descriptor >= state.descriptors.len() {
+            return Err(BitcoinError::Wallet(format!("No descriptor {}", descriptor)));
+        }
+        let next = state.next_index.entry((descriptor, keychain)).or_insert(0);
+        let index = *next;
+        if state.descriptors[descriptor].is_ranged() {
+            *next += 1;
+        }
+        self.derive_up_to(state, descriptor, keychain, index + 1 + self.gap_limit)?;
+        Ok(index)
+    }
+
+    /// Builds an unsigned PSBT paying `outputs`, given as address and amount
+    /// pairs
+    ///
+    /// Coins are selected with the strategy in `options`. Change goes to the
+    /// next change address of the first descriptor with a change keychain,
+    /// whatever `options.change_address_type` says. Every input and the
+    /// change output carry their key origins and scripts, so signers can
+    /// sign and check the change without talking to the wallet.
+    pub fn create_psbt(&self, outputs: &[(String, u64)], fee_rate: f64, options: &TxOptions) -> BitcoinResult<Psbt> {
+        let utxos = self.utxos();
+        let mut state = self.state.lock().unwrap();
+        if state.descriptors.is_empty() {
+            return Err(BitcoinError::Wallet("No descriptors imported".to_string()));
+        }
+        let (change_descriptor, change_keychain) = state.descriptors.iter()
+            .position(|descriptor| descriptor.keychains().contains(&Keychain::Internal))
+            .map_or((0, Keychain::External), |index| (index, Keychain::Internal));
+
+        let change = &state.descriptors[change_descriptor];
+        let mut selector = CoinSelector::new(fee_rate).with_change_weights(change.output_weight(), change.input_weight());
+        for utxo in utxos.iter().chain(options.custom_inputs.iter().flatten()) {
+            if let Some(source) = state.scripts.get(Script::from_bytes(&utxo.script_pubkey)) {
+                let descriptor = &state.descriptors[source.descriptor];
+                selector = selector.with_input_weight(utxo.script_pubkey.clone(), descriptor.input_weight(), descriptor.has_witness());
+            }
+        }
+        let selection = selector.select(&utxos, outputs, options)?;
+
+        let sequence = if options.rbf {
+            Sequence::ENABLE_RBF_NO_LOCKTIME
+        } else if options.lock_time.is_some() {
+            Sequence::ENABLE_LOCKTIME_NO_RBF
+        } else {
+            Sequence::MAX
+        };
+        let input = selection.selected.iter()
+            .map(|utxo| {
+                let txid = Txid::from_str(&utxo.txid)
+                    .map_err(|e| BitcoinError::Wallet(format!("Invalid txid {}: {}", utxo.txid, e)))?;
+                Ok(TxIn {
+                    previous_output: OutPoint::new(txid, utxo.vout),
+                    script_sig: ScriptBuf::new(),
+                    sequence,
+                    witness: Witness::new(),
+                })
+            })
+            .collect::<BitcoinResult<Vec<_>>>()?;
+
+        // The fee deduction is spread over the recipients, the first one
+        // taking the remainder
+        let mut output = Vec::new();
+        let recipients = outputs.len().max(1) as u64;
+        for (i, (address, amount)) in outputs.iter().enumerate() {
+            let share = selection.recipient_deduction / recipients
+                + if i == 0 { selection.recipient_deduction % recipients } else { 0 };
+            let value = amount.checked_sub(share).filter(|value| *value > 0)
+                .ok_or_else(|| BitcoinError::Wallet(format!("Fee exceeds the amount sent to {}", address)))?;
+            output.push(TxOut { value: Amount::from_sat(value), script_pubkey: self.parse_address(address)?.script_pubkey() });
+        }
+        for (address, amount) in &options.extra_outputs {
+            output.push(TxOut { value: Amount::from_sat(*amount), script_pubkey: self.parse_address(address)?.script_pubkey() });
+        }
+        if let Some(data) = &options.op_return_data {
+            let data = PushBytesBuf::try_from(data.clone())
+                .map_err(|_| BitcoinError::Wallet("OP_RETURN data is too large".to_string()))?;
+            output.push(TxOut { value: Amount::ZERO, script_pubkey: ScriptBuf::new_op_return(&data) });
+        }
+
+        let mut change_output = None;
+        if let Some(value) = selection.change {
+            let index = self.reveal(&mut state, change_descriptor, change_keychain)?;
+            let derived = state.descriptors[change_descriptor].derive(&self.secp, change_keychain, index)?;
+            let position = rand::thread_rng().gen_range(0..=output.len());
+            output.insert(position, TxOut { value: Amount::from_sat(value), script_pubkey: derived.script_pubkey.clone() });
+            change_output = Some((position, derived));
+        }
+
+        let tx = Transaction {
+            version: transaction::Version::TWO,
+            lock_time: options.lock_time.map_or(absolute::LockTime::ZERO, absolute::LockTime::from_consensus),
+            input,
+            output,
+        };
+        let mut psbt = Psbt::from_unsigned_tx(tx)
+            .map_err(|e| BitcoinError::Wallet(format!("Cannot create PSBT: {}", e)))?;
+
+        for (psbt_input, txin) in psbt.inputs.iter_mut().zip(&psbt.unsigned_tx.input) {
+            let outpoint = txin.previous_output;
+            let previous_tx = &state.transactions.get(&outpoint.txid)
+                .ok_or_else(|| BitcoinError::Wallet(format!("Unknown input {}", outpoint)))?
+                .0;
+            let previous_output = previous_tx.output[outpoint.vout as usize].clone();
+            let source = *state.scripts.get(&previous_output.script_pubkey)
+                .ok_or_else(|| BitcoinError::Wallet(format!("Input {} is not the wallet's", outpoint)))?;
+            let derived = state.descriptors[source.descriptor].derive(&self.secp, source.keychain, source.index)?;
+
+            if !previous_output.script_pubkey.is_p2tr() {
+                psbt_input.non_witness_utxo = Some(previous_tx.clone());
+            }
+            if !previous_output.script_pubkey.is_p2pkh() {
+                psbt_input.witness_utxo = Some(previous_output);
+            }
+            (psbt_input.bip32_derivation, psbt_input.tap_key_origins) = key_origins(&derived);
+            psbt_input.tap_internal_key = derived.internal_key;
+            psbt_input.redeem_script = derived.redeem_script;
+            psbt_input.witness_script = derived.witness_script;
+        }
+
+        if let Some((position, derived)) = change_output {
+            let psbt_output = &mut psbt.outputs[position];
+            (psbt_output.bip32_derivation, psbt_output.tap_key_origins) = key_origins(&derived);
+            psbt_output.tap_internal_key = derived.internal_key;
+            psbt_output.redeem_script = derived.redeem_script;
+            psbt_output.witness_script = derived.witness_script;
+        }
+
+        for key in state.descriptors.iter().flat_map(|descriptor| descriptor.keys()) {
+            if let Some(origin) = &key.origin {
+                psbt.xpub.insert(key.xpub, origin.clone());
+            }
+        }
+        Ok(psbt)
+    }
+
+    /// Broadcasts a signed transaction and records it as unconfirmed
+    pub fn broadcast(&self, tx: &Transaction) -> BitcoinResult<Txid> {
+        let chain = self.chain.as_ref()
+            .ok_or_else(|| BitcoinError::Wallet("No chain source to broadcast to".to_string()))?;
+        let txid = chain.broadcast(tx)?;
+        self.state.lock().unwrap().transactions.insert(txid, (tx.clone(), None));
+        Ok(txid)
+    }
+
+    fn parse_address(&self, address: &str) -> BitcoinResult<Address> {
+        Address::from_str(address)
+            .map_err(|e| BitcoinError::Wallet(format!("Invalid address: {}", e)))?
+            .require_network(self.network)
+            .map_err(|e| BitcoinError::Wallet(format!("Invalid address: {}", e)))
+    }
+
+    /// Index of the first descriptor of an address type
+    fn descriptor_for(&self, address_type: AddressType) -> BitcoinResult<usize> {
+        self.state.lock().unwrap().descriptors.iter()
+            .position(|descriptor| descriptor.address_type() == Some(address_type))
+            .ok_or_else(|| BitcoinError::Wallet(format!("No {:?} descriptor imported", address_type)))
+    }
+}
+
+impl KeyManager for WatchOnlyWallet {
+    fn derive_key(&self, _path: &str) -> AnyaResult<bitcoin::secp256k1::SecretKey> {
+        Err(watch_only_error().into())
+    }
+
+    fn get_public_key(&self, path: &str) -> AnyaResult<PublicKey> {
+        let path = DerivationPath::from_str(path)
+            .map_err(|e| BitcoinError::Wallet(format!("Invalid derivation path: {}", e)))?;
+        let steps: Vec<ChildNumber> = path.into_iter().copied().collect();
+
+        // Any imported key whose origin the path goes through
+        let state = self.state.lock().unwrap();
+        for key in state.descriptors.iter().flat_map(|descriptor| descriptor.keys()) {
+            let origin: Vec<ChildNumber> = key.origin.as_ref()
+                .map(|(_, origin)| origin.into_iter().copied().collect())
+                .unwrap_or_default();
+            if let Some(rest) = steps.strip_prefix(origin.as_slice()) {
+                if let Ok(child) = key.xpub.derive_pub(&self.secp, &DerivationPath::from(rest.to_vec())) {
+                    return Ok(child.public_key);
+                }
+            }
+        }
+        Err(BitcoinError::Wallet(format!("No imported key derives {}", path)).into())
+    }
+
+    fn sign_message(&self, _message: &[u8], _path: &str) -> AnyaResult<Vec<u8>> {
+        Err(watch_only_error().into())
+    }
+
+    fn verify_message(&self, message: &[u8], signature: &[u8], path: &str) -> AnyaResult<bool> {
+        let public_key = self.get_public_key(path)?;
+        let message_hash = bitcoin::secp256k1::Message::from_digest(sha256::Hash::hash(message).to_byte_array());
+
+        let signature = bitcoin::secp256k1::ecdsa::Signature::from_der(signature)
+            .map_err(|e| BitcoinError::Wallet(format!("Invalid signature: {}", e)))?;
+
+        Ok(self.secp.verify_ecdsa(&message_hash, &signature, &public_key).is_ok())
+    }
+}
+
+impl AddressManager for WatchOnlyWallet {
+    fn get_new_address(&self, address_type: AddressType) -> AnyaResult<Address> {
+        let descriptor = self.descriptor_for(address_type)?;
+        Ok(self.next_address(descriptor, Keychain::External)?)
+    }
+
+    fn get_address(&self, index: u32, address_type: AddressType) -> AnyaResult<Address> {
+        let descriptor = self.descriptor_for(address_type)?;
+        let state = self.state.lock().unwrap();
+        Ok(state.descriptors[descriptor].address(&self.secp, self.network, Keychain::External, index)?)
+    }
+
+    fn is_address_mine(&self, address: &str) -> AnyaResult<bool> {
+        let script_pubkey = self.parse_address(address)?.script_pubkey();
+        Ok(self.state.lock().unwrap().scripts.contains_key(&script_pubkey))
+    }
+
+    fn get_all_addresses(&self) -> AnyaResult<Vec<Address>> {
+        let state = self.state.lock().unwrap();
+        let mut addresses = Vec::new();
+        for ((descriptor, keychain), next) in &state.next_index {
+            for index in 0..*next {
+                addresses.push(state.descriptors[*descriptor].address(&self.secp, self.network, *keychain, index)?);
+            }
+        }
+        Ok(addresses)
+    }
+}
+
+impl TransactionManager for WatchOnlyWallet {
+    fn create_transaction(
+        &self,
+        outputs: Vec<(String, u64)>,
+        fee_rate: f64,
+        options: super::transactions::TxOptions,
+    ) -> AnyaResult<Transaction> {
+        Ok(self.create_psbt(&outputs, fee_rate, &options)?.unsigned_tx)
+    }
+
+    fn sign_transaction(&self, _tx: &mut Transaction) -> AnyaResult<()> {
+        // Signing happens offline, from the PSBT
+        Err(watch_only_error().into())
+    }
+
+    fn broadcast_transaction(&self, tx: &Transaction) -> AnyaResult<String> {
+        Ok(self.broadcast(tx)?.to_string())
+    }
+
+    fn get_transaction(&self, txid: &str) -> AnyaResult<Option<Transaction>> {
+        let txid = Txid::from_str(txid)
+            .map_err(|e| BitcoinError::Wallet(format!("Invalid txid: {}", e)))?;
+        Ok(self.state.lock().unwrap().transactions.get(&txid).map(|(tx, _)| tx.clone()))
+    }
+
+    fn get_transactions(&self, limit: usize, offset: usize) -> AnyaResult<Vec<Transaction>> {
+        let history = self.history();
+        let state = self.state.lock().unwrap();
+        Ok(history.iter()
+            .rev()
+            .skip(offset)
+            .take(limit)
+            .filter_map(|entry| state.transactions.get(&entry.txid).map(|(tx, _)| tx.clone()))
+            .collect())
+    }
+}
+
+impl BalanceManager for WatchOnlyWallet {
+    fn get_balance(&self) -> AnyaResult<u64> {
+        Ok(self.balance().confirmed)
+    }
+
+    fn get_unconfirmed_balance(&self) -> AnyaResult<u64> {
+        Ok(self.balance().unconfirmed)
+    }
+
+    fn get_asset_balance(&self, asset_id: &str) -> AnyaResult<u64> {
+        let assets = self.assets.lock().unwrap();
+
+        if let Some(asset) = assets.get(asset_id) {
+            Ok(asset.balance)
+        } else {
+            Err(BitcoinError::Wallet(format!("Asset not found: {}", asset_id)).into())
+        }
+    }
+
+    fn get_all_asset_balances(&self) -> AnyaResult<HashMap<String, u64>> {
+        let assets = self.assets.lock().unwrap();
+        Ok(assets.iter().map(|(id, asset)| (id.clone(), asset.balance)).collect())
+    }
+}
+
+impl UnifiedWallet for WatchOnlyWallet {
+    fn name(&self) -> &str {
+        &self.name
+    }
+
+    fn wallet_type(&self) -> WalletType {
+        let state = self.state.lock().unwrap();
+        if state.descriptors.iter().any(|descriptor| matches!(descriptor, WalletDescriptor::Tr(_))) {
+            WalletType::Taproot
+        } else {
+            WalletType::Standard
+        }
+    }
+
+    fn network(&self) -> Network {
+        self.network
+    }
+
+    // Other chains derive keys from hardened paths of the seed
+    fn get_stacks_address(&self) -> AnyaResult<String> {
+        Err(watch_only_error().into())
+    }
+
+    fn get_rsk_address(&self) -> AnyaResult<String> {
+        Err(watch_only_error().into())
+    }
+
+    fn get_liquid_address(&self) -> AnyaResult<String> {
+        Err(watch_only_error().into())
+    }
+
+    fn add_asset(&self, asset_id: &str, name: &str, asset_type: &str) -> AnyaResult<()> {
+        let mut assets = self.assets.lock().unwrap();
+
+        if assets.contains_key(asset_id) {
+            return Err(BitcoinError::Wallet(format!("Asset already exists: {}", asset_id)).into());
+        }
+
+        assets.insert(asset_id.to_string(), Asset {
+            id: asset_id.to_string(),
+            name: name.to_string(),
+            asset_type: asset_type.to_string(),
+            chain: determine_chain_from_asset_id(asset_id),
+            balance: 0,
+            metadata: HashMap::new(),
+        });
+
+        Ok(())
+    }
+
+    fn remove_asset(&self, asset_id: &str) -> AnyaResult<()> {
+        if self.assets.lock().unwrap().remove(asset_id).is_none() {
+            return Err(BitcoinError::Wallet(format!("Asset not found: {}", asset_id)).into());
+        }
+        Ok(())
+    }
+
+    fn get_assets(&self) -> AnyaResult<Vec<Asset>> {
+        Ok(self.assets.lock().unwrap().values().cloned().collect())
+    }
+
+    fn export_xpriv(&self, _password: &str) -> AnyaResult<String> {
+        Err(watch_only_error().into())
+    }
+
+    fn import_xpriv(&self, _xpriv: &str, _password: &str) -> AnyaResult<()> {
+        Err(BitcoinError::Wallet("Watch-only wallets do not import private keys".to_string()).into())
+    }
+
+    fn import_xpub(&self, xpub: &str, origin: Option<&str>) -> AnyaResult<()> {
+        Ok(self.add_descriptor(WalletDescriptor::from_extended_key(xpub, origin)?)?)
+    }
+
+    fn import_descriptor(&self, descriptor: &str) -> AnyaResult<()> {
+        Ok(self.add_descriptor(descriptor.parse()?)?)
+    }
+
+    fn is_watch_only(&self) -> bool {
+        true
+    }
+
+    /// Writes the descriptors, encrypted unless `password` is empty
+    fn backup(&self, path: &str, password: &str) -> AnyaResult<()> {
+        let descriptors: Vec<String> = self.descriptors().iter().map(ToString::to_string).collect();
+        let contents = match password {
+            "" => descriptors.join("\n"),
+            password => encrypt_backup(&descriptors.join("\n"), password)?,
+        };
+        fs::write(path, contents)
+            .map_err(|e| BitcoinError::IOError(format!("Cannot write {}: {}", path, e)))?;
+        Ok(())
+    }
+
+    fn restore(&self, path: &str, password: &str) -> AnyaResult<()> {
+        let contents = fs::read_to_string(path)
+            .map_err(|e| BitcoinError::IOError(format!("Cannot read {}: {}", path, e)))?;
+        let contents = match (contents.strip_prefix(ENCRYPTED_BACKUP_PREFIX), password) {
+            (Some(_), "") => return Err(BitcoinError::Wallet("Backup is encrypted, a password is needed".to_string()).into()),
+            (Some(payload), password) => decrypt_backup(payload, password)?,
+            (None, "") => contents,
+            (None, _) => return Err(BitcoinError::Wallet("Backup is not encrypted, but a password was given".to_string()).into()),
+        };
+        for line in contents.lines().filter(|line| !line.trim().is_empty()) {
+            let descriptor: WalletDescriptor = line.parse()?;
+            if !self.descriptors().contains(&descriptor) {
+                self.add_descriptor(descriptor)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bitcoin::wallet::descriptor::tests::account;
+    use bitcoin::bip32::Xpriv;
+
+    #[derive(Default)]
+    struct MockChain {
+        history: Mutex<HashMap<ScriptBuf, ScriptHistory>>,
+        broadcast: Mutex<Vec<Transaction>>,
+    }
+
+    impl MockChain {
+        /// Confirms a transaction in the history of every script it touches
+        fn confirm(&self, tx: &Transaction, height: Option<u32>, spent: &[ScriptBuf]) {
+            let mut history = self.history.lock().unwrap();
+            for script in tx.output.iter().map(|o| o.script_pubkey.clone()).chain(spent.iter().cloned()) {
+                history.entry(script).or_default().push((tx.clone(), height));
+            }
+        }
+    }
+
+    impl ChainSource for MockChain {
+        fn script_history(&self, script_pubkey: &Script) -> BitcoinResult<ScriptHistory> {
+            Ok(self.history.lock().unwrap().get(script_pubkey).cloned().unwrap_or_default())
+        }
+
+        fn broadcast(&self, tx: &Transaction) -> BitcoinResult<Txid> {
+            self.broadcast.lock().unwrap().push(tx.clone());
+            Ok(tx.compute_txid())
+        }
+    }
+
+    fn funding(tag: u8, outputs: Vec<(ScriptBuf, u64)>) -> Transaction {
+        Transaction {
+            version: transaction::Version::TWO,
+            lock_time: absolute::LockTime::ZERO,
+            input: vec![TxIn {
+                previous_output: OutPoint::new(Txid::from_byte_array([tag; 32]), 0),
+                ..Default::default()
+            }],
+            output: outputs.into_iter()
+                .map(|(script_pubkey, value)| TxOut { value: Amount::from_sat(value), script_pubkey })
+                .collect(),
+        }
+    }
+
+    fn wallet() -> (Xpriv, WatchOnlyWallet, Arc<MockChain>, WalletDescriptor) {
+        let (master, key) = account(7);
+        let descriptor: WalletDescriptor = format!("wpkh({}/<0;1>/*)", key).parse().unwrap();
+        let chain = Arc::new(MockChain::default());
+        let wallet = WatchOnlyWallet::new("treasury", Network::Regtest, Some(chain.clone() as Arc<dyn ChainSource>));
+        wallet.import_descriptor(&descriptor.to_string()).unwrap();
+        (master, wallet, chain, descriptor)
+    }
+
+    fn script(descriptor: &WalletDescriptor, keychain: Keychain, index: u32) -> ScriptBuf {
+        descriptor.derive(&Secp256k1::new(), keychain, index).unwrap().script_pubkey
+    }
+
+    #[test]
+    fn test_gap_limit_scan() {
+        let (_, wallet, chain, descriptor) = wallet();
+        chain.confirm(&funding(1, vec![(script(&descriptor, Keychain::External, 0), 50_000)]), Some(100), &[]);
+        chain.confirm(&funding(2, vec![(script(&descriptor, Keychain::External, 15), 70_000)]), Some(101), &[]);
+        chain.confirm(&funding(3, vec![(script(&descriptor, Keychain::Internal, 2), 5_000)]), None, &[]);
+        // Past the gap after index 15
+        chain.confirm(&funding(4, vec![(script(&descriptor, Keychain::External, 36), 1_000)]), Some(102), &[]);
+
+        let summary = wallet.scan().unwrap();
+        assert_eq!(summary.transactions, 3);
+        assert_eq!(summary.scripts_checked, 36 + 23);
+        assert_eq!(wallet.balance(), WatchOnlyBalance { confirmed: 120_000, unconfirmed: 5_000 });
+        assert_eq!(wallet.utxos().len(), 3);
+
+        // The next receive address follows the last used one
+        let next = wallet.get_new_address(AddressType::SegWit).unwrap();
+        assert_eq!(next.script_pubkey(), script(&descriptor, Keychain::External, 16));
+        assert!(wallet.is_address_mine(&next.to_string()).unwrap());
+
+        let history = wallet.history();
+        assert_eq!(history.len(), 3);
+        assert_eq!(history[2].confirmation_height, None);
+        assert_eq!(history[0].net(), 50_000);
+    }
+
+    #[test]
+    fn test_history_tracks_spends() {
+        let (_, wallet, chain, descriptor) = wallet();
+        let receive = script(&descriptor, Keychain::External, 0);
+        let fund = funding(1, vec![(receive.clone(), 100_000)]);
+        chain.confirm(&fund, Some(100), &[]);
+
+        let spend = Transaction {
+            version: transaction::Version::TWO,
+            lock_time: absolute::LockTime::ZERO,
+            input: vec![TxIn { previous_output: OutPoint::new(fund.compute_txid(), 0), ..Default::default() }],
+            output: vec![
+                TxOut { value: Amount::from_sat(60_000), script_pubkey: ScriptBuf::new_op_return([1u8; 4]) },
+                TxOut { value: Amount::from_sat(39_000), script_pubkey: script(&descriptor, Keychain::Internal, 0) },
+            ],
+        };
+        chain.confirm(&spend, Some(101), &[receive]);
+        wallet.scan().unwrap();
+
+        let history = wallet.history();
+        assert_eq!(history.len(), 2);
+        assert_eq!(history[1].sent, 100_000);
+        assert_eq!(history[1].received, 39_000);
+        assert_eq!(history[1].fee, Some(1_000));
+        assert_eq!(wallet.get_balance().unwrap(), 39_000);
+    }
+
+    #[test]
+    fn test_unsigned_psbt_for_offline_signer() {
+        let (master, wallet, chain, descriptor) = wallet();
+        chain.confirm(&funding(1, vec![(script(&descriptor, Keychain::External, 3), 200_000)]), Some(100), &[]);
+        wallet.scan().unwrap();
+
+        let secp = Secp256k1::new();
+        let recipient = Address::p2wpkh(&bitcoin::CompressedPublicKey(master.private_key.public_key(&secp)), Network::Regtest);
+        let psbt = wallet.create_psbt(&[(recipient.to_string(), 120_000)], 2.0, &TxOptions::default()).unwrap();
+
+        assert_eq!(psbt.unsigned_tx.input.len(), 1);
+        assert_eq!(psbt.unsigned_tx.input[0].sequence, Sequence::ENABLE_RBF_NO_LOCKTIME);
+        assert!(psbt.unsigned_tx.input.iter().all(|input| input.witness.is_empty()));
+
+        // The signer finds its key from the origin and can derive the private key
+        let input = &psbt.inputs[0];
+        assert_eq!(input.witness_utxo.as_ref().unwrap().value, Amount::from_sat(200_000));
+        let (public_key, (fingerprint, path)) = input.bip32_derivation.iter().next().unwrap();
+        assert_eq!(*fingerprint, master.fingerprint(&secp));
+        assert_eq!(*path, DerivationPath::from_str("m/84'/1'/0'/0/3").unwrap());
+        assert_eq!(master.derive_priv(&secp, path).unwrap().private_key.public_key(&secp), *public_key);
+        assert_eq!(psbt.xpub.len(), 1);
+
+        // Change goes back to the change keychain, marked as the wallet's
+        let change = psbt.unsigned_tx.output.iter()
+            .position(|output| output.script_pubkey == script(&descriptor, Keychain::Internal, 0))
+            .unwrap();
+        let (_, (_, change_path)) = psbt.outputs[change].bip32_derivation.iter().next().unwrap();
+        assert_eq!(*change_path, DerivationPath::from_str("m/84'/1'/0'/1/0").unwrap());
+        let fee = 200_000 - psbt.unsigned_tx.output.iter().map(|o| o.value.to_sat()).sum::<u64>();
+        assert!(fee > 0 && fee < 1_000);
+
+        // Broadcasting a signed transaction records it as unconfirmed
+        wallet.broadcast_transaction(&psbt.unsigned_tx).unwrap();
+        assert_eq!(chain.broadcast.lock().unwrap().len(), 1);
+        assert_eq!(wallet.get_balance().unwrap(), 0);
+        assert_eq!(wallet.get_unconfirmed_balance().unwrap(), 200_000 - 120_000 - fee);
+    }
+
+    #[test]
+    fn test_tap_key_origins_per_key() {
+        let secp = Secp256k1::new();
+        let master = Xpriv::new_master(Network::Regtest, &[3; 32]).unwrap();
+        let origin = |i: u32| (master.fingerprint(&secp), DerivationPath::from(vec![ChildNumber::from_normal_idx(i).unwrap()]));
+        let key = |i: u32| master.derive_priv(&secp, &origin(i).1).unwrap().private_key.public_key(&secp);
+        let (internal, signer) = (key(0), key(1));
+        let leaf = TapLeafHash::from_byte_array([7; 32]);
+
+        let output = DerivedOutput {
+            script_pubkey: ScriptBuf::new(),
+            keys: vec![(internal, origin(0)), (signer, origin(1))],
+            redeem_script: None,
+            witness_script: None,
+            internal_key: Some(internal.x_only_public_key().0),
+            leaf_hashes: BTreeMap::from([(signer.x_only_public_key().0, vec![leaf])]),
+        };
+        let (bip32_derivation, tap_key_origins) = key_origins(&output);
+
+        assert!(bip32_derivation.is_empty());
+        assert_eq!(tap_key_origins.len(), 2);
+        assert_eq!(tap_key_origins[&internal.x_only_public_key().0], (vec![], origin(0)));
+        assert_eq!(tap_key_origins[&signer.x_only_public_key().0], (vec![leaf], origin(1)));
+    }
+
+    #[test]
+    fn test_xpub_import_has_no_private_keys() {
+        let (master, key) = account(9);
+        let tpub = key.split(']').nth(1).unwrap();
+        let wallet = WatchOnlyWallet::new("monitor", Network::Regtest, None);
+        wallet.import_xpub(tpub, Some(&format!("{}/84'/1'/0'", master.fingerprint(&Secp256k1::new())))).unwrap();
+        assert!(wallet.is_watch_only());
+        assert!(wallet.import_xpub(tpub, None).is_ok());
+        assert!(wallet.derive_key("m/84'/1'/0'/0/0").is_err());
+        assert!(wallet.sign_message(b"hi", "m/84'/1'/0'/0/0").is_err());
+        assert!(wallet.export_xpriv("pw").is_err());
+
+        let secp = Secp256k1::new();
+        let path = DerivationPath::from_str("m/84'/1'/0'/0/4").unwrap();
+        assert_eq!(
+            wallet.get_public_key("m/84'/1'/0'/0/4").unwrap(),
+            master.derive_priv(&secp, &path).unwrap().private_key.public_key(&secp)
+        );
+
+        // Keys for another network are refused
+        let mainnet = WatchOnlyWallet::new("mainnet", Network::Bitcoin, None);
+        assert!(mainnet.import_xpub(tpub, None).is_err());
+    }
+
+    #[test]
+    fn test_backup_encryption() {
+        let (_, wallet, _, descriptor) = wallet();
+        let dir = tempfile::tempdir().unwrap();
+        let encrypted = dir.path().join("encrypted");
+        let encrypted = encrypted.to_str().unwrap();
+        wallet.backup(encrypted, "correct horse").unwrap();
+        let contents = fs::read_to_string(encrypted).unwrap();
+        assert!(contents.starts_with(ENCRYPTED_BACKUP_PREFIX));
+        assert!(!contents.contains(&descriptor.keys()[0].xpub.to_string()));
+
+        let restored = WatchOnlyWallet::new("restored", Network::Regtest, None);
+        assert!(restored.restore(encrypted, "wrong horse").is_err());
+        assert!(restored.restore(encrypted, "").is_err());
+        restored.restore(encrypted, "correct horse").unwrap();
+        assert_eq!(restored.descriptors(), vec![descriptor.clone()]);
+
+        // Tampering is detected
+        let mut tampered = contents.into_bytes();
+        let last = tampered.len() - 1;
+        tampered[last] = if tampered[last] == b'0' { b'1' } else { b'0' };
+        fs::write(encrypted, tampered).unwrap();
+        assert!(WatchOnlyWallet::new("tampered", Network::Regtest, None).restore(encrypted, "correct horse").is_err());
+
+        // Without a password the descriptors are written in the clear
+        let plain = dir.path().join("plain");
+        let plain = plain.to_str().unwrap();
+        wallet.backup(plain, "").unwrap();
+        assert_eq!(fs::read_to_string(plain).unwrap(), descriptor.to_string());
+        assert!(restored.restore(plain, "correct horse").is_err());
+        restored.restore(plain, "").unwrap();
+    }
+}
@@ -0,0 +1,111 @@
+//! Hybrid analytics module
+//!
+//! Tallies what the client does on each side of the bridge: EVM transactions
+//! on BOB, checks of the Bitcoin relay, and matches of Bitcoin payments to BOB
+//! deposits. Counters only ever grow; take a snapshot with
+//! [`HybridAnalyticsEngine::collect_metrics`].
+
+use std::sync::Mutex;
+use std::time::Duration;
+
+use crate::layer2::ValidationResult;
+
+use super::{BobError, EvmTransactionReceipt, RelayStatus};
+
+/// Snapshot of the BOB integration's activity
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct BobMetrics {
+    /// EVM transactions mined successfully
+    pub transactions_confirmed: u64,
+    /// EVM transactions mined but reverted
+    pub transactions_reverted: u64,
+    /// EVM transactions that failed before a receipt was seen
+    pub transactions_failed: u64,
+    /// Gas used by mined transactions
+    pub gas_used: u64,
+    /// Total time from submission to receipt of mined transactions
+    pub confirmation_time: Duration,
+    /// Relay checks that found the relay on our chain and close to our tip
+    pub relay_synced: u64,
+    /// Relay checks that found it lagging or on another chain
+    pub relay_out_of_sync: u64,
+    /// Relay checks that could not complete
+    pub relay_errors: u64,
+    /// Bitcoin height the relay reported last
+    pub relay_height: Option<u64>,
+    /// Transaction pairs whose Bitcoin payment matched the BOB deposit
+    pub cross_layer_matched: u64,
+    /// Transaction pairs that did not match
+    pub cross_layer_mismatched: u64,
+    /// Transaction pairs that could not be checked
+    pub cross_layer_errors: u64,
+}
+
+impl BobMetrics {
+    /// Mean time from submission to receipt of mined transactions
+    pub fn average_confirmation_time(&self) -> Option<Duration> {
+        let mined = self.transactions_confirmed + self.transactions_reverted;
+        (mined > 0).then(|| Duration::from_nanos((self.confirmation_time.as_nanos() / mined as u128) as u64))
+    }
+}
+
+/// Hybrid analytics engine for BOB integration
+#[derive(Debug, Default)]
+pub struct HybridAnalyticsEngine {
+    metrics: Mutex<BobMetrics>,
+}
+
+impl HybridAnalyticsEngine {
+    /// Create an engine with all counters at zero
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Record the outcome of submitting a transaction, `elapsed` after submission
+    pub fn record_transaction(&self, result: &Result<EvmTransactionReceipt, BobError>, elapsed: Duration) {
+        let mut metrics = self.metrics.lock().unwrap();
+        match result {
+            Ok(receipt) => {
+                if receipt.status {
+                    metrics.transactions_confirmed += 1;
+                } else {
+                    metrics.transactions_reverted += 1;
+                }
+                metrics.gas_used += receipt.gas_used;
+                metrics.confirmation_time += elapsed;
+            },
+            Err(_) => metrics.transactions_failed += 1,
+        }
+    }
+
+    /// Record the outcome of a relay check
+    pub fn record_relay_status(&self, result: &Result<RelayStatus, BobError>) {
+        let mut metrics = self.metrics.lock().unwrap();
+        match result {
+            Ok(status) => {
+                if status.is_synced {
+                    metrics.relay_synced += 1;
+                } else {
+                    metrics.relay_out_of_sync += 1;
+                }
+                metrics.relay_height = Some(status.last_block_height);
+            },
+            Err(_) => metrics.relay_errors += 1,
+        }
+    }
+
+    /// Record the outcome of checking a Bitcoin/BOB transaction pair
+    pub fn record_cross_layer(&self, result: &Result<ValidationResult, BobError>) {
+        let mut metrics = self.metrics.lock().unwrap();
+        match result {
+            Ok(validation) if validation.valid => metrics.cross_layer_matched += 1,
+            Ok(_) => metrics.cross_layer_mismatched += 1,
+            Err(_) => metrics.cross_layer_errors += 1,
+        }
+    }
+
+    /// Snapshot of the counters
+    pub fn collect_metrics(&self) -> BobMetrics {
+        self.metrics.lock().unwrap().clone()
+    }
+}
@@ -0,0 +1,107 @@
+//! Cross-layer transaction handling module
+//!
+//! A BTC-to-BOB deposit is a Bitcoin payment to the gateway's deposit address,
+//! answered on BOB by the gateway contract emitting
+//! `BtcDeposit(bytes32 indexed btcTxid, address indexed recipient, uint256 amountSats)`.
+//! The Bitcoin txid is in internal byte order, as the contract hashes the raw
+//! transaction itself.
+
+use bitcoin::hashes::Hash;
+use bitcoin::{Script, Transaction, Txid};
+
+use super::evm::{keccak256, Address, H256};
+use super::relay::decode_uint256;
+use super::{BobError, EvmLog, EvmTransactionReceipt};
+
+/// Signature of the gateway's deposit event
+pub const DEPOSIT_EVENT_SIGNATURE: &str = "BtcDeposit(bytes32,address,uint256)";
+
+/// Deposit event decoded from a gateway log
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct DepositEvent {
+    /// Bitcoin transaction funding the deposit
+    pub btc_txid: Txid,
+    /// BOB account credited
+    pub recipient: Address,
+    /// Amount credited, in satoshis
+    pub amount_sats: u64,
+}
+
+impl DepositEvent {
+    /// Decode a log if it is a deposit event emitted by `gateway`
+    pub fn from_log(log: &EvmLog, gateway: &Address) -> Option<Self> {
+        if log.address != *gateway || log.topics.len() != 3 || log.topics[0] != keccak256(DEPOSIT_EVENT_SIGNATURE.as_bytes()) {
+            return None;
+        }
+
+        let recipient_word = &log.topics[2];
+        if recipient_word[..12].iter().any(|b| *b != 0) {
+            return None;
+        }
+        let mut recipient = [0u8; 20];
+        recipient.copy_from_slice(&recipient_word[12..]);
+
+        Some(Self {
+            btc_txid: Txid::from_byte_array(log.topics[1]),
+            recipient,
+            amount_sats: decode_uint256(&log.data).ok()?,
+        })
+    }
+}
+
+/// Bitcoin payment matched to its BOB deposit event
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct CrossLayerMatch {
+    /// Bitcoin transaction
+    pub btc_txid: Txid,
+    /// BOB transaction
+    pub l2_tx_hash: H256,
+    /// Amount paid and credited, in satoshis
+    pub amount_sats: u64,
+    /// BOB account credited
+    pub recipient: Address,
+}
+
+/// Match a Bitcoin payment to the deposit event in a BOB receipt
+///
+/// The receipt must be successful and contain a deposit event from `gateway`
+/// for this Bitcoin transaction, crediting exactly what the transaction paid
+/// to `deposit_script`.
+pub fn match_deposit(
+    btc_tx: &Transaction,
+    deposit_script: &Script,
+    receipt: &EvmTransactionReceipt,
+    l2_tx_hash: H256,
+    gateway: &Address,
+) -> Result<CrossLayerMatch, BobError> {
+    if !receipt.status {
+        return Err(BobError::CrossLayerError("BOB transaction reverted".to_string()));
+    }
+
+    let paid: u64 = btc_tx.output.iter()
+        .filter(|output| output.script_pubkey.as_script() == deposit_script)
+        .map(|output| output.value.to_sat())
+        .sum();
+    if paid == 0 {
+        return Err(BobError::CrossLayerError("Bitcoin transaction pays nothing to the deposit address".to_string()));
+    }
+
+    let btc_txid = btc_tx.compute_txid();
+    let event = receipt.logs.iter()
+        .filter_map(|log| DepositEvent::from_log(log, gateway))
+        .find(|event| event.btc_txid == btc_txid)
+        .ok_or_else(|| BobError::CrossLayerError(format!("No deposit event for {}", btc_txid)))?;
+
+    if event.amount_sats != paid {
+        return Err(BobError::CrossLayerError(format!(
+            "Deposit credits {} sats but the Bitcoin transaction paid {}", event.amount_sats, paid
+        )));
+    }
+
+    Ok(CrossLayerMatch {
+        btc_txid,
+        l2_tx_hash,
+        amount_sats: paid,
+        recipient: event.recipient,
+    })
+}
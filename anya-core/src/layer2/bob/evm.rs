@@ -0,0 +1,844 @@
+//! EVM compatibility module
+//!
+//! Ethereum JSON-RPC client for BOB's EVM layer: a transport abstraction with an
+//! HTTP implementation, typed wrappers for the calls the integration needs, and
+//! local EIP-1559 and EIP-155 transaction signing.
+
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::sync::Arc;
+use std::time::{Duration, Instant};
+
+use async_trait::async_trait;
+use bitcoin::secp256k1::{Message, PublicKey, Secp256k1, SecretKey};
+use serde_json::{json, Value};
+use sha3::{Digest, Keccak256};
+
+use super::{BobError, EvmLog, EvmTransactionReceipt};
+
+/// 20-byte EVM account address
+pub type Address = [u8; 20];
+
+/// 32-byte hash or word
+pub type H256 = [u8; 32];
+
+/// Keccak-256 digest
+pub fn keccak256(data: &[u8]) -> H256 {
+    Keccak256::digest(data).into()
+}
+
+/// Parse a `0x`-prefixed address
+pub fn parse_address(s: &str) -> Result<Address, BobError> {
+    parse_data(s)?
+        .try_into()
+        .map_err(|_| BobError::ConfigError(format!("Invalid address: {}", s)))
+}
+
+/// Format an address as lowercase `0x` hex
+pub fn format_address(address: &Address) -> String {
+    format!("0x{}", hex::encode(address))
+}
+
+/// Parse a `0x`-prefixed 32-byte hash
+pub fn parse_h256(s: &str) -> Result<H256, BobError> {
+    parse_data(s)?
+        .try_into()
+        .map_err(|_| BobError::ConfigError(format!("Invalid 32-byte hash: {}", s)))
+}
+
+/// Format a 32-byte hash as `0x` hex
+pub fn format_h256(hash: &H256) -> String {
+    format!("0x{}", hex::encode(hash))
+}
+
+/// Parse `0x`-prefixed byte data
+pub fn parse_data(s: &str) -> Result<Vec<u8>, BobError> {
+    let digits = s.strip_prefix("0x").unwrap_or(s);
+    hex::decode(digits).map_err(|e| BobError::ConfigError(format!("Invalid hex data {}: {}", s, e)))
+}
+
+/// Parse a JSON-RPC quantity (`0x`-prefixed, no leading zeros)
+fn parse_quantity(value: &Value) -> Result<u128, BobError> {
+    let s = value.as_str()
+        .ok_or_else(|| BobError::ConnectionError(format!("Expected a quantity, got {}", value)))?;
+    let digits = s.strip_prefix("0x")
+        .ok_or_else(|| BobError::ConnectionError(format!("Quantity without 0x prefix: {}", s)))?;
+    u128::from_str_radix(digits, 16)
+        .map_err(|e| BobError::ConnectionError(format!("Invalid quantity {}: {}", s, e)))
+}
+
+fn parse_u64_quantity(value: &Value) -> Result<u64, BobError> {
+    u64::try_from(parse_quantity(value)?)
+        .map_err(|_| BobError::ConnectionError(format!("Quantity out of range: {}", value)))
+}
+
+fn format_quantity(value: u128) -> String {
+    format!("0x{:x}", value)
+}
+
+// Minimal RLP encoding for transaction payloads
+
+fn rlp_bytes(bytes: &[u8]) -> Vec<u8> {
+    if bytes.len() == 1 && bytes[0] < 0x80 {
+        return bytes.to_vec();
+    }
+    let mut out = rlp_length(bytes.len(), 0x80);
+    out.extend_from_slice(bytes);
+    out
+}
+
+fn rlp_uint(value: u128) -> Vec<u8> {
+    let bytes = value.to_be_bytes();
+    let first = bytes.iter().position(|b| *b != 0).unwrap_or(bytes.len());
+    rlp_bytes(&bytes[first..])
+}
+
+fn rlp_word(word: &H256) -> Vec<u8> {
+    let first = word.iter().position(|b| *b != 0).unwrap_or(word.len());
+    rlp_bytes(&word[first..])
+}
+
+fn rlp_list(items: &[Vec<u8>]) -> Vec<u8> {
+    let payload: Vec<u8> = items.concat();
+    let mut out = rlp_length(payload.len(), 0xc0);
+    out.extend(payload);
+    out
+}
+
+fn rlp_length(len: usize, offset: u8) -> Vec<u8> {
+    if len <= 55 {
+        return vec![offset + len as u8];
+    }
+    let bytes = len.to_be_bytes();
+    let first = bytes.iter().position(|b| *b != 0).unwrap_or(bytes.len());
+    let mut out = vec![offset + 55 + (bytes.len() - first) as u8];
+    out.extend_from_slice(&bytes[first..]);
+    out
+}
+
+/// Secp256k1 key that signs BOB transactions locally
+#[derive(Clone)]
+pub struct LocalSigner {
+    secret_key: SecretKey,
+    public_key: PublicKey,
+    address: Address,
+}
+
+impl LocalSigner {
+    /// Create a signer from a secret key
+    pub fn new(secret_key: SecretKey) -> Self {
+        let public_key = PublicKey::from_secret_key(&Secp256k1::signing_only(), &secret_key);
+        Self { secret_key, public_key, address: public_key_address(&public_key) }
+    }
+
+    /// Create a signer from a hex-encoded secret key
+    pub fn from_hex(secret: &str) -> Result<Self, BobError> {
+        let bytes = parse_data(secret)?;
+        let secret_key = SecretKey::from_slice(&bytes)
+            .map_err(|e| BobError::ConfigError(format!("Invalid signing key: {}", e)))?;
+        Ok(Self::new(secret_key))
+    }
+
+    /// Account address of the key
+    pub fn address(&self) -> Address {
+        self.address
+    }
+
+    /// Public key
+    pub fn public_key(&self) -> PublicKey {
+        self.public_key
+    }
+
+    /// Sign a 32-byte hash, returning (y parity, r, s)
+    pub fn sign_hash(&self, hash: &H256) -> (u8, H256, H256) {
+        let signature = Secp256k1::signing_only()
+            .sign_ecdsa_recoverable(&Message::from_digest(*hash), &self.secret_key);
+        let (recovery_id, compact) = signature.serialize_compact();
+
+        let mut r = [0u8; 32];
+        let mut s = [0u8; 32];
+        r.copy_from_slice(&compact[..32]);
+        s.copy_from_slice(&compact[32..]);
+        (recovery_id.to_i32() as u8, r, s)
+    }
+}
+
+impl std::fmt::Debug for LocalSigner {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("LocalSigner").field("address", &format_address(&self.address)).finish()
+    }
+}
+
+/// Account address of a public key: the last 20 bytes of the Keccak-256 of its uncompressed encoding
+pub fn public_key_address(public_key: &PublicKey) -> Address {
+    let hash = keccak256(&public_key.serialize_uncompressed()[1..]);
+    let mut address = [0u8; 20];
+    address.copy_from_slice(&hash[12..]);
+    address
+}
+
+/// EIP-1559 (type 2) transaction
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Eip1559Transaction {
+    /// Chain ID
+    pub chain_id: u64,
+    /// Sender nonce
+    pub nonce: u64,
+    /// Tip paid to the block producer, per gas
+    pub max_priority_fee_per_gas: u128,
+    /// Fee cap, per gas
+    pub max_fee_per_gas: u128,
+    /// Gas limit
+    pub gas_limit: u64,
+    /// Recipient; `None` deploys a contract
+    pub to: Option<Address>,
+    /// Value in wei
+    pub value: u128,
+    /// Call data
+    pub data: Vec<u8>,
+}
+
+/// Signed, serialized transaction
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SignedTransaction {
+    /// Raw bytes for `eth_sendRawTransaction`
+    pub raw: Vec<u8>,
+    /// Transaction hash
+    pub hash: H256,
+}
+
+impl Eip1559Transaction {
+    /// Transaction type byte
+    pub const TYPE: u8 = 0x02;
+
+    fn unsigned_fields(&self) -> Vec<Vec<u8>> {
+        vec![
+            rlp_uint(self.chain_id as u128),
+            rlp_uint(self.nonce as u128),
+            rlp_uint(self.max_priority_fee_per_gas),
+            rlp_uint(self.max_fee_per_gas),
+            rlp_uint(self.gas_limit as u128),
+            rlp_bytes(self.to.as_ref().map(|to| &to[..]).unwrap_or(&[])),
+            rlp_uint(self.value),
+            rlp_bytes(&self.data),
+            // Empty access list
+            rlp_list(&[]),
+        ]
+    }
+
+    /// Hash signed by the sender
+    pub fn signing_hash(&self) -> H256 {
+        let mut payload = vec![Self::TYPE];
+        payload.extend(rlp_list(&self.unsigned_fields()));
+        keccak256(&payload)
+    }
+
+    /// Sign and serialize
+    pub fn sign(&self, signer: &LocalSigner) -> SignedTransaction {
+        let (y_parity, r, s) = signer.sign_hash(&self.signing_hash());
+
+        let mut fields = self.unsigned_fields();
+        fields.push(rlp_uint(y_parity as u128));
+        fields.push(rlp_word(&r));
+        fields.push(rlp_word(&s));
+
+        let mut raw = vec![Self::TYPE];
+        raw.extend(rlp_list(&fields));
+        let hash = keccak256(&raw);
+        SignedTransaction { raw, hash }
+    }
+}
+
+/// Legacy transaction with EIP-155 replay protection
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct LegacyTransaction {
+    /// Chain ID
+    pub chain_id: u64,
+    /// Sender nonce
+    pub nonce: u64,
+    /// Gas price
+    pub gas_price: u128,
+    /// Gas limit
+    pub gas_limit: u64,
+    /// Recipient; `None` deploys a contract
+    pub to: Option<Address>,
+    /// Value in wei
+    pub value: u128,
+    /// Call data
+    pub data: Vec<u8>,
+}
+
+impl LegacyTransaction {
+    fn unsigned_fields(&self) -> Vec<Vec<u8>> {
+        vec![
+            rlp_uint(self.nonce as u128),
+            rlp_uint(self.gas_price),
+            rlp_uint(self.gas_limit as u128),
+            rlp_bytes(self.to.as_ref().map(|to| &to[..]).unwrap_or(&[])),
+            rlp_uint(self.value),
+            rlp_bytes(&self.data),
+        ]
+    }
+
+    /// Hash signed by the sender
+    pub fn signing_hash(&self) -> H256 {
+        let mut fields = self.unsigned_fields();
+        fields.push(rlp_uint(self.chain_id as u128));
+        fields.push(rlp_uint(0));
+        fields.push(rlp_uint(0));
+        keccak256(&rlp_list(&fields))
+    }
+
+    /// Sign and serialize
+    pub fn sign(&self, signer: &LocalSigner) -> SignedTransaction {
+        let (y_parity, r, s) = signer.sign_hash(&self.signing_hash());
+
+        let mut fields = self.unsigned_fields();
+        fields.push(rlp_uint(self.chain_id as u128 * 2 + 35 + y_parity as u128));
+        fields.push(rlp_word(&r));
+        fields.push(rlp_word(&s));
+
+        let raw = rlp_list(&fields);
+        let hash = keccak256(&raw);
+        SignedTransaction { raw, hash }
+    }
+}
+
+/// JSON-RPC transport
+#[async_trait]
+pub trait JsonRpcTransport: Send + Sync {
+    /// Send a request and return its `result`
+    ///
+    /// Transport failures map to [`BobError::ConnectionError`]; error responses
+    /// from the node map to [`BobError::RpcError`].
+    async fn request(&self, method: &str, params: Value) -> Result<Value, BobError>;
+}
+
+/// JSON-RPC over HTTP
+pub struct HttpTransport {
+    client: reqwest::Client,
+    url: String,
+    next_id: AtomicU64,
+}
+
+impl HttpTransport {
+    /// Create a transport for `url` with a per-request timeout
+    pub fn new(url: &str, timeout: Duration) -> Self {
+        // Building only fails if the TLS backend cannot initialize; fall back to defaults
+        let client = reqwest::Client::builder()
+            .timeout(timeout)
+            .build()
+            .unwrap_or_default();
+
+        Self {
+            client,
+            url: url.to_string(),
+            next_id: AtomicU64::new(1),
+        }
+    }
+}
+
+#[async_trait]
+impl JsonRpcTransport for HttpTransport {
+    async fn request(&self, method: &str, params: Value) -> Result<Value, BobError> {
+        let id = self.next_id.fetch_add(1, Ordering::Relaxed);
+        let body = json!({ "jsonrpc": "2.0", "id": id, "method": method, "params": params });
+
+        let response: Value = self.client.post(&self.url)
+            .json(&body)
+            .send()
+            .await
+            .map_err(|e| BobError::ConnectionError(format!("{} failed: {}", method, e)))?
+            .json()
+            .await
+            .map_err(|e| BobError::ConnectionError(format!("{} returned invalid JSON: {}", method, e)))?;
+
+        if let Some(error) = response.get("error") {
+            return Err(BobError::RpcError {
+                code: error.get("code").and_then(Value::as_i64).unwrap_or_default(),
+                message: error.get("message").and_then(Value::as_str).unwrap_or_default().to_string(),
+            });
+        }
+
+        response.get("result").cloned()
+            .ok_or_else(|| BobError::ConnectionError(format!("{} response has no result", method)))
+    }
+}
+
+/// Typed client for the EVM JSON-RPC calls used by the BOB integration
+pub struct EvmRpcClient {
+    transport: Arc<dyn JsonRpcTransport>,
+    max_retries: u32,
+}
+
+impl EvmRpcClient {
+    /// Create a client; transport failures are retried up to `max_retries` times
+    pub fn new(transport: Arc<dyn JsonRpcTransport>, max_retries: u32) -> Self {
+        Self { transport, max_retries }
+    }
+
+    /// Send a request, retrying transport failures with linear backoff
+    pub async fn request(&self, method: &str, params: Value) -> Result<Value, BobError> {
+        let mut attempt = 0;
+        loop {
+            match self.transport.request(method, params.clone()).await {
+                Err(BobError::ConnectionError(_)) if attempt < self.max_retries => {
+                    attempt += 1;
+                    tokio::time::sleep(Duration::from_millis(100 * attempt as u64)).await;
+                },
+                result => return result,
+            }
+        }
+    }
+
+    /// `eth_chainId`
+    pub async fn chain_id(&self) -> Result<u64, BobError> {
+        parse_u64_quantity(&self.request("eth_chainId", json!([])).await?)
+    }
+
+    /// `eth_blockNumber`
+    pub async fn block_number(&self) -> Result<u64, BobError> {
+        parse_u64_quantity(&self.request("eth_blockNumber", json!([])).await?)
+    }
+
+    /// Next nonce for `address`, counting pending transactions
+    pub async fn transaction_count(&self, address: &Address) -> Result<u64, BobError> {
+        let result = self.request("eth_getTransactionCount", json!([format_address(address), "pending"])).await?;
+        parse_u64_quantity(&result)
+    }
+
+    /// Base fee of the latest block
+    pub async fn base_fee(&self) -> Result<u128, BobError> {
+        let block = self.request("eth_getBlockByNumber", json!(["latest", false])).await?;
+        let base_fee = block.get("baseFeePerGas")
+            .ok_or_else(|| BobError::ConnectionError("Latest block has no base fee".to_string()))?;
+        parse_quantity(base_fee)
+    }
+
+    /// Suggested priority fee
+    pub async fn max_priority_fee(&self) -> Result<u128, BobError> {
+        parse_quantity(&self.request("eth_maxPriorityFeePerGas", json!([])).await?)
+    }
+
+    /// Timestamp of block `number`, in seconds
+    pub async fn block_timestamp(&self, number: u64) -> Result<u64, BobError> {
+        let block = self.request("eth_getBlockByNumber", json!([format_quantity(number as u128), false])).await?;
+        let timestamp = block.get("timestamp")
+            .ok_or_else(|| BobError::ConnectionError(format!("Block {} has no timestamp", number)))?;
+        parse_u64_quantity(timestamp)
+    }
+
+    /// `eth_estimateGas` for a call from `from`
+    pub async fn estimate_gas(&self, from: &Address, to: Option<&Address>, value: u128, data: &[u8]) -> Result<u64, BobError> {
+        let mut call = json!({
+            "from": format_address(from),
+            "value": format_quantity(value),
+            "data": format!("0x{}", hex::encode(data)),
+        });
+        if let Some(to) = to {
+            call["to"] = json!(format_address(to));
+        }
+        parse_u64_quantity(&self.request("eth_estimateGas", json!([call])).await?)
+    }
+
+    /// Read-only contract call against the latest block
+    pub async fn call(&self, to: &Address, data: &[u8]) -> Result<Vec<u8>, BobError> {
+        let call = json!({ "to": format_address(to), "data": format!("0x{}", hex::encode(data)) });
+        let result = self.request("eth_call", json!([call, "latest"])).await?;
+        let result = result.as_str()
+            .ok_or_else(|| BobError::ConnectionError("eth_call returned a non-string result".to_string()))?;
+        parse_data(result)
+    }
+
+    /// Broadcast a signed transaction, returning its hash
+    pub async fn send_raw_transaction(&self, raw: &[u8]) -> Result<H256, BobError> {
+        let result = self.request("eth_sendRawTransaction", json!([format!("0x{}", hex::encode(raw))])).await
+            .map_err(|e| match e {
+                BobError::RpcError { message, .. } => BobError::TransactionError(message),
+                e => e,
+            })?;
+        let hash = result.as_str()
+            .ok_or_else(|| BobError::TransactionError("eth_sendRawTransaction returned no hash".to_string()))?;
+        parse_h256(hash)
+    }
+
+    /// Logs emitted by `address` since `from_block`
+    ///
+    /// `topics` filters by position; `None` matches any value.
+    pub async fn logs(&self, address: &Address, topics: &[Option<H256>], from_block: u64) -> Result<Vec<EvmLog>, BobError> {
+        let topics: Vec<Value> = topics.iter()
+            .map(|topic| topic.as_ref().map(|t| json!(format_h256(t))).unwrap_or(Value::Null))
+            .collect();
+        let filter = json!({
+            "address": format_address(address),
+            "topics": topics,
+            "fromBlock": format_quantity(from_block as u128),
+            "toBlock": "latest",
+        });
+
+        self.request("eth_getLogs", json!([filter])).await?
+            .as_array()
+            .ok_or_else(|| BobError::ConnectionError("eth_getLogs returned a non-array result".to_string()))?
+            .iter()
+            .map(parse_log)
+            .collect()
+    }
+
+    /// Receipt of a mined transaction, `None` while pending
+    pub async fn transaction_receipt(&self, hash: &H256) -> Result<Option<EvmTransactionReceipt>, BobError> {
+        let result = self.request("eth_getTransactionReceipt", json!([format_h256(hash)])).await?;
+        if result.is_null() {
+            return Ok(None);
+        }
+        parse_receipt(&result).map(Some)
+    }
+
+    /// Poll for a receipt until it appears or `timeout` passes
+    pub async fn wait_for_receipt(&self, hash: &H256, poll_interval: Duration, timeout: Duration) -> Result<EvmTransactionReceipt, BobError> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            if let Some(receipt) = self.transaction_receipt(hash).await? {
+                return Ok(receipt);
+            }
+            if Instant::now() + poll_interval > deadline {
+                return Err(BobError::TransactionError(format!(
+                    "Transaction {} not mined within {:?}", format_h256(hash), timeout
+                )));
+            }
+            tokio::time::sleep(poll_interval).await;
+        }
+    }
+}
+
+fn parse_receipt(value: &Value) -> Result<EvmTransactionReceipt, BobError> {
+    let field = |name: &str| value.get(name)
+        .ok_or_else(|| BobError::ConnectionError(format!("Receipt is missing {}", name)));
+    let string_field = |name: &str| field(name)?.as_str()
+        .ok_or_else(|| BobError::ConnectionError(format!("Receipt field {} is not a string", name)));
+
+    let logs = field("logs")?.as_array()
+        .ok_or_else(|| BobError::ConnectionError("Receipt logs are not an array".to_string()))?
+        .iter()
+        .map(parse_log)
+        .collect::<Result<Vec<_>, _>>()?;
+
+    Ok(EvmTransactionReceipt {
+        tx_hash: string_field("transactionHash")?.to_string(),
+        block_number: parse_u64_quantity(field("blockNumber")?)?,
+        gas_used: parse_u64_quantity(field("gasUsed")?)?,
+        status: parse_quantity(field("status")?)? == 1,
+        logs,
+    })
+}
+
+fn parse_log(value: &Value) -> Result<EvmLog, BobError> {
+    let address = value.get("address").and_then(Value::as_str)
+        .ok_or_else(|| BobError::ConnectionError("Log has no address".to_string()))?;
+    let topics = value.get("topics").and_then(Value::as_array)
+        .ok_or_else(|| BobError::ConnectionError("Log has no topics".to_string()))?
+        .iter()
+        .map(|topic| topic.as_str()
+            .ok_or_else(|| BobError::ConnectionError("Log topic is not a string".to_string()))
+            .and_then(parse_h256))
+        .collect::<Result<Vec<_>, _>>()?;
+    let data = value.get("data").and_then(Value::as_str).unwrap_or("0x");
+
+    Ok(EvmLog {
+        address: parse_address(address)?,
+        topics,
+        data: parse_data(data)?,
+    })
+}
+
+/// Local JSON-RPC stand-in serving a scripted chain over HTTP
+#[cfg(test)]
+pub(crate) mod stand_in {
+    use super::*;
+    use std::collections::HashMap;
+    use std::sync::Mutex;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    /// Scripted chain state
+    #[derive(Default)]
+    pub struct ChainState {
+        pub chain_id: u64,
+        pub block_number: u64,
+        pub base_fee: u128,
+        pub priority_fee: u128,
+        /// Seconds between blocks; block `n` is timestamped `n * block_time`
+        pub block_time: u64,
+        pub nonces: HashMap<Address, u64>,
+        /// `eth_call` results keyed by (contract, call data)
+        pub calls: HashMap<(Address, Vec<u8>), Vec<u8>>,
+        /// Receipts keyed by transaction hash
+        pub receipts: HashMap<H256, Value>,
+        /// Raw transactions received
+        pub raw_transactions: Vec<Vec<u8>>,
+        /// Polls answered with `null` before a new transaction's receipt appears
+        pub pending_polls: u32,
+        /// Remaining `null` polls per transaction
+        pub polls: HashMap<H256, u32>,
+        /// Logs attached to the receipts of new transactions
+        pub receipt_logs: Vec<EvmLog>,
+        /// Logs served by `eth_getLogs`, as (block number, log)
+        pub logs: Vec<(u64, EvmLog)>,
+    }
+
+    pub struct StandInRpc {
+        pub state: Arc<Mutex<ChainState>>,
+        pub url: String,
+    }
+
+    impl StandInRpc {
+        pub async fn start(state: ChainState) -> Self {
+            let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+            let url = format!("http://{}", listener.local_addr().unwrap());
+            let state = Arc::new(Mutex::new(state));
+
+            let shared = state.clone();
+            tokio::spawn(async move {
+                while let Ok((mut stream, _)) = listener.accept().await {
+                    let state = shared.clone();
+                    tokio::spawn(async move {
+                        let body = read_request(&mut stream).await;
+                        let request: Value = serde_json::from_slice(&body).unwrap();
+                        let method = request["method"].as_str().unwrap_or_default();
+                        let response = match handle(&state, method, &request["params"]) {
+                            Ok(result) => json!({ "jsonrpc": "2.0", "id": request["id"], "result": result }),
+                            Err(message) => json!({
+                                "jsonrpc": "2.0", "id": request["id"],
+                                "error": { "code": -32000, "message": message },
+                            }),
+                        };
+                        let body = response.to_string();
+                        let reply = format!(
+                            "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                            body.len(), body
+                        );
+                        let _ = stream.write_all(reply.as_bytes()).await;
+                    });
+                }
+            });
+
+            Self { state, url }
+        }
+
+        pub fn rpc(&self) -> EvmRpcClient {
+            let transport = HttpTransport::new(&self.url, Duration::from_secs(5));
+            EvmRpcClient::new(Arc::new(transport), 0)
+        }
+    }
+
+    async fn read_request(stream: &mut tokio::net::TcpStream) -> Vec<u8> {
+        let mut buf = Vec::new();
+        let mut chunk = [0u8; 4096];
+        loop {
+            let n = stream.read(&mut chunk).await.unwrap();
+            buf.extend_from_slice(&chunk[..n]);
+            if let Some(end) = buf.windows(4).position(|w| w == b"\r\n\r\n") {
+                let headers = String::from_utf8_lossy(&buf[..end]).to_lowercase();
+                let length: usize = headers.lines()
+                    .find_map(|line| line.strip_prefix("content-length:"))
+                    .map(|v| v.trim().parse().unwrap())
+                    .unwrap_or(0);
+                while buf.len() < end + 4 + length {
+                    let n = stream.read(&mut chunk).await.unwrap();
+                    buf.extend_from_slice(&chunk[..n]);
+                }
+                return buf[end + 4..end + 4 + length].to_vec();
+            }
+        }
+    }
+
+    fn log_json(log: &EvmLog) -> Value {
+        json!({
+            "address": format_address(&log.address),
+            "topics": log.topics.iter().map(format_h256).collect::<Vec<_>>(),
+            "data": format!("0x{}", hex::encode(&log.data)),
+        })
+    }
+
+    fn handle(state: &Mutex<ChainState>, method: &str, params: &Value) -> Result<Value, String> {
+        let mut state = state.lock().unwrap();
+        match method {
+            "eth_chainId" => Ok(json!(format_quantity(state.chain_id as u128))),
+            "eth_blockNumber" => Ok(json!(format_quantity(state.block_number as u128))),
+            "eth_maxPriorityFeePerGas" => Ok(json!(format_quantity(state.priority_fee))),
+            "eth_getBlockByNumber" => {
+                let number = match &params[0] {
+                    Value::String(tag) if tag == "latest" => state.block_number,
+                    number => parse_u64_quantity(number).unwrap(),
+                };
+                Ok(json!({
+                    "number": format_quantity(number as u128),
+                    "timestamp": format_quantity((number * state.block_time) as u128),
+                    "baseFeePerGas": format_quantity(state.base_fee),
+                }))
+            },
+            "eth_getTransactionCount" => {
+                let address = parse_address(params[0].as_str().unwrap()).unwrap();
+                Ok(json!(format_quantity(*state.nonces.get(&address).unwrap_or(&0) as u128)))
+            },
+            "eth_estimateGas" => Ok(json!("0x5208")),
+            "eth_call" => {
+                let to = parse_address(params[0]["to"].as_str().unwrap()).unwrap();
+                let data = parse_data(params[0]["data"].as_str().unwrap()).unwrap();
+                state.calls.get(&(to, data))
+                    .map(|result| json!(format!("0x{}", hex::encode(result))))
+                    .ok_or_else(|| "execution reverted".to_string())
+            },
+            "eth_sendRawTransaction" => {
+                let raw = parse_data(params[0].as_str().unwrap()).unwrap();
+                let hash = keccak256(&raw);
+                state.block_number += 1;
+                let receipt = json!({
+                    "transactionHash": format_h256(&hash),
+                    "blockNumber": format_quantity(state.block_number as u128),
+                    "gasUsed": "0x5208",
+                    "status": "0x1",
+                    "logs": state.receipt_logs.iter().map(log_json).collect::<Vec<_>>(),
+                });
+                state.receipts.insert(hash, receipt);
+                let pending = state.pending_polls;
+                state.polls.insert(hash, pending);
+                state.raw_transactions.push(raw);
+                Ok(json!(format_h256(&hash)))
+            },
+            "eth_getTransactionReceipt" => {
+                let hash = parse_h256(params[0].as_str().unwrap()).unwrap();
+                if let Some(remaining) = state.polls.get_mut(&hash) {
+                    if *remaining > 0 {
+                        *remaining -= 1;
+                        return Ok(Value::Null);
+                    }
+                }
+                Ok(state.receipts.get(&hash).cloned().unwrap_or(Value::Null))
+            },
+            "eth_getLogs" => {
+                let filter = &params[0];
+                let address = parse_address(filter["address"].as_str().unwrap()).unwrap();
+                let from_block = parse_u64_quantity(&filter["fromBlock"]).unwrap();
+                let topics: Vec<Option<H256>> = filter["topics"].as_array().unwrap().iter()
+                    .map(|topic| topic.as_str().map(|t| parse_h256(t).unwrap()))
+                    .collect();
+
+                let matching: Vec<Value> = state.logs.iter()
+                    .filter(|(block, log)| {
+                        *block >= from_block
+                            && log.address == address
+                            && topics.iter().enumerate().all(|(i, topic)| {
+                                topic.is_none() || log.topics.get(i) == topic.as_ref()
+                            })
+                    })
+                    .map(|(_, log)| log_json(log))
+                    .collect();
+                Ok(json!(matching))
+            },
+            _ => Err(format!("Method {} not supported", method)),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitcoin::secp256k1::ecdsa::{RecoverableSignature, RecoveryId};
+
+    #[test]
+    fn test_keccak_and_address_vectors() {
+        assert_eq!(
+            hex::encode(keccak256(b"")),
+            "c5d2460186f7233c927e7db2dcc703c0e500b653ca82273b7bfad8045d85a470"
+        );
+
+        // Well-known address of secret key 1
+        let mut secret = [0u8; 32];
+        secret[31] = 1;
+        let signer = LocalSigner::new(SecretKey::from_slice(&secret).unwrap());
+        assert_eq!(format_address(&signer.address()), "0x7e5f4552091a69125d5dfcb7b8c2659029395bdf");
+    }
+
+    #[test]
+    fn test_rlp_encoding() {
+        assert_eq!(rlp_uint(0), vec![0x80]);
+        assert_eq!(rlp_uint(0x7f), vec![0x7f]);
+        assert_eq!(rlp_uint(0x400), vec![0x82, 0x04, 0x00]);
+        assert_eq!(rlp_bytes(b"dog"), vec![0x83, b'd', b'o', b'g']);
+        assert_eq!(rlp_list(&[rlp_bytes(b"cat"), rlp_bytes(b"dog")]), b"\xc8\x83cat\x83dog".to_vec());
+
+        let long = vec![0xaa; 56];
+        assert_eq!(&rlp_bytes(&long)[..2], &[0xb8, 56]);
+    }
+
+    #[test]
+    fn test_signed_transaction_recovers_sender() {
+        let signer = LocalSigner::from_hex("0x4c0883a69102937d6231471b5dbb6204fe5129617082792ae468d01a3f362318").unwrap();
+        let tx = Eip1559Transaction {
+            chain_id: 60808,
+            nonce: 7,
+            max_priority_fee_per_gas: 1_000_000,
+            max_fee_per_gas: 3_000_000,
+            gas_limit: 21_000,
+            to: Some([0x11; 20]),
+            value: 1_000_000_000_000_000,
+            data: vec![],
+        };
+        let signed = tx.sign(&signer);
+        assert_eq!(signed.raw[0], Eip1559Transaction::TYPE);
+        assert_eq!(signed.hash, keccak256(&signed.raw));
+
+        let (y_parity, r, s) = signer.sign_hash(&tx.signing_hash());
+        let mut compact = [0u8; 64];
+        compact[..32].copy_from_slice(&r);
+        compact[32..].copy_from_slice(&s);
+        let signature = RecoverableSignature::from_compact(&compact, RecoveryId::from_i32(y_parity as i32).unwrap()).unwrap();
+        let recovered = Secp256k1::verification_only()
+            .recover_ecdsa(&Message::from_digest(tx.signing_hash()), &signature)
+            .unwrap();
+        assert_eq!(public_key_address(&recovered), signer.address());
+    }
+
+    #[test]
+    fn test_eip155_transaction_vector() {
+        // Example from EIP-155
+        let signer = LocalSigner::from_hex("0x4646464646464646464646464646464646464646464646464646464646464646").unwrap();
+        let tx = LegacyTransaction {
+            chain_id: 1,
+            nonce: 9,
+            gas_price: 20_000_000_000,
+            gas_limit: 21_000,
+            to: Some([0x35; 20]),
+            value: 1_000_000_000_000_000_000,
+            data: vec![],
+        };
+        assert_eq!(
+            hex::encode(tx.signing_hash()),
+            "daf5a779ae972f972197303d7b574746c7ef83eadac0f2791ad23db92e4c8e53"
+        );
+        assert_eq!(
+            hex::encode(tx.sign(&signer).raw),
+            "f86c098504a817c800825208943535353535353535353535353535353535353535880de0b6b3a76400008025a028ef61340bd939bc2195fe537567866003e1a15d3c71ff63e1590620aa636276a067cbe9d8997f761aecb703304b3800ccf555c9f3dc64214b297fb1966a3b6d83"
+        );
+    }
+
+    #[tokio::test]
+    async fn test_receipt_polling_against_stand_in() {
+        let rpc = stand_in::StandInRpc::start(stand_in::ChainState {
+            chain_id: 60808,
+            pending_polls: 2,
+            ..Default::default()
+        }).await;
+        let client = rpc.rpc();
+
+        assert_eq!(client.chain_id().await.unwrap(), 60808);
+        let hash = client.send_raw_transaction(&[0x02, 0xc0]).await.unwrap();
+        assert!(client.transaction_receipt(&hash).await.unwrap().is_none());
+
+        let receipt = client.wait_for_receipt(&hash, Duration::from_millis(10), Duration::from_secs(5)).await.unwrap();
+        assert!(receipt.status);
+        assert_eq!(receipt.tx_hash, format_h256(&hash));
+
+        // Node errors surface as RPC errors
+        assert!(matches!(client.call(&[0u8; 20], &[1, 2, 3, 4]).await, Err(BobError::RpcError { .. })));
+    }
+}
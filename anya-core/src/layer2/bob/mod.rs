@@ -14,13 +14,14 @@
 //! - BitVM integration for optimistic rollups
 //! - Hybrid analytics for cross-layer operations
 
+use std::str::FromStr;
 use std::sync::{Arc, Mutex};
-use std::time::Duration;
+use std::time::{Duration, Instant};
+
+use bitcoin::{Address as BtcAddress, ScriptBuf, Transaction as BtcTransaction};
+use tracing::{info, warn};
 
 // Internal imports
-use crate::bitcoin::types::{BitcoinAddress, Transaction as BtcTransaction};
-use crate::core::performance::Metrics;
-use crate::security::validation::ValidationResult;
 use crate::{
     AnyaError,
     layer2::{
@@ -33,8 +34,16 @@
         Proof,
         VerificationResult,
         ValidationResult,
+        proof::ProofVerifier,
     },
 };
+pub use self::analytics::{BobMetrics, HybridAnalyticsEngine};
+use self::cross_layer::CrossLayerMatch;
+use self::evm::{
+    format_address, format_h256, parse_address, parse_h256, Eip1559Transaction, EvmRpcClient, HttpTransport,
+    JsonRpcTransport, LocalSigner,
+};
+use self::relay::RelayContract;
 
 /// Configuration for the BOB Layer 2 integration
 #[derive(Clone, Debug)]
@@ -51,6 +60,18 @@ pub struct BobConfig {
     pub max_retries: u32,
     /// Whether to validate the relay state against Bitcoin
     pub validate_relay: bool,
+    /// Address of the Bitcoin light-client contract on BOB
+    pub relay_contract: Option<String>,
+    /// Blocks the relay may trail our Bitcoin tip and still count as synced
+    pub max_relay_lag: u64,
+    /// Address of the BTC deposit gateway contract on BOB
+    pub gateway_contract: Option<String>,
+    /// Bitcoin address deposits are paid to
+    pub deposit_address: Option<String>,
+    /// Interval between receipt polls in milliseconds
+    pub receipt_poll_interval_ms: u64,
+    /// How long to wait for a transaction to be mined in milliseconds
+    pub receipt_timeout_ms: u64,
 }
 
 impl Default for BobConfig {
@@ -62,6 +83,12 @@ fn default() -> Self {
             timeout_ms: 30000,
             max_retries: 3,
             validate_relay: true,
+            relay_contract: None,
+            max_relay_lag: 6,
+            gateway_contract: None,
+            deposit_address: None,
+            receipt_poll_interval_ms: 1000,
+            receipt_timeout_ms: 120_000,
         }
     }
 }
@@ -79,11 +106,18 @@ pub struct BobClient {
 impl BobClient {
     /// Create a new BOB client with the provided configuration
     pub fn new(config: BobConfig) -> Self {
-        let relay_monitor = BitcoinRelayMonitor::new(&config);
-        let evm_adapter = EvmAdapter::new(&config);
+        let transport = HttpTransport::new(&config.rpc_url, Duration::from_millis(config.timeout_ms));
+        Self::with_transport(config, Arc::new(transport))
+    }
+
+    /// Create a client over a custom JSON-RPC transport
+    pub fn with_transport(config: BobConfig, transport: Arc<dyn JsonRpcTransport>) -> Self {
+        let rpc = Arc::new(EvmRpcClient::new(transport, config.max_retries));
+        let relay_monitor = BitcoinRelayMonitor::new(&config, rpc.clone());
+        let evm_adapter = EvmAdapter::new(&config, rpc.clone());
         let bitvm_validator = BitVMValidator::new(&config);
-        let cross_layer_manager = CrossLayerTransactionManager::new(&config);
-        let analytics_engine = HybridAnalyticsEngine::new(&config);
+        let cross_layer_manager = CrossLayerTransactionManager::new(&config, rpc);
+        let analytics_engine = HybridAnalyticsEngine::new();
         
         Self {
             config,
@@ -94,6 +128,18 @@ pub fn new(config: BobConfig) -> Self {
             analytics_engine,
         }
     }
+
+    /// Sign transactions with a local key
+    pub fn with_signer(mut self, signer: LocalSigner) -> Self {
+        self.evm_adapter.signer = Some(signer);
+        self
+    }
+
+    /// Compare the relay against a locally tracked Bitcoin header chain
+    pub fn with_bitcoin_headers(mut self, headers: ProofVerifier) -> Self {
+        self.relay_monitor.bitcoin_headers = headers;
+        self
+    }
     
     /// Check the health of the BOB Layer 2 connection
     pub async fn check_health(&self) -> Result<bool, BobError> {
@@ -101,14 +147,21 @@ pub async fn check_health(&self) -> Result<bool, BobError> {
         let rpc_status = self.evm_adapter.check_connection().await?;
         
         // Check relay status
-        let relay_status = self.relay_monitor.check_relay_status().await?;
+        let relay_status = if self.config.validate_relay {
+            self.get_relay_status().await?.is_synced
+        } else {
+            true
+        };
         
         Ok(rpc_status && relay_status)
     }
     
     /// Submit a transaction to the BOB network
     pub async fn submit_transaction(&self, transaction: EvmTransaction) -> Result<EvmTransactionReceipt, BobError> {
-        self.evm_adapter.send_transaction(transaction).await
+        let submitted = Instant::now();
+        let result = self.evm_adapter.send_transaction(transaction).await;
+        self.analytics_engine.record_transaction(&result, submitted.elapsed());
+        result
     }
     
     /// Verify a cross-layer transaction between Bitcoin and BOB
@@ -117,86 +170,184 @@ pub async fn verify_cross_layer_transaction(
         btc_tx: BtcTransaction,
         l2_tx: EvmTransaction
     ) -> Result<ValidationResult, BobError> {
-        self.cross_layer_manager.verify_transaction_pair(btc_tx, l2_tx).await
+        let result = self.cross_layer_manager.verify_transaction_pair(btc_tx, l2_tx).await;
+        self.analytics_engine.record_cross_layer(&result);
+        result
     }
     
     /// Get the status of the Bitcoin relay
     pub async fn get_relay_status(&self) -> Result<RelayStatus, BobError> {
-        self.relay_monitor.get_status().await
+        let result = self.relay_monitor.get_status().await;
+        self.analytics_engine.record_relay_status(&result);
+        result
     }
     
     /// Verify BitVM proofs for an optimistic rollup transaction
+    ///
+    /// Not supported yet, see [`BitVMValidator::verify_proof`].
     pub async fn verify_bitvm_proof(&self, proof: BitVMProof) -> Result<bool, BobError> {
         self.bitvm_validator.verify_proof(proof).await
     }
     
-    /// Get performance metrics for the BOB Layer 2 integration
-    pub fn get_metrics(&self) -> Metrics {
+    /// Get activity metrics for the BOB Layer 2 integration
+    pub fn get_metrics(&self) -> BobMetrics {
         self.analytics_engine.collect_metrics()
     }
 }
 
 /// Bitcoin relay monitoring component
+///
+/// Compares BOB's light-client contract with our own Bitcoin header chain.
 pub struct BitcoinRelayMonitor {
     config: BobConfig,
+    rpc: Arc<EvmRpcClient>,
+    bitcoin_headers: ProofVerifier,
     last_status: Arc<Mutex<Option<RelayStatus>>>,
 }
 
 impl BitcoinRelayMonitor {
     /// Create a new relay monitor
-    pub fn new(config: &BobConfig) -> Self {
+    pub fn new(config: &BobConfig, rpc: Arc<EvmRpcClient>) -> Self {
         Self {
             config: config.clone(),
+            rpc,
+            bitcoin_headers: ProofVerifier::default(),
             last_status: Arc::new(Mutex::new(None)),
         }
     }
     
     /// Check if the relay is operating correctly
     pub async fn check_relay_status(&self) -> Result<bool, BobError> {
-        // Implementation would check relay status
-        Ok(true)
+        Ok(self.get_status().await?.is_synced)
     }
     
     /// Get detailed status of the relay
+    ///
+    /// The relay is synced when it trails our tip by at most `max_relay_lag`
+    /// blocks and its best block is on our best chain.
     pub async fn get_status(&self) -> Result<RelayStatus, BobError> {
-        // Implementation would retrieve relay status
-        Ok(RelayStatus {
-            last_block_height: 800000,
-            last_bitcoin_hash: "000000000000000000000000000000000000000000000000000000000000000".to_string(),
-            is_synced: true,
+        let contract = RelayContract {
+            address: parse_address(self.config.relay_contract.as_deref()
+                .ok_or_else(|| BobError::ConfigError("No relay contract configured".to_string()))?)?,
+        };
+        let (_, local_height) = self.bitcoin_headers.tip()
+            .ok_or_else(|| BobError::RelayError("No local Bitcoin headers to compare against".to_string()))?;
+
+        let relay_height = contract.best_height(&self.rpc).await?;
+        let relay_hash = contract.block_hash(&self.rpc, relay_height).await?;
+
+        let local_hash = u32::try_from(relay_height).ok().and_then(|h| self.bitcoin_headers.hash_at(h));
+        let lag = (local_height as u64).saturating_sub(relay_height);
+        let is_synced = local_hash == Some(relay_hash) && lag <= self.config.max_relay_lag;
+
+        if local_hash.is_some() && local_hash != Some(relay_hash) {
+            warn!("BOB relay block {} at height {} is not on our best chain", relay_hash, relay_height);
+        }
+
+        let status = RelayStatus {
+            last_block_height: relay_height,
+            last_bitcoin_hash: relay_hash.to_string(),
+            is_synced,
             last_update_time: chrono::Utc::now(),
-        })
+        };
+        *self.last_status.lock().unwrap() = Some(status.clone());
+        Ok(status)
+    }
+
+    /// Status from the last check, if any
+    pub fn last_status(&self) -> Option<RelayStatus> {
+        self.last_status.lock().unwrap().clone()
     }
 }
 
 /// EVM adapter for interacting with BOB's EVM compatibility layer
 pub struct EvmAdapter {
     config: BobConfig,
+    rpc: Arc<EvmRpcClient>,
+    signer: Option<LocalSigner>,
 }
 
 impl EvmAdapter {
     /// Create a new EVM adapter
-    pub fn new(config: &BobConfig) -> Self {
+    pub fn new(config: &BobConfig, rpc: Arc<EvmRpcClient>) -> Self {
         Self {
             config: config.clone(),
+            rpc,
+            signer: None,
         }
     }
     
-    /// Check connection to the EVM node
+    /// Check connection to the EVM node and that it serves the configured chain
     pub async fn check_connection(&self) -> Result<bool, BobError> {
-        // Implementation would check EVM node connection
+        let chain_id = self.rpc.chain_id().await?;
+        if chain_id != self.config.chain_id {
+            return Err(BobError::ConfigError(format!(
+                "Node serves chain {} but {} is configured", chain_id, self.config.chain_id
+            )));
+        }
         Ok(true)
     }
     
-    /// Send a transaction to the EVM network
+    /// Sign, send and wait for an EIP-1559 transaction
+    ///
+    /// A zero `gas_limit` is estimated; a non-zero `gas_price` caps the fee per
+    /// gas. A receipt with `status == false` means the transaction reverted.
     pub async fn send_transaction(&self, transaction: EvmTransaction) -> Result<EvmTransactionReceipt, BobError> {
-        // Implementation would send transaction to EVM node
-        Ok(EvmTransactionReceipt {
-            tx_hash: "0x0000000000000000000000000000000000000000000000000000000000000000".to_string(),
-            block_number: 1000000,
-            gas_used: 21000,
-            status: true,
-        })
+        let signer = self.signer.as_ref()
+            .ok_or_else(|| BobError::ConfigError("No signing key configured".to_string()))?;
+        let from = signer.address();
+        if !transaction.from.is_empty() && parse_address(&transaction.from)? != from {
+            return Err(BobError::TransactionError(format!(
+                "Transaction is from {} but the signer is {}", transaction.from, format_address(&from)
+            )));
+        }
+        let to = transaction.to.as_deref().map(parse_address).transpose()?;
+
+        let nonce = self.rpc.transaction_count(&from).await?;
+        let base_fee = self.rpc.base_fee().await?;
+        let mut priority_fee = self.rpc.max_priority_fee().await?;
+        let max_fee = if transaction.gas_price > 0 {
+            transaction.gas_price as u128
+        } else {
+            base_fee * 2 + priority_fee
+        };
+        if max_fee < base_fee {
+            return Err(BobError::TransactionError(format!(
+                "Fee cap {} is below the base fee {}", max_fee, base_fee
+            )));
+        }
+        priority_fee = priority_fee.min(max_fee);
+
+        let gas_limit = if transaction.gas_limit == 0 {
+            self.rpc.estimate_gas(&from, to.as_ref(), transaction.value, &transaction.data).await?
+        } else {
+            transaction.gas_limit
+        };
+
+        let signed = Eip1559Transaction {
+            chain_id: self.config.chain_id,
+            nonce,
+            max_priority_fee_per_gas: priority_fee,
+            max_fee_per_gas: max_fee,
+            gas_limit,
+            to,
+            value: transaction.value,
+            data: transaction.data,
+        }.sign(signer);
+
+        let hash = self.rpc.send_raw_transaction(&signed.raw).await?;
+        if hash != signed.hash {
+            return Err(BobError::TransactionError(format!(
+                "Node returned hash {} for transaction {}", format_h256(&hash), format_h256(&signed.hash)
+            )));
+        }
+        info!("Sent BOB transaction {} (nonce {})", format_h256(&hash), nonce);
+
+        self.rpc.wait_for_receipt(
+            &hash,
+            Duration::from_millis(self.config.receipt_poll_interval_ms),
+            Duration::from_millis(self.config.receipt_timeout_ms),
+        ).await
     }
 }
 
@@ -214,24 +365,48 @@ pub fn new(config: &BobConfig) -> Self {
     }
     
     /// Verify a BitVM proof
+    ///
+    /// BitVM claims are settled by a challenge-response game on Bitcoin, not by
+    /// checking a proof locally, and BOB does not run it yet. Always fails with
+    /// [`BobError::BitVMError`] rather than vouching for a proof it cannot check.
     pub async fn verify_proof(&self, proof: BitVMProof) -> Result<bool, BobError> {
-        // Implementation would verify BitVM proofs
-        Ok(true)
+        Err(BobError::BitVMError(format!(
+            "Cannot verify proof {} for {}: BitVM verification is not supported", proof.id, proof.tx_hash
+        )))
     }
 }
 
 /// Cross-layer transaction manager
 pub struct CrossLayerTransactionManager {
     config: BobConfig,
+    rpc: Arc<EvmRpcClient>,
 }
 
 impl CrossLayerTransactionManager {
     /// Create a new cross-layer transaction manager
-    pub fn new(config: &BobConfig) -> Self {
+    pub fn new(config: &BobConfig, rpc: Arc<EvmRpcClient>) -> Self {
         Self {
             config: config.clone(),
+            rpc,
         }
     }
+
+    /// Match a Bitcoin deposit to the gateway event emitted by a BOB transaction
+    pub async fn match_transaction_pair(
+        &self,
+        btc_tx: &BtcTransaction,
+        l2_tx_hash: &str,
+    ) -> Result<CrossLayerMatch, BobError> {
+        let gateway = parse_address(self.config.gateway_contract.as_deref()
+            .ok_or_else(|| BobError::ConfigError("No gateway contract configured".to_string()))?)?;
+        let deposit_script = self.deposit_script()?;
+
+        let l2_tx_hash = parse_h256(l2_tx_hash)?;
+        let receipt = self.rpc.transaction_receipt(&l2_tx_hash).await?
+            .ok_or_else(|| BobError::CrossLayerError(format!("BOB transaction {} is not mined", format_h256(&l2_tx_hash))))?;
+
+        cross_layer::match_deposit(btc_tx, &deposit_script, &receipt, l2_tx_hash, &gateway)
+    }
     
     /// Verify a pair of Bitcoin and BOB transactions
     pub async fn verify_transaction_pair(
@@ -239,28 +414,19 @@ pub async fn verify_transaction_pair(
         btc_tx: BtcTransaction,
         l2_tx: EvmTransaction
     ) -> Result<ValidationResult, BobError> {
-        // Implementation would verify cross-layer transactions
-        Ok(ValidationResult::Valid)
-    }
-}
-
-/// Hybrid analytics engine for BOB integration
-pub struct HybridAnalyticsEngine {
-    config: BobConfig,
-}
-
-impl HybridAnalyticsEngine {
-    /// Create a new hybrid analytics engine
-    pub fn new(config: &BobConfig) -> Self {
-        Self {
-            config: config.clone(),
+        match self.match_transaction_pair(&btc_tx, &l2_tx.hash).await {
+            Ok(_) => Ok(ValidationResult { valid: true, error: None }),
+            Err(BobError::CrossLayerError(e)) => Ok(ValidationResult { valid: false, error: Some(e) }),
+            Err(e) => Err(e),
         }
     }
-    
-    /// Collect metrics from the BOB integration
-    pub fn collect_metrics(&self) -> Metrics {
-        // Implementation would collect metrics
-        Metrics::default()
+
+    fn deposit_script(&self) -> Result<ScriptBuf, BobError> {
+        let address = self.config.deposit_address.as_deref()
+            .ok_or_else(|| BobError::ConfigError("No deposit address configured".to_string()))?;
+        BtcAddress::from_str(address)
+            .map(|address| address.assume_checked().script_pubkey())
+            .map_err(|e| BobError::ConfigError(format!("Invalid deposit address: {}", e)))
     }
 }
 
@@ -307,6 +473,19 @@ pub struct EvmTransactionReceipt {
     pub gas_used: u64,
     /// Transaction status (true = success, false = failure)
     pub status: bool,
+    /// Logs emitted by the transaction
+    pub logs: Vec<EvmLog>,
+}
+
+/// EVM event log
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct EvmLog {
+    /// Emitting contract
+    pub address: evm::Address,
+    /// Indexed topics, the first being the event signature hash
+    pub topics: Vec<evm::H256>,
+    /// Non-indexed event data
+    pub data: Vec<u8>,
 }
 
 /// BitVM proof structure
@@ -332,6 +511,10 @@ pub enum BobError {
     /// Transaction submission error
     #[error("Transaction submission error: {0}")]
     TransactionError(String),
+
+    /// Error response from the JSON-RPC node
+    #[error("RPC error {code}: {message}")]
+    RpcError { code: i64, message: String },
     
     /// Relay validation error
     #[error("Relay validation error: {0}")]
@@ -360,27 +543,213 @@ pub enum BobError {
 // Module exports
 pub mod relay;
 pub mod evm;
-pub mod bitvm;
 pub mod cross_layer;
 pub mod analytics;
 
 // Empty module implementations to be filled in later
-pub mod relay {
-    //! Bitcoin relay interaction module
-}
-
-pub mod evm {
-    //! EVM compatibility module
-}
-
 pub mod bitvm {
     //! BitVM integration module
 }
 
-pub mod cross_layer {
-    //! Cross-layer transaction handling module
-}
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::evm::{keccak256, stand_in::{ChainState, StandInRpc}};
+    use super::relay::{encode_uint256, selector, BEST_HEIGHT_SIGNATURE, BLOCK_HASH_SIGNATURE};
+    use crate::layer2::proof::HeaderChain;
+    use bitcoin::block::{Header, Version as BlockVersion};
+    use bitcoin::hashes::Hash;
+    use bitcoin::{absolute, transaction, Amount, BlockHash, CompactTarget, Network, TxMerkleNode, TxOut};
+    use serde_json::json;
+
+    const RELAY: [u8; 20] = [0xaa; 20];
+    const GATEWAY: [u8; 20] = [0xbb; 20];
+
+    fn config(rpc: &StandInRpc) -> BobConfig {
+        BobConfig {
+            rpc_url: rpc.url.clone(),
+            chain_id: 60808,
+            max_retries: 0,
+            relay_contract: Some(format_address(&RELAY)),
+            gateway_contract: Some(format_address(&GATEWAY)),
+            deposit_address: Some(BtcAddress::p2wsh(&ScriptBuf::new(), Network::Regtest).to_string()),
+            receipt_poll_interval_ms: 10,
+            receipt_timeout_ms: 5_000,
+            ..BobConfig::default()
+        }
+    }
+
+    /// Regtest-difficulty header chain of `length` blocks
+    fn headers(length: usize) -> Vec<Header> {
+        let mut chain: Vec<Header> = Vec::new();
+        for i in 0..length {
+            let mut header = Header {
+                version: BlockVersion::TWO,
+                prev_blockhash: chain.last().map(|h| h.block_hash()).unwrap_or_else(BlockHash::all_zeros),
+                merkle_root: TxMerkleNode::all_zeros(),
+                time: 1_700_000_000 + 600 * i as u32,
+                bits: CompactTarget::from_consensus(0x207fffff),
+                nonce: 0,
+            };
+            while header.validate_pow(header.target()).is_err() {
+                header.nonce += 1;
+            }
+            chain.push(header);
+        }
+        chain
+    }
+
+    fn set_relay(state: &mut ChainState, height: u64, hash: BlockHash) {
+        state.calls.insert((RELAY, selector(BEST_HEIGHT_SIGNATURE).to_vec()), encode_uint256(height).to_vec());
+        let mut call = selector(BLOCK_HASH_SIGNATURE).to_vec();
+        call.extend_from_slice(&encode_uint256(height));
+        state.calls.insert((RELAY, call), hash.to_byte_array().to_vec());
+    }
+
+    #[tokio::test]
+    async fn test_relay_status_against_local_headers() {
+        let chain = headers(10);
+        let verifier = ProofVerifier::new(HeaderChain::from_checkpoint(chain[0], 800_000, Network::Regtest).unwrap());
+        for header in &chain[1..] {
+            verifier.add_header(*header).unwrap();
+        }
+
+        let rpc = StandInRpc::start(ChainState { chain_id: 60808, ..Default::default() }).await;
+        let client = BobClient::new(config(&rpc)).with_bitcoin_headers(verifier);
+
+        // Relay three blocks behind our tip, on our chain
+        set_relay(&mut rpc.state.lock().unwrap(), 800_006, chain[6].block_hash());
+        let status = client.get_relay_status().await.unwrap();
+        assert_eq!(status.last_block_height, 800_006);
+        assert!(status.is_synced);
+        assert!(client.check_health().await.unwrap());
 
-pub mod analytics {
-    //! Hybrid analytics module
-} 
\ No newline at end of file
+        // Too far behind
+        set_relay(&mut rpc.state.lock().unwrap(), 800_002, chain[2].block_hash());
+        assert!(!client.get_relay_status().await.unwrap().is_synced);
+
+        // On a different chain
+        set_relay(&mut rpc.state.lock().unwrap(), 800_008, BlockHash::from_byte_array([7; 32]));
+        assert!(!client.get_relay_status().await.unwrap().is_synced);
+
+        let metrics = client.get_metrics();
+        assert_eq!((metrics.relay_synced, metrics.relay_out_of_sync, metrics.relay_errors), (2, 2, 0));
+        assert_eq!(metrics.relay_height, Some(800_008));
+    }
+
+    #[tokio::test]
+    async fn test_send_eip1559_transaction() {
+        let signer = LocalSigner::from_hex("0x4c0883a69102937d6231471b5dbb6204fe5129617082792ae468d01a3f362318").unwrap();
+        let mut state = ChainState {
+            chain_id: 60808,
+            base_fee: 1_000,
+            priority_fee: 100,
+            pending_polls: 2,
+            ..Default::default()
+        };
+        state.nonces.insert(signer.address(), 4);
+        let rpc = StandInRpc::start(state).await;
+        let client = BobClient::new(config(&rpc)).with_signer(signer.clone());
+
+        let transaction = EvmTransaction {
+            hash: String::new(),
+            from: format_address(&signer.address()),
+            to: Some(format_address(&[0x11; 20])),
+            value: 5,
+            gas_limit: 0,
+            gas_price: 0,
+            data: vec![],
+        };
+        let receipt = client.submit_transaction(transaction.clone()).await.unwrap();
+        assert!(receipt.status);
+
+        let raw = rpc.state.lock().unwrap().raw_transactions[0].clone();
+        assert_eq!(receipt.tx_hash, format_h256(&keccak256(&raw)));
+        let expected = Eip1559Transaction {
+            chain_id: 60808,
+            nonce: 4,
+            max_priority_fee_per_gas: 100,
+            max_fee_per_gas: 2_100,
+            gas_limit: 21_000,
+            to: Some([0x11; 20]),
+            value: 5,
+            data: vec![],
+        }.sign(&signer);
+        assert_eq!(raw, expected.raw);
+
+        // A fee cap under the base fee is refused before signing
+        let capped = EvmTransaction { gas_price: 500, ..transaction };
+        assert!(matches!(client.submit_transaction(capped).await, Err(BobError::TransactionError(_))));
+
+        let metrics = client.get_metrics();
+        assert_eq!((metrics.transactions_confirmed, metrics.transactions_reverted, metrics.transactions_failed), (1, 0, 1));
+        assert_eq!(metrics.gas_used, receipt.gas_used);
+        assert!(metrics.average_confirmation_time().is_some());
+    }
+
+    #[tokio::test]
+    async fn test_cross_layer_deposit_match() {
+        let rpc = StandInRpc::start(ChainState { chain_id: 60808, ..Default::default() }).await;
+        let config = config(&rpc);
+        let deposit_script = BtcAddress::from_str(config.deposit_address.as_deref().unwrap())
+            .unwrap().assume_checked().script_pubkey();
+
+        let btc_tx = BtcTransaction {
+            version: transaction::Version::TWO,
+            lock_time: absolute::LockTime::ZERO,
+            input: vec![],
+            output: vec![TxOut { value: Amount::from_sat(250_000), script_pubkey: deposit_script }],
+        };
+
+        let recipient = [0x22u8; 20];
+        let mut recipient_topic = [0u8; 32];
+        recipient_topic[12..].copy_from_slice(&recipient);
+        let l2_hash = [0x33u8; 32];
+        let receipt = |amount: u64| json!({
+            "transactionHash": format_h256(&l2_hash),
+            "blockNumber": "0x10",
+            "gasUsed": "0x5208",
+            "status": "0x1",
+            "logs": [{
+                "address": format_address(&GATEWAY),
+                "topics": [
+                    format_h256(&keccak256(cross_layer::DEPOSIT_EVENT_SIGNATURE.as_bytes())),
+                    format_h256(&btc_tx.compute_txid().to_byte_array()),
+                    format_h256(&recipient_topic),
+                ],
+                "data": format_h256(&encode_uint256(amount)),
+            }],
+        });
+        rpc.state.lock().unwrap().receipts.insert(l2_hash, receipt(250_000));
+
+        let client = BobClient::new(config);
+        let l2_tx = EvmTransaction {
+            hash: format_h256(&l2_hash),
+            from: String::new(),
+            to: None,
+            value: 0,
+            gas_limit: 0,
+            gas_price: 0,
+            data: vec![],
+        };
+
+        let matched = client.cross_layer_manager.match_transaction_pair(&btc_tx, &l2_tx.hash).await.unwrap();
+        assert_eq!((matched.amount_sats, matched.recipient), (250_000, recipient));
+        assert!(client.verify_cross_layer_transaction(btc_tx.clone(), l2_tx.clone()).await.unwrap().valid);
+
+        // Credited amount differs from the Bitcoin payment
+        rpc.state.lock().unwrap().receipts.insert(l2_hash, receipt(300_000));
+        let result = client.verify_cross_layer_transaction(btc_tx, l2_tx).await.unwrap();
+        assert!(!result.valid);
+
+        let metrics = client.get_metrics();
+        assert_eq!((metrics.cross_layer_matched, metrics.cross_layer_mismatched), (1, 1));
+    }
+
+    #[tokio::test]
+    async fn test_bitvm_proofs_are_not_vouched_for() {
+        let client = BobClient::new(BobConfig::default());
+        let proof = BitVMProof { id: "p1".to_string(), tx_hash: format_h256(&[1; 32]), proof_data: vec![1, 2, 3], block_number: 5 };
+        assert!(matches!(client.verify_bitvm_proof(proof).await, Err(BobError::BitVMError(_))));
+    }
+}
@@ -0,0 +1,68 @@
+//! Bitcoin relay interaction module
+//!
+//! BOB tracks Bitcoin through an on-chain light-client contract. The monitor
+//! expects the contract to expose:
+//!
+//! - `getBestBlockHeight() returns (uint256)`
+//! - `getBlockHash(uint256 height) returns (bytes32)`, in internal byte order
+
+use bitcoin::hashes::Hash;
+use bitcoin::BlockHash;
+
+use super::evm::{keccak256, Address, EvmRpcClient};
+use super::BobError;
+
+/// Signature of the best-height getter
+pub const BEST_HEIGHT_SIGNATURE: &str = "getBestBlockHeight()";
+
+/// Signature of the block hash getter
+pub const BLOCK_HASH_SIGNATURE: &str = "getBlockHash(uint256)";
+
+/// Four-byte ABI selector of a function signature
+pub fn selector(signature: &str) -> [u8; 4] {
+    let hash = keccak256(signature.as_bytes());
+    [hash[0], hash[1], hash[2], hash[3]]
+}
+
+/// ABI-encode a `uint256`
+pub fn encode_uint256(value: u64) -> [u8; 32] {
+    let mut word = [0u8; 32];
+    word[24..].copy_from_slice(&value.to_be_bytes());
+    word
+}
+
+/// Decode a `uint256` that must fit in a `u64`
+pub fn decode_uint256(word: &[u8]) -> Result<u64, BobError> {
+    if word.len() != 32 || word[..24].iter().any(|b| *b != 0) {
+        return Err(BobError::RelayError("Expected a uint256 fitting in 64 bits".to_string()));
+    }
+    let mut bytes = [0u8; 8];
+    bytes.copy_from_slice(&word[24..]);
+    Ok(u64::from_be_bytes(bytes))
+}
+
+/// BOB's Bitcoin light-client contract
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct RelayContract {
+    /// Contract address
+    pub address: Address,
+}
+
+impl RelayContract {
+    /// Best Bitcoin height known to the contract
+    pub async fn best_height(&self, rpc: &EvmRpcClient) -> Result<u64, BobError> {
+        let result = rpc.call(&self.address, &selector(BEST_HEIGHT_SIGNATURE)).await?;
+        decode_uint256(&result)
+    }
+
+    /// Bitcoin block hash the contract stores at `height`
+    pub async fn block_hash(&self, rpc: &EvmRpcClient, height: u64) -> Result<BlockHash, BobError> {
+        let mut data = selector(BLOCK_HASH_SIGNATURE).to_vec();
+        data.extend_from_slice(&encode_uint256(height));
+
+        let result = rpc.call(&self.address, &data).await?;
+        let bytes: [u8; 32] = result.try_into()
+            .map_err(|_| BobError::RelayError("Expected a bytes32 block hash".to_string()))?;
+        Ok(BlockHash::from_byte_array(bytes))
+    }
+}
@@ -11,6 +11,7 @@
         Proof,
         VerificationResult,
         ValidationResult,
+        proof::{ProofVerifier, WithProofVerifier},
     },
 };
 use async_trait::async_trait;
@@ -19,6 +20,7 @@
 pub struct DlcProtocol {
     initialized: bool,
     connected: bool,
+    proof_verifier: ProofVerifier,
 }
 
 impl DlcProtocol {
@@ -26,10 +28,17 @@ pub fn new() -> Self {
         Self {
             initialized: false,
             connected: false,
+            proof_verifier: ProofVerifier::default(),
         }
     }
 }
 
+impl WithProofVerifier for DlcProtocol {
+    fn proof_verifier_mut(&mut self) -> &mut ProofVerifier {
+        &mut self.proof_verifier
+    }
+}
+
 #[async_trait]
 impl Layer2Protocol for DlcProtocol {
     async fn initialize(&self) -> AnyaResult<()> {
@@ -88,13 +97,12 @@ async fn transfer_asset(&self, transfer: AssetTransfer) -> AnyaResult<TransferRe
 
     async fn verify_proof(&self, proof: &Proof) -> AnyaResult<VerificationResult> {
         info!("Verifying DLC proof...");
-        // TODO: Implement actual proof verification
-        Ok(VerificationResult::default())
+        // No Bitcoin-side commitment to check beyond the shared inclusion checks
+        Ok(self.proof_verifier.verify_with(proof, |_| Ok(())))
     }
 
     async fn validate_state(&self, state: &ProtocolState) -> AnyaResult<ValidationResult> {
         info!("Validating DLC state...");
-        // TODO: Implement actual state validation
-        Ok(ValidationResult::default())
+        Ok(self.proof_verifier.validate_anchor(state))
     }
 } 
\ No newline at end of file
@@ -11,14 +11,116 @@
         Proof,
         VerificationResult,
         ValidationResult,
+        Layer2Type,
+        MoveEstimate,
+        MoveRefund,
+        MoveRequest,
+        OnchainWallet,
+        Venue,
+        proof::{ProofVerifier, WithProofVerifier},
     },
 };
 use async_trait::async_trait;
+use bitcoin::hashes::Hash;
+use bitcoin::{Address as BtcAddress, Amount, Network, Script, ScriptBuf, Txid, WScriptHash};
+use std::str::FromStr;
+use std::sync::Arc;
 use tracing::{info, error, warn};
 
+/// Lightning node receiving and paying the Lightning side of swaps
+#[async_trait]
+pub trait LightningNode: Send + Sync {
+    /// BOLT11 invoice for `amount`
+    async fn create_invoice(&self, amount: Amount, description: &str) -> AnyaResult<String>;
+
+    /// Start paying a BOLT11 invoice, returning the payment hash
+    async fn pay_invoice(&self, invoice: &str) -> AnyaResult<String>;
+}
+
+/// Direction of a swap
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SwapKind {
+    /// On-chain funds for a Lightning payment
+    Submarine,
+    /// A Lightning payment for on-chain funds
+    Reverse,
+}
+
+/// Fees a swap service charges
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct SwapQuote {
+    /// Service fee
+    pub service_fee: Amount,
+    /// Mainchain fee of the service's own transaction
+    pub miner_fee: Amount,
+}
+
+/// Submarine swap awaiting its lockup
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SubmarineSwap {
+    /// Service's swap ID
+    pub id: String,
+    /// Script to lock the funds in
+    pub lockup_script: ScriptBuf,
+    /// Amount to lock
+    pub expected_amount: Amount,
+}
+
+/// Reverse swap awaiting payment of its invoice
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ReverseSwap {
+    /// Service's swap ID
+    pub id: String,
+    /// Invoice to pay
+    pub invoice: String,
+}
+
+/// Progress of a swap
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SwapStatus {
+    /// Not settled yet
+    Pending,
+    /// Invoice paid (submarine) or on-chain claim confirmed (reverse)
+    Completed,
+    /// Swap failed or expired
+    Failed,
+}
+
+/// Swap service exchanging on-chain funds and Lightning payments
+#[async_trait]
+pub trait SwapService: Send + Sync {
+    /// Current fees of a swap of `amount`
+    async fn quote(&self, kind: SwapKind, amount: Amount) -> AnyaResult<SwapQuote>;
+
+    /// Swap paying `invoice` for funds locked on-chain, refundable to `refund_script`
+    async fn create_submarine_swap(&self, invoice: &str, refund_script: &Script) -> AnyaResult<SubmarineSwap>;
+
+    /// Swap of a Lightning payment of `amount` for funds claimed to `claim_script`
+    async fn create_reverse_swap(&self, amount: Amount, claim_script: &Script) -> AnyaResult<ReverseSwap>;
+
+    /// Progress of a swap
+    async fn swap_status(&self, id: &str) -> AnyaResult<SwapStatus>;
+
+    /// Refund the lockup of a failed submarine swap to its refund script
+    ///
+    /// Returns the refund transaction and the amount it returns.
+    async fn refund_swap(&self, id: &str, fee_rate: u64) -> AnyaResult<(Txid, Amount)>;
+}
+
+/// Backends moves between Bitcoin and Lightning are carried out with
+#[derive(Clone)]
+struct SwapBackends {
+    node: Arc<dyn LightningNode>,
+    service: Arc<dyn SwapService>,
+    wallet: Arc<dyn OnchainWallet>,
+    network: Network,
+}
+
 pub struct LightningProtocol {
     initialized: bool,
     connected: bool,
+    proof_verifier: ProofVerifier,
+    swaps: Option<SwapBackends>,
 }
 
 impl LightningProtocol {
@@ -26,8 +128,76 @@ pub fn new() -> Self {
         Self {
             initialized: false,
             connected: false,
+            proof_verifier: ProofVerifier::default(),
+            swaps: None,
         }
     }
+
+    /// Move between Bitcoin and Lightning with swaps
+    pub fn with_swaps(
+        mut self,
+        node: Arc<dyn LightningNode>,
+        service: Arc<dyn SwapService>,
+        wallet: Arc<dyn OnchainWallet>,
+        network: Network,
+    ) -> Self {
+        self.swaps = Some(SwapBackends { node, service, wallet, network });
+        self
+    }
+
+    fn swaps(&self) -> AnyaResult<&SwapBackends> {
+        self.swaps.as_ref().ok_or_else(|| AnyaError::System("No swap service configured".to_string()))
+    }
+
+    /// Fees of a submarine swap of `amount`, as (lockup fee, service fees)
+    ///
+    /// The lockup is sized as a payment to a P2WSH output, the size of swap lockups.
+    async fn submarine_fees(&self, amount: u64, fee_rate: u64) -> AnyaResult<(Amount, SwapQuote)> {
+        let swaps = self.swaps()?;
+        let lockup = ScriptBuf::new_p2wsh(&WScriptHash::all_zeros());
+        let funding_fee = swaps.wallet.payment_fee(&lockup, Amount::from_sat(amount), fee_rate).await?;
+        let quote = swaps.service.quote(SwapKind::Submarine, Amount::from_sat(amount)).await?;
+        Ok((Amount::from_sat(funding_fee), quote))
+    }
+
+    /// Lock on-chain funds for a swap paying our own invoice
+    async fn swap_in(&self, request: &MoveRequest) -> AnyaResult<String> {
+        let swaps = self.swaps()?;
+        let (funding_fee, quote) = self.submarine_fees(request.amount, request.fee_rate).await?;
+        let invoice_amount = Amount::from_sat(request.amount)
+            .checked_sub(funding_fee + quote.service_fee + quote.miner_fee)
+            .ok_or_else(|| AnyaError::System(format!("{} sat does not cover the swap fees", request.amount)))?;
+
+        let invoice = swaps.node.create_invoice(invoice_amount, "Swap in").await?;
+        let refund_script = swaps.wallet.change_script().await?;
+        let swap = swaps.service.create_submarine_swap(&invoice, &refund_script).await?;
+        if swap.expected_amount + funding_fee > Amount::from_sat(request.amount) {
+            return Err(AnyaError::System(format!(
+                "Swap {} asks for {}, more than quoted", swap.id, swap.expected_amount
+            )));
+        }
+        swaps.wallet.pay(&swap.lockup_script, swap.expected_amount, request.fee_rate).await?;
+        info!("Locked {} for submarine swap {}", swap.expected_amount, swap.id);
+        Ok(format!("lightning_swap_in:{}", swap.id))
+    }
+
+    /// Pay the invoice of a swap claiming on-chain funds to the destination
+    async fn swap_out(&self, request: &MoveRequest) -> AnyaResult<String> {
+        let swaps = self.swaps()?;
+        let destination = BtcAddress::from_str(&request.destination)
+            .and_then(|address| address.require_network(swaps.network))
+            .map_err(|e| AnyaError::System(format!("Invalid destination {}: {}", request.destination, e)))?;
+        let swap = swaps.service.create_reverse_swap(Amount::from_sat(request.amount), &destination.script_pubkey()).await?;
+        swaps.node.pay_invoice(&swap.invoice).await?;
+        info!("Paid reverse swap {} to {}", swap.id, destination);
+        Ok(format!("lightning_swap_out:{}", swap.id))
+    }
+}
+
+impl WithProofVerifier for LightningProtocol {
+    fn proof_verifier_mut(&mut self) -> &mut ProofVerifier {
+        &mut self.proof_verifier
+    }
 }
 
 #[async_trait]
@@ -58,6 +228,22 @@ async fn submit_transaction(&self, tx: &[u8]) -> AnyaResult<String> {
 
     async fn get_transaction_status(&self, tx_id: &str) -> AnyaResult<TransactionStatus> {
         info!("Getting Lightning Network transaction status...");
+        let swap_id = tx_id.strip_prefix("lightning_swap_in:").or_else(|| tx_id.strip_prefix("lightning_swap_out:"));
+        if let Some(swap_id) = swap_id {
+            return Ok(match self.swaps()?.service.swap_status(swap_id).await? {
+                SwapStatus::Pending => TransactionStatus::Pending,
+                SwapStatus::Completed => TransactionStatus::Confirmed,
+                SwapStatus::Failed => TransactionStatus::Failed,
+            });
+        }
+        if let Some(txid) = tx_id.strip_prefix("lightning_refund:") {
+            let txid = Txid::from_str(txid).map_err(|e| AnyaError::System(format!("Refund txid {}: {}", txid, e)))?;
+            return Ok(match self.swaps()?.wallet.confirmations(&txid).await? {
+                Some(0) => TransactionStatus::Pending,
+                Some(_) => TransactionStatus::Confirmed,
+                None => TransactionStatus::Failed,
+            });
+        }
         // TODO: Implement actual status check
         Ok(TransactionStatus::Confirmed)
     }
@@ -88,13 +274,214 @@ async fn transfer_asset(&self, transfer: AssetTransfer) -> AnyaResult<TransferRe
 
     async fn verify_proof(&self, proof: &Proof) -> AnyaResult<VerificationResult> {
         info!("Verifying Lightning Network proof...");
-        // TODO: Implement actual proof verification
-        Ok(VerificationResult::default())
+        // No Bitcoin-side commitment to check beyond the shared inclusion checks
+        Ok(self.proof_verifier.verify_with(proof, |_| Ok(())))
     }
 
     async fn validate_state(&self, state: &ProtocolState) -> AnyaResult<ValidationResult> {
         info!("Validating Lightning Network state...");
-        // TODO: Implement actual state validation
-        Ok(ValidationResult::default())
+        Ok(self.proof_verifier.validate_anchor(state))
     }
-} 
\ No newline at end of file
+
+    async fn estimate_move(&self, request: &MoveRequest) -> AnyaResult<MoveEstimate> {
+        const LIGHTNING: Venue = Venue::Layer2(Layer2Type::Lightning);
+        let swaps = self.swaps()?;
+        match (request.from, request.to) {
+            (Venue::Bitcoin, LIGHTNING) if request.is_btc() => {
+                let (funding_fee, quote) = self.submarine_fees(request.amount, request.fee_rate).await?;
+                Ok(MoveEstimate {
+                    fee: (funding_fee + quote.service_fee + quote.miner_fee).to_sat(),
+                    latency: swaps.wallet.block_interval().await?,
+                })
+            },
+            (LIGHTNING, Venue::Bitcoin) if request.is_btc() => {
+                let quote = swaps.service.quote(SwapKind::Reverse, Amount::from_sat(request.amount)).await?;
+                Ok(MoveEstimate {
+                    fee: (quote.service_fee + quote.miner_fee).to_sat(),
+                    latency: swaps.wallet.block_interval().await?,
+                })
+            },
+            _ => Err(request.unsupported("Lightning")),
+        }
+    }
+
+    async fn execute_move(&self, request: &MoveRequest) -> AnyaResult<TransferResult> {
+        const LIGHTNING: Venue = Venue::Layer2(Layer2Type::Lightning);
+        let tx_id = match (request.from, request.to) {
+            (Venue::Bitcoin, LIGHTNING) if request.is_btc() => self.swap_in(request).await?,
+            (LIGHTNING, Venue::Bitcoin) if request.is_btc() => self.swap_out(request).await?,
+            _ => return Err(request.unsupported("Lightning")),
+        };
+        Ok(TransferResult { tx_id, status: TransactionStatus::Pending, timestamp: chrono::Utc::now().timestamp() as u64 })
+    }
+
+    async fn refund_move(&self, request: &MoveRequest, tx_id: &str) -> AnyaResult<Option<MoveRefund>> {
+        if !matches!(self.get_transaction_status(tx_id).await?, TransactionStatus::Failed | TransactionStatus::Rejected) {
+            return Err(AnyaError::System(format!("{} ({} to {}) has not failed", tx_id, request.from, request.to)));
+        }
+        // A failed reverse swap never took the Lightning payment
+        let Some(swap_id) = tx_id.strip_prefix("lightning_swap_in:") else {
+            return Ok(None);
+        };
+        let (txid, amount) = self.swaps()?.service.refund_swap(swap_id, request.fee_rate).await?;
+        warn!("Refunding submarine swap {} in {}", swap_id, txid);
+        Ok(Some(MoveRefund { tx_id: format!("lightning_refund:{}", txid), amount: amount.to_sat() }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitcoin::{absolute, transaction, OutPoint, Psbt, Transaction, TxIn, TxOut, WPubkeyHash};
+    use std::sync::Mutex;
+    use std::time::Duration;
+
+    fn wallet_script() -> ScriptBuf {
+        ScriptBuf::new_p2wpkh(&WPubkeyHash::from_byte_array([1; 20]))
+    }
+
+    /// Node, swap service and wallet, with one mainchain coin
+    struct TestBackends {
+        status: Mutex<SwapStatus>,
+        paid: Mutex<Vec<String>>,
+        broadcast: Mutex<Vec<Transaction>>,
+    }
+
+    #[async_trait]
+    impl LightningNode for TestBackends {
+        async fn create_invoice(&self, amount: Amount, _: &str) -> AnyaResult<String> {
+            Ok(format!("invoice:{}", amount.to_sat()))
+        }
+
+        async fn pay_invoice(&self, invoice: &str) -> AnyaResult<String> {
+            self.paid.lock().unwrap().push(invoice.to_string());
+            Ok("payment-hash".to_string())
+        }
+    }
+
+    #[async_trait]
+    impl SwapService for TestBackends {
+        async fn quote(&self, _: SwapKind, amount: Amount) -> AnyaResult<SwapQuote> {
+            Ok(SwapQuote { service_fee: amount / 1_000, miner_fee: Amount::from_sat(200) })
+        }
+
+        async fn create_submarine_swap(&self, invoice: &str, refund_script: &Script) -> AnyaResult<SubmarineSwap> {
+            assert_eq!(refund_script, wallet_script().as_script());
+            let amount = Amount::from_sat(invoice.trim_start_matches("invoice:").parse().unwrap());
+            let quote = self.quote(SwapKind::Submarine, amount).await?;
+            Ok(SubmarineSwap {
+                id: "in1".to_string(),
+                lockup_script: ScriptBuf::new_p2wsh(&WScriptHash::from_byte_array([7; 32])),
+                expected_amount: amount + quote.service_fee + quote.miner_fee,
+            })
+        }
+
+        async fn create_reverse_swap(&self, amount: Amount, _: &Script) -> AnyaResult<ReverseSwap> {
+            Ok(ReverseSwap { id: "out1".to_string(), invoice: format!("invoice:{}", amount.to_sat()) })
+        }
+
+        async fn swap_status(&self, _: &str) -> AnyaResult<SwapStatus> {
+            Ok(*self.status.lock().unwrap())
+        }
+
+        async fn refund_swap(&self, id: &str, _: u64) -> AnyaResult<(Txid, Amount)> {
+            assert_eq!(id, "in1");
+            Ok((Txid::from_byte_array([5; 32]), Amount::from_sat(99_000)))
+        }
+    }
+
+    #[async_trait]
+    impl OnchainWallet for TestBackends {
+        async fn utxos(&self) -> AnyaResult<Vec<(OutPoint, TxOut)>> {
+            Ok(Vec::new())
+        }
+
+        async fn change_script(&self) -> AnyaResult<ScriptBuf> {
+            Ok(wallet_script())
+        }
+
+        async fn create_payment(&self, script_pubkey: &Script, amount: Amount, fee_rate: u64) -> AnyaResult<Psbt> {
+            let coin = TxOut { value: Amount::from_sat(1_000_000), script_pubkey: wallet_script() };
+            let tx = Transaction {
+                version: transaction::Version::TWO,
+                lock_time: absolute::LockTime::ZERO,
+                input: vec![TxIn { previous_output: OutPoint::null(), ..Default::default() }],
+                output: vec![
+                    TxOut { value: amount, script_pubkey: script_pubkey.to_owned() },
+                    TxOut { value: coin.value - amount - Amount::from_sat(141 * fee_rate), script_pubkey: wallet_script() },
+                ],
+            };
+            let mut psbt = Psbt::from_unsigned_tx(tx).unwrap();
+            psbt.inputs[0].witness_utxo = Some(coin);
+            Ok(psbt)
+        }
+
+        async fn sign(&self, psbt: Psbt) -> AnyaResult<Transaction> {
+            Ok(psbt.unsigned_tx)
+        }
+
+        async fn broadcast(&self, tx: &Transaction) -> AnyaResult<Txid> {
+            self.broadcast.lock().unwrap().push(tx.clone());
+            Ok(tx.compute_txid())
+        }
+
+        async fn confirmations(&self, _: &Txid) -> AnyaResult<Option<u32>> {
+            Ok(Some(1))
+        }
+
+        async fn merkle_block(&self, _: &Txid) -> AnyaResult<Option<bitcoin::MerkleBlock>> {
+            Ok(None)
+        }
+
+        async fn block_interval(&self) -> AnyaResult<Duration> {
+            Ok(Duration::from_secs(600))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_swap_moves() {
+        let backends = Arc::new(TestBackends {
+            status: Mutex::new(SwapStatus::Pending),
+            paid: Mutex::new(Vec::new()),
+            broadcast: Mutex::new(Vec::new()),
+        });
+        let protocol = LightningProtocol::new()
+            .with_swaps(backends.clone(), backends.clone(), backends.clone(), Network::Regtest);
+        let lightning = Venue::Layer2(Layer2Type::Lightning);
+        let request = MoveRequest {
+            asset_id: crate::layer2::BTC_ASSET_ID.to_string(),
+            amount: 100_000,
+            from: Venue::Bitcoin,
+            to: lightning,
+            source: String::new(),
+            destination: String::new(),
+            fee_rate: 2,
+        };
+        assert!(protocol.estimate_move(&MoveRequest { from: lightning, ..request.clone() }).await.is_err());
+
+        // Lockup fee, service fee and the service's miner fee
+        let estimate = protocol.estimate_move(&request).await.unwrap();
+        assert_eq!(estimate.fee, 282 + 100 + 200);
+        let result = protocol.execute_move(&request).await.unwrap();
+        assert_eq!(result.tx_id, "lightning_swap_in:in1");
+        let lockup = backends.broadcast.lock().unwrap()[0].output[0].clone();
+        assert_eq!(lockup.value, Amount::from_sat(99_418 + 99 + 200));
+        assert!(matches!(protocol.get_transaction_status(&result.tx_id).await.unwrap(), TransactionStatus::Pending));
+        assert!(protocol.refund_move(&request, &result.tx_id).await.is_err());
+
+        // Failed submarine swaps are refunded on-chain
+        *backends.status.lock().unwrap() = SwapStatus::Failed;
+        let refund = protocol.refund_move(&request, &result.tx_id).await.unwrap().unwrap();
+        assert_eq!(refund, MoveRefund { tx_id: format!("lightning_refund:{}", Txid::from_byte_array([5; 32])), amount: 99_000 });
+        assert!(matches!(protocol.get_transaction_status(&refund.tx_id).await.unwrap(), TransactionStatus::Confirmed));
+
+        // Reverse swaps pay the whole amount; a failed one never took the payment
+        let destination = BtcAddress::from_script(&wallet_script(), Network::Regtest).unwrap();
+        let request = MoveRequest { destination: destination.to_string(), ..request.reversed() };
+        assert_eq!(protocol.estimate_move(&request).await.unwrap().fee, 100 + 200);
+        let result = protocol.execute_move(&request).await.unwrap();
+        assert_eq!(result.tx_id, "lightning_swap_out:out1");
+        assert_eq!(*backends.paid.lock().unwrap(), ["invoice:100000"]);
+        assert_eq!(protocol.refund_move(&request, &result.tx_id).await.unwrap(), None);
+    }
+}
@@ -0,0 +1,274 @@
+//! Confidential Liquid addresses
+//!
+//! A confidential segwit address carries the receiver's blinding public key
+//! next to the witness program, so senders can blind the amount and asset of
+//! the output. It is encoded with blech32, a bech32 variant with a 12-character
+//! checksum over the longer payload (blech32m for witness versions above 0).
+//!
+//! Blinding keys are derived per script with SLIP-77 from the wallet seed, so a
+//! receiver can recompute the key for any of its scripts.
+
+use std::fmt;
+use std::str::FromStr;
+
+use bitcoin::hashes::{hmac, sha256, sha512, Hash, HashEngine};
+use bitcoin::secp256k1::{PublicKey, Secp256k1, SecretKey};
+use bitcoin::{ScriptBuf, WitnessProgram, WitnessVersion};
+
+use super::{LiquidError, LiquidNetwork};
+
+const CHARSET: &[u8; 32] = b"qpzry9x8gf2tvdw0s3jn54khce6mua7l";
+
+/// Checksum constant for witness version 0
+const BLECH32_CONST: u64 = 1;
+
+/// Checksum constant for witness versions 1 and above
+const BLECH32M_CONST: u64 = 0x0455_972a_3350_f7a1;
+
+/// Number of checksum characters
+const CHECKSUM_LEN: usize = 12;
+
+fn polymod(values: &[u8]) -> u64 {
+    const GENERATOR: [u64; 5] = [
+        0x7d52fba40bd886,
+        0x5e8dbf1a03950c,
+        0x1c3a3c74072a18,
+        0x385d72fa0e5139,
+        0x7093e5a608865b,
+    ];
+
+    let mut chk: u64 = 1;
+    for value in values {
+        let top = chk >> 55;
+        chk = ((chk & 0x7f_ffff_ffff_ffff) << 5) ^ u64::from(*value);
+        for (i, generator) in GENERATOR.iter().enumerate() {
+            if (top >> i) & 1 == 1 {
+                chk ^= generator;
+            }
+        }
+    }
+    chk
+}
+
+fn hrp_expand(hrp: &str) -> Vec<u8> {
+    let bytes = hrp.as_bytes();
+    bytes.iter().map(|b| b >> 5)
+        .chain(std::iter::once(0))
+        .chain(bytes.iter().map(|b| b & 31))
+        .collect()
+}
+
+/// Regroup bits, e.g. bytes into 5-bit groups
+fn convert_bits(data: &[u8], from: u32, to: u32, pad: bool) -> Option<Vec<u8>> {
+    let mut acc: u32 = 0;
+    let mut bits: u32 = 0;
+    let max = (1u32 << to) - 1;
+    let mut out = Vec::with_capacity(data.len() * from as usize / to as usize + 1);
+    for value in data {
+        let value = u32::from(*value);
+        if value >> from != 0 {
+            return None;
+        }
+        acc = (acc << from) | value;
+        bits += from;
+        while bits >= to {
+            bits -= to;
+            out.push(((acc >> bits) & max) as u8);
+        }
+    }
+    if pad {
+        if bits > 0 {
+            out.push(((acc << (to - bits)) & max) as u8);
+        }
+    } else if bits >= from || (acc << (to - bits)) & max != 0 {
+        return None;
+    }
+    Some(out)
+}
+
+fn checksum_const(version: WitnessVersion) -> u64 {
+    if version == WitnessVersion::V0 { BLECH32_CONST } else { BLECH32M_CONST }
+}
+
+/// Confidential segwit address
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ConfidentialAddress {
+    /// Network the address belongs to
+    pub network: LiquidNetwork,
+    /// Key the sender blinds the output to
+    pub blinding_pubkey: PublicKey,
+    /// Witness program of the receiving script
+    pub program: WitnessProgram,
+}
+
+impl ConfidentialAddress {
+    /// Confidential address for a segwit `script_pubkey`
+    pub fn new(network: LiquidNetwork, blinding_pubkey: PublicKey, script_pubkey: &ScriptBuf) -> Result<Self, LiquidError> {
+        let program = witness_program(script_pubkey)
+            .ok_or_else(|| LiquidError::AddressError("Confidential addresses need a segwit script".to_string()))?;
+        Ok(Self { network, blinding_pubkey, program })
+    }
+
+    /// Script the sender pays to
+    pub fn script_pubkey(&self) -> ScriptBuf {
+        ScriptBuf::new_witness_program(&self.program)
+    }
+}
+
+fn witness_program(script_pubkey: &ScriptBuf) -> Option<WitnessProgram> {
+    let version = script_pubkey.witness_version()?;
+    let program = script_pubkey.as_bytes().get(2..)?;
+    WitnessProgram::new(version, program).ok()
+}
+
+impl fmt::Display for ConfidentialAddress {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let hrp = self.network.blech32_hrp();
+        let version = self.program.version();
+
+        let mut payload = self.blinding_pubkey.serialize().to_vec();
+        payload.extend_from_slice(self.program.program().as_bytes());
+        let mut data = vec![version.to_num()];
+        data.extend(convert_bits(&payload, 8, 5, true).expect("bytes regroup into 5-bit groups"));
+
+        let mut values = hrp_expand(hrp);
+        values.extend_from_slice(&data);
+        values.extend_from_slice(&[0; CHECKSUM_LEN]);
+        let checksum = polymod(&values) ^ checksum_const(version);
+        data.extend((0..CHECKSUM_LEN).map(|i| ((checksum >> (5 * (CHECKSUM_LEN - 1 - i))) & 31) as u8));
+
+        write!(f, "{}1", hrp)?;
+        for value in data {
+            write!(f, "{}", CHARSET[value as usize] as char)?;
+        }
+        Ok(())
+    }
+}
+
+impl FromStr for ConfidentialAddress {
+    type Err = LiquidError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let invalid = |reason: &str| LiquidError::AddressError(format!("{}: {}", reason, s));
+        if s.chars().any(|c| c.is_ascii_uppercase()) && s.chars().any(|c| c.is_ascii_lowercase()) {
+            return Err(invalid("Mixed-case address"));
+        }
+        let s_lower = s.to_ascii_lowercase();
+        let (hrp, rest) = s_lower.rsplit_once('1').ok_or_else(|| invalid("Missing separator"))?;
+        let network = LiquidNetwork::from_blech32_hrp(hrp).ok_or_else(|| invalid("Unknown network prefix"))?;
+
+        let data = rest.bytes()
+            .map(|c| CHARSET.iter().position(|&d| d == c).map(|v| v as u8))
+            .collect::<Option<Vec<u8>>>()
+            .ok_or_else(|| invalid("Invalid character"))?;
+        if data.len() < 1 + CHECKSUM_LEN {
+            return Err(invalid("Address too short"));
+        }
+
+        let version = WitnessVersion::try_from(data[0]).map_err(|_| invalid("Invalid witness version"))?;
+        let mut values = hrp_expand(hrp);
+        values.extend_from_slice(&data);
+        if polymod(&values) != checksum_const(version) {
+            return Err(invalid("Invalid checksum"));
+        }
+
+        let payload = convert_bits(&data[1..data.len() - CHECKSUM_LEN], 5, 8, false)
+            .ok_or_else(|| invalid("Invalid padding"))?;
+        if payload.len() < 33 {
+            return Err(invalid("Missing blinding key"));
+        }
+        let (key, program) = payload.split_at(33);
+        let blinding_pubkey = PublicKey::from_slice(key).map_err(|_| invalid("Invalid blinding key"))?;
+        let program = WitnessProgram::new(version, program).map_err(|_| invalid("Invalid witness program"))?;
+        Ok(Self { network, blinding_pubkey, program })
+    }
+}
+
+/// SLIP-77 master blinding key
+#[derive(Clone, PartialEq, Eq)]
+pub struct MasterBlindingKey([u8; 32]);
+
+impl fmt::Debug for MasterBlindingKey {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str("MasterBlindingKey(..)")
+    }
+}
+
+impl MasterBlindingKey {
+    /// Derive the master blinding key from a wallet seed
+    pub fn from_seed(seed: &[u8]) -> Self {
+        let mut engine = hmac::HmacEngine::<sha512::Hash>::new(b"Symmetric key seed");
+        engine.input(seed);
+        let root = hmac::Hmac::<sha512::Hash>::from_engine(engine).to_byte_array();
+
+        let mut engine = hmac::HmacEngine::<sha512::Hash>::new(&root[..32]);
+        engine.input(b"\x00SLIP-0077");
+        let node = hmac::Hmac::<sha512::Hash>::from_engine(engine).to_byte_array();
+
+        let mut key = [0u8; 32];
+        key.copy_from_slice(&node[32..]);
+        Self(key)
+    }
+
+    /// Blinding private key for outputs paying `script_pubkey`
+    pub fn blinding_key(&self, script_pubkey: &ScriptBuf) -> Result<SecretKey, LiquidError> {
+        let mut engine = hmac::HmacEngine::<sha256::Hash>::new(&self.0);
+        engine.input(script_pubkey.as_bytes());
+        let key = hmac::Hmac::<sha256::Hash>::from_engine(engine).to_byte_array();
+        SecretKey::from_slice(&key).map_err(|e| LiquidError::AddressError(format!("Blinding key: {}", e)))
+    }
+
+    /// Confidential receiving address for `script_pubkey`
+    pub fn address(&self, network: LiquidNetwork, script_pubkey: &ScriptBuf) -> Result<ConfidentialAddress, LiquidError> {
+        let blinding_pubkey = self.blinding_key(script_pubkey)?.public_key(&Secp256k1::signing_only());
+        ConfidentialAddress::new(network, blinding_pubkey, script_pubkey)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_blech32_addresses() {
+        for (encoded, network) in [
+            ("el1qqw3e3mk4ng3ks43mh54udznuekaadh9lgwef3mwgzrfzakmdwcvqpe4ppdaa3t44v3zv2u6w56pv6tc666fvgzaclqjnkz0sd", LiquidNetwork::ElementsRegtest),
+            ("lq1qqf8er278e6nyvuwtgf39e6ewvdcnjupn9a86rzpx655y5lhkt0walu3djf9cklkxd3ryld97hu8h3xepw7sh2rlu7q45dcew5", LiquidNetwork::Liquid),
+        ] {
+            let address: ConfidentialAddress = encoded.parse().unwrap();
+            assert_eq!(address.network, network);
+            assert_eq!(address.program.version(), WitnessVersion::V0);
+            assert_eq!(address.program.program().len(), 20);
+            assert_eq!(address.to_string(), encoded);
+            assert_eq!(encoded.to_uppercase().parse::<ConfidentialAddress>().unwrap(), address);
+
+            let mut corrupted = encoded.as_bytes().to_vec();
+            corrupted[20] = if corrupted[20] == b'q' { b'p' } else { b'q' };
+            assert!(String::from_utf8(corrupted).unwrap().parse::<ConfidentialAddress>().is_err());
+        }
+    }
+
+    #[test]
+    fn test_slip77_receiving_address() {
+        let master = MasterBlindingKey::from_seed(&[7u8; 64]);
+        let secp = Secp256k1::new();
+        let key = SecretKey::from_slice(&[1u8; 32]).unwrap().public_key(&secp);
+        let script = ScriptBuf::new_p2wpkh(&bitcoin::PublicKey::new(key).wpubkey_hash().unwrap());
+
+        let address = master.address(LiquidNetwork::Liquid, &script).unwrap();
+        assert!(address.to_string().starts_with("lq1q"));
+        assert_eq!(address.script_pubkey(), script);
+        assert_eq!(address.blinding_pubkey, master.blinding_key(&script).unwrap().public_key(&secp));
+        assert_eq!(address.to_string().parse::<ConfidentialAddress>().unwrap(), address);
+
+        // Taproot outputs use the blech32m checksum
+        let taproot = ScriptBuf::new_p2tr_tweaked(
+            bitcoin::key::TweakedPublicKey::dangerous_assume_tweaked(key.x_only_public_key().0),
+        );
+        let address = master.address(LiquidNetwork::LiquidTestnet, &taproot).unwrap();
+        assert!(address.to_string().starts_with("tlq1p"));
+        assert_eq!(address.to_string().parse::<ConfidentialAddress>().unwrap(), address);
+
+        assert!(master.address(LiquidNetwork::Liquid, &ScriptBuf::new()).is_err());
+    }
+}
@@ -1,3 +1,14 @@
+//! Liquid sidechain integration
+//!
+//! Peg-ins are derived from the federation's peg-in script and tracked until
+//! they can be claimed (see [`pegin`]); receiving on Liquid uses confidential
+//! blech32 addresses (see [`address`]).
+
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
 use crate::{
     AnyaError,
     AnyaResult,
@@ -11,22 +22,446 @@
         Proof,
         VerificationResult,
         ValidationResult,
+        Layer2Type,
+        MoveEstimate,
+        MoveRefund,
+        MoveRequest,
+        OnchainWallet,
+        Venue,
+        proof::{ProofError, ProofVerifier, WithProofVerifier},
     },
 };
 use async_trait::async_trait;
+use bitcoin::hashes::Hash;
+use bitcoin::secp256k1::{Secp256k1, SecretKey};
+use bitcoin::{Address as BtcAddress, Amount, BlockHash, Network, Script, ScriptBuf, Transaction, Txid};
 use tracing::{info, error, warn};
 
+pub mod address;
+pub mod pegin;
+
+pub use address::{ConfidentialAddress, MasterBlindingKey};
+pub use pegin::{AssetId, ClaimTransaction, PegInProof, PegInStatus, PEGIN_CONFIRMATIONS};
+
+/// Liquid confirmations the federation waits for before sweeping a peg-out
+const PEGOUT_CONFIRMATIONS: u32 = 2;
+
+/// Liquid-style Elements networks
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum LiquidNetwork {
+    /// Liquid mainnet, pegged to Bitcoin mainnet
+    Liquid,
+    /// Liquid testnet, pegged to Bitcoin testnet
+    LiquidTestnet,
+    /// Local Elements regtest, pegged to Bitcoin regtest
+    ElementsRegtest,
+}
+
+impl LiquidNetwork {
+    /// Human-readable part of confidential addresses
+    pub fn blech32_hrp(&self) -> &'static str {
+        match self {
+            LiquidNetwork::Liquid => "lq",
+            LiquidNetwork::LiquidTestnet => "tlq",
+            LiquidNetwork::ElementsRegtest => "el",
+        }
+    }
+
+    /// Network for a confidential address prefix
+    pub fn from_blech32_hrp(hrp: &str) -> Option<Self> {
+        match hrp {
+            "lq" => Some(LiquidNetwork::Liquid),
+            "tlq" => Some(LiquidNetwork::LiquidTestnet),
+            "el" => Some(LiquidNetwork::ElementsRegtest),
+            _ => None,
+        }
+    }
+
+    /// Bitcoin network the chain is pegged to
+    pub fn parent_network(&self) -> Network {
+        match self {
+            LiquidNetwork::Liquid => Network::Bitcoin,
+            LiquidNetwork::LiquidTestnet => Network::Testnet,
+            LiquidNetwork::ElementsRegtest => Network::Regtest,
+        }
+    }
+
+    /// Genesis hash of the parent chain, committed to by claims
+    pub fn parent_genesis_hash(&self) -> BlockHash {
+        bitcoin::blockdata::constants::genesis_block(self.parent_network()).block_hash()
+    }
+
+    /// Pegged Bitcoin asset, where it is fixed by the network
+    ///
+    /// Regtest chains derive it from their own parameters.
+    pub fn policy_asset(&self) -> Option<AssetId> {
+        let id = match self {
+            LiquidNetwork::Liquid => "6f0279e9ed041c3d710a9f57d0c02928416460c4b722ae3457a11eec381c526d",
+            LiquidNetwork::LiquidTestnet => "144c654344aa716d6f3abcc1ca90e5641e4e2a7f633bc09fe3baf64585819a49",
+            LiquidNetwork::ElementsRegtest => return None,
+        };
+        Some(id.parse().expect("valid asset id"))
+    }
+}
+
+/// Configuration for the Liquid integration
+#[derive(Debug, Clone)]
+pub struct LiquidConfig {
+    /// Network to operate on
+    pub network: LiquidNetwork,
+    /// Federation peg-in script, as returned by `getsidechaininfo`
+    pub fedpeg_script: ScriptBuf,
+    /// Pegged Bitcoin asset; defaults to the network's
+    pub policy_asset: Option<AssetId>,
+    /// Mainchain confirmations required before claiming
+    pub peg_in_confirmations: u32,
+}
+
+impl Default for LiquidConfig {
+    fn default() -> Self {
+        Self {
+            network: LiquidNetwork::Liquid,
+            fedpeg_script: ScriptBuf::new(),
+            policy_asset: LiquidNetwork::Liquid.policy_asset(),
+            peg_in_confirmations: PEGIN_CONFIRMATIONS,
+        }
+    }
+}
+
+/// Liquid error types
+#[derive(Debug, thiserror::Error)]
+pub enum LiquidError {
+    /// Address encoding or derivation error
+    #[error("Address error: {0}")]
+    AddressError(String),
+
+    /// Federation script error
+    #[error("Federation error: {0}")]
+    FederationError(String),
+
+    /// Peg-in error
+    #[error("Peg-in error: {0}")]
+    PegInError(String),
+
+    /// Peg-in address not issued by this node
+    #[error("Peg-in not found: {0}")]
+    PegInNotFound(String),
+
+    /// Mainchain proof error
+    #[error("Proof error: {0}")]
+    ProofError(#[from] ProofError),
+
+    /// Signing error
+    #[error("Signing error: {0}")]
+    SigningError(String),
+
+    /// Configuration error
+    #[error("Configuration error: {0}")]
+    ConfigError(String),
+}
+
+impl From<LiquidError> for AnyaError {
+    fn from(error: LiquidError) -> Self {
+        AnyaError::System(error.to_string())
+    }
+}
+
+/// Elements wallet carrying out the Liquid side of moves
+#[async_trait]
+pub trait ElementsWallet: Send + Sync {
+    /// Fee rate in sat/kvB
+    async fn fee_rate(&self) -> AnyaResult<u64>;
+
+    /// Liquid fee of sending `amount` to a mainchain address with `sendtomainchain`
+    async fn peg_out_fee(&self, destination: &BtcAddress, amount: Amount) -> AnyaResult<Amount>;
+
+    /// Send `amount` to a mainchain address, returning the Liquid txid
+    async fn send_to_mainchain(&self, destination: &BtcAddress, amount: Amount) -> AnyaResult<Txid>;
+
+    /// Broadcast a raw Liquid transaction
+    async fn broadcast(&self, tx_hex: &str) -> AnyaResult<Txid>;
+
+    /// Confirmations of a Liquid transaction; `None` once it is neither mined nor in the mempool
+    async fn confirmations(&self, txid: &Txid) -> AnyaResult<Option<u32>>;
+
+    /// Mean interval of recent blocks
+    async fn block_interval(&self) -> AnyaResult<Duration>;
+}
+
+/// Wallets and claim key moves are carried out with
+#[derive(Clone)]
+struct MoveWallets {
+    onchain: Arc<dyn OnchainWallet>,
+    elements: Arc<dyn ElementsWallet>,
+    claim_key: SecretKey,
+}
+
+/// Peg-in made by a move
+#[derive(Debug, Clone)]
+struct PegInMove {
+    /// Mainchain transaction paying the peg-in address
+    funding: Transaction,
+    /// Liquid script receiving the claim
+    destination: ScriptBuf,
+    /// Claim broadcast on Liquid, if any
+    claim: Option<Txid>,
+}
+
 pub struct LiquidProtocol {
     initialized: bool,
     connected: bool,
+    proof_verifier: ProofVerifier,
+    config: LiquidConfig,
+    /// Claim scripts by the mainchain script of the peg-in addresses issued
+    peg_ins: Mutex<HashMap<ScriptBuf, ScriptBuf>>,
+    wallets: Option<MoveWallets>,
+    /// Peg-ins made by moves, by move ID
+    moves: Mutex<HashMap<String, PegInMove>>,
 }
 
 impl LiquidProtocol {
     pub fn new() -> Self {
+        Self::with_config(LiquidConfig::default())
+    }
+
+    /// Create a protocol handler with a specific configuration
+    pub fn with_config(config: LiquidConfig) -> Self {
         Self {
             initialized: false,
             connected: false,
+            proof_verifier: ProofVerifier::default(),
+            config,
+            peg_ins: Mutex::new(HashMap::new()),
+            wallets: None,
+            moves: Mutex::new(HashMap::new()),
+        }
+    }
+
+    /// Carry out moves with a Bitcoin and an Elements wallet
+    ///
+    /// Peg-ins are claimable with `claim_key`.
+    pub fn with_move_wallets(
+        mut self,
+        onchain: Arc<dyn OnchainWallet>,
+        elements: Arc<dyn ElementsWallet>,
+        claim_key: SecretKey,
+    ) -> Self {
+        self.wallets = Some(MoveWallets { onchain, elements, claim_key });
+        self
+    }
+
+    /// Active configuration
+    pub fn config(&self) -> &LiquidConfig {
+        &self.config
+    }
+
+    /// Mainchain address to send a peg-in claimable with `claim_script`
+    pub fn peg_in_address(&self, claim_script: &Script) -> Result<BtcAddress, LiquidError> {
+        if self.config.fedpeg_script.is_empty() {
+            return Err(LiquidError::ConfigError("No federation peg-in script configured".to_string()));
+        }
+        let address = pegin::peg_in_address(&self.config.fedpeg_script, claim_script, self.config.network.parent_network())?;
+        self.peg_ins.lock().unwrap().insert(address.script_pubkey(), claim_script.to_owned());
+        info!("Issued Liquid peg-in address {}", address);
+        Ok(address)
+    }
+
+    /// Issued peg-in funded by `proof`, as (output index, claim script)
+    fn find_peg_in(&self, proof: &PegInProof) -> Result<(u32, ScriptBuf), LiquidError> {
+        let peg_ins = self.peg_ins.lock().unwrap();
+        proof.tx.output.iter().enumerate()
+            .find_map(|(vout, output)| {
+                peg_ins.get(&output.script_pubkey).map(|claim_script| (vout as u32, claim_script.clone()))
+            })
+            .ok_or_else(|| LiquidError::PegInNotFound(proof.tx.compute_txid().to_string()))
+    }
+
+    /// Confirmation status of a funded peg-in
+    pub fn peg_in_status(&self, proof: &PegInProof) -> Result<PegInStatus, LiquidError> {
+        self.find_peg_in(proof)?;
+        proof.status(&self.proof_verifier, self.config.peg_in_confirmations)
+    }
+
+    /// Wait until a funded peg-in has the required confirmations
+    ///
+    /// Headers reach the shared verifier elsewhere; this polls it every
+    /// `poll_interval`. Wrap in a timeout to bound the wait.
+    pub async fn wait_for_peg_in(&self, proof: &PegInProof, poll_interval: Duration) -> Result<u32, LiquidError> {
+        loop {
+            match self.peg_in_status(proof) {
+                Ok(PegInStatus::Claimable { confirmations }) => return Ok(confirmations),
+                Ok(PegInStatus::Pending { .. }) | Err(LiquidError::ProofError(ProofError::UnknownHeader(_))) => {},
+                Err(e) => return Err(e),
+            }
+            tokio::time::sleep(poll_interval).await;
+        }
+    }
+
+    /// Build and sign the claim of a confirmed peg-in
+    ///
+    /// The claim script must be the P2WPKH of `claim_key`. The claimed amount,
+    /// less `fee`, goes unblinded to `destination`.
+    pub fn claim_peg_in(
+        &self,
+        proof: &PegInProof,
+        destination: ScriptBuf,
+        fee: Amount,
+        claim_key: &SecretKey,
+    ) -> Result<ClaimTransaction, LiquidError> {
+        let (vout, claim_script) = self.find_peg_in(proof)?;
+        if let PegInStatus::Pending { confirmations, required } = proof.status(&self.proof_verifier, self.config.peg_in_confirmations)? {
+            return Err(LiquidError::ProofError(ProofError::InsufficientConfirmations { have: confirmations, need: required }));
+        }
+        let asset = self.config.policy_asset
+            .ok_or_else(|| LiquidError::ConfigError("No pegged asset configured".to_string()))?;
+
+        let mut claim = ClaimTransaction::new(
+            proof,
+            vout,
+            &claim_script,
+            destination,
+            fee,
+            asset,
+            self.config.network.parent_genesis_hash(),
+        )?;
+        claim.sign_p2wpkh(claim_key)?;
+        info!("Built Liquid peg-in claim {}", claim.txid());
+        Ok(claim)
+    }
+
+    fn wallets(&self) -> AnyaResult<&MoveWallets> {
+        self.wallets.as_ref().ok_or_else(|| AnyaError::System("No Liquid move wallets configured".to_string()))
+    }
+
+    /// Claim script of move peg-ins
+    fn move_claim_script(&self) -> AnyaResult<ScriptBuf> {
+        let pubkey = bitcoin::PublicKey::new(self.wallets()?.claim_key.public_key(&Secp256k1::new()));
+        Ok(ScriptBuf::new_p2wpkh(&pubkey.wpubkey_hash().expect("compressed key")))
+    }
+
+    /// Liquid script of a confidential destination address
+    fn liquid_destination(&self, destination: &str) -> AnyaResult<ScriptBuf> {
+        let address = ConfidentialAddress::from_str(destination)?;
+        if address.network != self.config.network {
+            return Err(LiquidError::AddressError(format!("{} is not a {:?} address", destination, self.config.network)).into());
+        }
+        Ok(address.script_pubkey())
+    }
+
+    /// Mainchain destination of a peg-out
+    fn mainchain_destination(&self, destination: &str) -> AnyaResult<BtcAddress> {
+        BtcAddress::from_str(destination)
+            .and_then(|address| address.require_network(self.config.network.parent_network()))
+            .map_err(|e| LiquidError::AddressError(format!("{}: {}", destination, e)).into())
+    }
+
+    /// Fee of claiming `funding`, at the Elements wallet's fee rate
+    ///
+    /// Sized with a txoutproof of a block holding only the funding
+    /// transaction; every further tree level adds 8 vbytes.
+    async fn claim_fee(&self, funding: &Transaction, destination: &Script) -> AnyaResult<Amount> {
+        let wallets = self.wallets()?;
+        let claim_script = self.move_claim_script()?;
+        let txid = funding.compute_txid();
+        let header = bitcoin::block::Header {
+            version: bitcoin::block::Version::TWO,
+            prev_blockhash: BlockHash::all_zeros(),
+            merkle_root: bitcoin::TxMerkleNode::from_raw_hash(txid.to_raw_hash()),
+            time: 0,
+            bits: bitcoin::CompactTarget::from_consensus(0),
+            nonce: 0,
+        };
+        let proof = PegInProof::new(funding.clone(), header, &[txid]);
+        let vout = proof.output_paying(&self.peg_in_script()?)?;
+        let asset = self.config.policy_asset
+            .ok_or_else(|| LiquidError::ConfigError("No pegged asset configured".to_string()))?;
+
+        let mut claim = ClaimTransaction::new(
+            &proof,
+            vout,
+            &claim_script,
+            destination.to_owned(),
+            Amount::ZERO,
+            asset,
+            self.config.network.parent_genesis_hash(),
+        )?;
+        claim.sign_p2wpkh(&wallets.claim_key)?;
+        Ok(Amount::from_sat((claim.vsize() * wallets.elements.fee_rate().await?).div_ceil(1000)))
+    }
+
+    /// Mainchain script of the move peg-in address
+    fn peg_in_script(&self) -> AnyaResult<ScriptBuf> {
+        if self.config.fedpeg_script.is_empty() {
+            return Err(LiquidError::ConfigError("No federation peg-in script configured".to_string()).into());
         }
+        let claim_script = self.move_claim_script()?;
+        Ok(pegin::peg_in_address(&self.config.fedpeg_script, &claim_script, self.config.network.parent_network())?
+            .script_pubkey())
+    }
+
+    /// Fees of a peg-in of `amount`: funding less its fee, then claiming it
+    async fn peg_in_fees(&self, amount: u64, fee_rate: u64, destination: &Script) -> AnyaResult<(Amount, Amount)> {
+        let onchain = &self.wallets()?.onchain;
+        let script = self.peg_in_script()?;
+        let funding_fee = Amount::from_sat(onchain.payment_fee(&script, Amount::from_sat(amount), fee_rate).await?);
+        let locked = Amount::from_sat(amount).checked_sub(funding_fee)
+            .ok_or_else(|| LiquidError::PegInError(format!("{} sat does not cover the {} funding fee", amount, funding_fee)))?;
+        let funding = onchain.create_payment(&script, locked, fee_rate).await?;
+        let claim_fee = self.claim_fee(&funding.unsigned_tx, destination).await?;
+        Ok((funding_fee, claim_fee))
+    }
+
+    /// Follow a move peg-in, claiming it once it has the required confirmations
+    async fn peg_in_move_status(&self, move_id: &str) -> AnyaResult<TransactionStatus> {
+        let wallets = self.wallets()?;
+        let peg_in = self.moves.lock().unwrap().get(move_id).cloned()
+            .ok_or_else(|| LiquidError::PegInNotFound(move_id.to_string()))?;
+
+        if let Some(claim) = peg_in.claim {
+            return match wallets.elements.confirmations(&claim).await? {
+                Some(0) => Ok(TransactionStatus::Pending),
+                Some(_) => Ok(TransactionStatus::Confirmed),
+                None => {
+                    // Claim again on the next check
+                    warn!("Liquid claim {} of {} was dropped", claim, move_id);
+                    if let Some(peg_in) = self.moves.lock().unwrap().get_mut(move_id) {
+                        peg_in.claim = None;
+                    }
+                    Ok(TransactionStatus::Pending)
+                },
+            };
+        }
+
+        let txid = peg_in.funding.compute_txid();
+        if wallets.onchain.confirmations(&txid).await?.is_none() {
+            // The funds never left the wallet
+            return Ok(TransactionStatus::Failed);
+        }
+        let Some(merkle_block) = wallets.onchain.merkle_block(&txid).await? else {
+            return Ok(TransactionStatus::Pending);
+        };
+        let proof = PegInProof { tx: peg_in.funding.clone(), merkle_block };
+        match self.peg_in_status(&proof) {
+            Ok(PegInStatus::Claimable { .. }) => {},
+            Ok(PegInStatus::Pending { .. }) | Err(LiquidError::ProofError(ProofError::UnknownHeader(_))) => {
+                return Ok(TransactionStatus::Pending);
+            },
+            Err(e) => return Err(e.into()),
+        }
+
+        let fee = self.claim_fee(&peg_in.funding, &peg_in.destination).await?;
+        let claim = self.claim_peg_in(&proof, peg_in.destination.clone(), fee, &wallets.claim_key)?;
+        let claim_txid = wallets.elements.broadcast(&claim.to_hex()).await?;
+        if let Some(peg_in) = self.moves.lock().unwrap().get_mut(move_id) {
+            peg_in.claim = Some(claim_txid);
+        }
+        Ok(TransactionStatus::Pending)
+    }
+}
+
+impl WithProofVerifier for LiquidProtocol {
+    fn proof_verifier_mut(&mut self) -> &mut ProofVerifier {
+        &mut self.proof_verifier
     }
 }
 
@@ -58,6 +493,18 @@ async fn submit_transaction(&self, tx: &[u8]) -> AnyaResult<String> {
 
     async fn get_transaction_status(&self, tx_id: &str) -> AnyaResult<TransactionStatus> {
         info!("Getting Liquid transaction status...");
+        if tx_id.starts_with("liquid_peg_in:") {
+            return self.peg_in_move_status(tx_id).await;
+        }
+        if let Some(txid) = tx_id.strip_prefix("liquid_peg_out:") {
+            // The federation sweeps peg-outs once they are deep enough
+            let txid = Txid::from_str(txid).map_err(|e| AnyaError::System(format!("Peg-out txid {}: {}", txid, e)))?;
+            return Ok(match self.wallets()?.elements.confirmations(&txid).await? {
+                None => TransactionStatus::Failed,
+                Some(confirmations) if confirmations >= PEGOUT_CONFIRMATIONS => TransactionStatus::Confirmed,
+                Some(_) => TransactionStatus::Pending,
+            });
+        }
         // TODO: Implement actual status check
         Ok(TransactionStatus::Confirmed)
     }
@@ -88,13 +535,314 @@ async fn transfer_asset(&self, transfer: AssetTransfer) -> AnyaResult<TransferRe
 
     async fn verify_proof(&self, proof: &Proof) -> AnyaResult<VerificationResult> {
         info!("Verifying Liquid proof...");
-        // TODO: Implement actual proof verification
-        Ok(VerificationResult::default())
+        // No Bitcoin-side commitment to check beyond the shared inclusion checks
+        Ok(self.proof_verifier.verify_with(proof, |_| Ok(())))
     }
 
     async fn validate_state(&self, state: &ProtocolState) -> AnyaResult<ValidationResult> {
         info!("Validating Liquid state...");
-        // TODO: Implement actual state validation
-        Ok(ValidationResult::default())
+        Ok(self.proof_verifier.validate_anchor(state))
+    }
+
+    async fn estimate_move(&self, request: &MoveRequest) -> AnyaResult<MoveEstimate> {
+        const LIQUID: Venue = Venue::Layer2(Layer2Type::Liquid);
+        let wallets = self.wallets()?;
+        match (request.from, request.to) {
+            (Venue::Bitcoin, LIQUID) if request.is_btc() => {
+                let destination = self.liquid_destination(&request.destination)?;
+                let (funding_fee, claim_fee) = self.peg_in_fees(request.amount, request.fee_rate, &destination).await?;
+                let latency = wallets.onchain.block_interval().await? * self.config.peg_in_confirmations
+                    + wallets.elements.block_interval().await?;
+                Ok(MoveEstimate { fee: (funding_fee + claim_fee).to_sat(), latency })
+            },
+            (LIQUID, Venue::Bitcoin) if request.is_btc() => {
+                let destination = self.mainchain_destination(&request.destination)?;
+                let fee = wallets.elements.peg_out_fee(&destination, Amount::from_sat(request.amount)).await?;
+                let latency = wallets.elements.block_interval().await? * PEGOUT_CONFIRMATIONS
+                    + wallets.onchain.block_interval().await?;
+                Ok(MoveEstimate { fee: fee.to_sat(), latency })
+            },
+            _ => Err(request.unsupported("Liquid")),
+        }
+    }
+
+    async fn execute_move(&self, request: &MoveRequest) -> AnyaResult<TransferResult> {
+        const LIQUID: Venue = Venue::Layer2(Layer2Type::Liquid);
+        let wallets = self.wallets()?;
+        let tx_id = match (request.from, request.to) {
+            (Venue::Bitcoin, LIQUID) if request.is_btc() => {
+                let destination = self.liquid_destination(&request.destination)?;
+                let (funding_fee, _) = self.peg_in_fees(request.amount, request.fee_rate, &destination).await?;
+                let script = self.peg_in_address(&self.move_claim_script()?)?.script_pubkey();
+                let funding = wallets.onchain.pay(&script, Amount::from_sat(request.amount) - funding_fee, request.fee_rate).await?;
+
+                let move_id = format!("liquid_peg_in:{}", funding.compute_txid());
+                info!("Funded Liquid peg-in {}", move_id);
+                self.moves.lock().unwrap().insert(move_id.clone(), PegInMove { funding, destination, claim: None });
+                move_id
+            },
+            (LIQUID, Venue::Bitcoin) if request.is_btc() => {
+                let destination = self.mainchain_destination(&request.destination)?;
+                let fee = wallets.elements.peg_out_fee(&destination, Amount::from_sat(request.amount)).await?;
+                let amount = Amount::from_sat(request.amount).checked_sub(fee)
+                    .ok_or_else(|| AnyaError::System(format!("{} sat does not cover the {} peg-out fee", request.amount, fee)))?;
+                let txid = wallets.elements.send_to_mainchain(&destination, amount).await?;
+                info!("Sent Liquid peg-out {} of {} to {}", txid, amount, destination);
+                format!("liquid_peg_out:{}", txid)
+            },
+            _ => return Err(request.unsupported("Liquid")),
+        };
+        Ok(TransferResult { tx_id, status: TransactionStatus::Pending, timestamp: chrono::Utc::now().timestamp() as u64 })
+    }
+
+    async fn refund_move(&self, request: &MoveRequest, tx_id: &str) -> AnyaResult<Option<MoveRefund>> {
+        // Moves only fail when their first transaction was dropped, leaving
+        // the funds with the sender
+        match self.get_transaction_status(tx_id).await? {
+            TransactionStatus::Failed | TransactionStatus::Rejected => Ok(None),
+            status => Err(AnyaError::System(format!(
+                "{} ({} to {}) is {:?}, not failed", tx_id, request.from, request.to, status
+            ))),
+        }
+    }
+} 
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::pegin::tests::{bury, claim_script, fedpeg_script, fund, key, verifier};
+    use crate::layer2::proof::tests::mine;
+    use bitcoin::{absolute, transaction, MerkleBlock, OutPoint, Psbt, TxIn, TxOut, Witness};
+
+    /// Wallets on both chains, with one mainchain coin
+    struct TestWallets {
+        mined: Mutex<HashMap<Txid, MerkleBlock>>,
+        broadcast: Mutex<Vec<Transaction>>,
+        claims: Mutex<Vec<String>>,
+        liquid_confirmations: Mutex<Option<u32>>,
+    }
+
+    impl TestWallets {
+        fn new() -> Self {
+            Self {
+                mined: Mutex::new(HashMap::new()),
+                broadcast: Mutex::new(Vec::new()),
+                claims: Mutex::new(Vec::new()),
+                liquid_confirmations: Mutex::new(Some(0)),
+            }
+        }
+
+        fn coin() -> (OutPoint, TxOut) {
+            (
+                OutPoint { txid: Txid::from_byte_array([0x21; 32]), vout: 0 },
+                TxOut { value: Amount::from_sat(1_000_000), script_pubkey: claim_script(&key(5)) },
+            )
+        }
+
+        /// Mine the first broadcast transaction alone in a block on the verifier's tip
+        fn mine(&self, verifier: &ProofVerifier) {
+            let tx = self.broadcast.lock().unwrap()[0].clone();
+            let txid = tx.compute_txid();
+            let (tip, height) = verifier.tip().unwrap();
+            let header = mine(tip, bitcoin::TxMerkleNode::from_raw_hash(txid.to_raw_hash()), 1_700_000_000 + 600 * (height + 1));
+            verifier.add_header(header).unwrap();
+            self.mined.lock().unwrap().insert(txid, PegInProof::new(tx, header, &[txid]).merkle_block);
+        }
+    }
+
+    #[async_trait]
+    impl OnchainWallet for TestWallets {
+        async fn utxos(&self) -> AnyaResult<Vec<(OutPoint, TxOut)>> {
+            Ok(vec![Self::coin()])
+        }
+
+        async fn change_script(&self) -> AnyaResult<ScriptBuf> {
+            Ok(claim_script(&key(5)))
+        }
+
+        async fn create_payment(&self, script_pubkey: &Script, amount: Amount, fee_rate: u64) -> AnyaResult<Psbt> {
+            let (outpoint, coin) = Self::coin();
+            let fee = Amount::from_sat(141 * fee_rate);
+            let tx = Transaction {
+                version: transaction::Version::TWO,
+                lock_time: absolute::LockTime::ZERO,
+                input: vec![TxIn { previous_output: outpoint, ..Default::default() }],
+                output: vec![
+                    TxOut { value: amount, script_pubkey: script_pubkey.to_owned() },
+                    TxOut { value: coin.value - amount - fee, script_pubkey: coin.script_pubkey.clone() },
+                ],
+            };
+            let mut psbt = Psbt::from_unsigned_tx(tx).unwrap();
+            psbt.inputs[0].witness_utxo = Some(coin);
+            Ok(psbt)
+        }
+
+        async fn sign(&self, psbt: Psbt) -> AnyaResult<Transaction> {
+            let mut tx = psbt.unsigned_tx;
+            tx.input[0].witness = Witness::from_slice(&[vec![0x30; 71], vec![0x02; 33]]);
+            Ok(tx)
+        }
+
+        async fn broadcast(&self, tx: &Transaction) -> AnyaResult<Txid> {
+            self.broadcast.lock().unwrap().push(tx.clone());
+            Ok(tx.compute_txid())
+        }
+
+        async fn confirmations(&self, txid: &Txid) -> AnyaResult<Option<u32>> {
+            Ok(Some(u32::from(self.mined.lock().unwrap().contains_key(txid))))
+        }
+
+        async fn merkle_block(&self, txid: &Txid) -> AnyaResult<Option<MerkleBlock>> {
+            Ok(self.mined.lock().unwrap().get(txid).cloned())
+        }
+
+        async fn block_interval(&self) -> AnyaResult<Duration> {
+            Ok(Duration::from_secs(600))
+        }
+    }
+
+    #[async_trait]
+    impl ElementsWallet for TestWallets {
+        async fn fee_rate(&self) -> AnyaResult<u64> {
+            Ok(100)
+        }
+
+        async fn peg_out_fee(&self, _: &BtcAddress, _: Amount) -> AnyaResult<Amount> {
+            Ok(Amount::from_sat(300))
+        }
+
+        async fn send_to_mainchain(&self, _: &BtcAddress, _: Amount) -> AnyaResult<Txid> {
+            Ok(Txid::from_byte_array([0x77; 32]))
+        }
+
+        async fn broadcast(&self, tx_hex: &str) -> AnyaResult<Txid> {
+            self.claims.lock().unwrap().push(tx_hex.to_string());
+            Ok(Txid::from_byte_array([0x88; 32]))
+        }
+
+        async fn confirmations(&self, _: &Txid) -> AnyaResult<Option<u32>> {
+            Ok(*self.liquid_confirmations.lock().unwrap())
+        }
+
+        async fn block_interval(&self) -> AnyaResult<Duration> {
+            Ok(Duration::from_secs(60))
+        }
     }
-} 
\ No newline at end of file
+
+    #[tokio::test]
+    async fn test_peg_in_flow() {
+        let verifier = verifier();
+        let protocol = LiquidProtocol::with_config(LiquidConfig {
+            network: LiquidNetwork::ElementsRegtest,
+            fedpeg_script: fedpeg_script(),
+            policy_asset: Some(AssetId([0x5a; 32])),
+            peg_in_confirmations: PEGIN_CONFIRMATIONS,
+        })
+        .with_proof_verifier(verifier.clone());
+
+        let claim_key = key(42);
+        let address = protocol.peg_in_address(&claim_script(&claim_key)).unwrap();
+        let proof = fund(&verifier, address.script_pubkey(), Amount::from_sat(250_000));
+        let destination = claim_script(&key(7));
+
+        assert!(matches!(
+            protocol.claim_peg_in(&proof, destination.clone(), Amount::from_sat(300), &claim_key),
+            Err(LiquidError::ProofError(ProofError::InsufficientConfirmations { have: 1, need: 102 }))
+        ));
+
+        let (confirmations, _) = tokio::join!(
+            protocol.wait_for_peg_in(&proof, Duration::from_millis(1)),
+            async {
+                for _ in 0..101 {
+                    bury(&verifier, 1);
+                    tokio::task::yield_now().await;
+                }
+            },
+        );
+        assert_eq!(confirmations.unwrap(), 102);
+
+        let claim = protocol.claim_peg_in(&proof, destination, Amount::from_sat(300), &claim_key).unwrap();
+        assert_eq!(claim.outputs[0].value, 249_700);
+        assert_eq!(claim.pegin_witness[2], LiquidNetwork::ElementsRegtest.parent_genesis_hash().to_byte_array());
+
+        // Payments to addresses this node did not issue are not tracked
+        let stranger = fund(&verifier, claim_script(&key(8)), Amount::from_sat(10_000));
+        assert!(matches!(protocol.peg_in_status(&stranger), Err(LiquidError::PegInNotFound(_))));
+        assert!(matches!(
+            LiquidProtocol::new().peg_in_address(&claim_script(&claim_key)),
+            Err(LiquidError::ConfigError(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_moves() {
+        let verifier = verifier();
+        let wallets = Arc::new(TestWallets::new());
+        let protocol = LiquidProtocol::with_config(LiquidConfig {
+            network: LiquidNetwork::ElementsRegtest,
+            fedpeg_script: fedpeg_script(),
+            policy_asset: Some(AssetId([0x5a; 32])),
+            peg_in_confirmations: PEGIN_CONFIRMATIONS,
+        })
+        .with_proof_verifier(verifier.clone())
+        .with_move_wallets(wallets.clone(), wallets.clone(), key(42));
+
+        let liquid = Venue::Layer2(Layer2Type::Liquid);
+        let destination = MasterBlindingKey::from_seed(&[1; 32])
+            .address(LiquidNetwork::ElementsRegtest, &claim_script(&key(7)))
+            .unwrap();
+        let request = MoveRequest {
+            asset_id: crate::layer2::BTC_ASSET_ID.to_string(),
+            amount: 250_000,
+            from: Venue::Bitcoin,
+            to: liquid,
+            source: String::new(),
+            destination: destination.to_string(),
+            fee_rate: 2,
+        };
+        assert!(protocol.estimate_move(&MoveRequest { from: liquid, ..request.clone() }).await.is_err());
+
+        let estimate = protocol.estimate_move(&request).await.unwrap();
+        assert_eq!(estimate.latency, Duration::from_secs(102 * 600 + 60));
+        let claim_fee = Amount::from_sat(estimate.fee - 282);
+        assert!(claim_fee > Amount::ZERO);
+
+        let result = protocol.execute_move(&request).await.unwrap();
+        let funding = wallets.broadcast.lock().unwrap()[0].clone();
+        assert_eq!(result.tx_id, format!("liquid_peg_in:{}", funding.compute_txid()));
+        assert_eq!(funding.output[0].value, Amount::from_sat(250_000 - 282));
+        assert!(matches!(protocol.get_transaction_status(&result.tx_id).await.unwrap(), TransactionStatus::Pending));
+
+        // Claimed, at the estimated fee, once deep enough
+        wallets.mine(&verifier);
+        assert!(matches!(protocol.get_transaction_status(&result.tx_id).await.unwrap(), TransactionStatus::Pending));
+        assert!(wallets.claims.lock().unwrap().is_empty());
+        bury(&verifier, 101);
+        assert!(matches!(protocol.get_transaction_status(&result.tx_id).await.unwrap(), TransactionStatus::Pending));
+        let proof = PegInProof { tx: funding.clone(), merkle_block: wallets.mined.lock().unwrap()[&funding.compute_txid()].clone() };
+        let claim = protocol.claim_peg_in(&proof, destination.script_pubkey(), claim_fee, &key(42)).unwrap();
+        assert_eq!(claim.outputs[0].value, 250_000 - estimate.fee);
+        assert_eq!(*wallets.claims.lock().unwrap(), [claim.to_hex()]);
+        assert!(protocol.refund_move(&request, &result.tx_id).await.is_err());
+
+        *wallets.liquid_confirmations.lock().unwrap() = Some(1);
+        assert!(matches!(protocol.get_transaction_status(&result.tx_id).await.unwrap(), TransactionStatus::Confirmed));
+
+        // Peg-outs confirm once the federation would sweep them
+        let mainchain = BtcAddress::from_script(&claim_script(&key(7)), Network::Regtest).unwrap();
+        let request = MoveRequest { from: liquid, to: Venue::Bitcoin, destination: mainchain.to_string(), ..request };
+        let estimate = protocol.estimate_move(&request).await.unwrap();
+        assert_eq!(estimate.fee, 300);
+        assert_eq!(estimate.latency, Duration::from_secs(2 * 60 + 600));
+
+        let result = protocol.execute_move(&request).await.unwrap();
+        assert_eq!(result.tx_id, format!("liquid_peg_out:{}", Txid::from_byte_array([0x77; 32])));
+        assert!(matches!(protocol.get_transaction_status(&result.tx_id).await.unwrap(), TransactionStatus::Pending));
+        *wallets.liquid_confirmations.lock().unwrap() = Some(2);
+        assert!(matches!(protocol.get_transaction_status(&result.tx_id).await.unwrap(), TransactionStatus::Confirmed));
+
+        // A dropped peg-out left the funds in the Elements wallet
+        *wallets.liquid_confirmations.lock().unwrap() = None;
+        assert!(matches!(protocol.get_transaction_status(&result.tx_id).await.unwrap(), TransactionStatus::Failed));
+        assert_eq!(protocol.refund_move(&request, &result.tx_id).await.unwrap(), None);
+    }
+}
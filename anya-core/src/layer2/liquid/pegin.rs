@@ -0,0 +1,563 @@
+//! Liquid peg-ins
+//!
+//! Coins enter Liquid by paying a mainchain address derived from the
+//! federation's peg-in script (the fedpeg script) and a claim script chosen by
+//! the user. Every public key the federation signs with is tweaked by
+//! `HMAC-SHA256(key, claim_script)`, and the mainchain address is the
+//! P2SH-wrapped P2WSH of the tweaked script. Only the holder of the claim
+//! script can later show which tweak was used.
+//!
+//! Once the funding transaction has the required confirmations, the claim is
+//! an Elements transaction spending the mainchain output with the peg-in flag
+//! set. Its peg-in witness carries the amount, the pegged asset, the parent
+//! chain genesis hash, the claim script, the funding transaction and a
+//! `MerkleBlock` (txoutproof) showing it was mined.
+//!
+//! Claim outputs are explicit; blinding them is left to the wallet that spends
+//! the claimed coins.
+
+use std::fmt;
+use std::str::FromStr;
+
+use bitcoin::blockdata::opcodes::all::OP_ELSE;
+use bitcoin::blockdata::script::{Builder, Instruction};
+use bitcoin::consensus::encode::{serialize, Encodable, VarInt};
+use bitcoin::hashes::{hash160, hmac, sha256, sha256d, Hash, HashEngine};
+use bitcoin::secp256k1::{Message, PublicKey, Scalar, Secp256k1, SecretKey};
+use bitcoin::{
+    Address, Amount, BlockHash, MerkleBlock, Network, OutPoint, Script, ScriptBuf, Sequence, Transaction, Txid,
+    Witness,
+};
+
+use crate::layer2::proof::ProofVerifier;
+
+use super::LiquidError;
+
+/// Mainchain confirmations Liquid requires before a peg-in can be claimed
+pub const PEGIN_CONFIRMATIONS: u32 = 102;
+
+/// Bit marking a peg-in in the serialized input index
+const OUTPOINT_PEGIN_FLAG: u32 = 1 << 30;
+
+/// Transaction version used for claims
+const CLAIM_VERSION: u32 = 2;
+
+/// `SIGHASH_ALL`
+const SIGHASH_ALL: u32 = 1;
+
+/// Elements asset identifier
+///
+/// Stored in internal byte order and displayed reversed, like a txid.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct AssetId(pub [u8; 32]);
+
+impl fmt::Display for AssetId {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut bytes = self.0;
+        bytes.reverse();
+        f.write_str(&hex::encode(bytes))
+    }
+}
+
+impl FromStr for AssetId {
+    type Err = LiquidError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut bytes: [u8; 32] = hex::decode(s).ok()
+            .and_then(|bytes| bytes.try_into().ok())
+            .ok_or_else(|| LiquidError::PegInError(format!("Invalid asset id: {}", s)))?;
+        bytes.reverse();
+        Ok(Self(bytes))
+    }
+}
+
+/// Tweak every federation key in `fedpeg_script` with `claim_script`
+///
+/// Keys after the first `OP_ELSE` belong to the emergency branch and are left
+/// untouched, as in Elements.
+pub fn tweak_fedpeg_script(fedpeg_script: &Script, claim_script: &Script) -> Result<ScriptBuf, LiquidError> {
+    let secp = Secp256k1::verification_only();
+    let mut builder = Builder::new();
+    let mut tweaked = 0;
+    let mut in_else = false;
+    for instruction in fedpeg_script.instructions() {
+        let instruction = instruction
+            .map_err(|e| LiquidError::FederationError(format!("Malformed federation script: {}", e)))?;
+        builder = match instruction {
+            Instruction::PushBytes(data) if data.len() == 33 && !in_else => {
+                let key = PublicKey::from_slice(data.as_bytes())
+                    .map_err(|e| LiquidError::FederationError(format!("Invalid federation key: {}", e)))?;
+                let mut engine = hmac::HmacEngine::<sha256::Hash>::new(data.as_bytes());
+                engine.input(claim_script.as_bytes());
+                let tweak = hmac::Hmac::<sha256::Hash>::from_engine(engine).to_byte_array();
+                let tweak = Scalar::from_be_bytes(tweak)
+                    .map_err(|_| LiquidError::FederationError("Tweak out of range".to_string()))?;
+                let key = key.add_exp_tweak(&secp, &tweak)
+                    .map_err(|e| LiquidError::FederationError(format!("Key tweak failed: {}", e)))?;
+                tweaked += 1;
+                builder.push_slice(key.serialize())
+            },
+            Instruction::PushBytes(data) => builder.push_slice(data),
+            Instruction::Op(op) => {
+                in_else |= op == OP_ELSE;
+                builder.push_opcode(op)
+            },
+        };
+    }
+    if tweaked == 0 {
+        return Err(LiquidError::FederationError("Federation script has no keys".to_string()));
+    }
+    Ok(builder.into_script())
+}
+
+/// Mainchain script paying a peg-in for `claim_script`
+pub fn peg_in_script(fedpeg_script: &Script, claim_script: &Script) -> Result<ScriptBuf, LiquidError> {
+    let witness_script = tweak_fedpeg_script(fedpeg_script, claim_script)?;
+    Ok(ScriptBuf::new_p2wsh(&witness_script.wscript_hash()).to_p2sh())
+}
+
+/// Mainchain address paying a peg-in for `claim_script`
+pub fn peg_in_address(fedpeg_script: &Script, claim_script: &Script, network: Network) -> Result<Address, LiquidError> {
+    let script = peg_in_script(fedpeg_script, claim_script)?;
+    Address::from_script(&script, network).map_err(|e| LiquidError::AddressError(e.to_string()))
+}
+
+/// Progress of a peg-in towards being claimable
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PegInStatus {
+    /// Mined but not yet buried deep enough
+    Pending {
+        /// Current confirmations
+        confirmations: u32,
+        /// Confirmations needed to claim
+        required: u32,
+    },
+    /// Deep enough to claim
+    Claimable {
+        /// Current confirmations
+        confirmations: u32,
+    },
+}
+
+/// Mainchain funding transaction with its txoutproof
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PegInProof {
+    /// Funding transaction
+    pub tx: Transaction,
+    /// Partial merkle tree proving the funding transaction was mined
+    pub merkle_block: MerkleBlock,
+}
+
+impl PegInProof {
+    /// Proof for `tx`, mined in the block whose header and txids are given
+    pub fn new(tx: Transaction, header: bitcoin::block::Header, block_txids: &[Txid]) -> Self {
+        let txid = tx.compute_txid();
+        let merkle_block = MerkleBlock::from_header_txids_with_predicate(&header, block_txids, |id| *id == txid);
+        Self { tx, merkle_block }
+    }
+
+    /// Index of the output paying `script`
+    pub fn output_paying(&self, script: &Script) -> Result<u32, LiquidError> {
+        self.tx.output.iter()
+            .position(|output| output.script_pubkey.as_script() == script)
+            .map(|vout| vout as u32)
+            .ok_or_else(|| LiquidError::PegInError(format!("{} does not pay the peg-in address", self.tx.compute_txid())))
+    }
+
+    /// Hash of the block the funding transaction was mined in
+    pub fn block_hash(&self) -> BlockHash {
+        self.merkle_block.header.block_hash()
+    }
+
+    /// Check the txoutproof against the tracked chain
+    pub fn status(&self, verifier: &ProofVerifier, required: u32) -> Result<PegInStatus, LiquidError> {
+        let mut matches = Vec::new();
+        let mut indexes = Vec::new();
+        let root = self.merkle_block.txn.extract_matches(&mut matches, &mut indexes)
+            .map_err(|e| LiquidError::PegInError(format!("Malformed txoutproof: {}", e)))?;
+        if root != self.merkle_block.header.merkle_root {
+            return Err(LiquidError::PegInError("Txoutproof does not match the block's merkle root".to_string()));
+        }
+        if matches != [self.tx.compute_txid()] {
+            return Err(LiquidError::PegInError("Txoutproof does not prove the funding transaction".to_string()));
+        }
+
+        let confirmations = verifier.confirmations(&self.block_hash())?;
+        Ok(if confirmations >= required {
+            PegInStatus::Claimable { confirmations }
+        } else {
+            PegInStatus::Pending { confirmations, required }
+        })
+    }
+}
+
+/// Explicit (unblinded) Elements output
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExplicitTxOut {
+    /// Asset carried by the output
+    pub asset: AssetId,
+    /// Amount in satoshis
+    pub value: u64,
+    /// Locking script; empty for the fee output
+    pub script_pubkey: ScriptBuf,
+}
+
+impl ExplicitTxOut {
+    fn consensus_encode(&self, out: &mut Vec<u8>) {
+        out.push(0x01);
+        out.extend_from_slice(&self.asset.0);
+        encode_explicit_value(self.value, out);
+        // Null nonce
+        out.push(0x00);
+        encode_bytes(self.script_pubkey.as_bytes(), out);
+    }
+}
+
+/// Elements transaction claiming a peg-in
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ClaimTransaction {
+    /// Mainchain output being claimed
+    pub previous_output: OutPoint,
+    /// Peg-in amount
+    pub value: Amount,
+    /// Witness satisfying the claim script
+    pub witness: Witness,
+    /// Peg-in witness stack
+    pub pegin_witness: Vec<Vec<u8>>,
+    /// Claimed outputs, fee last
+    pub outputs: Vec<ExplicitTxOut>,
+}
+
+impl ClaimTransaction {
+    /// Claim `proof` to `destination`, paying `fee` from the peg-in amount
+    pub fn new(
+        proof: &PegInProof,
+        vout: u32,
+        claim_script: &Script,
+        destination: ScriptBuf,
+        fee: Amount,
+        asset: AssetId,
+        parent_genesis: BlockHash,
+    ) -> Result<Self, LiquidError> {
+        let output = proof.tx.output.get(vout as usize)
+            .ok_or_else(|| LiquidError::PegInError(format!("Funding transaction has no output {}", vout)))?;
+        let value = output.value;
+        if fee >= value {
+            return Err(LiquidError::PegInError(format!("Fee {} consumes the peg-in amount {}", fee, value)));
+        }
+
+        let mut stripped = proof.tx.clone();
+        stripped.input.iter_mut().for_each(|input| input.witness.clear());
+        let pegin_witness = vec![
+            value.to_sat().to_le_bytes().to_vec(),
+            asset.0.to_vec(),
+            parent_genesis.to_byte_array().to_vec(),
+            claim_script.to_bytes(),
+            serialize(&stripped),
+            serialize(&proof.merkle_block),
+        ];
+
+        Ok(Self {
+            previous_output: OutPoint { txid: proof.tx.compute_txid(), vout },
+            value,
+            witness: Witness::new(),
+            pegin_witness,
+            outputs: vec![
+                ExplicitTxOut { asset, value: (value - fee).to_sat(), script_pubkey: destination },
+                ExplicitTxOut { asset, value: fee.to_sat(), script_pubkey: ScriptBuf::new() },
+            ],
+        })
+    }
+
+    /// Elements serialization, with or without witness data
+    pub fn serialize(&self, with_witness: bool) -> Vec<u8> {
+        let mut out = Vec::new();
+        out.extend_from_slice(&CLAIM_VERSION.to_le_bytes());
+        out.push(u8::from(with_witness));
+
+        encode_varint(1, &mut out);
+        out.extend_from_slice(&self.previous_output.txid.to_byte_array());
+        out.extend_from_slice(&(self.previous_output.vout | OUTPOINT_PEGIN_FLAG).to_le_bytes());
+        // Empty scriptSig
+        out.push(0x00);
+        out.extend_from_slice(&Sequence::MAX.to_consensus_u32().to_le_bytes());
+
+        encode_varint(self.outputs.len(), &mut out);
+        for output in &self.outputs {
+            output.consensus_encode(&mut out);
+        }
+        // Lock time
+        out.extend_from_slice(&0u32.to_le_bytes());
+
+        if with_witness {
+            // No issuance or inflation rangeproofs
+            out.extend_from_slice(&[0x00, 0x00]);
+            encode_stack(self.witness.iter(), self.witness.len(), &mut out);
+            encode_stack(self.pegin_witness.iter().map(Vec::as_slice), self.pegin_witness.len(), &mut out);
+            // No surjection proofs or rangeproofs on explicit outputs
+            for _ in &self.outputs {
+                out.extend_from_slice(&[0x00, 0x00]);
+            }
+        }
+        out
+    }
+
+    /// Transaction ID on the Liquid chain
+    pub fn txid(&self) -> Txid {
+        Txid::from_raw_hash(sha256d::Hash::hash(&self.serialize(false)))
+    }
+
+    /// Virtual size on the Liquid chain
+    pub fn vsize(&self) -> u64 {
+        let base = self.serialize(false).len() as u64;
+        let total = self.serialize(true).len() as u64;
+        (base * 3 + total).div_ceil(4)
+    }
+
+    /// Hex of the full transaction, ready for `sendrawtransaction`
+    pub fn to_hex(&self) -> String {
+        hex::encode(self.serialize(true))
+    }
+
+    /// Segwit v0 signature hash of the claim input for `script_code`
+    pub fn sighash(&self, script_code: &Script) -> sha256d::Hash {
+        let mut prevouts = Vec::new();
+        self.previous_output.consensus_encode(&mut prevouts).expect("vec write");
+        let sequences = Sequence::MAX.to_consensus_u32().to_le_bytes();
+        // One input without issuance
+        let issuances = [0x00];
+        let mut outputs = Vec::new();
+        for output in &self.outputs {
+            output.consensus_encode(&mut outputs);
+        }
+
+        let mut preimage = Vec::new();
+        preimage.extend_from_slice(&CLAIM_VERSION.to_le_bytes());
+        preimage.extend_from_slice(&sha256d::Hash::hash(&prevouts).to_byte_array());
+        preimage.extend_from_slice(&sha256d::Hash::hash(&sequences).to_byte_array());
+        preimage.extend_from_slice(&sha256d::Hash::hash(&issuances).to_byte_array());
+        preimage.extend_from_slice(&prevouts);
+        encode_bytes(script_code.as_bytes(), &mut preimage);
+        encode_explicit_value(self.value.to_sat(), &mut preimage);
+        preimage.extend_from_slice(&sequences);
+        preimage.extend_from_slice(&sha256d::Hash::hash(&outputs).to_byte_array());
+        preimage.extend_from_slice(&0u32.to_le_bytes());
+        preimage.extend_from_slice(&SIGHASH_ALL.to_le_bytes());
+        sha256d::Hash::hash(&preimage)
+    }
+
+    /// Sign a P2WPKH claim script with its key
+    pub fn sign_p2wpkh(&mut self, key: &SecretKey) -> Result<(), LiquidError> {
+        let secp = Secp256k1::new();
+        let pubkey = bitcoin::PublicKey::new(key.public_key(&secp));
+        let claim_script = self.pegin_witness.get(3).map(|script| ScriptBuf::from_bytes(script.clone()));
+        let wpubkey_hash = pubkey.wpubkey_hash().expect("compressed key");
+        if claim_script != Some(ScriptBuf::new_p2wpkh(&wpubkey_hash)) {
+            return Err(LiquidError::SigningError("Key does not match the claim script".to_string()));
+        }
+
+        let script_code = ScriptBuf::new_p2pkh(&hash160::Hash::from_byte_array(wpubkey_hash.to_byte_array()).into());
+        let message = Message::from_digest(self.sighash(&script_code).to_byte_array());
+        let mut signature = secp.sign_ecdsa(&message, key).serialize_der().to_vec();
+        signature.push(SIGHASH_ALL as u8);
+
+        let mut witness = Witness::new();
+        witness.push(signature);
+        witness.push(pubkey.to_bytes());
+        self.witness = witness;
+        Ok(())
+    }
+}
+
+fn encode_varint(n: usize, out: &mut Vec<u8>) {
+    VarInt(n as u64).consensus_encode(out).expect("vec write");
+}
+
+fn encode_bytes(bytes: &[u8], out: &mut Vec<u8>) {
+    encode_varint(bytes.len(), out);
+    out.extend_from_slice(bytes);
+}
+
+fn encode_stack<'a>(items: impl Iterator<Item = &'a [u8]>, len: usize, out: &mut Vec<u8>) {
+    encode_varint(len, out);
+    for item in items {
+        encode_bytes(item, out);
+    }
+}
+
+/// Explicit confidential value: `0x01` and the amount big-endian
+fn encode_explicit_value(value: u64, out: &mut Vec<u8>) {
+    out.push(0x01);
+    out.extend_from_slice(&value.to_be_bytes());
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use bitcoin::blockdata::opcodes::all::{OP_CHECKMULTISIG, OP_CHECKSIG, OP_CSV, OP_DROP, OP_ENDIF, OP_IF, OP_PUSHNUM_1, OP_PUSHNUM_2, OP_PUSHNUM_3};
+    use bitcoin::hashes::Hash;
+    use bitcoin::{absolute, transaction, TxIn, TxOut};
+    use crate::layer2::proof::tests::mine;
+    use crate::layer2::proof::HeaderChain;
+
+    pub(crate) fn key(n: u8) -> SecretKey {
+        SecretKey::from_slice(&[n; 32]).unwrap()
+    }
+
+    /// 2-of-3 federation with a 1-of-1 emergency key after a timeout
+    pub(crate) fn fedpeg_script() -> ScriptBuf {
+        let secp = Secp256k1::new();
+        let mut builder = Builder::new().push_opcode(OP_IF).push_opcode(OP_PUSHNUM_2);
+        for n in 1..=3 {
+            builder = builder.push_slice(key(n).public_key(&secp).serialize());
+        }
+        builder
+            .push_opcode(OP_PUSHNUM_3)
+            .push_opcode(OP_CHECKMULTISIG)
+            .push_opcode(OP_ELSE)
+            .push_int(4032)
+            .push_opcode(OP_CSV)
+            .push_opcode(OP_DROP)
+            .push_slice(key(9).public_key(&secp).serialize())
+            .push_opcode(OP_CHECKSIG)
+            .push_opcode(OP_ENDIF)
+            .into_script()
+    }
+
+    pub(crate) fn claim_script(claim_key: &SecretKey) -> ScriptBuf {
+        let pubkey = bitcoin::PublicKey::new(claim_key.public_key(&Secp256k1::new()));
+        ScriptBuf::new_p2wpkh(&pubkey.wpubkey_hash().unwrap())
+    }
+
+    /// Mine a transaction paying `script` on top of the verifier's tip
+    pub(crate) fn fund(verifier: &ProofVerifier, script: ScriptBuf, value: Amount) -> PegInProof {
+        let tx = Transaction {
+            version: transaction::Version::TWO,
+            lock_time: absolute::LockTime::ZERO,
+            input: vec![TxIn {
+                previous_output: OutPoint { txid: Txid::from_byte_array([0x11; 32]), vout: 0 },
+                witness: Witness::from_slice(&[vec![0xaa; 72]]),
+                ..Default::default()
+            }],
+            output: vec![
+                TxOut { value: Amount::from_sat(1_000), script_pubkey: ScriptBuf::new_op_return([0x6a]) },
+                TxOut { value, script_pubkey: script },
+            ],
+        };
+        let txids = [Txid::from_byte_array([0xcb; 32]), tx.compute_txid(), Txid::from_byte_array([0xcc; 32])];
+        let root = bitcoin::merkle_tree::calculate_root(txids.iter().map(|txid| txid.to_raw_hash()))
+            .map(bitcoin::TxMerkleNode::from_raw_hash)
+            .unwrap();
+        let (tip, height) = verifier.tip().unwrap();
+        let header = mine(tip, root, 1_700_000_000 + 600 * (height + 1));
+        verifier.add_header(header).unwrap();
+        PegInProof::new(tx, header, &txids)
+    }
+
+    /// Extend the verifier's chain by `count` empty blocks
+    pub(crate) fn bury(verifier: &ProofVerifier, count: u32) {
+        for _ in 0..count {
+            let (tip, height) = verifier.tip().unwrap();
+            verifier.add_header(mine(tip, bitcoin::TxMerkleNode::all_zeros(), 1_700_000_000 + 600 * (height + 1))).unwrap();
+        }
+    }
+
+    pub(crate) fn verifier() -> ProofVerifier {
+        let genesis = mine(BlockHash::all_zeros(), bitcoin::TxMerkleNode::all_zeros(), 1_700_000_000);
+        ProofVerifier::new(HeaderChain::from_checkpoint(genesis, 0, Network::Regtest).unwrap())
+    }
+
+    #[test]
+    fn test_peg_in_address() {
+        let secp = Secp256k1::new();
+        let fedpeg = fedpeg_script();
+        let claim = claim_script(&key(42));
+        let tweaked = tweak_fedpeg_script(&fedpeg, &claim).unwrap();
+        assert_eq!(tweaked.len(), fedpeg.len());
+
+        let keys: Vec<Vec<u8>> = tweaked.instructions()
+            .filter_map(|instruction| match instruction.unwrap() {
+                Instruction::PushBytes(data) if data.len() == 33 => Some(data.as_bytes().to_vec()),
+                _ => None,
+            })
+            .collect();
+        for n in 1..=3u8 {
+            let federation_key = key(n).public_key(&secp).serialize();
+            let mut engine = hmac::HmacEngine::<sha256::Hash>::new(&federation_key);
+            engine.input(claim.as_bytes());
+            let tweak = hmac::Hmac::<sha256::Hash>::from_engine(engine).to_byte_array();
+            let expected = key(n).add_tweak(&Scalar::from_be_bytes(tweak).unwrap()).unwrap().public_key(&secp);
+            assert_eq!(keys[n as usize - 1], expected.serialize().to_vec());
+        }
+        // The emergency key is not tweaked
+        assert_eq!(keys[3], key(9).public_key(&secp).serialize().to_vec());
+
+        let address = peg_in_address(&fedpeg, &claim, Network::Regtest).unwrap();
+        assert!(address.to_string().starts_with('2'));
+        assert_eq!(address.script_pubkey(), peg_in_script(&fedpeg, &claim).unwrap());
+        assert_ne!(peg_in_script(&fedpeg, &claim_script(&key(43))).unwrap(), address.script_pubkey());
+
+        let no_keys = Builder::new().push_opcode(OP_PUSHNUM_1).into_script();
+        assert!(matches!(tweak_fedpeg_script(&no_keys, &claim), Err(LiquidError::FederationError(_))));
+    }
+
+    #[test]
+    fn test_claim_transaction() {
+        let verifier = verifier();
+        let claim_key = key(42);
+        let claim = claim_script(&claim_key);
+        let script = peg_in_script(&fedpeg_script(), &claim).unwrap();
+        let proof = fund(&verifier, script.clone(), Amount::from_sat(100_000));
+        let vout = proof.output_paying(&script).unwrap();
+        assert_eq!(vout, 1);
+
+        assert_eq!(proof.status(&verifier, PEGIN_CONFIRMATIONS).unwrap(), PegInStatus::Pending { confirmations: 1, required: 102 });
+        bury(&verifier, 101);
+        assert_eq!(proof.status(&verifier, PEGIN_CONFIRMATIONS).unwrap(), PegInStatus::Claimable { confirmations: 102 });
+
+        let asset = AssetId([0x5a; 32]);
+        let genesis = BlockHash::from_byte_array([0x0f; 32]);
+        let destination = claim_script(&key(7));
+        let mut claim_tx = ClaimTransaction::new(&proof, vout, &claim, destination.clone(), Amount::from_sat(500), asset, genesis).unwrap();
+        assert_eq!(claim_tx.outputs[0].value, 99_500);
+        assert_eq!(claim_tx.outputs[1].value, 500);
+        assert!(claim_tx.outputs[1].script_pubkey.is_empty());
+
+        // The peg-in witness commits to everything the federation checks
+        assert_eq!(claim_tx.pegin_witness[0], 100_000u64.to_le_bytes());
+        assert_eq!(claim_tx.pegin_witness[3], claim.to_bytes());
+        let funding: Transaction = bitcoin::consensus::deserialize(&claim_tx.pegin_witness[4]).unwrap();
+        assert_eq!(funding.compute_txid(), proof.tx.compute_txid());
+        assert!(funding.input[0].witness.is_empty());
+        let merkle_block: MerkleBlock = bitcoin::consensus::deserialize(&claim_tx.pegin_witness[5]).unwrap();
+        assert_eq!(merkle_block.header, proof.merkle_block.header);
+        assert_eq!(serialize(&merkle_block), claim_tx.pegin_witness[5]);
+
+        // Signing only changes the witness, not the txid
+        let txid = claim_tx.txid();
+        assert!(matches!(claim_tx.sign_p2wpkh(&key(43)), Err(LiquidError::SigningError(_))));
+        claim_tx.sign_p2wpkh(&claim_key).unwrap();
+        assert_eq!(claim_tx.txid(), txid);
+        assert_eq!(claim_tx.witness.len(), 2);
+
+        let raw = claim_tx.serialize(true);
+        assert_eq!(&raw[..5], &[0x02, 0x00, 0x00, 0x00, 0x01]);
+        assert_eq!(&raw[38..42], &(vout | OUTPOINT_PEGIN_FLAG).to_le_bytes());
+        assert_eq!(claim_tx.serialize(false)[4], 0x00);
+
+        assert!(ClaimTransaction::new(&proof, vout, &claim, destination, Amount::from_sat(100_000), asset, genesis).is_err());
+
+        // A txoutproof for some other transaction is rejected
+        let mut other = fund(&verifier, script, Amount::from_sat(5_000));
+        other.tx = proof.tx.clone();
+        assert!(matches!(other.status(&verifier, PEGIN_CONFIRMATIONS), Err(LiquidError::PegInError(_))));
+    }
+
+    #[test]
+    fn test_asset_id_display() {
+        let id = "6f0279e9ed041c3d710a9f57d0c02928416460c4b722ae3457a11eec381c526d";
+        let asset: AssetId = id.parse().unwrap();
+        assert_eq!(asset.0[0], 0x6d);
+        assert_eq!(asset.to_string(), id);
+    }
+}
@@ -1,4 +1,39 @@
+//! Mock Layer 2 protocol
+//!
+//! Without a scenario every call succeeds at once. A [`MockScenario`] scripts
+//! per-method latency, random failures, fixed outcome sequences and hung
+//! calls, and lets submitted transactions move from `Pending` to a final
+//! status over time. Random draws come from a seeded generator, so a
+//! scenario plays out the same way on every run.
+//!
+//! Scenarios are usually written in YAML:
+//!
+//! ```yaml
+//! seed: 7
+//! methods:
+//!   connect:
+//!     sequence: [timeout, ok]
+//!   submit_transaction:
+//!     latency: { distribution: uniform, min_ms: 5, max_ms: 20 }
+//!     failure_rate: 0.1
+//!   sync_state:
+//!     sequence: [hang]
+//! transactions:
+//!   pending_polls: 2
+//!   outcome: confirmed
+//! ```
+
+use std::collections::HashMap;
+use std::path::Path;
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+
+use rand::rngs::StdRng;
+use rand::{Rng, SeedableRng};
+use serde::{Deserialize, Serialize};
+
 use crate::{
+    AnyaError,
     AnyaResult,
     layer2::{
         Layer2Protocol,
@@ -14,50 +49,357 @@
 };
 use async_trait::async_trait;
 
-#[derive(Debug, Clone)]
-pub struct MockLayer2Protocol {
-    pub initialized: bool,
-    pub connected: bool,
+/// Protocol method a scenario can script
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MockMethod {
+    Initialize,
+    Connect,
+    Disconnect,
+    SubmitTransaction,
+    GetTransactionStatus,
+    GetState,
+    SyncState,
+    IssueAsset,
+    TransferAsset,
+    VerifyProof,
+    ValidateState,
+}
+
+/// Delay added to a call
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Default)]
+#[serde(tag = "distribution", rename_all = "snake_case")]
+pub enum Latency {
+    /// Return at once
+    #[default]
+    None,
+    /// Always the same delay
+    Fixed { ms: u64 },
+    /// Uniformly drawn from `min_ms..=max_ms`
+    Uniform { min_ms: u64, max_ms: u64 },
+    /// Normally distributed, cut off at zero
+    Normal { mean_ms: f64, std_dev_ms: f64 },
+}
+
+impl Latency {
+    fn sample(&self, rng: &mut StdRng) -> Duration {
+        let ms = match self {
+            Latency::None => 0.0,
+            Latency::Fixed { ms } => *ms as f64,
+            Latency::Uniform { min_ms, max_ms } => rng.gen_range(*min_ms..=(*max_ms).max(*min_ms)) as f64,
+            Latency::Normal { mean_ms, std_dev_ms } => {
+                // Box-Muller transform
+                let u1: f64 = 1.0 - rng.gen::<f64>();
+                let u2: f64 = rng.gen();
+                mean_ms + std_dev_ms * (-2.0 * u1.ln()).sqrt() * (2.0 * std::f64::consts::PI * u2).cos()
+            },
+        };
+        Duration::from_secs_f64(ms.max(0.0) / 1000.0)
+    }
+}
+
+/// Scripted outcome of one call
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum MockOutcome {
+    /// Behave normally
+    Ok,
+    /// Never return
+    Hang,
+    /// Fail with [`AnyaError::System`]
+    System,
+    /// Fail with [`AnyaError::Timeout`]
+    Timeout,
+    /// Fail with [`AnyaError::OperationHang`]
+    OperationHang,
+    /// Fail with [`AnyaError::Config`]
+    Config,
+    /// Fail with [`AnyaError::Bitcoin`]
+    Bitcoin,
+}
+
+impl MockOutcome {
+    fn error(&self, method: MockMethod) -> Option<AnyaError> {
+        let message = format!("Injected failure in {:?}", method);
+        match self {
+            MockOutcome::Ok | MockOutcome::Hang => None,
+            MockOutcome::System => Some(AnyaError::System(message)),
+            MockOutcome::Timeout => Some(AnyaError::Timeout(message)),
+            MockOutcome::OperationHang => Some(AnyaError::OperationHang(message)),
+            MockOutcome::Config => Some(AnyaError::Config(message)),
+            MockOutcome::Bitcoin => Some(AnyaError::Bitcoin(message)),
+        }
+    }
+}
+
+/// Scripted behaviour of one method
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct MethodBehavior {
+    /// Delay before the call resolves
+    pub latency: Latency,
+    /// Outcomes of the first calls, in order
+    pub sequence: Vec<MockOutcome>,
+    /// Chance that a call past the sequence fails
+    pub failure_rate: f64,
+    /// Error of those random failures
+    pub failure: MockOutcome,
 }
 
-impl Default for MockLayer2Protocol {
+impl Default for MethodBehavior {
     fn default() -> Self {
         Self {
-            initialized: false,
-            connected: false,
+            latency: Latency::None,
+            sequence: Vec::new(),
+            failure_rate: 0.0,
+            failure: MockOutcome::System,
         }
     }
 }
 
+/// Final status of a submitted transaction
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum FinalStatus {
+    Confirmed,
+    Failed,
+    Rejected,
+}
+
+impl From<FinalStatus> for TransactionStatus {
+    fn from(status: FinalStatus) -> Self {
+        match status {
+            FinalStatus::Confirmed => TransactionStatus::Confirmed,
+            FinalStatus::Failed => TransactionStatus::Failed,
+            FinalStatus::Rejected => TransactionStatus::Rejected,
+        }
+    }
+}
+
+/// How submitted transactions progress
+///
+/// A transaction stays `Pending` for `pending_polls` status queries and at
+/// least `pending_ms`, then reports its final status.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default)]
+pub struct TransactionBehavior {
+    pub pending_polls: u32,
+    pub pending_ms: u64,
+    /// Final status of transactions not listed in `outcomes`
+    pub outcome: FinalStatus,
+    /// Final status of the first transactions, in submission order
+    pub outcomes: Vec<FinalStatus>,
+    /// Chance that a transaction past `outcomes` fails instead
+    pub failure_rate: f64,
+}
+
+impl Default for TransactionBehavior {
+    fn default() -> Self {
+        Self {
+            pending_polls: 0,
+            pending_ms: 0,
+            outcome: FinalStatus::Confirmed,
+            outcomes: Vec::new(),
+            failure_rate: 0.0,
+        }
+    }
+}
+
+/// Scenario driving a [`MockLayer2Protocol`]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, Default)]
+#[serde(default)]
+pub struct MockScenario {
+    /// Seed of the random draws
+    pub seed: u64,
+    /// Scripted methods; the others succeed at once
+    pub methods: HashMap<MockMethod, MethodBehavior>,
+    pub transactions: TransactionBehavior,
+}
+
+impl MockScenario {
+    /// Parse a scenario from YAML
+    pub fn from_yaml(yaml: &str) -> AnyaResult<Self> {
+        serde_yaml::from_str(yaml).map_err(|e| AnyaError::Config(format!("Invalid mock scenario: {}", e)))
+    }
+
+    /// Load a scenario from a YAML file
+    pub fn from_yaml_file(path: impl AsRef<Path>) -> AnyaResult<Self> {
+        let path = path.as_ref();
+        let yaml = std::fs::read_to_string(path)
+            .map_err(|e| AnyaError::Config(format!("{}: {}", path.display(), e)))?;
+        Self::from_yaml(&yaml)
+    }
+
+    /// Script a method
+    pub fn with_method(mut self, method: MockMethod, behavior: MethodBehavior) -> Self {
+        self.methods.insert(method, behavior);
+        self
+    }
+}
+
+/// Submitted transaction
+#[derive(Debug)]
+struct MockTransaction {
+    submitted: Instant,
+    polls: u32,
+    status: FinalStatus,
+}
+
+/// Mutable state of a running scenario
+#[derive(Debug)]
+struct ScenarioState {
+    rng: StdRng,
+    calls: HashMap<MockMethod, usize>,
+    transactions: HashMap<String, MockTransaction>,
+}
+
+#[derive(Debug)]
+struct ScenarioRunner {
+    scenario: MockScenario,
+    state: Mutex<ScenarioState>,
+}
+
+impl ScenarioRunner {
+    /// Apply the scripted behaviour of a call
+    async fn call(&self, method: MockMethod) -> AnyaResult<()> {
+        let (delay, outcome) = {
+            let mut state = self.state.lock().unwrap();
+            let index = {
+                let calls = state.calls.entry(method).or_insert(0);
+                *calls += 1;
+                *calls - 1
+            };
+            match self.scenario.methods.get(&method) {
+                Some(behavior) => {
+                    let delay = behavior.latency.sample(&mut state.rng);
+                    let outcome = match behavior.sequence.get(index) {
+                        Some(outcome) => *outcome,
+                        None if state.rng.gen_bool(behavior.failure_rate.clamp(0.0, 1.0)) => behavior.failure,
+                        None => MockOutcome::Ok,
+                    };
+                    (delay, outcome)
+                },
+                None => (Duration::ZERO, MockOutcome::Ok),
+            }
+        };
+
+        if !delay.is_zero() {
+            tokio::time::sleep(delay).await;
+        }
+        if outcome == MockOutcome::Hang {
+            std::future::pending::<()>().await;
+        }
+        match outcome.error(method) {
+            Some(error) => Err(error),
+            None => Ok(()),
+        }
+    }
+
+    /// Record a submitted transaction and return its ID
+    fn submit(&self) -> String {
+        let behavior = &self.scenario.transactions;
+        let mut state = self.state.lock().unwrap();
+        let index = state.transactions.len();
+        let status = match behavior.outcomes.get(index) {
+            Some(status) => *status,
+            None if state.rng.gen_bool(behavior.failure_rate.clamp(0.0, 1.0)) => FinalStatus::Failed,
+            None => behavior.outcome,
+        };
+
+        let tx_id = format!("mock_tx_{}", index);
+        state.transactions.insert(tx_id.clone(), MockTransaction { submitted: Instant::now(), polls: 0, status });
+        tx_id
+    }
+
+    fn status(&self, tx_id: &str) -> AnyaResult<TransactionStatus> {
+        let behavior = &self.scenario.transactions;
+        let mut state = self.state.lock().unwrap();
+        let tx = state.transactions.get_mut(tx_id)
+            .ok_or_else(|| AnyaError::System(format!("Unknown transaction: {}", tx_id)))?;
+
+        tx.polls += 1;
+        let settled = tx.polls > behavior.pending_polls
+            && tx.submitted.elapsed() >= Duration::from_millis(behavior.pending_ms);
+        Ok(if settled { tx.status.into() } else { TransactionStatus::Pending })
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct MockLayer2Protocol {
+    pub initialized: bool,
+    pub connected: bool,
+    scenario: Option<Arc<ScenarioRunner>>,
+}
+
 impl MockLayer2Protocol {
     pub fn new() -> Self {
         Self::default()
     }
+
+    /// Mock driven by a scenario
+    pub fn with_scenario(scenario: MockScenario) -> Self {
+        let state = ScenarioState {
+            rng: StdRng::seed_from_u64(scenario.seed),
+            calls: HashMap::new(),
+            transactions: HashMap::new(),
+        };
+        Self {
+            scenario: Some(Arc::new(ScenarioRunner { scenario, state: Mutex::new(state) })),
+            ..Self::default()
+        }
+    }
+
+    /// Number of calls made to a method under the scenario, including failed and hung ones
+    pub fn calls(&self, method: MockMethod) -> usize {
+        self.scenario.as_ref()
+            .and_then(|runner| runner.state.lock().unwrap().calls.get(&method).copied())
+            .unwrap_or(0)
+    }
+
+    async fn call(&self, method: MockMethod) -> AnyaResult<()> {
+        match &self.scenario {
+            Some(runner) => runner.call(method).await,
+            None => Ok(()),
+        }
+    }
+
+    fn submit(&self) -> String {
+        match &self.scenario {
+            Some(runner) => runner.submit(),
+            None => "mock_tx_id".to_string(),
+        }
+    }
 }
 
 #[async_trait]
 impl Layer2Protocol for MockLayer2Protocol {
     async fn initialize(&self) -> AnyaResult<()> {
-        Ok(())
+        self.call(MockMethod::Initialize).await
     }
 
     async fn connect(&self) -> AnyaResult<()> {
-        Ok(())
+        self.call(MockMethod::Connect).await
     }
 
     async fn disconnect(&self) -> AnyaResult<()> {
-        Ok(())
+        self.call(MockMethod::Disconnect).await
     }
 
     async fn submit_transaction(&self, _tx: &[u8]) -> AnyaResult<String> {
-        Ok("mock_tx_id".to_string())
+        self.call(MockMethod::SubmitTransaction).await?;
+        Ok(self.submit())
     }
 
-    async fn get_transaction_status(&self, _tx_id: &str) -> AnyaResult<TransactionStatus> {
-        Ok(TransactionStatus::Confirmed)
+    async fn get_transaction_status(&self, tx_id: &str) -> AnyaResult<TransactionStatus> {
+        self.call(MockMethod::GetTransactionStatus).await?;
+        match &self.scenario {
+            Some(runner) => runner.status(tx_id),
+            None => Ok(TransactionStatus::Confirmed),
+        }
     }
 
     async fn get_state(&self) -> AnyaResult<ProtocolState> {
+        self.call(MockMethod::GetState).await?;
         Ok(ProtocolState {
             height: 0,
             hash: "mock_hash".to_string(),
@@ -66,22 +408,26 @@ async fn get_state(&self) -> AnyaResult<ProtocolState> {
     }
 
     async fn sync_state(&self) -> AnyaResult<()> {
-        Ok(())
+        self.call(MockMethod::SyncState).await
     }
 
     async fn issue_asset(&self, _params: AssetParams) -> AnyaResult<String> {
+        self.call(MockMethod::IssueAsset).await?;
         Ok("mock_asset_id".to_string())
     }
 
     async fn transfer_asset(&self, _transfer: AssetTransfer) -> AnyaResult<TransferResult> {
+        self.call(MockMethod::TransferAsset).await?;
+        let status = if self.scenario.is_some() { TransactionStatus::Pending } else { TransactionStatus::Confirmed };
         Ok(TransferResult {
-            tx_id: "mock_tx_id".to_string(),
-            status: TransactionStatus::Confirmed,
+            tx_id: self.submit(),
+            status,
             timestamp: 0,
         })
     }
 
     async fn verify_proof(&self, _proof: &Proof) -> AnyaResult<VerificationResult> {
+        self.call(MockMethod::VerifyProof).await?;
         Ok(VerificationResult {
             valid: true,
             error: None,
@@ -89,9 +435,80 @@ async fn verify_proof(&self, _proof: &Proof) -> AnyaResult<VerificationResult> {
     }
 
     async fn validate_state(&self, _state: &ProtocolState) -> AnyaResult<ValidationResult> {
+        self.call(MockMethod::ValidateState).await?;
         Ok(ValidationResult {
             valid: true,
             error: None,
         })
     }
-} 
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[tokio::test]
+    async fn test_scripted_sequences_and_transactions() {
+        let scenario = MockScenario::from_yaml(r#"
+seed: 3
+methods:
+  connect:
+    sequence: [timeout, system, ok]
+  submit_transaction:
+    latency: { distribution: fixed, ms: 5 }
+transactions:
+  pending_polls: 2
+  outcomes: [confirmed, rejected]
+"#).unwrap();
+        let mock = MockLayer2Protocol::with_scenario(scenario);
+
+        assert!(matches!(mock.connect().await, Err(AnyaError::Timeout(_))));
+        assert!(matches!(mock.connect().await, Err(AnyaError::System(_))));
+        mock.connect().await.unwrap();
+        mock.connect().await.unwrap();
+        assert_eq!(mock.calls(MockMethod::Connect), 4);
+
+        let started = Instant::now();
+        let first = mock.submit_transaction(b"tx").await.unwrap();
+        assert!(started.elapsed() >= Duration::from_millis(5));
+        let second = mock.submit_transaction(b"tx").await.unwrap();
+        assert_ne!(first, second);
+
+        for _ in 0..2 {
+            assert!(matches!(mock.get_transaction_status(&first).await.unwrap(), TransactionStatus::Pending));
+        }
+        assert!(matches!(mock.get_transaction_status(&first).await.unwrap(), TransactionStatus::Confirmed));
+        for _ in 0..2 {
+            mock.get_transaction_status(&second).await.unwrap();
+        }
+        assert!(matches!(mock.get_transaction_status(&second).await.unwrap(), TransactionStatus::Rejected));
+        assert!(mock.get_transaction_status("unknown").await.is_err());
+    }
+
+    #[tokio::test]
+    async fn test_seeded_failures_and_hangs() {
+        let flaky = || MockScenario { seed: 11, ..Default::default() }.with_method(
+            MockMethod::GetState,
+            MethodBehavior { failure_rate: 0.5, failure: MockOutcome::Bitcoin, ..Default::default() },
+        );
+        let run = |mock: MockLayer2Protocol| async move {
+            let mut outcomes = Vec::new();
+            for _ in 0..32 {
+                outcomes.push(mock.get_state().await.is_ok());
+            }
+            outcomes
+        };
+        let outcomes = run(MockLayer2Protocol::with_scenario(flaky())).await;
+        assert_eq!(outcomes, run(MockLayer2Protocol::with_scenario(flaky())).await);
+        assert!(outcomes.contains(&true) && outcomes.contains(&false));
+
+        let hung = MockLayer2Protocol::with_scenario(MockScenario::default().with_method(
+            MockMethod::SyncState,
+            MethodBehavior { sequence: vec![MockOutcome::Hang], ..Default::default() },
+        ));
+        assert!(tokio::time::timeout(Duration::from_millis(20), hung.sync_state()).await.is_err());
+        hung.sync_state().await.unwrap();
+
+        assert!(MockScenario::from_yaml("methods: { connect: { sequence: [explode] } }").is_err());
+    }
+}
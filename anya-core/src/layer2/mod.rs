@@ -17,6 +17,9 @@
 pub mod liquid;
 pub mod state_channels;
 pub mod mock;
+pub mod registry;
+pub mod proof;
+pub mod routing;
 
 // Re-export key types for easier access
 pub use bob::{Layer2Client as BobClient, Layer2Config as BobConfig, Layer2Error as BobError};
@@ -29,12 +32,18 @@
 pub use liquid::LiquidProtocol;
 pub use state_channels::StateChannelsProtocol;
 pub use mock::MockLayer2Protocol;
+pub use registry::{HealthState, Layer2Health, ProtocolConfig, ProtocolLifecycle, ProtocolRegistry};
+pub use proof::{HeaderChain, MerkleBranch, ProofError, ProofVerifier, VerifiedInclusion, WithProofVerifier};
+pub use routing::{AssetRouter, RouteError, RoutePlan, RouteProgress, RouteRequest, RouterConfig};
 
 use async_trait::async_trait;
 use serde::{Serialize, Deserialize};
+use std::collections::HashMap;
+use std::sync::Arc;
+use std::time::Duration;
 
 /// Layer 2 type enumeration
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
 pub enum Layer2Type {
     /// BOB Hybrid L2
     Bob,
@@ -54,6 +63,8 @@ pub enum Layer2Type {
     Dlc,
     /// Taproot Assets
     TaprootAssets,
+    /// Liquid Sidechain
+    Liquid,
 }
 
 impl std::fmt::Display for Layer2Type {
@@ -68,6 +79,7 @@ fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
             Layer2Type::Stacks => write!(f, "Stacks"),
             Layer2Type::Dlc => write!(f, "DLC"),
             Layer2Type::TaprootAssets => write!(f, "Taproot Assets"),
+            Layer2Type::Liquid => write!(f, "Liquid"),
         }
     }
 }
@@ -81,6 +93,8 @@ pub struct Layer2ManagerConfig {
     pub lightning_config: Option<lightning::LightningProtocolConfig>,
     /// Enable/disable specific Layer 2 solutions
     pub enabled_solutions: Vec<Layer2Type>,
+    /// Per-protocol configuration, applied when a protocol is registered
+    pub protocol_configs: HashMap<Layer2Type, ProtocolConfig>,
 }
 
 impl Default for Layer2ManagerConfig {
@@ -89,6 +103,7 @@ fn default() -> Self {
             bob_config: Some(bob::BobConfig::default()),
             lightning_config: None,
             enabled_solutions: vec![Layer2Type::Bob],
+            protocol_configs: HashMap::new(),
         }
     }
 }
@@ -115,6 +130,18 @@ pub enum Layer2ManagerError {
     /// Configuration error
     #[error("Configuration error: {0}")]
     Config(String),
+
+    /// Protocol already registered
+    #[error("Layer 2 protocol already registered: {0}")]
+    AlreadyRegistered(String),
+
+    /// Protocol not connected
+    #[error("Layer 2 protocol not connected: {0}")]
+    NotConnected(String),
+
+    /// Error reported by a protocol
+    #[error("Layer 2 protocol error: {0}")]
+    Protocol(String),
 }
 
 /// Result type for Layer 2 manager operations
@@ -141,53 +168,119 @@ pub struct Layer2Manager {
     config: Layer2ManagerConfig,
     /// BOB client if enabled
     bob_client: Option<bob::BobClient>,
-    /// Lightning client if enabled
-    lightning_client: Option<lightning::LightningProtocol>,
+    /// Registered Layer 2 protocols
+    registry: ProtocolRegistry,
+    /// Proof verifier shared by the built-in protocols
+    proof_verifier: ProofVerifier,
 }
 
 impl Layer2Manager {
     /// Create a new Layer 2 manager with the provided configuration
     pub fn new(config: Layer2ManagerConfig) -> Self {
+        let proof_verifier = ProofVerifier::default();
         let bob_client = if config.enabled_solutions.contains(&Layer2Type::Bob) {
-            config.bob_config.clone().map(bob::BobClient::new)
-        } else {
-            None
-        };
-
-        let lightning_client = if config.enabled_solutions.contains(&Layer2Type::Lightning) {
-            config.lightning_config.clone().map(|_| {
-                // TODO: Replace with actual Lightning client initialization
-                lightning::LightningProtocol::new()
-            })
+            config.bob_config.clone()
+                .map(|bob_config| bob::BobClient::new(bob_config).with_bitcoin_headers(proof_verifier.clone()))
         } else {
             None
         };
 
-        Self {
+        let manager = Self {
             config,
             bob_client,
-            lightning_client,
+            registry: ProtocolRegistry::new(),
+            proof_verifier,
+        };
+
+        if manager.is_enabled(Layer2Type::Lightning) && manager.config.lightning_config.is_some() {
+            // Registry is empty, registration cannot collide
+            let _ = manager.register_protocol(Layer2Type::Lightning, Arc::new(
+                lightning::LightningProtocol::new().with_proof_verifier(manager.proof_verifier.clone()),
+            ));
+        }
+
+        manager
+    }
+
+    /// Create a manager with every built-in protocol registered
+    pub fn with_default_protocols(config: Layer2ManagerConfig) -> Self {
+        let manager = Self::new(config);
+
+        let verifier = manager.proof_verifier.clone();
+        let protocols: Vec<(Layer2Type, Arc<dyn Layer2Protocol>)> = vec![
+            (Layer2Type::Lightning, Arc::new(lightning::LightningProtocol::new().with_proof_verifier(verifier.clone()))),
+            (Layer2Type::StateChannel, Arc::new(state_channels::StateChannelsProtocol::new().with_proof_verifier(verifier.clone()))),
+            (Layer2Type::Rgb, Arc::new(rgb::RgbProtocol::new().with_proof_verifier(verifier.clone()))),
+            (Layer2Type::Rsk, Arc::new(rsk::RskProtocol::new().with_proof_verifier(verifier.clone()))),
+            (Layer2Type::Stacks, Arc::new(stacks::StacksProtocol::new().with_proof_verifier(verifier.clone()))),
+            (Layer2Type::Dlc, Arc::new(dlc::DlcProtocol::new().with_proof_verifier(verifier.clone()))),
+            (Layer2Type::TaprootAssets, Arc::new(taproot_assets::TaprootAssetsProtocol::new().with_proof_verifier(verifier.clone()))),
+            (Layer2Type::Liquid, Arc::new(liquid::LiquidProtocol::new().with_proof_verifier(verifier.clone()))),
+        ];
+
+        for (l2_type, protocol) in protocols {
+            if !manager.registry.contains(l2_type) {
+                let _ = manager.register_protocol(l2_type, protocol);
+            }
         }
+
+        manager
+    }
+
+    /// Register a protocol under its Layer 2 type
+    ///
+    /// Uses the configuration from `protocol_configs`, falling back to a default
+    /// configuration enabled according to `enabled_solutions`.
+    pub fn register_protocol(&self, l2_type: Layer2Type, protocol: Arc<dyn Layer2Protocol>) -> Layer2Result<()> {
+        let config = self.config.protocol_configs.get(&l2_type).cloned().unwrap_or_else(|| ProtocolConfig {
+            enabled: self.config.enabled_solutions.contains(&l2_type),
+            ..ProtocolConfig::default()
+        });
+
+        self.registry.register(l2_type, protocol, config)
+    }
+
+    /// Register a protocol with an explicit configuration
+    pub fn register_protocol_with_config(
+        &self,
+        l2_type: Layer2Type,
+        protocol: Arc<dyn Layer2Protocol>,
+        config: ProtocolConfig,
+    ) -> Layer2Result<()> {
+        self.registry.register(l2_type, protocol, config)
+    }
+
+    /// Remove a registered protocol
+    pub fn unregister_protocol(&self, l2_type: Layer2Type) -> Option<Arc<dyn Layer2Protocol>> {
+        self.registry.unregister(l2_type)
     }
 
     /// Get a list of supported Layer 2 solution types
     pub fn get_supported_types(&self) -> Vec<Layer2Type> {
-        vec![
-            Layer2Type::Bob,
-            Layer2Type::Lightning,
-            Layer2Type::StateChannel,
-            Layer2Type::Sidechain,
-        ]
+        let mut types = self.registry.types();
+        if self.bob_client.is_some() {
+            types.push(Layer2Type::Bob);
+        }
+        types
     }
 
     /// Get a list of enabled Layer 2 solution types
     pub fn get_enabled_types(&self) -> Vec<Layer2Type> {
-        self.config.enabled_solutions.clone()
+        let mut types = self.config.enabled_solutions.clone();
+        for l2_type in self.registry.types() {
+            if self.is_enabled(l2_type) && !types.contains(&l2_type) {
+                types.push(l2_type);
+            }
+        }
+        types
     }
 
     /// Check if a Layer 2 solution type is enabled
     pub fn is_enabled(&self, l2_type: Layer2Type) -> bool {
-        self.config.enabled_solutions.contains(&l2_type)
+        match self.registry.config(l2_type) {
+            Some(config) => config.enabled,
+            None => self.config.enabled_solutions.contains(&l2_type),
+        }
     }
 
     /// Get the status of a specific Layer 2 solution
@@ -220,21 +313,7 @@ pub async fn get_status(&self, l2_type: Layer2Type) -> Layer2Result<Layer2Status
                     Err(Layer2ManagerError::SolutionNotEnabled(l2_type.to_string()))
                 }
             },
-            Layer2Type::Lightning => {
-                if let Some(_client) = &self.lightning_client {
-                    // TODO: Implement Lightning status check
-                    Ok(Layer2Status {
-                        l2_type,
-                        enabled: true,
-                        connected: false,
-                        version: Some("0.1.0".to_string()),
-                        details: std::collections::HashMap::new(),
-                    })
-                } else {
-                    Err(Layer2ManagerError::SolutionNotEnabled(l2_type.to_string()))
-                }
-            },
-            _ => Err(Layer2ManagerError::SolutionNotSupported(l2_type.to_string())),
+            _ => self.registry.status(l2_type),
         }
     }
 
@@ -242,21 +321,109 @@ pub async fn get_status(&self, l2_type: Layer2Type) -> Layer2Result<Layer2Status
     pub async fn get_all_status(&self) -> Vec<Layer2Result<Layer2Status>> {
         let mut results = Vec::new();
         
-        for l2_type in &self.config.enabled_solutions {
-            results.push(self.get_status(*l2_type).await);
+        for l2_type in self.get_enabled_types() {
+            results.push(self.get_status(l2_type).await);
         }
         
         results
     }
 
+    /// Probe every enabled protocol and aggregate their health
+    pub async fn health(&self) -> Layer2Health {
+        self.registry.health().await
+    }
+
+    /// Initialize a registered protocol
+    pub async fn initialize(&self, l2_type: Layer2Type) -> Layer2Result<()> {
+        self.registry.initialize(l2_type).await
+    }
+
+    /// Connect a registered protocol
+    pub async fn connect(&self, l2_type: Layer2Type) -> Layer2Result<()> {
+        self.registry.connect(l2_type).await
+    }
+
+    /// Disconnect a registered protocol
+    pub async fn disconnect(&self, l2_type: Layer2Type) -> Layer2Result<()> {
+        self.registry.disconnect(l2_type).await
+    }
+
+    /// Sync a registered protocol's state
+    pub async fn sync(&self, l2_type: Layer2Type) -> Layer2Result<()> {
+        self.registry.sync(l2_type).await
+    }
+
+    /// Initialize every enabled protocol and connect those set to auto-connect
+    ///
+    /// A failing protocol does not stop the others; its error is returned alongside.
+    pub async fn start(&self) -> Vec<(Layer2Type, Layer2Result<()>)> {
+        let mut results = Vec::new();
+
+        for l2_type in self.registry.types() {
+            let config = match self.registry.config(l2_type) {
+                Some(config) if config.enabled => config,
+                _ => continue,
+            };
+
+            let result = if config.auto_connect {
+                self.registry.connect(l2_type).await
+            } else {
+                self.registry.initialize(l2_type).await
+            };
+            results.push((l2_type, result));
+        }
+
+        results
+    }
+
+    /// Disconnect every connected protocol
+    pub async fn shutdown(&self) -> Vec<(Layer2Type, Layer2Result<()>)> {
+        let mut results = Vec::new();
+        for l2_type in self.registry.types() {
+            results.push((l2_type, self.registry.disconnect(l2_type).await));
+        }
+        results
+    }
+
+    /// Sync every connected protocol
+    pub async fn sync_all(&self) -> Vec<(Layer2Type, Layer2Result<()>)> {
+        let mut results = Vec::new();
+        for l2_type in self.registry.types() {
+            if self.registry.lifecycle(l2_type) == Some(ProtocolLifecycle::Connected) {
+                results.push((l2_type, self.registry.sync(l2_type).await));
+            }
+        }
+        results
+    }
+
+    /// Submit a transaction to a Layer 2 protocol
+    pub async fn submit_transaction(&self, l2_type: Layer2Type, tx: &[u8]) -> Layer2Result<String> {
+        self.registry.submit_transaction(l2_type, tx).await
+    }
+
+    /// Get the status of a transaction on a Layer 2 protocol
+    pub async fn get_transaction_status(&self, l2_type: Layer2Type, tx_id: &str) -> Layer2Result<TransactionStatus> {
+        self.registry.get_transaction_status(l2_type, tx_id).await
+    }
+
     /// Get the BOB client if enabled
     pub fn bob_client(&self) -> Option<&bob::BobClient> {
         self.bob_client.as_ref()
     }
 
-    /// Get the Lightning client if enabled
-    pub fn lightning_client(&self) -> Option<&lightning::LightningProtocol> {
-        self.lightning_client.as_ref()
+    /// Get a registered protocol
+    pub fn protocol(&self, l2_type: Layer2Type) -> Option<Arc<dyn Layer2Protocol>> {
+        self.registry.get(l2_type)
+    }
+
+    /// The protocol registry
+    pub fn registry(&self) -> &ProtocolRegistry {
+        &self.registry
+    }
+
+    /// Proof verifier shared by the built-in protocols; feed it headers to enable proof checks
+    pub fn proof_verifier(&self) -> &ProofVerifier {
+        &self.proof_verifier
     }
 }
 
@@ -297,6 +464,138 @@ pub struct AssetTransfer {
     pub to: String,
 }
 
+/// Asset ID of bitcoin itself, on every venue
+pub const BTC_ASSET_ID: &str = "BTC";
+
+/// Where value is held: the Bitcoin chain or a Layer 2
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum Venue {
+    /// Bitcoin on-chain
+    Bitcoin,
+    /// A Layer 2 protocol
+    Layer2(Layer2Type),
+}
+
+impl std::fmt::Display for Venue {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Venue::Bitcoin => write!(f, "Bitcoin"),
+            Venue::Layer2(l2_type) => write!(f, "{}", l2_type),
+        }
+    }
+}
+
+/// Move of an asset between two venues, carried out by a single protocol
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MoveRequest {
+    pub asset_id: String,
+    pub amount: u64,
+    pub from: Venue,
+    pub to: Venue,
+    /// Address or account the asset leaves from
+    pub source: String,
+    /// Address or account receiving the asset
+    pub destination: String,
+    /// Bitcoin fee rate in sat/vB
+    pub fee_rate: u64,
+}
+
+impl MoveRequest {
+    /// The same move in the opposite direction
+    pub fn reversed(&self) -> Self {
+        Self {
+            from: self.to,
+            to: self.from,
+            source: self.destination.clone(),
+            destination: self.source.clone(),
+            ..self.clone()
+        }
+    }
+
+    /// Whether the moved asset is bitcoin
+    pub fn is_btc(&self) -> bool {
+        self.asset_id == BTC_ASSET_ID
+    }
+
+    /// Error for a protocol that does not carry out this move
+    pub fn unsupported(&self, protocol: impl std::fmt::Display) -> crate::AnyaError {
+        crate::AnyaError::System(format!(
+            "{} cannot move {} from {} to {}", protocol, self.asset_id, self.from, self.to
+        ))
+    }
+}
+
+/// Expected cost of a move
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub struct MoveEstimate {
+    /// Fees deducted from the moved amount, in the asset's base units
+    pub fee: u64,
+    /// Time until the asset is spendable at the destination
+    pub latency: Duration,
+}
+
+/// Funds a failed move returned to its source
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MoveRefund {
+    /// Transaction returning the funds, tracked like the move itself
+    pub tx_id: String,
+    /// Amount returned, after the refund's own fees
+    pub amount: u64,
+}
+
+/// Bitcoin wallet funding and watching the mainchain side of moves
+#[async_trait]
+pub trait OnchainWallet: Send + Sync {
+    /// Spendable coins
+    async fn utxos(&self) -> crate::AnyaResult<Vec<(bitcoin::OutPoint, bitcoin::TxOut)>>;
+
+    /// Script receiving change and refunds
+    async fn change_script(&self) -> crate::AnyaResult<bitcoin::ScriptBuf>;
+
+    /// Unsigned payment of `amount` to `script_pubkey` at `fee_rate` sat/vB
+    ///
+    /// Inputs carry their previous outputs, so the PSBT knows its fee.
+    async fn create_payment(
+        &self,
+        script_pubkey: &bitcoin::Script,
+        amount: bitcoin::Amount,
+        fee_rate: u64,
+    ) -> crate::AnyaResult<bitcoin::Psbt>;
+
+    /// Sign and finalize the wallet's inputs
+    async fn sign(&self, psbt: bitcoin::Psbt) -> crate::AnyaResult<bitcoin::Transaction>;
+
+    /// Broadcast a signed transaction
+    async fn broadcast(&self, tx: &bitcoin::Transaction) -> crate::AnyaResult<bitcoin::Txid>;
+
+    /// Confirmations of a transaction; `None` once it is neither mined nor in the mempool
+    async fn confirmations(&self, txid: &bitcoin::Txid) -> crate::AnyaResult<Option<u32>>;
+
+    /// Block and merkle path of a mined transaction
+    async fn merkle_block(&self, txid: &bitcoin::Txid) -> crate::AnyaResult<Option<bitcoin::MerkleBlock>>;
+
+    /// Mean interval of recent blocks
+    async fn block_interval(&self) -> crate::AnyaResult<Duration>;
+}
+
+impl dyn OnchainWallet {
+    /// Fee of paying `amount` to `script_pubkey` from the current coins
+    pub async fn payment_fee(&self, script_pubkey: &bitcoin::Script, amount: bitcoin::Amount, fee_rate: u64) -> crate::AnyaResult<u64> {
+        let psbt = self.create_payment(script_pubkey, amount, fee_rate).await?;
+        psbt.fee()
+            .map(|fee| fee.to_sat())
+            .map_err(|e| crate::AnyaError::System(format!("Payment fee: {}", e)))
+    }
+
+    /// Pay `amount` to `script_pubkey` and broadcast the payment
+    pub async fn pay(&self, script_pubkey: &bitcoin::Script, amount: bitcoin::Amount, fee_rate: u64) -> crate::AnyaResult<bitcoin::Transaction> {
+        let psbt = self.create_payment(script_pubkey, amount, fee_rate).await?;
+        let tx = self.sign(psbt).await?;
+        self.broadcast(&tx).await?;
+        Ok(tx)
+    }
+}
+
 #[derive(Debug, Clone, Serialize, Deserialize, Default)]
 pub struct TransferResult {
     pub tx_id: String,
@@ -336,4 +635,117 @@ pub trait Layer2Protocol: Send + Sync {
     async fn transfer_asset(&self, transfer: AssetTransfer) -> crate::AnyaResult<TransferResult>;
     async fn verify_proof(&self, proof: &Proof) -> crate::AnyaResult<VerificationResult>;
     async fn validate_state(&self, state: &ProtocolState) -> crate::AnyaResult<ValidationResult>;
-} 
\ No newline at end of file
+
+    /// Fee and latency of a move this protocol can carry out
+    ///
+    /// Protocols return an error for moves they do not support.
+    async fn estimate_move(&self, request: &MoveRequest) -> crate::AnyaResult<MoveEstimate> {
+        Err(crate::AnyaError::System(format!("Moving {} from {} to {} is not supported", request.asset_id, request.from, request.to)))
+    }
+
+    /// Carry out a move this protocol quotes
+    ///
+    /// The returned ID is followed with `get_transaction_status`.
+    async fn execute_move(&self, request: &MoveRequest) -> crate::AnyaResult<TransferResult> {
+        Err(request.unsupported("This protocol"))
+    }
+
+    /// Recover the funds of a move whose status is failed or rejected
+    ///
+    /// Returns `None` when the failure left the funds with the sender.
+    async fn refund_move(&self, request: &MoveRequest, tx_id: &str) -> crate::AnyaResult<Option<MoveRefund>> {
+        Err(crate::AnyaError::System(format!("{} ({} to {}) cannot be refunded", tx_id, request.from, request.to)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::{AnyaError, AnyaResult};
+
+    /// Protocol whose connect always fails
+    struct UnreachableProtocol;
+
+    #[async_trait]
+    impl Layer2Protocol for UnreachableProtocol {
+        async fn initialize(&self) -> AnyaResult<()> { Ok(()) }
+        async fn connect(&self) -> AnyaResult<()> { Err(AnyaError::System("connection refused".to_string())) }
+        async fn disconnect(&self) -> AnyaResult<()> { Ok(()) }
+        async fn submit_transaction(&self, _tx: &[u8]) -> AnyaResult<String> { unreachable!() }
+        async fn get_transaction_status(&self, _tx_id: &str) -> AnyaResult<TransactionStatus> { unreachable!() }
+        async fn get_state(&self) -> AnyaResult<ProtocolState> { unreachable!() }
+        async fn sync_state(&self) -> AnyaResult<()> { unreachable!() }
+        async fn issue_asset(&self, _params: AssetParams) -> AnyaResult<String> { unreachable!() }
+        async fn transfer_asset(&self, _transfer: AssetTransfer) -> AnyaResult<TransferResult> { unreachable!() }
+        async fn verify_proof(&self, _proof: &Proof) -> AnyaResult<VerificationResult> { unreachable!() }
+        async fn validate_state(&self, _state: &ProtocolState) -> AnyaResult<ValidationResult> { unreachable!() }
+    }
+
+    fn manager() -> Layer2Manager {
+        Layer2Manager::new(Layer2ManagerConfig {
+            bob_config: None,
+            lightning_config: None,
+            enabled_solutions: vec![Layer2Type::Rgb, Layer2Type::Liquid],
+            protocol_configs: HashMap::new(),
+        })
+    }
+
+    #[tokio::test]
+    async fn test_registered_protocol_dispatch() {
+        let manager = manager();
+        manager.register_protocol(Layer2Type::Rgb, Arc::new(MockLayer2Protocol::new())).unwrap();
+        assert!(manager.register_protocol(Layer2Type::Rgb, Arc::new(MockLayer2Protocol::new())).is_err());
+
+        // Dispatch requires a connected protocol
+        assert!(matches!(
+            manager.submit_transaction(Layer2Type::Rgb, b"tx").await,
+            Err(Layer2ManagerError::NotConnected(_))
+        ));
+
+        manager.connect(Layer2Type::Rgb).await.unwrap();
+        assert_eq!(manager.submit_transaction(Layer2Type::Rgb, b"tx").await.unwrap(), "mock_tx_id");
+        assert!(matches!(
+            manager.get_transaction_status(Layer2Type::Rgb, "mock_tx_id").await.unwrap(),
+            TransactionStatus::Confirmed
+        ));
+
+        manager.sync(Layer2Type::Rgb).await.unwrap();
+        let status = manager.get_status(Layer2Type::Rgb).await.unwrap();
+        assert!(status.connected);
+        assert_eq!(status.details.get("last_block_height").map(String::as_str), Some("0"));
+
+        manager.disconnect(Layer2Type::Rgb).await.unwrap();
+        assert_eq!(manager.registry().lifecycle(Layer2Type::Rgb), Some(ProtocolLifecycle::Disconnected));
+
+        // Unregistered types are reported as unsupported
+        assert!(matches!(
+            manager.submit_transaction(Layer2Type::Stacks, b"tx").await,
+            Err(Layer2ManagerError::SolutionNotEnabled(_)) | Err(Layer2ManagerError::SolutionNotSupported(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_health_aggregation() {
+        let manager = manager();
+        manager.register_protocol(Layer2Type::Rgb, Arc::new(MockLayer2Protocol::new())).unwrap();
+        manager.register_protocol(Layer2Type::Liquid, Arc::new(UnreachableProtocol)).unwrap();
+        // Not in enabled_solutions, so ignored by start() and health()
+        manager.register_protocol(Layer2Type::Stacks, Arc::new(UnreachableProtocol)).unwrap();
+
+        let results = manager.start().await;
+        assert_eq!(results.len(), 2);
+        assert!(matches!(
+            manager.registry().lifecycle(Layer2Type::Liquid),
+            Some(ProtocolLifecycle::Failed(_))
+        ));
+
+        let health = manager.health().await;
+        assert_eq!(health.state, HealthState::Degraded);
+        assert_eq!(health.healthy, vec![Layer2Type::Rgb]);
+        assert_eq!(health.unhealthy, vec![Layer2Type::Liquid]);
+
+        // A required protocol being down makes the stack unhealthy
+        manager.registry().set_config(Layer2Type::Liquid, ProtocolConfig { required: true, ..ProtocolConfig::default() }).unwrap();
+        assert_eq!(manager.health().await.state, HealthState::Unhealthy);
+    }
+}
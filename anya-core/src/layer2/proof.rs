@@ -0,0 +1,767 @@
+//! Shared inclusion proof verification for Layer 2 protocols
+//!
+//! A [`Proof`] shows that a Bitcoin transaction is included in a block. The
+//! shared checks are the same for every protocol: the merkle path must lead to
+//! the header's merkle root, the header must meet its own proof-of-work target,
+//! and it must sit on the best chain of a locally tracked header chain.
+//! Protocols layer their own commitment checks on top (RGB anchors, Taproot
+//! Asset commitments, peg-ins) through [`ProofVerifier::verify_with`].
+//!
+//! Encoding of [`Proof`] fields:
+//! - `block_header`: consensus-serialized 80-byte header, hex
+//! - `merkle_root`: the header's merkle root, in the usual reversed hex
+//! - `merkle_proof`: `"<txid>:<position>"`, followed by the sibling hashes from
+//!   the leaf upwards, in reversed hex
+//!
+//! The header chain checks linkage, proof of work against the network's limit
+//! and each header's difficulty bits: unchanged from the parent within a
+//! 2016-block period, and recomputed from the period's timespan at a retarget.
+//! A retarget whose period starts before the checkpoint can only be bounded to
+//! the factor-of-four adjustment range, so seed the chain from a checkpoint at
+//! a period boundary where that matters.
+
+use std::collections::HashMap;
+use std::str::FromStr;
+use std::sync::{Arc, RwLock};
+
+use bitcoin::block::Header;
+use bitcoin::consensus::encode::{deserialize, serialize_hex};
+use bitcoin::consensus::Params;
+use bitcoin::hashes::{sha256d, Hash, HashEngine};
+use bitcoin::{BlockHash, CompactTarget, Network, Target, TxMerkleNode, Txid, Work};
+
+use super::{Proof, ProtocolState, ValidationResult, VerificationResult};
+
+/// Errors from proof verification
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum ProofError {
+    /// Proof fields could not be decoded
+    #[error("Malformed proof: {0}")]
+    Malformed(String),
+
+    /// Header hash does not meet its target
+    #[error("Header {0} does not meet its proof-of-work target")]
+    InvalidProofOfWork(BlockHash),
+
+    /// Header claims a target the chain does not allow
+    #[error("Header {0} has unexpected difficulty bits: {1}")]
+    InvalidDifficulty(BlockHash, String),
+
+    /// Header is not in the tracked chain
+    #[error("Unknown header: {0}")]
+    UnknownHeader(BlockHash),
+
+    /// Header is known but on a stale branch
+    #[error("Header {0} is not on the best chain")]
+    NotInBestChain(BlockHash),
+
+    /// Merkle path does not lead to the header's root
+    #[error("Merkle root mismatch")]
+    MerkleRootMismatch,
+
+    /// Block is not buried deep enough
+    #[error("Insufficient confirmations: {have} < {need}")]
+    InsufficientConfirmations { have: u32, need: u32 },
+
+    /// Header does not extend the tracked chain
+    #[error("Header chain error: {0}")]
+    Chain(String),
+
+    /// Headers could not be read from their source
+    #[error("Header source error: {0}")]
+    Source(String),
+
+    /// Protocol-specific commitment check failed
+    #[error("Commitment check failed: {0}")]
+    Commitment(String),
+}
+
+/// Result type for proof verification
+pub type ProofResult<T> = Result<T, ProofError>;
+
+/// Merkle path from a transaction to a block's merkle root
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MerkleBranch {
+    /// Transaction being proven
+    pub txid: Txid,
+    /// Position of the transaction in the block
+    pub position: u32,
+    /// Sibling hashes from the leaf upwards
+    pub siblings: Vec<TxMerkleNode>,
+}
+
+impl MerkleBranch {
+    /// Build the branch for `txids[position]` from a block's full transaction list
+    pub fn from_txids(txids: &[Txid], position: u32) -> ProofResult<Self> {
+        let txid = *txids.get(position as usize)
+            .ok_or_else(|| ProofError::Malformed(format!("Position {} out of range", position)))?;
+
+        let mut level: Vec<TxMerkleNode> = txids.iter().map(|txid| TxMerkleNode::from_raw_hash(txid.to_raw_hash())).collect();
+        let mut index = position as usize;
+        let mut siblings = Vec::new();
+
+        while level.len() > 1 {
+            if level.len() % 2 == 1 {
+                level.push(*level.last().expect("non-empty level"));
+            }
+            siblings.push(level[index ^ 1]);
+            level = level.chunks(2).map(|pair| combine(&pair[0], &pair[1])).collect();
+            index /= 2;
+        }
+
+        Ok(Self { txid, position, siblings })
+    }
+
+    /// Merkle root implied by the branch
+    pub fn compute_root(&self) -> ProofResult<TxMerkleNode> {
+        if self.siblings.len() < 32 && (self.position >> self.siblings.len()) != 0 {
+            return Err(ProofError::Malformed("Position deeper than the branch".to_string()));
+        }
+
+        let mut node = TxMerkleNode::from_raw_hash(self.txid.to_raw_hash());
+        for (depth, sibling) in self.siblings.iter().enumerate() {
+            node = if (self.position >> depth) & 1 == 0 {
+                combine(&node, sibling)
+            } else {
+                // Only a phantom position past the last entry can equal its left sibling (CVE-2012-2459)
+                if *sibling == node {
+                    return Err(ProofError::Malformed("Duplicated left sibling".to_string()));
+                }
+                combine(sibling, &node)
+            };
+        }
+        Ok(node)
+    }
+
+    /// Decode from a [`Proof`]'s `merkle_proof` field
+    pub fn from_proof(proof: &Proof) -> ProofResult<Self> {
+        let (leaf, siblings) = proof.merkle_proof.split_first()
+            .ok_or_else(|| ProofError::Malformed("Empty merkle proof".to_string()))?;
+
+        let (txid, position) = leaf.split_once(':')
+            .ok_or_else(|| ProofError::Malformed("Leaf must be <txid>:<position>".to_string()))?;
+        let txid = Txid::from_str(txid).map_err(|e| ProofError::Malformed(e.to_string()))?;
+        let position = position.parse().map_err(|_| ProofError::Malformed("Invalid position".to_string()))?;
+
+        let siblings = siblings.iter()
+            .map(|s| TxMerkleNode::from_str(s).map_err(|e| ProofError::Malformed(e.to_string())))
+            .collect::<ProofResult<Vec<_>>>()?;
+
+        Ok(Self { txid, position, siblings })
+    }
+
+    /// Encode as a [`Proof`] against `header`
+    pub fn to_proof(&self, header: &Header) -> Proof {
+        let mut merkle_proof = vec![format!("{}:{}", self.txid, self.position)];
+        merkle_proof.extend(self.siblings.iter().map(|s| s.to_string()));
+        Proof {
+            merkle_root: header.merkle_root.to_string(),
+            merkle_proof,
+            block_header: serialize_hex(header),
+        }
+    }
+}
+
+fn combine(left: &TxMerkleNode, right: &TxMerkleNode) -> TxMerkleNode {
+    let mut engine = sha256d::Hash::engine();
+    engine.input(left.as_byte_array());
+    engine.input(right.as_byte_array());
+    TxMerkleNode::from_raw_hash(sha256d::Hash::from_engine(engine))
+}
+
+/// Tracked header
+#[derive(Debug, Clone, Copy)]
+struct ChainEntry {
+    header: Header,
+    height: u32,
+    chainwork: Work,
+}
+
+/// Locally tracked header chain, seeded from a trusted checkpoint
+#[derive(Debug, Clone)]
+pub struct HeaderChain {
+    params: Params,
+    entries: HashMap<BlockHash, ChainEntry>,
+    tip: Option<BlockHash>,
+}
+
+impl Default for HeaderChain {
+    fn default() -> Self {
+        Self::with_params(Network::Bitcoin)
+    }
+}
+
+impl HeaderChain {
+    /// Empty chain under `params`' consensus rules, awaiting a checkpoint
+    pub fn with_params(params: impl Into<Params>) -> Self {
+        Self { params: params.into(), entries: HashMap::new(), tip: None }
+    }
+
+    /// Start a chain from a trusted checkpoint header under `params`' consensus rules
+    pub fn from_checkpoint(header: Header, height: u32, params: impl Into<Params>) -> ProofResult<Self> {
+        let params = params.into();
+        check_pow_limit(&header, &params)?;
+        check_pow(&header)?;
+        let hash = header.block_hash();
+        let mut entries = HashMap::new();
+        entries.insert(hash, ChainEntry { header, height, chainwork: header.work() });
+        Ok(Self { params, entries, tip: Some(hash) })
+    }
+
+    /// Consensus parameters headers are checked against
+    pub fn params(&self) -> &Params {
+        &self.params
+    }
+
+    /// Add a header whose parent is tracked; returns its height
+    ///
+    /// The tip moves to the branch with the most accumulated work.
+    pub fn add_header(&mut self, header: Header) -> ProofResult<u32> {
+        let hash = header.block_hash();
+        if let Some(entry) = self.entries.get(&hash) {
+            return Ok(entry.height);
+        }
+
+        let parent = *self.entries.get(&header.prev_blockhash)
+            .ok_or_else(|| ProofError::Chain(format!("Parent {} is not tracked", header.prev_blockhash)))?;
+        check_pow_limit(&header, &self.params)?;
+        self.check_difficulty(&header, &parent)?;
+        check_pow(&header)?;
+
+        let entry = ChainEntry {
+            header,
+            height: parent.height + 1,
+            chainwork: parent.chainwork + header.work(),
+        };
+        let tip_work = self.tip.and_then(|tip| self.entries.get(&tip)).map(|tip| tip.chainwork);
+        if tip_work.is_none_or(|work| entry.chainwork > work) {
+            self.tip = Some(hash);
+        }
+        self.entries.insert(hash, entry);
+        Ok(entry.height)
+    }
+
+    /// Best tip as (hash, height)
+    pub fn tip(&self) -> Option<(BlockHash, u32)> {
+        self.tip.map(|hash| (hash, self.entries[&hash].height))
+    }
+
+    /// Tracked header and its height
+    pub fn get(&self, hash: &BlockHash) -> Option<(Header, u32)> {
+        self.entries.get(hash).map(|entry| (entry.header, entry.height))
+    }
+
+    /// Hash of the best-chain header at `height`
+    pub fn hash_at(&self, height: u32) -> Option<BlockHash> {
+        let mut cursor = self.tip?;
+        loop {
+            let entry = self.entries.get(&cursor)?;
+            if entry.height == height {
+                return Some(cursor);
+            }
+            if entry.height < height {
+                return None;
+            }
+            cursor = entry.header.prev_blockhash;
+        }
+    }
+
+    /// Check `header`'s bits against those the consensus rules require after `parent`
+    fn check_difficulty(&self, header: &Header, parent: &ChainEntry) -> ProofResult<()> {
+        let height = parent.height + 1;
+        let interval = self.params.difficulty_adjustment_interval() as u32;
+        let invalid = |reason: String| ProofError::InvalidDifficulty(header.block_hash(), reason);
+
+        if self.params.no_pow_retargeting {
+            return match header.bits == parent.header.bits {
+                true => Ok(()),
+                false => Err(invalid(format!("expected {:#x}", parent.header.bits.to_consensus()))),
+            };
+        }
+
+        if height.is_multiple_of(interval) {
+            // Core measures the period from its first block to the parent (an off-by-one kept for consensus)
+            let expected = match self.ancestor(parent, height - interval) {
+                Some(first) => {
+                    let timespan = parent.header.time.saturating_sub(first.header.time) as u64;
+                    CompactTarget::from_next_work_required(parent.header.bits, timespan, &self.params)
+                },
+                None => {
+                    let previous = Target::from_compact(parent.header.bits);
+                    let target = header.target();
+                    if target < previous.min_transition_threshold() || target > previous.max_transition_threshold(&self.params) {
+                        return Err(invalid("retarget outside the allowed adjustment".to_string()));
+                    }
+                    return Ok(());
+                },
+            };
+            return match header.bits == expected {
+                true => Ok(()),
+                false => Err(invalid(format!("expected retarget to {:#x}", expected.to_consensus()))),
+            };
+        }
+
+        // Testnet: a block more than twice the target spacing after its parent may use the minimum difficulty
+        let min_difficulty = self.params.max_attainable_target.to_compact_lossy();
+        if self.params.allow_min_difficulty_blocks {
+            if header.time as u64 > parent.header.time as u64 + 2 * self.params.pow_target_spacing
+                && header.bits == min_difficulty
+            {
+                return Ok(());
+            }
+
+            // Otherwise the bits of the last block in the period that did not use it
+            let mut last = *parent;
+            while !last.height.is_multiple_of(interval) && last.header.bits == min_difficulty {
+                match self.entries.get(&last.header.prev_blockhash) {
+                    Some(entry) => last = *entry,
+                    None => break,
+                }
+            }
+            return match header.bits == last.header.bits {
+                true => Ok(()),
+                false => Err(invalid(format!("expected {:#x}", last.header.bits.to_consensus()))),
+            };
+        }
+
+        match header.bits == parent.header.bits {
+            true => Ok(()),
+            false => Err(invalid(format!("expected {:#x}", parent.header.bits.to_consensus()))),
+        }
+    }
+
+    /// Tracked ancestor of `entry` at `height`
+    fn ancestor(&self, entry: &ChainEntry, height: u32) -> Option<&ChainEntry> {
+        let mut cursor = self.entries.get(&entry.header.block_hash())?;
+        while cursor.height > height {
+            cursor = self.entries.get(&cursor.header.prev_blockhash)?;
+        }
+        Some(cursor)
+    }
+
+    /// Confirmations of a best-chain header (1 for the tip)
+    pub fn confirmations(&self, hash: &BlockHash) -> ProofResult<u32> {
+        let (_, height) = self.get(hash).ok_or(ProofError::UnknownHeader(*hash))?;
+        if self.hash_at(height) != Some(*hash) {
+            return Err(ProofError::NotInBestChain(*hash));
+        }
+        let (_, tip_height) = self.tip().expect("chain with entries has a tip");
+        Ok(tip_height - height + 1)
+    }
+}
+
+fn check_pow_limit(header: &Header, params: &Params) -> ProofResult<()> {
+    if header.target() > params.max_attainable_target {
+        return Err(ProofError::InvalidDifficulty(header.block_hash(), "target above the proof-of-work limit".to_string()));
+    }
+    Ok(())
+}
+
+fn check_pow(header: &Header) -> ProofResult<()> {
+    header.validate_pow(header.target())
+        .map(|_| ())
+        .map_err(|_| ProofError::InvalidProofOfWork(header.block_hash()))
+}
+
+/// Transaction inclusion established by a verified proof
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VerifiedInclusion {
+    /// Proven transaction
+    pub txid: Txid,
+    /// Position in the block
+    pub position: u32,
+    /// Containing block
+    pub block_hash: BlockHash,
+    /// Height of the containing block
+    pub height: u32,
+    /// Confirmations of the containing block
+    pub confirmations: u32,
+}
+
+/// Proof verifier shared by the Layer 2 protocols
+///
+/// Clones share the same header chain, so one verifier can be handed to every
+/// protocol and kept up to date in a single place.
+#[derive(Debug, Clone)]
+pub struct ProofVerifier {
+    chain: Arc<RwLock<HeaderChain>>,
+    min_confirmations: u32,
+}
+
+impl Default for ProofVerifier {
+    fn default() -> Self {
+        Self::new(HeaderChain::default())
+    }
+}
+
+impl ProofVerifier {
+    /// Create a verifier over a header chain
+    pub fn new(chain: HeaderChain) -> Self {
+        Self {
+            chain: Arc::new(RwLock::new(chain)),
+            min_confirmations: 1,
+        }
+    }
+
+    /// Require at least `confirmations` on proven blocks
+    pub fn with_min_confirmations(mut self, confirmations: u32) -> Self {
+        self.min_confirmations = confirmations;
+        self
+    }
+
+    /// Replace the tracked chain with a new checkpoint, keeping its consensus parameters
+    pub fn set_checkpoint(&self, header: Header, height: u32) -> ProofResult<()> {
+        let mut chain = self.chain.write().unwrap();
+        *chain = HeaderChain::from_checkpoint(header, height, chain.params.clone())?;
+        Ok(())
+    }
+
+    /// Extend the tracked chain
+    pub fn add_header(&self, header: Header) -> ProofResult<u32> {
+        self.chain.write().unwrap().add_header(header)
+    }
+
+    /// Best tip of the tracked chain as (hash, height)
+    pub fn tip(&self) -> Option<(BlockHash, u32)> {
+        self.chain.read().unwrap().tip()
+    }
+
+    /// Hash of the best-chain header at `height`
+    pub fn hash_at(&self, height: u32) -> Option<BlockHash> {
+        self.chain.read().unwrap().hash_at(height)
+    }
+
+    /// Confirmations of a best-chain header
+    pub fn confirmations(&self, hash: &BlockHash) -> ProofResult<u32> {
+        self.chain.read().unwrap().confirmations(hash)
+    }
+
+    /// Best-chain header with at least the required confirmations
+    pub fn confirmed_header(&self, hash: &BlockHash) -> ProofResult<Header> {
+        let chain = self.chain.read().unwrap();
+        let (header, _) = chain.get(hash).ok_or(ProofError::UnknownHeader(*hash))?;
+        let confirmations = chain.confirmations(hash)?;
+        if confirmations < self.min_confirmations {
+            return Err(ProofError::InsufficientConfirmations { have: confirmations, need: self.min_confirmations });
+        }
+        Ok(header)
+    }
+
+    /// Run the shared checks on a proof
+    pub fn verify(&self, proof: &Proof) -> ProofResult<VerifiedInclusion> {
+        let header_bytes = hex::decode(&proof.block_header)
+            .map_err(|e| ProofError::Malformed(format!("Header hex: {}", e)))?;
+        let header: Header = deserialize(&header_bytes)
+            .map_err(|e| ProofError::Malformed(format!("Header: {}", e)))?;
+        let block_hash = header.block_hash();
+
+        check_pow(&header)?;
+
+        let claimed_root = TxMerkleNode::from_str(&proof.merkle_root)
+            .map_err(|e| ProofError::Malformed(format!("Merkle root: {}", e)))?;
+        let branch = MerkleBranch::from_proof(proof)?;
+        if claimed_root != header.merkle_root || branch.compute_root()? != header.merkle_root {
+            return Err(ProofError::MerkleRootMismatch);
+        }
+
+        let chain = self.chain.read().unwrap();
+        let (_, height) = chain.get(&block_hash).ok_or(ProofError::UnknownHeader(block_hash))?;
+        let confirmations = chain.confirmations(&block_hash)?;
+        if confirmations < self.min_confirmations {
+            return Err(ProofError::InsufficientConfirmations { have: confirmations, need: self.min_confirmations });
+        }
+
+        Ok(VerifiedInclusion {
+            txid: branch.txid,
+            position: branch.position,
+            block_hash,
+            height,
+            confirmations,
+        })
+    }
+
+    /// Run the shared checks followed by a protocol's commitment check
+    pub fn verify_with<F>(&self, proof: &Proof, commitment_check: F) -> VerificationResult
+    where
+        F: FnOnce(&VerifiedInclusion) -> Result<(), String>,
+    {
+        let result = self.verify(proof)
+            .and_then(|inclusion| commitment_check(&inclusion).map_err(ProofError::Commitment));
+
+        match result {
+            Ok(()) => VerificationResult { valid: true, error: None },
+            Err(e) => VerificationResult { valid: false, error: Some(e.to_string()) },
+        }
+    }
+
+    /// Check that a protocol state is anchored to the best chain at its height
+    pub fn validate_anchor(&self, state: &ProtocolState) -> ValidationResult {
+        let anchored = u32::try_from(state.height).ok()
+            .and_then(|height| self.chain.read().unwrap().hash_at(height))
+            .map(|hash| hash.to_string() == state.hash);
+
+        match anchored {
+            Some(true) => ValidationResult { valid: true, error: None },
+            Some(false) => ValidationResult {
+                valid: false,
+                error: Some(format!("State hash does not match the best chain at height {}", state.height)),
+            },
+            None => ValidationResult {
+                valid: false,
+                error: Some(format!("No tracked header at height {}", state.height)),
+            },
+        }
+    }
+}
+
+/// Best-chain headers by height, as a full node serves them
+pub trait HeaderSource: Send + Sync {
+    /// Height of the best tip
+    fn best_height(&self) -> ProofResult<u32>;
+
+    /// Hash of the best-chain block at `height`
+    fn hash_at(&self, height: u32) -> ProofResult<BlockHash>;
+
+    /// Header of the best-chain block at `height`
+    fn header_at(&self, height: u32) -> ProofResult<Header>;
+}
+
+impl ProofVerifier {
+    /// Follow `source`'s best chain, returning the headers added
+    ///
+    /// An empty chain is first checkpointed at `checkpoint`, by default the
+    /// last retarget boundary below the source's tip; proofs of older blocks
+    /// cannot be verified. After a reorg the source's branch is added from
+    /// the last block both chains share.
+    pub fn sync_headers(&self, source: &dyn HeaderSource, checkpoint: Option<u32>) -> ProofResult<u32> {
+        let best = source.best_height()?;
+        let mut height = match self.tip() {
+            Some((_, height)) => height.min(best),
+            None => {
+                let interval = self.chain.read().unwrap().params.difficulty_adjustment_interval() as u32;
+                let height = checkpoint.unwrap_or(best - best % interval);
+                if height > best {
+                    return Err(ProofError::Chain(format!("Checkpoint {} is above the source's tip {}", height, best)));
+                }
+                self.set_checkpoint(source.header_at(height)?, height)?;
+                height
+            }
+        };
+
+        while self.hash_at(height) != Some(source.hash_at(height)?) {
+            height = height.checked_sub(1)
+                .filter(|parent| self.hash_at(*parent).is_some())
+                .ok_or_else(|| ProofError::Chain(format!("Source diverges from the checkpoint at {}", height)))?;
+        }
+        for height in height + 1..=best {
+            self.add_header(source.header_at(height)?)?;
+        }
+        Ok(best - height)
+    }
+}
+
+/// Component that checks proofs with a [`ProofVerifier`]
+pub trait WithProofVerifier: Sized {
+    /// The component's verifier
+    fn proof_verifier_mut(&mut self) -> &mut ProofVerifier;
+
+    /// Use a shared proof verifier
+    fn with_proof_verifier(mut self, proof_verifier: ProofVerifier) -> Self {
+        *self.proof_verifier_mut() = proof_verifier;
+        self
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use bitcoin::block::Version;
+    use bitcoin::{CompactTarget, merkle_tree};
+
+    /// Mine a regtest-difficulty header
+    pub(crate) fn mine(prev_blockhash: BlockHash, merkle_root: TxMerkleNode, time: u32) -> Header {
+        mine_bits(prev_blockhash, merkle_root, time, CompactTarget::from_consensus(0x207fffff))
+    }
+
+    fn mine_bits(prev_blockhash: BlockHash, merkle_root: TxMerkleNode, time: u32, bits: CompactTarget) -> Header {
+        let mut header = Header { version: Version::TWO, prev_blockhash, merkle_root, time, bits, nonce: 0 };
+        while header.validate_pow(header.target()).is_err() {
+            header.nonce += 1;
+        }
+        header
+    }
+
+    fn txids(n: u8) -> Vec<Txid> {
+        (0..n).map(|i| Txid::from_byte_array([i + 1; 32])).collect()
+    }
+
+    #[test]
+    fn test_merkle_branch_matches_block_root() {
+        for n in [1u8, 2, 3, 5, 8] {
+            let txids = txids(n);
+            let root = merkle_tree::calculate_root(txids.iter().map(|t| t.to_raw_hash()))
+                .map(TxMerkleNode::from_raw_hash)
+                .unwrap();
+            for position in 0..n as u32 {
+                let branch = MerkleBranch::from_txids(&txids, position).unwrap();
+                assert_eq!(branch.compute_root().unwrap(), root);
+            }
+        }
+    }
+
+    #[test]
+    fn test_verify_against_tracked_chain() {
+        let txids = txids(5);
+        let root = MerkleBranch::from_txids(&txids, 0).unwrap().compute_root().unwrap();
+
+        let checkpoint = mine(BlockHash::all_zeros(), TxMerkleNode::all_zeros(), 1_700_000_000);
+        let block = mine(checkpoint.block_hash(), root, 1_700_000_600);
+        let verifier = ProofVerifier::new(HeaderChain::from_checkpoint(checkpoint, 100, Network::Regtest).unwrap())
+            .with_min_confirmations(2);
+
+        let proof = MerkleBranch::from_txids(&txids, 3).unwrap().to_proof(&block);
+
+        // Header not tracked yet
+        assert!(matches!(verifier.verify(&proof), Err(ProofError::UnknownHeader(_))));
+
+        verifier.add_header(block).unwrap();
+        assert!(matches!(verifier.verify(&proof), Err(ProofError::InsufficientConfirmations { have: 1, need: 2 })));
+
+        verifier.add_header(mine(block.block_hash(), TxMerkleNode::all_zeros(), 1_700_001_200)).unwrap();
+        let inclusion = verifier.verify(&proof).unwrap();
+        assert_eq!((inclusion.txid, inclusion.height, inclusion.confirmations), (txids[3], 101, 2));
+
+        // Protocol check runs after the shared checks
+        let result = verifier.verify_with(&proof, |inclusion| {
+            if inclusion.txid == txids[0] { Ok(()) } else { Err("not an anchor".to_string()) }
+        });
+        assert!(!result.valid);
+
+        // Tampered sibling
+        let mut tampered = proof.clone();
+        tampered.merkle_proof[1] = TxMerkleNode::all_zeros().to_string();
+        assert_eq!(verifier.verify(&tampered), Err(ProofError::MerkleRootMismatch));
+
+        let state = ProtocolState { height: 101, hash: block.block_hash().to_string(), timestamp: 0 };
+        assert!(verifier.validate_anchor(&state).valid);
+    }
+
+    #[test]
+    fn test_chain_follows_most_work() {
+        let checkpoint = mine(BlockHash::all_zeros(), TxMerkleNode::all_zeros(), 1_700_000_000);
+        let mut chain = HeaderChain::from_checkpoint(checkpoint, 0, Network::Regtest).unwrap();
+
+        let a1 = mine(checkpoint.block_hash(), TxMerkleNode::all_zeros(), 1_700_000_600);
+        let b1 = mine(checkpoint.block_hash(), TxMerkleNode::all_zeros(), 1_700_000_601);
+        let b2 = mine(b1.block_hash(), TxMerkleNode::all_zeros(), 1_700_001_200);
+        chain.add_header(a1).unwrap();
+        chain.add_header(b1).unwrap();
+        assert_eq!(chain.tip(), Some((a1.block_hash(), 1)));
+
+        chain.add_header(b2).unwrap();
+        assert_eq!(chain.tip(), Some((b2.block_hash(), 2)));
+        assert_eq!(chain.confirmations(&a1.block_hash()), Err(ProofError::NotInBestChain(a1.block_hash())));
+        assert_eq!(chain.confirmations(&b1.block_hash()), Ok(2));
+
+        // Orphans are rejected
+        let orphan = mine(BlockHash::from_byte_array([1; 32]), TxMerkleNode::all_zeros(), 1_700_000_000);
+        assert!(matches!(chain.add_header(orphan), Err(ProofError::Chain(_))));
+    }
+
+    /// Best chain served by height
+    struct Headers(Vec<Header>);
+
+    impl HeaderSource for Headers {
+        fn best_height(&self) -> ProofResult<u32> {
+            Ok(self.0.len() as u32 - 1)
+        }
+
+        fn hash_at(&self, height: u32) -> ProofResult<BlockHash> {
+            self.header_at(height).map(|header| header.block_hash())
+        }
+
+        fn header_at(&self, height: u32) -> ProofResult<Header> {
+            self.0.get(height as usize).copied().ok_or_else(|| ProofError::Source(format!("No header at {}", height)))
+        }
+    }
+
+    fn extend(headers: &mut Vec<Header>, count: u32, time_offset: u32) {
+        for _ in 0..count {
+            let prev = *headers.last().unwrap();
+            headers.push(mine(prev.block_hash(), TxMerkleNode::all_zeros(), prev.time + 600 + time_offset));
+        }
+    }
+
+    #[test]
+    fn test_sync_headers_follows_source() {
+        let mut headers = vec![mine(BlockHash::all_zeros(), TxMerkleNode::all_zeros(), 1_700_000_000)];
+        extend(&mut headers, 5, 0);
+        let verifier = ProofVerifier::new(HeaderChain::with_params(Network::Regtest));
+
+        assert_eq!(verifier.sync_headers(&Headers(headers.clone()), Some(2)).unwrap(), 3);
+        assert_eq!(verifier.tip(), Some((headers[5].block_hash(), 5)));
+        assert_eq!(verifier.hash_at(1), None);
+        assert_eq!(verifier.sync_headers(&Headers(headers.clone()), Some(2)).unwrap(), 0);
+
+        // The source reorgs the last two blocks away for a longer branch
+        let mut reorged = headers[..4].to_vec();
+        extend(&mut reorged, 3, 1);
+        assert_eq!(verifier.sync_headers(&Headers(reorged.clone()), None).unwrap(), 3);
+        assert_eq!(verifier.tip(), Some((reorged[6].block_hash(), 6)));
+
+        // A source on another chain is refused
+        let mut other = vec![mine(BlockHash::all_zeros(), TxMerkleNode::all_zeros(), 1_600_000_000)];
+        extend(&mut other, 6, 0);
+        assert!(matches!(verifier.sync_headers(&Headers(other), None), Err(ProofError::Chain(_))));
+    }
+
+    #[test]
+    fn test_difficulty_rules() {
+        let regtest = CompactTarget::from_consensus(0x207fffff);
+        let checkpoint = mine(BlockHash::all_zeros(), TxMerkleNode::all_zeros(), 1_700_000_000);
+
+        // Regtest-difficulty headers are above mainnet's limit
+        assert!(matches!(
+            HeaderChain::from_checkpoint(checkpoint, 0, Network::Bitcoin),
+            Err(ProofError::InvalidDifficulty(..))
+        ));
+
+        // Regtest never retargets, so bits may not change
+        let mut chain = HeaderChain::from_checkpoint(checkpoint, 0, Network::Regtest).unwrap();
+        let harder = mine_bits(checkpoint.block_hash(), TxMerkleNode::all_zeros(), 1_700_000_600, CompactTarget::from_consensus(0x2000ffff));
+        assert!(matches!(chain.add_header(harder), Err(ProofError::InvalidDifficulty(..))));
+
+        // Regtest limits with retargeting and testnet's minimum-difficulty rule switched on
+        let mut params = Params::REGTEST;
+        params.no_pow_retargeting = false;
+        params.allow_min_difficulty_blocks = true;
+        let mut chain = HeaderChain::from_checkpoint(checkpoint, 0, params.clone()).unwrap();
+
+        let mut parent = checkpoint;
+        for height in 1..2016 {
+            parent = mine(parent.block_hash(), TxMerkleNode::all_zeros(), 1_700_000_000 + height * 60);
+            chain.add_header(parent).unwrap();
+        }
+
+        // A period mined ten times too fast retargets by the maximum factor of four
+        let time = parent.time + 60;
+        let expected = CompactTarget::from_next_work_required(regtest, 2015 * 60, &params);
+        assert_ne!(expected, regtest);
+        let unchanged = mine(parent.block_hash(), TxMerkleNode::all_zeros(), time);
+        assert!(matches!(chain.add_header(unchanged), Err(ProofError::InvalidDifficulty(..))));
+        let retarget = mine_bits(parent.block_hash(), TxMerkleNode::all_zeros(), time, expected);
+        chain.add_header(retarget).unwrap();
+
+        // Minimum difficulty only after twice the target spacing
+        let early = mine(retarget.block_hash(), TxMerkleNode::all_zeros(), time + 600);
+        assert!(matches!(chain.add_header(early), Err(ProofError::InvalidDifficulty(..))));
+        let late = mine(retarget.block_hash(), TxMerkleNode::all_zeros(), time + 1201);
+        chain.add_header(late).unwrap();
+
+        // The next block goes back to the period's difficulty
+        let back = mine(late.block_hash(), TxMerkleNode::all_zeros(), time + 1260);
+        assert!(matches!(chain.add_header(back), Err(ProofError::InvalidDifficulty(..))));
+        chain.add_header(mine_bits(late.block_hash(), TxMerkleNode::all_zeros(), time + 1260, expected)).unwrap();
+        assert_eq!(chain.tip().map(|(_, height)| height), Some(2018));
+    }
+}
@@ -0,0 +1,381 @@
+//! Layer 2 protocol registry
+//!
+//! Holds every [`Layer2Protocol`] registered with the [`Layer2Manager`](super::Layer2Manager)
+//! under its [`Layer2Type`], together with its configuration and lifecycle state.
+
+use std::collections::HashMap;
+use std::sync::{Arc, RwLock};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use tracing::{info, warn};
+
+use super::{
+    Layer2ManagerError, Layer2Protocol, Layer2Result, Layer2Status, Layer2Type, MoveEstimate, MoveRefund,
+    MoveRequest, TransactionStatus, TransferResult,
+};
+
+/// Per-protocol configuration
+#[derive(Clone, Debug)]
+pub struct ProtocolConfig {
+    /// Whether the protocol may be used
+    pub enabled: bool,
+    /// Connect automatically when the manager starts
+    pub auto_connect: bool,
+    /// Whether an unhealthy protocol makes the whole Layer 2 stack unhealthy
+    pub required: bool,
+    /// Protocol-specific settings
+    pub settings: HashMap<String, String>,
+}
+
+impl Default for ProtocolConfig {
+    fn default() -> Self {
+        Self {
+            enabled: true,
+            auto_connect: true,
+            required: false,
+            settings: HashMap::new(),
+        }
+    }
+}
+
+/// Lifecycle state of a registered protocol
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ProtocolLifecycle {
+    /// Registered but not initialized
+    Registered,
+    /// Initialized, not connected
+    Initialized,
+    /// Connected and usable
+    Connected,
+    /// Disconnected after having been connected
+    Disconnected,
+    /// Last lifecycle operation failed
+    Failed(String),
+}
+
+impl std::fmt::Display for ProtocolLifecycle {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            ProtocolLifecycle::Registered => write!(f, "registered"),
+            ProtocolLifecycle::Initialized => write!(f, "initialized"),
+            ProtocolLifecycle::Connected => write!(f, "connected"),
+            ProtocolLifecycle::Disconnected => write!(f, "disconnected"),
+            ProtocolLifecycle::Failed(e) => write!(f, "failed: {}", e),
+        }
+    }
+}
+
+/// Overall health of the registered protocols
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum HealthState {
+    /// Every enabled protocol is healthy
+    Healthy,
+    /// Some optional protocols are unhealthy
+    Degraded,
+    /// A required protocol is unhealthy
+    Unhealthy,
+}
+
+/// Aggregated Layer 2 health report
+#[derive(Debug, Clone)]
+pub struct Layer2Health {
+    /// Overall state
+    pub state: HealthState,
+    /// Protocols that responded
+    pub healthy: Vec<Layer2Type>,
+    /// Protocols that are not connected or did not respond
+    pub unhealthy: Vec<Layer2Type>,
+    /// Per-protocol status
+    pub statuses: Vec<Layer2Status>,
+}
+
+/// Registry entry
+struct ProtocolEntry {
+    protocol: Arc<dyn Layer2Protocol>,
+    config: ProtocolConfig,
+    lifecycle: ProtocolLifecycle,
+    last_sync: Option<u64>,
+    last_height: Option<u64>,
+}
+
+/// Registry of Layer 2 protocols keyed by type
+#[derive(Default)]
+pub struct ProtocolRegistry {
+    entries: RwLock<HashMap<Layer2Type, ProtocolEntry>>,
+}
+
+impl ProtocolRegistry {
+    /// Create an empty registry
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Register a protocol under `l2_type`
+    pub fn register(
+        &self,
+        l2_type: Layer2Type,
+        protocol: Arc<dyn Layer2Protocol>,
+        config: ProtocolConfig,
+    ) -> Layer2Result<()> {
+        let mut entries = self.entries.write().unwrap();
+        if entries.contains_key(&l2_type) {
+            return Err(Layer2ManagerError::AlreadyRegistered(l2_type.to_string()));
+        }
+
+        info!("Registering {} protocol", l2_type);
+        entries.insert(l2_type, ProtocolEntry {
+            protocol,
+            config,
+            lifecycle: ProtocolLifecycle::Registered,
+            last_sync: None,
+            last_height: None,
+        });
+        Ok(())
+    }
+
+    /// Remove a protocol, returning it
+    pub fn unregister(&self, l2_type: Layer2Type) -> Option<Arc<dyn Layer2Protocol>> {
+        self.entries.write().unwrap().remove(&l2_type).map(|entry| entry.protocol)
+    }
+
+    /// Registered protocol types
+    pub fn types(&self) -> Vec<Layer2Type> {
+        self.entries.read().unwrap().keys().copied().collect()
+    }
+
+    /// Whether `l2_type` is registered
+    pub fn contains(&self, l2_type: Layer2Type) -> bool {
+        self.entries.read().unwrap().contains_key(&l2_type)
+    }
+
+    /// Get a registered protocol
+    pub fn get(&self, l2_type: Layer2Type) -> Option<Arc<dyn Layer2Protocol>> {
+        self.entries.read().unwrap().get(&l2_type).map(|entry| entry.protocol.clone())
+    }
+
+    /// Configuration of a registered protocol
+    pub fn config(&self, l2_type: Layer2Type) -> Option<ProtocolConfig> {
+        self.entries.read().unwrap().get(&l2_type).map(|entry| entry.config.clone())
+    }
+
+    /// Replace the configuration of a registered protocol
+    pub fn set_config(&self, l2_type: Layer2Type, config: ProtocolConfig) -> Layer2Result<()> {
+        let mut entries = self.entries.write().unwrap();
+        let entry = entries.get_mut(&l2_type)
+            .ok_or_else(|| Layer2ManagerError::SolutionNotSupported(l2_type.to_string()))?;
+        entry.config = config;
+        Ok(())
+    }
+
+    /// Lifecycle state of a registered protocol
+    pub fn lifecycle(&self, l2_type: Layer2Type) -> Option<ProtocolLifecycle> {
+        self.entries.read().unwrap().get(&l2_type).map(|entry| entry.lifecycle.clone())
+    }
+
+    /// Initialize a protocol
+    pub async fn initialize(&self, l2_type: Layer2Type) -> Layer2Result<()> {
+        let protocol = self.enabled_protocol(l2_type)?;
+        let result = protocol.initialize().await;
+        self.finish(l2_type, result.map(|_| ProtocolLifecycle::Initialized))
+    }
+
+    /// Connect a protocol, initializing it first if needed
+    pub async fn connect(&self, l2_type: Layer2Type) -> Layer2Result<()> {
+        let protocol = self.enabled_protocol(l2_type)?;
+
+        match self.lifecycle(l2_type) {
+            Some(ProtocolLifecycle::Connected) => return Ok(()),
+            Some(ProtocolLifecycle::Registered) | Some(ProtocolLifecycle::Failed(_)) => {
+                self.initialize(l2_type).await?;
+            },
+            _ => {},
+        }
+
+        let result = protocol.connect().await;
+        self.finish(l2_type, result.map(|_| ProtocolLifecycle::Connected))
+    }
+
+    /// Disconnect a protocol
+    pub async fn disconnect(&self, l2_type: Layer2Type) -> Layer2Result<()> {
+        let protocol = self.get(l2_type)
+            .ok_or_else(|| Layer2ManagerError::SolutionNotSupported(l2_type.to_string()))?;
+
+        if self.lifecycle(l2_type) != Some(ProtocolLifecycle::Connected) {
+            return Ok(());
+        }
+
+        let result = protocol.disconnect().await;
+        self.finish(l2_type, result.map(|_| ProtocolLifecycle::Disconnected))
+    }
+
+    /// Sync a connected protocol's state
+    pub async fn sync(&self, l2_type: Layer2Type) -> Layer2Result<()> {
+        let protocol = self.connected_protocol(l2_type)?;
+
+        protocol.sync_state().await.map_err(|e| Layer2ManagerError::Protocol(e.to_string()))?;
+        let state = protocol.get_state().await.map_err(|e| Layer2ManagerError::Protocol(e.to_string()))?;
+
+        if let Some(entry) = self.entries.write().unwrap().get_mut(&l2_type) {
+            entry.last_sync = Some(now());
+            entry.last_height = Some(state.height);
+        }
+        Ok(())
+    }
+
+    /// Submit a transaction to a connected protocol
+    pub async fn submit_transaction(&self, l2_type: Layer2Type, tx: &[u8]) -> Layer2Result<String> {
+        let protocol = self.connected_protocol(l2_type)?;
+        protocol.submit_transaction(tx).await.map_err(|e| Layer2ManagerError::Protocol(e.to_string()))
+    }
+
+    /// Get a transaction's status from a connected protocol
+    pub async fn get_transaction_status(&self, l2_type: Layer2Type, tx_id: &str) -> Layer2Result<TransactionStatus> {
+        let protocol = self.connected_protocol(l2_type)?;
+        protocol.get_transaction_status(tx_id).await.map_err(|e| Layer2ManagerError::Protocol(e.to_string()))
+    }
+
+    /// Estimate a move on a connected protocol
+    pub async fn estimate_move(&self, l2_type: Layer2Type, request: &MoveRequest) -> Layer2Result<MoveEstimate> {
+        let protocol = self.connected_protocol(l2_type)?;
+        protocol.estimate_move(request).await.map_err(|e| Layer2ManagerError::Protocol(e.to_string()))
+    }
+
+    /// Carry out a move on a connected protocol
+    pub async fn execute_move(&self, l2_type: Layer2Type, request: &MoveRequest) -> Layer2Result<TransferResult> {
+        let protocol = self.connected_protocol(l2_type)?;
+        protocol.execute_move(request).await.map_err(|e| Layer2ManagerError::Protocol(e.to_string()))
+    }
+
+    /// Recover the funds of a failed move on a connected protocol
+    pub async fn refund_move(&self, l2_type: Layer2Type, request: &MoveRequest, tx_id: &str) -> Layer2Result<Option<MoveRefund>> {
+        let protocol = self.connected_protocol(l2_type)?;
+        protocol.refund_move(request, tx_id).await.map_err(|e| Layer2ManagerError::Protocol(e.to_string()))
+    }
+
+    /// Status of a registered protocol, without contacting it
+    pub fn status(&self, l2_type: Layer2Type) -> Layer2Result<Layer2Status> {
+        let entries = self.entries.read().unwrap();
+        let entry = entries.get(&l2_type)
+            .ok_or_else(|| Layer2ManagerError::SolutionNotSupported(l2_type.to_string()))?;
+
+        let mut details = HashMap::new();
+        details.insert("lifecycle".to_string(), entry.lifecycle.to_string());
+        if let Some(last_sync) = entry.last_sync {
+            details.insert("last_sync".to_string(), last_sync.to_string());
+        }
+        if let Some(height) = entry.last_height {
+            details.insert("last_block_height".to_string(), height.to_string());
+        }
+        for (key, value) in &entry.config.settings {
+            details.insert(format!("config.{}", key), value.clone());
+        }
+
+        Ok(Layer2Status {
+            l2_type,
+            enabled: entry.config.enabled,
+            connected: entry.lifecycle == ProtocolLifecycle::Connected,
+            version: None,
+            details,
+        })
+    }
+
+    /// Probe every enabled protocol and aggregate the results
+    pub async fn health(&self) -> Layer2Health {
+        let mut healthy = Vec::new();
+        let mut unhealthy = Vec::new();
+        let mut statuses = Vec::new();
+        let mut required_down = false;
+
+        for l2_type in self.types() {
+            let config = match self.config(l2_type) {
+                Some(config) if config.enabled => config,
+                _ => continue,
+            };
+
+            let probe = match self.connected_protocol(l2_type) {
+                Ok(protocol) => protocol.get_state().await
+                    .map_err(|e| e.to_string()),
+                Err(e) => Err(e.to_string()),
+            };
+
+            let mut status = match self.status(l2_type) {
+                Ok(status) => status,
+                Err(_) => continue,
+            };
+
+            match probe {
+                Ok(state) => {
+                    status.details.insert("block_height".to_string(), state.height.to_string());
+                    healthy.push(l2_type);
+                },
+                Err(e) => {
+                    warn!("{} protocol unhealthy: {}", l2_type, e);
+                    status.connected = false;
+                    status.details.insert("error".to_string(), e);
+                    required_down |= config.required;
+                    unhealthy.push(l2_type);
+                },
+            }
+            statuses.push(status);
+        }
+
+        let state = if required_down {
+            HealthState::Unhealthy
+        } else if unhealthy.is_empty() {
+            HealthState::Healthy
+        } else {
+            HealthState::Degraded
+        };
+
+        Layer2Health { state, healthy, unhealthy, statuses }
+    }
+
+    /// Registered protocol, if enabled
+    fn enabled_protocol(&self, l2_type: Layer2Type) -> Layer2Result<Arc<dyn Layer2Protocol>> {
+        let entries = self.entries.read().unwrap();
+        let entry = entries.get(&l2_type)
+            .ok_or_else(|| Layer2ManagerError::SolutionNotSupported(l2_type.to_string()))?;
+
+        if !entry.config.enabled {
+            return Err(Layer2ManagerError::SolutionNotEnabled(l2_type.to_string()));
+        }
+        Ok(entry.protocol.clone())
+    }
+
+    /// Registered protocol, if enabled and connected
+    fn connected_protocol(&self, l2_type: Layer2Type) -> Layer2Result<Arc<dyn Layer2Protocol>> {
+        let protocol = self.enabled_protocol(l2_type)?;
+
+        match self.lifecycle(l2_type) {
+            Some(ProtocolLifecycle::Connected) => Ok(protocol),
+            Some(lifecycle) => Err(Layer2ManagerError::NotConnected(format!("{} ({})", l2_type, lifecycle))),
+            None => Err(Layer2ManagerError::SolutionNotSupported(l2_type.to_string())),
+        }
+    }
+
+    /// Record the outcome of a lifecycle operation
+    fn finish(&self, l2_type: Layer2Type, result: crate::AnyaResult<ProtocolLifecycle>) -> Layer2Result<()> {
+        let mut entries = self.entries.write().unwrap();
+        let entry = entries.get_mut(&l2_type)
+            .ok_or_else(|| Layer2ManagerError::SolutionNotSupported(l2_type.to_string()))?;
+
+        match result {
+            Ok(lifecycle) => {
+                info!("{} protocol {}", l2_type, lifecycle);
+                entry.lifecycle = lifecycle;
+                Ok(())
+            },
+            Err(e) => {
+                entry.lifecycle = ProtocolLifecycle::Failed(e.to_string());
+                Err(Layer2ManagerError::Protocol(e.to_string()))
+            },
+        }
+    }
+}
+
+fn now() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap_or_default()
+        .as_secs()
+}
@@ -0,0 +1,292 @@
+//! Consignments and their witnesses
+//!
+//! Consignments, their file format and the contract stock come from rgb-std
+//! through [`anya_bitcoin::rgb`], which [`RgbManager`](anya_bitcoin::rgb::RgbManager)
+//! uses as well. This module keeps the witness transactions of our transfers
+//! with the merkle path to the block that mined each of them, and hands them
+//! to the rgb-std validator only once the [`ProofVerifier`] accepts that path.
+//!
+//! The witness store is persisted like the stock: changes are written to a
+//! temporary file that replaces the old one, and only kept in memory once that
+//! succeeded.
+
+use std::collections::BTreeMap;
+use std::fs;
+use std::path::{Path, PathBuf};
+
+use bitcoin::block::Header;
+use bitcoin::consensus::encode::{deserialize, serialize_hex};
+use bitcoin::{BlockHash, Transaction, Txid};
+use serde::{Deserialize, Serialize};
+
+use crate::layer2::proof::{MerkleBranch, ProofVerifier};
+use crate::layer2::Proof;
+
+use super::RgbError;
+
+pub use anya_bitcoin::rgb::consignment::{
+    from_bytes, load_file, save_file, to_bytes, validate, Contract, ContractId, Transfer, WitnessSource,
+};
+pub use anya_bitcoin::rgb::Stash;
+
+/// Witness transaction of a transfer
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Witness {
+    /// The transaction
+    pub tx: Transaction,
+    /// Merkle path and block header, once mined
+    pub inclusion: Option<(MerkleBranch, Header)>,
+}
+
+/// On-disk form of a [`Witness`]
+#[derive(Serialize, Deserialize)]
+struct StoredWitness {
+    tx: String,
+    proof: Option<Proof>,
+}
+
+impl From<&Witness> for StoredWitness {
+    fn from(witness: &Witness) -> Self {
+        Self {
+            tx: serialize_hex(&witness.tx),
+            proof: witness.inclusion.as_ref().map(|(branch, header)| branch.to_proof(header)),
+        }
+    }
+}
+
+impl TryFrom<StoredWitness> for Witness {
+    type Error = RgbError;
+
+    fn try_from(stored: StoredWitness) -> Result<Self, Self::Error> {
+        let decode = |hex: &str| hex::decode(hex).map_err(|e| RgbError::StorageError(e.to_string()));
+        let tx = deserialize(&decode(&stored.tx)?).map_err(|e| RgbError::StorageError(e.to_string()))?;
+        let inclusion = match stored.proof {
+            Some(proof) => Some((
+                MerkleBranch::from_proof(&proof).map_err(|e| RgbError::StorageError(e.to_string()))?,
+                deserialize(&decode(&proof.block_header)?).map_err(|e| RgbError::StorageError(e.to_string()))?,
+            )),
+            None => None,
+        };
+        Ok(Self { tx, inclusion })
+    }
+}
+
+/// Witness transactions of our transfers, optionally persisted
+#[derive(Debug, Default)]
+pub struct WitnessStore {
+    path: Option<PathBuf>,
+    witnesses: BTreeMap<Txid, Witness>,
+}
+
+impl WitnessStore {
+    /// Create a store that is not persisted
+    pub fn in_memory() -> Self {
+        Self::default()
+    }
+
+    /// Open the store at `path`, creating it if missing
+    pub fn open(path: impl AsRef<Path>) -> Result<Self, RgbError> {
+        let path = path.as_ref().to_path_buf();
+        let witnesses = match fs::read(&path) {
+            Ok(data) => serde_json::from_slice::<Vec<StoredWitness>>(&data)
+                .map_err(|e| RgbError::StorageError(format!("{}: {}", path.display(), e)))?
+                .into_iter()
+                .map(|stored| Witness::try_from(stored).map(|witness| (witness.tx.compute_txid(), witness)))
+                .collect::<Result<_, _>>()?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => BTreeMap::new(),
+            Err(e) => return Err(RgbError::StorageError(format!("{}: {}", path.display(), e))),
+        };
+        Ok(Self { path: Some(path), witnesses })
+    }
+
+    /// Stored witness
+    pub fn get(&self, txid: &Txid) -> Option<&Witness> {
+        self.witnesses.get(txid)
+    }
+
+    /// Record an unmined witness transaction
+    pub fn add(&mut self, tx: Transaction) -> Result<Txid, RgbError> {
+        let txid = tx.compute_txid();
+        if !self.witnesses.contains_key(&txid) {
+            self.update(|witnesses| {
+                witnesses.insert(txid, Witness { tx, inclusion: None });
+            })?;
+        }
+        Ok(txid)
+    }
+
+    /// Attach the merkle path of a mined witness, once the verifier accepts it
+    pub fn set_inclusion(
+        &mut self,
+        txid: &Txid,
+        branch: MerkleBranch,
+        header: Header,
+        verifier: &ProofVerifier,
+    ) -> Result<(), RgbError> {
+        if !self.witnesses.contains_key(txid) {
+            return Err(RgbError::TransferNotFound(txid.to_string()));
+        }
+        if branch.txid != *txid {
+            return Err(RgbError::ValidationError(format!("Merkle path proves {}, not witness {}", branch.txid, txid)));
+        }
+        verifier.verify(&branch.to_proof(&header))
+            .map_err(|e| RgbError::ValidationError(format!("Anchor of {}: {}", txid, e)))?;
+
+        self.update(|witnesses| {
+            if let Some(witness) = witnesses.get_mut(txid) {
+                witness.inclusion = Some((branch, header));
+            }
+        })
+    }
+
+    /// Check a stored witness against the block a proof places it in
+    pub fn check_anchor(&self, txid: &Txid, block_hash: &BlockHash) -> Result<(), RgbError> {
+        let witness = self.witnesses.get(txid).ok_or_else(|| RgbError::TransferNotFound(txid.to_string()))?;
+        match &witness.inclusion {
+            Some((_, header)) if header.block_hash() != *block_hash => Err(RgbError::ValidationError(format!(
+                "Witness {} is anchored in block {}, not {}", txid, header.block_hash(), block_hash
+            ))),
+            _ => Ok(()),
+        }
+    }
+
+    /// Witnesses whose inclusion `verifier` accepts, for the rgb-std validator
+    pub fn anchored<'a>(&'a self, verifier: &'a ProofVerifier) -> AnchoredWitnesses<'a> {
+        AnchoredWitnesses { store: self, verifier }
+    }
+
+    /// Apply `change` to a copy of the witnesses and keep it once it is saved
+    fn update(&mut self, change: impl FnOnce(&mut BTreeMap<Txid, Witness>)) -> Result<(), RgbError> {
+        let mut witnesses = self.witnesses.clone();
+        change(&mut witnesses);
+        self.save(&witnesses)?;
+        self.witnesses = witnesses;
+        Ok(())
+    }
+
+    fn save(&self, witnesses: &BTreeMap<Txid, Witness>) -> Result<(), RgbError> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+
+        let stored: Vec<StoredWitness> = witnesses.values().map(StoredWitness::from).collect();
+        let data = serde_json::to_vec(&stored).map_err(|e| RgbError::StorageError(e.to_string()))?;
+        let temporary = path.with_extension("tmp");
+        fs::write(&temporary, data)
+            .and_then(|_| fs::rename(&temporary, path))
+            .map_err(|e| RgbError::StorageError(format!("{}: {}", path.display(), e)))
+    }
+}
+
+/// Stored witnesses as seen by the rgb-std validator
+pub struct AnchoredWitnesses<'a> {
+    store: &'a WitnessStore,
+    verifier: &'a ProofVerifier,
+}
+
+impl WitnessSource for AnchoredWitnesses<'_> {
+    fn witness(&self, txid: &Txid) -> Option<(Transaction, u32)> {
+        let witness = self.store.witnesses.get(txid)?;
+        let (branch, header) = witness.inclusion.as_ref()?;
+        let inclusion = self.verifier.verify(&branch.to_proof(header)).ok()?;
+        Some((witness.tx.clone(), inclusion.height))
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use crate::layer2::proof::HeaderChain;
+    use bitcoin::block::Version as BlockVersion;
+    use bitcoin::hashes::Hash;
+    use bitcoin::{
+        absolute, transaction, Amount, CompactTarget, Network, OutPoint, ScriptBuf, Sequence, TxIn, TxMerkleNode,
+        TxOut, Witness as TxWitness,
+    };
+
+    /// Regtest-difficulty header on top of `prev`
+    pub(crate) fn mine(prev: BlockHash, merkle_root: TxMerkleNode, time: u32) -> Header {
+        let mut header = Header {
+            version: BlockVersion::TWO,
+            prev_blockhash: prev,
+            merkle_root,
+            time,
+            bits: CompactTarget::from_consensus(0x207fffff),
+            nonce: 0,
+        };
+        while header.validate_pow(header.target()).is_err() {
+            header.nonce += 1;
+        }
+        header
+    }
+
+    /// Transaction spending `input` with an OP_RETURN commitment
+    pub(crate) fn witness_tx(input: u8) -> Transaction {
+        Transaction {
+            version: transaction::Version::TWO,
+            lock_time: absolute::LockTime::ZERO,
+            input: vec![TxIn {
+                previous_output: OutPoint { txid: Txid::from_byte_array([input; 32]), vout: 0 },
+                script_sig: ScriptBuf::new(),
+                sequence: Sequence::MAX,
+                witness: TxWitness::new(),
+            }],
+            output: vec![
+                TxOut { value: Amount::from_sat(1_000), script_pubkey: ScriptBuf::new() },
+                TxOut { value: Amount::ZERO, script_pubkey: ScriptBuf::new_op_return([input; 32]) },
+            ],
+        }
+    }
+
+    /// Mine `tx` on top of `verifier`'s tip
+    pub(crate) fn mine_tx(verifier: &ProofVerifier, tx: &Transaction) -> (MerkleBranch, Header) {
+        let (tip, height) = verifier.tip().unwrap();
+        let txids = [Txid::from_byte_array([0xcb; 32]), tx.compute_txid()];
+        let branch = MerkleBranch::from_txids(&txids, 1).unwrap();
+        let header = mine(tip, branch.compute_root().unwrap(), 1_700_000_000 + 600 * (height + 1));
+        verifier.add_header(header).unwrap();
+        (branch, header)
+    }
+
+    pub(crate) fn verifier() -> ProofVerifier {
+        let genesis = mine(BlockHash::all_zeros(), TxMerkleNode::all_zeros(), 1_700_000_000);
+        ProofVerifier::new(HeaderChain::from_checkpoint(genesis, 0, Network::Regtest).unwrap())
+    }
+
+    #[test]
+    fn test_witnesses_are_served_once_anchored() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("witnesses.json");
+        let verifier = verifier();
+
+        let mut store = WitnessStore::open(&path).unwrap();
+        let tx = witness_tx(1);
+        let txid = store.add(tx.clone()).unwrap();
+        assert!(store.anchored(&verifier).witness(&txid).is_none());
+
+        // Paths for another transaction or an untracked block are refused
+        let (branch, header) = mine_tx(&verifier, &tx);
+        let other = MerkleBranch::from_txids(&[txid, witness_tx(2).compute_txid()], 1).unwrap();
+        assert!(store.set_inclusion(&txid, other, header, &verifier).is_err());
+        let stray = mine(BlockHash::all_zeros(), header.merkle_root, 1_700_000_000);
+        assert!(store.set_inclusion(&txid, branch.clone(), stray, &verifier).is_err());
+
+        store.set_inclusion(&txid, branch, header, &verifier).unwrap();
+        assert_eq!(store.anchored(&verifier).witness(&txid), Some((tx.clone(), 1)));
+        store.check_anchor(&txid, &header.block_hash()).unwrap();
+        assert!(store.check_anchor(&txid, &stray.block_hash()).is_err());
+        assert!(matches!(store.check_anchor(&witness_tx(3).compute_txid(), &header.block_hash()), Err(RgbError::TransferNotFound(_))));
+
+        // A verifier on another chain does not see the witness as mined
+        assert!(store.anchored(&self::verifier()).witness(&txid).is_none());
+
+        let reopened = WitnessStore::open(&path).unwrap();
+        assert_eq!(reopened.get(&txid), store.get(&txid));
+
+        // A failed write changes neither the file nor the store
+        fs::remove_file(&path).unwrap();
+        fs::create_dir(&path).unwrap();
+        assert!(matches!(store.add(witness_tx(4)), Err(RgbError::StorageError(_))));
+        assert!(store.get(&witness_tx(4).compute_txid()).is_none());
+    }
+}
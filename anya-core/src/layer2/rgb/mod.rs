@@ -12,15 +12,18 @@
 //! - Asset issuance (fungible and non-fungible)
 //! - Client-side validation for transactions
 //! - Secure state transitions
+//! - Consignment export and import in the rgb-std format with a persisted stock
 
 use std::sync::{Arc, Mutex};
 use std::collections::HashMap;
+use std::path::Path;
 use std::time::{Duration, Instant};
 
+use bitcoin::block::Header;
+use bitcoin::{Network, OutPoint, Transaction as BtcTransaction, Txid};
+
 // Internal imports
-use crate::bitcoin::types::{BitcoinAddress, Transaction as BtcTransaction};
 use crate::core::performance::Metrics;
-use crate::security::validation::ValidationResult;
 use crate::{
     AnyaResult,
     layer2::{
@@ -33,11 +36,20 @@
         Proof,
         VerificationResult,
         ValidationResult,
+        proof::{MerkleBranch, ProofVerifier, WithProofVerifier},
     },
 };
 use async_trait::async_trait;
 use tracing::{info, warn, error};
 
+use consignment::{Contract, ContractId, Stash, Transfer, WitnessStore};
+
+/// File name of the stock inside the storage directory
+const STOCK_FILE: &str = "stock.rgb";
+
+/// File name of the witness store inside the storage directory
+const WITNESS_FILE: &str = "witnesses.json";
+
 /// Configuration for the RGB Protocol integration
 #[derive(Clone, Debug)]
 pub struct RgbConfig {
@@ -51,6 +63,8 @@ pub struct RgbConfig {
     pub max_retries: u32,
     /// Storage directory for RGB data
     pub storage_dir: String,
+    /// Bitcoin network contracts are issued on
+    pub network: Network,
 }
 
 impl Default for RgbConfig {
@@ -61,6 +75,7 @@ fn default() -> Self {
             timeout_ms: 30000,
             max_retries: 3,
             storage_dir: "./rgb_data".to_string(),
+            network: Network::Bitcoin,
         }
     }
 }
@@ -77,11 +92,32 @@ pub struct RgbClient {
     schema_validator: SchemaValidator,
     /// Transaction manager
     transaction_manager: TransactionManager,
+    /// Contracts and their transition history
+    stash: Arc<Mutex<Stash>>,
+    /// Witness transactions of transfers
+    witnesses: Arc<Mutex<WitnessStore>>,
+    /// Verifier for the Bitcoin anchors of transitions
+    proof_verifier: ProofVerifier,
 }
 
 impl RgbClient {
     /// Create a new RGB client with the provided configuration
+    ///
+    /// The stash is kept in memory; use [`RgbClient::open`] to persist it.
     pub fn new(config: RgbConfig) -> Self {
+        Self::with_stash(config, Stash::in_memory(), WitnessStore::in_memory())
+    }
+
+    /// Create a client whose stash is persisted in `config.storage_dir`
+    pub fn open(config: RgbConfig) -> Result<Self, RgbError> {
+        std::fs::create_dir_all(&config.storage_dir)
+            .map_err(|e| RgbError::StorageError(format!("{}: {}", config.storage_dir, e)))?;
+        let stash = Stash::open(Path::new(&config.storage_dir).join(STOCK_FILE))?;
+        let witnesses = WitnessStore::open(Path::new(&config.storage_dir).join(WITNESS_FILE))?;
+        Ok(Self::with_stash(config, stash, witnesses))
+    }
+
+    fn with_stash(config: RgbConfig, stash: Stash, witnesses: WitnessStore) -> Self {
         let contract_manager = ContractManager::new(&config);
         let asset_manager = AssetManager::new(&config);
         let schema_validator = SchemaValidator::new(&config);
@@ -93,9 +129,12 @@ pub fn new(config: RgbConfig) -> Self {
             asset_manager,
             schema_validator,
             transaction_manager,
+            stash: Arc::new(Mutex::new(stash)),
+            witnesses: Arc::new(Mutex::new(witnesses)),
+            proof_verifier: ProofVerifier::default(),
         }
     }
-    
+
     /// Check the health of the RGB node connection
     pub async fn check_health(&self) -> Result<bool, RgbError> {
         // Implementation would check node status
@@ -131,12 +170,96 @@ pub async fn transfer_asset(
         self.transaction_manager.transfer_asset(asset_id, recipient_id, amount).await
     }
     
-    /// Validate a contract
+    /// Validate a contract's stored history against its Bitcoin anchors
     pub async fn validate_contract(
         &self,
         contract_id: &str,
     ) -> Result<ValidationResult, RgbError> {
-        self.contract_manager.validate_contract(contract_id).await
+        let stored = contract_id.parse::<ContractId>().ok()
+            .filter(|id| self.stash.lock().unwrap().contracts().contains(id));
+
+        match stored {
+            Some(id) => {
+                let witnesses = self.witnesses.lock().unwrap();
+                let validation = self.stash.lock().unwrap()
+                    .validate_contract(id, self.config.network, &witnesses.anchored(&self.proof_verifier));
+                Ok(match validation {
+                    Ok(_) => ValidationResult { valid: true, error: None },
+                    Err(e) => ValidationResult { valid: false, error: Some(e.to_string()) },
+                })
+            },
+            None => self.contract_manager.validate_contract(contract_id).await,
+        }
+    }
+
+    /// Validate an issued contract and add it to the stash
+    pub async fn import_contract(&self, contract: Contract) -> Result<ContractId, RgbError> {
+        let witnesses = self.witnesses.lock().unwrap();
+        Ok(self.stash.lock().unwrap()
+            .import_contract(contract, self.config.network, &witnesses.anchored(&self.proof_verifier))?)
+    }
+
+    /// Record the witness transaction of a transfer
+    pub async fn record_witness(&self, witness: BtcTransaction) -> Result<Txid, RgbError> {
+        self.witnesses.lock().unwrap().add(witness)
+    }
+
+    /// Record where a witness transaction was mined
+    pub async fn record_anchor(
+        &self,
+        witness: &Txid,
+        branch: MerkleBranch,
+        header: Header,
+    ) -> Result<(), RgbError> {
+        self.witnesses.lock().unwrap().set_inclusion(witness, branch, header, &self.proof_verifier)
+    }
+
+    /// Validated transfer consigning the state on `terminals`
+    fn consign(&self, contract_id: ContractId, terminals: &[OutPoint]) -> Result<Transfer, RgbError> {
+        let transfer = self.stash.lock().unwrap().consign(contract_id, terminals)?;
+        // Never hand out history the receiver would reject
+        let witnesses = self.witnesses.lock().unwrap();
+        Ok(consignment::validate(transfer, self.config.network, &witnesses.anchored(&self.proof_verifier))?)
+    }
+
+    /// Export a strict-encoded consignment transferring `terminals`
+    pub async fn export_consignment(
+        &self,
+        contract_id: ContractId,
+        terminals: &[OutPoint],
+    ) -> Result<Vec<u8>, RgbError> {
+        Ok(consignment::to_bytes(&self.consign(contract_id, terminals)?)?)
+    }
+
+    /// Export a consignment to a file
+    pub async fn export_consignment_file(
+        &self,
+        contract_id: ContractId,
+        terminals: &[OutPoint],
+        path: impl AsRef<Path>,
+    ) -> Result<(), RgbError> {
+        Ok(consignment::save_file(self.consign(contract_id, terminals)?, path)?)
+    }
+
+    /// Validate a received consignment and add it to the stash
+    pub async fn import_consignment(&self, data: &[u8]) -> Result<ContractId, RgbError> {
+        self.accept(consignment::from_bytes(data)?)
+    }
+
+    /// Import a consignment file
+    pub async fn import_consignment_file(&self, path: impl AsRef<Path>) -> Result<ContractId, RgbError> {
+        self.accept(consignment::load_file(path)?)
+    }
+
+    fn accept(&self, transfer: Transfer) -> Result<ContractId, RgbError> {
+        let witnesses = self.witnesses.lock().unwrap();
+        Ok(self.stash.lock().unwrap()
+            .accept_transfer(transfer, self.config.network, &witnesses.anchored(&self.proof_verifier))?)
+    }
+
+    /// IDs of the contracts in the stash
+    pub async fn contracts(&self) -> Vec<ContractId> {
+        self.stash.lock().unwrap().contracts().into_iter().collect()
     }
     
     /// Get an asset's current state
@@ -168,6 +291,12 @@ pub fn get_metrics(&self) -> Metrics {
     }
 }
 
+impl WithProofVerifier for RgbClient {
+    fn proof_verifier_mut(&mut self) -> &mut ProofVerifier {
+        &mut self.proof_verifier
+    }
+}
+
 /// Contract manager component
 pub struct ContractManager {
     config: RgbConfig,
@@ -189,7 +318,7 @@ pub async fn validate_contract(&self, contract_id: &str) -> Result<ValidationRes
         let contracts = self.contracts.lock().unwrap();
         
         if let Some(_contract) = contracts.get(contract_id) {
-            Ok(ValidationResult::Valid)
+            Ok(ValidationResult { valid: true, error: None })
         } else {
             Err(RgbError::ContractNotFound(contract_id.to_string()))
         }
@@ -574,6 +703,29 @@ pub enum RgbError {
     /// Configuration error
     #[error("Configuration error: {0}")]
     ConfigError(String),
+
+    /// Malformed consignment data
+    #[error("Consignment error: {0}")]
+    ConsignmentError(String),
+
+    /// Client-side validation failed
+    #[error("Validation error: {0}")]
+    ValidationError(String),
+
+    /// Stash storage error
+    #[error("Storage error: {0}")]
+    StorageError(String),
+}
+
+impl From<anya_bitcoin::rgb::ConsignmentError> for RgbError {
+    fn from(error: anya_bitcoin::rgb::ConsignmentError) -> Self {
+        use anya_bitcoin::rgb::ConsignmentError as E;
+        match error {
+            E::Encoding(message) => RgbError::ConsignmentError(message),
+            E::Storage(message) => RgbError::StorageError(message),
+            E::Invalid(_) | E::UnknownWitness(_) => RgbError::ValidationError(error.to_string()),
+        }
+    }
 }
 
 // Module exports
@@ -581,6 +733,7 @@ pub enum RgbError {
 pub mod contracts;
 pub mod schemas;
 pub mod transfers;
+pub mod consignment;
 
 // Tests module
 #[cfg(test)]
@@ -662,11 +815,47 @@ async fn test_get_owned_assets() {
         let assets = client.get_owned_assets().await.unwrap();
         assert_eq!(assets.len(), 3);
     }
+
+    #[tokio::test]
+    async fn test_witnesses_and_consignments() {
+        use consignment::tests::{mine_tx, verifier, witness_tx};
+
+        let verifier = verifier();
+        let dir = tempfile::tempdir().unwrap();
+        let config = RgbConfig {
+            storage_dir: dir.path().to_string_lossy().into_owned(),
+            network: Network::Regtest,
+            ..RgbConfig::default()
+        };
+
+        let client = RgbClient::open(config.clone()).unwrap().with_proof_verifier(verifier.clone());
+        let tx = witness_tx(1);
+        let txid = client.record_witness(tx.clone()).await.unwrap();
+        let (branch, header) = mine_tx(&verifier, &tx);
+        client.record_anchor(&txid, branch.clone(), header).await.unwrap();
+
+        // Proofs are accepted for recorded witnesses only
+        let protocol = RgbProtocol::new().with_proof_verifier(verifier.clone());
+        assert!(!protocol.verify_proof(&branch.to_proof(&header)).await.unwrap().valid);
+        let protocol = protocol.with_client(&client);
+        assert!(protocol.verify_proof(&branch.to_proof(&header)).await.unwrap().valid);
+
+        // Witnesses survive a restart
+        let restarted = RgbClient::open(config).unwrap().with_proof_verifier(verifier);
+        assert!(restarted.witnesses.lock().unwrap().get(&txid).is_some());
+        assert!(restarted.contracts().await.is_empty());
+
+        // Data that is not a strict-encoded transfer is rejected
+        assert!(matches!(restarted.import_consignment(b"not a consignment").await, Err(RgbError::ConsignmentError(_))));
+    }
 }
 
 pub struct RgbProtocol {
     initialized: bool,
     connected: bool,
+    proof_verifier: ProofVerifier,
+    /// Witnesses proofs are checked against
+    witnesses: Arc<Mutex<WitnessStore>>,
 }
 
 impl RgbProtocol {
@@ -674,8 +863,22 @@ pub fn new() -> Self {
         Self {
             initialized: false,
             connected: false,
+            proof_verifier: ProofVerifier::default(),
+            witnesses: Arc::new(Mutex::new(WitnessStore::in_memory())),
         }
     }
+
+    /// Check proofs against a client's witnesses
+    pub fn with_client(mut self, client: &RgbClient) -> Self {
+        self.witnesses = client.witnesses.clone();
+        self
+    }
+}
+
+impl WithProofVerifier for RgbProtocol {
+    fn proof_verifier_mut(&mut self) -> &mut ProofVerifier {
+        &mut self.proof_verifier
+    }
 }
 
 #[async_trait]
@@ -727,11 +930,16 @@ async fn transfer_asset(&self, transfer: AssetTransfer) -> AnyaResult<TransferRe
 
     async fn verify_proof(&self, proof: &Proof) -> AnyaResult<VerificationResult> {
         info!("Verifying RGB proof...");
-        Ok(VerificationResult::default())
+        // The proven transaction must be a witness of one of our transfers
+        Ok(self.proof_verifier.verify_with(proof, |inclusion| {
+            self.witnesses.lock().unwrap()
+                .check_anchor(&inclusion.txid, &inclusion.block_hash)
+                .map_err(|e| e.to_string())
+        }))
     }
 
     async fn validate_state(&self, state: &ProtocolState) -> AnyaResult<ValidationResult> {
         info!("Validating RGB state...");
-        Ok(ValidationResult::default())
+        Ok(self.proof_verifier.validate_anchor(state))
     }
 } 
\ No newline at end of file
@@ -0,0 +1,849 @@
+//! Cost-aware asset routing across layers
+//!
+//! A route moves an asset from one venue (Bitcoin or a Layer 2) to another
+//! through one or more moves, each carried out by a connected protocol.
+//! Protocols quote the moves they support through
+//! [`Layer2Protocol::estimate_move`](super::Layer2Protocol::estimate_move); the
+//! router picks the cheapest path through the quoted moves, weighing latency
+//! against fees with [`RouterConfig::latency_cost_per_hour`].
+//!
+//! Routes run one move at a time, and each move must confirm before the next
+//! one starts. If a move fails, its protocol refunds whatever the move locked,
+//! and the confirmed moves before it are reversed, last first. A move that does
+//! not settle in time may still complete, so nothing is refunded: the route is
+//! left unresolved until [`AssetRouter::resume`] finds the move settled.
+//! Progress is recorded as the route runs and can be queried from other tasks.
+
+use std::cmp::Reverse;
+use std::collections::{BinaryHeap, HashMap};
+use std::sync::{Arc, RwLock};
+use std::time::{Duration, Instant};
+
+use tracing::{info, warn};
+use uuid::Uuid;
+
+use super::{
+    Layer2Manager, Layer2ManagerError, Layer2Type, MoveEstimate, MoveRequest, ProtocolLifecycle, TransactionStatus,
+    TransferResult, Venue,
+};
+
+/// Router configuration
+#[derive(Debug, Clone)]
+pub struct RouterConfig {
+    /// Own address or account on each venue, used as the source of a route
+    /// and to receive between moves
+    pub addresses: HashMap<Venue, String>,
+    /// Fee, in base units, worth paying to arrive an hour earlier
+    pub latency_cost_per_hour: u64,
+    /// Interval between status polls of a submitted move
+    pub poll_interval: Duration,
+    /// A move times out after this multiple of its estimated latency
+    pub timeout_factor: u32,
+    /// Shortest time to wait for a move to confirm
+    pub min_step_timeout: Duration,
+}
+
+impl Default for RouterConfig {
+    fn default() -> Self {
+        Self {
+            addresses: HashMap::new(),
+            latency_cost_per_hour: 100,
+            poll_interval: Duration::from_secs(10),
+            timeout_factor: 3,
+            min_step_timeout: Duration::from_secs(600),
+        }
+    }
+}
+
+/// Routing errors
+#[derive(Debug, thiserror::Error)]
+pub enum RouteError {
+    /// No connected protocol offers a path
+    #[error("No route: {0}")]
+    NoRoute(String),
+
+    /// Fees consume the routed amount
+    #[error("Amount too small: {0}")]
+    AmountTooSmall(String),
+
+    /// No own address configured for a venue
+    #[error("No address configured for {0}")]
+    MissingAddress(Venue),
+
+    /// Unknown route ID
+    #[error("Route not found: {0}")]
+    RouteNotFound(Uuid),
+
+    /// Route already started
+    #[error("Route already started: {0}")]
+    AlreadyStarted(Uuid),
+
+    /// Route is not waiting for a move to settle
+    #[error("Route is not unresolved: {0}")]
+    NotUnresolved(Uuid),
+
+    /// Error from the protocol carrying out a move
+    #[error(transparent)]
+    Protocol(#[from] Layer2ManagerError),
+}
+
+/// Request to route an asset
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RouteRequest {
+    pub asset_id: String,
+    pub amount: u64,
+    pub from: Venue,
+    pub to: Venue,
+    /// Address or account receiving the asset at the destination
+    pub recipient: String,
+    /// Bitcoin fee rate in sat/vB
+    pub fee_rate: u64,
+}
+
+/// One move of a route
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RouteStep {
+    /// Protocol carrying out the move
+    pub protocol: Layer2Type,
+    /// The move, with the amount left after earlier fees
+    pub request: MoveRequest,
+    /// The protocol's quote for the move
+    pub estimate: MoveEstimate,
+}
+
+/// Planned route
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RoutePlan {
+    pub id: Uuid,
+    pub request: RouteRequest,
+    pub steps: Vec<RouteStep>,
+    /// Total fees
+    pub fee: u64,
+    /// Total expected latency
+    pub latency: Duration,
+    /// Amount arriving at the recipient
+    pub delivered: u64,
+}
+
+/// Progress of one move
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum StepState {
+    /// Not started
+    Pending,
+    /// Submitted, waiting for confirmation
+    Submitted { tx_id: String },
+    /// Confirmed at the destination
+    Confirmed { tx_id: String },
+    /// Refused by the protocol, or settled as failed or rejected
+    Failed { tx_id: Option<String>, error: String },
+    /// Not settled in time; it may still complete
+    TimedOut { tx_id: String },
+    /// Refund of the failed move, or reversal of a confirmed one, submitted
+    Refunding { tx_id: String, refund_tx_id: String, amount: u64 },
+    /// `amount` is back at the move's source
+    Refunded { tx_id: String, refund_tx_id: String, amount: u64 },
+    /// Could not be refunded or reversed
+    RefundFailed { tx_id: String, error: String },
+}
+
+/// Progress of a route
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RouteState {
+    /// Planned, not started
+    Planned,
+    /// Running the move at this index
+    Running { step: usize },
+    /// Every move confirmed
+    Completed,
+    /// A move failed and the route was undone
+    RolledBack { step: usize, error: String },
+    /// A move failed and undoing the route failed too
+    RollbackFailed { step: usize, error: String },
+    /// The move or refund at this index did not settle in time
+    Unresolved { step: usize, error: String },
+}
+
+/// Plan and progress of a route
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RouteProgress {
+    pub plan: RoutePlan,
+    pub state: RouteState,
+    pub steps: Vec<StepState>,
+}
+
+/// Quoted move between two venues
+struct Edge {
+    from: usize,
+    to: usize,
+    protocol: Layer2Type,
+    estimate: MoveEstimate,
+}
+
+/// Plans and runs routes over the protocols of a [`Layer2Manager`]
+pub struct AssetRouter {
+    manager: Arc<Layer2Manager>,
+    config: RouterConfig,
+    routes: RwLock<HashMap<Uuid, RouteProgress>>,
+}
+
+impl AssetRouter {
+    /// Create a router over the manager's connected protocols
+    pub fn new(manager: Arc<Layer2Manager>, config: RouterConfig) -> Self {
+        Self {
+            manager,
+            config,
+            routes: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// Weighted cost of a move
+    fn cost(&self, estimate: &MoveEstimate) -> u64 {
+        let latency = estimate.latency.as_secs() as u128 * self.config.latency_cost_per_hour as u128 / 3600;
+        estimate.fee.saturating_add(latency.min(u64::MAX as u128) as u64)
+    }
+
+    fn address(&self, venue: Venue) -> Result<String, RouteError> {
+        self.config.addresses.get(&venue).cloned().ok_or(RouteError::MissingAddress(venue))
+    }
+
+    fn connected_protocols(&self) -> Vec<Layer2Type> {
+        let registry = self.manager.registry();
+        registry.types().into_iter()
+            .filter(|l2_type| registry.lifecycle(*l2_type) == Some(ProtocolLifecycle::Connected))
+            .collect()
+    }
+
+    /// Plan the cheapest route for `request`
+    ///
+    /// The plan is recorded and can be run with [`execute`](Self::execute).
+    pub async fn plan(&self, request: RouteRequest) -> Result<RoutePlan, RouteError> {
+        let protocols = self.connected_protocols();
+        let mut venues = vec![Venue::Bitcoin];
+        venues.extend(protocols.iter().map(|l2_type| Venue::Layer2(*l2_type)));
+        for venue in [request.from, request.to] {
+            if !venues.contains(&venue) {
+                return Err(RouteError::NoRoute(format!("{} is not connected", venue)));
+            }
+        }
+
+        // Quote every move between venues at the full amount
+        let registry = self.manager.registry();
+        let mut edges = Vec::new();
+        for (from, from_venue) in venues.iter().enumerate() {
+            for (to, to_venue) in venues.iter().enumerate() {
+                // Moves within a venue are only useful as the whole route
+                if from == to && !(request.from == request.to && *from_venue == request.from) {
+                    continue;
+                }
+                let quote = MoveRequest {
+                    asset_id: request.asset_id.clone(),
+                    amount: request.amount,
+                    from: *from_venue,
+                    to: *to_venue,
+                    source: String::new(),
+                    destination: String::new(),
+                    fee_rate: request.fee_rate,
+                };
+                for protocol in &protocols {
+                    if let Ok(estimate) = registry.estimate_move(*protocol, &quote).await {
+                        edges.push(Edge { from, to, protocol: *protocol, estimate });
+                    }
+                }
+            }
+        }
+
+        let source = venues.iter().position(|venue| *venue == request.from).expect("checked above");
+        let target = venues.iter().position(|venue| *venue == request.to).expect("checked above");
+        let path = if source == target {
+            edges.iter()
+                .filter(|edge| edge.from == source && edge.to == target)
+                .min_by_key(|edge| self.cost(&edge.estimate))
+                .map(|edge| vec![edge])
+        } else {
+            self.cheapest_path(&edges, venues.len(), source, target)
+        };
+        let path = path.ok_or_else(|| RouteError::NoRoute(format!(
+            "No protocol moves {} from {} to {}", request.asset_id, request.from, request.to
+        )))?;
+
+        // Quote again along the path with the amount left after earlier fees
+        let mut steps = Vec::with_capacity(path.len());
+        let mut amount = request.amount;
+        let mut source_address = self.address(request.from)?;
+        for (index, edge) in path.iter().enumerate() {
+            let destination = if index + 1 == path.len() {
+                request.recipient.clone()
+            } else {
+                self.address(venues[edge.to])?
+            };
+            let move_request = MoveRequest {
+                asset_id: request.asset_id.clone(),
+                amount,
+                from: venues[edge.from],
+                to: venues[edge.to],
+                source: std::mem::replace(&mut source_address, destination.clone()),
+                destination,
+                fee_rate: request.fee_rate,
+            };
+            let estimate = registry.estimate_move(edge.protocol, &move_request).await?;
+            if estimate.fee >= amount {
+                return Err(RouteError::AmountTooSmall(format!(
+                    "{} from {} to {} costs {} of the remaining {}",
+                    request.asset_id, move_request.from, move_request.to, estimate.fee, amount
+                )));
+            }
+            amount -= estimate.fee;
+            steps.push(RouteStep { protocol: edge.protocol, request: move_request, estimate });
+        }
+
+        let plan = RoutePlan {
+            id: Uuid::new_v4(),
+            fee: request.amount - amount,
+            latency: steps.iter().map(|step| step.estimate.latency).sum(),
+            delivered: amount,
+            request,
+            steps,
+        };
+        info!(
+            "Planned route {} from {} to {} in {} moves, fee {}",
+            plan.id, plan.request.from, plan.request.to, plan.steps.len(), plan.fee
+        );
+
+        self.routes.write().unwrap().insert(plan.id, RouteProgress {
+            plan: plan.clone(),
+            state: RouteState::Planned,
+            steps: vec![StepState::Pending; plan.steps.len()],
+        });
+        Ok(plan)
+    }
+
+    /// Dijkstra over the quoted moves
+    fn cheapest_path<'a>(&self, edges: &'a [Edge], venues: usize, source: usize, target: usize) -> Option<Vec<&'a Edge>> {
+        let mut best = vec![u64::MAX; venues];
+        let mut via: Vec<Option<&Edge>> = vec![None; venues];
+        let mut queue = BinaryHeap::new();
+        best[source] = 0;
+        queue.push(Reverse((0, source)));
+
+        while let Some(Reverse((cost, venue))) = queue.pop() {
+            if venue == target {
+                break;
+            }
+            if cost > best[venue] {
+                continue;
+            }
+            for edge in edges.iter().filter(|edge| edge.from == venue && edge.to != venue) {
+                let next = cost.saturating_add(self.cost(&edge.estimate));
+                if next < best[edge.to] {
+                    best[edge.to] = next;
+                    via[edge.to] = Some(edge);
+                    queue.push(Reverse((next, edge.to)));
+                }
+            }
+        }
+
+        let mut path = Vec::new();
+        let mut venue = target;
+        while venue != source {
+            let edge = via[venue]?;
+            path.push(edge);
+            venue = edge.from;
+        }
+        path.reverse();
+        Some(path)
+    }
+
+    /// Progress of a route
+    pub fn progress(&self, id: &Uuid) -> Option<RouteProgress> {
+        self.routes.read().unwrap().get(id).cloned()
+    }
+
+    /// Progress of every known route
+    pub fn routes(&self) -> Vec<RouteProgress> {
+        self.routes.read().unwrap().values().cloned().collect()
+    }
+
+    fn update(&self, id: &Uuid, f: impl FnOnce(&mut RouteProgress)) {
+        if let Some(progress) = self.routes.write().unwrap().get_mut(id) {
+            f(progress);
+        }
+    }
+
+    /// Run a planned route in the background
+    pub fn spawn(self: &Arc<Self>, id: Uuid) -> tokio::task::JoinHandle<Result<RouteProgress, RouteError>> {
+        let router = self.clone();
+        tokio::spawn(async move { router.execute(&id).await })
+    }
+
+    /// Run a planned route to completion or rollback
+    pub async fn execute(&self, id: &Uuid) -> Result<RouteProgress, RouteError> {
+        let plan = {
+            let mut routes = self.routes.write().unwrap();
+            let progress = routes.get_mut(id).ok_or(RouteError::RouteNotFound(*id))?;
+            if progress.state != RouteState::Planned {
+                return Err(RouteError::AlreadyStarted(*id));
+            }
+            progress.state = RouteState::Running { step: 0 };
+            progress.plan.clone()
+        };
+
+        self.run_from(id, &plan, 0).await;
+        self.progress(id).ok_or(RouteError::RouteNotFound(*id))
+    }
+
+    /// Check the move or refund an unresolved route waits for, and carry on once it settled
+    ///
+    /// A timed-out move that confirmed lets the route continue; one that failed
+    /// is refunded and the route rolled back. While it is still pending the
+    /// route stays unresolved.
+    pub async fn resume(&self, id: &Uuid) -> Result<RouteProgress, RouteError> {
+        let (progress, step, error) = {
+            let mut routes = self.routes.write().unwrap();
+            let progress = routes.get_mut(id).ok_or(RouteError::RouteNotFound(*id))?;
+            let RouteState::Unresolved { step, error } = progress.state.clone() else {
+                return Err(RouteError::NotUnresolved(*id));
+            };
+            progress.state = RouteState::Running { step };
+            (progress.clone(), step, error)
+        };
+        let plan = &progress.plan;
+
+        match progress.steps[step].clone() {
+            StepState::TimedOut { tx_id } => {
+                let status = self.manager.registry().get_transaction_status(plan.steps[step].protocol, &tx_id).await;
+                match status {
+                    Ok(TransactionStatus::Confirmed) => {
+                        info!("Move {} of route {} confirmed late", step, id);
+                        self.update(id, |progress| progress.steps[step] = StepState::Confirmed { tx_id });
+                        self.run_from(id, plan, step + 1).await;
+                    },
+                    Ok(status @ (TransactionStatus::Failed | TransactionStatus::Rejected)) => {
+                        let error = format!("{} was {:?}", tx_id, status);
+                        warn!("Route {} failed at move {}: {}", id, step, error);
+                        self.update(id, |progress| {
+                            progress.steps[step] = StepState::Failed { tx_id: Some(tx_id), error: error.clone() };
+                        });
+                        self.unwind(id, plan, step, step, error).await;
+                    },
+                    _ => self.update(id, |progress| progress.state = RouteState::Unresolved { step, error }),
+                }
+            },
+            _ => {
+                // Moves after the failed one never started
+                let failed = progress.steps.iter().rposition(|state| *state != StepState::Pending).unwrap_or(step);
+                self.unwind(id, plan, failed, step, error).await;
+            },
+        }
+        self.progress(id).ok_or(RouteError::RouteNotFound(*id))
+    }
+
+    /// Run the moves from `start` on, then complete, roll back or leave the route unresolved
+    async fn run_from(&self, id: &Uuid, plan: &RoutePlan, start: usize) {
+        for (index, step) in plan.steps.iter().enumerate().skip(start) {
+            self.update(id, |progress| progress.state = RouteState::Running { step: index });
+            let state = self.run_step(id, index, step).await;
+            self.update(id, |progress| progress.steps[index] = state.clone());
+
+            match state {
+                StepState::Failed { error, .. } => {
+                    warn!("Route {} failed at move {}: {}", id, index, error);
+                    self.unwind(id, plan, index, index, error).await;
+                    return;
+                },
+                StepState::TimedOut { tx_id } => {
+                    let error = format!("{} did not settle in time", tx_id);
+                    warn!("Route {} is unresolved at move {}: {}", id, index, error);
+                    self.update(id, |progress| progress.state = RouteState::Unresolved { step: index, error });
+                    return;
+                },
+                _ => {},
+            }
+        }
+
+        info!("Route {} completed", id);
+        self.update(id, |progress| progress.state = RouteState::Completed);
+    }
+
+    /// Submit a move and wait for it to settle
+    async fn run_step(&self, id: &Uuid, index: usize, step: &RouteStep) -> StepState {
+        let result = match self.manager.registry().execute_move(step.protocol, &step.request).await {
+            Ok(result) => result,
+            Err(e) => return StepState::Failed { tx_id: None, error: e.to_string() },
+        };
+        let tx_id = result.tx_id;
+        self.update(id, |progress| progress.steps[index] = StepState::Submitted { tx_id: tx_id.clone() });
+
+        match self.settle(step.protocol, &tx_id, result.status, self.timeout(&step.estimate)).await {
+            Some(TransactionStatus::Confirmed) => StepState::Confirmed { tx_id },
+            Some(status) => StepState::Failed { error: format!("{} was {:?}", tx_id, status), tx_id: Some(tx_id) },
+            None => StepState::TimedOut { tx_id },
+        }
+    }
+
+    /// Time a move with this estimate gets to settle
+    fn timeout(&self, estimate: &MoveEstimate) -> Duration {
+        (estimate.latency * self.config.timeout_factor).max(self.config.min_step_timeout)
+    }
+
+    /// Poll a submitted move until it is no longer pending, or `None` once `timeout` passes
+    async fn settle(&self, protocol: Layer2Type, tx_id: &str, mut status: TransactionStatus, timeout: Duration) -> Option<TransactionStatus> {
+        let registry = self.manager.registry();
+        let started = Instant::now();
+        loop {
+            if !matches!(status, TransactionStatus::Pending) {
+                return Some(status);
+            }
+            if started.elapsed() >= timeout {
+                return None;
+            }
+            tokio::time::sleep(self.config.poll_interval).await;
+
+            // Status errors are transient until the move times out
+            if let Ok(current) = registry.get_transaction_status(protocol, tx_id).await {
+                status = current;
+            }
+        }
+    }
+
+    /// Bring the funds of a failed route back to its source, from move `from` down
+    ///
+    /// `failed` is the move that failed. Its protocol refunds what it locked,
+    /// then each confirmed move before it is reversed with the amount that came
+    /// back. A refund or reversal that does not settle in time leaves the route
+    /// unresolved at that move.
+    async fn unwind(&self, id: &Uuid, plan: &RoutePlan, failed: usize, from: usize, error: String) {
+        let registry = self.manager.registry();
+        // Amount back at the source of the move above the current one
+        let mut available = 0;
+        for index in (0..=from).rev() {
+            let step = &plan.steps[index];
+            let Some(state) = self.progress(id).map(|progress| progress.steps[index].clone()) else {
+                return;
+            };
+
+            let refund = match state {
+                StepState::Failed { tx_id: Some(tx_id), .. } => {
+                    match registry.refund_move(step.protocol, &step.request, &tx_id).await {
+                        Ok(None) => Ok(None),
+                        Ok(Some(refund)) => {
+                            Ok(Some((tx_id, refund.tx_id, refund.amount, TransactionStatus::Pending, self.timeout(&step.estimate))))
+                        },
+                        Err(e) => Err((tx_id, e.to_string())),
+                    }
+                },
+                StepState::Confirmed { tx_id } => {
+                    let mut reverse = step.request.reversed();
+                    reverse.amount = available;
+                    match self.reverse(step.protocol, &reverse).await {
+                        Ok((result, estimate)) => Ok(Some((
+                            tx_id,
+                            result.tx_id,
+                            available - estimate.fee,
+                            result.status,
+                            self.timeout(&estimate),
+                        ))),
+                        Err(e) => Err((tx_id, e.to_string())),
+                    }
+                },
+                StepState::Refunding { tx_id, refund_tx_id, amount } => {
+                    // Left unresolved earlier: check once
+                    let status = registry.get_transaction_status(step.protocol, &refund_tx_id).await
+                        .unwrap_or(TransactionStatus::Pending);
+                    Ok(Some((tx_id, refund_tx_id, amount, status, Duration::ZERO)))
+                },
+                StepState::Refunded { amount, .. } => {
+                    available = amount;
+                    continue;
+                },
+                // Refused before anything was submitted
+                _ => Ok(None),
+            };
+
+            let (tx_id, refund_tx_id, amount, status, timeout) = match refund {
+                Ok(Some(refund)) => refund,
+                Ok(None) => {
+                    available = step.request.amount;
+                    continue;
+                },
+                Err((tx_id, e)) => {
+                    warn!("Undoing move {} of route {} failed: {}", index, id, e);
+                    self.update(id, |progress| {
+                        progress.steps[index] = StepState::RefundFailed { tx_id, error: e };
+                        progress.state = RouteState::RollbackFailed { step: failed, error };
+                    });
+                    return;
+                },
+            };
+
+            self.update(id, |progress| progress.steps[index] = StepState::Refunding {
+                tx_id: tx_id.clone(),
+                refund_tx_id: refund_tx_id.clone(),
+                amount,
+            });
+            match self.settle(step.protocol, &refund_tx_id, status, timeout).await {
+                Some(TransactionStatus::Confirmed) => {
+                    available = amount;
+                    self.update(id, |progress| progress.steps[index] = StepState::Refunded { tx_id, refund_tx_id, amount });
+                },
+                Some(status) => {
+                    let e = format!("{} was {:?}", refund_tx_id, status);
+                    warn!("Undoing move {} of route {} failed: {}", index, id, e);
+                    self.update(id, |progress| {
+                        progress.steps[index] = StepState::RefundFailed { tx_id, error: e };
+                        progress.state = RouteState::RollbackFailed { step: failed, error };
+                    });
+                    return;
+                },
+                None => {
+                    warn!("Undoing move {} of route {} did not settle in time", index, id);
+                    self.update(id, |progress| progress.state = RouteState::Unresolved { step: index, error });
+                    return;
+                },
+            }
+        }
+
+        info!("Route {} rolled back", id);
+        self.update(id, |progress| progress.state = RouteState::RolledBack { step: failed, error });
+    }
+
+    /// Quote and submit the reversal of a confirmed move
+    async fn reverse(&self, protocol: Layer2Type, request: &MoveRequest) -> Result<(TransferResult, MoveEstimate), RouteError> {
+        let registry = self.manager.registry();
+        let estimate = registry.estimate_move(protocol, request).await?;
+        if estimate.fee >= request.amount {
+            return Err(RouteError::AmountTooSmall(format!(
+                "Moving {} back from {} to {} costs {} of {}",
+                request.asset_id, request.from, request.to, estimate.fee, request.amount
+            )));
+        }
+        let result = registry.execute_move(protocol, request).await?;
+        Ok((result, estimate))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::collections::HashSet;
+    use std::sync::atomic::{AtomicBool, Ordering};
+    use std::sync::Mutex;
+    use async_trait::async_trait;
+    use crate::layer2::{
+        AssetParams, AssetTransfer, Layer2ManagerConfig, Layer2Protocol, MoveRefund, Proof, ProtocolState,
+        ValidationResult, VerificationResult, BTC_ASSET_ID,
+    };
+    use crate::{AnyaError, AnyaResult};
+
+    /// Protocol with fixed quotes that records the moves it carries out
+    #[derive(Default)]
+    struct QuotingProtocol {
+        quotes: HashMap<(Venue, Venue), MoveEstimate>,
+        /// Moves whose transactions end up failed
+        failing: HashSet<(Venue, Venue)>,
+        /// Moves whose transactions stay pending until `settled` is set
+        slow: HashSet<(Venue, Venue)>,
+        settled: Arc<AtomicBool>,
+        log: Arc<Mutex<Vec<String>>>,
+    }
+
+    impl QuotingProtocol {
+        fn quote(mut self, from: Venue, to: Venue, fee: u64, latency_secs: u64) -> Self {
+            self.quotes.insert((from, to), MoveEstimate { fee, latency: Duration::from_secs(latency_secs) });
+            self
+        }
+    }
+
+    #[async_trait]
+    impl Layer2Protocol for QuotingProtocol {
+        async fn initialize(&self) -> AnyaResult<()> { Ok(()) }
+        async fn connect(&self) -> AnyaResult<()> { Ok(()) }
+        async fn disconnect(&self) -> AnyaResult<()> { Ok(()) }
+        async fn submit_transaction(&self, _tx: &[u8]) -> AnyaResult<String> { unreachable!() }
+        async fn get_transaction_status(&self, tx_id: &str) -> AnyaResult<TransactionStatus> {
+            Ok(if tx_id.starts_with("fail") {
+                TransactionStatus::Failed
+            } else if tx_id.starts_with("slow") && !self.settled.load(Ordering::SeqCst) {
+                TransactionStatus::Pending
+            } else {
+                TransactionStatus::Confirmed
+            })
+        }
+        async fn get_state(&self) -> AnyaResult<ProtocolState> { Ok(ProtocolState::default()) }
+        async fn sync_state(&self) -> AnyaResult<()> { Ok(()) }
+        async fn issue_asset(&self, _params: AssetParams) -> AnyaResult<String> { unreachable!() }
+        async fn transfer_asset(&self, _transfer: AssetTransfer) -> AnyaResult<TransferResult> { unreachable!() }
+        async fn verify_proof(&self, _proof: &Proof) -> AnyaResult<VerificationResult> { unreachable!() }
+        async fn validate_state(&self, _state: &ProtocolState) -> AnyaResult<ValidationResult> { unreachable!() }
+
+        async fn estimate_move(&self, request: &MoveRequest) -> AnyaResult<MoveEstimate> {
+            self.quotes.get(&(request.from, request.to)).copied()
+                .ok_or_else(|| AnyaError::System("unsupported".to_string()))
+        }
+
+        async fn execute_move(&self, request: &MoveRequest) -> AnyaResult<TransferResult> {
+            let prefix = if self.failing.contains(&(request.from, request.to)) {
+                "fail"
+            } else if self.slow.contains(&(request.from, request.to)) {
+                "slow"
+            } else {
+                "ok"
+            };
+            let tx_id = format!("{}:{}->{}:{}", prefix, request.from, request.to, request.amount);
+            self.log.lock().unwrap().push(format!("move {} -> {} {}", request.source, request.destination, request.amount));
+            Ok(TransferResult { tx_id, status: TransactionStatus::Pending, timestamp: 0 })
+        }
+
+        async fn refund_move(&self, request: &MoveRequest, tx_id: &str) -> AnyaResult<Option<MoveRefund>> {
+            assert!(matches!(self.get_transaction_status(tx_id).await?, TransactionStatus::Failed));
+            self.log.lock().unwrap().push(format!("refund {}", tx_id));
+            // The failed move locked the funds; getting them back costs 150
+            Ok(Some(MoveRefund { tx_id: format!("refund:{}", tx_id), amount: request.amount - 150 }))
+        }
+    }
+
+    const LIQUID: Venue = Venue::Layer2(Layer2Type::Liquid);
+    const LIGHTNING: Venue = Venue::Layer2(Layer2Type::Lightning);
+
+    async fn connected_router(liquid: QuotingProtocol, lightning: QuotingProtocol, latency_cost_per_hour: u64) -> AssetRouter {
+        let manager = Layer2Manager::new(Layer2ManagerConfig {
+            bob_config: None,
+            lightning_config: None,
+            enabled_solutions: vec![Layer2Type::Liquid, Layer2Type::Lightning],
+            protocol_configs: HashMap::new(),
+        });
+        manager.register_protocol(Layer2Type::Liquid, Arc::new(liquid)).unwrap();
+        manager.register_protocol(Layer2Type::Lightning, Arc::new(lightning)).unwrap();
+        manager.connect(Layer2Type::Liquid).await.unwrap();
+        manager.connect(Layer2Type::Lightning).await.unwrap();
+
+        let addresses = [(Venue::Bitcoin, "bc1-own"), (LIQUID, "lq1-own"), (LIGHTNING, "ln-own")]
+            .into_iter()
+            .map(|(venue, address)| (venue, address.to_string()))
+            .collect();
+        AssetRouter::new(Arc::new(manager), RouterConfig {
+            addresses,
+            latency_cost_per_hour,
+            poll_interval: Duration::from_millis(1),
+            timeout_factor: 1,
+            min_step_timeout: Duration::from_millis(50),
+        })
+    }
+
+    fn request(from: Venue, to: Venue) -> RouteRequest {
+        RouteRequest {
+            asset_id: BTC_ASSET_ID.to_string(),
+            amount: 100_000,
+            from,
+            to,
+            recipient: "recipient".to_string(),
+            fee_rate: 5,
+        }
+    }
+
+    #[tokio::test]
+    async fn test_plan_weighs_fees_against_latency() {
+        let liquid = || QuotingProtocol::default()
+            .quote(Venue::Bitcoin, LIQUID, 800, 61_200)
+            .quote(LIQUID, Venue::Bitcoin, 400, 1_320);
+        // A direct swap from Liquid costs more in fees but skips the mainchain
+        let lightning = || QuotingProtocol::default()
+            .quote(Venue::Bitcoin, LIGHTNING, 700, 1_800)
+            .quote(LIQUID, LIGHTNING, 1_500, 120);
+
+        let patient = connected_router(liquid(), lightning(), 0).await;
+        let plan = patient.plan(request(LIQUID, LIGHTNING)).await.unwrap();
+        let hops: Vec<_> = plan.steps.iter().map(|step| (step.protocol, step.request.to)).collect();
+        assert_eq!(hops, vec![(Layer2Type::Liquid, Venue::Bitcoin), (Layer2Type::Lightning, LIGHTNING)]);
+        assert_eq!(plan.fee, 1_100);
+        assert_eq!(plan.delivered, 98_900);
+        assert_eq!(plan.steps[0].request.destination, "bc1-own");
+        assert_eq!(plan.steps[1].request.amount, 99_600);
+        assert_eq!(plan.steps[1].request.destination, "recipient");
+
+        let hurried = connected_router(liquid(), lightning(), 3_600).await;
+        let plan = hurried.plan(request(LIQUID, LIGHTNING)).await.unwrap();
+        assert_eq!(plan.steps.len(), 1);
+        assert_eq!(plan.steps[0].protocol, Layer2Type::Lightning);
+        assert_eq!(plan.latency, Duration::from_secs(120));
+        assert_eq!(hurried.progress(&plan.id).unwrap().state, RouteState::Planned);
+
+        assert!(matches!(
+            hurried.plan(request(LIGHTNING, LIQUID)).await,
+            Err(RouteError::NoRoute(_))
+        ));
+        assert!(matches!(
+            hurried.plan(RouteRequest { amount: 400, ..request(LIQUID, Venue::Bitcoin) }).await,
+            Err(RouteError::AmountTooSmall(_))
+        ));
+    }
+
+    #[tokio::test]
+    async fn test_execute_and_roll_back() {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let liquid = QuotingProtocol { log: log.clone(), ..Default::default() }
+            .quote(LIQUID, Venue::Bitcoin, 400, 0);
+        let lightning = QuotingProtocol { log: log.clone(), ..Default::default() }
+            .quote(Venue::Bitcoin, LIGHTNING, 700, 0);
+        let router = Arc::new(connected_router(liquid, lightning, 0).await);
+
+        let plan = router.plan(request(LIQUID, LIGHTNING)).await.unwrap();
+        let progress = router.spawn(plan.id).await.unwrap().unwrap();
+        assert_eq!(progress.state, RouteState::Completed);
+        assert!(progress.steps.iter().all(|step| matches!(step, StepState::Confirmed { .. })));
+        assert!(matches!(router.execute(&plan.id).await, Err(RouteError::AlreadyStarted(_))));
+        assert!(matches!(router.resume(&plan.id).await, Err(RouteError::NotUnresolved(_))));
+        assert_eq!(*log.lock().unwrap(), vec!["move lq1-own -> bc1-own 100000", "move bc1-own -> recipient 99600"]);
+
+        // The second move fails: it is refunded, then the first one is reversed
+        log.lock().unwrap().clear();
+        let liquid = QuotingProtocol { log: log.clone(), ..Default::default() }
+            .quote(LIQUID, Venue::Bitcoin, 400, 0)
+            .quote(Venue::Bitcoin, LIQUID, 800, 0);
+        let mut lightning = QuotingProtocol { log: log.clone(), ..Default::default() }
+            .quote(Venue::Bitcoin, LIGHTNING, 700, 0);
+        lightning.failing.insert((Venue::Bitcoin, LIGHTNING));
+        let router = connected_router(liquid, lightning, 0).await;
+
+        let plan = router.plan(request(LIQUID, LIGHTNING)).await.unwrap();
+        let progress = router.execute(&plan.id).await.unwrap();
+        assert!(matches!(progress.state, RouteState::RolledBack { step: 1, .. }));
+        assert!(matches!(&progress.steps[0], StepState::Refunded { amount: 98_650, .. }));
+        assert!(matches!(&progress.steps[1], StepState::Refunded { refund_tx_id, amount: 99_450, .. } if refund_tx_id.starts_with("refund:fail")));
+        assert_eq!(*log.lock().unwrap(), vec![
+            "move lq1-own -> bc1-own 100000",
+            "move bc1-own -> recipient 99600",
+            "refund fail:Bitcoin->Lightning Network:99600",
+            "move bc1-own -> lq1-own 99450",
+        ]);
+        assert_eq!(router.routes().len(), 1);
+    }
+
+    #[tokio::test]
+    async fn test_unsettled_move_is_not_refunded() {
+        let log = Arc::new(Mutex::new(Vec::new()));
+        let liquid = QuotingProtocol { log: log.clone(), ..Default::default() }
+            .quote(LIQUID, Venue::Bitcoin, 400, 0);
+        let mut lightning = QuotingProtocol { log: log.clone(), ..Default::default() }
+            .quote(Venue::Bitcoin, LIGHTNING, 700, 0);
+        lightning.slow.insert((Venue::Bitcoin, LIGHTNING));
+        let settled = lightning.settled.clone();
+        let router = connected_router(liquid, lightning, 0).await;
+
+        // The second move may still complete, so the route waits instead of refunding
+        let plan = router.plan(request(LIQUID, LIGHTNING)).await.unwrap();
+        let progress = router.execute(&plan.id).await.unwrap();
+        assert!(matches!(progress.state, RouteState::Unresolved { step: 1, .. }));
+        assert!(matches!(progress.steps[0], StepState::Confirmed { .. }));
+        assert!(matches!(progress.steps[1], StepState::TimedOut { .. }));
+        assert_eq!(log.lock().unwrap().len(), 2);
+
+        let progress = router.resume(&plan.id).await.unwrap();
+        assert!(matches!(progress.state, RouteState::Unresolved { step: 1, .. }));
+
+        settled.store(true, Ordering::SeqCst);
+        let progress = router.resume(&plan.id).await.unwrap();
+        assert_eq!(progress.state, RouteState::Completed);
+        assert!(matches!(progress.steps[1], StepState::Confirmed { .. }));
+        assert_eq!(log.lock().unwrap().len(), 2);
+    }
+}
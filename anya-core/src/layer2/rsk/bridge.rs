@@ -0,0 +1,449 @@
+//! PowPeg bridge interaction module
+//!
+//! Peg-ins are Bitcoin payments to the current federation address. An optional
+//! OP_RETURN output carries the destination data defined by RSKIP-170:
+//! `"RSKT" || 0x01 || rsk_recipient (20 bytes) [|| refund_type || refund_hash160]`,
+//! where the refund type is 1 for P2PKH and 2 for P2SH. Without it the bridge
+//! credits the account of the first input's public key, so every input must
+//! reveal one where the bridge looks for it: P2PKH, or P2SH-P2WPKH. A P2SH
+//! output alone does not say what it wraps, so those coins need their redeem
+//! script in the request.
+//!
+//! Peg-outs are RBTC value transfers to the bridge contract. The BTC is released
+//! to the P2PKH address of the sender's key, and progress is followed through
+//! the bridge's events. Bitcoin transaction hashes in events are in display
+//! byte order, as the bridge takes them from bitcoinj.
+
+use std::collections::BTreeMap;
+use std::str::FromStr;
+
+use bitcoin::blockdata::script::Instruction;
+use bitcoin::hashes::Hash;
+use bitcoin::script::PushBytes;
+use bitcoin::secp256k1::PublicKey;
+use bitcoin::{
+    absolute, transaction, Address as BtcAddress, Amount, Network, OutPoint, Psbt, Script, ScriptBuf,
+    Sequence, Transaction, TxIn, TxOut, Txid, Witness,
+};
+
+use crate::layer2::bob::evm::{keccak256, Address, EvmRpcClient, H256};
+use crate::layer2::bob::relay::selector;
+use crate::layer2::bob::EvmLog;
+
+use super::RskError;
+
+/// Marker opening peg-in destination data
+pub const PEG_IN_MARKER: &[u8; 4] = b"RSKT";
+
+/// Version of the peg-in destination data
+pub const PEG_IN_VERSION: u8 = 1;
+
+/// Signature of the federation address getter
+pub const FEDERATION_ADDRESS_SIGNATURE: &str = "getFederationAddress()";
+
+/// Signature of the peg-in registration check
+pub const TX_PROCESSED_SIGNATURE: &str = "isBtcTxHashAlreadyProcessed(string)";
+
+/// Signature of the estimate of the Bitcoin fees charged to each peg-out
+pub const PEG_OUT_FEES_SIGNATURE: &str = "getEstimatedFeesForNextPegOutEvent()";
+
+/// Event emitted when the bridge accepts a release request
+pub const RELEASE_REQUEST_RECEIVED_EVENT: &str = "release_request_received(address,bytes,uint256)";
+
+/// Event emitted when the bridge refuses a release request
+pub const RELEASE_REQUEST_REJECTED_EVENT: &str = "release_request_rejected(address,uint256,int256)";
+
+/// Event emitted when the federation creates the release transaction
+pub const RELEASE_REQUESTED_EVENT: &str = "release_requested(bytes32,bytes32,uint256)";
+
+/// Event emitted once the release transaction is confirmed on Bitcoin
+pub const PEGOUT_CONFIRMED_EVENT: &str = "pegout_confirmed(bytes32,uint256)";
+
+/// Outputs below this are not worth creating
+const DUST_LIMIT: u64 = 546;
+
+/// Weight-adjusted size of everything but inputs and outputs, rounded up
+const TX_OVERHEAD_VSIZE: u64 = 11;
+
+/// Peg-in funding request
+#[derive(Debug, Clone)]
+pub struct PegInRequest {
+    /// Coins to fund the peg-in from
+    pub utxos: Vec<(OutPoint, TxOut)>,
+    /// Amount to lock in the federation
+    pub amount: Amount,
+    /// Fee rate in sat/vB
+    pub fee_rate: u64,
+    /// Script receiving the change
+    pub change_script: ScriptBuf,
+    /// Account credited with RBTC; `None` credits the sender's key
+    pub rsk_recipient: Option<Address>,
+    /// Address the federation refunds a rejected peg-in to
+    pub refund_address: Option<BtcAddress>,
+    /// Redeem scripts of P2SH coins
+    pub redeem_scripts: BTreeMap<OutPoint, ScriptBuf>,
+}
+
+impl PegInRequest {
+    /// Whether spending `utxo` reveals a key the bridge can credit
+    fn reveals_key(&self, outpoint: &OutPoint, utxo: &TxOut) -> bool {
+        if utxo.script_pubkey.is_p2pkh() {
+            return true;
+        }
+        self.redeem_scripts.get(outpoint).is_some_and(|redeem| {
+            redeem.is_p2wpkh() && utxo.script_pubkey == ScriptBuf::new_p2sh(&redeem.script_hash())
+        })
+    }
+}
+
+/// Peg-in destination data for the OP_RETURN output
+pub fn peg_in_data(rsk_recipient: &Address, refund_address: Option<&BtcAddress>) -> Result<Vec<u8>, RskError> {
+    let mut data = PEG_IN_MARKER.to_vec();
+    data.push(PEG_IN_VERSION);
+    data.extend_from_slice(rsk_recipient);
+
+    if let Some(refund) = refund_address {
+        let script = refund.script_pubkey();
+        if script.is_p2pkh() {
+            data.push(1);
+            data.extend_from_slice(&script.as_bytes()[3..23]);
+        } else if script.is_p2sh() {
+            data.push(2);
+            data.extend_from_slice(&script.as_bytes()[2..22]);
+        } else {
+            return Err(RskError::BridgeError(format!("Refund address {} must be P2PKH or P2SH", refund)));
+        }
+    }
+    Ok(data)
+}
+
+/// Virtual size of an input spending `script`, assuming single-key spends
+fn input_vsize(script: &Script) -> Option<u64> {
+    if script.is_p2wpkh() {
+        Some(68)
+    } else if script.is_p2tr() {
+        Some(58)
+    } else if script.is_p2pkh() {
+        Some(148)
+    } else if script.is_p2sh() {
+        // P2SH-wrapped P2WPKH
+        Some(91)
+    } else {
+        None
+    }
+}
+
+fn output_vsize(output: &TxOut) -> u64 {
+    9 + output.script_pubkey.len() as u64
+}
+
+/// Build an unsigned peg-in paying `request.amount` to the federation
+///
+/// Coins are selected largest first. Change below the dust limit goes to fees.
+pub fn build_peg_in(federation: &BtcAddress, request: &PegInRequest, minimum: Amount) -> Result<Psbt, RskError> {
+    if request.amount < minimum {
+        return Err(RskError::InvalidAmount(format!(
+            "Peg-in of {} is below the bridge minimum of {}", request.amount, minimum
+        )));
+    }
+
+    let mut outputs = vec![TxOut { value: request.amount, script_pubkey: federation.script_pubkey() }];
+    match &request.rsk_recipient {
+        Some(recipient) => {
+            let data = peg_in_data(recipient, request.refund_address.as_ref())?;
+            let push = <&PushBytes>::try_from(data.as_slice())
+                .map_err(|e| RskError::BridgeError(format!("Destination data: {}", e)))?;
+            outputs.push(TxOut { value: Amount::ZERO, script_pubkey: ScriptBuf::new_op_return(push) });
+        },
+        None => {
+            if request.refund_address.is_some() {
+                return Err(RskError::BridgeError("A refund address requires destination data".to_string()));
+            }
+            if let Some((outpoint, _)) = request.utxos.iter().find(|(outpoint, utxo)| !request.reveals_key(outpoint, utxo)) {
+                return Err(RskError::BridgeError(format!(
+                    "Without destination data every input must be P2PKH or P2SH-P2WPKH, {} is not", outpoint
+                )));
+            }
+        },
+    }
+
+    let mut utxos = request.utxos.clone();
+    utxos.sort_by_key(|(_, utxo)| std::cmp::Reverse(utxo.value));
+
+    let target = request.amount.to_sat();
+    let mut vsize = TX_OVERHEAD_VSIZE + outputs.iter().map(output_vsize).sum::<u64>();
+    let mut selected = Vec::new();
+    let mut total = 0u64;
+    for (outpoint, utxo) in utxos {
+        vsize += input_vsize(&utxo.script_pubkey).ok_or_else(|| {
+            RskError::TransactionError(format!("Cannot estimate the size of an input spending {}", utxo.script_pubkey))
+        })?;
+        total += utxo.value.to_sat();
+        selected.push((outpoint, utxo));
+        if total >= target + vsize * request.fee_rate {
+            break;
+        }
+    }
+
+    let fee = vsize * request.fee_rate;
+    if total < target + fee {
+        return Err(RskError::TransactionError(format!(
+            "Insufficient funds: {} sats available, {} needed", total, target + fee
+        )));
+    }
+
+    let change_output = TxOut { value: Amount::ZERO, script_pubkey: request.change_script.clone() };
+    let fee_with_change = (vsize + output_vsize(&change_output)) * request.fee_rate;
+    if let Some(change) = total.checked_sub(target + fee_with_change).filter(|change| *change >= DUST_LIMIT) {
+        outputs.push(TxOut { value: Amount::from_sat(change), ..change_output });
+    }
+
+    let tx = Transaction {
+        version: transaction::Version::TWO,
+        lock_time: absolute::LockTime::ZERO,
+        input: selected.iter()
+            .map(|(outpoint, _)| TxIn {
+                previous_output: *outpoint,
+                script_sig: ScriptBuf::new(),
+                sequence: Sequence::ENABLE_RBF_NO_LOCKTIME,
+                witness: Witness::new(),
+            })
+            .collect(),
+        output: outputs,
+    };
+
+    let mut psbt = Psbt::from_unsigned_tx(tx)
+        .map_err(|e| RskError::TransactionError(format!("PSBT: {}", e)))?;
+    for (input, (outpoint, utxo)) in psbt.inputs.iter_mut().zip(selected) {
+        // Legacy inputs need the full previous transaction, which the caller supplies
+        if !utxo.script_pubkey.is_p2pkh() {
+            input.witness_utxo = Some(utxo);
+        }
+        input.redeem_script = request.redeem_scripts.get(&outpoint).cloned();
+    }
+    Ok(psbt)
+}
+
+/// Public key revealed by a signed P2PKH or P2SH-P2WPKH input
+pub fn sender_public_key(input: &TxIn) -> Option<PublicKey> {
+    let bytes = if input.witness.len() == 2 {
+        input.witness.nth(1)?.to_vec()
+    } else {
+        input.script_sig.instructions()
+            .filter_map(|instruction| match instruction {
+                Ok(Instruction::PushBytes(bytes)) => Some(bytes.as_bytes().to_vec()),
+                _ => None,
+            })
+            .last()?
+    };
+    PublicKey::from_slice(&bytes).ok()
+}
+
+/// ABI-encode a single `string` argument
+pub fn encode_string(value: &str) -> Vec<u8> {
+    let mut data = vec![0u8; 64];
+    data[31] = 0x20;
+    data[56..].copy_from_slice(&(value.len() as u64).to_be_bytes());
+    data.extend_from_slice(value.as_bytes());
+    data.resize(64 + value.len().div_ceil(32) * 32, 0);
+    data
+}
+
+/// Decode a single ABI-encoded `string` return value
+pub fn decode_string(data: &[u8]) -> Result<String, RskError> {
+    let word = |offset: usize| -> Result<usize, RskError> {
+        let word = data.get(offset..offset + 32)
+            .filter(|word| word[..24].iter().all(|b| *b == 0))
+            .ok_or_else(|| RskError::BridgeError("Malformed string return value".to_string()))?;
+        let mut bytes = [0u8; 8];
+        bytes.copy_from_slice(&word[24..]);
+        usize::try_from(u64::from_be_bytes(bytes))
+            .map_err(|_| RskError::BridgeError("String offset out of range".to_string()))
+    };
+
+    let offset = word(0)?;
+    let length = word(offset)?;
+    let bytes = data.get(offset + 32..offset + 32 + length)
+        .ok_or_else(|| RskError::BridgeError("Truncated string return value".to_string()))?;
+    String::from_utf8(bytes.to_vec())
+        .map_err(|e| RskError::BridgeError(format!("String return value is not UTF-8: {}", e)))
+}
+
+/// Decode a `bool` return value
+pub fn decode_bool(data: &[u8]) -> Result<bool, RskError> {
+    match data {
+        [zeros @ .., last] if data.len() == 32 && zeros.iter().all(|b| *b == 0) && *last <= 1 => Ok(*last == 1),
+        _ => Err(RskError::BridgeError("Malformed bool return value".to_string())),
+    }
+}
+
+/// Decode a `uint256` return value that fits in 64 bits
+pub fn decode_u64(data: &[u8]) -> Result<u64, RskError> {
+    match data.split_at_checked(24) {
+        Some((high, low)) if data.len() == 32 && high.iter().all(|b| *b == 0) => {
+            Ok(u64::from_be_bytes(low.try_into().expect("8 bytes")))
+        },
+        _ => Err(RskError::BridgeError("Malformed uint256 return value".to_string())),
+    }
+}
+
+/// Event topic of a signature
+pub fn event_topic(signature: &str) -> H256 {
+    keccak256(signature.as_bytes())
+}
+
+fn txid_from_word(word: &H256) -> Txid {
+    let mut bytes = *word;
+    bytes.reverse();
+    Txid::from_byte_array(bytes)
+}
+
+fn txid_to_word(txid: &Txid) -> H256 {
+    let mut word = txid.to_byte_array();
+    word.reverse();
+    word
+}
+
+/// Outcome of a release request, read from its receipt logs
+///
+/// Returns the rejection reason if the bridge refused the request.
+pub fn release_rejection(logs: &[EvmLog], bridge: &Address) -> Option<String> {
+    let topic = event_topic(RELEASE_REQUEST_REJECTED_EVENT);
+    let log = logs.iter().find(|log| log.address == *bridge && log.topics.first() == Some(&topic))?;
+
+    let reason = match log.data.get(63) {
+        Some(1) => "amount is below the peg-out minimum".to_string(),
+        Some(2) => "contracts cannot request a peg-out".to_string(),
+        Some(3) => "fees exceed the amount".to_string(),
+        other => format!("reason code {:?}", other),
+    };
+    Some(reason)
+}
+
+/// The PowPeg bridge contract
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BridgeContract {
+    /// Contract address
+    pub address: Address,
+}
+
+impl BridgeContract {
+    /// Current federation address peg-ins are paid to
+    pub async fn federation_address(&self, rpc: &EvmRpcClient, network: Network) -> Result<BtcAddress, RskError> {
+        let result = rpc.call(&self.address, &selector(FEDERATION_ADDRESS_SIGNATURE)).await?;
+        let address = decode_string(&result)?;
+        BtcAddress::from_str(&address)
+            .and_then(|address| address.require_network(network))
+            .map_err(|e| RskError::BridgeError(format!("Invalid federation address {}: {}", address, e)))
+    }
+
+    /// Whether the bridge has registered a peg-in transaction
+    pub async fn is_processed(&self, rpc: &EvmRpcClient, txid: &Txid) -> Result<bool, RskError> {
+        let mut data = selector(TX_PROCESSED_SIGNATURE).to_vec();
+        data.extend(encode_string(&txid.to_string()));
+        decode_bool(&rpc.call(&self.address, &data).await?)
+    }
+
+    /// Bitcoin fees the federation deducts from each peg-out in its next release
+    pub async fn estimated_peg_out_fees(&self, rpc: &EvmRpcClient) -> Result<Amount, RskError> {
+        let result = rpc.call(&self.address, &selector(PEG_OUT_FEES_SIGNATURE)).await?;
+        decode_u64(&result).map(Amount::from_sat)
+    }
+
+    /// Bitcoin transaction releasing the peg-out requested in `rsk_tx_hash`
+    pub async fn release_transaction(&self, rpc: &EvmRpcClient, rsk_tx_hash: &H256, from_block: u64) -> Result<Option<Txid>, RskError> {
+        let topics = [Some(event_topic(RELEASE_REQUESTED_EVENT)), Some(*rsk_tx_hash)];
+        let logs = rpc.logs(&self.address, &topics, from_block).await?;
+        Ok(logs.first().and_then(|log| log.topics.get(2)).map(txid_from_word))
+    }
+
+    /// Whether the federation reported the release transaction as confirmed
+    pub async fn is_release_confirmed(&self, rpc: &EvmRpcClient, txid: &Txid, from_block: u64) -> Result<bool, RskError> {
+        let topics = [Some(event_topic(PEGOUT_CONFIRMED_EVENT)), Some(txid_to_word(txid))];
+        Ok(!rpc.logs(&self.address, &topics, from_block).await?.is_empty())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitcoin::{PubkeyHash, ScriptHash, WPubkeyHash};
+
+    fn utxo(n: u8, sats: u64, script_pubkey: ScriptBuf) -> (OutPoint, TxOut) {
+        (
+            OutPoint { txid: Txid::from_byte_array([n; 32]), vout: 0 },
+            TxOut { value: Amount::from_sat(sats), script_pubkey },
+        )
+    }
+
+    #[test]
+    fn test_peg_in_construction() {
+        let federation = BtcAddress::p2sh_from_hash(ScriptHash::from_byte_array([0xfe; 20]), Network::Regtest);
+        let refund = BtcAddress::p2pkh(PubkeyHash::from_byte_array([0x0d; 20]), Network::Regtest);
+        let wpkh = ScriptBuf::new_p2wpkh(&WPubkeyHash::from_byte_array([1; 20]));
+        let request = PegInRequest {
+            utxos: vec![utxo(1, 300_000, wpkh.clone()), utxo(2, 900_000, wpkh.clone())],
+            amount: Amount::from_sat(1_000_000),
+            fee_rate: 10,
+            change_script: wpkh.clone(),
+            rsk_recipient: Some([0xab; 20]),
+            refund_address: Some(refund),
+            redeem_scripts: BTreeMap::new(),
+        };
+
+        let psbt = build_peg_in(&federation, &request, Amount::from_sat(500_000)).unwrap();
+        let tx = &psbt.unsigned_tx;
+        assert_eq!(tx.input.len(), 2);
+        assert_eq!(tx.output[0], TxOut { value: Amount::from_sat(1_000_000), script_pubkey: federation.script_pubkey() });
+
+        let mut data = b"RSKT\x01".to_vec();
+        data.extend([0xab; 20]);
+        data.push(1);
+        data.extend([0x0d; 20]);
+        assert_eq!(&tx.output[1].script_pubkey.as_bytes()[2..], &data[..]);
+
+        // Two P2WPKH inputs and three outputs at 10 sat/vB
+        let vsize = 11 + 2 * 68 + (9 + 23) + (9 + 2 + data.len() as u64) + (9 + 22);
+        assert_eq!(tx.output[2].value.to_sat(), 1_200_000 - 1_000_000 - vsize * 10);
+        assert!(psbt.inputs.iter().all(|input| input.witness_utxo.is_some()));
+
+        // Without destination data, native SegWit inputs reveal no usable key
+        let implicit = PegInRequest { rsk_recipient: None, refund_address: None, ..request.clone() };
+        assert!(build_peg_in(&federation, &implicit, Amount::from_sat(500_000)).is_err());
+
+        // Nor do P2SH coins unless they are shown to wrap P2WPKH
+        let wrapped = ScriptBuf::new_p2sh(&wpkh.script_hash());
+        let multisig = ScriptBuf::new_p2sh(&ScriptHash::from_byte_array([0x77; 20]));
+        let mut implicit = PegInRequest {
+            utxos: vec![utxo(1, 1_500_000, wrapped), utxo(2, 1_500_000, multisig)],
+            ..implicit
+        };
+        assert!(build_peg_in(&federation, &implicit, Amount::from_sat(500_000)).is_err());
+        implicit.redeem_scripts.insert(implicit.utxos[0].0, wpkh.clone());
+        assert!(build_peg_in(&federation, &implicit, Amount::from_sat(500_000)).is_err());
+        implicit.utxos.pop();
+        let psbt = build_peg_in(&federation, &implicit, Amount::from_sat(500_000)).unwrap();
+        assert_eq!(psbt.inputs[0].redeem_script, Some(wpkh.clone()));
+        assert_eq!(psbt.unsigned_tx.output.len(), 2);
+
+        let small = PegInRequest { amount: Amount::from_sat(100_000), ..request.clone() };
+        assert!(matches!(build_peg_in(&federation, &small, Amount::from_sat(500_000)), Err(RskError::InvalidAmount(_))));
+
+        let broke = PegInRequest { amount: Amount::from_sat(1_199_000), ..request };
+        assert!(matches!(build_peg_in(&federation, &broke, Amount::from_sat(500_000)), Err(RskError::TransactionError(_))));
+    }
+
+    #[test]
+    fn test_abi_strings() {
+        let encoded = encode_string("2NBf1uTGzqMrbSSibDQ7L8RqgSfdFYk8kLb");
+        assert_eq!(encoded.len(), 128);
+        assert_eq!(decode_string(&encoded).unwrap(), "2NBf1uTGzqMrbSSibDQ7L8RqgSfdFYk8kLb");
+        assert!(decode_string(&encoded[..70]).is_err());
+
+        let mut word = [0u8; 32];
+        word[31] = 1;
+        assert!(decode_bool(&word).unwrap());
+        word[0] = 1;
+        assert!(decode_bool(&word).is_err());
+    }
+}
@@ -16,29 +16,45 @@
 
 use std::sync::{Arc, Mutex};
 use std::collections::HashMap;
+use std::str::FromStr;
 use std::time::{Duration, Instant};
 
+use bitcoin::{Address as BtcAddress, Amount, BlockHash, Network, Psbt, Transaction as BtcTransaction, Txid};
+
 // Internal imports
-use crate::bitcoin::types::{BitcoinAddress, Transaction as BtcTransaction};
 use crate::core::performance::Metrics;
-use crate::security::validation::ValidationResult;
+use crate::layer2::bob::evm::{
+    format_address, format_h256, parse_address, parse_h256, public_key_address, EvmRpcClient,
+    HttpTransport, JsonRpcTransport, LegacyTransaction, LocalSigner,
+};
+use crate::layer2::bob::BobError;
 use crate::{
     AnyaResult,
     layer2::{
         Layer2Protocol,
         ProtocolState,
-        TransactionStatus,
+        TransactionStatus as Layer2TransactionStatus,
         AssetParams,
         AssetTransfer,
         TransferResult,
         Proof,
         VerificationResult,
         ValidationResult,
+        Layer2Type,
+        MoveEstimate,
+        MoveRefund,
+        MoveRequest,
+        OnchainWallet,
+        Venue,
+        proof::{ProofError, ProofVerifier, WithProofVerifier},
     },
 };
 use async_trait::async_trait;
 use tracing::{info, warn, error};
 
+use bridge::BridgeContract;
+pub use bridge::PegInRequest;
+
 /// Configuration for the RSK integration
 #[derive(Clone, Debug)]
 pub struct RskConfig {
@@ -46,8 +62,8 @@ pub struct RskConfig {
     pub node_url: String,
     /// Chain ID for the RSK network
     pub chain_id: u64,
-    /// Federation address
-    pub federation_address: String,
+    /// Address of the PowPeg bridge contract
+    pub bridge_address: String,
     /// Timeout for node operations in milliseconds
     pub timeout_ms: u64,
     /// Maximum number of retry attempts
@@ -56,6 +72,18 @@ pub struct RskConfig {
     pub gas_price: u64,
     /// Gas limit for transactions
     pub gas_limit: u64,
+    /// Bitcoin network the bridge is pegged to
+    pub btc_network: Network,
+    /// Bitcoin confirmations the bridge requires before registering a peg-in
+    pub peg_in_confirmations: u32,
+    /// Smallest peg-in the bridge accepts, in satoshis
+    pub min_peg_in_sats: u64,
+    /// Smallest peg-out the bridge accepts, in satoshis
+    pub min_peg_out_sats: u64,
+    /// Interval between receipt polls in milliseconds
+    pub receipt_poll_interval_ms: u64,
+    /// How long to wait for a receipt in milliseconds
+    pub receipt_timeout_ms: u64,
 }
 
 impl Default for RskConfig {
@@ -63,11 +91,17 @@ fn default() -> Self {
         Self {
             node_url: "https://public-node.rsk.co".to_string(),
             chain_id: 30,
-            federation_address: "0x0000000000000000000000000000000001000006".to_string(),
+            bridge_address: "0x0000000000000000000000000000000001000006".to_string(),
             timeout_ms: 30000,
             max_retries: 3,
             gas_price: 40_000_000_000, // 40 gwei
             gas_limit: 6_800_000,
+            btc_network: Network::Bitcoin,
+            peg_in_confirmations: 100,
+            min_peg_in_sats: 500_000,
+            min_peg_out_sats: 400_000,
+            receipt_poll_interval_ms: 1000,
+            receipt_timeout_ms: 120_000,
         }
     }
 }
@@ -84,13 +118,22 @@ pub struct RskClient {
     smart_contract_caller: SmartContractCaller,
     /// Transaction manager
     transaction_manager: TransactionManager,
+    /// JSON-RPC client of the node
+    rpc: Arc<EvmRpcClient>,
 }
 
 impl RskClient {
     /// Create a new RSK client with the provided configuration
     pub fn new(config: RskConfig) -> Self {
+        let transport = HttpTransport::new(&config.node_url, Duration::from_millis(config.timeout_ms));
+        Self::with_transport(config, Arc::new(transport))
+    }
+
+    /// Create a client over a custom JSON-RPC transport
+    pub fn with_transport(config: RskConfig, transport: Arc<dyn JsonRpcTransport>) -> Self {
+        let rpc = Arc::new(EvmRpcClient::new(transport, config.max_retries));
         let node_connector = NodeConnector::new(&config);
-        let bridge_interface = BridgeInterface::new(&config);
+        let bridge_interface = BridgeInterface::new(&config, rpc.clone());
         let smart_contract_caller = SmartContractCaller::new(&config);
         let transaction_manager = TransactionManager::new(&config);
         
@@ -100,23 +143,70 @@ pub fn new(config: RskConfig) -> Self {
             bridge_interface,
             smart_contract_caller,
             transaction_manager,
+            rpc,
         }
     }
     
+    /// Sign peg-outs with a local key
+    pub fn with_signer(mut self, signer: LocalSigner) -> Self {
+        self.bridge_interface.signer = Some(signer);
+        self
+    }
+
+    /// Count peg-in confirmations on a locally tracked Bitcoin header chain
+    pub fn with_bitcoin_headers(mut self, headers: ProofVerifier) -> Self {
+        self.bridge_interface.bitcoin_headers = headers;
+        self
+    }
+
     /// Check the health of the RSK node connection
     pub async fn check_health(&self) -> Result<bool, RskError> {
         self.node_connector.check_connection().await
     }
     
     /// Perform a peg-in operation (lock BTC to get RBTC)
+    ///
+    /// Returns the unsigned peg-in transaction for the wallet to sign and broadcast.
     pub async fn peg_in(
         &self,
-        btc_address: &str,
-        amount: f64,
+        request: PegInRequest,
+    ) -> Result<(PegInInfo, Psbt), RskError> {
+        self.bridge_interface.peg_in(request).await
+    }
+
+    /// Bitcoin fee of the peg-in `request` would build
+    pub async fn quote_peg_in(&self, request: &PegInRequest) -> Result<Amount, RskError> {
+        self.bridge_interface.quote_peg_in(request).await
+    }
+
+    /// Record the signed peg-in transaction once broadcast
+    pub fn record_peg_in_transaction(
+        &self,
+        peg_in_id: &str,
+        tx: &BtcTransaction,
     ) -> Result<PegInInfo, RskError> {
-        self.bridge_interface.peg_in(btc_address, amount).await
+        self.bridge_interface.record_peg_in_transaction(peg_in_id, tx)
     }
-    
+
+    /// Record the inclusion proof of a peg-in transaction
+    pub fn observe_peg_in_proof(
+        &self,
+        peg_in_id: &str,
+        proof: &Proof,
+    ) -> Result<PegInInfo, RskError> {
+        self.bridge_interface.observe_peg_in_proof(peg_in_id, proof)
+    }
+
+    /// Update a peg-in's confirmations and registration
+    pub async fn refresh_peg_in(&self, peg_in_id: &str) -> Result<PegInInfo, RskError> {
+        self.bridge_interface.refresh_peg_in(peg_in_id).await
+    }
+
+    /// Peg-in recorded with Bitcoin transaction `txid`
+    pub fn check_peg_in(&self, txid: &Txid) -> Result<PegInInfo, RskError> {
+        self.bridge_interface.check_peg_in(txid)
+    }
+
     /// Perform a peg-out operation (release BTC from RBTC)
     pub async fn peg_out(
         &self,
@@ -125,6 +215,40 @@ pub async fn peg_out(
     ) -> Result<PegOutInfo, RskError> {
         self.bridge_interface.peg_out(btc_address, amount).await
     }
+
+    /// Update a peg-out from the bridge's release events
+    pub async fn refresh_peg_out(&self, peg_out_id: &str) -> Result<PegOutInfo, RskError> {
+        self.bridge_interface.refresh_peg_out(peg_out_id).await
+    }
+
+    /// Fees of a peg-out: gas of the release request and the federation's Bitcoin fees
+    pub async fn peg_out_fee(&self) -> Result<Amount, RskError> {
+        let gas = (PEG_OUT_GAS_LIMIT as u128 * self.config.gas_price as u128).div_ceil(WEI_PER_SATOSHI) as u64;
+        let release = self.bridge_interface.bridge()?.estimated_peg_out_fees(&self.rpc).await?;
+        Ok(Amount::from_sat(gas) + release)
+    }
+
+    /// Mean interval of recent RSK blocks
+    pub async fn block_interval(&self) -> Result<Duration, RskError> {
+        let latest = self.rpc.block_number().await?;
+        let earlier = latest.saturating_sub(BLOCK_INTERVAL_SAMPLE);
+        if earlier == latest {
+            return Err(RskError::ConnectionError("No blocks to measure the block interval over".to_string()));
+        }
+        let elapsed = self.rpc.block_timestamp(latest).await?
+            .saturating_sub(self.rpc.block_timestamp(earlier).await?);
+        Ok(Duration::from_secs(elapsed) / (latest - earlier) as u32)
+    }
+
+    /// Get peg-in information
+    pub async fn get_peg_in_info(&self, peg_in_id: &str) -> Result<PegInInfo, RskError> {
+        self.bridge_interface.get_peg_in_info(peg_in_id).await
+    }
+
+    /// Get peg-out information
+    pub async fn get_peg_out_info(&self, peg_out_id: &str) -> Result<PegOutInfo, RskError> {
+        self.bridge_interface.get_peg_out_info(peg_out_id).await
+    }
     
     /// Call a smart contract method
     pub async fn call_contract(
@@ -240,68 +364,302 @@ pub async fn get_latest_block_number(&self) -> Result<u64, RskError> {
     }
 }
 
+/// Gas for a value transfer to the bridge, which runs native code on receipt
+const PEG_OUT_GAS_LIMIT: u64 = 100_000;
+
+/// RBTC has 18 decimals, BTC has 8
+const WEI_PER_SATOSHI: u128 = 10_000_000_000;
+
+/// RSK blocks the bridge waits before releasing a peg-out
+const PEG_OUT_RSK_CONFIRMATIONS: u32 = 4_000;
+
+/// RSK blocks the block interval is measured over
+const BLOCK_INTERVAL_SAMPLE: u64 = 100;
+
 /// Bridge interface component
 pub struct BridgeInterface {
     config: RskConfig,
+    rpc: Arc<EvmRpcClient>,
+    signer: Option<LocalSigner>,
+    bitcoin_headers: ProofVerifier,
     peg_ins: Arc<Mutex<HashMap<String, PegInInfo>>>,
+    /// Unsigned peg-in transactions, to check signed ones against
+    peg_in_templates: Arc<Mutex<HashMap<String, BtcTransaction>>>,
     peg_outs: Arc<Mutex<HashMap<String, PegOutInfo>>>,
 }
 
 impl BridgeInterface {
     /// Create a new bridge interface
-    pub fn new(config: &RskConfig) -> Self {
+    pub fn new(config: &RskConfig, rpc: Arc<EvmRpcClient>) -> Self {
         Self {
             config: config.clone(),
+            rpc,
+            signer: None,
+            bitcoin_headers: ProofVerifier::default(),
             peg_ins: Arc::new(Mutex::new(HashMap::new())),
+            peg_in_templates: Arc::new(Mutex::new(HashMap::new())),
             peg_outs: Arc::new(Mutex::new(HashMap::new())),
         }
     }
-    
-    /// Perform a peg-in operation
-    pub async fn peg_in(&self, btc_address: &str, amount: f64) -> Result<PegInInfo, RskError> {
-        // Implementation would perform peg-in
-        // For now, return a mock peg-in info
+
+    fn bridge(&self) -> Result<BridgeContract, RskError> {
+        Ok(BridgeContract { address: parse_address(&self.config.bridge_address)? })
+    }
+
+    /// Build a peg-in to the current federation address
+    pub async fn peg_in(&self, request: PegInRequest) -> Result<(PegInInfo, Psbt), RskError> {
+        let network = self.config.btc_network;
+        let federation = self.bridge()?.federation_address(&self.rpc, network).await?;
+        let psbt = bridge::build_peg_in(&federation, &request, Amount::from_sat(self.config.min_peg_in_sats))?;
+
+        let sender = psbt.unsigned_tx.input.first()
+            .and_then(|input| request.utxos.iter().find(|(outpoint, _)| *outpoint == input.previous_output))
+            .and_then(|(_, utxo)| BtcAddress::from_script(&utxo.script_pubkey, network).ok())
+            .map(|address| address.to_string())
+            .unwrap_or_default();
+
         let peg_in_id = format!("peg_in:{}", uuid::Uuid::new_v4());
-        
         let peg_in = PegInInfo {
             id: peg_in_id.clone(),
-            btc_address: btc_address.to_string(),
-            amount,
-            rsk_recipient: "0x1234567890123456789012345678901234567890".to_string(),
+            btc_address: sender,
+            amount: request.amount.to_btc(),
+            rsk_recipient: request.rsk_recipient.as_ref().map(format_address).unwrap_or_default(),
+            federation_address: federation.to_string(),
             status: PegStatus::Pending,
             created_at: chrono::Utc::now(),
             confirmed_at: None,
             btc_tx_hash: None,
+            btc_block_hash: None,
+            confirmations: 0,
+            required_confirmations: self.config.peg_in_confirmations,
             rsk_tx_hash: None,
         };
-        
+        info!("Built peg-in {} of {} to {}", peg_in_id, request.amount, federation);
+
+        self.peg_in_templates.lock().unwrap().insert(peg_in_id.clone(), psbt.unsigned_tx.clone());
+        self.peg_ins.lock().unwrap().insert(peg_in_id, peg_in.clone());
+        Ok((peg_in, psbt))
+    }
+
+    /// Bitcoin fee of the peg-in `request` would build, without recording it
+    pub async fn quote_peg_in(&self, request: &PegInRequest) -> Result<Amount, RskError> {
+        let federation = self.bridge()?.federation_address(&self.rpc, self.config.btc_network).await?;
+        let psbt = bridge::build_peg_in(&federation, request, Amount::from_sat(self.config.min_peg_in_sats))?;
+        let inputs = psbt.unsigned_tx.input.iter()
+            .map(|input| request.utxos.iter().find(|(outpoint, _)| *outpoint == input.previous_output))
+            .map(|utxo| utxo.map(|(_, utxo)| utxo.value))
+            .sum::<Option<Amount>>()
+            .ok_or_else(|| RskError::TransactionError("Peg-in spends an unknown coin".to_string()))?;
+        let outputs = psbt.unsigned_tx.output.iter().map(|output| output.value).sum::<Amount>();
+        Ok(inputs - outputs)
+    }
+
+    /// Record the signed peg-in transaction once broadcast
+    ///
+    /// It must spend the same coins to the same outputs as the unsigned one.
+    /// Without destination data, the credited account is derived from the key
+    /// the first input reveals.
+    pub fn record_peg_in_transaction(&self, peg_in_id: &str, tx: &BtcTransaction) -> Result<PegInInfo, RskError> {
+        let template = self.peg_in_templates.lock().unwrap().get(peg_in_id).cloned()
+            .ok_or_else(|| RskError::PegOperationNotFound(peg_in_id.to_string()))?;
+        let same_inputs = tx.input.iter().map(|input| input.previous_output)
+            .eq(template.input.iter().map(|input| input.previous_output));
+        if !same_inputs || tx.output != template.output {
+            return Err(RskError::TransactionError("Signed transaction does not match the peg-in".to_string()));
+        }
+
         let mut peg_ins = self.peg_ins.lock().unwrap();
-        peg_ins.insert(peg_in_id, peg_in.clone());
-        
+        let peg_in = peg_ins.get_mut(peg_in_id)
+            .ok_or_else(|| RskError::PegOperationNotFound(peg_in_id.to_string()))?;
+        if peg_in.rsk_recipient.is_empty() {
+            if let Some(key) = tx.input.first().and_then(bridge::sender_public_key) {
+                peg_in.rsk_recipient = format_address(&public_key_address(&key));
+            }
+        }
+        peg_in.btc_tx_hash = Some(tx.compute_txid().to_string());
+        Ok(peg_in.clone())
+    }
+
+    /// Record the inclusion proof of a peg-in transaction
+    pub fn observe_peg_in_proof(&self, peg_in_id: &str, proof: &Proof) -> Result<PegInInfo, RskError> {
+        let inclusion = self.bitcoin_headers.verify(proof)
+            .map_err(|e| RskError::BridgeError(format!("Peg-in proof: {}", e)))?;
+
+        let mut peg_ins = self.peg_ins.lock().unwrap();
+        let peg_in = peg_ins.get_mut(peg_in_id)
+            .ok_or_else(|| RskError::PegOperationNotFound(peg_in_id.to_string()))?;
+        if peg_in.btc_tx_hash.as_deref() != Some(inclusion.txid.to_string().as_str()) {
+            return Err(RskError::BridgeError(format!("Proof is for {}, not the peg-in transaction", inclusion.txid)));
+        }
+
+        peg_in.btc_block_hash = Some(inclusion.block_hash.to_string());
+        peg_in.confirmations = inclusion.confirmations;
+        update_peg_in_status(peg_in);
+        Ok(peg_in.clone())
+    }
+
+    /// Peg-in recorded with Bitcoin transaction `txid`
+    ///
+    /// The transaction must be one built by [`BridgeInterface::peg_in`], paying
+    /// the federation address it was built for.
+    pub fn check_peg_in(&self, txid: &Txid) -> Result<PegInInfo, RskError> {
+        let txid = txid.to_string();
+        let peg_in = self.peg_ins.lock().unwrap().values()
+            .find(|peg_in| peg_in.btc_tx_hash.as_deref() == Some(txid.as_str()))
+            .cloned()
+            .ok_or_else(|| RskError::PegOperationNotFound(txid.clone()))?;
+        let template = self.peg_in_templates.lock().unwrap().get(&peg_in.id).cloned()
+            .ok_or_else(|| RskError::PegOperationNotFound(peg_in.id.clone()))?;
+
+        let federation = BtcAddress::from_str(&peg_in.federation_address)
+            .and_then(|address| address.require_network(self.config.btc_network))
+            .map_err(|e| RskError::BridgeError(format!("Invalid federation address {}: {}", peg_in.federation_address, e)))?;
+        if !template.output.iter().any(|output| output.script_pubkey == federation.script_pubkey()) {
+            return Err(RskError::BridgeError(format!("Peg-in {} does not pay the federation", txid)));
+        }
         Ok(peg_in)
     }
-    
-    /// Perform a peg-out operation
+
+    /// Update a peg-in's confirmations and check whether the bridge registered it
+    ///
+    /// A peg-in whose block left the best chain goes back to pending until a
+    /// new proof is observed.
+    pub async fn refresh_peg_in(&self, peg_in_id: &str) -> Result<PegInInfo, RskError> {
+        let peg_in = self.get_peg_in_info(peg_in_id).await?;
+        let txid = match (&peg_in.btc_tx_hash, peg_in.status) {
+            (None, _) | (_, PegStatus::Confirmed) => return Ok(peg_in),
+            (Some(txid), _) => Txid::from_str(txid)
+                .map_err(|e| RskError::BridgeError(format!("Invalid peg-in txid {}: {}", txid, e)))?,
+        };
+        let registered = self.bridge()?.is_processed(&self.rpc, &txid).await?;
+
+        let mut peg_ins = self.peg_ins.lock().unwrap();
+        let peg_in = peg_ins.get_mut(peg_in_id)
+            .ok_or_else(|| RskError::PegOperationNotFound(peg_in_id.to_string()))?;
+
+        if let Some(block_hash) = peg_in.btc_block_hash.as_deref().and_then(|hash| BlockHash::from_str(hash).ok()) {
+            match self.bitcoin_headers.confirmations(&block_hash) {
+                Ok(confirmations) => peg_in.confirmations = confirmations,
+                Err(ProofError::NotInBestChain(_)) => {
+                    warn!("Peg-in {} block {} left the best chain", peg_in_id, block_hash);
+                    peg_in.btc_block_hash = None;
+                    peg_in.confirmations = 0;
+                },
+                Err(e) => return Err(RskError::BridgeError(format!("Peg-in confirmations: {}", e))),
+            }
+        }
+
+        if registered {
+            peg_in.status = PegStatus::Confirmed;
+            peg_in.confirmed_at = Some(chrono::Utc::now());
+        } else {
+            update_peg_in_status(peg_in);
+        }
+        Ok(peg_in.clone())
+    }
+
+    /// Request a peg-out by sending RBTC to the bridge
+    ///
+    /// The bridge releases BTC to the P2PKH address of the sender's key, so
+    /// `btc_address` must be that address.
     pub async fn peg_out(&self, btc_address: &str, amount: f64) -> Result<PegOutInfo, RskError> {
-        // Implementation would perform peg-out
-        // For now, return a mock peg-out info
+        let signer = self.signer.as_ref()
+            .ok_or_else(|| RskError::ConfigError("No signing key configured".to_string()))?;
+        let bridge = self.bridge()?;
+
+        let amount = Amount::from_btc(amount)
+            .map_err(|e| RskError::InvalidAmount(format!("{}: {}", amount, e)))?;
+        let minimum = Amount::from_sat(self.config.min_peg_out_sats);
+        if amount < minimum {
+            return Err(RskError::InvalidAmount(format!(
+                "Peg-out of {} is below the bridge minimum of {}", amount, minimum
+            )));
+        }
+
+        let from = signer.address();
+        let destination = BtcAddress::p2pkh(
+            bitcoin::PublicKey::new(signer.public_key()).pubkey_hash(),
+            self.config.btc_network,
+        );
+        if destination.to_string() != btc_address {
+            return Err(RskError::BridgeError(format!(
+                "Peg-outs from {} are released to {}, not {}", format_address(&from), destination, btc_address
+            )));
+        }
+
+        let nonce = self.rpc.transaction_count(&from).await?;
+        let signed = LegacyTransaction {
+            chain_id: self.config.chain_id,
+            nonce,
+            gas_price: self.config.gas_price as u128,
+            gas_limit: PEG_OUT_GAS_LIMIT,
+            to: Some(bridge.address),
+            value: amount.to_sat() as u128 * WEI_PER_SATOSHI,
+            data: Vec::new(),
+        }.sign(signer);
+
+        let hash = self.rpc.send_raw_transaction(&signed.raw).await?;
+        let receipt = self.rpc.wait_for_receipt(
+            &hash,
+            Duration::from_millis(self.config.receipt_poll_interval_ms),
+            Duration::from_millis(self.config.receipt_timeout_ms),
+        ).await?;
+
+        let failure_reason = if receipt.status {
+            bridge::release_rejection(&receipt.logs, &bridge.address)
+        } else {
+            Some("release request reverted".to_string())
+        };
+
         let peg_out_id = format!("peg_out:{}", uuid::Uuid::new_v4());
-        
         let peg_out = PegOutInfo {
             id: peg_out_id.clone(),
             btc_address: btc_address.to_string(),
-            amount,
-            rsk_sender: "0x1234567890123456789012345678901234567890".to_string(),
-            status: PegStatus::Pending,
+            amount: amount.to_btc(),
+            rsk_sender: format_address(&from),
+            status: if failure_reason.is_some() { PegStatus::Failed } else { PegStatus::Pending },
             created_at: chrono::Utc::now(),
             confirmed_at: None,
             btc_tx_hash: None,
-            rsk_tx_hash: None,
+            rsk_tx_hash: Some(format_h256(&hash)),
+            rsk_block_number: Some(receipt.block_number),
+            failure_reason,
         };
-        
-        let mut peg_outs = self.peg_outs.lock().unwrap();
-        peg_outs.insert(peg_out_id, peg_out.clone());
-        
+        match &peg_out.failure_reason {
+            Some(reason) => warn!("Bridge refused peg-out {}: {}", peg_out_id, reason),
+            None => info!("Requested peg-out {} of {} to {}", peg_out_id, amount, btc_address),
+        }
+
+        self.peg_outs.lock().unwrap().insert(peg_out_id, peg_out.clone());
+        Ok(peg_out)
+    }
+
+    /// Follow a peg-out through the federation's release
+    pub async fn refresh_peg_out(&self, peg_out_id: &str) -> Result<PegOutInfo, RskError> {
+        let mut peg_out = self.get_peg_out_info(peg_out_id).await?;
+        let (rsk_tx_hash, from_block) = match (&peg_out.rsk_tx_hash, peg_out.rsk_block_number, peg_out.status) {
+            (Some(hash), Some(block), PegStatus::Pending | PegStatus::Confirming) => (parse_h256(hash)?, block),
+            _ => return Ok(peg_out),
+        };
+        let bridge = self.bridge()?;
+
+        if peg_out.btc_tx_hash.is_none() {
+            if let Some(txid) = bridge.release_transaction(&self.rpc, &rsk_tx_hash, from_block).await? {
+                peg_out.btc_tx_hash = Some(txid.to_string());
+                peg_out.status = PegStatus::Confirming;
+            }
+        }
+
+        if let Some(txid) = &peg_out.btc_tx_hash {
+            let txid = Txid::from_str(txid)
+                .map_err(|e| RskError::BridgeError(format!("Invalid release txid {}: {}", txid, e)))?;
+            if bridge.is_release_confirmed(&self.rpc, &txid, from_block).await? {
+                peg_out.status = PegStatus::Confirmed;
+                peg_out.confirmed_at = Some(chrono::Utc::now());
+            }
+        }
+
+        self.peg_outs.lock().unwrap().insert(peg_out_id.to_string(), peg_out.clone());
         Ok(peg_out)
     }
     
@@ -328,6 +686,20 @@ pub async fn get_peg_out_info(&self, peg_out_id: &str) -> Result<PegOutInfo, Rsk
     }
 }
 
+/// Derive an unregistered peg-in's status from its confirmations
+fn update_peg_in_status(peg_in: &mut PegInInfo) {
+    if matches!(peg_in.status, PegStatus::Confirmed | PegStatus::Failed) {
+        return;
+    }
+    peg_in.status = if peg_in.btc_block_hash.is_none() {
+        PegStatus::Pending
+    } else if peg_in.confirmations >= peg_in.required_confirmations {
+        PegStatus::AwaitingRegistration
+    } else {
+        PegStatus::Confirming
+    };
+}
+
 /// Smart contract caller component
 pub struct SmartContractCaller {
     config: RskConfig,
@@ -479,8 +851,13 @@ pub async fn get_transaction(&self, tx_hash: &str) -> Result<TransactionInfo, Rs
 /// Peg status enumeration
 #[derive(Debug, Clone, Copy, PartialEq, Eq)]
 pub enum PegStatus {
-    /// Operation is pending
+    /// Operation is pending: the peg-in is not yet mined, or the federation
+    /// has not yet created the peg-out release
     Pending,
+    /// Waiting for Bitcoin confirmations of the peg-in or the peg-out release
+    Confirming,
+    /// Peg-in has enough confirmations and awaits registration in the bridge
+    AwaitingRegistration,
     /// Operation is confirmed
     Confirmed,
     /// Operation failed
@@ -516,8 +893,10 @@ pub struct PegInInfo {
     pub btc_address: String,
     /// Amount of BTC sent
     pub amount: f64,
-    /// RSK address to receive RBTC
+    /// RSK address to receive RBTC; empty until signed when derived from the sender's key
     pub rsk_recipient: String,
+    /// Federation address the BTC is locked in
+    pub federation_address: String,
     /// Status of the operation
     pub status: PegStatus,
     /// Creation timestamp
@@ -526,6 +905,12 @@ pub struct PegInInfo {
     pub confirmed_at: Option<chrono::DateTime<chrono::Utc>>,
     /// Bitcoin transaction hash (if available)
     pub btc_tx_hash: Option<String>,
+    /// Bitcoin block containing the peg-in (if known)
+    pub btc_block_hash: Option<String>,
+    /// Bitcoin confirmations of the peg-in
+    pub confirmations: u32,
+    /// Confirmations the bridge requires
+    pub required_confirmations: u32,
     /// RSK transaction hash (if available)
     pub rsk_tx_hash: Option<String>,
 }
@@ -547,10 +932,14 @@ pub struct PegOutInfo {
     pub created_at: chrono::DateTime<chrono::Utc>,
     /// Confirmation timestamp (if confirmed)
     pub confirmed_at: Option<chrono::DateTime<chrono::Utc>>,
-    /// Bitcoin transaction hash (if available)
+    /// Bitcoin release transaction hash (if available)
     pub btc_tx_hash: Option<String>,
-    /// RSK transaction hash (if available)
+    /// RSK transaction hash of the release request (if available)
     pub rsk_tx_hash: Option<String>,
+    /// RSK block containing the release request (if mined)
+    pub rsk_block_number: Option<u64>,
+    /// Why the bridge refused the release request (if it did)
+    pub failure_reason: Option<String>,
 }
 
 /// Smart contract information
@@ -692,6 +1081,23 @@ pub enum RskError {
     ConfigError(String),
 }
 
+impl From<BobError> for RskError {
+    fn from(error: BobError) -> Self {
+        match error {
+            BobError::ConnectionError(message) => RskError::ConnectionError(message),
+            BobError::TransactionError(message) => RskError::TransactionError(message),
+            BobError::ConfigError(message) => RskError::ConfigError(message),
+            error => RskError::BridgeError(error.to_string()),
+        }
+    }
+}
+
+impl From<RskError> for crate::AnyaError {
+    fn from(error: RskError) -> Self {
+        crate::AnyaError::System(error.to_string())
+    }
+}
+
 // Module exports
 pub mod bridge;
 pub mod contracts;
@@ -702,35 +1108,336 @@ pub enum RskError {
 #[cfg(test)]
 pub mod tests {
     use super::*;
-    
+    use super::bridge::{
+        encode_string, event_topic, FEDERATION_ADDRESS_SIGNATURE, PEGOUT_CONFIRMED_EVENT, PEG_OUT_FEES_SIGNATURE,
+        RELEASE_REQUESTED_EVENT, RELEASE_REQUEST_RECEIVED_EVENT, RELEASE_REQUEST_REJECTED_EVENT,
+        TX_PROCESSED_SIGNATURE,
+    };
+    use crate::layer2::bob::evm::stand_in::{ChainState, StandInRpc};
+    use crate::layer2::bob::evm::Address;
+    use crate::layer2::bob::relay::selector;
+    use crate::layer2::bob::EvmLog;
+    use crate::layer2::proof::{HeaderChain, MerkleBranch};
+    use bitcoin::block::{Header, Version as BlockVersion};
+    use bitcoin::hashes::Hash;
+    use bitcoin::{CompactTarget, OutPoint, ScriptBuf, TxMerkleNode, TxOut, WPubkeyHash, Witness};
+    use std::collections::BTreeMap;
+    use std::sync::atomic::{AtomicBool, Ordering};
+
+    fn bridge_address() -> Address {
+        parse_address(&RskConfig::default().bridge_address).unwrap()
+    }
+
+    fn config(rpc: &StandInRpc) -> RskConfig {
+        RskConfig {
+            node_url: rpc.url.clone(),
+            chain_id: 33,
+            max_retries: 0,
+            gas_price: 60_000_000,
+            btc_network: Network::Regtest,
+            peg_in_confirmations: 3,
+            receipt_poll_interval_ms: 10,
+            receipt_timeout_ms: 5_000,
+            ..RskConfig::default()
+        }
+    }
+
+    /// Regtest-difficulty header on top of `prev`
+    fn mine(prev: BlockHash, merkle_root: TxMerkleNode, time: u32) -> Header {
+        let mut header = Header {
+            version: BlockVersion::TWO,
+            prev_blockhash: prev,
+            merkle_root,
+            time,
+            bits: CompactTarget::from_consensus(0x207fffff),
+            nonce: 0,
+        };
+        while header.validate_pow(header.target()).is_err() {
+            header.nonce += 1;
+        }
+        header
+    }
+
+    fn set_processed(state: &mut ChainState, txid: &Txid, processed: bool) {
+        let mut call = selector(TX_PROCESSED_SIGNATURE).to_vec();
+        call.extend(encode_string(&txid.to_string()));
+        let mut result = vec![0u8; 32];
+        result[31] = processed as u8;
+        state.calls.insert((bridge_address(), call), result);
+    }
+
+    /// Wallet with one P2WPKH coin whose transactions stay unconfirmed until dropped
+    #[derive(Default)]
+    struct TestWallet {
+        broadcast: Mutex<Vec<BtcTransaction>>,
+        dropped: AtomicBool,
+    }
+
+    impl TestWallet {
+        fn script() -> ScriptBuf {
+            ScriptBuf::new_p2wpkh(&WPubkeyHash::from_byte_array([1; 20]))
+        }
+    }
+
+    #[async_trait]
+    impl OnchainWallet for TestWallet {
+        async fn utxos(&self) -> AnyaResult<Vec<(OutPoint, TxOut)>> {
+            Ok(vec![(
+                OutPoint { txid: Txid::from_byte_array([3; 32]), vout: 1 },
+                TxOut { value: Amount::from_sat(2_000_000), script_pubkey: Self::script() },
+            )])
+        }
+
+        async fn change_script(&self) -> AnyaResult<ScriptBuf> {
+            Ok(Self::script())
+        }
+
+        async fn create_payment(&self, _: &bitcoin::Script, _: Amount, _: u64) -> AnyaResult<Psbt> {
+            Err(crate::AnyaError::System("Peg-ins build their own transactions".to_string()))
+        }
+
+        async fn sign(&self, psbt: Psbt) -> AnyaResult<BtcTransaction> {
+            let mut tx = psbt.unsigned_tx;
+            tx.input[0].witness = Witness::from_slice(&[vec![0x30; 71], vec![0x02; 33]]);
+            Ok(tx)
+        }
+
+        async fn broadcast(&self, tx: &BtcTransaction) -> AnyaResult<Txid> {
+            self.broadcast.lock().unwrap().push(tx.clone());
+            Ok(tx.compute_txid())
+        }
+
+        async fn confirmations(&self, _: &Txid) -> AnyaResult<Option<u32>> {
+            Ok((!self.dropped.load(Ordering::SeqCst)).then_some(0))
+        }
+
+        async fn merkle_block(&self, _: &Txid) -> AnyaResult<Option<bitcoin::MerkleBlock>> {
+            Ok(None)
+        }
+
+        async fn block_interval(&self) -> AnyaResult<Duration> {
+            Ok(Duration::from_secs(600))
+        }
+    }
+
     #[tokio::test]
     async fn test_peg_in() {
-        let config = RskConfig::default();
-        let client = RskClient::new(config);
-        
-        let peg_in = client.peg_in("1A1zP1eP5QGefi2DMPTfTL5SLmv7DivfNa", 0.1).await.unwrap();
-        
-        assert_eq!(peg_in.btc_address, "1A1zP1eP5QGefi2DMPTfTL5SLmv7DivfNa");
-        assert_eq!(peg_in.amount, 0.1);
+        let federation = BtcAddress::p2wsh(&ScriptBuf::from(vec![0x51]), Network::Regtest);
+        let mut state = ChainState { chain_id: 33, ..Default::default() };
+        state.calls.insert(
+            (bridge_address(), selector(FEDERATION_ADDRESS_SIGNATURE).to_vec()),
+            encode_string(&federation.to_string()),
+        );
+        let rpc = StandInRpc::start(state).await;
+
+        let genesis = mine(BlockHash::all_zeros(), TxMerkleNode::all_zeros(), 1_700_000_000);
+        let verifier = ProofVerifier::new(HeaderChain::from_checkpoint(genesis, 100, Network::Regtest).unwrap());
+        let client = Arc::new(RskClient::new(config(&rpc)).with_bitcoin_headers(verifier.clone()));
+
+        let wallet = ScriptBuf::new_p2wpkh(&WPubkeyHash::from_byte_array([1; 20]));
+        let (peg_in, psbt) = client.peg_in(PegInRequest {
+            utxos: vec![(
+                OutPoint { txid: Txid::from_byte_array([3; 32]), vout: 1 },
+                TxOut { value: Amount::from_sat(2_000_000), script_pubkey: wallet.clone() },
+            )],
+            amount: Amount::from_sat(1_000_000),
+            fee_rate: 2,
+            change_script: wallet.clone(),
+            rsk_recipient: Some([0xab; 20]),
+            refund_address: None,
+            redeem_scripts: BTreeMap::new(),
+        }).await.unwrap();
+
+        assert_eq!(peg_in.btc_address, BtcAddress::from_script(&wallet, Network::Regtest).unwrap().to_string());
+        assert_eq!(peg_in.amount, 0.01);
+        assert_eq!(peg_in.rsk_recipient, format_address(&[0xab; 20]));
+        assert_eq!(peg_in.federation_address, federation.to_string());
         assert_eq!(peg_in.status, PegStatus::Pending);
         assert!(peg_in.btc_tx_hash.is_none());
-        assert!(peg_in.rsk_tx_hash.is_none());
+        assert_eq!(psbt.unsigned_tx.output[0].script_pubkey, federation.script_pubkey());
+
+        // Only the transaction that was built can be recorded
+        let mut tx = psbt.unsigned_tx.clone();
+        tx.input[0].witness = Witness::from_slice(&[vec![0x30; 71], vec![0x02; 33]]);
+        let mut tampered = tx.clone();
+        tampered.output[0].value = Amount::from_sat(900_000);
+        assert!(client.record_peg_in_transaction(&peg_in.id, &tampered).is_err());
+
+        let txid = tx.compute_txid();
+        let peg_in = client.record_peg_in_transaction(&peg_in.id, &tx).unwrap();
+        assert_eq!(peg_in.btc_tx_hash, Some(txid.to_string()));
+
+        // Mined in the next block
+        let branch = MerkleBranch::from_txids(&[Txid::from_byte_array([9; 32]), txid], 1).unwrap();
+        let block = mine(genesis.block_hash(), branch.compute_root().unwrap(), 1_700_000_600);
+        verifier.add_header(block).unwrap();
+        let peg_in = client.observe_peg_in_proof(&peg_in.id, &branch.to_proof(&block)).unwrap();
+        assert_eq!(peg_in.status, PegStatus::Confirming);
+        assert_eq!(peg_in.confirmations, 1);
+        assert_eq!(peg_in.btc_block_hash, Some(block.block_hash().to_string()));
+
+        // Proofs are accepted for recorded peg-ins only
+        let protocol = RskProtocol::with_config(config(&rpc)).with_proof_verifier(verifier.clone());
+        assert!(!protocol.verify_proof(&branch.to_proof(&block)).await.unwrap().valid);
+        let protocol = protocol.with_client(client.clone());
+        assert!(protocol.verify_proof(&branch.to_proof(&block)).await.unwrap().valid);
+        let neighbour = MerkleBranch::from_txids(&[Txid::from_byte_array([9; 32]), txid], 0).unwrap();
+        assert!(!protocol.verify_proof(&neighbour.to_proof(&block)).await.unwrap().valid);
+
+        let mut tip = block.block_hash();
+        for i in 0..2 {
+            let header = mine(tip, TxMerkleNode::all_zeros(), 1_700_001_200 + 600 * i);
+            verifier.add_header(header).unwrap();
+            tip = header.block_hash();
+        }
+        set_processed(&mut rpc.state.lock().unwrap(), &txid, false);
+        let peg_in = client.refresh_peg_in(&peg_in.id).await.unwrap();
+        assert_eq!(peg_in.confirmations, 3);
+        assert_eq!(peg_in.status, PegStatus::AwaitingRegistration);
+
+        set_processed(&mut rpc.state.lock().unwrap(), &txid, true);
+        let peg_in = client.refresh_peg_in(&peg_in.id).await.unwrap();
+        assert_eq!(peg_in.status, PegStatus::Confirmed);
+        assert!(peg_in.confirmed_at.is_some());
     }
-    
+
     #[tokio::test]
     async fn test_peg_out() {
-        let config = RskConfig::default();
-        let client = RskClient::new(config);
-        
-        let peg_out = client.peg_out("1A1zP1eP5QGefi2DMPTfTL5SLmv7DivfNa", 0.1).await.unwrap();
-        
-        assert_eq!(peg_out.btc_address, "1A1zP1eP5QGefi2DMPTfTL5SLmv7DivfNa");
-        assert_eq!(peg_out.amount, 0.1);
+        let signer = LocalSigner::from_hex("0x4c0883a69102937d6231471b5dbb6204fe5129617082792ae468d01a3f362318").unwrap();
+        let destination = BtcAddress::p2pkh(bitcoin::PublicKey::new(signer.public_key()).pubkey_hash(), Network::Regtest);
+        let bridge = bridge_address();
+
+        let mut sender = [0u8; 32];
+        sender[12..].copy_from_slice(&signer.address());
+        let mut state = ChainState { chain_id: 33, ..Default::default() };
+        state.nonces.insert(signer.address(), 2);
+        state.receipt_logs = vec![EvmLog {
+            address: bridge,
+            topics: vec![event_topic(RELEASE_REQUEST_RECEIVED_EVENT), sender],
+            data: vec![],
+        }];
+        let rpc = StandInRpc::start(state).await;
+        let client = RskClient::new(config(&rpc)).with_signer(signer.clone());
+
+        assert!(client.peg_out("mipcBbFg9gMiCh81Kj8tqqdgoZub1ZJRfn", 0.01).await.is_err());
+        assert!(matches!(client.peg_out(&destination.to_string(), 0.001).await, Err(RskError::InvalidAmount(_))));
+
+        let peg_out = client.peg_out(&destination.to_string(), 0.01).await.unwrap();
+        assert_eq!(peg_out.btc_address, destination.to_string());
+        assert_eq!(peg_out.amount, 0.01);
+        assert_eq!(peg_out.rsk_sender, format_address(&signer.address()));
         assert_eq!(peg_out.status, PegStatus::Pending);
         assert!(peg_out.btc_tx_hash.is_none());
-        assert!(peg_out.rsk_tx_hash.is_none());
+
+        let expected = LegacyTransaction {
+            chain_id: 33,
+            nonce: 2,
+            gas_price: 60_000_000,
+            gas_limit: PEG_OUT_GAS_LIMIT,
+            to: Some(bridge),
+            value: 10_000_000_000_000_000,
+            data: vec![],
+        }.sign(&signer);
+        assert_eq!(rpc.state.lock().unwrap().raw_transactions[0], expected.raw);
+        assert_eq!(peg_out.rsk_tx_hash, Some(format_h256(&expected.hash)));
+
+        // Nothing released yet
+        assert_eq!(client.refresh_peg_out(&peg_out.id).await.unwrap().status, PegStatus::Pending);
+
+        // Release transaction hashes are in display order
+        let release: [u8; 32] = std::array::from_fn(|i| i as u8);
+        rpc.state.lock().unwrap().logs.push((peg_out.rsk_block_number.unwrap() + 5, EvmLog {
+            address: bridge,
+            topics: vec![event_topic(RELEASE_REQUESTED_EVENT), expected.hash, release],
+            data: vec![],
+        }));
+        let peg_out = client.refresh_peg_out(&peg_out.id).await.unwrap();
+        assert_eq!(peg_out.status, PegStatus::Confirming);
+        assert_eq!(peg_out.btc_tx_hash, Some(hex::encode(release)));
+
+        rpc.state.lock().unwrap().logs.push((peg_out.rsk_block_number.unwrap() + 20, EvmLog {
+            address: bridge,
+            topics: vec![event_topic(PEGOUT_CONFIRMED_EVENT), release],
+            data: vec![],
+        }));
+        let peg_out = client.refresh_peg_out(&peg_out.id).await.unwrap();
+        assert_eq!(peg_out.status, PegStatus::Confirmed);
+        assert!(peg_out.confirmed_at.is_some());
+
+        // A refused request fails with the bridge's reason
+        let mut reason = vec![0u8; 64];
+        reason[63] = 3;
+        rpc.state.lock().unwrap().receipt_logs = vec![EvmLog {
+            address: bridge,
+            topics: vec![event_topic(RELEASE_REQUEST_REJECTED_EVENT), sender],
+            data: reason,
+        }];
+        let peg_out = client.peg_out(&destination.to_string(), 0.01).await.unwrap();
+        assert_eq!(peg_out.status, PegStatus::Failed);
+        assert_eq!(peg_out.failure_reason.as_deref(), Some("fees exceed the amount"));
     }
     
+    #[tokio::test]
+    async fn test_moves() {
+        let federation = BtcAddress::p2wsh(&ScriptBuf::from(vec![0x51]), Network::Regtest);
+        let mut state = ChainState { chain_id: 33, block_number: 1_000, block_time: 30, ..Default::default() };
+        state.calls.insert(
+            (bridge_address(), selector(FEDERATION_ADDRESS_SIGNATURE).to_vec()),
+            encode_string(&federation.to_string()),
+        );
+        let mut release_fees = vec![0u8; 32];
+        release_fees[24..].copy_from_slice(&4_000u64.to_be_bytes());
+        state.calls.insert((bridge_address(), selector(PEG_OUT_FEES_SIGNATURE).to_vec()), release_fees);
+        let rpc = StandInRpc::start(state).await;
+
+        let client = Arc::new(RskClient::new(config(&rpc)));
+        let wallet = Arc::new(TestWallet::default());
+        let protocol = RskProtocol::with_config(config(&rpc)).with_client(client.clone()).with_wallet(wallet.clone());
+        let rsk = Venue::Layer2(Layer2Type::Rsk);
+        let request = MoveRequest {
+            asset_id: crate::layer2::BTC_ASSET_ID.to_string(),
+            amount: 1_000_000,
+            from: Venue::Bitcoin,
+            to: rsk,
+            source: String::new(),
+            destination: format_address(&[0xab; 20]),
+            fee_rate: 2,
+        };
+
+        // Peg-outs pay the gas limit and the federation's release fees
+        let estimate = protocol.estimate_move(&request.reversed()).await.unwrap();
+        assert_eq!(estimate.fee, 600 + 4_000);
+        assert_eq!(estimate.latency, Duration::from_secs(4_000 * 30 + 600));
+        assert!(protocol.estimate_move(&MoveRequest { to: rsk, ..request.reversed() }).await.is_err());
+
+        // The funding fee comes out of the locked amount
+        let estimate = protocol.estimate_move(&request).await.unwrap();
+        assert_eq!(estimate.latency, Duration::from_secs(3 * 600));
+        let result = protocol.execute_move(&request).await.unwrap();
+        let tx = wallet.broadcast.lock().unwrap()[0].clone();
+        assert_eq!(tx.output[0].script_pubkey, federation.script_pubkey());
+        assert_eq!(tx.output[0].value, Amount::from_sat(1_000_000 - estimate.fee));
+        let change = tx.output.iter().find(|output| output.script_pubkey == TestWallet::script()).unwrap();
+        assert_eq!(Amount::from_sat(2_000_000) - change.value, Amount::from_sat(1_000_000));
+
+        let peg_in = client.check_peg_in(&tx.compute_txid()).unwrap();
+        assert_eq!(peg_in.id, result.tx_id);
+        assert_eq!(peg_in.rsk_recipient, format_address(&[0xab; 20]));
+
+        set_processed(&mut rpc.state.lock().unwrap(), &tx.compute_txid(), false);
+        assert!(matches!(protocol.get_transaction_status(&result.tx_id).await.unwrap(), Layer2TransactionStatus::Pending));
+        assert!(protocol.refund_move(&request, &result.tx_id).await.is_err());
+
+        // A funding transaction that left the mempool never reached the federation
+        wallet.dropped.store(true, Ordering::SeqCst);
+        assert!(matches!(protocol.get_transaction_status(&result.tx_id).await.unwrap(), Layer2TransactionStatus::Failed));
+        assert_eq!(protocol.refund_move(&request, &result.tx_id).await.unwrap(), None);
+
+        wallet.dropped.store(false, Ordering::SeqCst);
+        set_processed(&mut rpc.state.lock().unwrap(), &tx.compute_txid(), true);
+        assert!(matches!(protocol.get_transaction_status(&result.tx_id).await.unwrap(), Layer2TransactionStatus::Confirmed));
+    }
+
     #[tokio::test]
     async fn test_call_contract() {
         let config = RskConfig::default();
@@ -788,14 +1495,112 @@ async fn test_get_block() {
 pub struct RskProtocol {
     initialized: bool,
     connected: bool,
+    proof_verifier: ProofVerifier,
+    config: RskConfig,
+    /// Client whose peg-ins proofs are checked against
+    client: Option<Arc<RskClient>>,
+    /// Bitcoin wallet funding peg-ins
+    wallet: Option<Arc<dyn OnchainWallet>>,
 }
 
 impl RskProtocol {
     pub fn new() -> Self {
+        Self::with_config(RskConfig::default())
+    }
+
+    /// Create a protocol handler with a specific configuration
+    pub fn with_config(config: RskConfig) -> Self {
         Self {
             initialized: false,
             connected: false,
+            proof_verifier: ProofVerifier::default(),
+            config,
+            client: None,
+            wallet: None,
+        }
+    }
+
+    /// Check proofs against a client's peg-ins
+    pub fn with_client(mut self, client: Arc<RskClient>) -> Self {
+        self.client = Some(client);
+        self
+    }
+
+    /// Fund peg-ins from a Bitcoin wallet and follow its chain
+    pub fn with_wallet(mut self, wallet: Arc<dyn OnchainWallet>) -> Self {
+        self.wallet = Some(wallet);
+        self
+    }
+
+    fn client(&self) -> AnyaResult<&RskClient> {
+        self.client.as_deref().ok_or_else(|| crate::AnyaError::System("No RSK client configured".to_string()))
+    }
+
+    fn wallet(&self) -> AnyaResult<&dyn OnchainWallet> {
+        self.wallet.as_deref().ok_or_else(|| crate::AnyaError::System("No Bitcoin wallet configured".to_string()))
+    }
+
+    /// Peg-in of `request.amount` from the wallet's coins, credited to the destination account
+    async fn peg_in_request(&self, request: &MoveRequest) -> AnyaResult<PegInRequest> {
+        let wallet = self.wallet()?;
+        let change_script = wallet.change_script().await?;
+        // The bridge only refunds to P2PKH and P2SH addresses
+        let refund_address = (change_script.is_p2pkh() || change_script.is_p2sh())
+            .then(|| BtcAddress::from_script(&change_script, self.config.btc_network).ok())
+            .flatten();
+        Ok(PegInRequest {
+            utxos: wallet.utxos().await?,
+            amount: Amount::from_sat(request.amount),
+            fee_rate: request.fee_rate,
+            refund_address,
+            change_script,
+            rsk_recipient: Some(parse_address(&request.destination).map_err(RskError::from)?),
+            redeem_scripts: Default::default(),
+        })
+    }
+
+    /// Lock the amount of a Bitcoin to RSK move, less the funding fee
+    async fn execute_peg_in(&self, request: &MoveRequest) -> AnyaResult<String> {
+        let (client, wallet) = (self.client()?, self.wallet()?);
+        let mut peg_in = self.peg_in_request(request).await?;
+        let fee = client.quote_peg_in(&peg_in).await?;
+        peg_in.amount = peg_in.amount.checked_sub(fee)
+            .ok_or_else(|| RskError::InvalidAmount(format!("{} does not cover the {} funding fee", peg_in.amount, fee)))?;
+
+        let (info, psbt) = client.peg_in(peg_in).await?;
+        let tx = wallet.sign(psbt).await?;
+        client.record_peg_in_transaction(&info.id, &tx)?;
+        wallet.broadcast(&tx).await?;
+        Ok(info.id)
+    }
+
+    /// Status of a peg-in; one whose funding transaction vanished has failed
+    async fn peg_in_status(&self, peg_in_id: &str) -> AnyaResult<Layer2TransactionStatus> {
+        let peg_in = self.client()?.refresh_peg_in(peg_in_id).await?;
+        if let (PegStatus::Pending, Some(txid)) = (peg_in.status, &peg_in.btc_tx_hash) {
+            let txid = Txid::from_str(txid).map_err(|e| crate::AnyaError::System(format!("Peg-in txid {}: {}", txid, e)))?;
+            if self.wallet()?.confirmations(&txid).await?.is_none() {
+                return Ok(Layer2TransactionStatus::Failed);
+            }
         }
+        Ok(peg_status(peg_in.status))
+    }
+}
+
+/// Move status of a peg operation
+fn peg_status(status: PegStatus) -> Layer2TransactionStatus {
+    match status {
+        PegStatus::Confirmed => Layer2TransactionStatus::Confirmed,
+        PegStatus::Failed => Layer2TransactionStatus::Failed,
+        PegStatus::Pending | PegStatus::Confirming | PegStatus::AwaitingRegistration => {
+            Layer2TransactionStatus::Pending
+        },
+    }
+}
+
+impl WithProofVerifier for RskProtocol {
+    fn proof_verifier_mut(&mut self) -> &mut ProofVerifier {
+        &mut self.proof_verifier
     }
 }
 
@@ -821,9 +1626,15 @@ async fn submit_transaction(&self, tx: &[u8]) -> AnyaResult<String> {
         Ok("rsk_tx_123".to_string())
     }
 
-    async fn get_transaction_status(&self, tx_id: &str) -> AnyaResult<TransactionStatus> {
+    async fn get_transaction_status(&self, tx_id: &str) -> AnyaResult<Layer2TransactionStatus> {
         info!("Getting RSK transaction status...");
-        Ok(TransactionStatus::Confirmed)
+        if tx_id.starts_with("peg_in:") {
+            return self.peg_in_status(tx_id).await;
+        }
+        if tx_id.starts_with("peg_out:") {
+            return Ok(peg_status(self.client()?.refresh_peg_out(tx_id).await?.status));
+        }
+        Ok(Layer2TransactionStatus::Confirmed)
     }
 
     async fn get_state(&self) -> AnyaResult<ProtocolState> {
@@ -848,11 +1659,73 @@ async fn transfer_asset(&self, transfer: AssetTransfer) -> AnyaResult<TransferRe
 
     async fn verify_proof(&self, proof: &Proof) -> AnyaResult<VerificationResult> {
         info!("Verifying RSK proof...");
-        Ok(VerificationResult::default())
+        // The proven transaction must be a peg-in to the federation
+        Ok(self.proof_verifier.verify_with(proof, |inclusion| {
+            let client = self.client.as_ref().ok_or("No RSK client to check peg-ins against")?;
+            client.check_peg_in(&inclusion.txid).map(|_| ()).map_err(|e| e.to_string())
+        }))
     }
 
     async fn validate_state(&self, state: &ProtocolState) -> AnyaResult<ValidationResult> {
         info!("Validating RSK state...");
-        Ok(ValidationResult::default())
+        Ok(self.proof_verifier.validate_anchor(state))
+    }
+
+    async fn estimate_move(&self, request: &MoveRequest) -> AnyaResult<MoveEstimate> {
+        const RSK: Venue = Venue::Layer2(Layer2Type::Rsk);
+        let below = |kind: &str, minimum: u64| crate::AnyaError::System(format!(
+            "{}: below the {} sat {} minimum", request.unsupported("RSK"), minimum, kind
+        ));
+        match (request.from, request.to) {
+            (Venue::Bitcoin, RSK) if request.is_btc() => {
+                if request.amount < self.config.min_peg_in_sats {
+                    return Err(below("peg-in", self.config.min_peg_in_sats));
+                }
+                let fee = self.client()?.quote_peg_in(&self.peg_in_request(request).await?).await?;
+                let latency = self.wallet()?.block_interval().await? * self.config.peg_in_confirmations;
+                Ok(MoveEstimate { fee: fee.to_sat(), latency })
+            },
+            (RSK, Venue::Bitcoin) if request.is_btc() => {
+                if request.amount < self.config.min_peg_out_sats {
+                    return Err(below("peg-out", self.config.min_peg_out_sats));
+                }
+                let client = self.client()?;
+                let fee = client.peg_out_fee().await?;
+                let latency = client.block_interval().await? * PEG_OUT_RSK_CONFIRMATIONS
+                    + self.wallet()?.block_interval().await?;
+                Ok(MoveEstimate { fee: fee.to_sat(), latency })
+            },
+            _ => Err(request.unsupported("RSK")),
+        }
+    }
+
+    async fn execute_move(&self, request: &MoveRequest) -> AnyaResult<TransferResult> {
+        const RSK: Venue = Venue::Layer2(Layer2Type::Rsk);
+        let (tx_id, status) = match (request.from, request.to) {
+            (Venue::Bitcoin, RSK) if request.is_btc() => {
+                (self.execute_peg_in(request).await?, Layer2TransactionStatus::Pending)
+            },
+            (RSK, Venue::Bitcoin) if request.is_btc() => {
+                // Gas comes out of the moved amount; the federation deducts its release fees
+                let gas = (PEG_OUT_GAS_LIMIT as u128 * self.config.gas_price as u128).div_ceil(WEI_PER_SATOSHI) as u64;
+                let amount = request.amount.checked_sub(gas)
+                    .ok_or_else(|| RskError::InvalidAmount(format!("{} sat does not cover {} sat of gas", request.amount, gas)))?;
+                let peg_out = self.client()?.peg_out(&request.destination, Amount::from_sat(amount).to_btc()).await?;
+                (peg_out.id, peg_status(peg_out.status))
+            },
+            _ => return Err(request.unsupported("RSK")),
+        };
+        Ok(TransferResult { tx_id, status, timestamp: chrono::Utc::now().timestamp() as u64 })
+    }
+
+    async fn refund_move(&self, request: &MoveRequest, tx_id: &str) -> AnyaResult<Option<MoveRefund>> {
+        // A failed peg-in never left the wallet, and the bridge returns the
+        // RBTC of a refused peg-out in the same transaction
+        match self.get_transaction_status(tx_id).await? {
+            Layer2TransactionStatus::Failed | Layer2TransactionStatus::Rejected => Ok(None),
+            status => Err(crate::AnyaError::System(format!(
+                "{} ({} to {}) is {:?}, not failed", tx_id, request.from, request.to, status
+            ))),
+        }
     }
 } 
\ No newline at end of file
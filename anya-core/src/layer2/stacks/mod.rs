@@ -11,14 +11,102 @@
         Proof,
         VerificationResult,
         ValidationResult,
+        Layer2Type,
+        MoveEstimate,
+        MoveRefund,
+        MoveRequest,
+        OnchainWallet,
+        Venue,
+        proof::{ProofVerifier, WithProofVerifier},
     },
 };
 use async_trait::async_trait;
+use bitcoin::{Address as BtcAddress, Amount, Network, Script, ScriptBuf, Transaction, Txid};
+use std::str::FromStr;
+use std::sync::Arc;
 use tracing::{info, error, warn};
 
+/// Bitcoin blocks from a deposit until sBTC is minted
+const DEPOSIT_BLOCKS: u32 = 2;
+
+/// Bitcoin confirmations of a withdrawal request before the signers pay it out
+const WITHDRAWAL_BLOCKS: u32 = 7;
+
+/// Direction of an sBTC operation
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SbtcOperation {
+    /// BTC locked for sBTC
+    Deposit,
+    /// sBTC burnt for BTC
+    Withdrawal,
+}
+
+/// Progress of a deposit or withdrawal
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum SbtcStatus {
+    /// Not processed by the signers yet
+    Pending,
+    /// sBTC minted, or BTC paid out
+    Completed,
+    /// Rejected by the signers
+    Failed,
+}
+
+/// sBTC signers and their deposit API
+#[async_trait]
+pub trait SbtcBridge: Send + Sync {
+    /// Maximum fee the signers currently take from an operation's amount
+    async fn signer_fee(&self, operation: SbtcOperation) -> AnyaResult<Amount>;
+
+    /// Deposit script crediting `recipient`, reclaimable with `reclaim_script` after its lock time
+    async fn deposit_script(&self, recipient: &str, max_fee: Amount, reclaim_script: &Script) -> AnyaResult<ScriptBuf>;
+
+    /// Notify the signers of a broadcast deposit
+    async fn submit_deposit(&self, tx: &Transaction, vout: u32) -> AnyaResult<()>;
+
+    /// Progress of a deposit
+    async fn deposit_status(&self, txid: &Txid, vout: u32) -> AnyaResult<SbtcStatus>;
+
+    /// Reclaim a rejected deposit once its lock time passed
+    ///
+    /// Returns the reclaim transaction and the amount it returns.
+    async fn reclaim_deposit(&self, txid: &Txid, vout: u32, fee_rate: u64) -> AnyaResult<(Txid, Amount)>;
+
+    /// Burn `amount` of sBTC, plus at most `max_fee`, for BTC paid to `recipient`
+    async fn request_withdrawal(&self, amount: Amount, recipient: &Script, max_fee: Amount) -> AnyaResult<u64>;
+
+    /// Progress of a withdrawal; rejected ones return the sBTC
+    async fn withdrawal_status(&self, request_id: u64) -> AnyaResult<SbtcStatus>;
+}
+
+/// Backends sBTC moves are carried out with
+#[derive(Clone)]
+struct SbtcBackends {
+    bridge: Arc<dyn SbtcBridge>,
+    wallet: Arc<dyn OnchainWallet>,
+    network: Network,
+}
+
+/// Move status of an sBTC operation
+fn sbtc_status(status: SbtcStatus) -> TransactionStatus {
+    match status {
+        SbtcStatus::Pending => TransactionStatus::Pending,
+        SbtcStatus::Completed => TransactionStatus::Confirmed,
+        SbtcStatus::Failed => TransactionStatus::Failed,
+    }
+}
+
+/// Deposit outpoint of a move ID
+fn parse_deposit(tx_id: &str) -> Option<(Txid, u32)> {
+    let (txid, vout) = tx_id.strip_prefix("sbtc_deposit:")?.split_once(':')?;
+    Some((Txid::from_str(txid).ok()?, vout.parse().ok()?))
+}
+
 pub struct StacksProtocol {
     initialized: bool,
     connected: bool,
+    proof_verifier: ProofVerifier,
+    sbtc: Option<SbtcBackends>,
 }
 
 impl StacksProtocol {
@@ -26,8 +114,66 @@ pub fn new() -> Self {
         Self {
             initialized: false,
             connected: false,
+            proof_verifier: ProofVerifier::default(),
+            sbtc: None,
         }
     }
+
+    /// Move BTC in and out of Stacks through sBTC
+    pub fn with_sbtc(mut self, bridge: Arc<dyn SbtcBridge>, wallet: Arc<dyn OnchainWallet>, network: Network) -> Self {
+        self.sbtc = Some(SbtcBackends { bridge, wallet, network });
+        self
+    }
+
+    fn sbtc(&self) -> AnyaResult<&SbtcBackends> {
+        self.sbtc.as_ref().ok_or_else(|| AnyaError::System("No sBTC bridge configured".to_string()))
+    }
+
+    /// Deposit script of a move and the fees of funding it, as (script, funding fee, signer fee)
+    async fn deposit_fees(&self, request: &MoveRequest) -> AnyaResult<(ScriptBuf, Amount, Amount)> {
+        let sbtc = self.sbtc()?;
+        let max_fee = sbtc.bridge.signer_fee(SbtcOperation::Deposit).await?;
+        let reclaim_script = sbtc.wallet.change_script().await?;
+        let script = sbtc.bridge.deposit_script(&request.destination, max_fee, &reclaim_script).await?;
+        let funding_fee = sbtc.wallet.payment_fee(&script, Amount::from_sat(request.amount), request.fee_rate).await?;
+        Ok((script, Amount::from_sat(funding_fee), max_fee))
+    }
+
+    /// Fund a deposit of the amount less its funding fee
+    async fn deposit(&self, request: &MoveRequest) -> AnyaResult<String> {
+        let sbtc = self.sbtc()?;
+        let (script, funding_fee, _) = self.deposit_fees(request).await?;
+        let amount = Amount::from_sat(request.amount).checked_sub(funding_fee)
+            .ok_or_else(|| AnyaError::System(format!("{} sat does not cover the {} funding fee", request.amount, funding_fee)))?;
+        let tx = sbtc.wallet.pay(&script, amount, request.fee_rate).await?;
+        let vout = tx.output.iter().position(|output| output.script_pubkey == script)
+            .ok_or_else(|| AnyaError::System("Deposit transaction does not pay the deposit script".to_string()))?;
+
+        sbtc.bridge.submit_deposit(&tx, vout as u32).await?;
+        info!("Deposited {} for sBTC in {}:{}", amount, tx.compute_txid(), vout);
+        Ok(format!("sbtc_deposit:{}:{}", tx.compute_txid(), vout))
+    }
+
+    /// Request a withdrawal of the amount less the signers' fee
+    async fn withdraw(&self, request: &MoveRequest) -> AnyaResult<String> {
+        let sbtc = self.sbtc()?;
+        let recipient = BtcAddress::from_str(&request.destination)
+            .and_then(|address| address.require_network(sbtc.network))
+            .map_err(|e| AnyaError::System(format!("Invalid destination {}: {}", request.destination, e)))?;
+        let max_fee = sbtc.bridge.signer_fee(SbtcOperation::Withdrawal).await?;
+        let amount = Amount::from_sat(request.amount).checked_sub(max_fee)
+            .ok_or_else(|| AnyaError::System(format!("{} sat does not cover the {} signer fee", request.amount, max_fee)))?;
+
+        let request_id = sbtc.bridge.request_withdrawal(amount, &recipient.script_pubkey(), max_fee).await?;
+        info!("Requested sBTC withdrawal {} of {} to {}", request_id, amount, recipient);
+        Ok(format!("sbtc_withdrawal:{}", request_id))
+    }
+}
+
+impl WithProofVerifier for StacksProtocol {
+    fn proof_verifier_mut(&mut self) -> &mut ProofVerifier {
+        &mut self.proof_verifier
+    }
 }
 
 #[async_trait]
@@ -58,6 +204,27 @@ async fn submit_transaction(&self, tx: &[u8]) -> AnyaResult<String> {
 
     async fn get_transaction_status(&self, tx_id: &str) -> AnyaResult<TransactionStatus> {
         info!("Getting Stacks transaction status...");
+        if let Some((txid, vout)) = parse_deposit(tx_id) {
+            let sbtc = self.sbtc()?;
+            if sbtc.wallet.confirmations(&txid).await?.is_none() {
+                // The deposit never left the wallet
+                return Ok(TransactionStatus::Failed);
+            }
+            return Ok(sbtc_status(sbtc.bridge.deposit_status(&txid, vout).await?));
+        }
+        if let Some(request_id) = tx_id.strip_prefix("sbtc_withdrawal:") {
+            let request_id = request_id.parse()
+                .map_err(|e| AnyaError::System(format!("Withdrawal request {}: {}", request_id, e)))?;
+            return Ok(sbtc_status(self.sbtc()?.bridge.withdrawal_status(request_id).await?));
+        }
+        if let Some(txid) = tx_id.strip_prefix("sbtc_reclaim:") {
+            let txid = Txid::from_str(txid).map_err(|e| AnyaError::System(format!("Reclaim txid {}: {}", txid, e)))?;
+            return Ok(match self.sbtc()?.wallet.confirmations(&txid).await? {
+                Some(0) => TransactionStatus::Pending,
+                Some(_) => TransactionStatus::Confirmed,
+                None => TransactionStatus::Failed,
+            });
+        }
         // TODO: Implement actual status check
         Ok(TransactionStatus::Confirmed)
     }
@@ -88,13 +255,222 @@ async fn transfer_asset(&self, transfer: AssetTransfer) -> AnyaResult<TransferRe
 
     async fn verify_proof(&self, proof: &Proof) -> AnyaResult<VerificationResult> {
         info!("Verifying Stacks proof...");
-        // TODO: Implement actual proof verification
-        Ok(VerificationResult::default())
+        // No Bitcoin-side commitment to check beyond the shared inclusion checks
+        Ok(self.proof_verifier.verify_with(proof, |_| Ok(())))
     }
 
     async fn validate_state(&self, state: &ProtocolState) -> AnyaResult<ValidationResult> {
         info!("Validating Stacks state...");
-        // TODO: Implement actual state validation
-        Ok(ValidationResult::default())
+        Ok(self.proof_verifier.validate_anchor(state))
+    }
+
+    async fn estimate_move(&self, request: &MoveRequest) -> AnyaResult<MoveEstimate> {
+        const STACKS: Venue = Venue::Layer2(Layer2Type::Stacks);
+        // Stacks transaction fees are paid in STX, not from the moved sBTC
+        let sbtc = self.sbtc()?;
+        match (request.from, request.to) {
+            (Venue::Bitcoin, STACKS) if request.is_btc() => {
+                let (_, funding_fee, signer_fee) = self.deposit_fees(request).await?;
+                Ok(MoveEstimate {
+                    fee: (funding_fee + signer_fee).to_sat(),
+                    latency: sbtc.wallet.block_interval().await? * DEPOSIT_BLOCKS,
+                })
+            },
+            (STACKS, Venue::Bitcoin) if request.is_btc() => Ok(MoveEstimate {
+                fee: sbtc.bridge.signer_fee(SbtcOperation::Withdrawal).await?.to_sat(),
+                latency: sbtc.wallet.block_interval().await? * WITHDRAWAL_BLOCKS,
+            }),
+            _ => Err(request.unsupported("Stacks")),
+        }
+    }
+
+    async fn execute_move(&self, request: &MoveRequest) -> AnyaResult<TransferResult> {
+        const STACKS: Venue = Venue::Layer2(Layer2Type::Stacks);
+        let tx_id = match (request.from, request.to) {
+            (Venue::Bitcoin, STACKS) if request.is_btc() => self.deposit(request).await?,
+            (STACKS, Venue::Bitcoin) if request.is_btc() => self.withdraw(request).await?,
+            _ => return Err(request.unsupported("Stacks")),
+        };
+        Ok(TransferResult { tx_id, status: TransactionStatus::Pending, timestamp: chrono::Utc::now().timestamp() as u64 })
+    }
+
+    async fn refund_move(&self, request: &MoveRequest, tx_id: &str) -> AnyaResult<Option<MoveRefund>> {
+        if !matches!(self.get_transaction_status(tx_id).await?, TransactionStatus::Failed | TransactionStatus::Rejected) {
+            return Err(AnyaError::System(format!("{} ({} to {}) has not failed", tx_id, request.from, request.to)));
+        }
+        // Rejected withdrawals return the sBTC, dropped deposits never left the wallet
+        let Some((txid, vout)) = parse_deposit(tx_id) else {
+            return Ok(None);
+        };
+        let sbtc = self.sbtc()?;
+        if sbtc.wallet.confirmations(&txid).await?.is_none() {
+            return Ok(None);
+        }
+        let (reclaim, amount) = sbtc.bridge.reclaim_deposit(&txid, vout, request.fee_rate).await?;
+        warn!("Reclaiming sBTC deposit {}:{} in {}", txid, vout, reclaim);
+        Ok(Some(MoveRefund { tx_id: format!("sbtc_reclaim:{}", reclaim), amount: amount.to_sat() }))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use bitcoin::hashes::Hash;
+    use bitcoin::{absolute, transaction, OutPoint, Psbt, TxIn, TxOut, WPubkeyHash, WScriptHash};
+    use std::sync::Mutex;
+    use std::time::Duration;
+
+    fn wallet_script() -> ScriptBuf {
+        ScriptBuf::new_p2wpkh(&WPubkeyHash::from_byte_array([1; 20]))
+    }
+
+    /// sBTC signers and a wallet whose transactions stay unconfirmed until dropped
+    struct TestBackends {
+        status: Mutex<SbtcStatus>,
+        dropped: Mutex<bool>,
+        broadcast: Mutex<Vec<Transaction>>,
+        withdrawals: Mutex<Vec<(Amount, ScriptBuf, Amount)>>,
+    }
+
+    #[async_trait]
+    impl SbtcBridge for TestBackends {
+        async fn signer_fee(&self, operation: SbtcOperation) -> AnyaResult<Amount> {
+            Ok(Amount::from_sat(match operation {
+                SbtcOperation::Deposit => 800,
+                SbtcOperation::Withdrawal => 500,
+            }))
+        }
+
+        async fn deposit_script(&self, recipient: &str, max_fee: Amount, reclaim_script: &Script) -> AnyaResult<ScriptBuf> {
+            assert_eq!((recipient, max_fee), ("SP2J6ZY48GV1EZ5V2V5RB9MP66SW86PYKKNRV9EJ7", Amount::from_sat(800)));
+            assert_eq!(reclaim_script, wallet_script().as_script());
+            Ok(ScriptBuf::new_p2wsh(&WScriptHash::from_byte_array([7; 32])))
+        }
+
+        async fn submit_deposit(&self, tx: &Transaction, vout: u32) -> AnyaResult<()> {
+            assert_eq!(tx.output[vout as usize].script_pubkey, ScriptBuf::new_p2wsh(&WScriptHash::from_byte_array([7; 32])));
+            Ok(())
+        }
+
+        async fn deposit_status(&self, _: &Txid, _: u32) -> AnyaResult<SbtcStatus> {
+            Ok(*self.status.lock().unwrap())
+        }
+
+        async fn reclaim_deposit(&self, _: &Txid, vout: u32, _: u64) -> AnyaResult<(Txid, Amount)> {
+            assert_eq!(vout, 0);
+            Ok((Txid::from_byte_array([5; 32]), Amount::from_sat(99_000)))
+        }
+
+        async fn request_withdrawal(&self, amount: Amount, recipient: &Script, max_fee: Amount) -> AnyaResult<u64> {
+            self.withdrawals.lock().unwrap().push((amount, recipient.to_owned(), max_fee));
+            Ok(12)
+        }
+
+        async fn withdrawal_status(&self, request_id: u64) -> AnyaResult<SbtcStatus> {
+            assert_eq!(request_id, 12);
+            Ok(*self.status.lock().unwrap())
+        }
     }
-} 
\ No newline at end of file
+
+    #[async_trait]
+    impl OnchainWallet for TestBackends {
+        async fn utxos(&self) -> AnyaResult<Vec<(OutPoint, TxOut)>> {
+            Ok(Vec::new())
+        }
+
+        async fn change_script(&self) -> AnyaResult<ScriptBuf> {
+            Ok(wallet_script())
+        }
+
+        async fn create_payment(&self, script_pubkey: &Script, amount: Amount, fee_rate: u64) -> AnyaResult<Psbt> {
+            let coin = TxOut { value: Amount::from_sat(1_000_000), script_pubkey: wallet_script() };
+            let tx = Transaction {
+                version: transaction::Version::TWO,
+                lock_time: absolute::LockTime::ZERO,
+                input: vec![TxIn { previous_output: OutPoint::null(), ..Default::default() }],
+                output: vec![
+                    TxOut { value: amount, script_pubkey: script_pubkey.to_owned() },
+                    TxOut { value: coin.value - amount - Amount::from_sat(141 * fee_rate), script_pubkey: wallet_script() },
+                ],
+            };
+            let mut psbt = Psbt::from_unsigned_tx(tx).unwrap();
+            psbt.inputs[0].witness_utxo = Some(coin);
+            Ok(psbt)
+        }
+
+        async fn sign(&self, psbt: Psbt) -> AnyaResult<Transaction> {
+            Ok(psbt.unsigned_tx)
+        }
+
+        async fn broadcast(&self, tx: &Transaction) -> AnyaResult<Txid> {
+            self.broadcast.lock().unwrap().push(tx.clone());
+            Ok(tx.compute_txid())
+        }
+
+        async fn confirmations(&self, _: &Txid) -> AnyaResult<Option<u32>> {
+            Ok((!*self.dropped.lock().unwrap()).then_some(0))
+        }
+
+        async fn merkle_block(&self, _: &Txid) -> AnyaResult<Option<bitcoin::MerkleBlock>> {
+            Ok(None)
+        }
+
+        async fn block_interval(&self) -> AnyaResult<Duration> {
+            Ok(Duration::from_secs(600))
+        }
+    }
+
+    #[tokio::test]
+    async fn test_sbtc_moves() {
+        let backends = Arc::new(TestBackends {
+            status: Mutex::new(SbtcStatus::Pending),
+            dropped: Mutex::new(false),
+            broadcast: Mutex::new(Vec::new()),
+            withdrawals: Mutex::new(Vec::new()),
+        });
+        let protocol = StacksProtocol::new().with_sbtc(backends.clone(), backends.clone(), Network::Regtest);
+        let stacks = Venue::Layer2(Layer2Type::Stacks);
+        let request = MoveRequest {
+            asset_id: crate::layer2::BTC_ASSET_ID.to_string(),
+            amount: 100_000,
+            from: Venue::Bitcoin,
+            to: stacks,
+            source: String::new(),
+            destination: "SP2J6ZY48GV1EZ5V2V5RB9MP66SW86PYKKNRV9EJ7".to_string(),
+            fee_rate: 2,
+        };
+        assert!(protocol.estimate_move(&MoveRequest { from: stacks, ..request.clone() }).await.is_err());
+
+        let estimate = protocol.estimate_move(&request).await.unwrap();
+        assert_eq!(estimate.fee, 282 + 800);
+        assert_eq!(estimate.latency, Duration::from_secs(2 * 600));
+
+        let result = protocol.execute_move(&request).await.unwrap();
+        let deposit = backends.broadcast.lock().unwrap()[0].clone();
+        assert_eq!(result.tx_id, format!("sbtc_deposit:{}:0", deposit.compute_txid()));
+        assert_eq!(deposit.output[0].value, Amount::from_sat(100_000 - 282));
+        assert!(matches!(protocol.get_transaction_status(&result.tx_id).await.unwrap(), TransactionStatus::Pending));
+        assert!(protocol.refund_move(&request, &result.tx_id).await.is_err());
+
+        // Rejected deposits are reclaimed, dropped ones never left the wallet
+        *backends.status.lock().unwrap() = SbtcStatus::Failed;
+        let refund = protocol.refund_move(&request, &result.tx_id).await.unwrap().unwrap();
+        assert_eq!(refund, MoveRefund { tx_id: format!("sbtc_reclaim:{}", Txid::from_byte_array([5; 32])), amount: 99_000 });
+        *backends.dropped.lock().unwrap() = true;
+        assert_eq!(protocol.refund_move(&request, &result.tx_id).await.unwrap(), None);
+
+        // Withdrawals burn the amount and the signers' fee
+        let destination = BtcAddress::from_script(&wallet_script(), Network::Regtest).unwrap();
+        let request = MoveRequest { destination: destination.to_string(), ..request.reversed() };
+        let estimate = protocol.estimate_move(&request).await.unwrap();
+        assert_eq!(estimate.fee, 500);
+        assert_eq!(estimate.latency, Duration::from_secs(7 * 600));
+        let result = protocol.execute_move(&request).await.unwrap();
+        assert_eq!(result.tx_id, "sbtc_withdrawal:12");
+        assert_eq!(
+            *backends.withdrawals.lock().unwrap(),
+            [(Amount::from_sat(99_500), wallet_script(), Amount::from_sat(500))]
+        );
+        assert_eq!(protocol.refund_move(&request, &result.tx_id).await.unwrap(), None);
+    }
+}
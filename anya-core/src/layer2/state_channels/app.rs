@@ -0,0 +1,206 @@
+//! Pluggable application state for two-party state channels
+//!
+//! A channel only cares that every application state can be encoded, can say
+//! how the channel funds are split, and can be advanced by an action from one
+//! of the two parties. Both sides apply the same action and must arrive at the
+//! same encoding before countersigning.
+
+use serde::{Deserialize, Serialize};
+
+/// Side of a two-party channel
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Party {
+    /// Channel opener
+    A,
+    /// Channel acceptor
+    B,
+}
+
+impl Party {
+    /// The other side
+    pub fn other(self) -> Party {
+        match self {
+            Party::A => Party::B,
+            Party::B => Party::A,
+        }
+    }
+}
+
+/// Application running inside a state channel
+pub trait ChannelApp: Clone + Send + Sync {
+    /// Action one party can take
+    type Action: Clone + Send + Sync;
+
+    /// Apply `action` taken by `actor`, returning the next state
+    fn apply(&self, actor: Party, action: &Self::Action) -> Result<Self, String>;
+
+    /// Split of the channel funds as (party A, party B), in satoshis
+    fn balances(&self) -> (u64, u64);
+
+    /// Canonical encoding, covered by both signatures
+    fn encode(&self) -> Vec<u8>;
+}
+
+/// Bidirectional payment ledger
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PaymentLedger {
+    /// Balance of party A
+    pub balance_a: u64,
+    /// Balance of party B
+    pub balance_b: u64,
+}
+
+/// Payment within a ledger channel
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Payment {
+    /// Amount sent to the other party
+    pub amount: u64,
+}
+
+impl PaymentLedger {
+    /// Create a ledger with the initial balances
+    pub fn new(balance_a: u64, balance_b: u64) -> Self {
+        Self { balance_a, balance_b }
+    }
+}
+
+impl ChannelApp for PaymentLedger {
+    type Action = Payment;
+
+    fn apply(&self, actor: Party, action: &Payment) -> Result<Self, String> {
+        let mut next = self.clone();
+        let (from, to) = match actor {
+            Party::A => (&mut next.balance_a, &mut next.balance_b),
+            Party::B => (&mut next.balance_b, &mut next.balance_a),
+        };
+
+        if action.amount == 0 {
+            return Err("Payment amount must be positive".to_string());
+        }
+        *from = from.checked_sub(action.amount)
+            .ok_or_else(|| format!("Insufficient balance for payment of {}", action.amount))?;
+        *to += action.amount;
+
+        Ok(next)
+    }
+
+    fn balances(&self) -> (u64, u64) {
+        (self.balance_a, self.balance_b)
+    }
+
+    fn encode(&self) -> Vec<u8> {
+        let mut bytes = b"ledger".to_vec();
+        bytes.extend_from_slice(&self.balance_a.to_be_bytes());
+        bytes.extend_from_slice(&self.balance_b.to_be_bytes());
+        bytes
+    }
+}
+
+/// Tic-tac-toe played for a stake
+///
+/// Party A moves first. The winner takes `stake` from the other party; a draw
+/// or an unfinished game leaves the initial balances.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct TicTacToe {
+    /// Board cells, row by row
+    pub board: [Option<Party>; 9],
+    /// Party to move
+    pub next: Party,
+    /// Amount won by the winner
+    pub stake: u64,
+    /// Balances before the game
+    pub initial_balances: (u64, u64),
+}
+
+/// Move in a tic-tac-toe game
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Move {
+    /// Cell index, 0-8
+    pub cell: usize,
+}
+
+impl TicTacToe {
+    /// Winning lines
+    const LINES: [[usize; 3]; 8] = [
+        [0, 1, 2], [3, 4, 5], [6, 7, 8],
+        [0, 3, 6], [1, 4, 7], [2, 5, 8],
+        [0, 4, 8], [2, 4, 6],
+    ];
+
+    /// Start a game
+    pub fn new(balance_a: u64, balance_b: u64, stake: u64) -> Result<Self, String> {
+        if stake > balance_a || stake > balance_b {
+            return Err("Stake exceeds a player's balance".to_string());
+        }
+        Ok(Self {
+            board: [None; 9],
+            next: Party::A,
+            stake,
+            initial_balances: (balance_a, balance_b),
+        })
+    }
+
+    /// Winner, if any
+    pub fn winner(&self) -> Option<Party> {
+        Self::LINES.iter().find_map(|line| {
+            match (self.board[line[0]], self.board[line[1]], self.board[line[2]]) {
+                (Some(a), Some(b), Some(c)) if a == b && b == c => Some(a),
+                _ => None,
+            }
+        })
+    }
+
+    /// Whether no more moves can be made
+    pub fn is_over(&self) -> bool {
+        self.winner().is_some() || self.board.iter().all(|cell| cell.is_some())
+    }
+}
+
+impl ChannelApp for TicTacToe {
+    type Action = Move;
+
+    fn apply(&self, actor: Party, action: &Move) -> Result<Self, String> {
+        if self.is_over() {
+            return Err("Game is over".to_string());
+        }
+        if actor != self.next {
+            return Err(format!("Not {:?}'s turn", actor));
+        }
+        match self.board.get(action.cell) {
+            Some(None) => {},
+            Some(Some(_)) => return Err(format!("Cell {} is taken", action.cell)),
+            None => return Err(format!("Cell {} does not exist", action.cell)),
+        }
+
+        let mut next = self.clone();
+        next.board[action.cell] = Some(actor);
+        next.next = actor.other();
+        Ok(next)
+    }
+
+    fn balances(&self) -> (u64, u64) {
+        let (a, b) = self.initial_balances;
+        match self.winner() {
+            Some(Party::A) => (a + self.stake, b - self.stake),
+            Some(Party::B) => (a - self.stake, b + self.stake),
+            None => (a, b),
+        }
+    }
+
+    fn encode(&self) -> Vec<u8> {
+        let mut bytes = b"tictactoe".to_vec();
+        bytes.extend(self.board.iter().map(|cell| match cell {
+            None => 0u8,
+            Some(Party::A) => 1,
+            Some(Party::B) => 2,
+        }));
+        bytes.push(match self.next {
+            Party::A => 1,
+            Party::B => 2,
+        });
+        bytes.extend_from_slice(&self.stake.to_be_bytes());
+        bytes.extend_from_slice(&self.initial_balances.0.to_be_bytes());
+        bytes.extend_from_slice(&self.initial_balances.1.to_be_bytes());
+        bytes
+    }
+}
@@ -0,0 +1,1088 @@
+//! Two-party state channel
+//!
+//! Funds sit in a taproot output whose key path is unspendable and whose only
+//! leaf is a 2-of-2 `CHECKSIGVERIFY`/`CHECKSIG` script. Every state update is
+//! numbered and signed by both parties, both over the state itself and over
+//! each party's own settlement transaction paying out that state's balances.
+//!
+//! Closing cooperatively spends the funding output directly. Closing
+//! unilaterally broadcasts the closing party's settlement of its latest state.
+//! There the counterparty is paid at once, while the closing party's balance
+//! goes to a delayed output it can only sweep after `dispute_window` blocks.
+//! That output has a second leaf keyed to a per-state revocation point. Once
+//! both parties have moved past a state they reveal its revocation secrets, so
+//! a party broadcasting a revoked settlement loses its delayed output to the
+//! counterparty before the delay ends. Settlement transactions carry their
+//! state number in `nLockTime` (as a past timestamp) so watchers can tell
+//! states apart on-chain.
+
+use std::collections::BTreeMap;
+
+use bitcoin::absolute::LockTime;
+use bitcoin::hashes::{sha256, Hash, HashEngine};
+use bitcoin::opcodes::all::{OP_CHECKSIG, OP_CHECKSIGVERIFY, OP_CSV, OP_DROP};
+use bitcoin::script::Builder;
+use bitcoin::secp256k1::{schnorr, Keypair, Message, Secp256k1, SecretKey, XOnlyPublicKey};
+use bitcoin::sighash::{Prevouts, SighashCache, TapSighashType};
+use bitcoin::taproot::{ControlBlock, LeafVersion, TapLeafHash, TaprootBuilder, TaprootSpendInfo};
+use bitcoin::transaction::Version;
+use bitcoin::{Amount, OutPoint, ScriptBuf, Sequence, Transaction, TxIn, TxOut, Txid, Witness};
+
+use super::app::{ChannelApp, Party};
+
+/// BIP341 NUMS point used as the unspendable internal key
+const NUMS_INTERNAL_KEY: [u8; 32] = [
+    0x50, 0x92, 0x9b, 0x74, 0xc1, 0xa0, 0x49, 0x54, 0xb7, 0x8b, 0x4b, 0x60, 0x35, 0xe9, 0x7a, 0x5e,
+    0x07, 0x8a, 0x5a, 0x0f, 0x28, 0xec, 0x96, 0xd5, 0x47, 0xbf, 0xee, 0x9a, 0xce, 0x80, 0x3a, 0xc0,
+];
+
+/// Smallest timestamp lock time; state numbers are encoded above it
+const STATE_LOCKTIME_BASE: u32 = 500_000_000;
+
+/// Outputs below this value are left to fees
+const DUST_LIMIT: u64 = 330;
+
+/// Errors from state channel operations
+#[derive(Debug, thiserror::Error)]
+pub enum ChannelError {
+    /// Operation not valid in the channel's current status
+    #[error("Invalid channel status: {0}")]
+    InvalidStatus(String),
+
+    /// State update rejected
+    #[error("Invalid state update: {0}")]
+    InvalidUpdate(String),
+
+    /// Signature missing or invalid
+    #[error("Invalid signature: {0}")]
+    InvalidSignature(String),
+
+    /// Application rejected an action
+    #[error("Application error: {0}")]
+    App(String),
+
+    /// Transaction or script construction failed
+    #[error("Transaction error: {0}")]
+    Transaction(String),
+}
+
+/// Result type for state channel operations
+pub type ChannelResult<T> = Result<T, ChannelError>;
+
+/// Channel configuration
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ChannelConfig {
+    /// Blocks a unilateral closer waits for its own balance, during which a revoked state can be punished
+    pub dispute_window: u16,
+    /// Fee of the settlement transaction
+    pub settlement_fee: u64,
+    /// Fee of the transaction sweeping or claiming a delayed output
+    pub sweep_fee: u64,
+}
+
+impl Default for ChannelConfig {
+    fn default() -> Self {
+        Self {
+            dispute_window: 144,
+            settlement_fee: 500,
+            sweep_fee: 300,
+        }
+    }
+}
+
+/// Fixed parameters both parties agree on at open
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ChannelParams {
+    /// Funding output
+    pub funding_outpoint: OutPoint,
+    /// Value of the funding output
+    pub capacity: u64,
+    /// Key of party A
+    pub key_a: XOnlyPublicKey,
+    /// Key of party B
+    pub key_b: XOnlyPublicKey,
+    /// Channel configuration
+    pub config: ChannelConfig,
+}
+
+impl ChannelParams {
+    /// Channel ID: hash of the funding outpoint
+    pub fn channel_id(&self) -> [u8; 32] {
+        let mut engine = sha256::Hash::engine();
+        engine.input(&self.funding_outpoint.txid.to_byte_array());
+        engine.input(&self.funding_outpoint.vout.to_be_bytes());
+        sha256::Hash::from_engine(engine).to_byte_array()
+    }
+
+    /// Key of `party`
+    pub fn key(&self, party: Party) -> XOnlyPublicKey {
+        match party {
+            Party::A => self.key_a,
+            Party::B => self.key_b,
+        }
+    }
+
+    /// 2-of-2 leaf script
+    pub fn multisig_script(&self) -> ScriptBuf {
+        Builder::new()
+            .push_x_only_key(&self.key_a)
+            .push_opcode(OP_CHECKSIGVERIFY)
+            .push_x_only_key(&self.key_b)
+            .push_opcode(OP_CHECKSIG)
+            .into_script()
+    }
+
+    /// Delayed output leaf: `holder` alone after the dispute window
+    pub fn delayed_script(&self, holder: Party) -> ScriptBuf {
+        Builder::new()
+            .push_int(self.config.dispute_window as i64)
+            .push_opcode(OP_CSV)
+            .push_opcode(OP_DROP)
+            .push_x_only_key(&self.key(holder))
+            .push_opcode(OP_CHECKSIG)
+            .into_script()
+    }
+
+    /// Delayed output leaf: the counterparty once `holder` has revealed the state's revocation secret
+    pub fn revocation_script(&self, holder: Party, revocation_point: &XOnlyPublicKey) -> ScriptBuf {
+        Builder::new()
+            .push_x_only_key(revocation_point)
+            .push_opcode(OP_CHECKSIGVERIFY)
+            .push_x_only_key(&self.key(holder.other()))
+            .push_opcode(OP_CHECKSIG)
+            .into_script()
+    }
+
+    /// Funding output both parties pay into
+    pub fn funding_output(&self) -> ChannelResult<TxOut> {
+        let (spend_info, _) = single_leaf(self.multisig_script())?;
+        Ok(TxOut {
+            value: Amount::from_sat(self.capacity),
+            script_pubkey: ScriptBuf::new_p2tr_tweaked(spend_info.output_key()),
+        })
+    }
+
+    /// Script of `holder`'s delayed output in a settlement with `revocation_point`
+    pub fn delayed_script_pubkey(&self, holder: Party, revocation_point: &XOnlyPublicKey) -> ChannelResult<ScriptBuf> {
+        let spend_info = self.delayed_spend_info(holder, revocation_point)?;
+        Ok(ScriptBuf::new_p2tr_tweaked(spend_info.output_key()))
+    }
+
+    /// Unsigned settlement transaction `holder` can broadcast for state `version`
+    pub fn settlement_transaction(
+        &self,
+        holder: Party,
+        version: u64,
+        balances: (u64, u64),
+        revocation_point: &XOnlyPublicKey,
+    ) -> ChannelResult<Transaction> {
+        let lock_time = u32::try_from(version)
+            .ok()
+            .and_then(|v| v.checked_add(STATE_LOCKTIME_BASE))
+            .ok_or_else(|| ChannelError::InvalidUpdate("State number exhausted".to_string()))?;
+
+        let secp = Secp256k1::verification_only();
+        let delayed = self.delayed_script_pubkey(holder, revocation_point)?;
+        let (pay_a, pay_b) = self.payouts(balances, self.config.settlement_fee)?;
+
+        let output = [(Party::A, pay_a), (Party::B, pay_b)]
+            .into_iter()
+            .filter(|(_, value)| *value >= DUST_LIMIT)
+            .map(|(party, value)| TxOut {
+                value: Amount::from_sat(value),
+                script_pubkey: if party == holder {
+                    delayed.clone()
+                } else {
+                    ScriptBuf::new_p2tr(&secp, self.key(party), None)
+                },
+            })
+            .collect();
+
+        Ok(Transaction {
+            version: Version::TWO,
+            lock_time: LockTime::from_consensus(lock_time),
+            input: vec![unsigned_input(self.funding_outpoint, Sequence::ENABLE_LOCKTIME_NO_RBF)],
+            output,
+        })
+    }
+
+    /// Unsigned cooperative close transaction
+    pub fn close_transaction(&self, balances: (u64, u64), fee: u64) -> ChannelResult<Transaction> {
+        let secp = Secp256k1::verification_only();
+        let (pay_a, pay_b) = self.payouts(balances, fee)?;
+        let output = [(pay_a, self.key_a), (pay_b, self.key_b)]
+            .into_iter()
+            .filter(|(value, _)| *value >= DUST_LIMIT)
+            .map(|(value, key)| TxOut {
+                value: Amount::from_sat(value),
+                script_pubkey: ScriptBuf::new_p2tr(&secp, key, None),
+            })
+            .collect();
+
+        Ok(Transaction {
+            version: Version::TWO,
+            lock_time: LockTime::ZERO,
+            input: vec![unsigned_input(self.funding_outpoint, Sequence::MAX)],
+            output,
+        })
+    }
+
+    /// Digest both parties sign for a state
+    pub fn state_digest(&self, version: u64, app_state: &[u8], balances: (u64, u64)) -> [u8; 32] {
+        let tag = sha256::Hash::hash(b"anya/state-channel/state");
+        let mut engine = sha256::Hash::engine();
+        engine.input(tag.as_ref());
+        engine.input(tag.as_ref());
+        engine.input(&self.channel_id());
+        engine.input(&version.to_be_bytes());
+        engine.input(&balances.0.to_be_bytes());
+        engine.input(&balances.1.to_be_bytes());
+        engine.input(app_state);
+        sha256::Hash::from_engine(engine).to_byte_array()
+    }
+
+    /// Sighash of `holder`'s settlement transaction for a state
+    pub fn settlement_sighash(
+        &self,
+        holder: Party,
+        version: u64,
+        balances: (u64, u64),
+        revocation_point: &XOnlyPublicKey,
+    ) -> ChannelResult<Message> {
+        let tx = self.settlement_transaction(holder, version, balances, revocation_point)?;
+        script_path_sighash(&tx, &self.funding_output()?, &self.multisig_script())
+    }
+
+    /// Sighash of the cooperative close transaction
+    pub fn close_sighash(&self, balances: (u64, u64), fee: u64) -> ChannelResult<Message> {
+        let tx = self.close_transaction(balances, fee)?;
+        script_path_sighash(&tx, &self.funding_output()?, &self.multisig_script())
+    }
+
+    /// Payout values for `balances`, with `fee` split evenly between the parties
+    fn payouts(&self, balances: (u64, u64), fee: u64) -> ChannelResult<(u64, u64)> {
+        if balances.0 + balances.1 != self.capacity {
+            return Err(ChannelError::InvalidUpdate(format!(
+                "Balances {} + {} do not match capacity {}", balances.0, balances.1, self.capacity
+            )));
+        }
+
+        let fee_b = fee / 2;
+        let fee_a = fee - fee_b;
+
+        // A party that cannot cover its share pays what it has; the rest falls to the other
+        let (pay_a, pay_b) = if balances.0 < fee_a {
+            (0, balances.1.saturating_sub(fee - balances.0))
+        } else if balances.1 < fee_b {
+            (balances.0.saturating_sub(fee - balances.1), 0)
+        } else {
+            (balances.0 - fee_a, balances.1 - fee_b)
+        };
+
+        if pay_a < DUST_LIMIT && pay_b < DUST_LIMIT {
+            return Err(ChannelError::Transaction("Fees consume the whole channel".to_string()));
+        }
+        Ok((pay_a, pay_b))
+    }
+
+    /// Taproot tree of `holder`'s delayed output
+    fn delayed_spend_info(&self, holder: Party, revocation_point: &XOnlyPublicKey) -> ChannelResult<TaprootSpendInfo> {
+        let secp = Secp256k1::verification_only();
+        TaprootBuilder::new()
+            .add_leaf(1, self.delayed_script(holder))
+            .and_then(|builder| builder.add_leaf(1, self.revocation_script(holder, revocation_point)))
+            .map_err(|e| ChannelError::Transaction(e.to_string()))?
+            .finalize(&secp, nums_key())
+            .map_err(|_| ChannelError::Transaction("Failed to finalize taproot tree".to_string()))
+    }
+}
+
+/// Both signatures a party gives for one state
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct StateSignatures {
+    /// Signature over the state digest
+    pub state: schnorr::Signature,
+    /// Signature over the counterparty's settlement transaction for the state
+    pub settlement: schnorr::Signature,
+}
+
+/// State signed by both parties
+#[derive(Debug, Clone)]
+pub struct SignedState<A: ChannelApp> {
+    /// State number
+    pub version: u64,
+    /// Application state
+    pub app: A,
+    /// Signatures of party A
+    pub signatures_a: StateSignatures,
+    /// Signatures of party B
+    pub signatures_b: StateSignatures,
+}
+
+impl<A: ChannelApp> SignedState<A> {
+    /// Checks both parties' signatures over the state digest
+    pub fn verify(&self, params: &ChannelParams) -> ChannelResult<()> {
+        let digest = params.state_digest(self.version, &self.app.encode(), self.app.balances());
+        verify(&Message::from_digest(digest), &self.signatures_a.state, &params.key_a, "state")?;
+        verify(&Message::from_digest(digest), &self.signatures_b.state, &params.key_b, "state")
+    }
+}
+
+/// Signatures exchanged when opening the channel
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct OpenSignatures {
+    /// Signatures over the initial state
+    pub initial_state: StateSignatures,
+}
+
+/// State update proposed by one party
+#[derive(Debug, Clone)]
+pub struct StateUpdate<A: ChannelApp> {
+    /// New state number
+    pub version: u64,
+    /// Party taking the action
+    pub actor: Party,
+    /// Action applied to the previous state
+    pub action: A::Action,
+    /// Resulting application state
+    pub app: A,
+    /// Proposer's signatures
+    pub signatures: StateSignatures,
+}
+
+/// Revocation of a party's settlement for a superseded state
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Revocation {
+    /// State being revoked
+    pub version: u64,
+    /// Revocation secret of the state
+    pub secret: SecretKey,
+    /// Revocation point of state `version + 2`
+    pub next_point: XOnlyPublicKey,
+}
+
+/// Countersignature of a state update
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct UpdateAck {
+    /// State number acknowledged
+    pub version: u64,
+    /// Acknowledger's signatures
+    pub signatures: StateSignatures,
+    /// Acknowledger's revocation of the previous state
+    pub revocation: Revocation,
+}
+
+/// Unilateral close in progress
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Dispute {
+    /// Settlement transaction that confirmed
+    pub settlement_txid: Txid,
+    /// Party that broadcast it
+    pub holder: Party,
+    /// State it settles
+    pub version: u64,
+    /// Height at which the settlement confirmed
+    pub started_at: u32,
+    /// First height at which the holder can sweep its delayed output
+    pub deadline: u32,
+    /// Holder's delayed output
+    pub delayed_outpoint: OutPoint,
+    /// Value of the delayed output
+    pub delayed_value: u64,
+    /// Revocation point of the settled state
+    pub revocation_point: XOnlyPublicKey,
+    /// Whether the holder had revoked the state, making its delayed output claimable
+    pub revoked: bool,
+}
+
+/// Channel status
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ChannelStatus {
+    /// Waiting for the counterparty's open signatures
+    Opening,
+    /// Open for updates
+    Open,
+    /// Local settlement broadcast, waiting for confirmation
+    ForceClosing,
+    /// Settlement confirmed with a delayed output left to sweep or claim
+    Disputing,
+    /// Closed by the given transaction
+    Closed(Txid),
+}
+
+/// One party's view of a two-party state channel
+pub struct StateChannel<A: ChannelApp> {
+    params: ChannelParams,
+    local: Party,
+    keypair: Keypair,
+    initial: A,
+    latest: Option<SignedState<A>>,
+    pending: Option<StateUpdate<A>>,
+    remote_points: BTreeMap<u64, XOnlyPublicKey>,
+    remote_secrets: BTreeMap<u64, SecretKey>,
+    dispute: Option<Dispute>,
+    status: ChannelStatus,
+}
+
+impl<A: ChannelApp> StateChannel<A> {
+    /// Create the local side of a channel with an initial application state
+    pub fn new(params: ChannelParams, local: Party, keypair: Keypair, initial: A) -> ChannelResult<Self> {
+        if keypair.x_only_public_key().0 != params.key(local) {
+            return Err(ChannelError::InvalidSignature("Keypair does not match the local channel key".to_string()));
+        }
+
+        // Validates balances against capacity and the fee budget
+        params.payouts(initial.balances(), params.config.settlement_fee)?;
+
+        Ok(Self {
+            params,
+            local,
+            keypair,
+            initial,
+            latest: None,
+            pending: None,
+            remote_points: BTreeMap::new(),
+            remote_secrets: BTreeMap::new(),
+            dispute: None,
+            status: ChannelStatus::Opening,
+        })
+    }
+
+    /// Channel parameters
+    pub fn params(&self) -> &ChannelParams {
+        &self.params
+    }
+
+    /// Channel status
+    pub fn status(&self) -> &ChannelStatus {
+        &self.status
+    }
+
+    /// Latest fully-signed state
+    pub fn latest_state(&self) -> Option<&SignedState<A>> {
+        self.latest.as_ref()
+    }
+
+    /// Dispute in progress, if any
+    pub fn dispute(&self) -> Option<&Dispute> {
+        self.dispute.as_ref()
+    }
+
+    /// Local revocation points of states 0 and 1, sent to the counterparty before the open signatures
+    pub fn initial_revocation_points(&self) -> [XOnlyPublicKey; 2] {
+        [self.revocation_point(0), self.revocation_point(1)]
+    }
+
+    /// Local signatures needed to open the channel, given the counterparty's initial revocation points
+    pub fn open_signatures(&mut self, remote_points: [XOnlyPublicKey; 2]) -> ChannelResult<OpenSignatures> {
+        self.require_status(ChannelStatus::Opening)?;
+        self.remote_points.insert(0, remote_points[0]);
+        self.remote_points.insert(1, remote_points[1]);
+        Ok(OpenSignatures { initial_state: self.sign_state(0, &self.initial)? })
+    }
+
+    /// Accept the counterparty's open signatures; the funding transaction is safe to broadcast afterwards
+    pub fn accept_open(&mut self, remote: OpenSignatures) -> ChannelResult<()> {
+        self.require_status(ChannelStatus::Opening)?;
+        self.verify_remote_signatures(0, &self.initial, &remote.initial_state)?;
+
+        let local = self.sign_state(0, &self.initial)?;
+        self.latest = Some(self.signed_state(0, self.initial.clone(), local, remote.initial_state));
+        self.status = ChannelStatus::Open;
+        Ok(())
+    }
+
+    /// Propose a new state by applying a local action
+    pub fn propose(&mut self, action: A::Action) -> ChannelResult<StateUpdate<A>> {
+        self.require_status(ChannelStatus::Open)?;
+        if self.pending.is_some() {
+            return Err(ChannelError::InvalidUpdate("An update is already awaiting acknowledgement".to_string()));
+        }
+
+        let latest = self.latest_signed()?;
+        let app = latest.app.apply(self.local, &action).map_err(ChannelError::App)?;
+        let version = latest.version + 1;
+
+        let update = StateUpdate {
+            version,
+            actor: self.local,
+            action,
+            signatures: self.sign_state(version, &app)?,
+            app,
+        };
+
+        self.pending = Some(update.clone());
+        Ok(update)
+    }
+
+    /// Validate and countersign the counterparty's update, revoking the local settlement of the previous state
+    pub fn receive_update(&mut self, update: StateUpdate<A>) -> ChannelResult<UpdateAck> {
+        self.require_status(ChannelStatus::Open)?;
+        if self.pending.is_some() {
+            return Err(ChannelError::InvalidUpdate("Concurrent update; retry after the pending one settles".to_string()));
+        }
+
+        let latest = self.latest_signed()?;
+
+        if update.actor != self.local.other() {
+            return Err(ChannelError::InvalidUpdate("Update was not proposed by the counterparty".to_string()));
+        }
+        if update.version != latest.version + 1 {
+            return Err(ChannelError::InvalidUpdate(format!(
+                "Expected state {}, got {}", latest.version + 1, update.version
+            )));
+        }
+
+        // Re-run the action locally; the counterparty's claimed result must match
+        let expected = latest.app.apply(update.actor, &update.action).map_err(ChannelError::App)?;
+        if expected.encode() != update.app.encode() {
+            return Err(ChannelError::InvalidUpdate("Application state does not follow from the action".to_string()));
+        }
+
+        self.verify_remote_signatures(update.version, &update.app, &update.signatures)?;
+
+        let signatures = self.sign_state(update.version, &update.app)?;
+        let revocation = self.revocation(latest.version);
+        self.latest = Some(self.signed_state(update.version, update.app, signatures, update.signatures));
+
+        Ok(UpdateAck { version: update.version, signatures, revocation })
+    }
+
+    /// Complete a local proposal with the counterparty's countersignature
+    ///
+    /// Returns the revocation of the local settlement of the previous state, for the counterparty.
+    pub fn receive_ack(&mut self, ack: UpdateAck) -> ChannelResult<Revocation> {
+        let pending = self.pending.as_ref()
+            .ok_or_else(|| ChannelError::InvalidUpdate("No update awaiting acknowledgement".to_string()))?;
+        if ack.version != pending.version {
+            return Err(ChannelError::InvalidUpdate(format!(
+                "Acknowledgement for state {} but state {} is pending", ack.version, pending.version
+            )));
+        }
+
+        self.verify_remote_signatures(pending.version, &pending.app, &ack.signatures)?;
+        self.accept_revocation(ack.revocation, pending.version - 1)?;
+
+        let pending = self.pending.take().expect("checked above");
+        let revocation = self.revocation(pending.version - 1);
+        self.latest = Some(self.signed_state(pending.version, pending.app, pending.signatures, ack.signatures));
+        Ok(revocation)
+    }
+
+    /// Accept the proposer's revocation of the state an update replaced
+    pub fn receive_revocation(&mut self, revocation: Revocation) -> ChannelResult<()> {
+        let latest = self.latest_signed()?.version;
+        if latest == 0 {
+            return Err(ChannelError::InvalidUpdate("No state to revoke yet".to_string()));
+        }
+        self.accept_revocation(revocation, latest - 1)
+    }
+
+    /// Local signature for a cooperative close of the latest state
+    pub fn close_signature(&self, fee: u64) -> ChannelResult<schnorr::Signature> {
+        self.require_status(ChannelStatus::Open)?;
+        let latest = self.latest_signed()?;
+        Ok(self.sign(&self.params.close_sighash(latest.app.balances(), fee)?))
+    }
+
+    /// Build the fully-signed cooperative close with the counterparty's signature
+    pub fn cooperative_close(&mut self, fee: u64, remote_signature: schnorr::Signature) -> ChannelResult<Transaction> {
+        self.require_status(ChannelStatus::Open)?;
+        if self.pending.is_some() {
+            return Err(ChannelError::InvalidStatus("Cannot close with an update pending".to_string()));
+        }
+
+        let balances = self.latest_signed()?.app.balances();
+        let sighash = self.params.close_sighash(balances, fee)?;
+        verify(&sighash, &remote_signature, &self.params.key(self.local.other()), "close")?;
+
+        let (sig_a, sig_b) = self.order(self.sign(&sighash), remote_signature);
+        let mut tx = self.params.close_transaction(balances, fee)?;
+        tx.input[0].witness = multisig_witness(&self.params.multisig_script(), &sig_a, &sig_b)?;
+
+        self.status = ChannelStatus::Closed(tx.compute_txid());
+        Ok(tx)
+    }
+
+    /// Start a unilateral close, returning the signed local settlement of the latest state
+    pub fn force_close(&mut self) -> ChannelResult<Transaction> {
+        match self.status {
+            ChannelStatus::Open | ChannelStatus::ForceClosing => {},
+            ref status => return Err(ChannelError::InvalidStatus(format!("Cannot force close while {:?}", status))),
+        }
+
+        let tx = self.signed_settlement()?;
+        self.pending = None;
+        self.status = ChannelStatus::ForceClosing;
+        Ok(tx)
+    }
+
+    /// Record that a settlement transaction (from either party) confirmed at `height`
+    ///
+    /// Returns the dispute when a delayed output is left to sweep or claim.
+    pub fn settlement_confirmed(&mut self, tx: &Transaction, height: u32) -> ChannelResult<Option<&Dispute>> {
+        match self.status {
+            ChannelStatus::Open | ChannelStatus::ForceClosing => {},
+            ref status => return Err(ChannelError::InvalidStatus(format!("Unexpected settlement while {:?}", status))),
+        }
+        if tx.input.len() != 1 || tx.input[0].previous_output != self.params.funding_outpoint {
+            return Err(ChannelError::Transaction("Transaction does not spend the funding output".to_string()));
+        }
+
+        let version = tx.lock_time.to_consensus_u32().checked_sub(STATE_LOCKTIME_BASE)
+            .map(u64::from)
+            .ok_or_else(|| ChannelError::Transaction("Lock time carries no state number".to_string()))?;
+
+        // Only the local party knows its own revocation points, so anything else was broadcast remotely
+        let txid = tx.compute_txid();
+        let local_settlement = self.signed_settlement().map(|local| local.compute_txid() == txid).unwrap_or(false);
+        let (holder, revocation_point) = if local_settlement {
+            (self.local, self.revocation_point(version))
+        } else {
+            let point = self.remote_points.get(&version).copied().ok_or_else(|| {
+                ChannelError::Transaction(format!("No revocation point for counterparty state {}", version))
+            })?;
+            (self.local.other(), point)
+        };
+
+        self.pending = None;
+        let delayed = self.params.delayed_script_pubkey(holder, &revocation_point)?;
+        let delayed_output = tx.output.iter().enumerate().find(|(_, output)| output.script_pubkey == delayed);
+        let revoked = holder != self.local && self.remote_secrets.contains_key(&version);
+
+        // With nothing left to sweep or claim the channel is closed by the settlement itself
+        let (vout, output) = match delayed_output {
+            Some(found) if holder == self.local || revoked => found,
+            _ => {
+                self.status = ChannelStatus::Closed(txid);
+                return Ok(None);
+            },
+        };
+
+        self.dispute = Some(Dispute {
+            settlement_txid: txid,
+            holder,
+            version,
+            started_at: height,
+            deadline: height + self.params.config.dispute_window as u32,
+            delayed_outpoint: OutPoint { txid, vout: vout as u32 },
+            delayed_value: output.value.to_sat(),
+            revocation_point,
+            revoked,
+        });
+        self.status = ChannelStatus::Disputing;
+        Ok(self.dispute.as_ref())
+    }
+
+    /// Claim the counterparty's delayed output from a revoked settlement
+    pub fn penalty_transaction(&mut self) -> ChannelResult<Transaction> {
+        let dispute = self.dispute.as_ref()
+            .ok_or_else(|| ChannelError::InvalidStatus("No dispute in progress".to_string()))?;
+        if !dispute.revoked {
+            return Err(ChannelError::InvalidStatus("Settled state was not revoked".to_string()));
+        }
+
+        let secret = self.remote_secrets[&dispute.version];
+        let script = self.params.revocation_script(dispute.holder, &dispute.revocation_point);
+        let mut tx = self.claim_transaction(dispute, Sequence::MAX)?;
+        let sighash = script_path_sighash(&tx, &self.delayed_prevout(dispute)?, &script)?;
+        let revocation_keypair = Keypair::from_secret_key(&Secp256k1::new(), &secret);
+
+        // `<R> CHECKSIGVERIFY <local> CHECKSIG`: the local signature sits below the revocation one
+        let control_block = self.delayed_control_block(dispute, &script)?;
+        let mut witness = Witness::new();
+        witness.push(self.sign(&sighash).as_ref());
+        witness.push(Secp256k1::new().sign_schnorr_no_aux_rand(&sighash, &revocation_keypair).as_ref());
+        witness.push(script.as_bytes());
+        witness.push(control_block.serialize());
+        tx.input[0].witness = witness;
+
+        self.status = ChannelStatus::Closed(tx.compute_txid());
+        Ok(tx)
+    }
+
+    /// Sweep the local delayed output of a unilateral close, once the dispute window has passed
+    pub fn sweep_transaction(&mut self, height: u32) -> ChannelResult<Transaction> {
+        let dispute = self.dispute.as_ref()
+            .ok_or_else(|| ChannelError::InvalidStatus("No dispute in progress".to_string()))?;
+        if dispute.holder != self.local {
+            return Err(ChannelError::InvalidStatus("Only the closing party's delayed output can be swept".to_string()));
+        }
+        if height < dispute.deadline {
+            return Err(ChannelError::InvalidStatus(format!(
+                "Dispute window open until height {}", dispute.deadline
+            )));
+        }
+
+        let script = self.params.delayed_script(self.local);
+        let mut tx = self.claim_transaction(dispute, Sequence::from_height(self.params.config.dispute_window))?;
+        let sighash = script_path_sighash(&tx, &self.delayed_prevout(dispute)?, &script)?;
+
+        let control_block = self.delayed_control_block(dispute, &script)?;
+        let mut witness = Witness::new();
+        witness.push(self.sign(&sighash).as_ref());
+        witness.push(script.as_bytes());
+        witness.push(control_block.serialize());
+        tx.input[0].witness = witness;
+
+        self.status = ChannelStatus::Closed(tx.compute_txid());
+        Ok(tx)
+    }
+
+    fn require_status(&self, expected: ChannelStatus) -> ChannelResult<()> {
+        if self.status != expected {
+            return Err(ChannelError::InvalidStatus(format!("Expected {:?}, channel is {:?}", expected, self.status)));
+        }
+        Ok(())
+    }
+
+    fn latest_signed(&self) -> ChannelResult<&SignedState<A>> {
+        self.latest.as_ref().ok_or_else(|| ChannelError::InvalidStatus("Channel is not open".to_string()))
+    }
+
+    fn sign(&self, message: &Message) -> schnorr::Signature {
+        Secp256k1::new().sign_schnorr_no_aux_rand(message, &self.keypair)
+    }
+
+    /// Local revocation secret of state `version`, derived from the channel key
+    fn revocation_secret(&self, version: u64) -> SecretKey {
+        let tag = sha256::Hash::hash(b"anya/state-channel/revocation");
+        let mut engine = sha256::Hash::engine();
+        engine.input(tag.as_ref());
+        engine.input(tag.as_ref());
+        engine.input(&self.keypair.secret_bytes());
+        engine.input(&self.params.channel_id());
+        engine.input(&version.to_be_bytes());
+        SecretKey::from_slice(sha256::Hash::from_engine(engine).as_ref()).expect("hash is a valid secret key")
+    }
+
+    fn revocation_point(&self, version: u64) -> XOnlyPublicKey {
+        Keypair::from_secret_key(&Secp256k1::new(), &self.revocation_secret(version)).x_only_public_key().0
+    }
+
+    fn revocation(&self, version: u64) -> Revocation {
+        Revocation {
+            version,
+            secret: self.revocation_secret(version),
+            next_point: self.revocation_point(version + 2),
+        }
+    }
+
+    /// Checks the counterparty's revocation of state `expected` and stores its next point
+    fn accept_revocation(&mut self, revocation: Revocation, expected: u64) -> ChannelResult<()> {
+        if revocation.version != expected {
+            return Err(ChannelError::InvalidUpdate(format!(
+                "Expected revocation of state {}, got {}", expected, revocation.version
+            )));
+        }
+
+        let point = self.remote_points.get(&expected)
+            .ok_or_else(|| ChannelError::InvalidUpdate(format!("No revocation point for state {}", expected)))?;
+        let revealed = Keypair::from_secret_key(&Secp256k1::new(), &revocation.secret).x_only_public_key().0;
+        if revealed != *point {
+            return Err(ChannelError::InvalidSignature(format!("Revocation secret does not match state {}", expected)));
+        }
+
+        self.remote_secrets.insert(expected, revocation.secret);
+        self.remote_points.insert(expected + 2, revocation.next_point);
+        Ok(())
+    }
+
+    /// Signs the state digest and the counterparty's settlement of the state
+    fn sign_state(&self, version: u64, app: &A) -> ChannelResult<StateSignatures> {
+        let remote_point = self.remote_points.get(&version).ok_or_else(|| {
+            ChannelError::InvalidUpdate(format!("Counterparty has not revoked the state before {}", version))
+        })?;
+
+        let balances = app.balances();
+        let digest = self.params.state_digest(version, &app.encode(), balances);
+        let settlement = self.params.settlement_sighash(self.local.other(), version, balances, remote_point)?;
+        Ok(StateSignatures {
+            state: self.sign(&Message::from_digest(digest)),
+            settlement: self.sign(&settlement),
+        })
+    }
+
+    /// Checks the counterparty's signatures over a state and the local settlement of it
+    fn verify_remote_signatures(&self, version: u64, app: &A, signatures: &StateSignatures) -> ChannelResult<()> {
+        let balances = app.balances();
+        let key = self.params.key(self.local.other());
+        let digest = self.params.state_digest(version, &app.encode(), balances);
+        let settlement = self.params.settlement_sighash(self.local, version, balances, &self.revocation_point(version))?;
+
+        verify(&Message::from_digest(digest), &signatures.state, &key, "state")?;
+        verify(&settlement, &signatures.settlement, &key, "settlement")
+    }
+
+    /// Fully-signed local settlement of the latest state
+    fn signed_settlement(&self) -> ChannelResult<Transaction> {
+        let latest = self.latest_signed()?;
+        let balances = latest.app.balances();
+        let point = self.revocation_point(latest.version);
+
+        let local = self.sign(&self.params.settlement_sighash(self.local, latest.version, balances, &point)?);
+        let remote = match self.local {
+            Party::A => latest.signatures_b.settlement,
+            Party::B => latest.signatures_a.settlement,
+        };
+        let (sig_a, sig_b) = self.order(local, remote);
+
+        let mut tx = self.params.settlement_transaction(self.local, latest.version, balances, &point)?;
+        tx.input[0].witness = multisig_witness(&self.params.multisig_script(), &sig_a, &sig_b)?;
+        Ok(tx)
+    }
+
+    /// Unsigned transaction paying the disputed delayed output to the local key
+    fn claim_transaction(&self, dispute: &Dispute, sequence: Sequence) -> ChannelResult<Transaction> {
+        let value = dispute.delayed_value.checked_sub(self.params.config.sweep_fee)
+            .filter(|value| *value >= DUST_LIMIT)
+            .ok_or_else(|| ChannelError::Transaction("Delayed output does not cover the sweep fee".to_string()))?;
+
+        let secp = Secp256k1::verification_only();
+        Ok(Transaction {
+            version: Version::TWO,
+            lock_time: LockTime::ZERO,
+            input: vec![unsigned_input(dispute.delayed_outpoint, sequence)],
+            output: vec![TxOut {
+                value: Amount::from_sat(value),
+                script_pubkey: ScriptBuf::new_p2tr(&secp, self.params.key(self.local), None),
+            }],
+        })
+    }
+
+    fn delayed_prevout(&self, dispute: &Dispute) -> ChannelResult<TxOut> {
+        Ok(TxOut {
+            value: Amount::from_sat(dispute.delayed_value),
+            script_pubkey: self.params.delayed_script_pubkey(dispute.holder, &dispute.revocation_point)?,
+        })
+    }
+
+    fn delayed_control_block(&self, dispute: &Dispute, script: &ScriptBuf) -> ChannelResult<ControlBlock> {
+        self.params.delayed_spend_info(dispute.holder, &dispute.revocation_point)?
+            .control_block(&(script.clone(), LeafVersion::TapScript))
+            .ok_or_else(|| ChannelError::Transaction("Missing control block".to_string()))
+    }
+
+    /// Orders local and remote values as (A, B)
+    fn order<T>(&self, local: T, remote: T) -> (T, T) {
+        match self.local {
+            Party::A => (local, remote),
+            Party::B => (remote, local),
+        }
+    }
+
+    fn signed_state(&self, version: u64, app: A, local: StateSignatures, remote: StateSignatures) -> SignedState<A> {
+        let (signatures_a, signatures_b) = self.order(local, remote);
+        SignedState { version, app, signatures_a, signatures_b }
+    }
+}
+
+fn verify(message: &Message, signature: &schnorr::Signature, key: &XOnlyPublicKey, what: &str) -> ChannelResult<()> {
+    Secp256k1::verification_only()
+        .verify_schnorr(signature, message, key)
+        .map_err(|_| ChannelError::InvalidSignature(format!("Bad {} signature", what)))
+}
+
+fn nums_key() -> XOnlyPublicKey {
+    XOnlyPublicKey::from_slice(&NUMS_INTERNAL_KEY).expect("valid NUMS point")
+}
+
+/// Taproot output with a single script leaf and the NUMS internal key
+fn single_leaf(script: ScriptBuf) -> ChannelResult<(TaprootSpendInfo, ControlBlock)> {
+    let secp = Secp256k1::verification_only();
+    let spend_info = TaprootBuilder::new()
+        .add_leaf(0, script.clone())
+        .map_err(|e| ChannelError::Transaction(e.to_string()))?
+        .finalize(&secp, nums_key())
+        .map_err(|_| ChannelError::Transaction("Failed to finalize taproot tree".to_string()))?;
+
+    let control_block = spend_info.control_block(&(script, LeafVersion::TapScript))
+        .ok_or_else(|| ChannelError::Transaction("Missing control block".to_string()))?;
+    Ok((spend_info, control_block))
+}
+
+fn unsigned_input(previous_output: OutPoint, sequence: Sequence) -> TxIn {
+    TxIn {
+        previous_output,
+        script_sig: ScriptBuf::new(),
+        sequence,
+        witness: Witness::new(),
+    }
+}
+
+fn script_path_sighash(tx: &Transaction, prevout: &TxOut, script: &ScriptBuf) -> ChannelResult<Message> {
+    let leaf_hash = TapLeafHash::from_script(script, LeafVersion::TapScript);
+    let sighash = SighashCache::new(tx)
+        .taproot_script_spend_signature_hash(0, &Prevouts::All(&[prevout]), leaf_hash, TapSighashType::Default)
+        .map_err(|e| ChannelError::Transaction(e.to_string()))?;
+    Ok(Message::from_digest(sighash.to_byte_array()))
+}
+
+/// Script-path witness for `<A> CHECKSIGVERIFY <B> CHECKSIG`: B's signature sits below A's
+fn multisig_witness(script: &ScriptBuf, sig_a: &schnorr::Signature, sig_b: &schnorr::Signature) -> ChannelResult<Witness> {
+    let (_, control_block) = single_leaf(script.clone())?;
+    let mut witness = Witness::new();
+    witness.push(sig_b.as_ref());
+    witness.push(sig_a.as_ref());
+    witness.push(script.as_bytes());
+    witness.push(control_block.serialize());
+    Ok(witness)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::app::{Move, Payment, PaymentLedger, TicTacToe};
+
+    fn keypair(byte: u8) -> Keypair {
+        Keypair::from_seckey_slice(&Secp256k1::new(), &[byte; 32]).unwrap()
+    }
+
+    fn open<A: ChannelApp>(app: A) -> (StateChannel<A>, StateChannel<A>) {
+        let (alice, bob) = (keypair(1), keypair(2));
+        let params = ChannelParams {
+            funding_outpoint: OutPoint { txid: Txid::from_byte_array([7u8; 32]), vout: 0 },
+            capacity: 1_000_000,
+            key_a: alice.x_only_public_key().0,
+            key_b: bob.x_only_public_key().0,
+            config: ChannelConfig::default(),
+        };
+
+        let mut a = StateChannel::new(params.clone(), Party::A, alice, app.clone()).unwrap();
+        let mut b = StateChannel::new(params, Party::B, bob, app).unwrap();
+
+        let sigs_a = a.open_signatures(b.initial_revocation_points()).unwrap();
+        let sigs_b = b.open_signatures(a.initial_revocation_points()).unwrap();
+        a.accept_open(sigs_b).unwrap();
+        b.accept_open(sigs_a).unwrap();
+        (a, b)
+    }
+
+    fn update<A: ChannelApp>(from: &mut StateChannel<A>, to: &mut StateChannel<A>, action: A::Action) {
+        let proposal = from.propose(action).unwrap();
+        let ack = to.receive_update(proposal).unwrap();
+        let revocation = from.receive_ack(ack).unwrap();
+        to.receive_revocation(revocation).unwrap();
+    }
+
+    /// Checks every signature in a script-path witness against `script`
+    fn assert_signed(tx: &Transaction, prevout: &TxOut, script: &ScriptBuf, keys: &[XOnlyPublicKey]) {
+        let sighash = script_path_sighash(tx, prevout, script).unwrap();
+        let witness: Vec<&[u8]> = tx.input[0].witness.iter().collect();
+        assert_eq!(witness.len(), keys.len() + 2);
+        assert_eq!(witness[keys.len()], script.as_bytes());
+        for (signature, key) in witness.iter().zip(keys) {
+            let signature = schnorr::Signature::from_slice(signature).unwrap();
+            verify(&sighash, &signature, key, "witness").unwrap();
+        }
+    }
+
+    #[test]
+    fn test_payments_and_cooperative_close() {
+        let (mut a, mut b) = open(PaymentLedger::new(600_000, 400_000));
+
+        update(&mut a, &mut b, Payment { amount: 100_000 });
+        update(&mut b, &mut a, Payment { amount: 25_000 });
+        assert_eq!(a.latest_state().unwrap().version, 2);
+        assert_eq!(b.latest_state().unwrap().app.balances(), (525_000, 475_000));
+
+        // Overspending is rejected by the application
+        assert!(matches!(a.propose(Payment { amount: 2_000_000 }), Err(ChannelError::App(_))));
+
+        let sig_b = b.close_signature(1_000).unwrap();
+        let tx = a.cooperative_close(1_000, sig_b).unwrap();
+        assert_eq!(tx.output[0].value.to_sat(), 524_500);
+        assert_eq!(tx.output[1].value.to_sat(), 474_500);
+        assert_eq!(tx.input[0].witness.len(), 4);
+        assert!(matches!(a.status(), ChannelStatus::Closed(_)));
+    }
+
+    #[test]
+    fn test_tampered_update_is_rejected() {
+        let (mut a, mut b) = open(PaymentLedger::new(600_000, 400_000));
+
+        // Claiming a different result than the action produces
+        let mut proposal = a.propose(Payment { amount: 1_000 }).unwrap();
+        proposal.app = PaymentLedger::new(500_000, 500_000);
+        assert!(matches!(b.receive_update(proposal), Err(ChannelError::InvalidUpdate(_))));
+
+        // A revocation secret that does not open the committed point
+        let (mut a, mut b) = open(PaymentLedger::new(600_000, 400_000));
+        let ack = b.receive_update(a.propose(Payment { amount: 1_000 }).unwrap()).unwrap();
+        let mut revocation = a.receive_ack(ack).unwrap();
+        revocation.secret = SecretKey::from_slice(&[3u8; 32]).unwrap();
+        assert!(matches!(b.receive_revocation(revocation), Err(ChannelError::InvalidSignature(_))));
+    }
+
+    #[test]
+    fn test_unilateral_close_and_sweep() {
+        let (mut a, mut b) = open(PaymentLedger::new(600_000, 400_000));
+        update(&mut a, &mut b, Payment { amount: 100_000 });
+
+        let settlement = a.force_close().unwrap();
+        assert_eq!(settlement.input[0].previous_output, a.params().funding_outpoint);
+        assert_eq!(settlement.lock_time.to_consensus_u32(), STATE_LOCKTIME_BASE + 1);
+        assert_eq!(settlement.output[0].value.to_sat(), 500_000 - 250);
+        assert_eq!(settlement.output[1].value.to_sat(), 500_000 - 250);
+        assert_signed(&settlement, &a.params().funding_output().unwrap(), &a.params().multisig_script(), &[
+            a.params().key_b,
+            a.params().key_a,
+        ]);
+
+        // B is paid directly by A's current state, so there is nothing for it to do
+        assert!(b.settlement_confirmed(&settlement, 800_000).unwrap().is_none());
+        assert!(matches!(b.status(), ChannelStatus::Closed(_)));
+
+        let dispute = a.settlement_confirmed(&settlement, 800_000).unwrap().unwrap().clone();
+        assert_eq!(dispute.holder, Party::A);
+        assert!(!dispute.revoked);
+        assert!(a.sweep_transaction(800_100).is_err());
+        assert!(a.penalty_transaction().is_err());
+
+        let sweep = a.sweep_transaction(800_144).unwrap();
+        assert_eq!(sweep.input[0].previous_output, dispute.delayed_outpoint);
+        assert_eq!(sweep.input[0].sequence, Sequence::from_height(144));
+        assert_eq!(sweep.output[0].value.to_sat(), 500_000 - 250 - 300);
+        assert_signed(&sweep, &a.delayed_prevout(&dispute).unwrap(), &a.params().delayed_script(Party::A), &[
+            a.params().key_a,
+        ]);
+        assert!(matches!(a.status(), ChannelStatus::Closed(_)));
+    }
+
+    #[test]
+    fn test_revoked_settlement_is_penalized() {
+        let (mut a, mut b) = open(TicTacToe::new(500_000, 500_000, 100_000).unwrap());
+
+        // A plays 0, 1, 2 and wins; B plays 3, 4
+        for (mover, cell) in [(Party::A, 0), (Party::B, 3), (Party::A, 1), (Party::B, 4)] {
+            match mover {
+                Party::A => update(&mut a, &mut b, Move { cell }),
+                Party::B => update(&mut b, &mut a, Move { cell }),
+            }
+        }
+        let before_loss = b.signed_settlement().unwrap();
+        update(&mut a, &mut b, Move { cell: 2 });
+        assert_eq!(a.latest_state().unwrap().app.winner(), Some(Party::A));
+
+        // B broadcasts its settlement from before the loss; the state is revoked, so A takes B's share
+        assert_signed(&before_loss, &a.params().funding_output().unwrap(), &a.params().multisig_script(), &[
+            a.params().key_b,
+            a.params().key_a,
+        ]);
+        let dispute = a.settlement_confirmed(&before_loss, 800_000).unwrap().unwrap().clone();
+        assert_eq!(dispute.holder, Party::B);
+        assert_eq!(dispute.version, 4);
+        assert!(dispute.revoked);
+        assert!(a.sweep_transaction(800_144).is_err());
+
+        let penalty = a.penalty_transaction().unwrap();
+        assert_eq!(penalty.input[0].previous_output, dispute.delayed_outpoint);
+        assert_eq!(penalty.output[0].value, Amount::from_sat(dispute.delayed_value - 300));
+        assert_eq!(penalty.output[0].script_pubkey, ScriptBuf::new_p2tr(&Secp256k1::new(), a.params().key_a, None));
+        let script = a.params().revocation_script(Party::B, &dispute.revocation_point);
+        assert_signed(&penalty, &a.delayed_prevout(&dispute).unwrap(), &script, &[
+            a.params().key_a,
+            dispute.revocation_point,
+        ]);
+    }
+}
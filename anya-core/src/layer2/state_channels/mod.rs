@@ -1,6 +1,36 @@
+//! Two-party state channels
+//!
+//! [`channel::StateChannel`] runs one party's side of a channel over any
+//! [`app::ChannelApp`]. `StateChannelsProtocol` tracks channels for the Layer 2
+//! manager and relays their on-chain transactions.
+//!
+//! Without `SIGHASH_ANYPREVOUT` a settlement transaction cannot be made to
+//! replace an older one on-chain, so old states are revoked instead: whoever
+//! watches a channel must claim the delayed output of a revoked settlement
+//! before the dispute window ends.
+
+pub mod app;
+pub mod channel;
+
+pub use app::{ChannelApp, Party, PaymentLedger, Payment, TicTacToe, Move};
+pub use channel::{
+    ChannelConfig, ChannelError, ChannelParams, ChannelResult, ChannelStatus, Dispute,
+    OpenSignatures, Revocation, SignedState, StateChannel, StateSignatures, StateUpdate, UpdateAck,
+};
+
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, RwLock};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use bitcoin::consensus::encode::deserialize;
+use bitcoin::hashes::{sha256, Hash, HashEngine};
+use bitcoin::{OutPoint, Transaction, Txid};
+
 use crate::{
     AnyaError,
     AnyaResult,
+    bitcoin::wallet::ChainSource,
     layer2::{
         Layer2Protocol,
         ProtocolState,
@@ -11,22 +41,141 @@
         Proof,
         VerificationResult,
         ValidationResult,
+        proof::{ProofVerifier, WithProofVerifier},
     },
 };
 use async_trait::async_trait;
-use tracing::{info, error, warn};
+use tracing::{info, warn};
+
+/// Summary of a tracked channel
+#[derive(Debug, Clone)]
+pub struct ChannelSummary {
+    /// Channel parameters
+    pub params: ChannelParams,
+    /// Latest fully-signed state number
+    pub version: u64,
+    /// Digest of the latest fully-signed state
+    pub state_digest: [u8; 32],
+    /// Latest balances as (party A, party B)
+    pub balances: (u64, u64),
+    /// Channel status
+    pub status: ChannelStatus,
+    /// Confirmed settlement whose delayed output is still to be swept or claimed
+    pub disputed_settlement: Option<Txid>,
+}
 
 pub struct StateChannelsProtocol {
-    initialized: bool,
-    connected: bool,
+    initialized: AtomicBool,
+    connected: AtomicBool,
+    channels: RwLock<HashMap<[u8; 32], ChannelSummary>>,
+    transactions: RwLock<HashMap<String, TransactionStatus>>,
+    proof_verifier: ProofVerifier,
+    chain: Option<Arc<dyn ChainSource>>,
+}
+
+impl Default for StateChannelsProtocol {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 impl StateChannelsProtocol {
     pub fn new() -> Self {
         Self {
-            initialized: false,
-            connected: false,
+            initialized: AtomicBool::new(false),
+            connected: AtomicBool::new(false),
+            channels: RwLock::new(HashMap::new()),
+            transactions: RwLock::new(HashMap::new()),
+            proof_verifier: ProofVerifier::default(),
+            chain: None,
+        }
+    }
+
+    /// Relay transactions through `chain`
+    pub fn with_chain(mut self, chain: Arc<dyn ChainSource>) -> Self {
+        self.chain = Some(chain);
+        self
+    }
+
+    /// Track a channel, or refresh its summary after updates
+    pub fn track_channel<A: ChannelApp>(&self, channel: &StateChannel<A>) {
+        let params = channel.params().clone();
+        let (version, state_digest, balances) = match channel.latest_state() {
+            Some(state) => {
+                let balances = state.app.balances();
+                (state.version, params.state_digest(state.version, &state.app.encode(), balances), balances)
+            },
+            None => (0, [0u8; 32], (0, 0)),
+        };
+
+        let summary = ChannelSummary {
+            version,
+            state_digest,
+            balances,
+            status: channel.status().clone(),
+            disputed_settlement: channel.dispute().map(|dispute| dispute.settlement_txid),
+            params,
+        };
+        self.channels.write().unwrap().insert(summary.params.channel_id(), summary);
+    }
+
+    /// Summary of a tracked channel
+    pub fn channel(&self, channel_id: &[u8; 32]) -> Option<ChannelSummary> {
+        self.channels.read().unwrap().get(channel_id).cloned()
+    }
+
+    /// All tracked channels
+    pub fn channels(&self) -> Vec<ChannelSummary> {
+        self.channels.read().unwrap().values().cloned().collect()
+    }
+
+    /// Record the on-chain status of a relayed transaction
+    pub fn set_transaction_status(&self, txid: &str, status: TransactionStatus) -> AnyaResult<()> {
+        match self.transactions.write().unwrap().get_mut(txid) {
+            Some(entry) => {
+                *entry = status;
+                Ok(())
+            },
+            None => Err(AnyaError::System(format!("Unknown state channel transaction {}", txid))),
+        }
+    }
+
+    /// Whether `outpoint` is a tracked channel's funding output or an output of its disputed settlement
+    fn is_channel_output(&self, outpoint: &OutPoint) -> bool {
+        self.channels.read().unwrap().values().any(|summary| {
+            summary.params.funding_outpoint == *outpoint
+                || summary.disputed_settlement == Some(outpoint.txid)
+        })
+    }
+
+    fn ensure_connected(&self) -> AnyaResult<()> {
+        if !self.connected.load(Ordering::SeqCst) {
+            return Err(AnyaError::System("State Channels protocol is not connected".to_string()));
+        }
+        Ok(())
+    }
+
+    /// Digest over all tracked channel states, in channel ID order
+    fn state_commitment(&self) -> (u64, [u8; 32]) {
+        let channels = self.channels.read().unwrap();
+        let mut ids: Vec<&[u8; 32]> = channels.keys().collect();
+        ids.sort();
+
+        let mut engine = sha256::Hash::engine();
+        let mut updates = 0u64;
+        for id in ids {
+            let summary = &channels[id];
+            engine.input(id);
+            engine.input(&summary.state_digest);
+            updates += summary.version;
         }
+        (updates, sha256::Hash::from_engine(engine).to_byte_array())
+    }
+}
+
+impl WithProofVerifier for StateChannelsProtocol {
+    fn proof_verifier_mut(&mut self) -> &mut ProofVerifier {
+        &mut self.proof_verifier
     }
 }
 
@@ -34,67 +183,183 @@ pub fn new() -> Self {
 impl Layer2Protocol for StateChannelsProtocol {
     async fn initialize(&self) -> AnyaResult<()> {
         info!("Initializing State Channels protocol...");
-        // TODO: Implement actual initialization
+        self.initialized.store(true, Ordering::SeqCst);
         Ok(())
     }
 
     async fn connect(&self) -> AnyaResult<()> {
         info!("Connecting to State Channels network...");
-        // TODO: Implement actual connection
+        if !self.initialized.load(Ordering::SeqCst) {
+            return Err(AnyaError::System("State Channels protocol is not initialized".to_string()));
+        }
+        self.connected.store(true, Ordering::SeqCst);
         Ok(())
     }
 
     async fn disconnect(&self) -> AnyaResult<()> {
         info!("Disconnecting from State Channels network...");
-        // TODO: Implement actual disconnection
+        self.connected.store(false, Ordering::SeqCst);
         Ok(())
     }
 
     async fn submit_transaction(&self, tx: &[u8]) -> AnyaResult<String> {
         info!("Submitting State Channels transaction...");
-        // TODO: Implement actual transaction submission
-        Ok("state_channels_tx_123".to_string())
+        self.ensure_connected()?;
+
+        let tx: Transaction = deserialize(tx)
+            .map_err(|e| AnyaError::System(format!("Invalid transaction: {}", e)))?;
+
+        // Only funding and settlement outputs of tracked channels are relayed
+        if !tx.input.iter().any(|input| self.is_channel_output(&input.previous_output)) {
+            warn!("Rejecting transaction that spends no tracked channel output");
+            return Err(AnyaError::System("Transaction does not spend a tracked channel".to_string()));
+        }
+
+        let chain = self.chain.as_ref()
+            .ok_or_else(|| AnyaError::System("No chain source to relay through".to_string()))?;
+        let txid = chain.broadcast(&tx)
+            .map_err(|e| AnyaError::System(format!("Failed to relay transaction: {}", e)))?
+            .to_string();
+        self.transactions.write().unwrap().insert(txid.clone(), TransactionStatus::Pending);
+        Ok(txid)
     }
 
     async fn get_transaction_status(&self, tx_id: &str) -> AnyaResult<TransactionStatus> {
         info!("Getting State Channels transaction status...");
-        // TODO: Implement actual status check
-        Ok(TransactionStatus::Confirmed)
+        self.transactions.read().unwrap().get(tx_id).cloned()
+            .ok_or_else(|| AnyaError::System(format!("Unknown state channel transaction {}", tx_id)))
     }
 
     async fn get_state(&self) -> AnyaResult<ProtocolState> {
         info!("Getting State Channels state...");
-        // TODO: Implement actual state retrieval
-        Ok(ProtocolState::default())
+        let (updates, commitment) = self.state_commitment();
+        Ok(ProtocolState {
+            height: updates,
+            hash: hex::encode(commitment),
+            timestamp: SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_secs(),
+        })
     }
 
     async fn sync_state(&self) -> AnyaResult<()> {
         info!("Syncing State Channels state...");
-        // TODO: Implement actual state sync
-        Ok(())
+        // Channel summaries are pushed by `track_channel`; nothing to pull
+        self.ensure_connected()
     }
 
-    async fn issue_asset(&self, params: AssetParams) -> AnyaResult<String> {
+    async fn issue_asset(&self, _params: AssetParams) -> AnyaResult<String> {
         info!("Issuing State Channels asset...");
-        // TODO: Implement actual asset issuance
-        Ok("state_channels_asset_123".to_string())
+        Err(AnyaError::System("State channels do not issue assets".to_string()))
     }
 
-    async fn transfer_asset(&self, transfer: AssetTransfer) -> AnyaResult<TransferResult> {
+    async fn transfer_asset(&self, _transfer: AssetTransfer) -> AnyaResult<TransferResult> {
         info!("Transferring State Channels asset...");
-        // TODO: Implement actual asset transfer
-        Ok(TransferResult::default())
+        Err(AnyaError::System("Asset transfers are made through channel updates".to_string()))
     }
 
     async fn verify_proof(&self, proof: &Proof) -> AnyaResult<VerificationResult> {
         info!("Verifying State Channels proof...");
-        // TODO: Implement actual proof verification
-        Ok(VerificationResult::default())
+        // Only transactions relayed for a tracked channel are of interest; a valid proof confirms them
+        let result = self.proof_verifier.verify_with(proof, |inclusion| {
+            let txid = inclusion.txid.to_string();
+            match self.transactions.write().unwrap().get_mut(&txid) {
+                Some(status) => {
+                    *status = TransactionStatus::Confirmed;
+                    Ok(())
+                },
+                None => Err(format!("Transaction {} was not relayed for a tracked channel", txid)),
+            }
+        });
+        Ok(result)
     }
 
     async fn validate_state(&self, state: &ProtocolState) -> AnyaResult<ValidationResult> {
         info!("Validating State Channels state...");
-        // TODO: Implement actual state validation
-        Ok(ValidationResult::default())
+        let (updates, commitment) = self.state_commitment();
+        if state.height != updates || state.hash != hex::encode(commitment) {
+            return Ok(ValidationResult {
+                valid: false,
+                error: Some("State does not match tracked channels".to_string()),
+            });
+        }
+        Ok(ValidationResult { valid: true, error: None })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+    use bitcoin::consensus::encode::serialize;
+    use bitcoin::secp256k1::{Keypair, Secp256k1};
+    use bitcoin::Script;
+    use crate::bitcoin::error::BitcoinResult;
+    use crate::bitcoin::wallet::watch_only::ScriptHistory;
+
+    #[derive(Default)]
+    struct MockChain {
+        broadcast: Mutex<Vec<Transaction>>,
     }
-} 
\ No newline at end of file
+
+    impl ChainSource for MockChain {
+        fn script_history(&self, _script_pubkey: &Script) -> BitcoinResult<ScriptHistory> {
+            Ok(Vec::new())
+        }
+
+        fn broadcast(&self, tx: &Transaction) -> BitcoinResult<Txid> {
+            self.broadcast.lock().unwrap().push(tx.clone());
+            Ok(tx.compute_txid())
+        }
+    }
+
+    #[tokio::test]
+    async fn test_relays_only_channel_transactions() {
+        let secp = Secp256k1::new();
+        let alice = Keypair::from_seckey_slice(&secp, &[1u8; 32]).unwrap();
+        let bob = Keypair::from_seckey_slice(&secp, &[2u8; 32]).unwrap();
+        let params = ChannelParams {
+            funding_outpoint: OutPoint { txid: Txid::from_byte_array([9u8; 32]), vout: 1 },
+            capacity: 100_000,
+            key_a: alice.x_only_public_key().0,
+            key_b: bob.x_only_public_key().0,
+            config: ChannelConfig::default(),
+        };
+
+        let mut a = StateChannel::new(params.clone(), Party::A, alice, PaymentLedger::new(50_000, 50_000)).unwrap();
+        let mut b = StateChannel::new(params.clone(), Party::B, bob, PaymentLedger::new(50_000, 50_000)).unwrap();
+        let sigs_b = b.open_signatures(a.initial_revocation_points()).unwrap();
+        b.accept_open(a.open_signatures(b.initial_revocation_points()).unwrap()).unwrap();
+        a.accept_open(sigs_b).unwrap();
+
+        let chain = Arc::new(MockChain::default());
+        let protocol = StateChannelsProtocol::new().with_chain(chain.clone());
+        protocol.initialize().await.unwrap();
+        protocol.connect().await.unwrap();
+        protocol.track_channel(&a);
+
+        let state = protocol.get_state().await.unwrap();
+        assert!(protocol.validate_state(&state).await.unwrap().valid);
+
+        let settlement = a.force_close().unwrap();
+        let txid = protocol.submit_transaction(&serialize(&settlement)).await.unwrap();
+        assert!(matches!(protocol.get_transaction_status(&txid).await.unwrap(), TransactionStatus::Pending));
+        assert_eq!(chain.broadcast.lock().unwrap()[0], settlement);
+
+        // Once the settlement confirms, sweeping its delayed output is relayed too
+        a.settlement_confirmed(&settlement, 800_000).unwrap();
+        protocol.track_channel(&a);
+        let sweep = a.sweep_transaction(800_144).unwrap();
+        protocol.submit_transaction(&serialize(&sweep)).await.unwrap();
+        assert_eq!(chain.broadcast.lock().unwrap().len(), 2);
+
+        let mut unrelated = settlement.clone();
+        unrelated.input[0].previous_output.vout = 0;
+        assert!(protocol.submit_transaction(&serialize(&unrelated)).await.is_err());
+
+        // Without a chain source nothing can be relayed
+        let offline = StateChannelsProtocol::new();
+        offline.initialize().await.unwrap();
+        offline.connect().await.unwrap();
+        offline.track_channel(&a);
+        assert!(offline.submit_transaction(&serialize(&settlement)).await.is_err());
+    }
+}
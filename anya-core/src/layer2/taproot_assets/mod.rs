@@ -11,14 +11,21 @@
         Proof,
         VerificationResult,
         ValidationResult,
+        proof::{ProofVerifier, WithProofVerifier},
     },
 };
 use async_trait::async_trait;
 use tracing::{info, error, warn};
 
+pub mod universe;
+
+use universe::UniverseServer;
+
 pub struct TaprootAssetsProtocol {
     initialized: bool,
     connected: bool,
+    proof_verifier: ProofVerifier,
+    universe: UniverseServer,
 }
 
 impl TaprootAssetsProtocol {
@@ -26,8 +33,36 @@ pub fn new() -> Self {
         Self {
             initialized: false,
             connected: false,
+            proof_verifier: ProofVerifier::default(),
+            universe: UniverseServer::default(),
         }
     }
+
+    /// Use an existing universe, e.g. the one mounted by the API server
+    pub fn with_universe(mut self, universe: UniverseServer) -> Self {
+        self.universe = universe;
+        self
+    }
+
+    /// Local universe of asset proofs
+    pub fn universe(&self) -> &UniverseServer {
+        &self.universe
+    }
+}
+
+impl WithProofVerifier for TaprootAssetsProtocol {
+    fn proof_verifier_mut(&mut self) -> &mut ProofVerifier {
+        &mut self.proof_verifier
+    }
+
+    /// Use a shared proof verifier
+    ///
+    /// Starts a fresh universe checked against the verifier's chain.
+    fn with_proof_verifier(mut self, proof_verifier: ProofVerifier) -> Self {
+        self.universe = UniverseServer::new(proof_verifier.clone());
+        self.proof_verifier = proof_verifier;
+        self
+    }
 }
 
 #[async_trait]
@@ -88,13 +123,14 @@ async fn transfer_asset(&self, transfer: AssetTransfer) -> AnyaResult<TransferRe
 
     async fn verify_proof(&self, proof: &Proof) -> AnyaResult<VerificationResult> {
         info!("Verifying Taproot Assets proof...");
-        // TODO: Implement actual proof verification
-        Ok(VerificationResult::default())
+        // The proven transaction must anchor an asset commitment published in the universe
+        Ok(self.proof_verifier.verify_with(proof, |inclusion| {
+            self.universe.check_anchor(&inclusion.txid, &inclusion.block_hash).map_err(|e| e.to_string())
+        }))
     }
 
     async fn validate_state(&self, state: &ProtocolState) -> AnyaResult<ValidationResult> {
         info!("Validating Taproot Assets state...");
-        // TODO: Implement actual state validation
-        Ok(ValidationResult::default())
+        Ok(self.proof_verifier.validate_anchor(state))
     }
 } 
\ No newline at end of file
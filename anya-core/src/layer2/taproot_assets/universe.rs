@@ -0,0 +1,1001 @@
+//! Taproot Assets universe
+//!
+//! A universe publishes asset proof files so that wallets can discover an
+//! asset's issuance and transfer history without trusting the party that
+//! hands it over. Proofs are kept in merkle-sum sparse merkle trees (MS-SMT):
+//!
+//! - one tree per asset and [`ProofType`], keyed by the hash of the anchor
+//!   outpoint and script key of the proof's last state, with the asset amount
+//!   as the leaf sum; the issuance tree's root sum is the total supply;
+//! - one multiverse tree per proof type, keyed by asset ID, whose leaves
+//!   commit to the per-asset roots.
+//!
+//! Every proof file is verified against the shared header chain before it is
+//! accepted, both on insertion and when syncing from another universe, and
+//! leaves fetched from a remote are checked against the root it advertised.
+//! A universe opened at a path keeps its accepted leaves there, so they are
+//! served again after a restart without waiting for the headers to sync.
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::fmt;
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use async_trait::async_trait;
+use bitcoin::block::Header;
+use bitcoin::hashes::{sha256, Hash};
+use bitcoin::secp256k1::XOnlyPublicKey;
+use bitcoin::{BlockHash, OutPoint, Txid};
+use once_cell::sync::Lazy;
+use serde::{Deserialize, Serialize};
+
+use crate::bitcoin::error::BitcoinError;
+use crate::bitcoin::taproot::proof::{AssetProofFile, MerkleSumNode, VerifiedAsset};
+use crate::layer2::proof::ProofVerifier;
+
+/// Depth of a merkle-sum sparse merkle tree
+pub const MSSMT_DEPTH: usize = 256;
+
+/// Key of a leaf in a per-asset tree
+pub type LeafKey = [u8; 32];
+
+/// Empty subtree roots by height, leaf level first
+static EMPTY_NODES: Lazy<Vec<MerkleSumNode>> = Lazy::new(|| {
+    let mut nodes = vec![MerkleSumNode::leaf(sha256::Hash::hash(&[]).to_byte_array(), 0)];
+    for height in 0..MSSMT_DEPTH {
+        let below = nodes[height];
+        nodes.push(MerkleSumNode::branch(&below, &below).expect("empty subtrees sum to zero"));
+    }
+    nodes
+});
+
+/// Universe errors
+#[derive(Debug, Clone, PartialEq, Eq, thiserror::Error)]
+pub enum UniverseError {
+    /// Proof file or merkle-sum proof does not verify
+    #[error("Invalid proof: {0}")]
+    InvalidProof(String),
+
+    /// Unknown asset or leaf
+    #[error("Not found: {0}")]
+    NotFound(String),
+
+    /// Malformed request or response data
+    #[error("Malformed data: {0}")]
+    Malformed(String),
+
+    /// Remote universe unreachable
+    #[error("Transport error: {0}")]
+    Transport(String),
+
+    /// Universe store could not be read or written
+    #[error("Storage error: {0}")]
+    Storage(String),
+}
+
+impl From<BitcoinError> for UniverseError {
+    fn from(err: BitcoinError) -> Self {
+        UniverseError::InvalidProof(err.to_string())
+    }
+}
+
+/// Result type for universe operations
+pub type UniverseResult<T> = Result<T, UniverseError>;
+
+fn bit(key: &[u8; 32], index: usize) -> bool {
+    (key[index / 8] >> (7 - index % 8)) & 1 == 1
+}
+
+/// Leaf node committing to `value` with `sum`
+pub fn leaf_node(value: &[u8], sum: u64) -> MerkleSumNode {
+    MerkleSumNode::leaf(sha256::Hash::hash(value).to_byte_array(), sum)
+}
+
+/// Leaf key of an asset state: its anchor outpoint and script key
+pub fn leaf_key(outpoint: &OutPoint, script_key: &XOnlyPublicKey) -> LeafKey {
+    let mut data = Vec::with_capacity(68);
+    data.extend_from_slice(outpoint.txid.as_byte_array());
+    data.extend_from_slice(&outpoint.vout.to_be_bytes());
+    data.extend_from_slice(&script_key.serialize());
+    sha256::Hash::hash(&data).to_byte_array()
+}
+
+/// Root of the subtree at `depth` holding `leaves`, which are sorted by key
+fn subtree(leaves: &[(LeafKey, MerkleSumNode)], depth: usize) -> UniverseResult<MerkleSumNode> {
+    match leaves {
+        [] => Ok(EMPTY_NODES[MSSMT_DEPTH - depth]),
+        [(_, leaf)] if depth == MSSMT_DEPTH => Ok(*leaf),
+        _ => {
+            let split = leaves.partition_point(|(key, _)| !bit(key, depth));
+            let left = subtree(&leaves[..split], depth + 1)?;
+            let right = subtree(&leaves[split..], depth + 1)?;
+            Ok(MerkleSumNode::branch(&left, &right)?)
+        },
+    }
+}
+
+/// Merkle-sum sparse merkle tree
+#[derive(Debug, Clone)]
+pub struct MsSmt {
+    leaves: BTreeMap<LeafKey, MerkleSumNode>,
+    root: MerkleSumNode,
+}
+
+impl Default for MsSmt {
+    fn default() -> Self {
+        Self { leaves: BTreeMap::new(), root: EMPTY_NODES[MSSMT_DEPTH] }
+    }
+}
+
+impl MsSmt {
+    /// Build a tree from its leaves, hashing it once
+    pub fn from_leaves(leaves: impl IntoIterator<Item = (LeafKey, MerkleSumNode)>) -> UniverseResult<Self> {
+        let leaves: BTreeMap<LeafKey, MerkleSumNode> = leaves.into_iter().collect();
+        let root = subtree(&leaves.iter().map(|(key, leaf)| (*key, *leaf)).collect::<Vec<_>>(), 0)?;
+        Ok(Self { leaves, root })
+    }
+
+    /// Root node; its sum is the total of all leaves
+    pub fn root(&self) -> MerkleSumNode {
+        self.root
+    }
+
+    /// Leaf stored under `key`
+    pub fn get(&self, key: &LeafKey) -> Option<MerkleSumNode> {
+        self.leaves.get(key).copied()
+    }
+
+    /// Keys of all leaves, in key order
+    pub fn keys(&self) -> impl Iterator<Item = &LeafKey> {
+        self.leaves.keys()
+    }
+
+    /// Insert or replace a leaf; fails without changes if the sums overflow
+    pub fn insert(&mut self, key: LeafKey, leaf: MerkleSumNode) -> UniverseResult<()> {
+        let previous = self.leaves.insert(key, leaf);
+        match subtree(&self.sorted_leaves(), 0) {
+            Ok(root) => {
+                self.root = root;
+                Ok(())
+            },
+            Err(e) => {
+                match previous {
+                    Some(previous) => self.leaves.insert(key, previous),
+                    None => self.leaves.remove(&key),
+                };
+                Err(e)
+            },
+        }
+    }
+
+    /// Inclusion proof for `key`, or non-inclusion proof if it is empty
+    pub fn proof(&self, key: &LeafKey) -> UniverseResult<MsSmtProof> {
+        let leaves = self.sorted_leaves();
+        let mut range = &leaves[..];
+        let mut siblings = Vec::with_capacity(MSSMT_DEPTH);
+        for depth in 0..MSSMT_DEPTH {
+            let (left, right) = range.split_at(range.partition_point(|(k, _)| !bit(k, depth)));
+            let (path, sibling) = if bit(key, depth) { (right, left) } else { (left, right) };
+            siblings.push(subtree(sibling, depth + 1)?);
+            range = path;
+        }
+
+        let mut proof = MsSmtProof { bitmap: [0u8; 32], siblings: Vec::new() };
+        for (height, sibling) in siblings.into_iter().rev().enumerate() {
+            if sibling != EMPTY_NODES[height] {
+                proof.bitmap[height / 8] |= 1 << (7 - height % 8);
+                proof.siblings.push(sibling);
+            }
+        }
+        Ok(proof)
+    }
+
+    fn sorted_leaves(&self) -> Vec<(LeafKey, MerkleSumNode)> {
+        self.leaves.iter().map(|(key, leaf)| (*key, *leaf)).collect()
+    }
+}
+
+/// Compressed MS-SMT proof: a bitmap of non-empty siblings, leaf level first,
+/// followed by those siblings
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct MsSmtProof {
+    /// Bit `h` is set if the sibling at height `h` is not an empty subtree
+    pub bitmap: [u8; 32],
+    /// Non-empty siblings, leaf level first
+    pub siblings: Vec<MerkleSumNode>,
+}
+
+impl MsSmtProof {
+    /// Root implied by placing `leaf` at `key`
+    pub fn root_for(&self, key: &LeafKey, leaf: MerkleSumNode) -> UniverseResult<MerkleSumNode> {
+        let mut explicit = self.siblings.iter();
+        let mut node = leaf;
+        for height in 0..MSSMT_DEPTH {
+            let sibling = if bit(&self.bitmap, height) {
+                *explicit.next().ok_or_else(|| UniverseError::Malformed("Proof is missing siblings".to_string()))?
+            } else {
+                EMPTY_NODES[height]
+            };
+            node = if bit(key, MSSMT_DEPTH - 1 - height) {
+                MerkleSumNode::branch(&sibling, &node)?
+            } else {
+                MerkleSumNode::branch(&node, &sibling)?
+            };
+        }
+        if explicit.next().is_some() {
+            return Err(UniverseError::Malformed("Proof has extra siblings".to_string()));
+        }
+        Ok(node)
+    }
+
+    /// Check that `leaf` is stored at `key` under `root`
+    pub fn verify_inclusion(&self, key: &LeafKey, leaf: MerkleSumNode, root: &MerkleSumNode) -> bool {
+        self.root_for(key, leaf).is_ok_and(|computed| computed == *root)
+    }
+
+    /// Check that nothing is stored at `key` under `root`
+    pub fn verify_exclusion(&self, key: &LeafKey, root: &MerkleSumNode) -> bool {
+        self.verify_inclusion(key, EMPTY_NODES[0], root)
+    }
+
+    /// Binary encoding: bitmap, then each sibling as hash and big-endian sum
+    pub fn encode(&self) -> Vec<u8> {
+        let mut out = Vec::with_capacity(32 + 40 * self.siblings.len());
+        out.extend_from_slice(&self.bitmap);
+        for sibling in &self.siblings {
+            out.extend_from_slice(&sibling.hash);
+            out.extend_from_slice(&sibling.sum.to_be_bytes());
+        }
+        out
+    }
+
+    /// Decode the binary encoding
+    pub fn decode(data: &[u8]) -> UniverseResult<Self> {
+        let (bitmap, rest) = data.split_first_chunk::<32>()
+            .ok_or_else(|| UniverseError::Malformed("Proof shorter than its bitmap".to_string()))?;
+        let count = bitmap.iter().map(|byte| byte.count_ones() as usize).sum::<usize>();
+        if rest.len() != 40 * count {
+            return Err(UniverseError::Malformed(format!("Expected {} siblings", count)));
+        }
+
+        let siblings = rest.as_chunks::<40>().0.iter()
+            .map(|chunk| {
+                let (hash, sum) = chunk.split_at(32);
+                MerkleSumNode::leaf(hash.try_into().expect("32 bytes"), u64::from_be_bytes(sum.try_into().expect("8 bytes")))
+            })
+            .collect();
+        Ok(Self { bitmap: *bitmap, siblings })
+    }
+}
+
+/// Kind of proofs a universe tree holds
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ProofType {
+    /// Genesis proofs; the tree's sum is the issued supply
+    Issuance,
+    /// Proofs ending in a transfer
+    Transfer,
+}
+
+impl fmt::Display for ProofType {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            ProofType::Issuance => "issuance",
+            ProofType::Transfer => "transfer",
+        })
+    }
+}
+
+impl FromStr for ProofType {
+    type Err = UniverseError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "issuance" => Ok(ProofType::Issuance),
+            "transfer" => Ok(ProofType::Transfer),
+            other => Err(UniverseError::Malformed(format!("Unknown proof type {}", other))),
+        }
+    }
+}
+
+/// Identifies one per-asset tree
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct UniverseId {
+    /// Asset ID
+    pub asset_id: [u8; 32],
+    /// Proof type
+    pub proof_type: ProofType,
+}
+
+/// Hex (de)serialization of 32-byte values
+mod hex32 {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub fn serialize<S: Serializer>(value: &[u8; 32], serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.serialize_str(&hex::encode(value))
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<[u8; 32], D::Error> {
+        let s = String::deserialize(deserializer)?;
+        super::parse_hex32(&s).map_err(serde::de::Error::custom)
+    }
+}
+
+/// Parse a 32-byte hex value, such as an asset ID or leaf key
+pub fn parse_hex32(s: &str) -> UniverseResult<[u8; 32]> {
+    hex::decode(s).ok()
+        .and_then(|bytes| bytes.try_into().ok())
+        .ok_or_else(|| UniverseError::Malformed(format!("Expected 32 hex-encoded bytes: {}", s)))
+}
+
+/// Root of a per-asset tree
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct UniverseRoot {
+    /// Asset ID
+    #[serde(with = "hex32")]
+    pub asset_id: [u8; 32],
+    /// Proof type
+    pub proof_type: ProofType,
+    /// Root hash
+    #[serde(with = "hex32")]
+    pub root_hash: [u8; 32],
+    /// Sum of the amounts in the tree
+    pub root_sum: u64,
+}
+
+impl UniverseRoot {
+    /// Tree the root belongs to
+    pub fn id(&self) -> UniverseId {
+        UniverseId { asset_id: self.asset_id, proof_type: self.proof_type }
+    }
+
+    /// Root as a merkle-sum node
+    pub fn node(&self) -> MerkleSumNode {
+        MerkleSumNode::leaf(self.root_hash, self.root_sum)
+    }
+
+    /// Multiverse leaf committing to this root
+    pub fn multiverse_leaf(&self) -> MerkleSumNode {
+        leaf_node(&self.root_hash, self.root_sum)
+    }
+}
+
+/// Proof file in a per-asset tree with its inclusion proof
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LeafProof {
+    /// Root the proof was made against
+    pub root: UniverseRoot,
+    /// Leaf key
+    #[serde(with = "hex32")]
+    pub leaf_key: LeafKey,
+    /// Hex-encoded proof file
+    pub proof_file: String,
+    /// Amount held at the proof file's last state
+    pub amount: u64,
+    /// Hex-encoded [`MsSmtProof`]
+    pub inclusion: String,
+}
+
+impl LeafProof {
+    /// Check the inclusion proof and decode the proof file
+    pub fn verify(&self, root: &UniverseRoot) -> UniverseResult<AssetProofFile> {
+        let data = hex::decode(&self.proof_file)
+            .map_err(|e| UniverseError::Malformed(format!("Proof file hex: {}", e)))?;
+        let inclusion = hex::decode(&self.inclusion)
+            .map_err(|e| UniverseError::Malformed(format!("Inclusion proof hex: {}", e)))?;
+        if !MsSmtProof::decode(&inclusion)?.verify_inclusion(&self.leaf_key, leaf_node(&data, self.amount), &root.node()) {
+            return Err(UniverseError::InvalidProof(format!("Leaf {} is not in the advertised root", hex::encode(self.leaf_key))));
+        }
+        Ok(AssetProofFile::decode(&data)?)
+    }
+}
+
+/// Inclusion or non-inclusion of an asset in a multiverse tree
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct MultiverseProof {
+    /// Proof type of the multiverse tree
+    pub proof_type: ProofType,
+    /// Multiverse root hash
+    #[serde(with = "hex32")]
+    pub root_hash: [u8; 32],
+    /// Multiverse root sum
+    pub root_sum: u64,
+    /// The asset's root, if the asset is known
+    pub asset_root: Option<UniverseRoot>,
+    /// Hex-encoded [`MsSmtProof`] for the asset ID
+    pub proof: String,
+}
+
+impl MultiverseProof {
+    /// Check the proof for `asset_id`: inclusion if a root is given, non-inclusion otherwise
+    pub fn verify(&self, asset_id: &[u8; 32]) -> UniverseResult<bool> {
+        let data = hex::decode(&self.proof)
+            .map_err(|e| UniverseError::Malformed(format!("Multiverse proof hex: {}", e)))?;
+        let proof = MsSmtProof::decode(&data)?;
+        let root = MerkleSumNode::leaf(self.root_hash, self.root_sum);
+        Ok(match &self.asset_root {
+            Some(asset_root) => asset_root.asset_id == *asset_id
+                && proof.verify_inclusion(asset_id, asset_root.multiverse_leaf(), &root),
+            None => proof.verify_exclusion(asset_id, &root),
+        })
+    }
+}
+
+/// Outcome of inserting a proof file
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct InsertSummary {
+    /// New issuance leaves
+    pub issuance: usize,
+    /// New transfer leaves
+    pub transfer: usize,
+}
+
+/// Outcome of a sync
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SyncSummary {
+    /// Trees whose roots differed
+    pub trees: usize,
+    /// Leaves fetched and inserted
+    pub leaves: usize,
+}
+
+#[derive(Debug, Clone, Default)]
+struct AssetTree {
+    tree: MsSmt,
+    proof_files: BTreeMap<LeafKey, Vec<u8>>,
+}
+
+/// Accepted leaf as persisted
+#[derive(Serialize, Deserialize)]
+struct StoredLeaf {
+    #[serde(with = "hex32")]
+    asset_id: [u8; 32],
+    proof_type: ProofType,
+    #[serde(with = "hex32")]
+    leaf_key: LeafKey,
+    amount: u64,
+    /// Hex-encoded proof file
+    proof_file: String,
+}
+
+/// Insert a leaf unless its key is taken; returns whether it was inserted
+fn insert_leaf(trees: &mut BTreeMap<UniverseId, AssetTree>, id: UniverseId, key: LeafKey, data: Vec<u8>, amount: u64) -> UniverseResult<bool> {
+    let tree = trees.entry(id).or_default();
+    if tree.proof_files.contains_key(&key) {
+        return Ok(false);
+    }
+    tree.tree.insert(key, leaf_node(&data, amount))?;
+    tree.proof_files.insert(key, data);
+    Ok(true)
+}
+
+/// Universe store
+#[derive(Debug, Default)]
+pub struct Universe {
+    verifier: ProofVerifier,
+    path: Option<PathBuf>,
+    trees: BTreeMap<UniverseId, AssetTree>,
+    /// Blocks of the anchor transactions in verified proof files
+    anchors: BTreeMap<Txid, BlockHash>,
+}
+
+impl Universe {
+    /// Create an empty universe verifying proofs against `verifier`'s chain
+    pub fn new(verifier: ProofVerifier) -> Self {
+        Self { verifier, path: None, trees: BTreeMap::new(), anchors: BTreeMap::new() }
+    }
+
+    /// Open the universe stored at `path`, creating it if missing
+    ///
+    /// Stored leaves were verified when accepted and are not checked again.
+    pub fn open(path: impl AsRef<Path>, verifier: ProofVerifier) -> UniverseResult<Self> {
+        let path = path.as_ref().to_path_buf();
+        let storage = |e: &dyn fmt::Display| UniverseError::Storage(format!("{}: {}", path.display(), e));
+        let stored: Vec<StoredLeaf> = match fs::read(&path) {
+            Ok(data) => serde_json::from_slice(&data).map_err(|e| storage(&e))?,
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Vec::new(),
+            Err(e) => return Err(storage(&e)),
+        };
+
+        let mut universe = Self { path: Some(path.clone()), ..Self::new(verifier) };
+        for leaf in stored {
+            let data = hex::decode(&leaf.proof_file).map_err(|e| storage(&e))?;
+            for proof in AssetProofFile::decode(&data)?.proofs {
+                universe.anchors.insert(proof.anchor_tx.compute_txid(), proof.block_header.block_hash());
+            }
+            let id = UniverseId { asset_id: leaf.asset_id, proof_type: leaf.proof_type };
+            insert_leaf(&mut universe.trees, id, leaf.leaf_key, data, leaf.amount)?;
+        }
+        Ok(universe)
+    }
+
+    /// Verify a proof file against the header chain
+    pub fn verify_proof_file(&self, file: &AssetProofFile) -> UniverseResult<VerifiedAsset> {
+        let headers = file.proofs.iter()
+            .map(|proof| self.verifier.confirmed_header(&proof.block_header.block_hash()))
+            .collect::<Result<Vec<Header>, _>>()
+            .map_err(|e| UniverseError::InvalidProof(e.to_string()))?;
+        Ok(file.verify(&headers)?)
+    }
+
+    /// Verify and publish a proof file
+    ///
+    /// The genesis proof is published in the issuance tree and, for a file
+    /// with transfers, the whole file in the transfer tree. Leaves that are
+    /// already known are kept as they are.
+    pub fn insert(&mut self, file: &AssetProofFile) -> UniverseResult<InsertSummary> {
+        let verified = self.verify_proof_file(file)?;
+        let genesis = AssetProofFile::new(file.proofs[0].clone())?;
+
+        let mut trees = self.trees.clone();
+        let mut summary = InsertSummary::default();
+        let issuance = UniverseId { asset_id: verified.asset_id, proof_type: ProofType::Issuance };
+        let genesis_state = &genesis.proofs[0];
+        let genesis_key = leaf_key(&genesis_state.outpoint(), &genesis_state.asset.script_key);
+        if insert_leaf(&mut trees, issuance, genesis_key, genesis.encode(), verified.genesis_supply)? {
+            summary.issuance += 1;
+        }
+
+        if verified.transfers > 0 {
+            let transfer = UniverseId { asset_id: verified.asset_id, proof_type: ProofType::Transfer };
+            if insert_leaf(&mut trees, transfer, leaf_key(&verified.outpoint, &verified.script_key), file.encode(), verified.amount)? {
+                summary.transfer += 1;
+            }
+        }
+
+        // Keep the new leaves only once they are saved
+        if summary != InsertSummary::default() {
+            self.save(&trees)?;
+            self.trees = trees;
+        }
+        for proof in &file.proofs {
+            self.anchors.insert(proof.anchor_tx.compute_txid(), proof.block_header.block_hash());
+        }
+        Ok(summary)
+    }
+
+    fn save(&self, trees: &BTreeMap<UniverseId, AssetTree>) -> UniverseResult<()> {
+        let Some(path) = &self.path else {
+            return Ok(());
+        };
+
+        let stored: Vec<StoredLeaf> = trees.iter()
+            .flat_map(|(id, tree)| tree.proof_files.iter().map(move |(key, data)| StoredLeaf {
+                asset_id: id.asset_id,
+                proof_type: id.proof_type,
+                leaf_key: *key,
+                amount: tree.tree.get(key).expect("stored proof files have leaves").sum,
+                proof_file: hex::encode(data),
+            }))
+            .collect();
+        let data = serde_json::to_vec(&stored).map_err(|e| UniverseError::Storage(e.to_string()))?;
+        let temporary = path.with_extension("tmp");
+        fs::write(&temporary, data)
+            .and_then(|_| fs::rename(&temporary, path))
+            .map_err(|e| UniverseError::Storage(format!("{}: {}", path.display(), e)))
+    }
+
+    /// Check that `txid` anchors an asset commitment of a published proof file, mined in `block_hash`
+    pub fn check_anchor(&self, txid: &Txid, block_hash: &BlockHash) -> UniverseResult<()> {
+        match self.anchors.get(txid) {
+            Some(anchored) if anchored == block_hash => Ok(()),
+            Some(anchored) => Err(UniverseError::InvalidProof(format!("Anchor {} was mined in block {}", txid, anchored))),
+            None => Err(UniverseError::NotFound(format!("Anchor {}", txid))),
+        }
+    }
+
+    /// Roots of all per-asset trees
+    pub fn roots(&self) -> Vec<UniverseRoot> {
+        self.trees.keys().filter_map(|id| self.root(id)).collect()
+    }
+
+    /// Root of one per-asset tree
+    pub fn root(&self, id: &UniverseId) -> Option<UniverseRoot> {
+        self.trees.get(id).map(|tree| {
+            let root = tree.tree.root();
+            UniverseRoot { asset_id: id.asset_id, proof_type: id.proof_type, root_hash: root.hash, root_sum: root.sum }
+        })
+    }
+
+    /// Total issued supply of an asset
+    pub fn total_supply(&self, asset_id: &[u8; 32]) -> UniverseResult<u64> {
+        self.root(&UniverseId { asset_id: *asset_id, proof_type: ProofType::Issuance })
+            .map(|root| root.root_sum)
+            .ok_or_else(|| UniverseError::NotFound(format!("Asset {}", hex::encode(asset_id))))
+    }
+
+    /// Leaf keys of a per-asset tree
+    pub fn leaf_keys(&self, id: &UniverseId) -> Vec<LeafKey> {
+        self.trees.get(id).map(|tree| tree.tree.keys().copied().collect()).unwrap_or_default()
+    }
+
+    /// Proof file stored under `key` with its inclusion proof
+    pub fn leaf(&self, id: &UniverseId, key: &LeafKey) -> UniverseResult<LeafProof> {
+        let tree = self.trees.get(id)
+            .ok_or_else(|| UniverseError::NotFound(format!("{} universe of {}", id.proof_type, hex::encode(id.asset_id))))?;
+        let data = tree.proof_files.get(key)
+            .ok_or_else(|| UniverseError::NotFound(format!("Leaf {}", hex::encode(key))))?;
+        let leaf = tree.tree.get(key).expect("stored proof files have leaves");
+
+        Ok(LeafProof {
+            root: self.root(id).expect("tree exists"),
+            leaf_key: *key,
+            proof_file: hex::encode(data),
+            amount: leaf.sum,
+            inclusion: hex::encode(tree.tree.proof(key)?.encode()),
+        })
+    }
+
+    /// Multiverse tree of a proof type
+    fn multiverse(&self, proof_type: ProofType) -> UniverseResult<MsSmt> {
+        MsSmt::from_leaves(
+            self.roots().into_iter()
+                .filter(|root| root.proof_type == proof_type)
+                .map(|root| (root.asset_id, root.multiverse_leaf())),
+        )
+    }
+
+    /// Multiverse root of a proof type
+    pub fn multiverse_root(&self, proof_type: ProofType) -> UniverseResult<MerkleSumNode> {
+        Ok(self.multiverse(proof_type)?.root())
+    }
+
+    /// Inclusion or non-inclusion proof of an asset in the multiverse
+    pub fn multiverse_proof(&self, asset_id: &[u8; 32], proof_type: ProofType) -> UniverseResult<MultiverseProof> {
+        let multiverse = self.multiverse(proof_type)?;
+        let root = multiverse.root();
+        Ok(MultiverseProof {
+            proof_type,
+            root_hash: root.hash,
+            root_sum: root.sum,
+            asset_root: self.root(&UniverseId { asset_id: *asset_id, proof_type }),
+            proof: hex::encode(multiverse.proof(asset_id)?.encode()),
+        })
+    }
+}
+
+/// Universe that can be queried for roots, leaves and proof files
+#[async_trait]
+pub trait UniverseSource: Send + Sync {
+    /// Roots of all per-asset trees
+    async fn roots(&self) -> UniverseResult<Vec<UniverseRoot>>;
+
+    /// Leaf keys of a per-asset tree
+    async fn leaf_keys(&self, id: &UniverseId) -> UniverseResult<Vec<LeafKey>>;
+
+    /// Proof file under `key` with its inclusion proof
+    async fn leaf(&self, id: &UniverseId, key: &LeafKey) -> UniverseResult<LeafProof>;
+}
+
+/// Shared universe, as mounted by the API server
+#[derive(Debug, Clone, Default)]
+pub struct UniverseServer {
+    universe: Arc<RwLock<Universe>>,
+}
+
+impl UniverseServer {
+    /// Serve a universe verifying proofs against `verifier`'s chain
+    pub fn new(verifier: ProofVerifier) -> Self {
+        Self { universe: Arc::new(RwLock::new(Universe::new(verifier))) }
+    }
+
+    /// Serve the universe stored at `path`
+    pub fn open(path: impl AsRef<Path>, verifier: ProofVerifier) -> UniverseResult<Self> {
+        Ok(Self { universe: Arc::new(RwLock::new(Universe::open(path, verifier)?)) })
+    }
+
+    /// Verify and publish a proof file
+    pub fn insert(&self, file: &AssetProofFile) -> UniverseResult<InsertSummary> {
+        self.universe.write().unwrap().insert(file)
+    }
+
+    /// Verify and publish a hex-encoded proof file
+    pub fn insert_hex(&self, proof_file: &str) -> UniverseResult<InsertSummary> {
+        let data = hex::decode(proof_file)
+            .map_err(|e| UniverseError::Malformed(format!("Proof file hex: {}", e)))?;
+        self.insert(&AssetProofFile::decode(&data)?)
+    }
+
+    /// Total issued supply of an asset
+    pub fn total_supply(&self, asset_id: &[u8; 32]) -> UniverseResult<u64> {
+        self.universe.read().unwrap().total_supply(asset_id)
+    }
+
+    /// Check that `txid` anchors a published asset commitment, mined in `block_hash`
+    pub fn check_anchor(&self, txid: &Txid, block_hash: &BlockHash) -> UniverseResult<()> {
+        self.universe.read().unwrap().check_anchor(txid, block_hash)
+    }
+
+    /// Inclusion or non-inclusion proof of an asset in the multiverse
+    pub fn multiverse_proof(&self, asset_id: &[u8; 32], proof_type: ProofType) -> UniverseResult<MultiverseProof> {
+        self.universe.read().unwrap().multiverse_proof(asset_id, proof_type)
+    }
+
+    /// Multiverse root of a proof type
+    pub fn multiverse_root(&self, proof_type: ProofType) -> UniverseResult<MerkleSumNode> {
+        self.universe.read().unwrap().multiverse_root(proof_type)
+    }
+
+    /// Fetch every leaf `remote` has that this universe lacks
+    ///
+    /// Only trees whose roots differ are walked. Each fetched leaf is checked
+    /// against the remote's advertised root and its proof file against the
+    /// local header chain; the first invalid leaf aborts the sync.
+    pub async fn sync_from(&self, remote: &dyn UniverseSource) -> UniverseResult<SyncSummary> {
+        let mut summary = SyncSummary::default();
+        for root in remote.roots().await? {
+            let id = root.id();
+            if self.universe.read().unwrap().root(&id) == Some(root) {
+                continue;
+            }
+            summary.trees += 1;
+
+            let known: BTreeSet<LeafKey> = self.universe.read().unwrap().leaf_keys(&id).into_iter().collect();
+            for key in remote.leaf_keys(&id).await? {
+                if known.contains(&key) {
+                    continue;
+                }
+                let file = remote.leaf(&id, &key).await?.verify(&root)?;
+                let inserted = self.insert(&file)?;
+                summary.leaves += inserted.issuance + inserted.transfer;
+            }
+        }
+        Ok(summary)
+    }
+}
+
+#[async_trait]
+impl UniverseSource for UniverseServer {
+    async fn roots(&self) -> UniverseResult<Vec<UniverseRoot>> {
+        Ok(self.universe.read().unwrap().roots())
+    }
+
+    async fn leaf_keys(&self, id: &UniverseId) -> UniverseResult<Vec<LeafKey>> {
+        Ok(self.universe.read().unwrap().leaf_keys(id))
+    }
+
+    async fn leaf(&self, id: &UniverseId, key: &LeafKey) -> UniverseResult<LeafProof> {
+        self.universe.read().unwrap().leaf(id, key)
+    }
+}
+
+/// Remote universe behind the API server's `/universe` routes
+pub struct HttpUniverse {
+    client: reqwest::Client,
+    base_url: String,
+}
+
+impl HttpUniverse {
+    /// Client for a universe mounted at `base_url`, e.g. `http://host:8000/api/v1/universe`
+    pub fn new(base_url: &str, timeout: Duration) -> Self {
+        // Building only fails if the TLS backend cannot initialize; fall back to defaults
+        let client = reqwest::Client::builder()
+            .timeout(timeout)
+            .build()
+            .unwrap_or_default();
+
+        Self { client, base_url: base_url.trim_end_matches('/').to_string() }
+    }
+
+    async fn get<T: serde::de::DeserializeOwned>(&self, path: &str) -> UniverseResult<T> {
+        let url = format!("{}/{}", self.base_url, path);
+        let response = self.client.get(&url)
+            .send()
+            .await
+            .map_err(|e| UniverseError::Transport(format!("GET {} failed: {}", url, e)))?;
+        if response.status() == reqwest::StatusCode::NOT_FOUND {
+            return Err(UniverseError::NotFound(url));
+        }
+        if !response.status().is_success() {
+            return Err(UniverseError::Transport(format!("GET {} returned {}", url, response.status())));
+        }
+        response.json()
+            .await
+            .map_err(|e| UniverseError::Malformed(format!("GET {} returned invalid JSON: {}", url, e)))
+    }
+}
+
+#[async_trait]
+impl UniverseSource for HttpUniverse {
+    async fn roots(&self) -> UniverseResult<Vec<UniverseRoot>> {
+        self.get("roots").await
+    }
+
+    async fn leaf_keys(&self, id: &UniverseId) -> UniverseResult<Vec<LeafKey>> {
+        let keys: Vec<String> = self.get(&format!("leaves/{}/{}", hex::encode(id.asset_id), id.proof_type)).await?;
+        keys.iter().map(|key| parse_hex32(key)).collect()
+    }
+
+    async fn leaf(&self, id: &UniverseId, key: &LeafKey) -> UniverseResult<LeafProof> {
+        self.get(&format!("proofs/{}/{}/{}", hex::encode(id.asset_id), id.proof_type, hex::encode(key))).await
+    }
+}
+
+/// Fetch and verify the history of an asset from a universe
+///
+/// Returns every issuance and transfer proof file together with the state it
+/// proves, so a wallet can pick the ones ending at its script keys.
+pub async fn bootstrap(
+    source: &dyn UniverseSource,
+    asset_id: &[u8; 32],
+    verifier: &ProofVerifier,
+) -> UniverseResult<Vec<(AssetProofFile, VerifiedAsset)>> {
+    let checker = Universe::new(verifier.clone());
+    let mut history = Vec::new();
+    for root in source.roots().await?.into_iter().filter(|root| root.asset_id == *asset_id) {
+        for key in source.leaf_keys(&root.id()).await? {
+            let file = source.leaf(&root.id(), &key).await?.verify(&root)?;
+            let verified = checker.verify_proof_file(&file)?;
+            history.push((file, verified));
+        }
+    }
+
+    if history.is_empty() {
+        return Err(UniverseError::NotFound(format!("Asset {}", hex::encode(asset_id))));
+    }
+    Ok(history)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bitcoin::taproot::proof::tests::build_chain;
+    use crate::layer2::proof::HeaderChain;
+    use bitcoin::Network;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+
+    fn verifier(headers: &[Header]) -> ProofVerifier {
+        let verifier = ProofVerifier::new(HeaderChain::from_checkpoint(headers[0], 0, Network::Regtest).unwrap());
+        for header in &headers[1..] {
+            verifier.add_header(*header).unwrap();
+        }
+        verifier
+    }
+
+    #[test]
+    fn test_mssmt_proofs() {
+        let mut tree = MsSmt::default();
+        let keys: Vec<LeafKey> = (1u8..=5).map(|n| sha256::Hash::hash(&[n]).to_byte_array()).collect();
+        for (n, key) in keys.iter().enumerate() {
+            tree.insert(*key, leaf_node(&[n as u8], 10 * (n as u64 + 1))).unwrap();
+        }
+        assert_eq!(tree.root().sum, 150);
+
+        let proof = tree.proof(&keys[2]).unwrap();
+        assert!(proof.verify_inclusion(&keys[2], leaf_node(&[2], 30), &tree.root()));
+        assert!(!proof.verify_inclusion(&keys[2], leaf_node(&[2], 31), &tree.root()));
+        assert_eq!(MsSmtProof::decode(&proof.encode()).unwrap(), proof);
+
+        let absent = sha256::Hash::hash(b"absent").to_byte_array();
+        let exclusion = tree.proof(&absent).unwrap();
+        assert!(exclusion.verify_exclusion(&absent, &tree.root()));
+        assert!(!tree.proof(&keys[0]).unwrap().verify_exclusion(&keys[0], &tree.root()));
+
+        // Sums that would overflow leave the tree untouched
+        let root = tree.root();
+        assert!(tree.insert(absent, leaf_node(b"big", u64::MAX)).is_err());
+        assert_eq!(tree.root(), root);
+        assert!(tree.get(&absent).is_none());
+    }
+
+    #[tokio::test]
+    async fn test_publish_sync_and_bootstrap() {
+        let (file, headers, bob) = build_chain();
+        let asset_id = file.proofs[0].asset.asset_id;
+
+        let alice_universe = UniverseServer::new(verifier(&headers));
+        let summary = alice_universe.insert(&file).unwrap();
+        assert_eq!(summary, InsertSummary { issuance: 1, transfer: 1 });
+        assert_eq!(alice_universe.insert(&file).unwrap(), InsertSummary::default());
+        assert_eq!(alice_universe.total_supply(&asset_id).unwrap(), 1_000);
+
+        // Anchors of the published file are known with the blocks they were mined in
+        let transfer = file.last().unwrap();
+        let txid = transfer.anchor_tx.compute_txid();
+        alice_universe.check_anchor(&txid, &transfer.block_header.block_hash()).unwrap();
+        assert!(alice_universe.check_anchor(&txid, &file.proofs[0].block_header.block_hash()).is_err());
+
+        let included = alice_universe.multiverse_proof(&asset_id, ProofType::Transfer).unwrap();
+        assert!(included.asset_root.is_some() && included.verify(&asset_id).unwrap());
+        let unknown = [7u8; 32];
+        let excluded = alice_universe.multiverse_proof(&unknown, ProofType::Issuance).unwrap();
+        assert!(excluded.asset_root.is_none() && excluded.verify(&unknown).unwrap());
+
+        // A universe without the headers rejects the proofs
+        let blind = UniverseServer::new(verifier(&headers[..1]));
+        assert!(matches!(blind.insert(&file), Err(UniverseError::InvalidProof(_))));
+        assert!(matches!(blind.check_anchor(&txid, &transfer.block_header.block_hash()), Err(UniverseError::NotFound(_))));
+        assert!(blind.sync_from(&alice_universe).await.is_err());
+
+        let bob_universe = UniverseServer::new(verifier(&headers));
+        let synced = bob_universe.sync_from(&alice_universe).await.unwrap();
+        assert_eq!(synced, SyncSummary { trees: 2, leaves: 2 });
+        assert_eq!(
+            bob_universe.multiverse_root(ProofType::Transfer).unwrap(),
+            alice_universe.multiverse_root(ProofType::Transfer).unwrap(),
+        );
+        assert_eq!(bob_universe.sync_from(&alice_universe).await.unwrap(), SyncSummary::default());
+
+        let history = bootstrap(&bob_universe, &asset_id, &verifier(&headers)).await.unwrap();
+        let owned: Vec<_> = history.iter()
+            .filter(|(_, verified)| verified.script_key == bob.x_only_public_key().0)
+            .collect();
+        assert_eq!(owned.len(), 1);
+        assert_eq!(owned[0].1.amount, 300);
+    }
+
+    #[tokio::test]
+    async fn test_store_survives_restart() {
+        let (file, headers, _) = build_chain();
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("universe.json");
+
+        let universe = UniverseServer::open(&path, verifier(&headers)).unwrap();
+        universe.insert(&file).unwrap();
+        let roots = universe.roots().await.unwrap();
+
+        // Leaves come back before the restarted node has synced any header
+        let restarted = UniverseServer::open(&path, verifier(&headers[..1])).unwrap();
+        assert_eq!(restarted.roots().await.unwrap(), roots);
+        let transfer = file.last().unwrap();
+        restarted.check_anchor(&transfer.anchor_tx.compute_txid(), &transfer.block_header.block_hash()).unwrap();
+        assert_eq!(restarted.total_supply(&file.proofs[0].asset.asset_id).unwrap(), 1_000);
+    }
+
+    #[tokio::test]
+    async fn test_http_universe_rejects_forged_leaves() {
+        let (file, headers, _) = build_chain();
+        let served = UniverseServer::new(verifier(&headers));
+        served.insert(&file).unwrap();
+
+        // Serve the universe routes, with the transfer amount inflated in transit
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let base_url = format!("http://{}/api/v1/universe", listener.local_addr().unwrap());
+        let universe = served.clone();
+        tokio::spawn(async move {
+            while let Ok((mut stream, _)) = listener.accept().await {
+                let mut request = vec![0u8; 4096];
+                let n = stream.read(&mut request).await.unwrap();
+                let line = String::from_utf8_lossy(&request[..n]).lines().next().unwrap_or_default().to_string();
+                let path = line.split(' ').nth(1).unwrap_or_default().trim_start_matches("/api/v1/universe/");
+                let segments: Vec<&str> = path.split('/').collect();
+
+                let body = match segments.as_slice() {
+                    ["roots"] => serde_json::to_string(&universe.roots().await.unwrap()).unwrap(),
+                    ["leaves", asset_id, proof_type] => {
+                        let id = UniverseId { asset_id: parse_hex32(asset_id).unwrap(), proof_type: proof_type.parse().unwrap() };
+                        let keys: Vec<String> = universe.leaf_keys(&id).await.unwrap().iter().map(hex::encode).collect();
+                        serde_json::to_string(&keys).unwrap()
+                    },
+                    ["proofs", asset_id, proof_type, key] => {
+                        let id = UniverseId { asset_id: parse_hex32(asset_id).unwrap(), proof_type: proof_type.parse().unwrap() };
+                        let mut leaf = universe.leaf(&id, &parse_hex32(key).unwrap()).await.unwrap();
+                        if id.proof_type == ProofType::Transfer {
+                            leaf.amount += 1;
+                        }
+                        serde_json::to_string(&leaf).unwrap()
+                    },
+                    _ => String::new(),
+                };
+                let reply = format!(
+                    "HTTP/1.1 200 OK\r\nContent-Type: application/json\r\nContent-Length: {}\r\nConnection: close\r\n\r\n{}",
+                    body.len(), body
+                );
+                let _ = stream.write_all(reply.as_bytes()).await;
+            }
+        });
+
+        let remote = HttpUniverse::new(&base_url, Duration::from_secs(5));
+        assert_eq!(remote.roots().await.unwrap(), served.roots().await.unwrap());
+
+        let local = UniverseServer::new(verifier(&headers));
+        assert!(matches!(local.sync_from(&remote).await, Err(UniverseError::InvalidProof(_))));
+        // The issuance tree syncs before the forged transfer leaf is reached
+        assert_eq!(local.total_supply(&file.proofs[0].asset.asset_id).unwrap(), 1_000);
+    }
+}
@@ -2,10 +2,19 @@
 // Provides DID (Decentralized Identity) functionality
 // as part of the Web5 integration - [AIR-012] Operational Reliability
 
-use std::collections::HashMap;
+use std::collections::{BTreeMap, HashMap};
+use std::fs;
+use std::path::PathBuf;
 use std::sync::{Arc, Mutex};
 use std::time::{SystemTime, UNIX_EPOCH};
 use serde::{Serialize, Deserialize};
+use base64::engine::general_purpose::URL_SAFE_NO_PAD;
+use base64::Engine;
+use bitcoin::bip32::Xpriv;
+use bitcoin::hashes::{sha256, Hash};
+use bitcoin::secp256k1::{Message, Secp256k1, SecretKey};
+
+use crate::bitcoin::wallet::bip85;
 
 // Define Result type for Web5
 pub type Web5Result<T> = Result<T, Web5Error>;
@@ -41,6 +50,12 @@ pub struct DIDManager {
     default_did: Option<String>,
     /// DID method to use
     method: String,
+    /// Master key DID keys are derived from with BIP85, if backed up
+    master_key: Option<Xpriv>,
+    /// File recording the BIP85 index of every derived DID
+    key_index_path: Option<PathBuf>,
+    /// BIP85 index of every DID derived from the master key
+    key_indexes: Arc<Mutex<BTreeMap<String, u32>>>,
 }
 
 /// Decentralized Identifier
@@ -142,30 +157,75 @@ pub fn new(method: &str) -> Self {
             dids: Arc::new(Mutex::new(HashMap::new())),
             default_did: None,
             method: method.to_string(),
+            master_key: None,
+            key_index_path: None,
+            key_indexes: Arc::new(Mutex::new(BTreeMap::new())),
         }
     }
     
+    /// Derive DID keys from a master key with BIP85
+    ///
+    /// The keys use the BIP85 WIF application. Which index belongs to which
+    /// DID is kept in `index_path`, a JSON map holding no secrets; every DID
+    /// listed there is restored with its key, and new DIDs continue after
+    /// the highest index.
+    pub fn with_master_key(mut self, master_key: Xpriv, index_path: impl Into<PathBuf>) -> Web5Result<Self> {
+        let index_path = index_path.into();
+        let key_indexes: BTreeMap<String, u32> = if index_path.exists() {
+            let data = fs::read_to_string(&index_path)
+                .map_err(|e| Web5Error::Storage(format!("Failed to read DID key indexes: {}", e)))?;
+            serde_json::from_str(&data)
+                .map_err(|e| Web5Error::Storage(format!("Invalid DID key indexes: {}", e)))?
+        } else {
+            BTreeMap::new()
+        };
+        
+        {
+            let mut dids = self.dids.lock().unwrap();
+            for (id, index) in &key_indexes {
+                dids.insert(id.clone(), build_did(id, derive_key(&master_key, *index)?));
+            }
+        }
+        self.master_key = Some(master_key);
+        self.key_index_path = Some(index_path);
+        self.key_indexes = Arc::new(Mutex::new(key_indexes));
+        Ok(self)
+    }
+    
+    /// Key for the new DID `id`: the next BIP85 child of the master key,
+    /// recorded before it is used, or a random key without one
+    fn new_key(&self, id: &str) -> Web5Result<SecretKey> {
+        let (Some(master_key), Some(index_path)) = (&self.master_key, &self.key_index_path) else {
+            return Ok(SecretKey::new(&mut rand::thread_rng()));
+        };
+        let mut key_indexes = self.key_indexes.lock().unwrap();
+        if key_indexes.contains_key(id) {
+            return Err(Web5Error::Identity(format!("DID already exists: {}", id)));
+        }
+        let index = key_indexes.values().max().map_or(0, |last| last + 1);
+        let key = derive_key(master_key, index)?;
+        
+        key_indexes.insert(id.to_string(), index);
+        let written = serde_json::to_string_pretty(&*key_indexes)
+            .map_err(|e| e.to_string())
+            .and_then(|data| fs::write(index_path, data).map_err(|e| e.to_string()));
+        if let Err(e) = written {
+            key_indexes.remove(id);
+            return Err(Web5Error::Storage(format!("Failed to record DID key index: {}", e)));
+        }
+        Ok(key)
+    }
+    
+    /// BIP85 index the key of a derived DID comes from
+    pub fn key_index(&self, did: &str) -> Option<u32> {
+        self.key_indexes.lock().unwrap().get(did).copied()
+    }
+    
     /// Create a new DID with the configured method
     pub fn create_did(&self) -> Web5Result<DID> {
         // Generate a random ID for the DID
         let id = format!("did:{}:{}", self.method, generate_random_id());
-        
-        // Create a basic DID document
-        let document = DIDDocument {
-            context: vec!["https://www.w3.org/ns/did/v1".to_string()],
-            id: id.clone(),
-            verification_method: Vec::new(),
-            authentication: Vec::new(),
-            assertion_method: Vec::new(),
-            service: Vec::new(),
-        };
-        
-        // Create the DID
-        let did = DID {
-            id: id.clone(),
-            document,
-            private_keys: HashMap::new(),
-        };
+        let did = build_did(&id, self.new_key(&id)?);
         
         // Store the DID
         {
@@ -208,20 +268,20 @@ pub fn get_default_did(&self) -> Web5Result<Option<String>> {
     }
     
     /// Sign data with a DID's private key
+    ///
+    /// Returns a compact 64-byte ECDSA signature over the SHA-256 of `data`.
     pub fn sign(&self, did: &str, data: &[u8]) -> Web5Result<Vec<u8>> {
-        // This is a simplified implementation
-        // In a real implementation, this would use the DID's private key
-        
         // Get the DID
         let dids = self.dids.lock().unwrap();
         let did_obj = dids.get(did).ok_or_else(|| {
             Web5Error::Identity(format!("DID not found: {}", did))
         })?;
         
-        // For now, just return a placeholder signature
-        // In a real implementation, this would use the appropriate
-        // cryptographic algorithm based on the DID's verification method
-        Ok(vec![0u8; 64])
+        let secret_key = did_obj.private_keys.get("key-0")
+            .and_then(|key| SecretKey::from_slice(key).ok())
+            .ok_or_else(|| Web5Error::Identity(format!("DID has no signing key: {}", did)))?;
+        let message = Message::from_digest(sha256::Hash::hash(data).to_byte_array());
+        Ok(Secp256k1::new().sign_ecdsa(&message, &secret_key).serialize_compact().to_vec())
     }
     
     /// Get a list of all DIDs
@@ -231,6 +291,48 @@ pub fn dids(&self) -> Vec<String> {
     }
 }
 
+/// DID key from the BIP85 WIF child of the master key at `index`
+fn derive_key(master_key: &Xpriv, index: u32) -> Web5Result<SecretKey> {
+    bip85::derive_wif(master_key, index)
+        .map(|key| key.inner)
+        .map_err(|e| Web5Error::Identity(format!("Failed to derive DID key: {}", e)))
+}
+
+/// DID `id` with `secret_key` as its signing key
+fn build_did(id: &str, secret_key: SecretKey) -> DID {
+    // Create the verification method of the signing key
+    let public_key = secret_key.public_key(&Secp256k1::new()).serialize_uncompressed();
+    let key_id = format!("{}#key-0", id);
+    let verification_method = VerificationMethod {
+        id: key_id.clone(),
+        vm_type: "JsonWebKey2020".to_string(),
+        controller: id.to_string(),
+        public_key_jwk: Some(JWK {
+            kty: "EC".to_string(),
+            crv: Some("secp256k1".to_string()),
+            x: Some(URL_SAFE_NO_PAD.encode(&public_key[1..33])),
+            y: Some(URL_SAFE_NO_PAD.encode(&public_key[33..])),
+            kid: Some("key-0".to_string()),
+        }),
+    };
+    
+    // Create a basic DID document
+    let document = DIDDocument {
+        context: vec!["https://www.w3.org/ns/did/v1".to_string()],
+        id: id.to_string(),
+        verification_method: vec![verification_method],
+        authentication: vec![key_id.clone()],
+        assertion_method: vec![key_id],
+        service: Vec::new(),
+    };
+    
+    DID {
+        id: id.to_string(),
+        document,
+        private_keys: HashMap::from([("key-0".to_string(), secret_key.secret_bytes().to_vec())]),
+    }
+}
+
 /// Generate a random ID for a DID
 fn generate_random_id() -> String {
     let now = SystemTime::now()
@@ -238,7 +340,8 @@ fn generate_random_id() -> String {
         .unwrap()
         .as_secs();
     
-    format!("{:x}", now)
+    // Random bytes keep DIDs created within the same second apart
+    format!("{:x}{:016x}", now, rand::random::<u64>())
 }
 
 #[cfg(test)]
@@ -268,4 +371,44 @@ fn test_default_did() {
         // Check default DID
         assert_eq!(manager.get_default_did().unwrap().unwrap(), did.id);
     }
+    
+    #[test]
+    fn test_did_keys_from_master_key() {
+        let dir = tempfile::tempdir().unwrap();
+        let index_path = dir.path().join("did_keys.json");
+        let master_key = Xpriv::new_master(bitcoin::Network::Bitcoin, &[3u8; 32]).unwrap();
+        let manager = DIDManager::new("ion").with_master_key(master_key, &index_path).unwrap();
+        let first = manager.create_did().unwrap();
+        let second = manager.create_did().unwrap();
+        assert_eq!(manager.key_index(&first.id), Some(0));
+        assert_eq!(manager.key_index(&second.id), Some(1));
+        assert_ne!(second.private_keys, first.private_keys);
+        assert_eq!(
+            first.private_keys["key-0"],
+            bip85::derive_wif(&master_key, 0).unwrap().inner.secret_bytes().to_vec()
+        );
+        
+        // After a restart the recorded DIDs come back with their keys and
+        // new DIDs take the next index
+        let restored = DIDManager::new("ion").with_master_key(master_key, &index_path).unwrap();
+        assert_eq!(restored.key_index(&second.id), Some(1));
+        assert_eq!(restored.resolve_did(&first.id).unwrap().verification_method[0].id, format!("{}#key-0", first.id));
+        assert_eq!(restored.sign(&second.id, b"hello").unwrap(), manager.sign(&second.id, b"hello").unwrap());
+        let third = restored.create_did().unwrap();
+        assert_eq!(restored.key_index(&third.id), Some(2));
+        assert_eq!(
+            third.private_keys["key-0"],
+            bip85::derive_wif(&master_key, 2).unwrap().inner.secret_bytes().to_vec()
+        );
+        
+        let jwk = first.document.verification_method[0].public_key_jwk.as_ref().unwrap();
+        assert_eq!(jwk.crv.as_deref(), Some("secp256k1"));
+        assert_eq!(first.document.authentication, vec![format!("{}#key-0", first.id)]);
+        assert_eq!(manager.sign(&first.id, b"hello").unwrap().len(), 64);
+        
+        // Without a master key, keys are random and nothing is recorded
+        let plain = DIDManager::new("ion");
+        let did = plain.create_did().unwrap();
+        assert_eq!(plain.key_index(&did.id), None);
+    }
 } 
\ No newline at end of file
@@ -7,15 +7,16 @@
         TransactionStatus,
         AssetParams,
         AssetTransfer,
-        TransferResult,
         Proof,
-        VerificationResult,
-        ValidationResult,
+        mock::{MockLayer2Protocol, MockMethod, MockScenario},
+    },
+    core::reliability::{
+        Watchdog, ProgressTracker, AiVerification, ConfidenceAssessment,
+        execute_with_monitoring, execute_with_recovery,
     },
-    core::reliability::{Watchdog, ProgressTracker, AiVerification},
 };
+use std::path::PathBuf;
 use std::time::Duration;
-use tokio::time::sleep;
 
 /// Test milestone tracking
 #[derive(Debug, Clone, PartialEq)]
@@ -71,8 +72,9 @@ pub fn add_milestone(&mut self, name: &str) {
     /// Run all test milestones for a protocol
     pub async fn run_protocol_tests<P: Layer2Protocol>(&mut self, protocol: &P) -> AnyaResult<()> {
         let total_milestones = self.milestones.len();
+        let mut milestones = std::mem::take(&mut self.milestones);
         
-        for (i, milestone) in self.milestones.iter_mut().enumerate() {
+        for (i, milestone) in milestones.iter_mut().enumerate() {
             milestone.status = MilestoneStatus::InProgress;
             let start_time = std::time::Instant::now();
             
@@ -85,11 +87,13 @@ pub async fn run_protocol_tests<P: Layer2Protocol>(&mut self, protocol: &P) -> A
                 Err(e) => {
                     milestone.status = MilestoneStatus::Failed;
                     milestone.error = Some(e.to_string());
+                    self.milestones = milestones;
                     return Err(e);
                 }
             }
         }
         
+        self.milestones = milestones;
         self.progress.complete();
         self.watchdog.stop();
         Ok(())
@@ -109,7 +113,7 @@ async fn run_milestone<P: Layer2Protocol>(
             "asset_management" => self.test_asset_management(protocol).await,
             "security" => self.test_security(protocol).await,
             "performance" => self.test_performance(protocol).await,
-            _ => Err(AnyaError::InvalidInput(format!("Unknown milestone: {}", milestone.name))),
+            _ => Err(AnyaError::Config(format!("Unknown milestone: {}", milestone.name))),
         }
     }
 
@@ -131,7 +135,7 @@ async fn test_transaction_submission<P: Layer2Protocol>(&self, protocol: &P) ->
         let tx = vec![0u8; 100]; // Placeholder transaction data
         
         let result = protocol.submit_transaction(&tx).await;
-        self.verify_result(result, "Transaction submission")
+        self.verify_result(result, "Transaction submission").map(|_| ())
     }
 
     /// Test state management
@@ -163,19 +167,23 @@ async fn test_asset_management<P: Layer2Protocol>(&self, protocol: &P) -> AnyaRe
         };
         
         let transfer_result = protocol.transfer_asset(transfer).await;
-        self.verify_result(transfer_result, "Asset transfer")
+        self.verify_result(transfer_result, "Asset transfer").map(|_| ())
     }
 
     /// Test security features
     async fn test_security<P: Layer2Protocol>(&self, protocol: &P) -> AnyaResult<()> {
-        let proof = Proof::default(); // Placeholder proof
+        let proof = Proof {
+            merkle_root: "test_root".to_string(),
+            merkle_proof: Vec::new(),
+            block_header: "test_header".to_string(),
+        };
         
         let verify_result = protocol.verify_proof(&proof).await;
         self.verify_result(verify_result, "Proof verification")?;
         
         let state = ProtocolState::default(); // Placeholder state
         let validate_result = protocol.validate_state(&state).await;
-        self.verify_result(validate_result, "State validation")
+        self.verify_result(validate_result, "State validation").map(|_| ())
     }
 
     /// Test performance
@@ -195,7 +203,7 @@ async fn test_performance<P: Layer2Protocol>(&self, protocol: &P) -> AnyaResult<
         let tps = tx_count as f64 / duration.as_secs_f64();
         
         if tps < 10.0 {
-            return Err(AnyaError::PerformanceError(format!(
+            return Err(AnyaError::System(format!(
                 "Transaction throughput too low: {:.2} TPS",
                 tps
             )));
@@ -207,7 +215,7 @@ async fn test_performance<P: Layer2Protocol>(&self, protocol: &P) -> AnyaResult<
     /// Verify a result with AI verification
     fn verify_result<T>(&self, result: AnyaResult<T>, operation: &str) -> AnyaResult<T> {
         let assessment = ConfidenceAssessment {
-            output: result,
+            output: result?,
             confidence: 0.95,
             verification_steps: vec![
                 "Result validation".to_string(),
@@ -221,37 +229,50 @@ fn verify_result<T>(&self, result: AnyaResult<T>, operation: &str) -> AnyaResult
     }
 }
 
+/// Load a scenario from `tests/layer2/scenarios`
+fn scenario(name: &str) -> MockScenario {
+    let path = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("tests/layer2/scenarios")
+        .join(format!("{}.yaml", name));
+    MockScenario::from_yaml_file(path).unwrap()
+}
+
+/// Poll a transaction until it leaves `Pending`, returning the final status and the number of polls
+async fn await_final_status<P: Layer2Protocol>(protocol: &P, tx_id: &str) -> AnyaResult<(TransactionStatus, u32)> {
+    let mut polls = 0;
+    loop {
+        polls += 1;
+        match protocol.get_transaction_status(tx_id).await? {
+            TransactionStatus::Pending => tokio::time::sleep(Duration::from_millis(1)).await,
+            status => return Ok((status, polls)),
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
-    use anya_core::layer2::MockLayer2Protocol;
+
+    fn full_suite() -> ProtocolTestSuite {
+        let mut suite = ProtocolTestSuite::new("Test Protocol");
+        for milestone in [
+            "initialization",
+            "connection",
+            "transaction_submission",
+            "state_management",
+            "asset_management",
+            "security",
+            "performance",
+        ] {
+            suite.add_milestone(milestone);
+        }
+        suite
+    }
 
     #[tokio::test]
     async fn test_protocol_suite() {
-        let mut suite = ProtocolTestSuite::new("Test Protocol");
-        
-        // Add test milestones
-        suite.add_milestone("initialization");
-        suite.add_milestone("connection");
-        suite.add_milestone("transaction_submission");
-        suite.add_milestone("state_management");
-        suite.add_milestone("asset_management");
-        suite.add_milestone("security");
-        suite.add_milestone("performance");
-        
-        // Create mock protocol
-        let mut protocol = MockLayer2Protocol::new();
-        
-        // Set up mock expectations
-        protocol.expect_initialize().returning(|| Ok(()));
-        protocol.expect_connect().returning(|| Ok(()));
-        protocol.expect_submit_transaction().returning(|_| Ok("test_tx_id".to_string()));
-        protocol.expect_get_state().returning(|| Ok(ProtocolState::default()));
-        protocol.expect_sync_state().returning(|| Ok(()));
-        protocol.expect_issue_asset().returning(|_| Ok("test_asset".to_string()));
-        protocol.expect_transfer_asset().returning(|_| Ok(TransferResult::default()));
-        protocol.expect_verify_proof().returning(|_| Ok(VerificationResult::default()));
-        protocol.expect_validate_state().returning(|_| Ok(ValidationResult::default()));
+        let mut suite = full_suite();
+        let protocol = MockLayer2Protocol::new();
         
         // Run test suite
         let result = suite.run_protocol_tests(&protocol).await;
@@ -264,4 +285,91 @@ async fn test_protocol_suite() {
             assert!(milestone.error.is_none());
         }
     }
-} 
\ No newline at end of file
+
+    #[tokio::test]
+    async fn test_suite_records_failed_milestone() {
+        let mut suite = full_suite();
+        let protocol = MockLayer2Protocol::with_scenario(scenario("flaky_connection"));
+
+        let result = suite.run_protocol_tests(&protocol).await;
+        assert!(matches!(result, Err(AnyaError::Timeout(_))));
+
+        let statuses: Vec<_> = suite.milestones.iter().map(|m| m.status.clone()).collect();
+        assert_eq!(statuses[0], MilestoneStatus::Completed);
+        assert_eq!(statuses[1], MilestoneStatus::Failed);
+        assert!(statuses[2..].iter().all(|s| *s == MilestoneStatus::Pending));
+        assert!(suite.milestones[1].error.as_deref().unwrap().contains("Connect"));
+    }
+
+    #[tokio::test]
+    async fn test_retry_through_scripted_errors() {
+        let protocol = MockLayer2Protocol::with_scenario(scenario("flaky_connection"));
+
+        let mut attempts = 0;
+        let result = loop {
+            attempts += 1;
+            match execute_with_monitoring("connect", Duration::from_secs(1), protocol.connect()).await {
+                Ok(()) => break Ok(()),
+                Err(e) if attempts < 5 => tracing::debug!("Retrying after {}", e),
+                Err(e) => break Err(e),
+            }
+        };
+
+        assert!(result.is_ok());
+        assert_eq!(attempts, 3);
+        assert_eq!(protocol.calls(MockMethod::Connect), 3);
+    }
+
+    #[tokio::test]
+    async fn test_hung_call_times_out_and_recovers() {
+        let protocol = MockLayer2Protocol::with_scenario(scenario("hung_sync"));
+
+        let result = execute_with_monitoring("sync", Duration::from_millis(50), protocol.sync_state()).await;
+        assert!(matches!(result, Err(AnyaError::Timeout(_))));
+
+        // The scenario hangs every first sync, so recovery gets through on the second call
+        let protocol = MockLayer2Protocol::with_scenario(scenario("hung_sync"));
+        let result = execute_with_recovery(
+            "sync",
+            Duration::from_millis(50),
+            Duration::from_millis(500),
+            protocol.sync_state(),
+            protocol.sync_state(),
+        ).await;
+        assert!(result.is_ok());
+        assert_eq!(protocol.calls(MockMethod::SyncState), 2);
+    }
+
+    #[tokio::test]
+    async fn test_slow_calls_exceed_deadline() {
+        let protocol = MockLayer2Protocol::with_scenario(scenario("slow_state"));
+
+        let result = execute_with_monitoring("state", Duration::from_millis(20), protocol.get_state()).await;
+        assert!(matches!(result, Err(AnyaError::Timeout(_))));
+        let state = execute_with_monitoring("state", Duration::from_secs(1), protocol.get_state()).await.unwrap();
+        assert_eq!(state.hash, "mock_hash");
+    }
+
+    #[tokio::test]
+    async fn test_transaction_status_transitions() {
+        let protocol = MockLayer2Protocol::with_scenario(scenario("pending_transactions"));
+
+        let confirmed = protocol.submit_transaction(&[0u8; 100]).await.unwrap();
+        let failed = protocol.submit_transaction(&[0u8; 100]).await.unwrap();
+        assert!(matches!(
+            protocol.get_transaction_status(&confirmed).await.unwrap(),
+            TransactionStatus::Pending
+        ));
+
+        let (status, polls) = await_final_status(&protocol, &confirmed).await.unwrap();
+        assert!(matches!(status, TransactionStatus::Confirmed));
+        assert_eq!(polls, 2);
+        let (status, _) = await_final_status(&protocol, &failed).await.unwrap();
+        assert!(matches!(status, TransactionStatus::Failed));
+
+        // The scenario falls back to confirming later transactions
+        let later = protocol.submit_transaction(&[0u8; 100]).await.unwrap();
+        let (status, _) = await_final_status(&protocol, &later).await.unwrap();
+        assert!(matches!(status, TransactionStatus::Confirmed));
+    }
+}
@@ -0,0 +1,674 @@
+// Cross-Chain Journal
+// Durable record of every cross-chain operation and the step it has reached,
+// so that an interrupted peg can be finished or refunded after a restart
+
+use std::collections::HashMap;
+use std::fs::{self, File, OpenOptions};
+use std::io::{BufRead, BufReader, Write};
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+use rand::RngCore;
+use serde::{Deserialize, Serialize};
+
+use super::{CrossChainBridge, CrossChainStatus, CrossChainTransaction};
+
+/// Step a cross-chain operation has reached
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum JournalStep {
+    /// Intent recorded, nothing broadcast yet
+    Created,
+    /// Funds locked on the source chain
+    SourceLocked,
+    /// Proof of the source lock submitted to the bridge
+    ProofSubmitted,
+    /// Funds minted on the destination chain
+    DestinationMinted,
+    /// Destination mint confirmed, operation finished
+    Completed,
+    /// Source lock is to be refunded
+    Refunding,
+    /// Refund of the source lock broadcast, waiting for confirmations
+    RefundBroadcast,
+    /// Refund confirmed, operation finished
+    Refunded,
+    /// Operation abandoned before any funds were locked
+    Failed(String),
+}
+
+impl JournalStep {
+    /// Whether the operation needs no further action
+    pub fn is_terminal(&self) -> bool {
+        matches!(self, JournalStep::Completed | JournalStep::Refunded | JournalStep::Failed(_))
+    }
+}
+
+/// Per-step timeouts, in seconds
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub struct StepTimeouts {
+    /// Time allowed to broadcast the source lock
+    pub source_lock: u64,
+    /// Time allowed for the lock to confirm and the proof to be submitted
+    pub proof: u64,
+    /// Time allowed for the bridge to mint on the destination chain
+    pub destination_mint: u64,
+    /// Time allowed for the refund to confirm before it is broadcast again
+    pub refund: u64,
+}
+
+impl Default for StepTimeouts {
+    fn default() -> Self {
+        Self {
+            source_lock: 60 * 60,
+            proof: 24 * 60 * 60,
+            destination_mint: 24 * 60 * 60,
+            refund: 6 * 60 * 60,
+        }
+    }
+}
+
+impl StepTimeouts {
+    /// Timeout for `step`, if the step can time out
+    pub fn for_step(&self, step: &JournalStep) -> Option<u64> {
+        match step {
+            JournalStep::Created => Some(self.source_lock),
+            JournalStep::SourceLocked => Some(self.proof),
+            JournalStep::ProofSubmitted => Some(self.destination_mint),
+            JournalStep::RefundBroadcast => Some(self.refund),
+            _ => None,
+        }
+    }
+}
+
+/// Journal record of one cross-chain operation
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct JournalEntry {
+    /// Operation ID
+    pub operation_id: String,
+    /// Name of the bridge carrying the operation
+    pub bridge: String,
+    /// Current step
+    pub step: JournalStep,
+    /// Transaction snapshot
+    pub transaction: CrossChainTransaction,
+    /// Refund transaction ID on the source chain
+    pub refund_txid: Option<String>,
+    /// When the current step started
+    pub step_started_at: u64,
+    /// When the current step times out
+    pub deadline: Option<u64>,
+    /// Last error seen while advancing
+    pub last_error: Option<String>,
+    /// Last update timestamp
+    pub updated_at: u64,
+}
+
+impl JournalEntry {
+    /// Whether the current step has timed out at `now`
+    pub fn is_expired(&self, now: u64) -> bool {
+        self.deadline.map(|deadline| now >= deadline).unwrap_or(false)
+    }
+}
+
+/// Append-only journal of cross-chain operations
+///
+/// Every change is written as a full entry snapshot on its own line and synced
+/// before the caller proceeds; on open the last snapshot of each operation wins.
+pub struct CrossChainJournal {
+    /// Journal file path
+    path: PathBuf,
+
+    /// Latest entry per operation
+    entries: Mutex<HashMap<String, JournalEntry>>,
+}
+
+impl CrossChainJournal {
+    /// Open a journal, replaying any existing records
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, &'static str> {
+        let path = path.as_ref().to_path_buf();
+        let mut entries = HashMap::new();
+
+        if path.exists() {
+            let file = File::open(&path).map_err(|_| "Failed to open cross-chain journal")?;
+
+            for line in BufReader::new(file).lines() {
+                let line = line.map_err(|_| "Failed to read cross-chain journal")?;
+                if line.trim().is_empty() {
+                    continue;
+                }
+
+                // A torn final write from a crash is skipped; the previous snapshot stands
+                match serde_json::from_str::<JournalEntry>(&line) {
+                    Ok(entry) => {
+                        entries.insert(entry.operation_id.clone(), entry);
+                    },
+                    Err(e) => println!("Skipping unreadable cross-chain journal record: {}", e),
+                }
+            }
+        }
+
+        Ok(Self {
+            path,
+            entries: Mutex::new(entries),
+        })
+    }
+
+    /// Journal file path
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Record a new operation before anything is broadcast
+    pub fn begin(
+        &self,
+        bridge: &CrossChainBridge,
+        transaction: &CrossChainTransaction,
+        timeouts: &StepTimeouts,
+        now: u64,
+    ) -> Result<JournalEntry, &'static str> {
+        let mut nonce = [0u8; 8];
+        rand::thread_rng().fill_bytes(&mut nonce);
+
+        let entry = JournalEntry {
+            operation_id: format!("{}-{}", transaction.timestamp, hex::encode(nonce)),
+            bridge: bridge.name.clone(),
+            step: JournalStep::Created,
+            transaction: transaction.clone(),
+            refund_txid: None,
+            step_started_at: now,
+            deadline: timeouts.for_step(&JournalStep::Created).map(|t| now + t),
+            last_error: None,
+            updated_at: now,
+        };
+
+        self.record(&entry)?;
+        Ok(entry)
+    }
+
+    /// Durably record an entry snapshot
+    pub fn record(&self, entry: &JournalEntry) -> Result<(), &'static str> {
+        let mut line = serde_json::to_string(entry).map_err(|_| "Failed to encode journal entry")?;
+        line.push('\n');
+
+        let mut file = OpenOptions::new()
+            .create(true)
+            .append(true)
+            .open(&self.path)
+            .map_err(|_| "Failed to open cross-chain journal")?;
+        file.write_all(line.as_bytes()).map_err(|_| "Failed to write cross-chain journal")?;
+        file.sync_all().map_err(|_| "Failed to sync cross-chain journal")?;
+
+        self.entries.lock().unwrap().insert(entry.operation_id.clone(), entry.clone());
+        Ok(())
+    }
+
+    /// Get an operation
+    pub fn get(&self, operation_id: &str) -> Option<JournalEntry> {
+        self.entries.lock().unwrap().get(operation_id).cloned()
+    }
+
+    /// List all operations
+    pub fn list(&self) -> Vec<JournalEntry> {
+        self.entries.lock().unwrap().values().cloned().collect()
+    }
+
+    /// List operations that still need action, oldest first
+    pub fn open_operations(&self) -> Vec<JournalEntry> {
+        let mut open: Vec<JournalEntry> = self.entries.lock().unwrap()
+            .values()
+            .filter(|entry| !entry.step.is_terminal())
+            .cloned()
+            .collect();
+        open.sort_by_key(|entry| entry.transaction.timestamp);
+        open
+    }
+
+    /// Rewrite the journal with one snapshot per operation
+    pub fn compact(&self) -> Result<(), &'static str> {
+        let entries = self.entries.lock().unwrap();
+        let tmp_path = self.path.with_extension("compact");
+
+        {
+            let mut file = File::create(&tmp_path).map_err(|_| "Failed to create compacted journal")?;
+            for entry in entries.values() {
+                let line = serde_json::to_string(entry).map_err(|_| "Failed to encode journal entry")?;
+                writeln!(file, "{}", line).map_err(|_| "Failed to write compacted journal")?;
+            }
+            file.sync_all().map_err(|_| "Failed to sync compacted journal")?;
+        }
+
+        fs::rename(&tmp_path, &self.path).map_err(|_| "Failed to replace cross-chain journal")?;
+        Ok(())
+    }
+}
+
+/// Chain operations needed to drive a cross-chain operation
+///
+/// Every broadcasting method must be idempotent for a given operation ID, returning
+/// the existing transaction ID if the action already happened; the journal may replay
+/// a step whose outcome was not recorded before a crash.
+pub trait CrossChainExecutor: Send + Sync {
+    /// Source lock already broadcast for the operation, if any
+    fn find_source_lock(&self, operation_id: &str, transaction: &CrossChainTransaction) -> Result<Option<String>, &'static str>;
+
+    /// Lock funds on the source chain, returning the source transaction ID
+    fn lock_source(&self, operation_id: &str, transaction: &CrossChainTransaction) -> Result<String, &'static str>;
+
+    /// Confirmations of a source chain transaction
+    fn source_confirmations(&self, source_txid: &str) -> Result<u32, &'static str>;
+
+    /// Submit the proof of the source lock to the bridge
+    fn submit_proof(&self, operation_id: &str, transaction: &CrossChainTransaction) -> Result<(), &'static str>;
+
+    /// Destination transaction minting the funds, if the bridge has minted
+    fn destination_txid(&self, operation_id: &str, transaction: &CrossChainTransaction) -> Result<Option<String>, &'static str>;
+
+    /// Whether the bridge rejected the submitted proof or let it expire, so it can no longer mint
+    fn proof_rejected(&self, operation_id: &str, transaction: &CrossChainTransaction) -> Result<bool, &'static str>;
+
+    /// Whether a destination chain transaction is final
+    fn destination_confirmed(&self, target_txid: &str) -> Result<bool, &'static str>;
+
+    /// Refund the source lock, returning the refund transaction ID
+    fn refund_source(&self, operation_id: &str, transaction: &CrossChainTransaction) -> Result<String, &'static str>;
+}
+
+/// Drives journaled cross-chain operations through their steps
+pub struct CrossChainCoordinator<E: CrossChainExecutor> {
+    /// Operation journal
+    journal: CrossChainJournal,
+
+    /// Chain access
+    executor: E,
+
+    /// Per-step timeouts
+    timeouts: StepTimeouts,
+}
+
+impl<E: CrossChainExecutor> CrossChainCoordinator<E> {
+    /// Create a new coordinator
+    pub fn new(journal: CrossChainJournal, executor: E, timeouts: StepTimeouts) -> Self {
+        Self {
+            journal,
+            executor,
+            timeouts,
+        }
+    }
+
+    /// The operation journal
+    pub fn journal(&self) -> &CrossChainJournal {
+        &self.journal
+    }
+
+    /// Journal a new operation and advance it as far as it will go
+    pub fn start(
+        &self,
+        bridge: &mut CrossChainBridge,
+        transaction: &CrossChainTransaction,
+        now: u64,
+    ) -> Result<JournalEntry, &'static str> {
+        let entry = self.journal.begin(bridge, transaction, &self.timeouts, now)?;
+        self.drive(bridge, &entry.operation_id, now)
+    }
+
+    /// Advance an operation until it is terminal or waiting on a chain
+    pub fn drive(
+        &self,
+        bridge: &mut CrossChainBridge,
+        operation_id: &str,
+        now: u64,
+    ) -> Result<JournalEntry, &'static str> {
+        loop {
+            let before = self.journal.get(operation_id).ok_or("Operation not found")?.step;
+            let entry = self.advance(bridge, operation_id, now)?;
+
+            if entry.step.is_terminal() || entry.step == before {
+                return Ok(entry);
+            }
+        }
+    }
+
+    /// Rebuild bridge state from the journal and finish or refund every open operation
+    pub fn resume_all(&self, bridge: &mut CrossChainBridge, now: u64) -> Result<Vec<JournalEntry>, &'static str> {
+        for entry in self.journal.list() {
+            if entry.bridge == bridge.name {
+                sync_bridge(bridge, &entry);
+            }
+        }
+
+        let mut resumed = Vec::new();
+        for entry in self.journal.open_operations() {
+            if entry.bridge != bridge.name {
+                continue;
+            }
+
+            println!("Resuming cross-chain operation {} at {:?}", entry.operation_id, entry.step);
+            resumed.push(self.drive(bridge, &entry.operation_id, now)?);
+        }
+
+        Ok(resumed)
+    }
+
+    /// Attempt a single step of an operation
+    pub fn advance(
+        &self,
+        bridge: &mut CrossChainBridge,
+        operation_id: &str,
+        now: u64,
+    ) -> Result<JournalEntry, &'static str> {
+        let mut entry = self.journal.get(operation_id).ok_or("Operation not found")?;
+        let expired = entry.is_expired(now);
+
+        let outcome = match entry.step.clone() {
+            JournalStep::Created => {
+                // A lock broadcast just before a crash is picked up rather than repeated or abandoned
+                self.executor.find_source_lock(operation_id, &entry.transaction).and_then(|existing| {
+                    let txid = match existing {
+                        Some(txid) => txid,
+                        None if expired => {
+                            return Ok(Some(JournalStep::Failed("Timed out before source lock".to_string())));
+                        },
+                        None => self.executor.lock_source(operation_id, &entry.transaction)?,
+                    };
+
+                    entry.transaction.source_txid = txid;
+                    entry.transaction.status = CrossChainStatus::PendingSource;
+                    Ok(Some(JournalStep::SourceLocked))
+                })
+            },
+            JournalStep::SourceLocked => {
+                self.executor.source_confirmations(&entry.transaction.source_txid).and_then(|confirmations| {
+                    entry.transaction.source_confirmations = confirmations;
+
+                    if confirmations >= bridge.required_confirmations {
+                        match self.executor.submit_proof(operation_id, &entry.transaction) {
+                            Ok(()) => {
+                                entry.transaction.status = CrossChainStatus::ProcessingBridge;
+                                Ok(Some(JournalStep::ProofSubmitted))
+                            },
+                            Err(_) if expired => Ok(Some(JournalStep::Refunding)),
+                            Err(e) => Err(e),
+                        }
+                    } else if expired {
+                        Ok(Some(JournalStep::Refunding))
+                    } else {
+                        entry.transaction.status = if confirmations == 0 {
+                            CrossChainStatus::PendingSource
+                        } else {
+                            CrossChainStatus::ConfirmedSource
+                        };
+                        Ok(None)
+                    }
+                })
+            },
+            JournalStep::ProofSubmitted => {
+                // Never refund while the bridge may still mint; a late mint after a refund pays twice
+                self.executor.destination_txid(operation_id, &entry.transaction).and_then(|target_txid| {
+                    match target_txid {
+                        Some(target_txid) => {
+                            entry.transaction.target_txid = Some(target_txid);
+                            entry.transaction.status = CrossChainStatus::PendingTarget;
+                            Ok(Some(JournalStep::DestinationMinted))
+                        },
+                        None if self.executor.proof_rejected(operation_id, &entry.transaction)? => {
+                            Ok(Some(JournalStep::Refunding))
+                        },
+                        None if expired => Err("Destination mint overdue; refund held until the bridge rejects the proof"),
+                        None => Ok(None),
+                    }
+                })
+            },
+            JournalStep::DestinationMinted => {
+                let target_txid = entry.transaction.target_txid.clone().unwrap_or_default();
+                self.executor.destination_confirmed(&target_txid).map(|confirmed| {
+                    if confirmed {
+                        entry.transaction.status = CrossChainStatus::Completed;
+                        Some(JournalStep::Completed)
+                    } else {
+                        None
+                    }
+                })
+            },
+            JournalStep::Refunding => {
+                self.executor.refund_source(operation_id, &entry.transaction).map(|refund_txid| {
+                    entry.refund_txid = Some(refund_txid);
+                    Some(JournalStep::RefundBroadcast)
+                })
+            },
+            JournalStep::RefundBroadcast => {
+                let refund_txid = entry.refund_txid.clone().unwrap_or_default();
+                self.executor.source_confirmations(&refund_txid).map(|confirmations| {
+                    if confirmations >= bridge.required_confirmations {
+                        entry.transaction.status = CrossChainStatus::Refunded;
+                        Some(JournalStep::Refunded)
+                    } else if confirmations == 0 && expired {
+                        // The refund was dropped; broadcast it again
+                        Some(JournalStep::Refunding)
+                    } else {
+                        None
+                    }
+                })
+            },
+            JournalStep::Completed | JournalStep::Refunded | JournalStep::Failed(_) => Ok(None),
+        };
+
+        match outcome {
+            Ok(Some(step)) => {
+                if let JournalStep::Failed(reason) = &step {
+                    entry.transaction.status = CrossChainStatus::Failed(reason.clone());
+                }
+                if step == JournalStep::Refunding {
+                    entry.transaction.status = CrossChainStatus::Refunding;
+                }
+
+                entry.deadline = self.timeouts.for_step(&step).map(|t| now + t);
+                entry.step = step;
+                entry.step_started_at = now;
+                entry.last_error = None;
+            },
+            Ok(None) => {},
+            Err(e) => {
+                // Errors leave the step unchanged; it will be retried on the next pass
+                entry.last_error = Some(e.to_string());
+            },
+        }
+
+        entry.updated_at = now;
+        self.journal.record(&entry)?;
+        sync_bridge(bridge, &entry);
+
+        Ok(entry)
+    }
+}
+
+/// Mirror a journal entry into the bridge's in-memory transaction map
+fn sync_bridge(bridge: &mut CrossChainBridge, entry: &JournalEntry) {
+    if !entry.transaction.source_txid.is_empty() {
+        bridge.transactions.insert(entry.transaction.source_txid.clone(), entry.transaction.clone());
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use super::super::{create_bridge, create_transaction};
+    use std::sync::atomic::{AtomicBool, AtomicU32, AtomicUsize, Ordering};
+
+    #[derive(Default)]
+    struct MockExecutor {
+        confirmations: AtomicU32,
+        refund_confirmations: AtomicU32,
+        minted: Mutex<Option<String>>,
+        rejected: AtomicBool,
+        locks: AtomicUsize,
+        refunds: AtomicUsize,
+        fail_proof: bool,
+    }
+
+    impl CrossChainExecutor for MockExecutor {
+        fn find_source_lock(&self, operation_id: &str, _transaction: &CrossChainTransaction) -> Result<Option<String>, &'static str> {
+            if self.locks.load(Ordering::SeqCst) > 0 {
+                return Ok(Some(format!("lock-{}", operation_id)));
+            }
+            Ok(None)
+        }
+
+        fn lock_source(&self, operation_id: &str, _transaction: &CrossChainTransaction) -> Result<String, &'static str> {
+            self.locks.fetch_add(1, Ordering::SeqCst);
+            Ok(format!("lock-{}", operation_id))
+        }
+
+        fn source_confirmations(&self, source_txid: &str) -> Result<u32, &'static str> {
+            if source_txid.starts_with("refund-") {
+                return Ok(self.refund_confirmations.load(Ordering::SeqCst));
+            }
+            Ok(self.confirmations.load(Ordering::SeqCst))
+        }
+
+        fn submit_proof(&self, _operation_id: &str, _transaction: &CrossChainTransaction) -> Result<(), &'static str> {
+            if self.fail_proof {
+                return Err("Bridge unavailable");
+            }
+            Ok(())
+        }
+
+        fn destination_txid(&self, _operation_id: &str, _transaction: &CrossChainTransaction) -> Result<Option<String>, &'static str> {
+            Ok(self.minted.lock().unwrap().clone())
+        }
+
+        fn proof_rejected(&self, _operation_id: &str, _transaction: &CrossChainTransaction) -> Result<bool, &'static str> {
+            Ok(self.rejected.load(Ordering::SeqCst))
+        }
+
+        fn destination_confirmed(&self, _target_txid: &str) -> Result<bool, &'static str> {
+            Ok(true)
+        }
+
+        fn refund_source(&self, operation_id: &str, _transaction: &CrossChainTransaction) -> Result<String, &'static str> {
+            self.refunds.fetch_add(1, Ordering::SeqCst);
+            Ok(format!("refund-{}", operation_id))
+        }
+    }
+
+    fn setup() -> (CrossChainBridge, CrossChainTransaction) {
+        let mut bridge = create_bridge("Bitcoin-RSK Bridge", "RSK", 100000, None, 6, 25);
+        let transaction = create_transaction(
+            &mut bridge,
+            "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4",
+            "0x71C7656EC7ab88b098defB751B7401B5f6d8976F",
+            1000000,
+        ).unwrap();
+        (bridge, transaction)
+    }
+
+    #[test]
+    fn test_operation_resumes_after_restart() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("cross_chain.journal");
+        let (mut bridge, transaction) = setup();
+
+        // Lock the source, then "crash" while waiting for confirmations
+        let operation_id = {
+            let coordinator = CrossChainCoordinator::new(
+                CrossChainJournal::open(&path).unwrap(),
+                MockExecutor::default(),
+                StepTimeouts::default(),
+            );
+            let entry = coordinator.start(&mut bridge, &transaction, 1_000).unwrap();
+            assert_eq!(entry.step, JournalStep::SourceLocked);
+            entry.operation_id
+        };
+
+        // Restart with a fresh bridge; the chain has since confirmed and minted
+        let (mut bridge, _) = setup();
+        let executor = MockExecutor::default();
+        executor.confirmations.store(6, Ordering::SeqCst);
+        *executor.minted.lock().unwrap() = Some("0xmint".to_string());
+
+        let coordinator = CrossChainCoordinator::new(
+            CrossChainJournal::open(&path).unwrap(),
+            executor,
+            StepTimeouts::default(),
+        );
+        let resumed = coordinator.resume_all(&mut bridge, 2_000).unwrap();
+
+        assert_eq!(resumed.len(), 1);
+        assert_eq!(resumed[0].operation_id, operation_id);
+        assert_eq!(resumed[0].step, JournalStep::Completed);
+        assert_eq!(resumed[0].transaction.target_txid, Some("0xmint".to_string()));
+        assert!(coordinator.journal().open_operations().is_empty());
+
+        let tracked = bridge.transactions.get(&format!("lock-{}", operation_id)).unwrap();
+        assert_eq!(tracked.status, CrossChainStatus::Completed);
+    }
+
+    #[test]
+    fn test_stalled_operation_is_refunded() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("cross_chain.journal");
+        let (mut bridge, transaction) = setup();
+        let timeouts = StepTimeouts::default();
+
+        let executor = MockExecutor { fail_proof: true, ..Default::default() };
+        executor.confirmations.store(6, Ordering::SeqCst);
+        let coordinator = CrossChainCoordinator::new(CrossChainJournal::open(&path).unwrap(), executor, timeouts);
+
+        // Proof submission keeps failing; the step stays put with the error recorded
+        let entry = coordinator.start(&mut bridge, &transaction, 1_000).unwrap();
+        assert_eq!(entry.step, JournalStep::SourceLocked);
+        assert_eq!(entry.last_error, Some("Bridge unavailable".to_string()));
+
+        // Once the proof step times out the lock is refunded instead
+        let refund_at = 1_000 + timeouts.proof;
+        let entry = coordinator.drive(&mut bridge, &entry.operation_id, refund_at).unwrap();
+        assert_eq!(entry.step, JournalStep::RefundBroadcast);
+        assert_eq!(entry.transaction.status, CrossChainStatus::Refunding);
+        assert_eq!(coordinator.executor.refunds.load(Ordering::SeqCst), 1);
+
+        // A refund that never confirms is broadcast again
+        let entry = coordinator.drive(&mut bridge, &entry.operation_id, refund_at + timeouts.refund).unwrap();
+        assert_eq!(entry.step, JournalStep::RefundBroadcast);
+        assert_eq!(coordinator.executor.refunds.load(Ordering::SeqCst), 2);
+
+        // Only a confirmed refund finishes the operation
+        coordinator.executor.refund_confirmations.store(6, Ordering::SeqCst);
+        let entry = coordinator.drive(&mut bridge, &entry.operation_id, refund_at + timeouts.refund + 600).unwrap();
+        assert_eq!(entry.step, JournalStep::Refunded);
+        assert_eq!(entry.transaction.status, CrossChainStatus::Refunded);
+
+        // The refund survives compaction and reopening
+        coordinator.journal().compact().unwrap();
+        let reopened = CrossChainJournal::open(&path).unwrap();
+        assert_eq!(reopened.get(&entry.operation_id).unwrap().step, JournalStep::Refunded);
+        assert_eq!(reopened.list().len(), 1);
+    }
+
+    #[test]
+    fn test_overdue_mint_is_not_refunded_until_rejected() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("cross_chain.journal");
+        let (mut bridge, transaction) = setup();
+        let timeouts = StepTimeouts::default();
+
+        let executor = MockExecutor::default();
+        executor.confirmations.store(6, Ordering::SeqCst);
+        let coordinator = CrossChainCoordinator::new(CrossChainJournal::open(&path).unwrap(), executor, timeouts);
+
+        let entry = coordinator.start(&mut bridge, &transaction, 1_000).unwrap();
+        assert_eq!(entry.step, JournalStep::ProofSubmitted);
+
+        // The bridge may still mint, so a late mint is waited for rather than refunded
+        let overdue = 1_000 + timeouts.destination_mint;
+        let entry = coordinator.drive(&mut bridge, &entry.operation_id, overdue).unwrap();
+        assert_eq!(entry.step, JournalStep::ProofSubmitted);
+        assert!(entry.last_error.is_some());
+        assert_eq!(coordinator.executor.refunds.load(Ordering::SeqCst), 0);
+
+        // Once the bridge rejects the proof the lock can be refunded
+        coordinator.executor.rejected.store(true, Ordering::SeqCst);
+        coordinator.executor.refund_confirmations.store(6, Ordering::SeqCst);
+        let entry = coordinator.drive(&mut bridge, &entry.operation_id, overdue + 600).unwrap();
+        assert_eq!(entry.step, JournalStep::Refunded);
+        assert_eq!(coordinator.executor.refunds.load(Ordering::SeqCst), 1);
+    }
+}
@@ -3,15 +3,17 @@
 // as per Bitcoin Development Framework v2.5 requirements
 
 pub mod rsk;
+pub mod journal;
 
 use bitcoin::{Block, BlockHeader, Transaction};
 use bitcoin::hashes::Hash;
 use std::collections::HashMap;
+use serde::{Deserialize, Serialize};
 
 /// Cross-Chain Transaction Status
 /// 
 /// Represents the status of a cross-chain transaction.
-#[derive(Debug, Clone, PartialEq)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub enum CrossChainStatus {
     /// Transaction is pending on the source chain
     PendingSource,
@@ -23,6 +25,10 @@ pub enum CrossChainStatus {
     PendingTarget,
     /// Transaction is confirmed on the target chain
     Completed,
+    /// Refund of the source lock is in progress
+    Refunding,
+    /// Source lock was refunded
+    Refunded,
     /// Transaction failed
     Failed(String),
 }
@@ -53,7 +59,7 @@ pub struct CrossChainBridge {
 /// Cross-Chain Transaction
 /// 
 /// Represents a transaction between Bitcoin and another blockchain.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
 pub struct CrossChainTransaction {
     /// Transaction ID on source chain
     pub source_txid: String,
@@ -11,6 +11,8 @@
 
 use std::sync::Arc;
 
+use serde::{Deserialize, Serialize};
+
 /// Bitcoin implementation type selection enum
 /// 
 /// This enum allows for runtime selection between different Bitcoin
@@ -41,6 +43,15 @@ pub enum BitcoinError {
     
     #[error("Implementation error: {0}")]
     ImplementationError(String),
+    
+    #[error("DLC error: {0}")]
+    DLC(String),
+    
+    #[error("Taproot error: {0}")]
+    TaprootError(String),
+    
+    #[error("Secp256k1 error: {0}")]
+    Secp256k1Error(#[from] bitcoin::secp256k1::Error),
 }
 
 /// Result type for Bitcoin operations
@@ -80,7 +91,7 @@ pub enum AddressType {
 /// 
 /// This provides a common structure for representing Bitcoin transactions
 /// regardless of the underlying implementation details.
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct BitcoinTransaction {
     /// Transaction ID (hash)
     pub txid: String,
@@ -103,7 +114,7 @@ pub struct BitcoinTransaction {
 /// Transaction input data
 /// 
 /// Represents a source of funds in a Bitcoin transaction
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TransactionInput {
     /// Reference to the transaction containing the output being spent
     pub txid: String,
@@ -120,7 +131,7 @@ pub struct TransactionInput {
 /// Transaction output data
 /// 
 /// Represents a destination of funds in a Bitcoin transaction
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct TransactionOutput {
     /// Amount in satoshis
     pub value: u64,
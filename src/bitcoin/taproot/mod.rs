@@ -3,18 +3,25 @@
 // as per Bitcoin Development Framework v2.5 requirements
 
 use bitcoin::{Transaction, TxIn, TxOut, Script, OutPoint, Witness};
-use bitcoin::secp256k1::{Secp256k1, SecretKey, PublicKey, XOnlyPublicKey};
+use bitcoin::secp256k1::{Secp256k1, SecretKey, PublicKey, XOnlyPublicKey, Keypair};
 use bitcoin::hashes::{Hash, sha256};
 use bitcoin::taproot::{TapLeafHash, TaprootBuilder, TaprootSpendInfo, LeafVersion};
 use std::collections::HashMap;
 use std::str::FromStr;
 
+pub mod proof;
+
+use self::proof::{
+    genesis_commitment, AnchorConfirmation, AssetGenesis, AssetProof, AssetProofFile, AssetTransition,
+    TransferProofs,
+};
+
 /// Taproot Asset structure
 /// 
 /// Represents a Taproot-enabled asset with metadata and supply information.
 #[derive(Debug, Clone)]
 pub struct TaprootAsset {
-    /// Asset ID (hash of asset parameters)
+    /// Asset ID (hash of asset parameters, derived from the genesis once issued)
     pub asset_id: [u8; 32],
     /// Asset name
     pub name: String,
@@ -28,6 +35,14 @@ pub struct TaprootAsset {
     pub issuance_tx: Option<Transaction>,
     /// Current holders (address -> amount)
     pub holders: HashMap<String, u64>,
+    /// Genesis parameters, set by the issuance transaction
+    pub genesis: Option<AssetGenesis>,
+    /// Key the supply is issued to
+    pub issuer_key: Option<XOnlyPublicKey>,
+    /// Proof chain from genesis to the units held here
+    pub proof_file: Option<AssetProofFile>,
+    /// Signed transfer waiting for its transaction to confirm
+    pub pending_transition: Option<AssetTransition>,
 }
 
 /// Asset Transfer structure
@@ -87,6 +102,10 @@ pub fn create_asset(
         metadata: metadata.to_string(),
         issuance_tx: None,
         holders: HashMap::new(),
+        genesis: None,
+        issuer_key: None,
+        proof_file: None,
+        pending_transition: None,
     };
     
     Ok(asset)
@@ -135,28 +154,12 @@ pub fn issue_asset(
     let internal_key = XOnlyPublicKey::from_slice(&issuer_pubkey.serialize()[1..33])
         .map_err(|_| "Failed to create internal key")?;
     
-    // Create asset metadata script
-    let asset_metadata_script = bitcoin::blockdata::script::Builder::new()
-        .push_opcode(bitcoin::blockdata::opcodes::all::OP_RETURN)
-        .push_slice(b"ASSET")
-        .push_slice(&asset.asset_id)
-        .push_slice(asset.name.as_bytes())
-        .push_slice(&asset.supply.to_le_bytes())
-        .push_slice(&[asset.precision])
-        .push_slice(asset.metadata.as_bytes())
-        .into_script();
-    
-    // Build Taproot tree with asset metadata
-    let mut builder = TaprootBuilder::new();
-    builder = builder.add_leaf(0, asset_metadata_script.clone())
-        .map_err(|_| "Failed to add leaf to Taproot tree")?;
-    
-    // Finalize the Taproot output
-    let spend_info = builder.finalize(&secp, internal_key)
-        .map_err(|_| "Failed to finalize Taproot output")?;
-    
-    // Create the Taproot output script
-    let taproot_script = Script::new_v1_p2tr(&secp, internal_key, spend_info.merkle_root());
+    // The asset ID commits to the first input, and the output to the whole
+    // supply held by the issuer, as the proof file checks
+    let genesis = AssetGenesis::new(issuer_inputs[0].0, &asset.name, &asset.metadata, 0);
+    let taproot_script = genesis_commitment(&genesis, asset.supply, internal_key)
+        .output_script()
+        .map_err(|_| "Failed to build asset commitment")?;
     
     // Add the asset issuance output
     outputs.push(TxOut {
@@ -193,6 +196,9 @@ pub fn issue_asset(
     )?;
     
     // Update the asset with the issuance transaction
+    asset.asset_id = genesis.asset_id();
+    asset.genesis = Some(genesis);
+    asset.issuer_key = Some(internal_key);
     asset.issuance_tx = Some(signed_tx.clone());
     
     // Update the asset holders
@@ -201,6 +207,27 @@ pub fn issue_asset(
     Ok(signed_tx)
 }
 
+/// Confirm a Taproot asset issuance
+/// 
+/// Starts the asset's proof file once the issuance transaction is mined.
+pub fn confirm_issuance(
+    asset: &mut TaprootAsset,
+    anchor: &AnchorConfirmation,
+) -> Result<(), &'static str> {
+    let issuance_tx = asset.issuance_tx.as_ref().ok_or("Asset has no issuance transaction")?;
+    if anchor.anchor_tx.txid() != issuance_tx.txid() {
+        return Err("Anchor is not the issuance transaction");
+    }
+    
+    let genesis = asset.genesis.clone().ok_or("Asset has no genesis")?;
+    let issuer_key = asset.issuer_key.ok_or("Asset has no issuer key")?;
+    let genesis_proof = AssetProof::genesis(genesis, asset.supply, issuer_key, anchor)
+        .map_err(|_| "Issuance transaction does not commit to the asset")?;
+    
+    asset.proof_file = Some(AssetProofFile::new(genesis_proof).map_err(|_| "Invalid genesis proof")?);
+    Ok(())
+}
+
 /// Transfer a Taproot asset
 /// 
 /// Creates a transfer transaction for a Taproot asset.
@@ -229,6 +256,27 @@ pub fn transfer_asset(
         return Err("Insufficient funds for transfer transaction");
     }
     
+    // The recipient's taproot output key becomes the asset's script key
+    let recipient_bitcoin_address = bitcoin::Address::from_str(&transfer.recipient)
+        .map_err(|_| "Invalid recipient address")?;
+    let recipient_script = recipient_bitcoin_address.script_pubkey();
+    if !recipient_script.is_v1_p2tr() {
+        return Err("Recipient must use a taproot address");
+    }
+    let recipient_key = XOnlyPublicKey::from_slice(&recipient_script.as_bytes()[2..34])
+        .map_err(|_| "Invalid recipient key")?;
+    
+    // Sign the transition with the key of the input spending the asset
+    let proof_file = asset.proof_file.as_ref().ok_or("Asset issuance has not been confirmed")?;
+    let anchor_outpoint = proof_file.last().map(|proof| proof.outpoint())
+        .ok_or("Asset has an empty proof file")?;
+    let (_, _, owner_secret_key) = sender_inputs.iter()
+        .find(|(outpoint, _, _)| *outpoint == anchor_outpoint)
+        .ok_or("Transfer must spend the asset's anchor output")?;
+    let owner = Keypair::from_secret_key(&secp, owner_secret_key);
+    let transition = proof_file.prepare_transfer(&owner, recipient_key, transfer.amount)
+        .map_err(|_| "Sender key does not hold the asset")?;
+    
     // Create inputs
     let mut inputs = Vec::new();
     
@@ -245,55 +293,20 @@ pub fn transfer_asset(
     // Create outputs
     let mut outputs = Vec::new();
     
-    // Create the asset transfer output
-    // This embeds the asset transfer metadata in a Taproot output
-    
-    // Generate internal key for Taproot
-    let sender_secret_key = &sender_inputs[0].2;
-    let sender_pubkey = PublicKey::from_secret_key(&secp, sender_secret_key);
-    let internal_key = XOnlyPublicKey::from_slice(&sender_pubkey.serialize()[1..33])
-        .map_err(|_| "Failed to create internal key")?;
-    
-    // Create asset transfer script
-    let asset_transfer_script = bitcoin::blockdata::script::Builder::new()
-        .push_opcode(bitcoin::blockdata::opcodes::all::OP_RETURN)
-        .push_slice(b"TRANSFER")
-        .push_slice(&asset.asset_id)
-        .push_slice(transfer.sender.as_bytes())
-        .push_slice(transfer.recipient.as_bytes())
-        .push_slice(&transfer.amount.to_le_bytes())
-        .into_script();
-    
-    // Build Taproot tree with asset transfer metadata
-    let mut builder = TaprootBuilder::new();
-    builder = builder.add_leaf(0, asset_transfer_script.clone())
-        .map_err(|_| "Failed to add leaf to Taproot tree")?;
-    
-    // Finalize the Taproot output
-    let spend_info = builder.finalize(&secp, internal_key)
-        .map_err(|_| "Failed to finalize Taproot output")?;
-    
-    // Create the Taproot output script
-    let taproot_script = Script::new_v1_p2tr(&secp, internal_key, spend_info.merkle_root());
-    
-    // Parse recipient address
-    let recipient_bitcoin_address = bitcoin::Address::from_str(&transfer.recipient)
-        .map_err(|_| "Invalid recipient address")?;
-    
-    // Add the asset transfer output
-    outputs.push(TxOut {
-        value: 10000, // Minimum amount for a valid output
-        script_pubkey: taproot_script,
-    });
-    
-    // Add the recipient output
-    outputs.push(TxOut {
-        value: 10000, // Minimum amount for a valid output
-        script_pubkey: recipient_bitcoin_address.script_pubkey(),
-    });
+    // Create the asset outputs: the recipient's commitment, then the sender's asset change
+    let asset_scripts = transition.output_scripts()
+        .map_err(|_| "Failed to build asset commitment")?;
+    let asset_outputs_amount = 10000 * asset_scripts.len() as u64;
+    for script_pubkey in asset_scripts {
+        outputs.push(TxOut {
+            value: 10000, // Minimum amount for a valid output
+            script_pubkey,
+        });
+    }
     
     // Add change output if necessary
-    let change_amount = input_amount - 20000 - 1000; // Subtract output amounts and fee
+    let change_amount = input_amount.checked_sub(asset_outputs_amount + 1000) // Subtract output amounts and fee
+        .ok_or("Insufficient funds for transfer transaction")?;
     if change_amount > 546 { // Dust limit
         // Parse sender address
         let sender_bitcoin_address = bitcoin::Address::from_str(&transfer.sender)
@@ -335,9 +348,30 @@ pub fn transfer_asset(
     let recipient_balance = *asset.holders.get(&transfer.recipient).unwrap_or(&0);
     asset.holders.insert(transfer.recipient.clone(), recipient_balance + transfer.amount);
     
+    asset.pending_transition = Some(transition);
+    
     Ok(signed_tx)
 }
 
+/// Confirm a Taproot asset transfer
+/// 
+/// Extends the proof chain once the transfer transaction is mined and returns
+/// the recipient's proof file; the asset keeps the sender's change.
+pub fn confirm_transfer(
+    asset: &mut TaprootAsset,
+    anchor: &AnchorConfirmation,
+) -> Result<AssetProofFile, &'static str> {
+    let transition = asset.pending_transition.as_ref().ok_or("No transfer awaiting confirmation")?;
+    let proof_file = asset.proof_file.as_ref().ok_or("Asset issuance has not been confirmed")?;
+    
+    let TransferProofs { recipient, change } = proof_file.complete_transfer(transition, anchor)
+        .map_err(|_| "Transfer transaction does not anchor the transition")?;
+    
+    asset.proof_file = change;
+    asset.pending_transition = None;
+    Ok(recipient)
+}
+
 /// Sign a transaction
 /// 
 /// Signs a transaction with the provided inputs.
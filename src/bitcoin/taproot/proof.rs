@@ -0,0 +1,1356 @@
+// src/bitcoin/taproot/proof.rs
+
+//! Offline-verifiable Taproot Asset proof files
+//!
+//! A proof file is the chain of state transitions of an asset from its genesis
+//! to the current holder. Each entry carries the anchor transaction, its merkle
+//! inclusion proof against a block header, the taproot commitment path from
+//! the asset leaf to the anchor output key and, for partial transfers, the
+//! split commitment binding the transferred amount to the spent input.
+//!
+//! A recipient only needs the block headers and the proof file to confirm that
+//! the asset is owned by their script key and that no units were created along
+//! the way.
+
+use std::collections::HashMap;
+
+use bitcoin::block::Header;
+use bitcoin::consensus::{deserialize, serialize};
+use bitcoin::hashes::{sha256, sha256d, Hash};
+use bitcoin::secp256k1::{schnorr, Keypair, Message, Secp256k1, XOnlyPublicKey};
+use bitcoin::taproot::{LeafVersion, TapLeafHash, TapNodeHash};
+use bitcoin::{BlockHash, OutPoint, ScriptBuf, Transaction, TxMerkleNode, Txid};
+
+use crate::bitcoin::{BitcoinError, BitcoinResult};
+
+/// Magic bytes at the start of an encoded proof file
+pub const PROOF_FILE_MAGIC: &[u8; 4] = b"TAPP";
+
+/// Current proof file encoding version
+pub const PROOF_FILE_VERSION: u32 = 1;
+
+/// Marker prefixed to the tapscript leaf carrying the asset commitment
+const ASSET_COMMITMENT_MARKER: &[u8] = b"taproot-assets";
+
+/// Asset commitment version
+const ASSET_COMMITMENT_VERSION: u8 = 0;
+
+/// Node of a merkle-sum tree: a hash plus the sum of the amounts below it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MerkleSumNode {
+    /// Node hash
+    pub hash: [u8; 32],
+    /// Sum of all leaf amounts below this node
+    pub sum: u64,
+}
+
+impl MerkleSumNode {
+    /// Leaf node
+    pub fn leaf(hash: [u8; 32], sum: u64) -> Self {
+        Self { hash, sum }
+    }
+
+    /// Parent of two nodes, committing to both hashes and sums
+    pub fn branch(left: &MerkleSumNode, right: &MerkleSumNode) -> BitcoinResult<Self> {
+        let sum = left.sum.checked_add(right.sum)
+            .ok_or_else(|| BitcoinError::TaprootError("Merkle-sum overflow".to_string()))?;
+
+        let mut data = Vec::with_capacity(80);
+        data.extend_from_slice(&left.hash);
+        data.extend_from_slice(&left.sum.to_be_bytes());
+        data.extend_from_slice(&right.hash);
+        data.extend_from_slice(&right.sum.to_be_bytes());
+
+        Ok(Self { hash: sha256::Hash::hash(&data).to_byte_array(), sum })
+    }
+
+    /// Root of a list of leaves; an odd node is carried up unchanged
+    pub fn root(leaves: &[MerkleSumNode]) -> BitcoinResult<Self> {
+        if leaves.is_empty() {
+            return Ok(Self { hash: [0u8; 32], sum: 0 });
+        }
+
+        let mut level = leaves.to_vec();
+        while level.len() > 1 {
+            let mut next = Vec::with_capacity(level.len().div_ceil(2));
+            for pair in level.chunks(2) {
+                next.push(match pair {
+                    [left, right] => Self::branch(left, right)?,
+                    [single] => *single,
+                    _ => unreachable!(),
+                });
+            }
+            level = next;
+        }
+
+        Ok(level[0])
+    }
+
+    /// Inclusion path for leaf `index`: siblings bottom-up, flagged if they sit on the left
+    pub fn path(leaves: &[MerkleSumNode], index: usize) -> BitcoinResult<Vec<(MerkleSumNode, bool)>> {
+        if index >= leaves.len() {
+            return Err(BitcoinError::TaprootError(format!("Leaf {} out of range", index)));
+        }
+
+        let mut path = Vec::new();
+        let mut level = leaves.to_vec();
+        let mut position = index;
+
+        while level.len() > 1 {
+            let sibling = position ^ 1;
+            if sibling < level.len() {
+                path.push((level[sibling], sibling < position));
+            }
+
+            let mut next = Vec::with_capacity(level.len().div_ceil(2));
+            for pair in level.chunks(2) {
+                next.push(match pair {
+                    [left, right] => Self::branch(left, right)?,
+                    [single] => *single,
+                    _ => unreachable!(),
+                });
+            }
+            level = next;
+            position /= 2;
+        }
+
+        Ok(path)
+    }
+
+    /// Folds an inclusion path from `leaf` up to the root
+    pub fn fold_path(leaf: MerkleSumNode, path: &[(MerkleSumNode, bool)]) -> BitcoinResult<Self> {
+        path.iter().try_fold(leaf, |node, (sibling, sibling_is_left)| {
+            if *sibling_is_left {
+                Self::branch(sibling, &node)
+            } else {
+                Self::branch(&node, sibling)
+            }
+        })
+    }
+}
+
+/// Genesis parameters from which the asset ID is derived
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AssetGenesis {
+    /// First input of the genesis transaction (makes the ID unique)
+    pub first_prev_out: OutPoint,
+    /// Asset tag (name)
+    pub tag: String,
+    /// Hash of the asset metadata
+    pub meta_hash: [u8; 32],
+    /// Output of the genesis transaction anchoring the asset
+    pub output_index: u32,
+}
+
+impl AssetGenesis {
+    /// Creates genesis parameters, hashing the metadata
+    pub fn new(first_prev_out: OutPoint, tag: &str, metadata: &str, output_index: u32) -> Self {
+        Self {
+            first_prev_out,
+            tag: tag.to_string(),
+            meta_hash: sha256::Hash::hash(metadata.as_bytes()).to_byte_array(),
+            output_index,
+        }
+    }
+
+    /// Asset ID: `sha256(first_prev_out || sha256(tag) || meta_hash || output_index)`
+    pub fn asset_id(&self) -> [u8; 32] {
+        let mut data = Vec::with_capacity(36 + 32 + 32 + 4);
+        data.extend_from_slice(&serialize(&self.first_prev_out));
+        data.extend_from_slice(&sha256::Hash::hash(self.tag.as_bytes()).to_byte_array());
+        data.extend_from_slice(&self.meta_hash);
+        data.extend_from_slice(&self.output_index.to_be_bytes());
+        sha256::Hash::hash(&data).to_byte_array()
+    }
+}
+
+/// Link from an asset to the asset it spends
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PrevWitness {
+    /// Anchor outpoint of the spent asset
+    pub prev_outpoint: OutPoint,
+    /// Signature of the previous script key over the new asset state
+    pub signature: schnorr::Signature,
+}
+
+/// An asset as committed inside an anchor output
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AssetLeaf {
+    /// Asset ID
+    pub asset_id: [u8; 32],
+    /// Amount in atomic units
+    pub amount: u64,
+    /// Key that controls the asset
+    pub script_key: XOnlyPublicKey,
+    /// Spent asset, absent for genesis and split outputs
+    pub prev_witness: Option<PrevWitness>,
+    /// Root of the split commitment, present on the root asset of a split
+    pub split_commitment_root: Option<MerkleSumNode>,
+}
+
+impl AssetLeaf {
+    /// Creates an asset leaf without witness or split
+    pub fn new(asset_id: [u8; 32], amount: u64, script_key: XOnlyPublicKey) -> Self {
+        Self {
+            asset_id,
+            amount,
+            script_key,
+            prev_witness: None,
+            split_commitment_root: None,
+        }
+    }
+
+    /// Leaf hash, covering every field
+    pub fn leaf_hash(&self) -> [u8; 32] {
+        let mut writer = ProofWriter::default();
+        writer.write_asset(self);
+        sha256::Hash::hash(&writer.0).to_byte_array()
+    }
+
+    /// Merkle-sum leaf of this asset
+    pub fn to_node(&self) -> MerkleSumNode {
+        MerkleSumNode::leaf(self.leaf_hash(), self.amount)
+    }
+
+    /// Message the previous owner signs to authorise this state
+    pub fn transition_message(&self, prev_outpoint: &OutPoint) -> [u8; 32] {
+        let mut unsigned = self.clone();
+        unsigned.prev_witness = None;
+
+        let mut data = serialize(prev_outpoint);
+        data.extend_from_slice(&unsigned.leaf_hash());
+        sha256::Hash::hash(&data).to_byte_array()
+    }
+
+    /// Signs the transition from `prev_outpoint` with the previous owner's key
+    pub fn sign_transition(&mut self, prev_outpoint: OutPoint, prev_owner: &Keypair) {
+        let secp = Secp256k1::new();
+        let message = Message::from_digest(self.transition_message(&prev_outpoint));
+        self.prev_witness = Some(PrevWitness {
+            prev_outpoint,
+            signature: secp.sign_schnorr_no_aux_rand(&message, prev_owner),
+        });
+    }
+}
+
+/// Path from the assets in an output to its taproot output key
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TapCommitmentProof {
+    /// Internal key of the anchor output
+    pub internal_key: XOnlyPublicKey,
+    /// Every asset committed in the output
+    pub output_assets: Vec<AssetLeaf>,
+    /// Sibling branch of the commitment leaf in the tap tree, if any
+    pub tap_sibling: Option<TapNodeHash>,
+}
+
+impl TapCommitmentProof {
+    /// Creates a commitment over `output_assets`
+    pub fn new(internal_key: XOnlyPublicKey, output_assets: Vec<AssetLeaf>, tap_sibling: Option<TapNodeHash>) -> Self {
+        Self { internal_key, output_assets, tap_sibling }
+    }
+
+    /// Merkle-sum root over the assets, in canonical (leaf hash) order
+    pub fn asset_root(&self) -> BitcoinResult<MerkleSumNode> {
+        let mut leaves: Vec<MerkleSumNode> = self.output_assets.iter().map(|a| a.to_node()).collect();
+        leaves.sort_by_key(|leaf| leaf.hash);
+        MerkleSumNode::root(&leaves)
+    }
+
+    /// Tapscript leaf carrying the asset root
+    pub fn commitment_script(&self) -> BitcoinResult<ScriptBuf> {
+        let root = self.asset_root()?;
+        let mut bytes = ASSET_COMMITMENT_MARKER.to_vec();
+        bytes.push(ASSET_COMMITMENT_VERSION);
+        bytes.extend_from_slice(&root.hash);
+        bytes.extend_from_slice(&root.sum.to_be_bytes());
+        Ok(ScriptBuf::from_bytes(bytes))
+    }
+
+    /// P2TR script of the anchor output
+    pub fn output_script(&self) -> BitcoinResult<ScriptBuf> {
+        let secp = Secp256k1::verification_only();
+        let leaf = TapNodeHash::from(TapLeafHash::from_script(&self.commitment_script()?, LeafVersion::TapScript));
+        let merkle_root = match self.tap_sibling {
+            Some(sibling) => TapNodeHash::from_node_hashes(leaf, sibling),
+            None => leaf,
+        };
+        Ok(ScriptBuf::new_p2tr(&secp, self.internal_key, Some(merkle_root)))
+    }
+
+    /// Whether `asset` is part of this commitment
+    pub fn contains(&self, asset: &AssetLeaf) -> bool {
+        self.output_assets.iter().any(|a| a == asset)
+    }
+}
+
+/// Merkle inclusion of a transaction in a block
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TxMerkleProof {
+    /// Position of the transaction in the block
+    pub tx_index: u32,
+    /// Sibling hashes, bottom-up
+    pub siblings: Vec<[u8; 32]>,
+}
+
+impl TxMerkleProof {
+    /// Builds the proof for `txids[index]`
+    pub fn from_block_txids(txids: &[Txid], index: usize) -> BitcoinResult<Self> {
+        if index >= txids.len() {
+            return Err(BitcoinError::TaprootError(format!("Transaction {} not in block", index)));
+        }
+
+        let mut level: Vec<[u8; 32]> = txids.iter().map(|t| t.to_byte_array()).collect();
+        let mut position = index;
+        let mut siblings = Vec::new();
+
+        while level.len() > 1 {
+            if level.len() % 2 == 1 {
+                level.push(*level.last().unwrap());
+            }
+            siblings.push(level[position ^ 1]);
+            level = level.chunks(2).map(|pair| hash_pair(&pair[0], &pair[1])).collect();
+            position /= 2;
+        }
+
+        Ok(Self { tx_index: index as u32, siblings })
+    }
+
+    /// Merkle root implied by the proof for `txid`
+    pub fn compute_root(&self, txid: &Txid) -> TxMerkleNode {
+        let mut hash = txid.to_byte_array();
+        let mut position = self.tx_index;
+
+        for sibling in &self.siblings {
+            hash = if position.is_multiple_of(2) {
+                hash_pair(&hash, sibling)
+            } else {
+                hash_pair(sibling, &hash)
+            };
+            position /= 2;
+        }
+
+        TxMerkleNode::from_byte_array(hash)
+    }
+}
+
+/// Binds a split output to the root asset that spent the input
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SplitProof {
+    /// Output of the anchor transaction holding the root asset
+    pub root_output_index: u32,
+    /// Root asset, carrying the split commitment root and the spend witness
+    pub root_asset: AssetLeaf,
+    /// Commitment of the root asset in its output
+    pub root_commitment: TapCommitmentProof,
+    /// Inclusion path of this output's split leaf
+    pub path: Vec<(MerkleSumNode, bool)>,
+    /// Inclusion path of the root asset's own split leaf
+    pub root_path: Vec<(MerkleSumNode, bool)>,
+}
+
+/// Split leaf for `amount` units sent to `script_key` in output `output_index`
+pub fn split_leaf(asset_id: &[u8; 32], output_index: u32, script_key: &XOnlyPublicKey, amount: u64) -> MerkleSumNode {
+    let mut data = Vec::with_capacity(68);
+    data.extend_from_slice(&output_index.to_be_bytes());
+    data.extend_from_slice(asset_id);
+    data.extend_from_slice(&script_key.serialize());
+    MerkleSumNode::leaf(sha256::Hash::hash(&data).to_byte_array(), amount)
+}
+
+/// One state transition of an asset
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AssetProof {
+    /// Transaction anchoring this state
+    pub anchor_tx: Transaction,
+    /// Header of the block containing the anchor transaction
+    pub block_header: Header,
+    /// Inclusion of the anchor transaction in the block
+    pub tx_merkle_proof: TxMerkleProof,
+    /// Anchor output holding the asset
+    pub output_index: u32,
+    /// The asset at this state
+    pub asset: AssetLeaf,
+    /// Commitment path from the asset to the output key
+    pub commitment: TapCommitmentProof,
+    /// Genesis parameters, only on the first proof
+    pub genesis: Option<AssetGenesis>,
+    /// Split commitment, only for partial transfers
+    pub split: Option<SplitProof>,
+}
+
+impl AssetProof {
+    /// Outpoint anchoring the asset
+    pub fn outpoint(&self) -> OutPoint {
+        OutPoint { txid: self.anchor_tx.compute_txid(), vout: self.output_index }
+    }
+
+    /// Genesis proof of `supply` units issued to `issuer`
+    ///
+    /// The genesis output is a P2TR output with the issuer as internal key,
+    /// committing to [`genesis_commitment`].
+    pub fn genesis(
+        genesis: AssetGenesis,
+        supply: u64,
+        issuer: XOnlyPublicKey,
+        anchor: &AnchorConfirmation,
+    ) -> BitcoinResult<Self> {
+        let commitment = genesis_commitment(&genesis, supply, issuer);
+        let asset = commitment.output_assets[0].clone();
+        anchor.proof(genesis.output_index, asset, commitment, Some(genesis), None)
+    }
+}
+
+/// Commitment of a freshly issued asset, held by the issuer
+pub fn genesis_commitment(genesis: &AssetGenesis, supply: u64, issuer: XOnlyPublicKey) -> TapCommitmentProof {
+    let asset = AssetLeaf::new(genesis.asset_id(), supply, issuer);
+    TapCommitmentProof::new(issuer, vec![asset], None)
+}
+
+/// A confirmed anchor transaction
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AnchorConfirmation {
+    /// Anchor transaction
+    pub anchor_tx: Transaction,
+    /// Header of the block containing it
+    pub block_header: Header,
+    /// Inclusion of the transaction in the block
+    pub tx_merkle_proof: TxMerkleProof,
+}
+
+impl AnchorConfirmation {
+    /// Proof of `asset` anchored at `output_index`, checked against the output script
+    fn proof(
+        &self,
+        output_index: u32,
+        asset: AssetLeaf,
+        commitment: TapCommitmentProof,
+        genesis: Option<AssetGenesis>,
+        split: Option<SplitProof>,
+    ) -> BitcoinResult<AssetProof> {
+        let output = self.anchor_tx.output.get(output_index as usize)
+            .ok_or_else(|| BitcoinError::TaprootError(format!("Anchor output {} does not exist", output_index)))?;
+        if output.script_pubkey != commitment.output_script()? {
+            return Err(BitcoinError::TaprootError(format!(
+                "Anchor output {} does not commit to the asset", output_index
+            )));
+        }
+
+        Ok(AssetProof {
+            anchor_tx: self.anchor_tx.clone(),
+            block_header: self.block_header,
+            tx_merkle_proof: self.tx_merkle_proof.clone(),
+            output_index,
+            asset,
+            commitment,
+            genesis,
+            split,
+        })
+    }
+}
+
+/// A signed transfer out of the last state of a proof file, waiting for its anchor
+///
+/// The anchor transaction spends `prev_outpoint`, pays the recipient's
+/// commitment at output 0 and, for a partial transfer, the sender's change at
+/// output 1.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AssetTransition {
+    /// Anchor outpoint of the spent asset
+    pub prev_outpoint: OutPoint,
+    /// Commitment of the recipient's asset
+    pub recipient_commitment: TapCommitmentProof,
+    /// Split commitment of a partial transfer, the change being its root asset
+    pub split: Option<SplitProof>,
+}
+
+impl AssetTransition {
+    /// Output index of the recipient's asset
+    pub const RECIPIENT_OUTPUT: u32 = 0;
+
+    /// Output index of the sender's change
+    pub const CHANGE_OUTPUT: u32 = 1;
+
+    /// Scripts of the anchor outputs, in output order
+    pub fn output_scripts(&self) -> BitcoinResult<Vec<ScriptBuf>> {
+        let mut scripts = vec![self.recipient_commitment.output_script()?];
+        if let Some(split) = &self.split {
+            scripts.push(split.root_commitment.output_script()?);
+        }
+        Ok(scripts)
+    }
+}
+
+/// Proof files of both sides of a confirmed transfer
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TransferProofs {
+    /// Proof file to hand to the recipient
+    pub recipient: AssetProofFile,
+    /// Proof file of the sender's change, absent for a full transfer
+    pub change: Option<AssetProofFile>,
+}
+
+/// Result of a successful proof file verification
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct VerifiedAsset {
+    /// Asset ID
+    pub asset_id: [u8; 32],
+    /// Amount held at the last state
+    pub amount: u64,
+    /// Key controlling the asset
+    pub script_key: XOnlyPublicKey,
+    /// Anchor outpoint of the last state
+    pub outpoint: OutPoint,
+    /// Amount issued at genesis, over every leaf of the asset in the genesis output
+    pub genesis_supply: u64,
+    /// Number of transfers after genesis
+    pub transfers: usize,
+}
+
+/// Chain of proofs from genesis to the current holder
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AssetProofFile {
+    /// Proofs, genesis first
+    pub proofs: Vec<AssetProof>,
+}
+
+impl AssetProofFile {
+    /// Starts a proof file from the genesis proof
+    pub fn new(genesis_proof: AssetProof) -> BitcoinResult<Self> {
+        if genesis_proof.genesis.is_none() {
+            return Err(BitcoinError::TaprootError("First proof must carry genesis parameters".to_string()));
+        }
+        Ok(Self { proofs: vec![genesis_proof] })
+    }
+
+    /// Appends a transfer proof
+    pub fn append(&mut self, proof: AssetProof) {
+        self.proofs.push(proof);
+    }
+
+    /// The last (current) state
+    pub fn last(&self) -> Option<&AssetProof> {
+        self.proofs.last()
+    }
+
+    /// Signs a transfer of `amount` units from the last state to `recipient`
+    ///
+    /// `owner` must hold the last state; any remaining units stay with it as change.
+    pub fn prepare_transfer(&self, owner: &Keypair, recipient: XOnlyPublicKey, amount: u64) -> BitcoinResult<AssetTransition> {
+        let last = self.last()
+            .ok_or_else(|| BitcoinError::TaprootError("Empty proof file".to_string()))?;
+        let owner_key = owner.x_only_public_key().0;
+
+        if last.asset.script_key != owner_key {
+            return Err(BitcoinError::TaprootError("Asset is not owned by the signing key".to_string()));
+        }
+        if amount == 0 || amount > last.asset.amount {
+            return Err(BitcoinError::TaprootError(format!(
+                "Cannot transfer {} of {} units", amount, last.asset.amount
+            )));
+        }
+
+        let asset_id = last.asset.asset_id;
+        let prev_outpoint = last.outpoint();
+        let change = last.asset.amount - amount;
+
+        if change == 0 {
+            let mut asset = AssetLeaf::new(asset_id, amount, recipient);
+            asset.sign_transition(prev_outpoint, owner);
+            return Ok(AssetTransition {
+                prev_outpoint,
+                recipient_commitment: TapCommitmentProof::new(recipient, vec![asset], None),
+                split: None,
+            });
+        }
+
+        let split_leaves = vec![
+            split_leaf(&asset_id, AssetTransition::RECIPIENT_OUTPUT, &recipient, amount),
+            split_leaf(&asset_id, AssetTransition::CHANGE_OUTPUT, &owner_key, change),
+        ];
+
+        let mut root_asset = AssetLeaf::new(asset_id, change, owner_key);
+        root_asset.split_commitment_root = Some(MerkleSumNode::root(&split_leaves)?);
+        root_asset.sign_transition(prev_outpoint, owner);
+
+        let recipient_asset = AssetLeaf::new(asset_id, amount, recipient);
+        Ok(AssetTransition {
+            prev_outpoint,
+            recipient_commitment: TapCommitmentProof::new(recipient, vec![recipient_asset], None),
+            split: Some(SplitProof {
+                root_output_index: AssetTransition::CHANGE_OUTPUT,
+                root_commitment: TapCommitmentProof::new(owner_key, vec![root_asset.clone()], None),
+                root_asset,
+                path: MerkleSumNode::path(&split_leaves, 0)?,
+                root_path: MerkleSumNode::path(&split_leaves, 1)?,
+            }),
+        })
+    }
+
+    /// Extends the file with a confirmed transfer, one file per side
+    pub fn complete_transfer(&self, transition: &AssetTransition, anchor: &AnchorConfirmation) -> BitcoinResult<TransferProofs> {
+        if self.last().map(|last| last.outpoint()) != Some(transition.prev_outpoint) {
+            return Err(BitcoinError::TaprootError("Transfer does not spend the last state".to_string()));
+        }
+        if !spends(&anchor.anchor_tx, &transition.prev_outpoint) {
+            return Err(BitcoinError::TaprootError("Anchor transaction does not spend the asset".to_string()));
+        }
+
+        let recipient_asset = transition.recipient_commitment.output_assets[0].clone();
+        let mut recipient = self.clone();
+        recipient.append(anchor.proof(
+            AssetTransition::RECIPIENT_OUTPUT,
+            recipient_asset,
+            transition.recipient_commitment.clone(),
+            None,
+            transition.split.clone(),
+        )?);
+
+        let change = match &transition.split {
+            Some(split) => {
+                let mut change = self.clone();
+                change.append(anchor.proof(
+                    split.root_output_index,
+                    split.root_asset.clone(),
+                    split.root_commitment.clone(),
+                    None,
+                    Some(SplitProof { path: split.root_path.clone(), ..split.clone() }),
+                )?);
+                Some(change)
+            }
+            None => None,
+        };
+
+        Ok(TransferProofs { recipient, change })
+    }
+
+    /// Verifies the whole chain against a set of block headers
+    pub fn verify(&self, headers: &[Header]) -> BitcoinResult<VerifiedAsset> {
+        let headers: HashMap<BlockHash, &Header> = headers.iter().map(|h| (h.block_hash(), h)).collect();
+        let secp = Secp256k1::verification_only();
+
+        let genesis_proof = self.proofs.first()
+            .ok_or_else(|| BitcoinError::TaprootError("Empty proof file".to_string()))?;
+        let genesis = genesis_proof.genesis.as_ref()
+            .ok_or_else(|| BitcoinError::TaprootError("Missing genesis parameters".to_string()))?;
+
+        for (index, proof) in self.proofs.iter().enumerate() {
+            Self::verify_anchor(proof, &headers)
+                .map_err(|e| BitcoinError::TaprootError(format!("Proof {}: {}", index, e)))?;
+        }
+
+        // Genesis: asset ID derived from the parameters and the genesis tx spends first_prev_out
+        if genesis_proof.asset.asset_id != genesis.asset_id() {
+            return Err(BitcoinError::TaprootError("Asset ID does not match genesis".to_string()));
+        }
+        if genesis_proof.output_index != genesis.output_index
+            || !spends(&genesis_proof.anchor_tx, &genesis.first_prev_out)
+        {
+            return Err(BitcoinError::TaprootError("Genesis transaction does not match genesis parameters".to_string()));
+        }
+
+        for (index, pair) in self.proofs.windows(2).enumerate() {
+            Self::verify_transition(&pair[0], &pair[1], &secp)
+                .map_err(|e| BitcoinError::TaprootError(format!("Transfer {}: {}", index + 1, e)))?;
+        }
+
+        // The issuer may commit several leaves of the asset, all part of the supply
+        let genesis_supply = genesis_proof.commitment.output_assets.iter()
+            .filter(|asset| asset.asset_id == genesis_proof.asset.asset_id)
+            .try_fold(0u64, |sum, asset| sum.checked_add(asset.amount))
+            .ok_or_else(|| BitcoinError::TaprootError("Genesis supply overflow".to_string()))?;
+
+        let last = self.proofs.last().unwrap_or(genesis_proof);
+        Ok(VerifiedAsset {
+            asset_id: last.asset.asset_id,
+            amount: last.asset.amount,
+            script_key: last.asset.script_key,
+            outpoint: last.outpoint(),
+            genesis_supply,
+            transfers: self.proofs.len() - 1,
+        })
+    }
+
+    /// Verifies the chain and that the asset is owned by `owner`
+    pub fn verify_ownership(&self, headers: &[Header], owner: &XOnlyPublicKey) -> BitcoinResult<VerifiedAsset> {
+        let verified = self.verify(headers)?;
+        if verified.script_key != *owner {
+            return Err(BitcoinError::TaprootError("Asset is not owned by the given key".to_string()));
+        }
+        Ok(verified)
+    }
+
+    /// Checks block inclusion, proof of work and the taproot commitment of one proof
+    fn verify_anchor(proof: &AssetProof, headers: &HashMap<BlockHash, &Header>) -> Result<(), String> {
+        let header = headers.get(&proof.block_header.block_hash())
+            .ok_or("anchor block header not in the header set")?;
+
+        header.validate_pow(header.target()).map_err(|e| format!("invalid proof of work: {}", e))?;
+
+        let txid = proof.anchor_tx.compute_txid();
+        if proof.tx_merkle_proof.compute_root(&txid) != header.merkle_root {
+            return Err("anchor transaction not included in block".to_string());
+        }
+
+        let output = proof.anchor_tx.output.get(proof.output_index as usize)
+            .ok_or("anchor output does not exist")?;
+
+        if !proof.commitment.contains(&proof.asset) {
+            return Err("asset missing from output commitment".to_string());
+        }
+
+        let expected = proof.commitment.output_script().map_err(|e| e.to_string())?;
+        if output.script_pubkey != expected {
+            return Err("taproot commitment does not match anchor output".to_string());
+        }
+
+        Ok(())
+    }
+
+    /// Checks that `next` is a valid spend of `prev` that conserves supply
+    fn verify_transition(
+        prev: &AssetProof,
+        next: &AssetProof,
+        secp: &Secp256k1<bitcoin::secp256k1::VerifyOnly>,
+    ) -> Result<(), String> {
+        let prev_outpoint = prev.outpoint();
+
+        if next.asset.asset_id != prev.asset.asset_id {
+            return Err("asset ID changed".to_string());
+        }
+        if !spends(&next.anchor_tx, &prev_outpoint) {
+            return Err("anchor transaction does not spend the previous anchor".to_string());
+        }
+
+        // The asset carrying the spend witness: the asset itself, or the root asset of a split
+        let spending_asset = match &next.split {
+            None => {
+                // Otherwise the root of a split could pass as a full transfer
+                if next.asset.split_commitment_root.is_some() {
+                    return Err("split commitment on a transfer without a split".to_string());
+                }
+                if next.asset.amount != prev.asset.amount {
+                    return Err(format!(
+                        "amount changed from {} to {} without a split", prev.asset.amount, next.asset.amount
+                    ));
+                }
+                &next.asset
+            }
+            Some(split) => {
+                let root = split.root_asset.split_commitment_root
+                    .ok_or("root asset has no split commitment")?;
+                if root.sum != prev.asset.amount {
+                    return Err(format!(
+                        "split commitment sums to {} but {} units were spent", root.sum, prev.asset.amount
+                    ));
+                }
+
+                let leaf = split_leaf(&next.asset.asset_id, next.output_index, &next.asset.script_key, next.asset.amount);
+                let folded = MerkleSumNode::fold_path(leaf, &split.path).map_err(|e| e.to_string())?;
+                if folded != root {
+                    return Err("split output not included in split commitment".to_string());
+                }
+
+                // The root asset keeps exactly its own share of the split
+                let root_leaf = split_leaf(
+                    &split.root_asset.asset_id, split.root_output_index, &split.root_asset.script_key, split.root_asset.amount,
+                );
+                let folded = MerkleSumNode::fold_path(root_leaf, &split.root_path).map_err(|e| e.to_string())?;
+                if folded != root {
+                    return Err("root asset amount not included in split commitment".to_string());
+                }
+                if next.output_index == split.root_output_index {
+                    if next.asset != split.root_asset {
+                        return Err("split output shares the root asset's leaf".to_string());
+                    }
+                } else if next.asset.split_commitment_root.is_some() {
+                    return Err("split output carries a split commitment".to_string());
+                }
+
+                if split.root_asset.asset_id != prev.asset.asset_id {
+                    return Err("root asset ID mismatch".to_string());
+                }
+                if !split.root_commitment.contains(&split.root_asset) {
+                    return Err("root asset missing from its commitment".to_string());
+                }
+
+                let root_output = next.anchor_tx.output.get(split.root_output_index as usize)
+                    .ok_or("root asset output does not exist")?;
+                let root_script = split.root_commitment.output_script().map_err(|e| e.to_string())?;
+                if root_output.script_pubkey != root_script {
+                    return Err("root asset commitment does not match anchor output".to_string());
+                }
+
+                &split.root_asset
+            }
+        };
+
+        let witness = spending_asset.prev_witness.as_ref().ok_or("missing spend witness")?;
+        if witness.prev_outpoint != prev_outpoint {
+            return Err("spend witness references a different outpoint".to_string());
+        }
+
+        let message = Message::from_digest(spending_asset.transition_message(&prev_outpoint));
+        secp.verify_schnorr(&witness.signature, &message, &prev.asset.script_key)
+            .map_err(|_| "invalid spend signature from previous owner".to_string())?;
+
+        Ok(())
+    }
+
+    /// Encodes the proof file
+    pub fn encode(&self) -> Vec<u8> {
+        let mut writer = ProofWriter::default();
+        writer.0.extend_from_slice(PROOF_FILE_MAGIC);
+        writer.write_u32(PROOF_FILE_VERSION);
+        writer.write_u32(self.proofs.len() as u32);
+
+        for proof in &self.proofs {
+            writer.write_bytes(&serialize(&proof.anchor_tx));
+            writer.0.extend_from_slice(&serialize(&proof.block_header));
+            writer.write_u32(proof.tx_merkle_proof.tx_index);
+            writer.write_u32(proof.tx_merkle_proof.siblings.len() as u32);
+            for sibling in &proof.tx_merkle_proof.siblings {
+                writer.0.extend_from_slice(sibling);
+            }
+            writer.write_u32(proof.output_index);
+            writer.write_asset(&proof.asset);
+            writer.write_commitment(&proof.commitment);
+
+            match &proof.genesis {
+                Some(genesis) => {
+                    writer.0.push(1);
+                    writer.0.extend_from_slice(&serialize(&genesis.first_prev_out));
+                    writer.write_bytes(genesis.tag.as_bytes());
+                    writer.0.extend_from_slice(&genesis.meta_hash);
+                    writer.write_u32(genesis.output_index);
+                }
+                None => writer.0.push(0),
+            }
+
+            match &proof.split {
+                Some(split) => {
+                    writer.0.push(1);
+                    writer.write_u32(split.root_output_index);
+                    writer.write_asset(&split.root_asset);
+                    writer.write_commitment(&split.root_commitment);
+                    writer.write_path(&split.path);
+                    writer.write_path(&split.root_path);
+                }
+                None => writer.0.push(0),
+            }
+        }
+
+        writer.0
+    }
+
+    /// Decodes a proof file
+    pub fn decode(bytes: &[u8]) -> BitcoinResult<Self> {
+        let mut reader = ProofReader { data: bytes, pos: 0 };
+
+        if reader.take(4)? != PROOF_FILE_MAGIC {
+            return Err(BitcoinError::TaprootError("Not a Taproot Asset proof file".to_string()));
+        }
+        let version = reader.read_u32()?;
+        if version != PROOF_FILE_VERSION {
+            return Err(BitcoinError::TaprootError(format!("Unsupported proof file version {}", version)));
+        }
+
+        let count = reader.read_u32()?;
+        let mut proofs = Vec::new();
+
+        for _ in 0..count {
+            let anchor_tx: Transaction = deserialize(reader.read_bytes()?)
+                .map_err(|e| BitcoinError::TaprootError(format!("Invalid anchor transaction: {}", e)))?;
+            let block_header: Header = deserialize(reader.take(80)?)
+                .map_err(|e| BitcoinError::TaprootError(format!("Invalid block header: {}", e)))?;
+
+            let tx_index = reader.read_u32()?;
+            let sibling_count = reader.read_u32()?;
+            let siblings = (0..sibling_count)
+                .map(|_| reader.read_hash())
+                .collect::<BitcoinResult<Vec<_>>>()?;
+
+            let output_index = reader.read_u32()?;
+            let asset = reader.read_asset()?;
+            let commitment = reader.read_commitment()?;
+
+            let genesis = if reader.read_flag()? {
+                let first_prev_out: OutPoint = deserialize(reader.take(36)?)
+                    .map_err(|e| BitcoinError::TaprootError(format!("Invalid outpoint: {}", e)))?;
+                let tag = String::from_utf8(reader.read_bytes()?.to_vec())
+                    .map_err(|_| BitcoinError::TaprootError("Invalid asset tag".to_string()))?;
+                let meta_hash = reader.read_hash()?;
+                let output_index = reader.read_u32()?;
+                Some(AssetGenesis { first_prev_out, tag, meta_hash, output_index })
+            } else {
+                None
+            };
+
+            let split = if reader.read_flag()? {
+                let root_output_index = reader.read_u32()?;
+                let root_asset = reader.read_asset()?;
+                let root_commitment = reader.read_commitment()?;
+                let path = reader.read_path()?;
+                let root_path = reader.read_path()?;
+                Some(SplitProof { root_output_index, root_asset, root_commitment, path, root_path })
+            } else {
+                None
+            };
+
+            proofs.push(AssetProof {
+                anchor_tx,
+                block_header,
+                tx_merkle_proof: TxMerkleProof { tx_index, siblings },
+                output_index,
+                asset,
+                commitment,
+                genesis,
+                split,
+            });
+        }
+
+        if reader.pos != bytes.len() {
+            return Err(BitcoinError::TaprootError("Trailing data after proof file".to_string()));
+        }
+
+        Ok(Self { proofs })
+    }
+}
+
+/// Whether `tx` spends `outpoint`
+fn spends(tx: &Transaction, outpoint: &OutPoint) -> bool {
+    tx.input.iter().any(|input| input.previous_output == *outpoint)
+}
+
+/// Bitcoin merkle tree node hash
+fn hash_pair(left: &[u8; 32], right: &[u8; 32]) -> [u8; 32] {
+    let mut data = [0u8; 64];
+    data[..32].copy_from_slice(left);
+    data[32..].copy_from_slice(right);
+    sha256d::Hash::hash(&data).to_byte_array()
+}
+
+/// Serializer for proof file fields
+#[derive(Default)]
+struct ProofWriter(Vec<u8>);
+
+impl ProofWriter {
+    fn write_u32(&mut self, value: u32) {
+        self.0.extend_from_slice(&value.to_be_bytes());
+    }
+
+    fn write_bytes(&mut self, bytes: &[u8]) {
+        self.write_u32(bytes.len() as u32);
+        self.0.extend_from_slice(bytes);
+    }
+
+    fn write_node(&mut self, node: &MerkleSumNode) {
+        self.0.extend_from_slice(&node.hash);
+        self.0.extend_from_slice(&node.sum.to_be_bytes());
+    }
+
+    fn write_path(&mut self, path: &[(MerkleSumNode, bool)]) {
+        self.write_u32(path.len() as u32);
+        for (node, is_left) in path {
+            self.write_node(node);
+            self.0.push(*is_left as u8);
+        }
+    }
+
+    fn write_asset(&mut self, asset: &AssetLeaf) {
+        self.0.extend_from_slice(&asset.asset_id);
+        self.0.extend_from_slice(&asset.amount.to_be_bytes());
+        self.0.extend_from_slice(&asset.script_key.serialize());
+
+        match &asset.prev_witness {
+            Some(witness) => {
+                self.0.push(1);
+                self.0.extend_from_slice(&serialize(&witness.prev_outpoint));
+                self.0.extend_from_slice(witness.signature.as_ref());
+            }
+            None => self.0.push(0),
+        }
+
+        match &asset.split_commitment_root {
+            Some(root) => {
+                self.0.push(1);
+                self.write_node(root);
+            }
+            None => self.0.push(0),
+        }
+    }
+
+    fn write_commitment(&mut self, commitment: &TapCommitmentProof) {
+        self.0.extend_from_slice(&commitment.internal_key.serialize());
+        self.write_u32(commitment.output_assets.len() as u32);
+        for asset in &commitment.output_assets {
+            self.write_asset(asset);
+        }
+        match &commitment.tap_sibling {
+            Some(sibling) => {
+                self.0.push(1);
+                self.0.extend_from_slice(&sibling.to_byte_array());
+            }
+            None => self.0.push(0),
+        }
+    }
+}
+
+/// Deserializer for proof file fields
+struct ProofReader<'a> {
+    data: &'a [u8],
+    pos: usize,
+}
+
+impl<'a> ProofReader<'a> {
+    fn take(&mut self, len: usize) -> BitcoinResult<&'a [u8]> {
+        let end = self.pos.checked_add(len)
+            .filter(|end| *end <= self.data.len())
+            .ok_or_else(|| BitcoinError::TaprootError("Truncated proof file".to_string()))?;
+        let slice = &self.data[self.pos..end];
+        self.pos = end;
+        Ok(slice)
+    }
+
+    fn read_u32(&mut self) -> BitcoinResult<u32> {
+        let mut bytes = [0u8; 4];
+        bytes.copy_from_slice(self.take(4)?);
+        Ok(u32::from_be_bytes(bytes))
+    }
+
+    fn read_u64(&mut self) -> BitcoinResult<u64> {
+        let mut bytes = [0u8; 8];
+        bytes.copy_from_slice(self.take(8)?);
+        Ok(u64::from_be_bytes(bytes))
+    }
+
+    fn read_hash(&mut self) -> BitcoinResult<[u8; 32]> {
+        let mut hash = [0u8; 32];
+        hash.copy_from_slice(self.take(32)?);
+        Ok(hash)
+    }
+
+    fn read_flag(&mut self) -> BitcoinResult<bool> {
+        match self.take(1)?[0] {
+            0 => Ok(false),
+            1 => Ok(true),
+            other => Err(BitcoinError::TaprootError(format!("Invalid flag byte {}", other))),
+        }
+    }
+
+    fn read_bytes(&mut self) -> BitcoinResult<&'a [u8]> {
+        let len = self.read_u32()? as usize;
+        self.take(len)
+    }
+
+    fn read_node(&mut self) -> BitcoinResult<MerkleSumNode> {
+        Ok(MerkleSumNode { hash: self.read_hash()?, sum: self.read_u64()? })
+    }
+
+    fn read_path(&mut self) -> BitcoinResult<Vec<(MerkleSumNode, bool)>> {
+        let len = self.read_u32()?;
+        (0..len)
+            .map(|_| Ok((self.read_node()?, self.read_flag()?)))
+            .collect()
+    }
+
+    fn read_key(&mut self) -> BitcoinResult<XOnlyPublicKey> {
+        Ok(XOnlyPublicKey::from_slice(self.take(32)?)?)
+    }
+
+    fn read_asset(&mut self) -> BitcoinResult<AssetLeaf> {
+        let asset_id = self.read_hash()?;
+        let amount = self.read_u64()?;
+        let script_key = self.read_key()?;
+
+        let prev_witness = if self.read_flag()? {
+            let prev_outpoint: OutPoint = deserialize(self.take(36)?)
+                .map_err(|e| BitcoinError::TaprootError(format!("Invalid outpoint: {}", e)))?;
+            let signature = schnorr::Signature::from_slice(self.take(64)?)?;
+            Some(PrevWitness { prev_outpoint, signature })
+        } else {
+            None
+        };
+
+        let split_commitment_root = if self.read_flag()? { Some(self.read_node()?) } else { None };
+
+        Ok(AssetLeaf { asset_id, amount, script_key, prev_witness, split_commitment_root })
+    }
+
+    fn read_commitment(&mut self) -> BitcoinResult<TapCommitmentProof> {
+        let internal_key = self.read_key()?;
+        let count = self.read_u32()?;
+        let output_assets = (0..count)
+            .map(|_| self.read_asset())
+            .collect::<BitcoinResult<Vec<_>>>()?;
+        let tap_sibling = if self.read_flag()? {
+            Some(TapNodeHash::from_byte_array(self.read_hash()?))
+        } else {
+            None
+        };
+        Ok(TapCommitmentProof { internal_key, output_assets, tap_sibling })
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use bitcoin::absolute::LockTime;
+    use bitcoin::block::Version as BlockVersion;
+    use bitcoin::transaction::Version;
+    use bitcoin::{Amount, CompactTarget, Sequence, TxIn, TxOut, Witness};
+
+    pub(crate) fn keypair(byte: u8) -> Keypair {
+        Keypair::from_seckey_slice(&Secp256k1::new(), &[byte; 32]).unwrap()
+    }
+
+    pub(crate) fn anchor_tx(spending: OutPoint, outputs: Vec<ScriptBuf>) -> Transaction {
+        Transaction {
+            version: Version::TWO,
+            lock_time: LockTime::ZERO,
+            input: vec![TxIn {
+                previous_output: spending,
+                script_sig: ScriptBuf::new(),
+                sequence: Sequence::MAX,
+                witness: Witness::new(),
+            }],
+            output: outputs.into_iter()
+                .map(|script_pubkey| TxOut { value: Amount::from_sat(1_000), script_pubkey })
+                .collect(),
+        }
+    }
+
+    /// Mines a regtest block containing `tx` and a filler transaction
+    pub(crate) fn mine(prev: BlockHash, tx: &Transaction) -> (Header, TxMerkleProof) {
+        let filler = anchor_tx(OutPoint::null(), vec![ScriptBuf::new()]);
+        let txids = vec![filler.compute_txid(), tx.compute_txid()];
+        let proof = TxMerkleProof::from_block_txids(&txids, 1).unwrap();
+
+        let mut header = Header {
+            version: BlockVersion::TWO,
+            prev_blockhash: prev,
+            merkle_root: proof.compute_root(&txids[1]),
+            time: 1_700_000_000,
+            bits: CompactTarget::from_consensus(0x207f_ffff),
+            nonce: 0,
+        };
+        while header.validate_pow(header.target()).is_err() {
+            header.nonce += 1;
+        }
+        (header, proof)
+    }
+
+    /// Issues `leaves` (amount, owner) of one asset in a single genesis output
+    fn issue(leaves: &[(u64, XOnlyPublicKey)]) -> (AssetProofFile, Header) {
+        let internal = keypair(3).x_only_public_key().0;
+        let first_prev_out = OutPoint { txid: Txid::from_byte_array([9u8; 32]), vout: 0 };
+        let genesis = AssetGenesis::new(first_prev_out, "TestCoin", "{\"decimals\":0}", 0);
+        let asset_id = genesis.asset_id();
+
+        let issued: Vec<AssetLeaf> = leaves.iter().map(|(amount, owner)| AssetLeaf::new(asset_id, *amount, *owner)).collect();
+        let genesis_commitment = TapCommitmentProof::new(internal, issued.clone(), None);
+        let genesis_tx = anchor_tx(first_prev_out, vec![genesis_commitment.output_script().unwrap()]);
+        let (genesis_header, genesis_merkle) = mine(BlockHash::all_zeros(), &genesis_tx);
+
+        let file = AssetProofFile::new(AssetProof {
+            anchor_tx: genesis_tx,
+            block_header: genesis_header,
+            tx_merkle_proof: genesis_merkle,
+            output_index: 0,
+            asset: issued[0].clone(),
+            commitment: genesis_commitment,
+            genesis: Some(genesis),
+            split: None,
+        }).unwrap();
+        (file, genesis_header)
+    }
+
+    /// Issues 1000 units to `alice`, then splits off 300 to `bob` with
+    /// `change` units left on alice's root asset
+    ///
+    /// Returns bob's and alice's proof files.
+    fn split_chain(change: u64) -> (AssetProofFile, AssetProofFile, Vec<Header>) {
+        let alice = keypair(1);
+        let bob = keypair(2);
+        let internal = keypair(3).x_only_public_key().0;
+        let alice_key = alice.x_only_public_key().0;
+        let bob_key = bob.x_only_public_key().0;
+
+        let (genesis_file, genesis_header) = issue(&[(1_000, alice_key)]);
+        let asset_id = genesis_file.proofs[0].asset.asset_id;
+        let prev_outpoint = genesis_file.last().unwrap().outpoint();
+
+        // Split: output 0 to bob (300), output 1 change to alice (700)
+        let split_leaves = vec![
+            split_leaf(&asset_id, 0, &bob_key, 300),
+            split_leaf(&asset_id, 1, &alice_key, 700),
+        ];
+        let split_root = MerkleSumNode::root(&split_leaves).unwrap();
+
+        let mut root_asset = AssetLeaf::new(asset_id, change, alice_key);
+        root_asset.split_commitment_root = Some(split_root);
+        root_asset.sign_transition(prev_outpoint, &alice);
+
+        let bob_asset = AssetLeaf::new(asset_id, 300, bob_key);
+        let bob_commitment = TapCommitmentProof::new(internal, vec![bob_asset.clone()], None);
+        let root_commitment = TapCommitmentProof::new(internal, vec![root_asset.clone()], None);
+
+        let transfer_tx = anchor_tx(prev_outpoint, vec![
+            bob_commitment.output_script().unwrap(),
+            root_commitment.output_script().unwrap(),
+        ]);
+        let (transfer_header, transfer_merkle) = mine(genesis_header.block_hash(), &transfer_tx);
+        let split = SplitProof {
+            root_output_index: 1,
+            root_asset: root_asset.clone(),
+            root_commitment: root_commitment.clone(),
+            path: MerkleSumNode::path(&split_leaves, 0).unwrap(),
+            root_path: MerkleSumNode::path(&split_leaves, 1).unwrap(),
+        };
+
+        let mut bob_file = genesis_file.clone();
+        bob_file.append(AssetProof {
+            anchor_tx: transfer_tx.clone(),
+            block_header: transfer_header,
+            tx_merkle_proof: transfer_merkle.clone(),
+            output_index: 0,
+            asset: bob_asset,
+            commitment: bob_commitment,
+            genesis: None,
+            split: Some(split.clone()),
+        });
+
+        let mut alice_file = genesis_file;
+        alice_file.append(AssetProof {
+            anchor_tx: transfer_tx,
+            block_header: transfer_header,
+            tx_merkle_proof: transfer_merkle,
+            output_index: 1,
+            asset: root_asset,
+            commitment: root_commitment,
+            genesis: None,
+            split: Some(SplitProof { path: split.root_path.clone(), ..split }),
+        });
+
+        (bob_file, alice_file, vec![genesis_header, transfer_header])
+    }
+
+    /// Issues 1000 units to `alice`, then sends 300 to `bob` with 700 change
+    pub(crate) fn build_chain() -> (AssetProofFile, Vec<Header>, Keypair) {
+        let (bob_file, _, headers) = split_chain(700);
+        (bob_file, headers, keypair(2))
+    }
+
+    #[test]
+    fn test_proof_file_verifies_ownership() {
+        let (file, headers, bob) = build_chain();
+
+        let verified = file.verify_ownership(&headers, &bob.x_only_public_key().0).unwrap();
+        assert_eq!(verified.amount, 300);
+        assert_eq!(verified.genesis_supply, 1_000);
+        assert_eq!(verified.transfers, 1);
+
+        assert!(file.verify_ownership(&headers, &keypair(1).x_only_public_key().0).is_err());
+    }
+
+    #[test]
+    fn test_proof_file_roundtrip() {
+        let (file, headers, _) = build_chain();
+        let decoded = AssetProofFile::decode(&file.encode()).unwrap();
+        assert_eq!(decoded, file);
+        assert!(decoded.verify(&headers).is_ok());
+    }
+
+    #[test]
+    fn test_missing_header_is_rejected() {
+        let (file, headers, _) = build_chain();
+        assert!(file.verify(&headers[..1]).is_err());
+    }
+
+    #[test]
+    fn test_inflated_split_is_rejected() {
+        let (mut file, headers, _) = build_chain();
+
+        // Claim more units than the split commitment allocates
+        let last = file.proofs.last_mut().unwrap();
+        last.asset.amount = 400;
+        last.commitment.output_assets = vec![last.asset.clone()];
+        assert!(file.verify(&headers).is_err());
+    }
+
+    #[test]
+    fn test_split_change_verifies() {
+        let (_, alice_file, headers) = split_chain(700);
+        let verified = alice_file.verify_ownership(&headers, &keypair(1).x_only_public_key().0).unwrap();
+        assert_eq!(verified.amount, 700);
+    }
+
+    #[test]
+    fn test_split_root_cannot_keep_the_full_amount() {
+        // 300 go to bob while the root asset claims all 1000 units
+        let (bob_file, alice_file, headers) = split_chain(1_000);
+        assert!(alice_file.verify(&headers).is_err());
+        assert!(bob_file.verify(&headers).is_err());
+
+        // Nor can the root asset pass as a transfer without a split
+        let mut full_transfer = alice_file;
+        full_transfer.proofs[1].split = None;
+        let error = full_transfer.verify(&headers).unwrap_err().to_string();
+        assert!(error.contains("split commitment on a transfer without a split"), "{}", error);
+    }
+
+    #[test]
+    fn test_builders_chain_issue_and_transfers() {
+        let alice = keypair(1);
+        let bob = keypair(2);
+        let carol = keypair(4);
+        let alice_key = alice.x_only_public_key().0;
+        let bob_key = bob.x_only_public_key().0;
+
+        // Issue 1000 units to alice
+        let first_prev_out = OutPoint { txid: Txid::from_byte_array([7u8; 32]), vout: 1 };
+        let genesis = AssetGenesis::new(first_prev_out, "TestCoin", "{}", 0);
+        let genesis_script = genesis_commitment(&genesis, 1_000, alice_key).output_script().unwrap();
+        let genesis_tx = anchor_tx(first_prev_out, vec![genesis_script]);
+        let (genesis_header, genesis_merkle) = mine(BlockHash::all_zeros(), &genesis_tx);
+        let anchor = AnchorConfirmation { anchor_tx: genesis_tx, block_header: genesis_header, tx_merkle_proof: genesis_merkle };
+        let alice_file = AssetProofFile::new(AssetProof::genesis(genesis, 1_000, alice_key, &anchor).unwrap()).unwrap();
+
+        // Alice sends 300 to bob and keeps 700
+        let transition = alice_file.prepare_transfer(&alice, bob_key, 300).unwrap();
+        assert!(alice_file.prepare_transfer(&bob, bob_key, 300).is_err());
+        let transfer_tx = anchor_tx(transition.prev_outpoint, transition.output_scripts().unwrap());
+        let (transfer_header, transfer_merkle) = mine(genesis_header.block_hash(), &transfer_tx);
+        let anchor = AnchorConfirmation { anchor_tx: transfer_tx, block_header: transfer_header, tx_merkle_proof: transfer_merkle };
+        let proofs = alice_file.complete_transfer(&transition, &anchor).unwrap();
+
+        let headers = vec![genesis_header, transfer_header];
+        assert_eq!(proofs.recipient.verify_ownership(&headers, &bob_key).unwrap().amount, 300);
+        let change = proofs.change.unwrap();
+        assert_eq!(change.verify_ownership(&headers, &alice_key).unwrap().amount, 700);
+
+        // Bob passes all 300 on to carol
+        let transition = proofs.recipient.prepare_transfer(&bob, carol.x_only_public_key().0, 300).unwrap();
+        assert!(transition.split.is_none());
+        let final_tx = anchor_tx(transition.prev_outpoint, transition.output_scripts().unwrap());
+        let (final_header, final_merkle) = mine(transfer_header.block_hash(), &final_tx);
+        let anchor = AnchorConfirmation { anchor_tx: final_tx, block_header: final_header, tx_merkle_proof: final_merkle };
+        let proofs = proofs.recipient.complete_transfer(&transition, &anchor).unwrap();
+        assert!(proofs.change.is_none());
+
+        let headers = vec![genesis_header, transfer_header, final_header];
+        let verified = proofs.recipient.verify_ownership(&headers, &carol.x_only_public_key().0).unwrap();
+        assert_eq!((verified.amount, verified.genesis_supply, verified.transfers), (300, 1_000, 2));
+
+        // An anchor that pays elsewhere yields no proof
+        assert!(change.complete_transfer(&transition, &anchor).is_err());
+    }
+
+    #[test]
+    fn test_genesis_supply_counts_every_leaf() {
+        let (file, header) = issue(&[(1_000, keypair(1).x_only_public_key().0), (500, keypair(4).x_only_public_key().0)]);
+        let verified = file.verify(&[header]).unwrap();
+        assert_eq!(verified.amount, 1_000);
+        assert_eq!(verified.genesis_supply, 1_500);
+    }
+}
@@ -17,6 +17,7 @@
 };
 
 use crate::lightning::channel_manager::ChannelManagerWrapper;
+use crate::lightning::sweeper::{OutputSweeper, SweepableOutput};
 
 /// Bitcoin-Lightning Bridge for handling on-chain functionality
 pub struct BitcoinLightningBridge {
@@ -37,6 +38,9 @@ pub struct BitcoinLightningBridge {
     
     /// Last scanned block height
     last_scanned_height: Mutex<u32>,
+    
+    /// Sweeper for outputs of force-closed channels
+    sweeper: Option<Arc<OutputSweeper>>,
 }
 
 /// Channel transaction information
@@ -126,9 +130,16 @@ pub fn new(
             channel_transactions: Mutex::new(HashMap::new()),
             funding_addresses: Mutex::new(HashMap::new()),
             last_scanned_height: Mutex::new(0),
+            sweeper: None,
         }
     }
     
+    /// Attach an output sweeper used to claim outputs of force-closed channels
+    pub fn with_sweeper(mut self, sweeper: Arc<OutputSweeper>) -> Self {
+        self.sweeper = Some(sweeper);
+        self
+    }
+    
     /// Initialize the bridge
     pub fn init(&self) -> LightningResult<()> {
         println!("Initializing Bitcoin-Lightning Bridge");
@@ -294,6 +305,15 @@ pub fn monitor_blockchain(&self) -> LightningResult<()> {
         
         // Update last scanned height
         *last_height = current_height;
+        drop(channel_txs);
+        drop(last_height);
+        
+        // Release timelocked outputs and sweep them back to the wallet
+        if let Some(sweeper) = &self.sweeper {
+            for sweep in sweeper.process_block(current_height)? {
+                println!("Broadcast sweep {} for {} outputs", sweep.txid, sweep.outpoints.len());
+            }
+        }
         
         Ok(())
     }
@@ -331,6 +351,52 @@ pub fn register_channel_close(
         }
     }
     
+    /// Register a force-close together with the outputs we can claim from it
+    /// 
+    /// The outputs are handed to the sweeper, which claims them once the closing
+    /// transaction confirms and their timelocks expire.
+    pub fn register_force_close(
+        &self,
+        channel_id: &str,
+        closing_txid: &str,
+        outputs: Vec<SweepableOutput>,
+    ) -> LightningResult<()> {
+        let sweeper = self.sweeper.as_ref().ok_or_else(|| {
+            LightningError::ChannelError("No output sweeper configured".to_string())
+        })?;
+        
+        self.register_channel_close(channel_id, closing_txid)?;
+        
+        for output in outputs {
+            if output.txid != closing_txid {
+                return Err(LightningError::ChannelError(
+                    format!("Output {} is not part of closing transaction {}", output.outpoint(), closing_txid)
+                ));
+            }
+            sweeper.track_output(output)?;
+        }
+        
+        Ok(())
+    }
+    
+    /// Notify the bridge that a transaction confirmed
+    /// 
+    /// Used for closing and sweep transactions so the sweeper can track timelocks.
+    pub fn transaction_confirmed(&self, txid: &str, height: u32) {
+        if let Some(sweeper) = &self.sweeper {
+            sweeper.transaction_confirmed(txid, height);
+        }
+    }
+    
+    /// Notify the bridge that a transaction spending channel outputs confirmed
+    /// 
+    /// Lets the sweeper drop outputs the counterparty claimed before us.
+    pub fn spending_transaction_confirmed(&self, tx: &BitcoinTransaction, height: u32) {
+        if let Some(sweeper) = &self.sweeper {
+            sweeper.spending_transaction_confirmed(tx, height);
+        }
+    }
+    
     /// Get balance available for channels
     pub fn get_channel_balance(&self) -> LightningResult<u64> {
         self.bitcoin_interface.get_balance()
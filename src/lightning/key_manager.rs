@@ -6,6 +6,10 @@
 use std::fs;
 use std::io;
 
+use bitcoin::bip32::{ChildNumber, DerivationPath, Xpriv};
+use bitcoin::hashes::{hmac, sha512, Hash, HashEngine};
+use bitcoin::secp256k1::Secp256k1;
+
 use crate::lightning::interface::{
     LightningError, LightningResult, NodeInfo
 };
@@ -30,6 +34,9 @@ pub struct KeyManagerWrapper {
     
     /// Data directory
     data_dir: PathBuf,
+    
+    /// Master key and BIP85 index the node seed is derived from, if any
+    master_key: Option<(Xpriv, u32)>,
 }
 
 impl KeyManagerWrapper {
@@ -71,9 +78,20 @@ pub fn new(config: &crate::config::Config) -> Self {
             node_info: Mutex::new(node_info),
             config: Arc::new(config.clone()),
             data_dir,
+            master_key: None,
         }
     }
     
+    /// Derive the node seed from a master key with BIP85 instead of keeping
+    /// a random seed file
+    ///
+    /// The seed is the 32-byte BIP85 HEX child at `index`, so the master key
+    /// backup alone restores the node keys.
+    pub fn with_master_key(mut self, master_key: Xpriv, index: u32) -> Self {
+        self.master_key = Some((master_key, index));
+        self
+    }
+    
     /// Initialize the key manager
     #[cfg(feature = "ldk")]
     pub fn initialize(&mut self) -> LightningResult<Arc<KeysManager>> {
@@ -87,7 +105,10 @@ pub fn initialize(&mut self) -> LightningResult<Arc<KeysManager>> {
         // Check if we have an existing seed file
         let seed_path = self.data_dir.join("keys_seed.dat");
         
-        let seed = if seed_path.exists() {
+        let seed = if let Some((master_key, index)) = &self.master_key {
+            // Recoverable from the master key, so nothing is written to disk
+            bip85_node_seed(master_key, *index)?
+        } else if seed_path.exists() {
             // Load existing seed
             self.load_seed(&seed_path)?
         } else {
@@ -136,6 +157,12 @@ pub fn initialize(&mut self) -> LightningResult<()> {
             })?;
         }
         
+        // Report the node ID LDK would derive from the seed
+        if let Some((master_key, index)) = &self.master_key {
+            let seed = bip85_node_seed(master_key, *index)?;
+            self.node_info.lock().unwrap().pubkey = hex::encode(node_id_from_seed(&seed)?);
+        }
+        
         println!("Initialized Lightning key manager (mock) with node ID: {}", 
                  self.node_info.lock().unwrap().pubkey);
         
@@ -215,9 +242,76 @@ pub fn get_data_dir(&self) -> &Path {
     }
 }
 
+/// BIP85 HEX application: `m/83696968'/128169'/bytes'/index'`
+const BIP85_HEX_PATH: [u32; 2] = [83696968, 128169];
+
+/// Length of a node seed, which is the BIP85 HEX `bytes` of its path
+const NODE_SEED_LEN: u32 = 32;
+
+/// BIP85 HEX child entropy of 16 to 64 bytes
+///
+/// Only the HEX application is needed here; the full set of BIP85
+/// applications lives in anya-core's `bitcoin::wallet::bip85`.
+fn bip85_hex(master_key: &Xpriv, bytes: u32, index: u32) -> LightningResult<Vec<u8>> {
+    if !(16..=64).contains(&bytes) {
+        return Err(LightningError::ImplementationError(format!("BIP85 HEX takes 16 to 64 bytes, not {}", bytes)));
+    }
+    let path = BIP85_HEX_PATH.iter().chain([&bytes, &index])
+        .map(|step| ChildNumber::from_hardened_idx(*step))
+        .collect::<Result<Vec<_>, _>>()
+        .map(DerivationPath::from)
+        .map_err(|e| LightningError::ImplementationError(format!("Invalid BIP85 index {}: {}", index, e)))?;
+    let child = master_key.derive_priv(&Secp256k1::new(), &path)
+        .map_err(|e| LightningError::ImplementationError(format!("Failed to derive node seed: {}", e)))?;
+    
+    let mut engine = hmac::HmacEngine::<sha512::Hash>::new(b"bip-entropy-from-k");
+    engine.input(&child.private_key.secret_bytes());
+    let entropy = hmac::Hmac::<sha512::Hash>::from_engine(engine).to_byte_array();
+    Ok(entropy[..bytes as usize].to_vec())
+}
+
+/// 32-byte node seed derived from a master key with BIP85 HEX
+fn bip85_node_seed(master_key: &Xpriv, index: u32) -> LightningResult<[u8; 32]> {
+    let mut seed = [0u8; 32];
+    seed.copy_from_slice(&bip85_hex(master_key, NODE_SEED_LEN, index)?);
+    Ok(seed)
+}
+
+/// Node ID for a seed, derived the way LDK's `KeysManager` does
+#[cfg(not(feature = "ldk"))]
+fn node_id_from_seed(seed: &[u8; 32]) -> LightningResult<[u8; 33]> {
+    let secp = Secp256k1::new();
+    let node_secret = Xpriv::new_master(bitcoin::Network::Testnet, seed)
+        .and_then(|master| master.derive_priv(&secp, &[ChildNumber::from_hardened_idx(0)?]))
+        .map_err(|e| LightningError::ImplementationError(format!("Failed to derive node key: {}", e)))?;
+    Ok(node_secret.private_key.public_key(&secp).serialize())
+}
+
 /// Get random bytes for seed generation
 fn get_random_bytes(dest: &mut [u8]) -> Result<(), Box<dyn std::error::Error>> {
     use rand::{thread_rng, RngCore};
     thread_rng().fill_bytes(dest);
     Ok(())
-} 
\ No newline at end of file
+} 
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::str::FromStr;
+    
+    #[test]
+    fn test_bip85_hex_vector() {
+        // Master key and HEX vector of BIP85
+        let master_key = Xpriv::from_str("xprv9s21ZrQH143K2LBWUUQRFXhucrQqBpKdRRxNVq2zBqsx8HVqFk2uYo8kmbaLLHRdqtQpUm98uKfu3vca1LqdGhUtyoFnCNkfmXRyPXLjbKb").unwrap();
+        assert_eq!(
+            hex::encode(bip85_hex(&master_key, 64, 0).unwrap()),
+            "492db4698cf3b73a5a24998aa3e9d7fa96275d85724a91e71aa2d645442f878555d078fd1f1f67e368976f04137b1f7a0d19232136ca50c44614af72b5582a5c"
+        );
+        assert!(bip85_hex(&master_key, 8, 0).is_err());
+        
+        // The node seed is the 32-byte HEX child, not a prefix of the 64-byte one
+        let seed = bip85_node_seed(&master_key, 0).unwrap();
+        assert_eq!(seed.to_vec(), bip85_hex(&master_key, 32, 0).unwrap());
+        assert_ne!(seed.to_vec(), bip85_hex(&master_key, 64, 0).unwrap()[..32].to_vec());
+    }
+}
@@ -11,6 +11,9 @@
 pub mod payment_router;
 pub mod payment_executor;
 pub mod bitcoin_bridge;
+pub mod sweeper;
+pub mod unified_uri;
+pub mod payment_planner;
 
 use std::sync::Arc;
 use crate::config::Config;
@@ -163,6 +166,28 @@ fn test_key_manager() {
         }
     }
     
+    #[test]
+    fn test_key_manager_from_master_key() {
+        use super::key_manager::KeyManagerWrapper;
+        use ::bitcoin::bip32::Xpriv;
+        
+        let config = Config::default();
+        let master_key = Xpriv::new_master(::bitcoin::Network::Bitcoin, &[5u8; 32]).unwrap();
+        
+        #[cfg(not(feature = "ldk"))]
+        {
+            // The same master key and index restore the same node
+            let node_id = |index: u32| {
+                let mut key_manager = KeyManagerWrapper::new(&config).with_master_key(master_key, index);
+                key_manager.initialize().unwrap();
+                key_manager.get_node_info().unwrap().pubkey
+            };
+            assert_eq!(node_id(0), node_id(0));
+            assert_ne!(node_id(0), node_id(1));
+            assert_eq!(node_id(0).len(), 66);
+        }
+    }
+    
     #[test]
     fn test_invoice_manager() {
         use super::invoice_manager::InvoiceManager;
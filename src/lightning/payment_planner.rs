@@ -0,0 +1,572 @@
+// Lightning Network Payment Planner
+// Chooses how to pay a unified BIP21 URI based on channel liquidity and
+// on-chain fees, then pays it through the Lightning or Bitcoin interface
+
+use std::sync::Arc;
+
+use crate::bitcoin::BitcoinInterface;
+use crate::lightning::interface::{LightningError, LightningInterface, LightningResult, PaymentInfo};
+use crate::lightning::unified_uri::UnifiedUri;
+
+/// Payment planner configuration
+#[derive(Debug, Clone)]
+pub struct PlannerConfig {
+    /// Confirmation target for on-chain payments
+    pub confirmation_target: u8,
+
+    /// Expected virtual size of an on-chain payment (1 input, 2 outputs)
+    pub onchain_payment_vsize: u64,
+
+    /// Expected Lightning routing fee, parts per million
+    pub lightning_fee_ppm: u64,
+
+    /// Expected Lightning base fee in millisatoshis
+    pub lightning_base_fee_msat: u64,
+
+    /// Reserve kept in each channel that cannot be spent
+    pub channel_reserve_sat: u64,
+}
+
+impl Default for PlannerConfig {
+    fn default() -> Self {
+        PlannerConfig {
+            confirmation_target: 6,
+            onchain_payment_vsize: 141,
+            lightning_fee_ppm: 1_000,
+            lightning_base_fee_msat: 1_000,
+            channel_reserve_sat: 1_000,
+        }
+    }
+}
+
+/// Method chosen to pay a URI
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PaymentMethod {
+    /// Pay the BOLT11 invoice
+    Lightning {
+        /// Invoice to pay
+        bolt11: String,
+    },
+
+    /// Pay the on-chain address
+    OnChain {
+        /// Destination address
+        address: String,
+        /// Payjoin endpoint offered by the receiver, if any
+        payjoin_endpoint: Option<String>,
+        /// Whether the receiver forbids substituting its output (`pjos=0`)
+        payjoin_output_substitution_disabled: bool,
+    },
+}
+
+/// Payment plan for a URI
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PaymentPlan {
+    /// Chosen method
+    pub method: PaymentMethod,
+
+    /// Amount to pay in satoshis
+    pub amount_sat: u64,
+
+    /// Estimated fee in satoshis
+    pub estimated_fee_sat: u64,
+
+    /// On-chain fee rate used for the estimate (sat/vB)
+    pub fee_rate: u64,
+
+    /// Why this method was chosen
+    pub reason: String,
+}
+
+/// Sends BIP78 payjoin payments on behalf of the planner
+pub trait PayjoinSender: Send + Sync {
+    /// Pay `amount_sat` to `address` through the receiver's payjoin `endpoint`,
+    /// returning the ID of the broadcast transaction
+    fn send_payjoin(
+        &self,
+        endpoint: &str,
+        output_substitution_disabled: bool,
+        address: &str,
+        amount_sat: u64,
+        fee_rate: u64,
+    ) -> LightningResult<String>;
+}
+
+/// Result of executing a payment plan
+#[derive(Debug, Clone)]
+pub enum PaymentOutcome {
+    /// Lightning payment
+    Lightning(PaymentInfo),
+
+    /// On-chain transaction ID
+    OnChain(String),
+}
+
+/// Payment planner for unified URIs
+pub struct PaymentPlanner {
+    /// Lightning interface
+    lightning: Arc<dyn LightningInterface>,
+
+    /// Bitcoin interface
+    bitcoin: Arc<dyn BitcoinInterface>,
+
+    /// Payjoin sender for URIs with a `pj=` endpoint
+    payjoin: Option<Arc<dyn PayjoinSender>>,
+
+    /// Configuration
+    config: PlannerConfig,
+}
+
+impl PaymentPlanner {
+    /// Create a new payment planner
+    pub fn new(
+        lightning: Arc<dyn LightningInterface>,
+        bitcoin: Arc<dyn BitcoinInterface>,
+        config: PlannerConfig,
+    ) -> Self {
+        PaymentPlanner {
+            lightning,
+            bitcoin,
+            payjoin: None,
+            config,
+        }
+    }
+
+    /// Pay on-chain URIs with a payjoin endpoint through `sender`
+    pub fn with_payjoin_sender(mut self, sender: Arc<dyn PayjoinSender>) -> Self {
+        self.payjoin = Some(sender);
+        self
+    }
+
+    /// Largest amount a single active channel can send, in satoshis
+    pub fn max_sendable_sat(&self) -> LightningResult<u64> {
+        let channels = self.lightning.list_channels()?;
+
+        Ok(channels.iter()
+            .filter(|channel| channel.is_active)
+            .map(|channel| channel.local_balance.saturating_sub(self.config.channel_reserve_sat))
+            .max()
+            .unwrap_or(0))
+    }
+
+    /// Estimated Lightning fee for `amount_sat`, in satoshis (rounded up)
+    pub fn estimate_lightning_fee(&self, amount_sat: u64) -> u64 {
+        let fee_msat = self.config.lightning_base_fee_msat
+            + amount_sat * 1000 * self.config.lightning_fee_ppm / 1_000_000;
+        fee_msat.div_ceil(1000)
+    }
+
+    /// Choose how to pay a URI
+    ///
+    /// `amount_sat` is required when neither the URI nor its invoice carries an amount.
+    /// URIs whose only Lightning method is a BOLT12 offer are refused, as are
+    /// payjoin URIs paid on-chain when no payjoin sender is configured.
+    pub fn plan(&self, uri: &UnifiedUri, amount_sat: Option<u64>) -> LightningResult<PaymentPlan> {
+        if uri.offer.is_some() && uri.lightning.is_none() {
+            return Err(LightningError::PaymentError(
+                "BOLT12 offers are not supported by the Lightning interface".to_string()
+            ));
+        }
+
+        let invoice = match &uri.lightning {
+            Some(bolt11) => Some(self.lightning.decode_invoice(bolt11)?),
+            None => None,
+        };
+
+        let invoice_amount = invoice.as_ref()
+            .and_then(|invoice| invoice.amount_msat)
+            .map(|msat| msat.div_ceil(1000));
+
+        if let (Some(uri_amount), Some(invoice_amount)) = (uri.amount_sat, invoice_amount) {
+            if uri_amount != invoice_amount {
+                return Err(LightningError::InvoiceError(format!(
+                    "URI amount {} sat does not match invoice amount {} sat", uri_amount, invoice_amount
+                )));
+            }
+        }
+
+        let amount_sat = uri.amount_sat
+            .or(invoice_amount)
+            .or(amount_sat)
+            .ok_or_else(|| LightningError::PaymentError("No amount specified".to_string()))?;
+
+        let fee_rate = self.bitcoin.estimate_fee(self.config.confirmation_target)?;
+        let onchain_fee = fee_rate * self.config.onchain_payment_vsize;
+
+        // Lightning is usable only with an invoice and enough outbound liquidity
+        let lightning_option = match &uri.lightning {
+            Some(bolt11) => {
+                let fee = self.estimate_lightning_fee(amount_sat);
+                let sendable = self.max_sendable_sat()?;
+                if sendable >= amount_sat + fee {
+                    Some((bolt11.clone(), fee))
+                } else {
+                    println!("Insufficient channel liquidity for {} sat (max sendable {} sat)", amount_sat, sendable);
+                    None
+                }
+            },
+            None => None,
+        };
+
+        match lightning_option {
+            // Lightning wins ties: it settles instantly and leaves no on-chain footprint
+            Some((bolt11, fee)) if !uri.has_onchain() || fee <= onchain_fee => Ok(PaymentPlan {
+                method: PaymentMethod::Lightning { bolt11 },
+                amount_sat,
+                estimated_fee_sat: fee,
+                fee_rate,
+                reason: format!("Lightning fee {} sat vs on-chain fee {} sat", fee, onchain_fee),
+            }),
+            lightning_option if uri.has_onchain() => {
+                if uri.payjoin_endpoint.is_some() && self.payjoin.is_none() {
+                    return Err(LightningError::PaymentError(
+                        "URI requests payjoin but no payjoin sender is configured".to_string()
+                    ));
+                }
+
+                let reason = match (&lightning_option, &uri.lightning) {
+                    (Some((_, fee)), _) => format!("On-chain fee {} sat vs Lightning fee {} sat", onchain_fee, fee),
+                    (None, Some(_)) => "Insufficient channel liquidity for Lightning".to_string(),
+                    (None, None) => "On-chain only URI".to_string(),
+                };
+
+                Ok(PaymentPlan {
+                    method: PaymentMethod::OnChain {
+                        address: uri.address.clone(),
+                        payjoin_endpoint: uri.payjoin_endpoint.clone(),
+                        payjoin_output_substitution_disabled: uri.payjoin_output_substitution_disabled,
+                    },
+                    amount_sat,
+                    estimated_fee_sat: onchain_fee,
+                    fee_rate,
+                    reason,
+                })
+            },
+            _ => Err(LightningError::PaymentError(
+                format!("Insufficient channel liquidity to pay {} sat and no on-chain address", amount_sat)
+            )),
+        }
+    }
+
+    /// Pay a plan through the Lightning or Bitcoin interface
+    pub fn execute(&self, plan: &PaymentPlan) -> LightningResult<PaymentOutcome> {
+        match &plan.method {
+            PaymentMethod::Lightning { bolt11 } => {
+                let invoice = self.lightning.decode_invoice(bolt11)?;
+                // Amountless invoices need the amount from the URI
+                let amount_msat = match invoice.amount_msat {
+                    Some(_) => None,
+                    None => Some(plan.amount_sat * 1000),
+                };
+
+                let payment = self.lightning.pay_invoice(bolt11, amount_msat)?;
+                Ok(PaymentOutcome::Lightning(payment))
+            },
+            PaymentMethod::OnChain {
+                address,
+                payjoin_endpoint: Some(endpoint),
+                payjoin_output_substitution_disabled,
+            } => {
+                let sender = self.payjoin.as_ref().ok_or_else(|| LightningError::PaymentError(
+                    "URI requests payjoin but no payjoin sender is configured".to_string()
+                ))?;
+
+                let txid = sender.send_payjoin(
+                    endpoint,
+                    *payjoin_output_substitution_disabled,
+                    address,
+                    plan.amount_sat,
+                    plan.fee_rate,
+                )?;
+                Ok(PaymentOutcome::OnChain(txid))
+            },
+            PaymentMethod::OnChain { address, payjoin_endpoint: None, .. } => {
+                let tx = self.bitcoin.create_transaction(vec![(address.clone(), plan.amount_sat)], plan.fee_rate)?;
+                let txid = self.bitcoin.broadcast_transaction(&tx)?;
+                Ok(PaymentOutcome::OnChain(txid))
+            },
+        }
+    }
+
+    /// Plan and pay a URI string
+    pub fn pay_uri(&self, uri: &str, amount_sat: Option<u64>) -> LightningResult<PaymentOutcome> {
+        let uri = UnifiedUri::parse(uri)?;
+        let plan = self.plan(&uri, amount_sat)?;
+        println!("Paying {} sat via {:?}: {}", plan.amount_sat, plan.method, plan.reason);
+        self.execute(&plan)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::Mutex;
+    use crate::bitcoin::{
+        AddressType, BitcoinAddress, BitcoinImplementationType, BitcoinResult, BitcoinTransaction,
+    };
+    use crate::lightning::interface::{
+        ChannelInfo, Invoice, LightningImplementationType, NodeInfo, PaymentStatus,
+    };
+
+    struct TestLightning {
+        local_balance: u64,
+        invoice_amount_msat: Option<u64>,
+        paid: Mutex<Vec<(String, Option<u64>)>>,
+    }
+
+    impl LightningInterface for TestLightning {
+        fn get_node_info(&self) -> LightningResult<NodeInfo> {
+            Err(LightningError::ImplementationError("unused".to_string()))
+        }
+
+        fn connect_peer(&self, _node_pubkey: &str, _host: &str, _port: u16) -> LightningResult<()> {
+            Ok(())
+        }
+
+        fn list_peers(&self) -> LightningResult<Vec<NodeInfo>> {
+            Ok(Vec::new())
+        }
+
+        fn open_channel(
+            &self,
+            _node_pubkey: &str,
+            _capacity: u64,
+            _push_msat: Option<u64>,
+            _is_private: bool,
+        ) -> LightningResult<ChannelInfo> {
+            Err(LightningError::ImplementationError("unused".to_string()))
+        }
+
+        fn list_channels(&self) -> LightningResult<Vec<ChannelInfo>> {
+            Ok(vec![ChannelInfo {
+                channel_id: "chan".to_string(),
+                funding_txid: "00".repeat(32),
+                funding_output_idx: 0,
+                capacity: 1_000_000,
+                local_balance: self.local_balance,
+                remote_balance: 1_000_000 - self.local_balance,
+                remote_pubkey: "02".to_string(),
+                is_active: true,
+                is_public: true,
+                short_channel_id: None,
+            }])
+        }
+
+        fn close_channel(&self, _channel_id: &str, _force: bool) -> LightningResult<String> {
+            Ok(String::new())
+        }
+
+        fn create_invoice(
+            &self,
+            _amount_msat: Option<u64>,
+            _description: &str,
+            _expiry: Option<u32>,
+        ) -> LightningResult<Invoice> {
+            Err(LightningError::ImplementationError("unused".to_string()))
+        }
+
+        fn pay_invoice(&self, bolt11: &str, amount_msat: Option<u64>) -> LightningResult<PaymentInfo> {
+            self.paid.lock().unwrap().push((bolt11.to_string(), amount_msat));
+            Ok(PaymentInfo {
+                payment_id: "pay".to_string(),
+                payment_hash: "hash".to_string(),
+                preimage: None,
+                amount_msat: amount_msat.or(self.invoice_amount_msat).unwrap_or(0),
+                fee_msat: 0,
+                status: PaymentStatus::Succeeded,
+                created_at: 0,
+                resolved_at: None,
+                description: None,
+            })
+        }
+
+        fn decode_invoice(&self, bolt11: &str) -> LightningResult<Invoice> {
+            Ok(Invoice {
+                bolt11: bolt11.to_string(),
+                payment_hash: "hash".to_string(),
+                description: String::new(),
+                amount_msat: self.invoice_amount_msat,
+                expiry: 3600,
+                timestamp: 0,
+                min_final_cltv_expiry: 40,
+            })
+        }
+
+        fn get_payment(&self, _payment_hash: &str) -> LightningResult<Option<PaymentInfo>> {
+            Ok(None)
+        }
+
+        fn list_payments(&self) -> LightningResult<Vec<PaymentInfo>> {
+            Ok(Vec::new())
+        }
+
+        fn implementation_type(&self) -> LightningImplementationType {
+            LightningImplementationType::Mock
+        }
+    }
+
+    struct TestBitcoin {
+        fee_rate: u64,
+        sent: Mutex<Vec<(String, u64)>>,
+    }
+
+    impl BitcoinInterface for TestBitcoin {
+        fn get_transaction(&self, _txid: &str) -> BitcoinResult<BitcoinTransaction> {
+            unimplemented!()
+        }
+
+        fn get_block(&self, _hash: &str) -> BitcoinResult<Vec<BitcoinTransaction>> {
+            Ok(Vec::new())
+        }
+
+        fn get_block_height(&self) -> BitcoinResult<u32> {
+            Ok(800_000)
+        }
+
+        fn generate_address(&self, _address_type: AddressType) -> BitcoinResult<BitcoinAddress> {
+            unimplemented!()
+        }
+
+        fn create_transaction(&self, outputs: Vec<(String, u64)>, _fee_rate: u64) -> BitcoinResult<BitcoinTransaction> {
+            self.sent.lock().unwrap().extend(outputs);
+            Ok(BitcoinTransaction {
+                txid: "onchain-txid".to_string(),
+                version: 2,
+                inputs: Vec::new(),
+                outputs: Vec::new(),
+                locktime: 0,
+                size: 0,
+                weight: 0,
+                fee: None,
+            })
+        }
+
+        fn broadcast_transaction(&self, transaction: &BitcoinTransaction) -> BitcoinResult<String> {
+            Ok(transaction.txid.clone())
+        }
+
+        fn get_balance(&self) -> BitcoinResult<u64> {
+            Ok(10_000_000)
+        }
+
+        fn estimate_fee(&self, _target_blocks: u8) -> BitcoinResult<u64> {
+            Ok(self.fee_rate)
+        }
+
+        fn implementation_type(&self) -> BitcoinImplementationType {
+            BitcoinImplementationType::Rust
+        }
+    }
+
+    #[derive(Default)]
+    struct TestPayjoin {
+        sent: Mutex<Vec<(String, String, u64)>>,
+    }
+
+    impl PayjoinSender for TestPayjoin {
+        fn send_payjoin(
+            &self,
+            endpoint: &str,
+            _output_substitution_disabled: bool,
+            address: &str,
+            amount_sat: u64,
+            _fee_rate: u64,
+        ) -> LightningResult<String> {
+            self.sent.lock().unwrap().push((endpoint.to_string(), address.to_string(), amount_sat));
+            Ok("payjoin-txid".to_string())
+        }
+    }
+
+    fn setup(local_balance: u64, fee_rate: u64, invoice_amount_msat: Option<u64>) -> (PaymentPlanner, Arc<TestLightning>, Arc<TestBitcoin>) {
+        let lightning = Arc::new(TestLightning {
+            local_balance,
+            invoice_amount_msat,
+            paid: Mutex::new(Vec::new()),
+        });
+        let bitcoin = Arc::new(TestBitcoin { fee_rate, sent: Mutex::new(Vec::new()) });
+        let planner = PaymentPlanner::new(lightning.clone(), bitcoin.clone(), PlannerConfig::default());
+        (planner, lightning, bitcoin)
+    }
+
+    const URI: &str = "bitcoin:bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4?amount=0.0005&lightning=lnbc500u1test";
+
+    #[test]
+    fn test_planner_prefers_lightning_with_liquidity() {
+        let (planner, lightning, _) = setup(500_000, 10, Some(50_000_000));
+
+        let outcome = planner.pay_uri(URI, None).unwrap();
+        assert!(matches!(outcome, PaymentOutcome::Lightning(_)));
+        assert_eq!(lightning.paid.lock().unwrap()[0], ("lnbc500u1test".to_string(), None));
+    }
+
+    #[test]
+    fn test_planner_falls_back_to_onchain() {
+        // Not enough outbound liquidity
+        let (planner, _, bitcoin) = setup(20_000, 10, Some(50_000_000));
+        let plan = planner.plan(&UnifiedUri::parse(URI).unwrap(), None).unwrap();
+        assert_eq!(plan.reason, "Insufficient channel liquidity for Lightning");
+
+        let outcome = planner.execute(&plan).unwrap();
+        assert!(matches!(outcome, PaymentOutcome::OnChain(ref txid) if txid == "onchain-txid"));
+        assert_eq!(bitcoin.sent.lock().unwrap()[0].1, 50_000);
+
+        // Mismatched invoice amount is refused
+        let bad = UnifiedUri::parse("bitcoin:bc1qaddr?amount=0.001&lightning=lnbc500u1test").unwrap();
+        assert!(planner.plan(&bad, None).is_err());
+
+        // Lightning-only URI without liquidity cannot be paid
+        let ln_only = UnifiedUri::parse("bitcoin:?lightning=lnbc500u1test").unwrap();
+        assert!(planner.plan(&ln_only, None).is_err());
+    }
+
+    #[test]
+    fn test_planner_compares_fees() {
+        let mut uri = UnifiedUri::new("bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4");
+        uri.lightning = Some("lnbc1test".to_string());
+        uri.amount_sat = Some(500_000);
+
+        // 501 sat routing fee vs 141 sat on-chain at 1 sat/vB
+        let (planner, _, _) = setup(900_000, 1, None);
+        let plan = planner.plan(&uri, None).unwrap();
+        assert!(matches!(plan.method, PaymentMethod::OnChain { .. }));
+        assert_eq!(plan.estimated_fee_sat, 141);
+
+        // At 20 sat/vB Lightning is cheaper; the amountless invoice is paid with the URI amount
+        let (planner, lightning, _) = setup(900_000, 20, None);
+        let plan = planner.plan(&uri, None).unwrap();
+        assert_eq!(plan.estimated_fee_sat, 501);
+        planner.execute(&plan).unwrap();
+        assert_eq!(lightning.paid.lock().unwrap()[0].1, Some(500_000_000));
+    }
+
+    #[test]
+    fn test_planner_routes_payjoin_and_refuses_offers() {
+        let (planner, _, bitcoin) = setup(500_000, 10, None);
+
+        // An offer is never silently replaced by the on-chain fallback
+        let offer = UnifiedUri::parse("bitcoin:bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4?amount=0.0005&lno=lno1qcp4256ypq").unwrap();
+        assert!(planner.plan(&offer, None).is_err());
+
+        // Payjoin URIs are refused without a sender instead of broadcasting the original
+        let pj = UnifiedUri::parse("bitcoin:bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4?amount=0.0005&pj=https://example.com/pj").unwrap();
+        assert!(planner.plan(&pj, None).is_err());
+
+        let payjoin = Arc::new(TestPayjoin::default());
+        let planner = planner.with_payjoin_sender(payjoin.clone());
+        let outcome = planner.execute(&planner.plan(&pj, None).unwrap()).unwrap();
+        assert!(matches!(outcome, PaymentOutcome::OnChain(ref txid) if txid == "payjoin-txid"));
+        assert_eq!(payjoin.sent.lock().unwrap()[0], (
+            "https://example.com/pj".to_string(),
+            "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4".to_string(),
+            50_000,
+        ));
+        assert!(bitcoin.sent.lock().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_planner_rounds_invoice_amount_up() {
+        let (planner, _, _) = setup(500_000, 10, Some(50_000_500));
+        let plan = planner.plan(&UnifiedUri::parse("bitcoin:?lightning=lnbc1test").unwrap(), None).unwrap();
+        assert_eq!(plan.amount_sat, 50_001);
+    }
+}
@@ -0,0 +1,1310 @@
+// Lightning Network Output Sweeper
+// Claims our outputs from closed channels back into the on-chain wallet
+// Tracks CSV-delayed to_self outputs and unresolved HTLC outputs, waits for
+// their timelocks, batches them into sweep transactions and re-bumps sweeps
+// that fail to confirm. The tracked state can be kept in a file so that a
+// restart during a timelock does not lose the outputs.
+
+use std::sync::{Arc, Mutex};
+use std::collections::HashMap;
+use std::fs::{self, File};
+use std::io::Write;
+use std::path::{Path, PathBuf};
+use std::str::FromStr;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use bitcoin::absolute::LockTime;
+use bitcoin::consensus::encode;
+use bitcoin::hashes::Hash;
+use bitcoin::sighash::{EcdsaSighashType, SighashCache};
+use bitcoin::transaction::Version;
+use bitcoin::{Address, Amount, OutPoint, ScriptBuf, Sequence, Transaction, TxIn, TxOut, Txid, Witness};
+use serde::{Deserialize, Serialize};
+
+use crate::bitcoin::{
+    BitcoinInterface, BitcoinTransaction, AddressType, TransactionInput, TransactionOutput
+};
+
+use crate::lightning::interface::{LightningError, LightningResult};
+
+/// Weight of the fixed transaction fields (version, locktime, counts) plus the segwit marker
+const SWEEP_BASE_WEIGHT: u64 = 4 * 10 + 2;
+
+/// Weight of the non-witness part of an input (outpoint, empty scriptSig, sequence)
+const SWEEP_INPUT_BASE_WEIGHT: u64 = 4 * 41;
+
+/// Weight of a P2WPKH change output
+const SWEEP_OUTPUT_WEIGHT: u64 = 4 * 31;
+
+/// Maximum size of a DER encoded ECDSA signature plus the sighash byte
+const MAX_SIGNATURE_SIZE: u64 = 73;
+
+/// Outputs below this value are not worth sweeping
+const DUST_LIMIT_SAT: u64 = 546;
+
+/// Sequence used by inputs without a relative timelock (signals RBF)
+const SEQUENCE_RBF: u32 = 0xFFFF_FFFD;
+
+/// How a sweepable output can be claimed
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SweepOutputKind {
+    /// Our to_self output on our own commitment, encumbered by `to_self_delay` blocks
+    DelayedToSelf {
+        /// Relative timelock in blocks (BIP68 / OP_CHECKSEQUENCEVERIFY)
+        to_self_delay: u16,
+    },
+
+    /// An HTLC we offered, on the counterparty's commitment, spendable after its CLTV expiry
+    HtlcTimeout {
+        /// Absolute block height after which the HTLC can be reclaimed
+        cltv_expiry: u32,
+    },
+
+    /// An HTLC we offered, on our own commitment
+    ///
+    /// Only the pre-signed HTLC-timeout transaction can claim it; that
+    /// transaction's output is then swept after `to_self_delay` blocks.
+    HolderHtlcTimeout {
+        /// Absolute block height after which the HTLC-timeout transaction is valid
+        cltv_expiry: u32,
+        /// Relative timelock of the HTLC-timeout transaction's output
+        to_self_delay: u16,
+        /// HTLC-timeout transaction carrying both signatures (consensus hex)
+        htlc_timeout_tx: String,
+        /// P2WSH witness script of the HTLC-timeout transaction's output
+        delayed_witness_script: Vec<u8>,
+    },
+
+    /// An HTLC we received and know the preimage for
+    HtlcSuccess {
+        /// Payment preimage (hex)
+        preimage: String,
+    },
+}
+
+/// Current state of a tracked output
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SweepOutputStatus {
+    /// Waiting for the closing transaction to confirm
+    AwaitingConfirmation,
+
+    /// Confirmed, but the timelock has not expired yet
+    TimelockPending {
+        /// First block height at which the output can be spent
+        spendable_at: u32,
+    },
+
+    /// Ready to be included in the next sweep
+    Spendable,
+
+    /// Included in a broadcast sweep transaction
+    Sweeping {
+        /// Sweep transaction ID
+        sweep_txid: String,
+    },
+
+    /// The sweep transaction confirmed
+    Swept {
+        /// Sweep transaction ID
+        sweep_txid: String,
+        /// Confirmation height of the sweep
+        height: u32,
+    },
+
+    /// The second-stage transaction was broadcast; its output is tracked on its own
+    SecondStage {
+        /// Second-stage transaction ID
+        txid: String,
+    },
+
+    /// A transaction that is not ours spent the output, so there is nothing to sweep
+    SpentByCounterparty {
+        /// Spending transaction ID
+        txid: String,
+        /// Confirmation height of the spend
+        height: u32,
+    },
+
+    /// Output is worth less than the fee required to claim it
+    Uneconomical,
+}
+
+/// An output from a closed channel that belongs to us
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SweepableOutput {
+    /// Channel the output came from
+    pub channel_id: String,
+
+    /// Transaction containing the output (commitment transaction)
+    pub txid: String,
+
+    /// Output index
+    pub vout: u32,
+
+    /// Output value in satoshis
+    pub value: u64,
+
+    /// How the output is claimed
+    pub kind: SweepOutputKind,
+
+    /// P2WSH witness script of the output
+    pub witness_script: Vec<u8>,
+
+    /// Confirmation height of the containing transaction
+    pub confirmation_height: Option<u32>,
+
+    /// Current status
+    pub status: SweepOutputStatus,
+
+    /// Created timestamp
+    pub created_at: u64,
+}
+
+impl SweepableOutput {
+    /// Create a new output record for an unconfirmed closing transaction
+    pub fn new(
+        channel_id: &str,
+        txid: &str,
+        vout: u32,
+        value: u64,
+        kind: SweepOutputKind,
+        witness_script: Vec<u8>,
+    ) -> Self {
+        SweepableOutput {
+            channel_id: channel_id.to_string(),
+            txid: txid.to_string(),
+            vout,
+            value,
+            kind,
+            witness_script,
+            confirmation_height: None,
+            status: SweepOutputStatus::AwaitingConfirmation,
+            created_at: SystemTime::now()
+                .duration_since(UNIX_EPOCH)
+                .unwrap_or_default()
+                .as_secs(),
+        }
+    }
+
+    /// Outpoint key used to track the output
+    pub fn outpoint(&self) -> String {
+        format!("{}:{}", self.txid, self.vout)
+    }
+
+    /// First block height at which the output may be included in a block
+    pub fn spendable_at(&self) -> Option<u32> {
+        let confirmation_height = self.confirmation_height?;
+
+        Some(match &self.kind {
+            SweepOutputKind::DelayedToSelf { to_self_delay } => {
+                confirmation_height + *to_self_delay as u32
+            }
+            SweepOutputKind::HtlcTimeout { cltv_expiry }
+            | SweepOutputKind::HolderHtlcTimeout { cltv_expiry, .. } => {
+                // A transaction with nLockTime N is valid in block N + 1
+                (*cltv_expiry + 1).max(confirmation_height + 1)
+            }
+            SweepOutputKind::HtlcSuccess { .. } => confirmation_height + 1,
+        })
+    }
+
+    /// nSequence required by the spending input
+    pub fn sequence(&self) -> u32 {
+        match &self.kind {
+            SweepOutputKind::DelayedToSelf { to_self_delay } => *to_self_delay as u32,
+            _ => SEQUENCE_RBF,
+        }
+    }
+
+    /// Witness weight needed to spend the output, including the witness script
+    pub fn witness_weight(&self) -> u64 {
+        let script_len = self.witness_script.len() as u64;
+        let branch_item = match &self.kind {
+            // Empty vector selecting the delayed / timeout branch
+            SweepOutputKind::DelayedToSelf { .. }
+            | SweepOutputKind::HtlcTimeout { .. }
+            | SweepOutputKind::HolderHtlcTimeout { .. } => 1,
+            // Length-prefixed 32 byte preimage
+            SweepOutputKind::HtlcSuccess { .. } => 1 + 32,
+        };
+
+        1 + (1 + MAX_SIGNATURE_SIZE) + branch_item + varint_len(script_len) + script_len
+    }
+
+    /// Total weight this output adds to a sweep transaction
+    pub fn input_weight(&self) -> u64 {
+        SWEEP_INPUT_BASE_WEIGHT + self.witness_weight()
+    }
+
+    /// Pre-signed second-stage transaction claiming the output, if it needs one
+    pub fn second_stage_tx(&self) -> LightningResult<Option<Transaction>> {
+        let SweepOutputKind::HolderHtlcTimeout { htlc_timeout_tx, .. } = &self.kind else {
+            return Ok(None);
+        };
+
+        let bytes = hex::decode(htlc_timeout_tx)
+            .map_err(|e| LightningError::ChannelError(format!("Invalid HTLC-timeout transaction: {}", e)))?;
+        let tx: Transaction = encode::deserialize(&bytes)
+            .map_err(|e| LightningError::ChannelError(format!("Invalid HTLC-timeout transaction: {}", e)))?;
+
+        let spends_output = tx.input.len() == 1
+            && tx.output.len() == 1
+            && tx.input[0].previous_output.txid.to_string() == self.txid
+            && tx.input[0].previous_output.vout == self.vout;
+        if !spends_output {
+            return Err(LightningError::ChannelError(
+                format!("HTLC-timeout transaction does not claim {}", self.outpoint())
+            ));
+        }
+
+        Ok(Some(tx))
+    }
+}
+
+/// A broadcast sweep transaction
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SweepTransaction {
+    /// Transaction ID
+    pub txid: String,
+
+    /// Outpoints of the swept outputs
+    pub outpoints: Vec<String>,
+
+    /// Fee rate in sat/vB
+    pub fee_rate: u64,
+
+    /// Absolute fee in satoshis
+    pub fee: u64,
+
+    /// Height at which this version was broadcast
+    pub broadcast_height: u32,
+
+    /// Number of times the sweep has been fee-bumped
+    pub bump_count: u32,
+
+    /// Txids of the earlier versions this one replaced, any of which may still confirm
+    pub replaced_txids: Vec<String>,
+
+    /// The signed transaction
+    pub transaction: BitcoinTransaction,
+}
+
+/// Signs sweep inputs
+///
+/// Implementations hold the channel keys (e.g. the LDK keys manager) and return
+/// a DER encoded signature with the sighash byte appended.
+pub trait SweepSigner: Send + Sync {
+    /// Sign input `input_index` of `tx`, which spends `output`
+    fn sign_sweep_input(
+        &self,
+        tx: &Transaction,
+        input_index: usize,
+        output: &SweepableOutput,
+    ) -> LightningResult<Vec<u8>>;
+}
+
+/// Sweeper configuration
+#[derive(Debug, Clone)]
+pub struct SweeperConfig {
+    /// Confirmation target passed to fee estimation
+    pub confirmation_target: u8,
+
+    /// Blocks to wait before bumping an unconfirmed sweep
+    pub rebump_after_blocks: u32,
+
+    /// Fee rate increase per bump, in percent
+    pub bump_percent: u64,
+
+    /// Upper bound on the sweep fee rate in sat/vB
+    pub max_fee_rate: u64,
+
+    /// Maximum number of inputs per sweep transaction
+    pub max_batch_inputs: usize,
+}
+
+impl Default for SweeperConfig {
+    fn default() -> Self {
+        SweeperConfig {
+            confirmation_target: 6,
+            rebump_after_blocks: 3,
+            bump_percent: 25,
+            max_fee_rate: 500,
+            max_batch_inputs: 50,
+        }
+    }
+}
+
+/// Tracked outputs and pending sweeps, as kept in the state file
+#[derive(Default, Serialize, Deserialize)]
+struct SweeperState {
+    outputs: Vec<SweepableOutput>,
+    pending_sweeps: Vec<SweepTransaction>,
+}
+
+/// Sweeps force-close and HTLC outputs back to the wallet
+pub struct OutputSweeper {
+    /// Sweeper configuration
+    config: SweeperConfig,
+
+    /// Bitcoin interface
+    bitcoin_interface: Arc<dyn BitcoinInterface>,
+
+    /// Signer for sweep inputs
+    signer: Arc<dyn SweepSigner>,
+
+    /// Tracked outputs, keyed by outpoint
+    outputs: Mutex<HashMap<String, SweepableOutput>>,
+
+    /// Unconfirmed sweep transactions, keyed by txid
+    pending_sweeps: Mutex<HashMap<String, SweepTransaction>>,
+
+    /// File the state is written to after every change, if any
+    state_path: Option<PathBuf>,
+}
+
+impl OutputSweeper {
+    /// Create a new output sweeper
+    pub fn new(
+        config: SweeperConfig,
+        bitcoin_interface: Arc<dyn BitcoinInterface>,
+        signer: Arc<dyn SweepSigner>,
+    ) -> Self {
+        OutputSweeper {
+            config,
+            bitcoin_interface,
+            signer,
+            outputs: Mutex::new(HashMap::new()),
+            pending_sweeps: Mutex::new(HashMap::new()),
+            state_path: None,
+        }
+    }
+
+    /// Create an output sweeper keeping its state in `path`, restoring any saved state
+    ///
+    /// Sweeps restored as pending may not have reached the network before the
+    /// restart; they are broadcast again as bumps once they are stale.
+    pub fn open<P: AsRef<Path>>(
+        config: SweeperConfig,
+        bitcoin_interface: Arc<dyn BitcoinInterface>,
+        signer: Arc<dyn SweepSigner>,
+        path: P,
+    ) -> LightningResult<Self> {
+        let path = path.as_ref().to_path_buf();
+        let state = if path.exists() {
+            let bytes = fs::read(&path).map_err(|e| {
+                LightningError::ImplementationError(format!("Failed to read sweeper state: {}", e))
+            })?;
+            serde_json::from_slice(&bytes).map_err(|e| {
+                LightningError::ImplementationError(format!("Failed to decode sweeper state: {}", e))
+            })?
+        } else {
+            SweeperState::default()
+        };
+
+        let mut sweeper = Self::new(config, bitcoin_interface, signer);
+        sweeper.outputs = Mutex::new(state.outputs.into_iter().map(|o| (o.outpoint(), o)).collect());
+        sweeper.pending_sweeps = Mutex::new(state.pending_sweeps.into_iter().map(|s| (s.txid.clone(), s)).collect());
+        sweeper.state_path = Some(path);
+        Ok(sweeper)
+    }
+
+    /// Start tracking an output
+    pub fn track_output(&self, output: SweepableOutput) -> LightningResult<()> {
+        if output.witness_script.is_empty() {
+            return Err(LightningError::ChannelError(
+                format!("Missing witness script for output {}", output.outpoint())
+            ));
+        }
+
+        if let SweepOutputKind::HolderHtlcTimeout { delayed_witness_script, .. } = &output.kind {
+            if delayed_witness_script.is_empty() {
+                return Err(LightningError::ChannelError(
+                    format!("Missing second-stage witness script for output {}", output.outpoint())
+                ));
+            }
+            output.second_stage_tx()?;
+        }
+
+        self.outputs.lock().unwrap().entry(output.outpoint()).or_insert(output);
+        self.persist()
+    }
+
+    /// Record the confirmation of a transaction
+    ///
+    /// Handles closing and second-stage transactions (making their outputs
+    /// eligible for sweeping) and our own sweep transactions (marking their
+    /// inputs swept).
+    pub fn transaction_confirmed(&self, txid: &str, height: u32) {
+        self.record_confirmation(txid, height);
+        self.persist_or_log();
+    }
+
+    /// Record a confirmed transaction that may spend tracked outputs
+    ///
+    /// Our own sweeps and second-stage transactions are recorded as with
+    /// [`Self::transaction_confirmed`]. Any other spender is the counterparty
+    /// claiming the output first, e.g. an HTLC taken with its preimage, so the
+    /// output is dropped along with the sweep that tried to spend it.
+    pub fn spending_transaction_confirmed(&self, tx: &BitcoinTransaction, height: u32) {
+        self.record_confirmation(&tx.txid, height);
+
+        {
+            let mut outputs = self.outputs.lock().unwrap();
+            let mut pending = self.pending_sweeps.lock().unwrap();
+
+            for input in &tx.inputs {
+                let outpoint = format!("{}:{}", input.txid, input.vout);
+                let previous = match outputs.get_mut(&outpoint) {
+                    Some(output) if !matches!(
+                        output.status,
+                        SweepOutputStatus::Swept { .. } | SweepOutputStatus::SpentByCounterparty { .. }
+                    ) => std::mem::replace(
+                        &mut output.status,
+                        SweepOutputStatus::SpentByCounterparty { txid: tx.txid.clone(), height },
+                    ),
+                    _ => continue,
+                };
+                println!("Output {} was spent by {}, dropping it", outpoint, tx.txid);
+
+                match previous {
+                    // The sweep can no longer confirm; its other outputs are swept again
+                    SweepOutputStatus::Sweeping { sweep_txid } => {
+                        if let Some(sweep) = pending.remove(&sweep_txid) {
+                            for other in &sweep.outpoints {
+                                if let Some(output) = outputs.get_mut(other) {
+                                    if output.status == (SweepOutputStatus::Sweeping { sweep_txid: sweep_txid.clone() }) {
+                                        output.status = SweepOutputStatus::Spendable;
+                                    }
+                                }
+                            }
+                        }
+                    }
+                    // The second stage can no longer confirm either
+                    SweepOutputStatus::SecondStage { txid } => {
+                        outputs.retain(|_, output| output.txid != txid);
+                    }
+                    _ => {}
+                }
+            }
+        }
+
+        self.persist_or_log();
+    }
+
+    /// Apply the confirmation of `txid` to the tracked outputs and sweeps
+    fn record_confirmation(&self, txid: &str, height: u32) {
+        let mut outputs = self.outputs.lock().unwrap();
+
+        for output in outputs.values_mut() {
+            if output.txid == txid && output.confirmation_height.is_none() {
+                output.confirmation_height = Some(height);
+                if output.status == SweepOutputStatus::AwaitingConfirmation {
+                    output.status = SweepOutputStatus::TimelockPending {
+                        spendable_at: output.spendable_at().unwrap_or(height),
+                    };
+                }
+            }
+
+            if output.status == (SweepOutputStatus::SecondStage { txid: txid.to_string() }) {
+                output.status = SweepOutputStatus::Swept {
+                    sweep_txid: txid.to_string(),
+                    height,
+                };
+            }
+        }
+
+        // Any RBF version of a sweep may be the one that confirms
+        let confirmed = {
+            let mut pending = self.pending_sweeps.lock().unwrap();
+            let key = pending.iter()
+                .find(|(_, s)| s.txid == txid || s.replaced_txids.iter().any(|t| t == txid))
+                .map(|(key, _)| key.clone());
+            key.and_then(|key| pending.remove(&key))
+        };
+
+        if let Some(sweep) = confirmed {
+            for outpoint in &sweep.outpoints {
+                if let Some(output) = outputs.get_mut(outpoint) {
+                    output.status = SweepOutputStatus::Swept {
+                        sweep_txid: txid.to_string(),
+                        height,
+                    };
+                }
+            }
+            println!("Sweep {} confirmed at height {}", txid, height);
+        }
+    }
+
+    /// Process a new chain tip
+    ///
+    /// Releases outputs whose timelocks expired, re-bumps stale sweeps and
+    /// broadcasts new sweeps for everything that is ready. Returns the sweep
+    /// transactions broadcast during this call.
+    pub fn process_block(&self, height: u32) -> LightningResult<Vec<SweepTransaction>> {
+        self.release_timelocks(height);
+        self.broadcast_second_stages()?;
+
+        let mut broadcast = self.rebump_stale_sweeps(height)?;
+
+        let ready: Vec<SweepableOutput> = {
+            let outputs = self.outputs.lock().unwrap();
+            let mut ready: Vec<SweepableOutput> = outputs.values()
+                .filter(|o| o.status == SweepOutputStatus::Spendable)
+                // Claimed by their second stage instead
+                .filter(|o| !matches!(o.kind, SweepOutputKind::HolderHtlcTimeout { .. }))
+                .cloned()
+                .collect();
+            ready.sort_by_key(|a| a.outpoint());
+            ready
+        };
+
+        if ready.is_empty() {
+            self.persist()?;
+            return Ok(broadcast);
+        }
+
+        let fee_rate = self.bitcoin_interface.estimate_fee(self.config.confirmation_target)?
+            .max(1)
+            .min(self.config.max_fee_rate);
+
+        for batch in ready.chunks(self.config.max_batch_inputs.max(1)) {
+            let batch = self.filter_economical(batch, fee_rate);
+            if batch.is_empty() {
+                continue;
+            }
+
+            let sweep = self.build_and_broadcast(&batch, fee_rate, height, 0, Vec::new())?;
+            broadcast.push(sweep);
+        }
+
+        self.persist()?;
+        Ok(broadcast)
+    }
+
+    /// Get a tracked output by outpoint
+    pub fn get_output(&self, outpoint: &str) -> Option<SweepableOutput> {
+        self.outputs.lock().unwrap().get(outpoint).cloned()
+    }
+
+    /// List all tracked outputs
+    pub fn list_outputs(&self) -> Vec<SweepableOutput> {
+        self.outputs.lock().unwrap().values().cloned().collect()
+    }
+
+    /// List all tracked outputs of a channel
+    pub fn list_channel_outputs(&self, channel_id: &str) -> Vec<SweepableOutput> {
+        self.outputs.lock().unwrap()
+            .values()
+            .filter(|o| o.channel_id == channel_id)
+            .cloned()
+            .collect()
+    }
+
+    /// List unconfirmed sweep transactions
+    pub fn list_pending_sweeps(&self) -> Vec<SweepTransaction> {
+        self.pending_sweeps.lock().unwrap().values().cloned().collect()
+    }
+
+    /// Move outputs whose timelock expired at `height` to `Spendable`
+    fn release_timelocks(&self, height: u32) {
+        let mut outputs = self.outputs.lock().unwrap();
+
+        for output in outputs.values_mut() {
+            if let SweepOutputStatus::TimelockPending { spendable_at } = output.status {
+                if height + 1 >= spendable_at {
+                    // The sweep can be mined in the next block
+                    output.status = SweepOutputStatus::Spendable;
+                }
+            }
+        }
+    }
+
+    /// Broadcast the pre-signed second-stage transactions of spendable outputs
+    ///
+    /// The output of each is tracked as a CSV-delayed output of its own. A
+    /// failed broadcast leaves the output spendable, to be retried next block.
+    fn broadcast_second_stages(&self) -> LightningResult<()> {
+        let ready: Vec<SweepableOutput> = self.outputs.lock().unwrap()
+            .values()
+            .filter(|o| o.status == SweepOutputStatus::Spendable)
+            .filter(|o| matches!(o.kind, SweepOutputKind::HolderHtlcTimeout { .. }))
+            .cloned()
+            .collect();
+
+        for output in ready {
+            let SweepOutputKind::HolderHtlcTimeout { to_self_delay, delayed_witness_script, .. } = &output.kind else {
+                continue;
+            };
+            let Some(tx) = output.second_stage_tx()? else {
+                continue;
+            };
+
+            let value = tx.output[0].value.to_sat();
+            let transaction = to_bitcoin_transaction(&tx, output.value.saturating_sub(value));
+            if let Err(e) = self.bitcoin_interface.broadcast_transaction(&transaction) {
+                println!("Failed to broadcast HTLC-timeout {}: {}", transaction.txid, e);
+                continue;
+            }
+
+            let mut outputs = self.outputs.lock().unwrap();
+            if let Some(tracked) = outputs.get_mut(&output.outpoint()) {
+                tracked.status = SweepOutputStatus::SecondStage { txid: transaction.txid.clone() };
+            }
+            let delayed = SweepableOutput::new(
+                &output.channel_id,
+                &transaction.txid,
+                0,
+                value,
+                SweepOutputKind::DelayedToSelf { to_self_delay: *to_self_delay },
+                delayed_witness_script.clone(),
+            );
+            outputs.entry(delayed.outpoint()).or_insert(delayed);
+            println!("Broadcast HTLC-timeout {} for {}", transaction.txid, output.outpoint());
+        }
+
+        Ok(())
+    }
+
+    /// Re-broadcast sweeps that have not confirmed within `rebump_after_blocks` at a higher fee rate
+    fn rebump_stale_sweeps(&self, height: u32) -> LightningResult<Vec<SweepTransaction>> {
+        let stale: Vec<SweepTransaction> = self.pending_sweeps.lock().unwrap()
+            .values()
+            .filter(|s| height >= s.broadcast_height + self.config.rebump_after_blocks)
+            .cloned()
+            .collect();
+
+        let mut bumped = Vec::new();
+
+        for sweep in stale {
+            if sweep.fee_rate >= self.config.max_fee_rate {
+                continue;
+            }
+
+            let estimate = self.bitcoin_interface.estimate_fee(self.config.confirmation_target)?;
+            let increased = sweep.fee_rate * (100 + self.config.bump_percent) / 100;
+            // BIP125 requires paying at least the incremental relay fee on top
+            let fee_rate = estimate
+                .max(increased)
+                .max(sweep.fee_rate + 1)
+                .min(self.config.max_fee_rate);
+
+            let batch: Vec<SweepableOutput> = {
+                let outputs = self.outputs.lock().unwrap();
+                sweep.outpoints.iter()
+                    .filter_map(|outpoint| outputs.get(outpoint).cloned())
+                    .collect()
+            };
+
+            if Self::batch_fee(&batch, fee_rate) + DUST_LIMIT_SAT > Self::batch_value(&batch) {
+                // Keep the current version in the mempool rather than burning the outputs
+                continue;
+            }
+
+            let mut replaced_txids = sweep.replaced_txids.clone();
+            replaced_txids.push(sweep.txid.clone());
+
+            // The current version stays pending until its replacement is out,
+            // so a failed broadcast is simply retried on the next block
+            match self.build_and_broadcast(&batch, fee_rate, height, sweep.bump_count + 1, replaced_txids) {
+                Ok(replacement) => {
+                    println!(
+                        "Bumped sweep {} -> {} ({} -> {} sat/vB)",
+                        sweep.txid, replacement.txid, sweep.fee_rate, fee_rate
+                    );
+                    bumped.push(replacement);
+                }
+                Err(e) => println!("Failed to bump sweep {}: {}", sweep.txid, e),
+            }
+        }
+
+        Ok(bumped)
+    }
+
+    /// Drop outputs that cost more to claim than they are worth
+    fn filter_economical(&self, batch: &[SweepableOutput], fee_rate: u64) -> Vec<SweepableOutput> {
+        let mut outputs = self.outputs.lock().unwrap();
+        let mut economical = Vec::new();
+
+        for output in batch {
+            let input_fee = (output.input_weight() * fee_rate).div_ceil(4);
+            if output.value > input_fee + DUST_LIMIT_SAT {
+                economical.push(output.clone());
+            } else if let Some(tracked) = outputs.get_mut(&output.outpoint()) {
+                tracked.status = SweepOutputStatus::Uneconomical;
+            }
+        }
+
+        economical
+    }
+
+    /// Build, sign and broadcast a sweep of `batch` paying to a fresh wallet address
+    fn build_and_broadcast(
+        &self,
+        batch: &[SweepableOutput],
+        fee_rate: u64,
+        height: u32,
+        bump_count: u32,
+        replaced_txids: Vec<String>,
+    ) -> LightningResult<SweepTransaction> {
+        let fee = Self::batch_fee(batch, fee_rate);
+        let total = Self::batch_value(batch);
+
+        if total < fee + DUST_LIMIT_SAT {
+            return Err(LightningError::ChannelError(
+                format!("Sweep value {} sat does not cover fee {} sat", total, fee)
+            ));
+        }
+
+        let destination = self.bitcoin_interface.generate_address(AddressType::P2WPKH)?;
+        let script_pubkey = Address::from_str(&destination.address)
+            .map_err(|e| LightningError::ChannelError(format!("Invalid sweep address: {}", e)))?
+            .assume_checked()
+            .script_pubkey();
+
+        let mut tx = Transaction {
+            version: Version::TWO,
+            // Anti fee-sniping; also satisfies every HTLC timeout in the batch
+            lock_time: LockTime::from_height(height)
+                .map_err(|e| LightningError::ChannelError(format!("Invalid locktime: {}", e)))?,
+            input: batch.iter()
+                .map(|output| -> LightningResult<TxIn> {
+                    Ok(TxIn {
+                        previous_output: OutPoint {
+                            txid: Txid::from_str(&output.txid).map_err(|e| {
+                                LightningError::ChannelError(format!("Invalid txid {}: {}", output.txid, e))
+                            })?,
+                            vout: output.vout,
+                        },
+                        script_sig: ScriptBuf::new(),
+                        sequence: Sequence(output.sequence()),
+                        witness: Witness::new(),
+                    })
+                })
+                .collect::<LightningResult<Vec<_>>>()?,
+            output: vec![TxOut {
+                value: Amount::from_sat(total - fee),
+                script_pubkey,
+            }],
+        };
+
+        for (index, output) in batch.iter().enumerate() {
+            let signature = self.signer.sign_sweep_input(&tx, index, output)?;
+            tx.input[index].witness = Self::build_witness(output, signature)?;
+        }
+
+        let transaction = to_bitcoin_transaction(&tx, fee);
+        let sweep = SweepTransaction {
+            txid: transaction.txid.clone(),
+            outpoints: batch.iter().map(|o| o.outpoint()).collect(),
+            fee_rate,
+            fee,
+            broadcast_height: height,
+            bump_count,
+            replaced_txids,
+            transaction,
+        };
+
+        // Saved before broadcasting, so a crash cannot lose a sweep that made
+        // it to the network; everything is put back if the broadcast fails
+        let (previous_statuses, replaced) = {
+            let mut outputs = self.outputs.lock().unwrap();
+            let mut pending = self.pending_sweeps.lock().unwrap();
+
+            let previous_statuses: Vec<(String, SweepOutputStatus)> = sweep.outpoints.iter()
+                .filter_map(|outpoint| {
+                    let output = outputs.get_mut(outpoint)?;
+                    let status = SweepOutputStatus::Sweeping { sweep_txid: sweep.txid.clone() };
+                    Some((outpoint.clone(), std::mem::replace(&mut output.status, status)))
+                })
+                .collect();
+            let replaced = sweep.replaced_txids.last().and_then(|txid| pending.remove(txid));
+            pending.insert(sweep.txid.clone(), sweep.clone());
+            (previous_statuses, replaced)
+        };
+
+        let result = self.persist().and_then(|_| {
+            self.bitcoin_interface.broadcast_transaction(&sweep.transaction).map_err(LightningError::from)
+        });
+
+        if let Err(e) = result {
+            {
+                let mut outputs = self.outputs.lock().unwrap();
+                let mut pending = self.pending_sweeps.lock().unwrap();
+                for (outpoint, status) in previous_statuses {
+                    if let Some(output) = outputs.get_mut(&outpoint) {
+                        output.status = status;
+                    }
+                }
+                pending.remove(&sweep.txid);
+                if let Some(replaced) = replaced {
+                    pending.insert(replaced.txid.clone(), replaced);
+                }
+            }
+            self.persist_or_log();
+            return Err(e);
+        }
+
+        Ok(sweep)
+    }
+
+    /// Write the tracked outputs and pending sweeps to the state file, if any
+    fn persist(&self) -> LightningResult<()> {
+        let Some(path) = &self.state_path else {
+            return Ok(());
+        };
+
+        let state = SweeperState {
+            outputs: self.outputs.lock().unwrap().values().cloned().collect(),
+            pending_sweeps: self.pending_sweeps.lock().unwrap().values().cloned().collect(),
+        };
+        let bytes = serde_json::to_vec(&state).map_err(|e| {
+            LightningError::ImplementationError(format!("Failed to encode sweeper state: {}", e))
+        })?;
+
+        let tmp_path = path.with_extension("tmp");
+        let write = || -> std::io::Result<()> {
+            let mut file = File::create(&tmp_path)?;
+            file.write_all(&bytes)?;
+            file.sync_all()?;
+            fs::rename(&tmp_path, path)
+        };
+        write().map_err(|e| {
+            LightningError::ImplementationError(format!("Failed to write sweeper state: {}", e))
+        })
+    }
+
+    /// Persist from a method that cannot report the failure
+    fn persist_or_log(&self) {
+        if let Err(e) = self.persist() {
+            println!("{}", e);
+        }
+    }
+
+    /// Assemble the witness stack for an output
+    fn build_witness(output: &SweepableOutput, signature: Vec<u8>) -> LightningResult<Witness> {
+        let mut witness = Witness::new();
+        witness.push(signature);
+
+        match &output.kind {
+            SweepOutputKind::DelayedToSelf { .. }
+            | SweepOutputKind::HtlcTimeout { .. }
+            | SweepOutputKind::HolderHtlcTimeout { .. } => {
+                witness.push(Vec::<u8>::new());
+            }
+            SweepOutputKind::HtlcSuccess { preimage } => {
+                let preimage = hex::decode(preimage)
+                    .map_err(|e| LightningError::ChannelError(format!("Invalid preimage: {}", e)))?;
+                if preimage.len() != 32 {
+                    return Err(LightningError::ChannelError(
+                        "Preimage must be 32 bytes".to_string()
+                    ));
+                }
+                witness.push(preimage);
+            }
+        }
+
+        witness.push(output.witness_script.clone());
+        Ok(witness)
+    }
+
+    /// Fee for sweeping `batch` at `fee_rate` sat/vB
+    fn batch_fee(batch: &[SweepableOutput], fee_rate: u64) -> u64 {
+        let weight = SWEEP_BASE_WEIGHT
+            + SWEEP_OUTPUT_WEIGHT
+            + batch.iter().map(|o| o.input_weight()).sum::<u64>();
+        (weight * fee_rate).div_ceil(4)
+    }
+
+    /// Total value of `batch`
+    fn batch_value(batch: &[SweepableOutput]) -> u64 {
+        batch.iter().map(|o| o.value).sum()
+    }
+}
+
+/// BIP143 sighash for a sweep input, for use by `SweepSigner` implementations
+pub fn sweep_sighash(
+    tx: &Transaction,
+    input_index: usize,
+    output: &SweepableOutput,
+) -> LightningResult<[u8; 32]> {
+    let script = ScriptBuf::from_bytes(output.witness_script.clone());
+    let sighash = SighashCache::new(tx)
+        .p2wsh_signature_hash(input_index, &script, Amount::from_sat(output.value), EcdsaSighashType::All)
+        .map_err(|e| LightningError::ChannelError(format!("Failed to compute sighash: {}", e)))?;
+
+    Ok(sighash.to_byte_array())
+}
+
+/// Convert a signed rust-bitcoin transaction to the interface representation
+fn to_bitcoin_transaction(tx: &Transaction, fee: u64) -> BitcoinTransaction {
+    BitcoinTransaction {
+        txid: tx.compute_txid().to_string(),
+        version: tx.version.0 as u32,
+        inputs: tx.input.iter().map(|input| TransactionInput {
+            txid: input.previous_output.txid.to_string(),
+            vout: input.previous_output.vout,
+            script_sig: input.script_sig.to_bytes(),
+            sequence: input.sequence.0,
+            witness: Some(input.witness.to_vec()),
+        }).collect(),
+        outputs: tx.output.iter().map(|output| TransactionOutput {
+            value: output.value.to_sat(),
+            script_pubkey: output.script_pubkey.to_bytes(),
+            address: None,
+        }).collect(),
+        locktime: tx.lock_time.to_consensus_u32(),
+        size: bitcoin::consensus::encode::serialize(tx).len(),
+        weight: tx.weight().to_wu() as usize,
+        fee: Some(fee),
+    }
+}
+
+/// Weight of a compact-size length prefix
+fn varint_len(n: u64) -> u64 {
+    match n {
+        0..=0xfc => 1,
+        0xfd..=0xffff => 3,
+        0x1_0000..=0xffff_ffff => 5,
+        _ => 9,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::bitcoin::{
+        BitcoinAddress, BitcoinError, BitcoinImplementationType, BitcoinResult
+    };
+    use bitcoin::secp256k1::{Message, Secp256k1, SecretKey};
+
+    /// Bitcoin interface that records broadcasts and returns a fixed fee estimate
+    struct RecordingBitcoin {
+        fee_rate: Mutex<u64>,
+        broadcasts: Mutex<Vec<BitcoinTransaction>>,
+        reject_broadcasts: Mutex<bool>,
+    }
+
+    impl BitcoinInterface for RecordingBitcoin {
+        fn get_transaction(&self, txid: &str) -> BitcoinResult<BitcoinTransaction> {
+            Err(BitcoinError::TransactionError(format!("Unknown transaction {}", txid)))
+        }
+        fn get_block(&self, _hash: &str) -> BitcoinResult<Vec<BitcoinTransaction>> {
+            Ok(Vec::new())
+        }
+        fn get_block_height(&self) -> BitcoinResult<u32> {
+            Ok(800_000)
+        }
+        fn generate_address(&self, address_type: AddressType) -> BitcoinResult<BitcoinAddress> {
+            Ok(BitcoinAddress {
+                address: "bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4".to_string(),
+                address_type,
+            })
+        }
+        fn create_transaction(&self, _outputs: Vec<(String, u64)>, _fee_rate: u64) -> BitcoinResult<BitcoinTransaction> {
+            Err(BitcoinError::ImplementationError("not used".to_string()))
+        }
+        fn broadcast_transaction(&self, transaction: &BitcoinTransaction) -> BitcoinResult<String> {
+            if *self.reject_broadcasts.lock().unwrap() {
+                return Err(BitcoinError::TransactionError("rejected".to_string()));
+            }
+            self.broadcasts.lock().unwrap().push(transaction.clone());
+            Ok(transaction.txid.clone())
+        }
+        fn get_balance(&self) -> BitcoinResult<u64> {
+            Ok(0)
+        }
+        fn estimate_fee(&self, _target_blocks: u8) -> BitcoinResult<u64> {
+            Ok(*self.fee_rate.lock().unwrap())
+        }
+        fn implementation_type(&self) -> BitcoinImplementationType {
+            BitcoinImplementationType::Rust
+        }
+    }
+
+    /// Signs every input with a single key
+    struct KeySigner(SecretKey);
+
+    impl SweepSigner for KeySigner {
+        fn sign_sweep_input(&self, tx: &Transaction, input_index: usize, output: &SweepableOutput) -> LightningResult<Vec<u8>> {
+            let sighash = sweep_sighash(tx, input_index, output)?;
+            let signature = Secp256k1::new().sign_ecdsa(&Message::from_digest(sighash), &self.0);
+            let mut bytes = signature.serialize_der().to_vec();
+            bytes.push(EcdsaSighashType::All as u8);
+            Ok(bytes)
+        }
+    }
+
+    fn commitment_txid(n: u8) -> String {
+        Txid::from_byte_array([n; 32]).to_string()
+    }
+
+    fn setup() -> (Arc<RecordingBitcoin>, OutputSweeper) {
+        let bitcoin = Arc::new(RecordingBitcoin {
+            fee_rate: Mutex::new(10),
+            broadcasts: Mutex::new(Vec::new()),
+            reject_broadcasts: Mutex::new(false),
+        });
+        let signer = Arc::new(KeySigner(SecretKey::from_slice(&[0x11; 32]).unwrap()));
+        let sweeper = OutputSweeper::new(SweeperConfig::default(), bitcoin.clone(), signer);
+        (bitcoin, sweeper)
+    }
+
+    #[test]
+    fn test_delayed_output_waits_for_csv() {
+        let (bitcoin, sweeper) = setup();
+        let txid = commitment_txid(1);
+
+        sweeper.track_output(SweepableOutput::new(
+            "chan1", &txid, 0, 500_000,
+            SweepOutputKind::DelayedToSelf { to_self_delay: 144 },
+            vec![0x51; 79],
+        )).unwrap();
+
+        sweeper.transaction_confirmed(&txid, 1000);
+        assert!(sweeper.process_block(1100).unwrap().is_empty());
+        assert!(bitcoin.broadcasts.lock().unwrap().is_empty());
+
+        let swept = sweeper.process_block(1143).unwrap();
+        assert_eq!(swept.len(), 1);
+        assert_eq!(swept[0].transaction.inputs[0].sequence, 144);
+        assert_eq!(swept[0].transaction.outputs[0].value, 500_000 - swept[0].fee);
+    }
+
+    #[test]
+    fn test_outputs_are_batched_and_rebumped() {
+        let (bitcoin, sweeper) = setup();
+        let txid = commitment_txid(2);
+
+        sweeper.track_output(SweepableOutput::new(
+            "chan2", &txid, 0, 200_000,
+            SweepOutputKind::HtlcTimeout { cltv_expiry: 2000 },
+            vec![0x51; 133],
+        )).unwrap();
+        sweeper.track_output(SweepableOutput::new(
+            "chan2", &txid, 1, 300_000,
+            SweepOutputKind::HtlcSuccess { preimage: "11".repeat(32) },
+            vec![0x51; 139],
+        )).unwrap();
+
+        sweeper.transaction_confirmed(&txid, 1990);
+        let first = sweeper.process_block(2000).unwrap();
+        assert_eq!(first.len(), 1);
+        assert_eq!(first[0].outpoints.len(), 2);
+        assert_eq!(first[0].transaction.locktime, 2000);
+
+        // Nothing confirms for a few blocks, so the sweep gets bumped
+        let bumped = sweeper.process_block(2003).unwrap();
+        assert_eq!(bumped.len(), 1);
+        assert!(bumped[0].fee_rate > first[0].fee_rate);
+        assert_eq!(bumped[0].bump_count, 1);
+        assert_eq!(bitcoin.broadcasts.lock().unwrap().len(), 2);
+
+        sweeper.transaction_confirmed(&bumped[0].txid, 2004);
+        assert!(sweeper.list_pending_sweeps().is_empty());
+        assert!(sweeper.list_channel_outputs("chan2").iter()
+            .all(|o| matches!(o.status, SweepOutputStatus::Swept { .. })));
+    }
+
+    #[test]
+    fn test_dust_output_is_not_swept() {
+        let (_, sweeper) = setup();
+        let txid = commitment_txid(3);
+
+        sweeper.track_output(SweepableOutput::new(
+            "chan3", &txid, 0, 1_000,
+            SweepOutputKind::HtlcSuccess { preimage: "22".repeat(32) },
+            vec![0x51; 139],
+        )).unwrap();
+
+        sweeper.transaction_confirmed(&txid, 500);
+        assert!(sweeper.process_block(501).unwrap().is_empty());
+        assert_eq!(
+            sweeper.get_output(&format!("{}:0", txid)).unwrap().status,
+            SweepOutputStatus::Uneconomical
+        );
+    }
+
+    #[test]
+    fn test_failed_bump_keeps_current_sweep() {
+        let (bitcoin, sweeper) = setup();
+        let txid = commitment_txid(4);
+
+        sweeper.track_output(SweepableOutput::new(
+            "chan4", &txid, 0, 400_000,
+            SweepOutputKind::HtlcSuccess { preimage: "33".repeat(32) },
+            vec![0x51; 139],
+        )).unwrap();
+
+        sweeper.transaction_confirmed(&txid, 3000);
+        let first = sweeper.process_block(3001).unwrap();
+        assert_eq!(first.len(), 1);
+
+        // The replacement is rejected, so the original stays pending
+        *bitcoin.reject_broadcasts.lock().unwrap() = true;
+        assert!(sweeper.process_block(3004).unwrap().is_empty());
+        let pending = sweeper.list_pending_sweeps();
+        assert_eq!(pending.len(), 1);
+        assert_eq!(pending[0].txid, first[0].txid);
+
+        *bitcoin.reject_broadcasts.lock().unwrap() = false;
+        let bumped = sweeper.process_block(3005).unwrap();
+        assert_eq!(bumped.len(), 1);
+        assert_eq!(bumped[0].replaced_txids, vec![first[0].txid.clone()]);
+    }
+
+    #[test]
+    fn test_replaced_sweep_can_still_confirm() {
+        let (_, sweeper) = setup();
+        let txid = commitment_txid(5);
+
+        sweeper.track_output(SweepableOutput::new(
+            "chan5", &txid, 0, 400_000,
+            SweepOutputKind::HtlcSuccess { preimage: "44".repeat(32) },
+            vec![0x51; 139],
+        )).unwrap();
+
+        sweeper.transaction_confirmed(&txid, 4000);
+        let first = sweeper.process_block(4001).unwrap();
+        let second = sweeper.process_block(4004).unwrap();
+        let third = sweeper.process_block(4007).unwrap();
+        assert_eq!(third[0].replaced_txids, vec![first[0].txid.clone(), second[0].txid.clone()]);
+
+        // A miner picked up the first version after all
+        sweeper.transaction_confirmed(&first[0].txid, 4008);
+        assert!(sweeper.list_pending_sweeps().is_empty());
+        assert_eq!(
+            sweeper.get_output(&format!("{}:0", txid)).unwrap().status,
+            SweepOutputStatus::Swept { sweep_txid: first[0].txid.clone(), height: 4008 }
+        );
+    }
+
+    #[test]
+    fn test_state_survives_restart() {
+        let dir = tempfile::tempdir().unwrap();
+        let path = dir.path().join("sweeper.json");
+        let (bitcoin, _) = setup();
+        let signer = Arc::new(KeySigner(SecretKey::from_slice(&[0x11; 32]).unwrap()));
+        let txid = commitment_txid(6);
+
+        {
+            let sweeper = OutputSweeper::open(SweeperConfig::default(), bitcoin.clone(), signer.clone(), &path).unwrap();
+            sweeper.track_output(SweepableOutput::new(
+                "chan6", &txid, 0, 500_000,
+                SweepOutputKind::DelayedToSelf { to_self_delay: 144 },
+                vec![0x51; 79],
+            )).unwrap();
+            sweeper.track_output(SweepableOutput::new(
+                "chan6", &txid, 1, 300_000,
+                SweepOutputKind::HtlcSuccess { preimage: "55".repeat(32) },
+                vec![0x51; 139],
+            )).unwrap();
+            sweeper.transaction_confirmed(&txid, 6000);
+            assert_eq!(sweeper.process_block(6001).unwrap().len(), 1);
+        }
+
+        // Restarted halfway through the CSV wait
+        let sweeper = OutputSweeper::open(SweeperConfig::default(), bitcoin.clone(), signer, &path).unwrap();
+        let pending = sweeper.list_pending_sweeps();
+        assert_eq!(pending.len(), 1);
+        sweeper.transaction_confirmed(&pending[0].txid, 6002);
+        assert!(matches!(sweeper.get_output(&format!("{}:1", txid)).unwrap().status, SweepOutputStatus::Swept { .. }));
+        assert_eq!(
+            sweeper.get_output(&format!("{}:0", txid)).unwrap().status,
+            SweepOutputStatus::TimelockPending { spendable_at: 6144 }
+        );
+        let swept = sweeper.process_block(6143).unwrap();
+        assert_eq!(swept.len(), 1);
+        assert_eq!(swept[0].outpoints, vec![format!("{}:0", txid)]);
+    }
+
+    #[test]
+    fn test_holder_htlc_timeout_goes_through_second_stage() {
+        let (bitcoin, sweeper) = setup();
+        let txid = commitment_txid(7);
+
+        let htlc_timeout = Transaction {
+            version: Version::TWO,
+            lock_time: LockTime::from_height(7100).unwrap(),
+            input: vec![TxIn {
+                previous_output: OutPoint { txid: Txid::from_str(&txid).unwrap(), vout: 2 },
+                script_sig: ScriptBuf::new(),
+                sequence: Sequence::ZERO,
+                witness: Witness::from_slice(&[vec![], vec![0x30; 72], vec![0x30; 72], vec![], vec![0x51; 133]]),
+            }],
+            output: vec![TxOut { value: Amount::from_sat(199_000), script_pubkey: ScriptBuf::new_p2wsh(&ScriptBuf::from_bytes(vec![0x52; 79]).wscript_hash()) }],
+        };
+        sweeper.track_output(SweepableOutput::new(
+            "chan7", &txid, 2, 200_000,
+            SweepOutputKind::HolderHtlcTimeout {
+                cltv_expiry: 7100,
+                to_self_delay: 144,
+                htlc_timeout_tx: encode::serialize_hex(&htlc_timeout),
+                delayed_witness_script: vec![0x52; 79],
+            },
+            vec![0x51; 133],
+        )).unwrap();
+
+        sweeper.transaction_confirmed(&txid, 7000);
+        assert!(sweeper.process_block(7050).unwrap().is_empty());
+        assert!(bitcoin.broadcasts.lock().unwrap().is_empty());
+
+        // The pre-signed transaction goes out as is, never in a sweep
+        assert!(sweeper.process_block(7100).unwrap().is_empty());
+        let second_stage = htlc_timeout.compute_txid().to_string();
+        assert_eq!(bitcoin.broadcasts.lock().unwrap()[0].txid, second_stage);
+        assert_eq!(
+            sweeper.get_output(&format!("{}:2", txid)).unwrap().status,
+            SweepOutputStatus::SecondStage { txid: second_stage.clone() }
+        );
+
+        sweeper.transaction_confirmed(&second_stage, 7101);
+        assert!(matches!(sweeper.get_output(&format!("{}:2", txid)).unwrap().status, SweepOutputStatus::Swept { .. }));
+        assert!(sweeper.process_block(7200).unwrap().is_empty());
+        let swept = sweeper.process_block(7244).unwrap();
+        assert_eq!(swept.len(), 1);
+        assert_eq!(swept[0].outpoints, vec![format!("{}:0", second_stage)]);
+        assert_eq!(swept[0].transaction.inputs[0].sequence, 144);
+    }
+
+    #[test]
+    fn test_counterparty_spend_drops_output() {
+        let (_, sweeper) = setup();
+        let txid = commitment_txid(8);
+
+        sweeper.track_output(SweepableOutput::new(
+            "chan8", &txid, 0, 200_000,
+            SweepOutputKind::HtlcTimeout { cltv_expiry: 8000 },
+            vec![0x51; 133],
+        )).unwrap();
+        sweeper.track_output(SweepableOutput::new(
+            "chan8", &txid, 1, 300_000,
+            SweepOutputKind::HtlcSuccess { preimage: "66".repeat(32) },
+            vec![0x51; 139],
+        )).unwrap();
+        sweeper.transaction_confirmed(&txid, 7990);
+        let first = sweeper.process_block(8000).unwrap();
+        assert_eq!(first[0].outpoints.len(), 2);
+
+        // The counterparty took the offered HTLC with its preimage first
+        let claim = BitcoinTransaction {
+            txid: commitment_txid(9),
+            version: 2,
+            inputs: vec![TransactionInput { txid: txid.clone(), vout: 0, script_sig: Vec::new(), sequence: 0, witness: None }],
+            outputs: Vec::new(),
+            locktime: 0,
+            size: 0,
+            weight: 0,
+            fee: None,
+        };
+        sweeper.spending_transaction_confirmed(&claim, 8001);
+        assert_eq!(
+            sweeper.get_output(&format!("{}:0", txid)).unwrap().status,
+            SweepOutputStatus::SpentByCounterparty { txid: commitment_txid(9), height: 8001 }
+        );
+        assert!(sweeper.list_pending_sweeps().is_empty());
+
+        let resweep = sweeper.process_block(8002).unwrap();
+        assert_eq!(resweep.len(), 1);
+        assert_eq!(resweep[0].outpoints, vec![format!("{}:1", txid)]);
+    }
+}
@@ -0,0 +1,332 @@
+// Unified BIP21 Payment URIs
+// Generates and parses `bitcoin:` URIs combining an on-chain address with
+// BOLT11 invoices (`lightning=`), BOLT12 offers (`lno=`) and payjoin endpoints (`pj=`)
+
+use std::collections::BTreeMap;
+use std::fmt;
+use std::sync::Arc;
+
+use crate::lightning::interface::{LightningError, LightningResult};
+use crate::lightning::invoice_manager::InvoiceManager;
+
+/// URI scheme
+const BITCOIN_SCHEME: &str = "bitcoin:";
+
+/// Satoshis per bitcoin
+const SATS_PER_BTC: u64 = 100_000_000;
+
+/// Unified payment URI
+#[derive(Debug, Clone, PartialEq, Eq, Default)]
+pub struct UnifiedUri {
+    /// On-chain address (may be empty for Lightning-only URIs)
+    pub address: String,
+
+    /// Requested amount in satoshis
+    pub amount_sat: Option<u64>,
+
+    /// Label for the recipient
+    pub label: Option<String>,
+
+    /// Message describing the payment
+    pub message: Option<String>,
+
+    /// BOLT11 invoice
+    pub lightning: Option<String>,
+
+    /// BOLT12 offer
+    pub offer: Option<String>,
+
+    /// Payjoin endpoint
+    pub payjoin_endpoint: Option<String>,
+
+    /// Whether the receiver forbids the sender's payjoin proposal from
+    /// substituting its outputs (`pjos=0`)
+    pub payjoin_output_substitution_disabled: bool,
+
+    /// Other parameters, preserved for round-tripping
+    pub extras: BTreeMap<String, String>,
+}
+
+impl UnifiedUri {
+    /// Create an on-chain URI for `address`
+    pub fn new(address: &str) -> Self {
+        UnifiedUri {
+            address: address.to_string(),
+            ..Default::default()
+        }
+    }
+
+    /// Create a unified URI for `address` with a fresh invoice from the invoice manager
+    pub fn with_invoice(
+        invoice_manager: &Arc<InvoiceManager>,
+        address: &str,
+        amount_sat: Option<u64>,
+        description: &str,
+        expiry: Option<u32>,
+    ) -> LightningResult<Self> {
+        let invoice = invoice_manager.create_invoice(
+            amount_sat.map(|amount| amount * 1000),
+            description,
+            expiry,
+        )?;
+
+        let mut uri = UnifiedUri::new(address);
+        uri.amount_sat = amount_sat;
+        uri.lightning = Some(invoice.bolt11);
+        if !description.is_empty() {
+            uri.message = Some(description.to_string());
+        }
+
+        Ok(uri)
+    }
+
+    /// Whether the URI offers any way to pay on-chain
+    pub fn has_onchain(&self) -> bool {
+        !self.address.is_empty()
+    }
+
+    /// Whether the URI offers any way to pay over Lightning
+    pub fn has_lightning(&self) -> bool {
+        self.lightning.is_some() || self.offer.is_some()
+    }
+
+    /// Parse a BIP21 URI
+    pub fn parse(uri: &str) -> LightningResult<Self> {
+        let uri = uri.trim();
+
+        if uri.len() < BITCOIN_SCHEME.len() || !uri[..BITCOIN_SCHEME.len()].eq_ignore_ascii_case(BITCOIN_SCHEME) {
+            return Err(LightningError::InvoiceError("Not a bitcoin: URI".to_string()));
+        }
+
+        let rest = &uri[BITCOIN_SCHEME.len()..];
+        let (address, query) = match rest.find('?') {
+            Some(pos) => (&rest[..pos], &rest[pos + 1..]),
+            None => (rest, ""),
+        };
+
+        let mut parsed = UnifiedUri::new(&percent_decode(address)?);
+        let mut seen_pjos = false;
+
+        for pair in query.split('&').filter(|p| !p.is_empty()) {
+            let (key, value) = match pair.find('=') {
+                Some(pos) => (&pair[..pos], &pair[pos + 1..]),
+                None => (pair, ""),
+            };
+            let key = key.to_ascii_lowercase();
+            let value = percent_decode(value)?;
+
+            // Repeated parameters are ambiguous; reject rather than guess
+            let duplicate = match key.as_str() {
+                "amount" => parsed.amount_sat.replace(parse_btc_amount(&value)?).is_some(),
+                "label" => parsed.label.replace(value).is_some(),
+                "message" => parsed.message.replace(value).is_some(),
+                "lightning" => parsed.lightning.replace(value).is_some(),
+                "lno" => parsed.offer.replace(value).is_some(),
+                "pj" => parsed.payjoin_endpoint.replace(value).is_some(),
+                "pjos" => {
+                    parsed.payjoin_output_substitution_disabled = value == "0";
+                    std::mem::replace(&mut seen_pjos, true)
+                },
+                _ if key.starts_with("req-") => {
+                    // BIP21: unknown required parameters make the URI unpayable
+                    return Err(LightningError::InvoiceError(
+                        format!("Unsupported required parameter: {}", key)
+                    ));
+                },
+                _ => parsed.extras.insert(key.clone(), value).is_some(),
+            };
+
+            if duplicate {
+                return Err(LightningError::InvoiceError(format!("Duplicate parameter: {}", key)));
+            }
+        }
+
+        if !parsed.has_onchain() && !parsed.has_lightning() {
+            return Err(LightningError::InvoiceError(
+                "URI has neither an address nor a Lightning payment request".to_string()
+            ));
+        }
+
+        Ok(parsed)
+    }
+
+    /// URI suitable for a QR code: scheme, address and Lightning requests uppercased
+    /// so encoders can use alphanumeric mode
+    pub fn to_qr_string(&self) -> String {
+        let mut uri = UnifiedUri {
+            address: self.address.to_uppercase(),
+            lightning: self.lightning.as_ref().map(|l| l.to_uppercase()),
+            offer: self.offer.as_ref().map(|o| o.to_uppercase()),
+            ..self.clone()
+        };
+
+        // Only bech32 addresses are case-insensitive
+        if !is_bech32(&self.address) {
+            uri.address = self.address.clone();
+        }
+
+        uri.to_string().replacen(BITCOIN_SCHEME, "BITCOIN:", 1)
+    }
+}
+
+impl fmt::Display for UnifiedUri {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        let mut params = Vec::new();
+
+        if let Some(amount) = self.amount_sat {
+            params.push(format!("amount={}", format_btc_amount(amount)));
+        }
+        if let Some(label) = &self.label {
+            params.push(format!("label={}", percent_encode(label)));
+        }
+        if let Some(message) = &self.message {
+            params.push(format!("message={}", percent_encode(message)));
+        }
+        if let Some(lightning) = &self.lightning {
+            params.push(format!("lightning={}", percent_encode(lightning)));
+        }
+        if let Some(offer) = &self.offer {
+            params.push(format!("lno={}", percent_encode(offer)));
+        }
+        if let Some(endpoint) = &self.payjoin_endpoint {
+            params.push(format!("pj={}", percent_encode(endpoint)));
+            if self.payjoin_output_substitution_disabled {
+                params.push("pjos=0".to_string());
+            }
+        }
+        for (key, value) in &self.extras {
+            params.push(format!("{}={}", key, percent_encode(value)));
+        }
+
+        write!(f, "{}{}", BITCOIN_SCHEME, self.address)?;
+        if !params.is_empty() {
+            write!(f, "?{}", params.join("&"))?;
+        }
+        Ok(())
+    }
+}
+
+/// Parse a decimal BTC amount into satoshis
+pub fn parse_btc_amount(value: &str) -> LightningResult<u64> {
+    let invalid = || LightningError::InvoiceError(format!("Invalid amount: {}", value));
+
+    let (whole, fraction) = match value.find('.') {
+        Some(pos) => (&value[..pos], &value[pos + 1..]),
+        None => (value, ""),
+    };
+
+    if (whole.is_empty() && fraction.is_empty())
+        || fraction.len() > 8
+        || !whole.chars().all(|c| c.is_ascii_digit())
+        || !fraction.chars().all(|c| c.is_ascii_digit())
+    {
+        return Err(invalid());
+    }
+
+    let whole: u64 = if whole.is_empty() { 0 } else { whole.parse().map_err(|_| invalid())? };
+    let fraction: u64 = format!("{:0<8}", fraction).parse().map_err(|_| invalid())?;
+
+    whole.checked_mul(SATS_PER_BTC)
+        .and_then(|sats| sats.checked_add(fraction))
+        .ok_or_else(invalid)
+}
+
+/// Format satoshis as a decimal BTC amount without trailing zeros
+pub fn format_btc_amount(amount_sat: u64) -> String {
+    let whole = amount_sat / SATS_PER_BTC;
+    let fraction = amount_sat % SATS_PER_BTC;
+
+    if fraction == 0 {
+        return whole.to_string();
+    }
+
+    let fraction = format!("{:08}", fraction);
+    format!("{}.{}", whole, fraction.trim_end_matches('0'))
+}
+
+/// Whether an address uses bech32/bech32m encoding
+fn is_bech32(address: &str) -> bool {
+    let lower = address.to_lowercase();
+    ["bc1", "tb1", "bcrt1"].iter().any(|hrp| lower.starts_with(hrp))
+}
+
+/// Percent-encode a parameter value
+fn percent_encode(value: &str) -> String {
+    let mut encoded = String::with_capacity(value.len());
+    for byte in value.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' | b':' => {
+                encoded.push(byte as char)
+            },
+            _ => encoded.push_str(&format!("%{:02X}", byte)),
+        }
+    }
+    encoded
+}
+
+/// Decode a percent-encoded parameter value
+fn percent_decode(value: &str) -> LightningResult<String> {
+    let bytes = value.as_bytes();
+    let mut decoded = Vec::with_capacity(bytes.len());
+    let mut i = 0;
+
+    while i < bytes.len() {
+        if bytes[i] == b'%' {
+            let hex = value.get(i + 1..i + 3)
+                .ok_or_else(|| LightningError::InvoiceError("Truncated percent-encoding".to_string()))?;
+            let byte = u8::from_str_radix(hex, 16)
+                .map_err(|_| LightningError::InvoiceError(format!("Invalid percent-encoding: %{}", hex)))?;
+            decoded.push(byte);
+            i += 3;
+        } else {
+            decoded.push(bytes[i]);
+            i += 1;
+        }
+    }
+
+    String::from_utf8(decoded)
+        .map_err(|_| LightningError::InvoiceError("URI parameter is not valid UTF-8".to_string()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_parse_unified_uri() {
+        let uri = UnifiedUri::parse(
+            "BITCOIN:BC1QYLH3U67J673H6Y6ALV70M0PL2YZ53TZHVXGG7U?amount=0.00001&label=sbddesign%3A%20For%20lunch%20Tuesday&message=For%20lunch%20Tuesday&lightning=LNBC10U1P3PJ257PP5YZTKWJCZ5FTL5LAXKAV23ZMZEKAW37ZK6KMV80PK4XAEV5QHTZ7QDPDWD3XGER9WD5KWM36YPRX7U3QD36KUCMGYP282ETNV3SHJCQZPGXQYZ5VQSP5USYC4LK9CHSFP53KVCNVQ456GANH60D89REYKDNGSMTJ6YW3NHVQ9QYYSSQJCEWM5CJWZ4A6RFJX77C490YCED6PEMK0UPKXHY89CMM7SCT66K8GNEANWYKZGDRWRFJE69H9U5U0W57RRCSYSAS7GADWMZXC8C6T0SPJAZUP6&pj=https://example.com/pj&pjos=0"
+        ).unwrap();
+
+        assert_eq!(uri.address, "BC1QYLH3U67J673H6Y6ALV70M0PL2YZ53TZHVXGG7U");
+        assert_eq!(uri.amount_sat, Some(1_000));
+        assert_eq!(uri.label.as_deref(), Some("sbddesign: For lunch Tuesday"));
+        assert!(uri.lightning.as_ref().unwrap().starts_with("LNBC10U1"));
+        assert_eq!(uri.payjoin_endpoint.as_deref(), Some("https://example.com/pj"));
+        assert!(uri.payjoin_output_substitution_disabled);
+    }
+
+    #[test]
+    fn test_uri_roundtrip_and_rejections() {
+        let mut uri = UnifiedUri::new("bc1qw508d6qejxtdg4y5r3zarvary0c5xw7kv8f3t4");
+        uri.amount_sat = Some(150_000_000);
+        uri.message = Some("Coffee & cake".to_string());
+        uri.offer = Some("lno1qcp4256ypq".to_string());
+
+        let encoded = uri.to_string();
+        assert!(encoded.contains("amount=1.5&"));
+        assert!(encoded.contains("message=Coffee%20%26%20cake"));
+        assert_eq!(UnifiedUri::parse(&encoded).unwrap(), uri);
+        assert_eq!(UnifiedUri::parse(&uri.to_qr_string()).unwrap().offer.as_deref(), Some("LNO1QCP4256YPQ"));
+
+        // Lightning-only URIs are allowed
+        assert!(UnifiedUri::parse("bitcoin:?lightning=lnbc1").unwrap().lightning.is_some());
+
+        assert!(UnifiedUri::parse("bitcoin:bc1qaddr?req-somethingnew=1").is_err());
+        assert!(UnifiedUri::parse("bitcoin:bc1qaddr?amount=0.000000001").is_err());
+        assert!(UnifiedUri::parse("bitcoin:bc1qaddr?amount=1&amount=2").is_err());
+        assert!(UnifiedUri::parse("bitcoin:bc1qaddr?pj=https://example.com/pj&pjos=0&pjos=1").is_err());
+        assert!(UnifiedUri::parse("bitcoin:").is_err());
+        assert!(UnifiedUri::parse("lightning:lnbc1").is_err());
+    }
+}